@@ -8,9 +8,13 @@ use lux_lib::{
     rockspec::lua_dependency::{self},
 };
 
-use crate::utils::project::{
-    sync_build_dependencies_if_locked, sync_dependencies_if_locked,
-    sync_test_dependencies_if_locked, PackageReqOrGitShorthand,
+use crate::utils::{
+    picker::pick_dependencies,
+    project::{
+        sync_build_dependencies_if_locked, sync_dependencies_if_locked,
+        sync_test_dependencies_if_locked, PackageReqOrGitShorthand,
+    },
+    prompt::is_interactive,
 };
 
 #[derive(clap::Args)]
@@ -23,7 +27,10 @@ pub struct Add {
     /// Example: "github:owner/repo" {n}
     /// Supported git host prefixes are: "github:", "gitlab:", "sourcehut:" and "codeberg:". {n}
     /// Lux will automatically fetch the latest SemVer tag or commit SHA if no SemVer tag is found. {n}
-    /// Note that projects with git dependencies cannot be published to luarocks.org.
+    /// Note that projects with git dependencies cannot be published to luarocks.org. {n}
+    /// {n}
+    /// If no packages are specified in an interactive terminal, lux lets you
+    /// search the rock servers' manifests for dependencies to add.
     package_req: Vec<PackageReqOrGitShorthand>,
 
     /// Reinstall without prompt if a package is already installed.
@@ -47,13 +54,26 @@ pub async fn add(data: Add, config: Config) -> Result<()> {
 
     let progress = MultiProgress::new_arc();
 
+    let package_req = if data.package_req.is_empty()
+        && data.build.is_none()
+        && data.test.is_none()
+        && is_interactive()
+    {
+        pick_dependencies(&db)?
+            .into_iter()
+            .map(PackageReqOrGitShorthand::PackageReq)
+            .collect_vec()
+    } else {
+        data.package_req
+    };
+
     let (dependencies, git_dependencies): (Vec<_>, Vec<_>) =
-        data.package_req.iter().partition_map(|req| match req {
+        package_req.iter().partition_map(|req| match req {
             PackageReqOrGitShorthand::PackageReq(req) => Either::Left(req.clone()),
             PackageReqOrGitShorthand::GitShorthand(url) => Either::Right(url.clone()),
         });
 
-    if !data.package_req.is_empty() {
+    if !package_req.is_empty() {
         project
             .add(lua_dependency::DependencyType::Regular(dependencies), &db)
             .await?;
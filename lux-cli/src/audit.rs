@@ -0,0 +1,92 @@
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::{config::Config, operations, progress::MultiProgress};
+use url::Url;
+
+use serde_json::json;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    project::locked_packages,
+};
+
+#[derive(Args)]
+pub struct Audit {
+    /// The URL of the advisory database (takes priority over the `advisory_db` config option).{n}
+    /// `file://` URLs are supported.
+    #[arg(long, value_name = "url")]
+    db: Option<Url>,
+
+    /// Advisory IDs to ignore.
+    #[arg(long, value_name = "id")]
+    ignore: Vec<String>,
+}
+
+/// Check the locked packages of the current project (or the user tree)
+/// against a security advisory database.
+pub async fn audit(args: Audit, config: Config) -> Result<()> {
+    let packages = locked_packages(&config)?;
+    let package_count = packages.len();
+    let vulnerabilities = operations::Audit::new(&config)
+        .packages(packages)
+        .maybe_advisory_db(args.db)
+        .ignore(args.ignore.into_iter().collect())
+        .progress(MultiProgress::new_arc())
+        .audit()
+        .await?;
+
+    if vulnerabilities.is_empty() {
+        output::message(format!(
+            "No vulnerabilities found in {package_count} packages."
+        ));
+        return Ok(());
+    }
+
+    if is_json_output() {
+        for vulnerability in &vulnerabilities {
+            let package = vulnerability.package();
+            let advisory = vulnerability.advisory();
+            output::emit(
+                "vulnerability",
+                json!({
+                    "name": package.name().to_string(),
+                    "version": package.version().to_string(),
+                    "id": advisory.id(),
+                    "severity": advisory.severity().to_string(),
+                    "title": advisory.title(),
+                    "vulnerable": advisory.vulnerable().to_string(),
+                    "patched": advisory.patched().map(|patched| patched.to_string()),
+                    "url": advisory.url().map(|url| url.to_string()),
+                }),
+            );
+        }
+    } else {
+        for vulnerability in &vulnerabilities {
+            let package = vulnerability.package();
+            let advisory = vulnerability.advisory();
+            output::message(format!(
+                "{}@{}: {} [{}] {}",
+                package.name(),
+                package.version(),
+                advisory.id(),
+                advisory.severity(),
+                advisory.title()
+            ));
+            output::message(format!("  vulnerable: {}", advisory.vulnerable()));
+            match advisory.patched() {
+                Some(patched) => {
+                    output::message(format!("  fix: upgrade to {} {patched}", package.name()))
+                }
+                None => output::message("  fix: no patched version available"),
+            }
+            if let Some(url) = advisory.url() {
+                output::message(format!("  more info: {url}"));
+            }
+        }
+    }
+
+    Err(eyre!(
+        "found {} vulnerabilities in {package_count} packages.",
+        vulnerabilities.len()
+    ))
+}
@@ -1,83 +1,221 @@
 use std::time::Duration;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use eyre::Result;
 use lux_cli::{
-    add, build, check, completion, config,
-    debug::Debug,
-    doc, download, exec, fetch, format, generate_rockspec, info, install, install_lua,
-    install_rockspec, list, outdated, pack, path, pin, project, purge, remove, run, run_lua,
-    search, shell, test, uninstall, unpack, update,
+    add, audit, build, bundle, cache, check, completion, config,
+    debug::{self, Debug},
+    doc, doctor, download, exec, fetch, format, gc, generate_rockspec, help, import, index, info,
+    install, install_lua, install_rockspec, license, lint_rockspec, list, login, migrate_tree,
+    mirror, outdated, pack, path, pin, project, purge, release, remove, rollback, run, run_lua,
+    sbom, search, shell, test, tool, toolchain, tree, uninstall, unpack, update,
     upload::{self},
-    which, Cli, Commands,
+    utils::{
+        logging::init_logging,
+        output,
+        prompt::{ASSUME_YES_ENV, NON_INTERACTIVE_ENV},
+    },
+    vendor, venv, verify, version, which, why, Cli, Commands,
 };
 use lux_lib::{
-    config::{tree::RockLayoutConfig, ConfigBuilder},
+    config::{ConfigBuilder, LuaVersion},
     lockfile::PinnedState::{Pinned, Unpinned},
+    message::MessageFormat,
+    operations::Venv,
+    progress::{self, Verbosity},
+    project::Project,
 };
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
+    CompleteEnv::with_factory(Cli::command)
+        .var(completion::COMPLETE_ENV)
+        .complete();
+
     let cli = Cli::parse();
 
-    let mut config_builder = ConfigBuilder::new()
-        .unwrap()
-        .dev(Some(cli.dev))
-        .lua_dir(cli.lua_dir)
-        .lua_version(cli.lua_version)
+    if !cli.json {
+        return run(cli).await;
+    }
+    output::enable_json_output();
+    if let Err(err) = run(cli).await {
+        output::emit_error(&err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    init_logging(cli.verbose, cli.log_file.as_deref())?;
+
+    // Editing the config files must not require loading them,
+    // so that a broken config file can be repaired.
+    let command = match cli.command {
+        Commands::Config(config_cmd) if !config_cmd.needs_config() => {
+            return config::config(config_cmd, None);
+        }
+        command => command,
+    };
+
+    let project = Project::current().ok().flatten();
+    // A Lua version pinned by the project takes precedence over the config file.
+    // An invalid `.lua-version` must not break every command, e.g. `lx toolchain pin`.
+    let pinned_lua_version = match project.as_ref().map(Project::pinned_lua_version) {
+        Some(Ok(lua_version)) => lua_version,
+        Some(Err(err)) => {
+            eprintln!("⚠️ WARNING: ignoring the pinned Lua version: {err}");
+            None
+        }
+        None => None,
+    };
+
+    // An activated environment's Lua installation and tree take precedence over the config file.
+    // A stale LUX_VENV, e.g. from a deleted environment, must not break every command.
+    let active_venv = Venv::from_env().unwrap_or_else(|err| {
+        eprintln!("⚠️ WARNING: ignoring the activated environment: {err}");
+        None
+    });
+
+    if let Some(lua_version) = cli.lua_version.as_ref().filter(|_| cli.nvim) {
+        if !matches!(lua_version, LuaVersion::Lua51 | LuaVersion::LuaJIT) {
+            eprintln!(
+                "⚠️ WARNING: Neovim requires Lua 5.1 compatible rocks. Using luajit instead of {lua_version}."
+            );
+        }
+    }
+
+    // `lx doctor` reports an invalid config file, so it must not fail to load it.
+    let config_builder = match ConfigBuilder::new() {
+        Err(_) if matches!(command, Commands::Doctor) => ConfigBuilder::default(),
+        result => result?,
+    };
+
+    let mut config_builder = config_builder
+        .dev(cli.dev.then_some(true))
+        .lua_dir(
+            cli.lua_dir
+                .or(active_venv.as_ref().map(|venv| venv.lua_dir())),
+        )
+        .lua_version(
+            cli.lua_version
+                .or(active_venv.as_ref().map(|venv| venv.lua_version().clone()))
+                .or(pinned_lua_version),
+        )
         .namespace(cli.namespace)
         .extra_servers(cli.extra_servers)
         .only_sources(cli.only_sources)
         .server(cli.server)
-        .user_tree(cli.tree)
+        .user_tree(
+            cli.tree
+                .or(active_venv.as_ref().map(|venv| venv.root().to_path_buf())),
+        )
         .timeout(
             cli.timeout
                 .map(|duration| Duration::from_secs(duration as u64)),
         )
-        .no_project(Some(cli.no_project))
+        .retries(cli.retries)
+        .max_jobs(cli.jobs)
+        .no_project(cli.no_project.then_some(true))
+        .offline(cli.offline.then_some(true))
+        .refresh_manifests(cli.refresh_manifests.then_some(true))
+        .minimal_versions(cli.minimal_versions.then_some(true))
+        .sandbox_builds(cli.sandbox_builds.then_some(true))
+        .vendor_dir(
+            project
+                .as_ref()
+                .map(|project| project.root().join("vendor"))
+                .filter(|vendor_dir| vendor_dir.is_dir()),
+        )
         .variables(
             cli.variables
+                .clone()
                 .map(|variables| variables.into_iter().collect()),
         )
-        .verbose(Some(cli.verbose));
+        .message_format(cli.message_format)
+        .verbose((cli.verbose > 0).then_some(true));
 
     if cli.nvim {
-        config_builder = config_builder.entrypoint_layout(RockLayoutConfig::new_nvim_layout());
+        config_builder = config_builder.nvim_profile();
     }
 
-    let config = config_builder.build()?;
+    let mut config = config_builder.build()?;
+
+    // The project's overrides (e.g. the C compiler and flags) only apply to the project,
+    // and variables set with CLI flags take precedence over them.
+    if let Some(project) = project.as_ref().filter(|_| !config.no_project()) {
+        config = project
+            .apply_config_overrides(config)
+            .with_variables(cli.variables.unwrap_or_default().into_iter().collect());
+    }
 
     if config.verbose() {
         std::env::set_var("CC_ENABLE_DEBUG_OUTPUT", "1");
+        progress::set_verbosity(Verbosity::Verbose);
+    } else if cli.quiet {
+        progress::set_verbosity(Verbosity::Quiet);
     }
 
-    match cli.command {
+    if output::is_json_output() || config.message_format() == MessageFormat::Json {
+        progress::disable_bars();
+    }
+
+    if cli.no_input {
+        std::env::set_var(NON_INTERACTIVE_ENV, "1");
+    }
+    if cli.yes {
+        std::env::set_var(ASSUME_YES_ENV, "1");
+    }
+
+    match command {
         Commands::Completion(completion_args) => completion::completion(completion_args).await?,
+        Commands::Sbom(sbom_args) => sbom::sbom(sbom_args, config).await?,
         Commands::Search(search_data) => search::search(search_data, config).await?,
         Commands::Download(download_data) => download::download(download_data, config).await?,
         Commands::Debug(debug) => match debug {
             Debug::FetchRemote(unpack_data) => fetch::fetch_remote(unpack_data, config).await?,
             Debug::Unpack(unpack_data) => unpack::unpack(unpack_data).await?,
             Debug::UnpackRemote(unpack_data) => unpack::unpack_remote(unpack_data, config).await?,
-            Debug::Project(debug_project) => project::debug_project(debug_project)?,
+            Debug::Project(debug_project) => project::debug_project(debug_project, config)?,
+            Debug::Tree => debug::debug_tree(config)?,
+            Debug::Lockfile(debug_lockfile) => debug::debug_lockfile(debug_lockfile, config)?,
+            Debug::Config => debug::debug_config(config)?,
         },
-        Commands::New(project_data) => project::write_project_rockspec(project_data).await?,
-        Commands::Build(build_data) => {
-            build::build(build_data, config).await?;
+        Commands::New(project_data) => {
+            project::write_project_rockspec(project_data, config).await?
         }
+        Commands::Build(build_data) => build::build_command(build_data, config).await?,
+        Commands::Bundle(bundle_args) => bundle::bundle(bundle_args, config).await?,
         Commands::List(list_data) => list::list_installed(list_data, config)?,
+        Commands::Login(login_args) => login::login(login_args, config)?,
+        Commands::Logout(logout_args) => login::logout(logout_args, config)?,
         Commands::Lua(run_lua) => run_lua::run_lua(run_lua, config).await?,
+        Commands::Import(import_data) => import::import(import_data)?,
+        Commands::Index(index_args) => index::index(index_args)?,
+        Commands::Init(init_args) => project::init_project(init_args, config).await?,
         Commands::Install(install_data) => install::install(install_data, config).await?,
         Commands::InstallRockspec(install_data) => {
             install_rockspec::install_rockspec(install_data, config).await?
         }
         Commands::Outdated(outdated) => outdated::outdated(outdated, config).await?,
-        Commands::InstallLua => install_lua::install_lua(config).await?,
+        Commands::License(license_args) => license::license(license_args, config).await?,
+        Commands::MigrateTree(migrate_tree_args) => {
+            migrate_tree::migrate_tree(migrate_tree_args, config).await?
+        }
+        Commands::Mirror(mirror_args) => mirror::mirror(mirror_args, config).await?,
+        Commands::InstallLua(install_lua_args) => {
+            install_lua::install_lua_cmd(install_lua_args, config).await?
+        }
         Commands::Fmt(fmt_args) => format::format(fmt_args)?,
         Commands::Purge => purge::purge(config).await?,
+        Commands::Release(release_args) => release::release(release_args, config).await?,
         Commands::Remove(remove_args) => remove::remove(remove_args, config).await?,
+        Commands::Rollback(rollback_args) => rollback::rollback(rollback_args, config).await?,
         Commands::Exec(run_args) => exec::exec(run_args, config).await?,
         Commands::Test(test) => test::test(test, config).await?,
+        Commands::Tool(tool_cmd) => tool::tool(tool_cmd, config).await?,
+        Commands::Toolchain(toolchain_cmd) => toolchain::toolchain(toolchain_cmd, config).await?,
+        Commands::Tree(tree_args) => tree::tree(tree_args, config)?,
         Commands::Update(update_args) => update::update(update_args, config).await?,
         Commands::Info(info_data) => info::info(info_data, config).await?,
         Commands::Path(path_data) => path::path(path_data, config).await?,
@@ -86,16 +224,27 @@ async fn main() -> Result<()> {
         Commands::Upload(upload_data) => upload::upload(upload_data, config).await?,
         Commands::Check(check_args) => check::check(check_args, config).await?,
         Commands::Add(add_data) => add::add(add_data, config).await?,
-        Commands::Config(config_cmd) => config::config(config_cmd, config)?,
+        Commands::Audit(audit_args) => audit::audit(audit_args, config).await?,
+        Commands::Cache(cache_cmd) => cache::cache(cache_cmd, config).await?,
+        Commands::Config(config_cmd) => config::config(config_cmd, Some(config))?,
         Commands::Doc(doc_args) => doc::doc(doc_args, config).await?,
-        Commands::Lint => unimplemented!(),
+        Commands::Doctor => doctor::doctor(config).await?,
+        Commands::LintRockspec(lint_args) => lint_rockspec::lint_rockspec(lint_args)?,
         Commands::Pack(pack_args) => pack::pack(pack_args, config).await?,
+        Commands::Gc(gc_args) => gc::gc(gc_args, config).await?,
         Commands::Uninstall(uninstall_data) => {
             uninstall::uninstall(uninstall_data, config).await.unwrap()
         }
+        Commands::Vendor(vendor_args) => vendor::vendor(vendor_args, config).await?,
+        Commands::Venv(venv_args) => venv::venv(venv_args, config).await?,
+        Commands::Verify(verify_args) => verify::verify(verify_args, config).await?,
+        Commands::Version(version_args) => version::version(version_args, config).await?,
         Commands::Which(which_args) => which::which(which_args, config)?,
+        Commands::Why(why_args) => why::why(why_args, config)?,
         Commands::Run(run_args) => run::run(run_args, config).await?,
         Commands::GenerateRockspec(data) => generate_rockspec::generate_rockspec(data)?,
+        Commands::Help(help_args) => help::help(help_args)?,
+        Commands::HelpMan(help_man_args) => help::help_man(help_man_args)?,
         Commands::Shell(data) => shell::shell(data, config).await?,
     }
     Ok(())
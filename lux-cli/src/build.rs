@@ -4,10 +4,12 @@ use lux_lib::{
     config::Config,
     lockfile::LocalPackage,
     operations::{self},
-    project::Project,
+    project::{workspace::Workspace, Project},
 };
 
-#[derive(Args, Default)]
+use crate::utils::watch::watch;
+
+#[derive(Args, Default, Clone)]
 pub struct Build {
     /// Ignore the project's lockfile and don't create one.
     #[arg(long)]
@@ -16,10 +18,55 @@ pub struct Build {
     /// Build only the dependencies
     #[arg(long)]
     only_deps: bool,
+
+    /// Build all members of the current workspace.
+    #[arg(long)]
+    workspace: bool,
+}
+
+/// The arguments of `lx build`.
+#[derive(Args)]
+pub struct BuildCommand {
+    #[clap(flatten)]
+    build: Build,
+
+    /// Rebuild the project whenever its files change.
+    #[arg(long)]
+    watch: bool,
+}
+
+pub async fn build_command(data: BuildCommand, config: Config) -> Result<()> {
+    if !data.watch {
+        build(data.build, config).await?;
+        return Ok(());
+    }
+    let root = if data.build.workspace {
+        Workspace::current_or_err()?.root().to_path_buf()
+    } else {
+        Project::current_or_err()?.root().to_path_buf()
+    };
+    watch(&root, "Build", || {
+        let data = data.build.clone();
+        let config = config.clone();
+        async move { build(data, config).await.map(|_| ()) }
+    })
+    .await
 }
 
 /// Returns `Some` if the `only_deps` arg is set to `false`.
+/// When building a workspace, this always returns `None`.
 pub async fn build(data: Build, config: Config) -> Result<Option<LocalPackage>> {
+    if data.workspace {
+        let workspace = Workspace::current_or_err()?;
+        for project in workspace.members() {
+            operations::BuildProject::new(project, &config)
+                .no_lock(data.no_lock)
+                .only_deps(data.only_deps)
+                .build()
+                .await?;
+        }
+        return Ok(None);
+    }
     let project = Project::current_or_err()?;
     let result = operations::BuildProject::new(&project, &config)
         .no_lock(data.no_lock)
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    config::Config,
+    operations::{self, BundleFormat},
+    progress::MultiProgress,
+    project::Project,
+};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct Bundle {
+    /// The Lua script to run, relative to the project root.{n}
+    /// Defaults to the project's binary, if it has exactly one.
+    #[arg(long, value_name = "script")]
+    entrypoint: Option<PathBuf>,
+
+    /// Where to write the bundle.{n}
+    /// Defaults to the package name in the project's `dist` directory.
+    #[arg(short, long, value_name = "file")]
+    output: Option<PathBuf>,
+
+    /// The kind of bundle to create.{n}
+    /// `lua` amalgamates the Lua modules the entrypoint requires into a single Lua script,{n}
+    /// which only works for pure-Lua projects.
+    #[arg(long, value_enum, default_value_t = BundleFormat::Executable)]
+    format: BundleFormat,
+}
+
+/// Bundle the project and its dependencies into a single executable or Lua script.
+pub async fn bundle(args: Bundle, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let output = operations::Bundle::new(&project, &config)
+        .maybe_entrypoint(args.entrypoint)
+        .maybe_output(args.output)
+        .format(args.format)
+        .progress(MultiProgress::new_arc())
+        .bundle()
+        .await?;
+    output::message(format!("Bundled the project into {}.", output.display()));
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use eyre::{eyre, Result};
+use indicatif::HumanBytes;
+use lux_lib::{
+    cache::{BuildCache, DownloadCache},
+    config::{Config, ConfigBuilder},
+    progress::{MultiProgress, Progress},
+    remote_package_db::RemotePackageDB,
+};
+
+use crate::utils::output;
+
+#[derive(clap::Subcommand)]
+pub enum CacheCmd {
+    /// List the downloaded archives in the cache.
+    List,
+    /// Remove all downloaded archives and cached builds from the cache.
+    Clean,
+    /// Check the integrity of the cached archives,{n}
+    /// removing any that are missing or corrupted.
+    Verify,
+    /// Download the manifests of all configured servers,{n}
+    /// instead of revalidating the cached manifests.
+    RefreshManifests,
+}
+
+pub async fn cache(cmd: CacheCmd, config: Config) -> Result<()> {
+    let cache = DownloadCache::new(&config);
+    match cmd {
+        CacheCmd::List => {
+            let entries = cache.entries().await?;
+            if entries.is_empty() {
+                output::message("The download cache is empty.");
+                return Ok(());
+            }
+            let total_size: u64 = entries.iter().map(|entry| entry.size()).sum();
+            for entry in &entries {
+                output::message(format!("{} ({})", entry.url(), HumanBytes(entry.size())));
+            }
+            output::message(format!(
+                "\n{} archives ({}) in {}",
+                entries.len(),
+                HumanBytes(total_size),
+                cache.root().display()
+            ));
+        }
+        CacheCmd::Clean => {
+            cache.clean().await?;
+            output::message(format!(
+                "Removed all archives from {}",
+                cache.root().display()
+            ));
+            let build_cache = BuildCache::new(&config);
+            build_cache.clean()?;
+            output::message(format!(
+                "Removed all builds from {}",
+                build_cache.root().display()
+            ));
+        }
+        CacheCmd::Verify => {
+            let invalid = cache.verify().await?;
+            if invalid.is_empty() {
+                output::message("All cached archives are intact.");
+            } else {
+                for entry in &invalid {
+                    output::message(format!("corrupted: {}", entry.url()));
+                }
+                return Err(eyre!(
+                    "removed {} corrupted archive(s) from the cache. They will be downloaded again when needed.",
+                    invalid.len()
+                ));
+            }
+        }
+        CacheCmd::RefreshManifests => {
+            let config = ConfigBuilder::from(config)
+                .refresh_manifests(Some(true))
+                .build()?;
+            let progress = MultiProgress::new();
+            let bar = Progress::Progress(progress.new_bar());
+            RemotePackageDB::from_config(&config, &bar).await?;
+            bar.map(|b| b.finish_and_clear());
+            output::message(format!(
+                "Refreshed the manifests of {} server(s).",
+                config.servers_by_priority().len()
+            ));
+        }
+    }
+    Ok(())
+}
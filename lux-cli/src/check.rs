@@ -1,11 +1,11 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, path::PathBuf};
 
-use clap::Args;
-use eyre::Result;
+use clap::{Args, ValueEnum};
+use eyre::{eyre, Result};
 use itertools::Itertools;
 use lux_lib::{
-    config::Config,
-    operations::{Exec, Install, PackageInstallSpec},
+    config::{Config, LuaVersion},
+    operations::{Exec, Install, PackageInstallSpec, Sync},
     progress::MultiProgress,
     project::Project,
     tree,
@@ -15,9 +15,14 @@ use walkdir::WalkDir;
 
 #[derive(Args)]
 pub struct Check {
-    /// Arguments to pass to the luacheck command.{n}
-    /// If you pass arguments to luacheck, Lux will not pass any default arguments.
+    /// Arguments to pass to the linter.{n}
+    /// If you pass arguments to the linter, Lux will not pass any default arguments.
     check_args: Option<Vec<String>>,
+    /// The linter to run.{n}
+    /// luacheck is installed into the project tree.{n}
+    /// selene must be installed and on the PATH.
+    #[arg(long, value_enum, default_value_t = Linter::Luacheck)]
+    linter: Linter,
     /// By default, Lux will add top-level ignored files and directories{n}
     /// (like those in .gitignore) to luacheck's exclude files.{n}
     /// This flag disables that behaviour.{n}
@@ -25,36 +30,63 @@ pub struct Check {
     no_ignore: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Linter {
+    Luacheck,
+    Selene,
+}
+
 pub async fn check(check: Check, config: Config) -> Result<()> {
     let project = Project::current_or_err()?;
 
+    // Map the project's Lua version to the linter's std,
+    // unless the project has its own linter configuration.
+    let std = project
+        .lua_version(&config)
+        .ok()
+        .map(|lua_version| match lua_version {
+            LuaVersion::Lua51 => "lua51",
+            LuaVersion::Lua52 => "lua52",
+            LuaVersion::Lua53 => "lua53",
+            LuaVersion::Lua54 => "lua54",
+            LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => "luajit",
+        });
+
+    if check.linter == Linter::Selene {
+        return selene(check, std, &project, &config).await;
+    }
+
     let luacheck =
         PackageInstallSpec::new("luacheck".parse()?, tree::EntryType::Entrypoint).build();
 
+    let progress = MultiProgress::new_arc();
+
     Install::new(&config)
         .package(luacheck)
         .project(&project)?
-        .progress(MultiProgress::new_arc())
+        .progress(progress.clone())
         .install()
         .await?;
 
+    // Make sure the project's test and dev dependencies are available to luacheck plugins.
+    Sync::new(&project, &config)
+        .progress(progress)
+        .sync_test_dependencies()
+        .await?;
+
     let check_args: Vec<String> = match check.check_args {
         Some(args) => args,
-        None if check.no_ignore => Vec::new(),
         None => {
-            let top_level_project_files = ignore::WalkBuilder::new(project.root())
-                .max_depth(Some(1))
-                .build()
-                .filter_map(Result::ok)
-                .filter_map(|entry| {
-                    let file = entry.into_path();
-                    if file.is_dir() || file.extension().is_some_and(|ext| ext == "lua") {
-                        Some(file)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<HashSet<_>>();
+            let std_args = match std {
+                Some(std) if !project.root().join(".luacheckrc").is_file() => {
+                    vec!["--std".into(), std.into()]
+                }
+                _ => Vec::new(),
+            };
+            if check.no_ignore {
+                return run_luacheck(&project, &config, std_args).await;
+            }
+            let top_level_project_files = top_level_project_files(&project);
 
             let top_level_files = WalkDir::new(project.root())
                 .max_depth(1)
@@ -74,17 +106,84 @@ pub async fn check(check: Check, config: Config) -> Result<()> {
                 .difference(&top_level_project_files)
                 .map(|file| file.to_slash_lossy().to_string());
 
-            std::iter::once("--exclude-files".into())
+            std_args
+                .into_iter()
+                .chain(std::iter::once("--exclude-files".into()))
                 .chain(ignored_files)
                 .collect_vec()
         }
     };
 
-    Exec::new("luacheck", Some(&project), &config)
+    run_luacheck(&project, &config, check_args).await
+}
+
+async fn run_luacheck(project: &Project, config: &Config, args: Vec<String>) -> Result<()> {
+    Exec::new("luacheck", Some(project), config)
         .arg(project.root().to_slash_lossy())
+        .args(args)
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+async fn selene(check: Check, std: Option<&str>, project: &Project, config: &Config) -> Result<()> {
+    which::which("selene").map_err(|_| {
+        eyre!(
+            "selene not found on the PATH.
+See https://kampfkarren.github.io/selene/cli/installation.html for installation instructions."
+        )
+    })?;
+
+    let temp_dir = tempdir::TempDir::new("lux-selene")?;
+    let check_args = match check.check_args {
+        Some(args) => args,
+        None => {
+            let config_args = match std {
+                Some(std) if !project.root().join("selene.toml").is_file() => {
+                    let selene_toml = temp_dir.path().join("selene.toml");
+                    std::fs::write(&selene_toml, format!("std = \"{std}\"\n"))?;
+                    vec!["--config".into(), selene_toml.to_slash_lossy().to_string()]
+                }
+                _ => Vec::new(),
+            };
+            // selene doesn't respect .gitignore, so we pass the files that aren't ignored.
+            let files = if check.no_ignore {
+                vec![project.root().to_slash_lossy().to_string()]
+            } else {
+                top_level_project_files(project)
+                    .into_iter()
+                    .filter(|file| file != project.root().as_path())
+                    .map(|file| file.to_slash_lossy().to_string())
+                    .sorted()
+                    .collect_vec()
+            };
+            config_args.into_iter().chain(files).collect_vec()
+        }
+    };
+
+    Exec::new("selene", Some(project), config)
         .args(check_args)
         .exec()
         .await?;
 
     Ok(())
 }
+
+/// Top-level Lua files and directories in the project root that are not ignored
+/// (e.g. by a .gitignore file).
+fn top_level_project_files(project: &Project) -> HashSet<PathBuf> {
+    ignore::WalkBuilder::new(project.root())
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file = entry.into_path();
+            if file.is_dir() || file.extension().is_some_and(|ext| ext == "lua") {
+                Some(file)
+            } else {
+                None
+            }
+        })
+        .collect::<HashSet<_>>()
+}
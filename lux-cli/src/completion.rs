@@ -1,23 +1,43 @@
 use std::env;
+use std::ffi::OsStr;
 use std::path::PathBuf;
-use std::str::FromStr;
 
 use clap::Args;
 use clap::CommandFactory;
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
 use clap_complete::generate as clap_generate;
-use clap_complete::Shell;
+use clap_complete_nushell::Nushell;
 use eyre::eyre;
 use eyre::Result;
+use itertools::Itertools;
+use lux_lib::config::ConfigBuilder;
 
+use crate::utils::project::current_project_or_user_tree;
 use crate::Cli;
 
+/// The environment variable that makes `lx` print completions instead of running a command.
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum CompletionShell {
+    Bash,
+    Elvish,
+    Fish,
+    Nushell,
+    Powershell,
+    Zsh,
+}
+
 #[derive(Args)]
 pub struct Completion {
     /// The shell to generate the completion script for.{n}
     /// If not set, Lux will try to detect the current shell.{n}
-    /// Possible values: "bash", "elvish", "fish", "powershell", "zsh"{n}
+    /// Possible values: "bash", "elvish", "fish", "nushell", "powershell", "zsh"{n}
     #[arg(value_enum)]
-    shell: Option<Shell>,
+    shell: Option<CompletionShell>,
 }
 
 pub async fn completion(args: Completion) -> Result<()> {
@@ -33,7 +53,7 @@ or specify the shell for which to generate completions.
 
 Example: `lx completion zsh`
 
-Supported shells: "bash", "elvish", "fish", "powershell", "zsh"
+Supported shells: "bash", "elvish", "fish", "nushell", "powershell", "zsh"
 "#
                     )
                 })?
@@ -42,20 +62,57 @@ Supported shells: "bash", "elvish", "fish", "powershell", "zsh"
                 .file_name()
                 .unwrap_or_else(|| shell_var.as_os_str())
                 .to_string_lossy();
-            Shell::from_str(&shell_name).map_err(|_| {
+            let shell_name = match shell_name.as_ref() {
+                "nu" => "nushell",
+                "pwsh" => "powershell",
+                shell_name => shell_name,
+            };
+            CompletionShell::from_str(shell_name, true).map_err(|_| {
                 eyre!(
                     r#"unsupported shell: {}.
 Please specify the shell for which to generate completions.
 
 Example: `lx completion zsh`
 
-Supported shells: "bash", "elvish", "fish", "powershell", "zsh"
+Supported shells: "bash", "elvish", "fish", "nushell", "powershell", "zsh"
 "#,
                     &shell_name
                 )
             })?
         }
     };
-    clap_generate(shell, &mut Cli::command(), "lx", &mut std::io::stdout());
+    let mut stdout = std::io::stdout();
+    // These scripts call back into `lx` to complete arguments,
+    // so that e.g. installed package names can be completed.
+    let completer: &dyn EnvCompleter = match shell {
+        CompletionShell::Bash => &Bash,
+        CompletionShell::Elvish => &Elvish,
+        CompletionShell::Fish => &Fish,
+        CompletionShell::Powershell => &Powershell,
+        CompletionShell::Zsh => &Zsh,
+        CompletionShell::Nushell => {
+            clap_generate(Nushell, &mut Cli::command(), "lx", &mut stdout);
+            return Ok(());
+        }
+    };
+    completer.write_registration(COMPLETE_ENV, "lx", "lx", "lx", &mut stdout)?;
     Ok(())
 }
+
+/// Complete the names of the packages installed in the current project's tree or the user tree.
+pub(crate) fn installed_packages(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    let packages = ConfigBuilder::new()
+        .and_then(ConfigBuilder::build)
+        .ok()
+        .and_then(|config| current_project_or_user_tree(&config).ok())
+        .and_then(|tree| tree.list().ok())
+        .unwrap_or_default();
+    packages
+        .into_keys()
+        .map(|name| name.to_string())
+        .filter(|name| name.starts_with(current.as_ref()))
+        .sorted()
+        .map(CompletionCandidate::new)
+        .collect_vec()
+}
@@ -1,6 +1,13 @@
-use eyre::{eyre, Result};
+use std::collections::BTreeMap;
+
+use eyre::{eyre, OptionExt, Result};
 use inquire::Confirm;
-use lux_lib::config::{Config, ConfigBuilder};
+use lux_lib::config::{
+    layers::{self, ConfigFile, ConfigLocation},
+    Config, ConfigBuilder,
+};
+
+use crate::utils::{output, prompt::PromptOrDefault};
 
 #[derive(clap::Subcommand)]
 pub enum ConfigCmd {
@@ -11,6 +18,48 @@ pub enum ConfigCmd {
     /// Show the current config.
     /// This includes options picked up from CLI flags.
     Show,
+    /// Get a config value by its key, e.g. `lua_version` or `variables.CC`.{n}
+    /// Without `--location`, gets the value from the current config,{n}
+    /// including options picked up from CLI flags.
+    Get(ConfigGet),
+    /// Set a config value in a config file.
+    Set(ConfigSet),
+    /// List the config values that are set in the config files and environment variables.{n}
+    /// Without `--location`, shows where each value is set.{n}
+    /// Layers, in order of increasing precedence:{n}
+    /// system, user, project (`.lux/config.toml`), environment (`LUX_<KEY>`), CLI flags.
+    List(ConfigList),
+}
+
+#[derive(clap::Args)]
+pub struct ConfigGet {
+    /// The key of the value, e.g. `lua_version` or `variables.CC`.
+    key: String,
+
+    /// Get the value from the config file at this location.
+    #[arg(long, value_enum)]
+    location: Option<ConfigLocation>,
+}
+
+#[derive(clap::Args)]
+pub struct ConfigSet {
+    /// The key of the value, e.g. `lua_version` or `variables.CC`.
+    key: String,
+
+    /// The value. TOML values like `true` or `["a", "b"]` are parsed,{n}
+    /// if that results in a valid config.
+    value: String,
+
+    /// The location of the config file to edit.
+    #[arg(long, value_enum, default_value_t = ConfigLocation::User)]
+    location: ConfigLocation,
+}
+
+#[derive(clap::Args)]
+pub struct ConfigList {
+    /// Only list the values set in the config file at this location.
+    #[arg(long, value_enum)]
+    location: Option<ConfigLocation>,
 }
 
 #[derive(clap::Args)]
@@ -26,14 +75,28 @@ pub struct Init {
     current: bool,
 }
 
-pub fn config(cmd: ConfigCmd, config: Config) -> Result<()> {
+impl ConfigCmd {
+    /// Whether the command needs the current config.
+    /// Commands that only read or edit the config files don't, so that they can be used
+    /// to repair a config file that fails to load.
+    pub fn needs_config(&self) -> bool {
+        match self {
+            ConfigCmd::Init(init) => init.current,
+            ConfigCmd::Show => true,
+            ConfigCmd::Get(args) => args.location.is_none(),
+            ConfigCmd::Edit | ConfigCmd::Set(_) | ConfigCmd::List(_) => false,
+        }
+    }
+}
+
+pub fn config(cmd: ConfigCmd, config: Option<Config>) -> Result<()> {
     match cmd {
         ConfigCmd::Init(init) => {
             let config_file = ConfigBuilder::config_file()?;
             if !config_file.is_file()
                 || Confirm::new("Config already exists. Overwrite?")
                     .with_default(false)
-                    .prompt()
+                    .prompt_or_default()
                     .expect("Error prompting to overwrite config")
             {
                 std::fs::create_dir_all(config_file.parent().unwrap())?;
@@ -41,13 +104,13 @@ pub fn config(cmd: ConfigCmd, config: Config) -> Result<()> {
                     let cfg: ConfigBuilder = ConfigBuilder::default().build()?.into();
                     toml::to_string(&cfg)?
                 } else if init.current {
-                    let cfg: ConfigBuilder = config.into();
+                    let cfg: ConfigBuilder = config.ok_or_eyre("config not loaded")?.into();
                     toml::to_string(&cfg)?
                 } else {
                     String::default()
                 };
                 std::fs::write(&config_file, content)?;
-                print!("Config initialised at {}", config_file.display());
+                output::message(format!("Config initialised at {}", config_file.display()));
             }
         }
         ConfigCmd::Edit => {
@@ -64,9 +127,63 @@ to initialise a config file.
             edit::edit_file(config_file)?;
         }
         ConfigCmd::Show => {
-            let cfg: ConfigBuilder = config.into();
-            print!("{}", toml::to_string(&cfg)?);
+            let cfg: ConfigBuilder = config.ok_or_eyre("config not loaded")?.into();
+            output::message(toml::to_string(&cfg)?.trim_end());
         }
+        ConfigCmd::Get(args) => {
+            let value = match args.location {
+                Some(location) => ConfigFile::open(location)?.get(&args.key).cloned(),
+                None => {
+                    let cfg: ConfigBuilder = config.ok_or_eyre("config not loaded")?.into();
+                    match toml::Value::try_from(cfg)? {
+                        toml::Value::Table(table) => layers::get_value(&table, &args.key).cloned(),
+                        _ => None,
+                    }
+                }
+            }
+            .ok_or_eyre(format!("`{}` is not set", args.key))?;
+            // Print strings without quotes, so that the output can be used in scripts
+            if let Some(value) = value.as_str() {
+                output::message(value);
+            } else {
+                output::message(format!("{value}"));
+            }
+        }
+        ConfigCmd::Set(args) => {
+            let mut config_file = ConfigFile::open(args.location)?;
+            config_file.set(&args.key, &args.value)?;
+            config_file.save()?;
+            output::message(format!(
+                "Set `{}` in {}",
+                args.key,
+                config_file.path().display()
+            ));
+        }
+        ConfigCmd::List(args) => match args.location {
+            Some(location) => {
+                let config_file = ConfigFile::open(location)?;
+                let table = layers::redact_secrets(config_file.table());
+                for (key, value) in layers::flatten_table(&table) {
+                    output::message(format!("{key} = {value}"));
+                }
+            }
+            None => {
+                let config_layers = layers::config_layers()?;
+                let mut values = BTreeMap::new();
+                let config_layers = config_layers
+                    .into_iter()
+                    .map(|(layer, table)| (layer, layers::redact_secrets(&table)))
+                    .collect::<Vec<_>>();
+                for (layer, table) in &config_layers {
+                    for (key, value) in layers::flatten_table(table) {
+                        values.insert(key, (value, layer));
+                    }
+                }
+                for (key, (value, layer)) in values {
+                    output::message(format!("{key} = {value} # {layer}"));
+                }
+            }
+        },
     }
     Ok(())
 }
@@ -1,8 +1,18 @@
 use crate::{
     project::DebugProject,
     unpack::{Unpack, UnpackRemote},
+    utils::project::current_project_or_user_tree,
 };
-use clap::Subcommand;
+use clap::{Args, Subcommand};
+use eyre::Result;
+use itertools::Itertools;
+use lux_lib::{
+    config::Config,
+    lockfile::{DependencyGraph, LocalPackageLockType},
+    project::Project,
+};
+
+use crate::utils::output;
 
 #[derive(Subcommand)]
 pub enum Debug {
@@ -14,4 +24,109 @@ pub enum Debug {
     UnpackRemote(UnpackRemote),
     /// View information about the current project.
     Project(DebugProject),
+    /// View the layout of the current project's tree or the user tree,{n}
+    /// including the paths of each installed rock.
+    Tree,
+    /// View the dependency graph of the current project's lockfile{n}
+    /// or the user tree's lockfile.
+    Lockfile(DebugLockfile),
+    /// View the resolved configuration.
+    Config,
+}
+
+#[derive(Args)]
+pub struct DebugLockfile {
+    /// Show the test dependencies of the current project.
+    #[arg(long, conflicts_with = "build")]
+    test: bool,
+    /// Show the build dependencies of the current project.
+    #[arg(long)]
+    build: bool,
+}
+
+pub fn debug_tree(config: Config) -> Result<()> {
+    let tree = current_project_or_user_tree(&config)?;
+    output::message(format!("Tree root: {}", tree.root().display()));
+    output::message(format!("Tree bin: {}", tree.bin().display()));
+    output::message(format!("Lua version: {}", tree.version()));
+    output::message(format!("Lockfile: {}", tree.lockfile_path().display()));
+    for package in tree
+        .list()?
+        .into_values()
+        .flatten()
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+    {
+        let layout = tree.installed_rock_layout(&package)?;
+        output::message(format!(
+            "\n{}@{} ({})",
+            package.name(),
+            package.version(),
+            package.id()
+        ));
+        output::message(format!("  rock: {}", layout.rock_path.display()));
+        output::message(format!("  src: {}", layout.src.display()));
+        output::message(format!("  lib: {}", layout.lib.display()));
+        output::message(format!("  bin: {}", layout.bin.display()));
+        output::message(format!("  etc: {}", layout.etc.display()));
+        output::message(format!("  conf: {}", layout.conf.display()));
+        output::message(format!("  doc: {}", layout.doc.display()));
+    }
+    Ok(())
+}
+
+pub fn debug_lockfile(args: DebugLockfile, config: Config) -> Result<()> {
+    let graph = match Project::current()? {
+        Some(project) => {
+            let lockfile = project.lockfile()?;
+            output::message(format!("Lockfile: {}", project.lockfile_path().display()));
+            let deps = if args.test {
+                LocalPackageLockType::Test
+            } else if args.build {
+                LocalPackageLockType::Build
+            } else {
+                LocalPackageLockType::Regular
+            };
+            lockfile.dependency_graph(&deps)
+        }
+        None => {
+            let tree = current_project_or_user_tree(&config)?;
+            output::message(format!("Lockfile: {}", tree.lockfile_path().display()));
+            tree.lockfile()?.dependency_graph()
+        }
+    };
+    print_dependency_graph(&graph);
+    Ok(())
+}
+
+fn print_dependency_graph(graph: &DependencyGraph) {
+    for package in graph.packages() {
+        let entrypoint = if graph.is_entrypoint(package) {
+            " (entrypoint)"
+        } else {
+            ""
+        };
+        output::message(format!(
+            "\n{}@{}{entrypoint}",
+            package.name(),
+            package.version()
+        ));
+        output::message(format!("  id: {}", package.id()));
+        output::message(format!("  constraint: {:?}", package.constraint()));
+        output::message(format!("  pinned: {}", package.pinned()));
+        output::message(format!("  opt: {}", package.opt()));
+        let dependencies = graph
+            .dependencies(package)
+            .into_iter()
+            .map(|dep| format!("{}@{}", dep.name(), dep.version()))
+            .join(", ");
+        if !dependencies.is_empty() {
+            output::message(format!("  dependencies: {dependencies}"));
+        }
+    }
+}
+
+pub fn debug_config(config: Config) -> Result<()> {
+    // Secrets, like API keys, are redacted by the Debug implementations.
+    output::message(format!("{config:#?}"));
+    Ok(())
 }
@@ -6,25 +6,40 @@ use lux_lib::{
     config::{Config, LuaVersion},
     lockfile::LocalPackage,
     lua_rockspec::RemoteLuaRockspec,
+    operations::{Exec, Install, PackageInstallSpec},
     package::PackageReq,
+    project::Project,
     rockspec::Rockspec,
-    tree::{RockMatches, Tree},
+    tree::{self, RockMatches, Tree},
 };
+use path_slash::PathBufExt;
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::utils::{output, prompt::PromptOrDefault};
+
 #[derive(Args)]
 pub struct Doc {
-    package: PackageReq,
+    /// The installed package whose documentation to open.{n}
+    /// If not set, generates the current project's documentation with ldoc.
+    package: Option<PackageReq>,
 
     /// Ignore local docs and open the package's homepage in a browser.
-    #[arg(long)]
+    #[arg(long, requires = "package")]
     online: bool,
+
+    /// Open the generated documentation in a browser.
+    #[arg(long, conflicts_with = "package")]
+    open: bool,
 }
 
 pub async fn doc(args: Doc, config: Config) -> Result<()> {
+    let package = match args.package {
+        Some(package) => package,
+        None => return generate_project_docs(args.open, config).await,
+    };
     let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
-    let package_id = match tree.match_rocks(&args.package)? {
+    let package_id = match tree.match_rocks(&package)? {
         RockMatches::NotFound(package_req) => {
             Err(eyre!("No package matching {} found.", package_req))
         }
@@ -33,7 +48,7 @@ pub async fn doc(args: Doc, config: Config) -> Result<()> {
 Found multiple packages matching {}.
 Please specify an exact package (<name>@<version>) or narrow the version requirement.
 ",
-            &args.package
+            &package
         )),
         RockMatches::Single(package_id) => Ok(package_id),
     }?;
@@ -49,6 +64,50 @@ Please specify an exact package (<name>@<version>) or narrow the version require
     }
 }
 
+/// Generate HTML documentation for the current project in `target/doc` with ldoc,
+/// which is installed into the user tree if it isn't already.
+async fn generate_project_docs(open: bool, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let tools_tree = config.user_tree(project.lua_version(&config)?)?;
+    let ldoc = PackageReq::new("ldoc".into(), None)?;
+    if !tools_tree.match_rocks(&ldoc)?.is_found() {
+        Install::new(&config)
+            .package(PackageInstallSpec::new(ldoc, tree::EntryType::Entrypoint).build())
+            .tree(tools_tree)
+            .install()
+            .await?;
+    }
+
+    let output_dir = project.root().join("target").join("doc");
+    std::fs::create_dir_all(&output_dir)?;
+
+    // An ldoc config.ld file in the project root takes care of the source directories
+    let source = if project.root().join("config.ld").is_file() {
+        project.root().to_path_buf()
+    } else {
+        ["src", "lua", "lib"]
+            .into_iter()
+            .map(|dir| project.root().join(dir))
+            .find(|dir| dir.is_dir())
+            .unwrap_or(project.root().to_path_buf())
+    };
+
+    Exec::new("ldoc", Some(&project), &config)
+        .arg("--dir")
+        .arg(output_dir.to_slash_lossy())
+        .arg(source.to_slash_lossy())
+        .exec()
+        .await?;
+
+    let index = output_dir.join("index.html");
+    if open {
+        open::that(&index)?;
+    } else {
+        output::message(format!("Documentation generated in {}", index.display()));
+    }
+    Ok(())
+}
+
 async fn open_homepage(pkg: LocalPackage, tree: &Tree) -> Result<()> {
     let homepage = match get_homepage(&pkg, tree)? {
         Some(homepage) => Ok(homepage),
@@ -95,7 +154,7 @@ async fn open_local_docs(pkg: LocalPackage, tree: &Tree) -> Result<()> {
             Some(homepage) => {
                 if Confirm::new("No local documentation found. Open homepage?")
                     .with_default(false)
-                    .prompt()
+                    .prompt_or_default()
                     .expect("Error prompting to open homepage")
                 {
                     open::that(homepage.to_string())?;
@@ -111,8 +170,7 @@ async fn open_local_docs(pkg: LocalPackage, tree: &Tree) -> Result<()> {
             "Multiple documentation files found. Please select one to open.",
             files,
         )
-        .prompt()
-        .expect("error selecting from multiple files");
+        .prompt_or_default()?;
         edit::edit_file(layout.doc.join(file))?;
         Ok(())
     }
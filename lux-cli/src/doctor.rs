@@ -0,0 +1,54 @@
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::{Config, LuaVersion},
+    operations::{CheckStatus, Doctor},
+    project::Project,
+};
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
+
+pub async fn doctor(config: Config) -> Result<()> {
+    // A tree that can't be resolved (e.g. because no Lua version is set)
+    // is reported by the Lua version check.
+    let tree = match Project::current()? {
+        Some(project) => project.tree(&config).ok(),
+        None => LuaVersion::from(&config)
+            .ok()
+            .and_then(|lua_version| config.user_tree(lua_version.clone()).ok()),
+    };
+    let report = Doctor::new(&config)
+        .maybe_tree(tree.as_ref())
+        .diagnose()
+        .await;
+
+    for check in report.checks() {
+        let status = match check.status() {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Error => "error",
+        };
+        if is_json_output() {
+            output::emit(
+                "check",
+                json!({
+                    "name": check.name(),
+                    "status": status,
+                    "message": check.message(),
+                    "fix": check.fix(),
+                }),
+            );
+            continue;
+        }
+        output::message(format!("[{status}] {}: {}", check.name(), check.message()));
+        if let Some(fix) = check.fix() {
+            output::message(format!("        fix: {fix}"));
+        }
+    }
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(eyre!("some checks failed. See the suggested fixes above."))
+    }
+}
@@ -14,7 +14,7 @@ use crate::build::Build;
 
 #[derive(Args)]
 pub struct Exec {
-    /// The command to run.
+    /// The command or project script to run.
     command: String,
 
     /// Arguments to pass to the program.
@@ -30,6 +30,20 @@ pub struct Exec {
 
 pub async fn exec(run: Exec, config: Config) -> Result<()> {
     let project = Project::current()?;
+    if let Some(project) = &project {
+        if project
+            .toml()
+            .scripts()
+            .is_some_and(|scripts| scripts.contains_key(&run.command))
+        {
+            operations::RunScript::new(&run.command, project, &config)
+                .args(run.args.unwrap_or_default())
+                .disable_loader(run.no_loader)
+                .run()
+                .await?;
+            return Ok(());
+        }
+    }
     let tree = match &project {
         Some(project) => project.tree(&config)?,
         None => {
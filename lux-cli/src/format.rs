@@ -1,27 +1,32 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Args;
-use eyre::{OptionExt, Result};
+use eyre::{eyre, OptionExt, Result};
 use lux_lib::project::Project;
 use stylua_lib::Config;
 use walkdir::WalkDir;
 
+use crate::utils::output;
+
 #[derive(Args)]
 pub struct Fmt {
     /// Optional path to a workspace or Lua file to format
     workspace_or_file: Option<PathBuf>,
+
+    /// Don't write the formatted files.{n}
+    /// Instead, list the files that are not formatted and exit with an error if there are any.
+    #[arg(long)]
+    check: bool,
 }
 
-// TODO: Add `PathBuf` parameter that describes what directory or file to format here.
 pub fn format(args: Fmt) -> Result<()> {
     let project = Project::current()?.ok_or_eyre(
         "`lx fmt` can only be executed in a lux project! Run `lx new` to create one.",
     )?;
 
-    let config: Config = std::fs::read_to_string("stylua.toml")
-        .or_else(|_| std::fs::read_to_string(".stylua.toml"))
-        .map(|config: String| toml::from_str(&config).unwrap_or_default())
-        .unwrap_or_default();
+    let config = stylua_config(&project)?;
+
+    let mut unformatted = Vec::new();
 
     WalkDir::new(project.root().join("src"))
         .into_iter()
@@ -40,32 +45,101 @@ pub fn format(args: Fmt) -> Result<()> {
                 .extension()
                 .is_some_and(|ext| ext == "lua")
             {
-                let formatted_code = stylua_lib::format_code(
-                    &std::fs::read_to_string(file.path())?,
-                    config,
-                    None,
-                    stylua_lib::OutputVerification::Full,
-                )?;
-
-                std::fs::write(file.into_path(), formatted_code)?;
+                format_file(file.path(), config, args.check, &mut unformatted)?;
             };
             Ok::<_, eyre::Report>(())
         })?;
 
     // Format the rockspec
 
-    let rockspec = project.root().join("extra.rockspec");
+    let rockspec = project.extra_rockspec_path();
 
     if rockspec.exists() {
-        let formatted_code = stylua_lib::format_code(
-            &std::fs::read_to_string(&rockspec)?,
-            config,
-            None,
-            stylua_lib::OutputVerification::Full,
-        )?;
-
-        std::fs::write(rockspec, formatted_code)?;
+        format_file(&rockspec, config, args.check, &mut unformatted)?;
+    }
+
+    if unformatted.is_empty() {
+        Ok(())
+    } else {
+        for file in &unformatted {
+            output::message(file.display());
+        }
+        Err(eyre!(
+            "{} file(s) are not formatted. Run `lx fmt` to format them.",
+            unformatted.len()
+        ))
     }
+}
+
+/// Format a file in place, or, in `check` mode, add it to `unformatted` if it is not formatted.
+fn format_file(
+    file: &Path,
+    config: Config,
+    check: bool,
+    unformatted: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let code = std::fs::read_to_string(file)?;
+    let formatted_code =
+        stylua_lib::format_code(&code, config, None, stylua_lib::OutputVerification::Full)?;
 
+    if check {
+        if formatted_code != code {
+            unformatted.push(file.to_path_buf());
+        }
+    } else {
+        std::fs::write(file, formatted_code)?;
+    }
     Ok(())
 }
+
+/// The stylua configuration is read from a `stylua.toml` or `.stylua.toml`
+/// in the project root, or from the `[stylua]` table in the `lux.toml`.
+fn stylua_config(project: &Project) -> Result<Config> {
+    let config_file = [
+        project.root().join("stylua.toml"),
+        project.root().join(".stylua.toml"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file());
+
+    if let Some(config_file) = config_file {
+        return toml::from_str(&std::fs::read_to_string(&config_file)?)
+            .map_err(|err| eyre!("invalid stylua config {}:\n{err}", config_file.display()));
+    }
+
+    let project_toml: toml::Table = toml::from_str(&std::fs::read_to_string(project.toml_path())?)?;
+    match project_toml.get("stylua") {
+        Some(stylua) => stylua
+            .clone()
+            .try_into()
+            .map_err(|err| eyre!("invalid [stylua] table in lux.toml:\n{err}")),
+        None => Ok(Config::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stylua_config_from_project_toml() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            dir.join("lux.toml"),
+            r#"
+package = "my-package"
+version = "1.0.0"
+lua = "5.1"
+
+[stylua]
+column_width = 100
+"#,
+        )
+        .unwrap();
+        let project = Project::from_exact(dir.path()).unwrap().unwrap();
+        assert_eq!(stylua_config(&project).unwrap().column_width, 100);
+
+        std::fs::write(dir.join("stylua.toml"), "column_width = 80\n").unwrap();
+        assert_eq!(stylua_config(&project).unwrap().column_width, 80);
+    }
+}
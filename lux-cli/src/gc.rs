@@ -0,0 +1,70 @@
+use clap::Args;
+use eyre::Result;
+use indicatif::HumanBytes;
+use lux_lib::config::Config;
+
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    project::current_project_or_user_tree,
+};
+
+#[derive(Args)]
+pub struct Gc {
+    /// Only report what would be removed.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Remove the rocks in the current project's tree (or the user tree)
+/// that are not needed by any entrypoint.
+pub async fn gc(args: Gc, config: Config) -> Result<()> {
+    let tree = current_project_or_user_tree(&config)?;
+    let report = tree.gc(&config).dry_run(args.dry_run).gc().await?;
+    if is_json_output() {
+        output::emit(
+            "gc",
+            json!({
+                "dry_run": args.dry_run,
+                "packages": report
+                    .packages()
+                    .iter()
+                    .map(|package| json!({
+                        "name": package.name().to_string(),
+                        "version": package.version().to_string(),
+                    }))
+                    .collect_vec(),
+                "orphaned_dirs": report.orphaned_dirs(),
+                "reclaimed": report.reclaimed(),
+            }),
+        );
+        return Ok(());
+    }
+
+    if report.is_empty() {
+        output::message("Nothing to remove.");
+        return Ok(());
+    }
+
+    let action = if args.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    for package in report.packages() {
+        output::message(format!("{action} {}@{}", package.name(), package.version()));
+    }
+    for dir in report.orphaned_dirs() {
+        output::message(format!("{action} {}", dir.display()));
+    }
+    let reclaimed = if args.dry_run {
+        "would be reclaimed"
+    } else {
+        "reclaimed"
+    };
+    output::message(format!("{} {reclaimed}.", HumanBytes(report.reclaimed())));
+
+    Ok(())
+}
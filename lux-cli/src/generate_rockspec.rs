@@ -1,23 +1,37 @@
+use std::path::PathBuf;
+
 use clap::Args;
-use eyre::Result;
-use lux_lib::{project::Project, rockspec::Rockspec};
+use eyre::{OptionExt, Result};
+use lux_lib::project::Project;
+
+use crate::utils::output;
 
 #[derive(Args)]
-pub struct GenerateRockspec {}
+pub struct GenerateRockspec {
+    /// Where to write the rockspec.{n}
+    /// Defaults to `<package>-<version>.rockspec` in the project root.{n}
+    /// Use `-` to print the rockspec to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
 
-pub fn generate_rockspec(_data: GenerateRockspec) -> Result<()> {
-    let project = Project::current()?.unwrap();
+pub fn generate_rockspec(data: GenerateRockspec) -> Result<()> {
+    let project = Project::current()?.ok_or_eyre("not in a lux project directory")?;
 
-    let toml = project.toml().into_remote()?;
-    let rockspec = toml.to_lua_remote_rockspec_string()?;
+    let rockspec = project.to_rockspec()?;
 
-    let path = project
-        .root()
-        .join(format!("{}-{}.rockspec", toml.package(), toml.version()));
+    let path = match data.output {
+        Some(path) if path.as_os_str() == "-" => {
+            output::message(rockspec.trim_end());
+            return Ok(());
+        }
+        Some(path) => path,
+        None => project.root().join(project.rockspec_file_name()?),
+    };
 
     std::fs::write(&path, rockspec)?;
 
-    println!("Wrote rockspec to {}", path.display());
+    output::message(format!("Wrote rockspec to {}", path.display()));
 
     Ok(())
 }
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use clap::{Args, CommandFactory};
+use clap_mangen::Man;
+use eyre::{eyre, Result};
+use itertools::Itertools;
+
+use crate::utils::output;
+use crate::Cli;
+
+/// Long-form guides, by topic.
+const TOPICS: &[(&str, &str, &str)] = &[
+    (
+        "lockfile",
+        "The lux.lock file format",
+        include_str!("../resources/help/lockfile.md"),
+    ),
+    (
+        "manifest",
+        "The lux.toml project manifest",
+        include_str!("../resources/help/manifest.md"),
+    ),
+];
+
+#[derive(Args)]
+pub struct Help {
+    /// A help topic (e.g. `manifest` or `lockfile`),{n}
+    /// or the command to print the help for (e.g. `cache clean`).
+    topic: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct HelpMan {
+    /// Write a man page for each command to this directory,{n}
+    /// instead of printing the man page of `lx` to stdout.
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+}
+
+pub fn help(args: Help) -> Result<()> {
+    let mut cmd = Cli::command();
+    let Some(name) = args.topic.first() else {
+        cmd.print_long_help()?;
+        output::message("\nHelp topics (`lx help <topic>`):");
+        for (name, description, _) in TOPICS {
+            output::message(format!("  {name:<10} {description}"));
+        }
+        return Ok(());
+    };
+    if let Some((_, _, content)) = TOPICS.iter().find(|(topic, _, _)| topic == name) {
+        output::message(content.trim_end());
+        return Ok(());
+    }
+    let mut subcommand = &mut cmd;
+    for name in &args.topic {
+        subcommand = subcommand.find_subcommand_mut(name).ok_or_else(|| {
+            eyre!(
+                "no help topic or command named '{}'. Help topics: {}",
+                args.topic.join(" "),
+                TOPICS.iter().map(|(topic, _, _)| topic).join(", ")
+            )
+        })?;
+    }
+    subcommand.print_long_help()?;
+    Ok(())
+}
+
+pub fn help_man(args: HelpMan) -> Result<()> {
+    let cmd = Cli::command();
+    match args.out_dir {
+        Some(out_dir) => {
+            std::fs::create_dir_all(&out_dir)?;
+            clap_mangen::generate_to(cmd, &out_dir)?;
+            output::message(format!("Wrote man pages to {}", out_dir.display()));
+        }
+        None => Man::new(cmd).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::project::{import::import_rockspec, PROJECT_TOML};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct Import {
+    /// The rockspec to import.
+    rockspec: PathBuf,
+
+    /// The directory to write the `lux.toml` to.{n}
+    /// Defaults to the current directory.
+    #[arg(long)]
+    dir: Option<PathBuf>,
+
+    /// Overwrite an existing `lux.toml`.
+    #[arg(long)]
+    force: bool,
+}
+
+pub fn import(data: Import) -> Result<()> {
+    let dir = match data.dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let path = dir.join(PROJECT_TOML);
+    if path.exists() && !data.force {
+        return Err(eyre!(
+            "{} already exists. Use --force to overwrite it.",
+            path.display()
+        ));
+    }
+
+    let content = std::fs::read_to_string(&data.rockspec)?;
+    let project_toml = import_rockspec(&content)?;
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, project_toml)?;
+
+    output::message(format!(
+        "Imported {} into {}",
+        data.rockspec.display(),
+        path.display()
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::operations;
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
+
+#[derive(Args)]
+pub struct Index {
+    /// The directory with the `.rockspec` and `.rock` files.{n}
+    /// Defaults to the current directory.
+    dir: Option<PathBuf>,
+}
+
+/// Generate the manifests for a directory of rockspecs and rocks.
+pub fn index(args: Index) -> Result<()> {
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let report = operations::Index::new(dir.clone()).index()?;
+
+    if is_json_output() {
+        output::emit(
+            "index",
+            json!({
+                "dir": dir,
+                "packages": report.packages(),
+                "manifests": report.manifests(),
+                "skipped": report.skipped(),
+            }),
+        );
+    } else {
+        for file_name in report.skipped() {
+            eprintln!("Skipped {file_name}: expected a `<name>-<version>-<revision>` file name");
+        }
+        output::message(format!(
+            "Indexed {} packages in {} ({} manifests written).",
+            report.packages(),
+            dir.display(),
+            report.manifests().len(),
+        ));
+    }
+    Ok(())
+}
@@ -1,18 +1,39 @@
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 use eyre::Result;
+use itertools::Itertools;
 use lux_lib::{
     config::Config,
+    lua_rockspec::RemoteLuaRockspec,
     operations::Download,
-    package::PackageReq,
+    package::{PackageReq, PackageVersion},
     progress::{MultiProgress, Progress},
+    remote_package_db::RemotePackageDB,
     rockspec::Rockspec,
 };
+use serde_json::json;
 
-use crate::utils::project::current_project_or_user_tree;
+use crate::{
+    completion::installed_packages,
+    utils::{
+        output::{self, is_json_output, print_json},
+        project::current_project_or_user_tree,
+    },
+};
 
 #[derive(Args)]
 pub struct Info {
+    #[arg(add = ArgValueCompleter::new(installed_packages))]
     package: PackageReq,
+
+    /// Show the metadata of the latest matching version on the rock servers,{n}
+    /// even if a matching version is installed.
+    #[arg(long)]
+    remote: bool,
+
+    /// Print the metadata as JSON.
+    #[arg(long)]
+    json: bool,
 }
 
 pub async fn info(data: Info, config: Config) -> Result<()> {
@@ -21,54 +42,151 @@ pub async fn info(data: Info, config: Config) -> Result<()> {
     let progress = MultiProgress::new();
     let bar = Progress::Progress(progress.new_bar());
 
-    let rockspec = Download::new(&data.package, &config, &bar)
-        .download_rockspec()
-        .await?
-        .rockspec;
+    let package_db = RemotePackageDB::from_config(&config, &bar).await?;
+
+    let installed = tree
+        .list()?
+        .remove(data.package.name())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|package| data.package.version_req().matches(package.version()))
+        .sorted_by(|a, b| b.version().cmp(a.version()))
+        .collect_vec();
+
+    let rockspec = match installed.first() {
+        Some(package) if !data.remote => {
+            let content =
+                std::fs::read_to_string(tree.installed_rock_layout(package)?.rockspec_path())?;
+            RemoteLuaRockspec::new(&content)?
+        }
+        _ => {
+            Download::new(&data.package, &config, &bar)
+                .package_db(&package_db)
+                .download_rockspec()
+                .await?
+                .rockspec
+        }
+    };
 
     bar.map(|b| b.finish_and_clear());
 
-    if tree.match_rocks(&data.package)?.is_found() {
-        println!("Currently installed in {}", tree.root().display());
+    let versions = package_db.versions(rockspec.package());
+    let is_installed =
+        |version: &PackageVersion| installed.iter().any(|package| package.version() == version);
+    let dependencies = rockspec
+        .dependencies()
+        .current_platform()
+        .iter()
+        .filter(|dep| dep.name() != &"lua".into())
+        .map(|dep| dep.to_string())
+        .collect_vec();
+    let build_dependencies = rockspec
+        .build_dependencies()
+        .current_platform()
+        .iter()
+        .map(|dep| dep.to_string())
+        .collect_vec();
+    let description = rockspec.description();
+
+    if data.json || is_json_output() {
+        let info = json!({
+            "name": rockspec.package().to_string(),
+            "version": rockspec.version().to_string(),
+            "installed": is_installed(rockspec.version()),
+            "summary": description.summary,
+            "description": description.detailed.as_ref().map(|detailed| detailed.trim()),
+            "license": description.license,
+            "homepage": description.homepage.as_ref().map(|url| url.to_string()),
+            "maintainer": description.maintainer,
+            "labels": description.labels,
+            "lua": rockspec.lua().to_string(),
+            "dependencies": dependencies,
+            "build_dependencies": build_dependencies,
+            "versions": versions.iter().map(|version| version.to_string()).collect_vec(),
+        });
+        print_json("info", info)?;
+        return Ok(());
+    }
+
+    if is_installed(rockspec.version()) {
+        output::message(format!("Currently installed in {}", tree.root().display()));
     }
 
-    println!("Package name: {}", rockspec.package());
-    println!("Package version: {}", rockspec.version());
-    println!();
+    output::message(format!("Package name: {}", rockspec.package()));
+    output::message(format!("Package version: {}", rockspec.version()));
+    output::message("");
 
-    println!(
+    output::message(format!(
         "Summary: {}",
-        rockspec
-            .description()
-            .summary
-            .as_ref()
-            .unwrap_or(&"None".to_string())
-    );
-    println!(
+        description.summary.as_ref().unwrap_or(&"None".to_string())
+    ));
+    output::message(format!(
         "Description: {}",
-        rockspec
-            .description()
+        description
             .detailed
             .as_ref()
             .unwrap_or(&"None".to_string())
             .trim()
-    );
-    println!(
+    ));
+    output::message(format!(
         "License: {}",
-        rockspec
-            .description()
+        description
             .license
             .as_ref()
             .unwrap_or(&"Unknown (all rights reserved by the author)".to_string())
-    );
-    println!(
+    ));
+    output::message(format!(
+        "Homepage: {}",
+        description
+            .homepage
+            .as_ref()
+            .map(|url| url.to_string())
+            .unwrap_or("None".to_string())
+    ));
+    output::message(format!(
         "Maintainer: {}",
-        rockspec
-            .description()
+        description
             .maintainer
             .as_ref()
             .unwrap_or(&"Unspecified".to_string())
-    );
+    ));
+    if !description.labels.is_empty() {
+        output::message(format!("Labels: {}", description.labels.join(", ")));
+    }
+    output::message(format!(
+        "Lua: {}",
+        if rockspec.lua().is_any() {
+            "any".to_string()
+        } else {
+            rockspec.lua().to_string()
+        }
+    ));
+    output::message("");
+
+    output::message(format!(
+        "Dependencies: {}",
+        if dependencies.is_empty() {
+            "None".to_string()
+        } else {
+            dependencies.join(", ")
+        }
+    ));
+    if !build_dependencies.is_empty() {
+        output::message(format!(
+            "Build dependencies: {}",
+            build_dependencies.join(", ")
+        ));
+    }
+    output::message("");
+
+    output::message("Available versions:");
+    for version in &versions {
+        if is_installed(version) {
+            output::message(format!("  {version} (installed)"));
+        } else {
+            output::message(format!("  {version}"));
+        }
+    }
 
     Ok(())
 }
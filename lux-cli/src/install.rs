@@ -1,18 +1,31 @@
+use std::str::FromStr;
+
 use eyre::Result;
+use inquire::Confirm;
+use itertools::Itertools;
 use lux_lib::{
-    config::{Config, LuaVersion},
+    config::{file_conflicts::FileConflictPolicy, Config, LuaVersion},
     lockfile::PinnedState,
-    operations,
+    operations::{self, write_global_tool_shims, GitPackage, InstallError, RockFile},
     package::PackageReq,
-    progress::MultiProgress,
+    path::BinPath,
+    progress::{MultiProgress, Progress},
+    tree::RockMatches,
 };
+use serde_json::json;
 
-use crate::utils::install::apply_build_behaviour;
+use crate::utils::{
+    install::apply_build_behaviour,
+    output::{self, is_json_output},
+    prompt::PromptOrDefault,
+};
 
 #[derive(clap::Args)]
 pub struct Install {
-    /// Package or list of packages to install.
-    package_req: Vec<PackageReq>,
+    /// Package or list of packages to install.{n}
+    /// Paths or URLs to `.rockspec`, `.src.rock` or `.rock` files are installed directly.{n}
+    /// Git repositories can be installed with `git+<url>[#<rev>]`.
+    package_req: Vec<InstallTarget>,
 
     /// Pin the packages so that they don't get updated.
     #[arg(long)]
@@ -21,6 +34,32 @@ pub struct Install {
     /// Reinstall without prompt if a package is already installed.
     #[arg(long)]
     force: bool,
+
+    /// Make the packages' executables globally available, with shims{n}
+    /// in the global bin directory (`~/.lux/bin` by default).
+    #[arg(long)]
+    global: bool,
+}
+
+#[derive(Debug, Clone)]
+enum InstallTarget {
+    Package(PackageReq),
+    RockFile(RockFile),
+    Git(GitPackage),
+}
+
+impl FromStr for InstallTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("git+") {
+            return s.parse().map(Self::Git).map_err(|err| err.to_string());
+        }
+        match RockFile::parse(s) {
+            Some(rock_file) => Ok(Self::RockFile(rock_file)),
+            None => s.parse().map(Self::Package).map_err(|err| err.to_string()),
+        }
+    }
 }
 
 /// Install a rock into the user tree.
@@ -30,15 +69,128 @@ pub async fn install(data: Install, config: Config) -> Result<()> {
     let lua_version = LuaVersion::from(&config)?.clone();
     let tree = config.user_tree(lua_version)?;
 
-    let packages = apply_build_behaviour(data.package_req, pin, data.force, &tree)?;
+    let mut package_reqs = Vec::new();
+    for target in data.package_req {
+        match target {
+            InstallTarget::Package(req) => package_reqs.push((req, None, None)),
+            InstallTarget::RockFile(rock_file) => {
+                let rock = rock_file.fetch(&config, &Progress::NoProgress).await?;
+                package_reqs.push((
+                    rock.package().into_package_req(),
+                    Some(rock_file.source_spec().clone()),
+                    Some(rock),
+                ));
+            }
+            InstallTarget::Git(git_package) => {
+                let (source, rock) = git_package.resolve(&config, &Progress::NoProgress).await?;
+                package_reqs.push((rock.package().into_package_req(), Some(source), Some(rock)));
+            }
+        }
+    }
+
+    let requested = package_reqs
+        .iter()
+        .map(|(req, _, _)| req.clone())
+        .collect_vec();
+    let packages = apply_build_behaviour(package_reqs, pin, data.force, &tree)?;
 
     // TODO(vhyrro): If the tree doesn't exist then error out.
-    operations::Install::new(&config)
-        .packages(packages)
-        .tree(tree)
+    let installed = match operations::Install::new(&config)
+        .packages(packages.clone())
+        .tree(tree.clone())
         .progress(MultiProgress::new_arc())
         .install()
-        .await?;
+        .await
+    {
+        Ok(installed) => installed,
+        Err(InstallError::FileConflicts(conflicts))
+            if config.file_conflicts() == FileConflictPolicy::Prompt =>
+        {
+            eprintln!("{conflicts}");
+            if !Confirm::new("Keep the files of the installed packages and continue?")
+                .with_default(false)
+                .prompt_or_default()?
+            {
+                return Err(conflicts.into());
+            }
+            let config = config
+                .clone()
+                .with_file_conflicts(FileConflictPolicy::FirstWins);
+            operations::Install::new(&config)
+                .packages(packages)
+                .tree(tree.clone())
+                .progress(MultiProgress::new_arc())
+                .install()
+                .await?
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if is_json_output() {
+        for package in &installed {
+            output::emit(
+                "installed",
+                json!({
+                    "name": package.name().to_string(),
+                    "version": package.version().to_string(),
+                }),
+            );
+        }
+    }
+
+    if data.global {
+        // Only the requested packages' executables are made available, not their dependencies'.
+        // Requested packages that were already installed are included.
+        let lockfile = tree.lockfile()?;
+        let mut requested_packages = Vec::new();
+        for req in &requested {
+            let ids = match tree.match_rocks(req)? {
+                RockMatches::Single(id) => vec![id],
+                RockMatches::Many(ids) => ids,
+                RockMatches::NotFound(_) => Vec::new(),
+            };
+            if let Some(package) = ids
+                .iter()
+                .filter_map(|id| lockfile.get(id))
+                .max_by(|a, b| a.version().cmp(b.version()))
+            {
+                requested_packages.push(package.clone());
+            }
+        }
+        let tools = write_global_tool_shims(&requested_packages, &tree, &config)?;
+        for tool in &tools {
+            if is_json_output() {
+                output::emit(
+                    "installed-tool",
+                    json!({
+                        "name": tool.name(),
+                        "package": tool.package().to_string(),
+                    }),
+                );
+            } else {
+                output::message(format!("Installed {} ({})", tool.name(), tool.package()));
+            }
+        }
+        let bin_dir = config.global_bin_dir();
+        if !BinPath::from_env().contains(bin_dir) {
+            eprintln!(
+                "⚠️ WARNING: {} is not on your PATH. Add it to use the installed tools.",
+                bin_dir.display()
+            );
+        }
+    }
 
     Ok(())
 }
+
+/// Install rocks into the user tree and make their executables globally available.
+pub async fn install_tools(data: Install, config: Config) -> Result<()> {
+    install(
+        Install {
+            global: true,
+            ..data
+        },
+        config,
+    )
+    .await
+}
@@ -1,20 +1,51 @@
+use clap::Args;
 use eyre::Result;
+use inquire::Confirm;
 use lux_lib::{
     config::{Config, LuaVersion},
     lua_installation::LuaInstallation,
     progress::{MultiProgress, ProgressBar},
 };
 
-pub async fn install_lua(config: Config) -> Result<()> {
-    let version_stringified = &LuaVersion::from(&config)?;
+use crate::utils::{output, prompt::PromptOrDefault};
+
+#[derive(Args)]
+pub struct InstallLua {
+    /// Reinstall Lua without prompting if it is already installed.
+    #[arg(long)]
+    force: bool,
+}
+
+pub async fn install_lua_cmd(args: InstallLua, config: Config) -> Result<()> {
+    let lua_version = LuaVersion::from(&config)?.clone();
+    install_lua(lua_version, args.force, config).await
+}
+
+pub async fn install_lua(lua_version: LuaVersion, force: bool, config: Config) -> Result<()> {
+    let version_stringified = &lua_version;
+
+    if let Some(lua) = LuaInstallation::find_installed(version_stringified, &config) {
+        let location = lua
+            .prefix()
+            .map(|prefix| prefix.display().to_string())
+            .unwrap_or_default();
+        output::message(format!(
+            "Lua {version_stringified} is already installed in {location}"
+        ));
+        if !force
+            && !Confirm::new("Reinstall?")
+                .with_default(false)
+                .prompt_or_default()?
+        {
+            return Ok(());
+        }
+    }
 
     let progress = MultiProgress::new();
     let bar = progress.add(ProgressBar::from(format!(
         "🌔 Installing Lua ({version_stringified})",
     )));
 
-    // TODO: Detect when path already exists by checking `Lua::path()` and prompt the user
-    // whether they'd like to forcefully reinstall.
     let lua = LuaInstallation::install(version_stringified, &config).await?;
     let lua_root = lua
         .includes()
@@ -1,9 +1,16 @@
-use crate::{completion::Completion, format::Fmt, project::NewProject};
+use crate::{
+    completion::Completion,
+    format::Fmt,
+    project::{InitProject, NewProject},
+};
 use std::error::Error;
 use std::path::PathBuf;
 
 use add::Add;
-use build::Build;
+use audit::Audit;
+use build::BuildCommand;
+use bundle::Bundle;
+use cache::CacheCmd;
 use check::Check;
 use clap::{Parser, Subcommand};
 use config::ConfigCmd;
@@ -11,67 +18,120 @@ use debug::Debug;
 use doc::Doc;
 use download::Download;
 use exec::Exec;
+use gc::Gc;
 use generate_rockspec::GenerateRockspec;
+use help::{Help, HelpMan};
+use import::Import;
+use index::Index;
 use info::Info;
 use install::Install;
+use install_lua::InstallLua;
 use install_rockspec::InstallRockspec;
+use license::License;
+use lint_rockspec::LintRockspec;
 use list::ListCmd;
+use login::{Login, Logout};
 use lux_lib::config::LuaVersion;
+use lux_lib::message::MessageFormat;
+use migrate_tree::MigrateTreeArgs;
+use mirror::Mirror;
 use outdated::Outdated;
 use pack::Pack;
 use path::Path;
 use pin::ChangePin;
+use release::Release;
 use remove::Remove;
+use rollback::Rollback;
 use run::Run;
 use run_lua::RunLua;
+use sbom::Sbom;
 use search::Search;
 use shell::Shell;
 use test::Test;
+use tool::ToolCmd;
+use toolchain::ToolchainCmd;
+use tree::Tree;
 use uninstall::Uninstall;
 use update::Update;
 use upload::Upload;
 use url::Url;
+use vendor::Vendor;
+use venv::VenvArgs;
+use verify::Verify;
+use version::Version;
 use which::Which;
+use why::Why;
 
 pub mod add;
+pub mod audit;
 pub mod build;
+pub mod bundle;
+pub mod cache;
 pub mod check;
 pub mod completion;
 pub mod config;
 pub mod debug;
 pub mod doc;
+pub mod doctor;
 pub mod download;
 pub mod exec;
 pub mod fetch;
 pub mod format;
+pub mod gc;
 pub mod generate_rockspec;
+pub mod help;
+pub mod import;
+pub mod index;
 pub mod info;
 pub mod install;
 pub mod install_lua;
 pub mod install_rockspec;
+pub mod license;
+pub mod lint_rockspec;
 pub mod list;
+pub mod login;
+pub mod migrate_tree;
+pub mod mirror;
 pub mod outdated;
 pub mod pack;
 pub mod path;
 pub mod pin;
 pub mod project;
 pub mod purge;
+pub mod release;
 pub mod remove;
+pub mod rollback;
 pub mod run;
 pub mod run_lua;
+pub mod sbom;
 pub mod search;
 pub mod shell;
 pub mod test;
+pub mod tool;
+pub mod toolchain;
+pub mod tree;
 pub mod uninstall;
 pub mod unpack;
 pub mod update;
 pub mod upload;
 pub mod utils;
+pub mod vendor;
+pub mod venv;
+pub mod verify;
+pub mod version;
 pub mod which;
+pub mod why;
 
 /// A luxurious package manager for Lua.
 #[derive(Parser)]
-#[command(author, version, about, long_about = None, arg_required_else_help = true)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    arg_required_else_help = true,
+    disable_help_subcommand = true
+)]
 pub struct Cli {
     /// Enable the sub-repositories in luarocks servers for rockspecs of in-development versions.
     #[arg(long)]
@@ -94,8 +154,9 @@ pub struct Cli {
     #[arg(long, value_name = "namespace")]
     pub namespace: Option<String>,
 
-    /// Specify the directory in which to install Lua{n}
-    /// if not found.
+    /// Specify the directory of a Lua installation to use.{n}
+    /// If it doesn't contain a compatible Lua installation,{n}
+    /// Lua is installed there if not found on the system.
     #[arg(long, value_name = "prefix")]
     pub lua_dir: Option<PathBuf>,
 
@@ -121,11 +182,26 @@ pub struct Cli {
     #[arg(long, value_name = "variable", visible_short_aliases = ['v'], value_parser = parse_key_val::<String, String>)]
     pub variables: Option<Vec<(String, String)>>,
 
-    /// Display verbose output of commands executed.
-    #[arg(long)]
-    pub verbose: bool,
+    /// Display verbose output of commands executed,{n}
+    /// and log each step above the progress bars.{n}
+    /// Also logs how long each step takes. Pass it twice for debug logs.{n}
+    /// The `LUX_LOG` environment variable overrides the log filter, e.g. `LUX_LOG=lux_lib=trace`.
+    #[arg(long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Write a debug log to this file, e.g. to attach it to a bug report.
+    #[arg(long, value_name = "path")]
+    pub log_file: Option<PathBuf>,
 
-    /// Configure lux for installing Neovim packages.
+    /// Don't show any progress. Warnings and errors are still printed.{n}
+    /// If stderr is not a terminal, progress is printed as plain log lines.
+    #[arg(long, short, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Configure lux for installing Neovim packages.{n}
+    /// Installs packages into Neovim's `site/pack` layout and uses luajit,{n}
+    /// unless a Lua 5.1 compatible version is set.{n}
+    /// Use `lx --nvim path nvim` to generate a snippet for your `init.lua`.
     #[arg(long)]
     pub nvim: bool,
 
@@ -134,10 +210,71 @@ pub struct Cli {
     #[arg(long, value_name = "seconds")]
     pub timeout: Option<usize>,
 
+    /// How often to retry failed downloads, with exponential backoff.{n}
+    /// Interrupted downloads are resumed, if the server supports it. Default is 3.
+    #[arg(long, value_name = "retries")]
+    pub retries: Option<usize>,
+
+    /// Maximum number of packages to build in parallel.{n}
+    /// 0 means one job per available CPU, which is the default.
+    #[arg(long, value_name = "jobs")]
+    pub jobs: Option<usize>,
+
     /// Whether to generate or update a `.luarc.json` file for the project.
     #[arg(long)]
     pub generate_luarc: bool,
 
+    /// Never access the network.{n}
+    /// Downloads are looked up in the project's `vendor` directory (see `lx vendor`){n}
+    /// and in the caches, failing if they are not found.
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Download the manifests from the servers,{n}
+    /// instead of revalidating the cached manifests.{n}
+    /// Other caches, e.g. of source archives and builds, are still used.
+    #[arg(long)]
+    pub refresh_manifests: bool,
+
+    /// Resolve dependencies to the lowest versions that satisfy all constraints,{n}
+    /// e.g. to verify that the declared lower bounds of a library actually work.{n}
+    /// Installed versions are not preferred in this mode.
+    #[arg(long)]
+    pub minimal_versions: bool,
+
+    /// Run the `make`, `cmake` and `command` build steps of packages in a sandbox,{n}
+    /// with a filtered environment and write access restricted to the build{n}
+    /// and install directories. Requires `bwrap` on Linux.{n}
+    /// See the `build_sandbox` config to allow more environment variables or deny network access.
+    #[arg(long)]
+    pub sandbox_builds: bool,
+
+    /// Never prompt for input.{n}
+    /// Prompts take their default answer, or fail if they don't have one.{n}
+    /// This can also be enabled by setting `LUX_NONINTERACTIVE=1`.
+    #[arg(long)]
+    pub no_input: bool,
+
+    /// Answer all confirmations with yes, and never prompt for input.{n}
+    /// Other prompts take their default answer, or fail if they don't have one.{n}
+    /// This can also be enabled by setting `LUX_ASSUME_YES=1`.
+    #[arg(long, short)]
+    pub yes: bool,
+
+    /// Print results and errors as JSON lines on stdout,{n}
+    /// for editor and CI integrations. Each line is an object{n}
+    /// with an `event` field, e.g. `installed`, `removed` or `error`.{n}
+    /// Human-readable messages and progress are printed to stderr.
+    #[arg(long)]
+    pub json: bool,
+
+    /// The format of the messages printed while downloading, building and installing rocks.{n}
+    /// With `json`, each event (download started/finished, build started,{n}
+    /// compiler output, install finished) is printed to stdout as a JSON object{n}
+    /// on its own line, with an `event` field that identifies its kind, as with `--json`.
+    #[arg(long, value_enum, value_name = "format")]
+    pub message_format: Option<MessageFormat>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -146,44 +283,143 @@ pub struct Cli {
 pub enum Commands {
     /// Add a dependency to the current project.
     Add(Add),
-    /// Build/compile a project.
-    Build(Build),
-    /// Runs `luacheck` in the current project.
+    /// Check the locked packages of the current project (or the user tree){n}
+    /// against a security advisory database, configured with the `advisory_db` option.{n}
+    /// Exits with an error if any vulnerable packages are found.
+    Audit(Audit),
+    /// Build/compile a project.{n}
+    /// With `--watch`, the project is rebuilt whenever its files change.
+    Build(BuildCommand),
+    /// Bundle the project, its dependencies and the Lua interpreter{n}
+    /// into a single self-contained executable.{n}
+    /// C modules are built as static libraries, in a separate tree.{n}
+    /// Only C modules built with the builtin build backend can be bundled.{n}
+    /// {n}
+    /// With `--format lua`, pure-Lua projects are amalgamated into a single Lua script{n}
+    /// that inlines the modules the entrypoint requires.
+    Bundle(Bundle),
+    /// Manage the cache of downloaded source archives, packed rocks and built rocks.
+    #[command(subcommand, arg_required_else_help = true)]
+    Cache(CacheCmd),
+    /// Lint the current project with `luacheck` (default) or `selene`.{n}
+    /// The linter's std is derived from the project's Lua version,{n}
+    /// unless the project has a `.luacheckrc` or `selene.toml`.
     Check(Check),
     /// Interact with the lux configuration.
     #[command(subcommand, arg_required_else_help = true)]
     Config(ConfigCmd),
     /// Generate autocompletion scripts for the shell.{n}
+    /// Except for nushell, the scripts call `lx` to complete{n}
+    /// the names of installed packages, e.g. for `lx uninstall`.{n}
     /// Example: `lx completion zsh > ~/.zsh/completions/_lx`
+    #[command(visible_alias = "completions")]
     Completion(Completion),
     /// Internal commands for debugging Lux itself.
     #[command(subcommand, arg_required_else_help = true)]
     Debug(Debug),
-    /// Show documentation for an installed rock.
+    /// Show documentation for an installed rock.{n}
+    /// Without a package, generates HTML documentation for the current project{n}
+    /// in `target/doc` using ldoc (installed on demand).{n}
+    /// If the project root contains a `config.ld`, ldoc uses it.
     Doc(Doc),
+    /// Check the toolchain (C compiler, Lua headers, git, unzip), the config{n}
+    /// and the consistency of the tree with its lockfile,{n}
+    /// and suggest fixes for the checks that fail.
+    Doctor,
     /// Download a specific rock file from a luarocks server.
     #[command(arg_required_else_help = true)]
     Download(Download),
-    /// Formats the codebase with stylua.
+    /// Formats the codebase with stylua.{n}
+    /// The stylua configuration is read from a `stylua.toml` or `.stylua.toml`{n}
+    /// in the project root, or from a `[stylua]` table in the `lux.toml`.{n}
+    /// Use `--check` in CI to fail if any files are not formatted.
     Fmt(Fmt),
-    /// Generate a rockspec file from a project.
+    /// Remove the rocks in the current project's tree (or the user tree){n}
+    /// that are not needed by any entrypoint in the lockfile,{n}
+    /// as well as rock directories that are not in the lockfile.{n}
+    /// Use `--dry-run` to see what would be removed.
+    Gc(Gc),
+    /// Generate a luarocks-compatible rockspec file from a project,{n}
+    /// including the build instructions and a source pinned to the current version{n}
+    /// (e.g. the current git tag or revision).
     GenerateRockspec(GenerateRockspec),
-    /// Show metadata for any rock.
+    /// Print the help for a command, or a guide to a help topic.{n}
+    /// Topics: `manifest` (the lux.toml format) and `lockfile` (the lux.lock format).
+    Help(Help),
+    /// Generate man pages from the command line definitions.{n}
+    /// Example: `lx help-man > lx.1`
+    HelpMan(HelpMan),
+    /// Convert a luarocks rockspec into a `lux.toml`,{n}
+    /// including its dependencies, description, source and build specification.
+    #[command(arg_required_else_help = true)]
+    Import(Import),
+    /// Generate luarocks-compatible manifests (and zipped variants){n}
+    /// for a directory of `.rockspec` and `.rock` files,{n}
+    /// so that it can be hosted as a rock server.{n}
+    /// Replaces `luarocks-admin make-manifest`.
+    Index(Index),
+    /// Generate a `lux.toml` for an existing codebase.{n}
+    /// Unlike `lx new`, this doesn't scaffold a new project, but detects{n}
+    /// the existing rockspec, source directory (`lua/`, `src/`), busted specs{n}
+    /// and C sources, and generates a `lux.toml` that matches them.
+    Init(InitProject),
+    /// Show metadata for any rock, including its dependencies{n}
+    /// and the versions available on the rock servers.{n}
+    /// Installed rocks are described by their installed rockspec.
     Info(Info),
-    /// Install a rock for use on the system.
+    /// Install a rock for use on the system.{n}
+    /// Also accepts local paths or URLs to `.rockspec`, `.src.rock` or `.rock` files,{n}
+    /// and git repositories as `git+<url>[#<rev>]`.
     #[command(arg_required_else_help = true)]
     Install(Install),
     /// Install a local rockspec for use on the system.
     #[command(arg_required_else_help = true)]
     InstallRockspec(InstallRockspec),
-    /// Manually install and manage Lua headers for various Lua versions.
-    InstallLua,
-    /// [UNIMPLEMENTED] Check syntax of a rockspec.
-    Lint,
-    /// List currently installed rocks.
+    /// Manually install and manage Lua headers for various Lua versions.{n}
+    /// Prompts before reinstalling an existing installation, unless `--force` is set.{n}
+    /// See also `lx toolchain`.
+    InstallLua(InstallLua),
+    /// Print the licenses of the locked packages of the current project{n}
+    /// (or the user tree), collected from their rockspecs.{n}
+    /// Fails if a package has a license that is forbidden by the `licenses`{n}
+    /// config table, e.g.:{n}
+    /// {n}
+    /// ```toml{n}
+    /// [licenses]{n}
+    /// allow = ["MIT", "Apache-2.0"] # Optional: only allow these licenses{n}
+    /// deny = ["GPL-3.0"]{n}
+    /// ```{n}
+    License(License),
+    /// Check a rockspec or `lux.toml` for schema errors, unknown fields,{n}
+    /// invalid versions and version constraints, non-SPDX licenses{n}
+    /// and missing source URLs.{n}
+    /// Each problem is reported with its line and column.{n}
+    /// Use `--json` for machine-readable diagnostics.
+    #[command(alias = "lint")]
+    LintRockspec(LintRockspec),
+    /// List the rocks installed in the user tree (or the project's tree, with `--project`),{n}
+    /// with their pinned and optional state and whether they are dependencies.{n}
+    /// Use `--format json` for scripts.
     List(ListCmd),
-    /// Run lua, with the `LUA_PATH` and `LUA_CPATH` set to the specified lux tree.
+    /// Store a token for a server in the OS keyring, or in a plaintext file{n}
+    /// if no keyring is available.{n}
+    /// The token is sent with requests to the server and used to upload packages to it.{n}
+    /// Reads the token from stdin if it isn't a terminal.
+    Login(Login),
+    /// Remove the token for a server that was stored with `lx login`.
+    Logout(Logout),
+    /// Run lua, with the `LUA_PATH` and `LUA_CPATH` set to the specified lux tree.{n}
+    /// Starts a REPL, or runs a script with `lx lua -- <script> [args]...`.{n}
+    /// In a project, the project is built first and its tree is used.
     Lua(RunLua),
+    /// Migrate the rocks installed in a luarocks tree to the lux user tree,{n}
+    /// recreating them and the lockfile without rebuilding them.
+    MigrateTree(MigrateTreeArgs),
+    /// Download a copy of the server's manifests, rockspecs and source rocks{n}
+    /// into a directory that can itself be served as a rock server,{n}
+    /// e.g. for machines without internet access.{n}
+    /// Running it again on the same directory only downloads new files.
+    Mirror(Mirror),
     /// Create a new Lua project.
     New(NewProject),
     /// List outdated rocks.
@@ -192,19 +428,55 @@ pub enum Commands {
     Pack(Pack),
     /// Return the currently configured package path.
     Path(Path),
-    /// Pin an existing rock, preventing any updates to the package.
+    /// Pin an existing rock, preventing any updates to the package.{n}
+    /// In a project, this pins the dependency in the lux.toml.{n}
+    /// Otherwise, the installed rock is pinned in place, without reinstalling it.
     Pin(ChangePin),
     /// Remove all installed rocks from a tree.
     Purge,
+    /// Release the current project in one step:{n}
+    /// bump the version, commit and tag it in git, add a heading for it to the{n}
+    /// `CHANGELOG.md`, write the rockspec, pack the source rock and upload it.{n}
+    /// Each stage can be skipped with a `--no-<stage>` flag.
+    Release(Release),
     /// Remove a rock from the current project's lux.toml dependencies.
     Remove(Remove),
-    /// Run the current project with the provided arguments.
+    /// Restore a previous version of the current project's lockfile{n}
+    /// and sync the project's trees with it.{n}
+    /// A snapshot of the lockfile is taken whenever it changes.
+    Rollback(Rollback),
+    /// Run the current project with the provided arguments.{n}
+    /// The command and arguments are configured in the lux.toml,{n}
+    /// optionally with named profiles:{n}
+    /// {n}
+    /// ```toml{n}
+    /// [run]{n}
+    /// args = ["src/main.lua"]{n}
+    /// {n}
+    /// [run.profiles.debug]{n}
+    /// args = ["src/main.lua", "--debug"]{n}
+    /// ```{n}
     Run(Run),
-    /// Execute a command that has been installed with lux.
-    /// If the command is not found, a package named after the command
-    /// will be installed.
+    /// Execute a command that has been installed with lux,{n}
+    /// or a script from the `[scripts]` table in the project's lux.toml.{n}
+    /// If the command is not found, a package named after the command{n}
+    /// will be installed.{n}
+    /// {n}
+    /// Example:{n}
+    /// {n}
+    /// ```toml{n}
+    /// [scripts]{n}
+    /// lint = "luacheck ."{n}
+    /// fmt = "stylua ."{n}
+    /// ```{n}
+    #[command(visible_alias = "x")]
     Exec(Exec),
-    /// Query the luarocks servers.
+    /// Generate a software bill of materials (SBOM) from the lockfile{n}
+    /// of the current project (or the user tree), including versions, hashes,{n}
+    /// licenses and source URLs, as CycloneDX or SPDX JSON.
+    Sbom(Sbom),
+    /// Search the configured rock servers for packages.{n}
+    /// Exact matches are listed first.
     #[command(arg_required_else_help = true)]
     Search(Search),
     /// Run the test suite in the current project directory.{n}
@@ -267,14 +539,42 @@ pub enum Commands {
     ///     script = "tests.lua" # Expects a tests.lua file in the project root{n}
     ///     flags = [ ] # Optional arguments passed to the test script{n}
     ///     ```{n}
+    /// {n}
+    /// The busted backends support filtering tests with `--filter`, `--tags`{n}
+    /// and `--exclude-tags`, and reporting the results with per-test durations{n}
+    /// and failure diagnostics as TAP, JUnit XML or JSON with `--output tap|junit|json`.{n}
+    /// {n}
+    /// With `--watch`, the tests are rerun whenever the project's files change.{n}
+    /// With `--lua-versions 5.1,5.4,jit`, the tests are run against each Lua version{n}
+    /// in a separate tree, followed by a summary of the results.{n}
     Test(Test),
-    /// Uninstall a rock from the system.
+    /// Manage globally available tools, i.e. rocks with executables{n}
+    /// that are installed into the user tree, with shims in the global bin directory{n}
+    /// (`~/.lux/bin` by default) that set up the paths for them.
+    #[command(subcommand, arg_required_else_help = true)]
+    Tool(ToolCmd),
+    /// Manage Lua toolchains: list, install and remove Lua versions,{n}
+    /// set the default version or pin the version of the current project{n}
+    /// in a `.lua-version` file.
+    #[command(subcommand, arg_required_else_help = true)]
+    Toolchain(ToolchainCmd),
+    /// Print the dependency tree of the current project's lockfile{n}
+    /// (or of the user tree, if not in a project).{n}
+    /// Use `--invert <package>` to show why a package is installed.
+    Tree(Tree),
+    /// Uninstall a rock from the system, along with the dependencies{n}
+    /// that are no longer needed by any other installed rock.{n}
+    /// Rocks that other rocks depend on are only uninstalled with `--force`.
     Uninstall(Uninstall),
-    /// Unpins an existing rock, allowing updates to alter the package.
+    /// Unpins an existing rock, allowing updates to alter the package.{n}
+    /// In a project, this unpins the dependency in the lux.toml.{n}
+    /// Otherwise, the installed rock is unpinned in place, without reinstalling it.
     Unpin(ChangePin),
     /// Updates all rocks in a project.
     Update(Update),
-    /// Generate a Lua rockspec for a Lux project and upload it to the public luarocks repository.{n}
+    /// Generate a Lua rockspec for a Lux project, pack it into a source rock{n}
+    /// and upload both to the configured luarocks server.{n}
+    /// The API key is read from `$LUX_API_KEY` or from the `api_key` config option.{n}
     /// You can specify a source template for release and dev packages in the lux.toml.{n}
     /// {n}
     /// Example:{n}
@@ -295,8 +595,29 @@ pub enum Commands {
     /// If the `version` is not set in the lux.toml, lux will search the current
     /// commit for SemVer tags and if found, will use it to generate the package version.
     Upload(Upload),
-    /// Tell which file corresponds to a given module name.
+    /// Download the rockspecs and sources of all packages in the project's lockfile{n}
+    /// into a `vendor` directory, so that they can be installed with `--offline`.
+    Vendor(Vendor),
+    /// Create an isolated environment with its own Lua installation and tree,{n}
+    /// along with scripts to activate it in bash/zsh, fish or PowerShell,{n}
+    /// e.g. `source .lux/activate`.{n}
+    /// While an environment is active, lux uses its Lua installation and tree.
+    Venv(VenvArgs),
+    /// Verify the installed rocks against the checksums recorded at install time,{n}
+    /// reporting rocks with modified or deleted files and orphaned rock directories.
+    Verify(Verify),
+    /// Bump the version in the current project's `lux.toml`{n}
+    /// by `major`, `minor` or `patch`, or set an explicit version.{n}
+    /// The new version must be newer than the versions published to the rock servers.{n}
+    /// With `--commit` and `--tag`, the change is committed and tagged in git.
+    Version(Version),
+    /// Tell which file corresponds to a given module name or binary.{n}
+    /// With `--owner`, also tell which package provides it.{n}
+    /// Searches the current project's tree, if in a project, and the user tree.
     Which(Which),
+    /// Explain why a package is installed, by printing the chains of dependencies{n}
+    /// and version constraints from the lockfile that lead to it.
+    Why(Why),
     /// Spawns an interactive shell with PATH, LUA_PATH, LUA_CPATH and LUA_INIT set.
     Shell(Shell),
 }
@@ -0,0 +1,93 @@
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::{license_policy::LicensePolicy, Config, LuaVersion},
+    operations::Licenses,
+    progress::MultiProgress,
+    project::Project,
+};
+
+use crate::utils::{output, project::locked_packages};
+
+#[derive(Args)]
+pub struct License {
+    /// Only allow these licenses (in addition to the `licenses.allow` config option).
+    #[arg(long, value_name = "license")]
+    allow: Vec<String>,
+
+    /// Forbid these licenses (in addition to the `licenses.deny` config option).
+    #[arg(long, value_name = "license")]
+    deny: Vec<String>,
+}
+
+/// Print the licenses of the locked packages of the current project (or the user tree),
+/// failing if any of them are forbidden by the license policy.
+pub async fn license(args: License, config: Config) -> Result<()> {
+    let packages = locked_packages(&config)?;
+    let trees = match Project::current()? {
+        Some(project) => {
+            let tree = project.tree(&config)?;
+            vec![tree.test_tree(&config)?, tree.build_tree(&config)?, tree]
+        }
+        None => vec![config.user_tree(LuaVersion::from(&config)?.clone())?],
+    };
+
+    let report = Licenses::new(&config)
+        .packages(packages)
+        .trees(trees)
+        .progress(MultiProgress::new_arc())
+        .collect()
+        .await?;
+
+    for package in report.packages() {
+        output::message(format!(
+            "{}@{}: {}",
+            package.package().name(),
+            package.package().version(),
+            package.license().unwrap_or("unknown")
+        ));
+    }
+    output::message("");
+    output::message("Summary:");
+    for (license, count) in report.summary() {
+        output::message(format!("  {}: {count}", license.unwrap_or("unknown")));
+    }
+
+    let config_policy = config.licenses();
+    let allow = match (config_policy.allow(), args.allow.is_empty()) {
+        (None, true) => None,
+        (allow, _) => Some(
+            allow
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .chain(args.allow)
+                .collect(),
+        ),
+    };
+    let deny = config_policy
+        .deny()
+        .iter()
+        .cloned()
+        .chain(args.deny)
+        .collect();
+    let policy = LicensePolicy::new(allow, deny);
+
+    let violations = report.violations(&policy);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    output::message("");
+    for package in &violations {
+        output::message(format!(
+            "forbidden license: {}@{} ({})",
+            package.package().name(),
+            package.package().version(),
+            package.license().unwrap_or("unknown")
+        ));
+    }
+    Err(eyre!(
+        "{} package(s) have forbidden licenses.",
+        violations.len()
+    ))
+}
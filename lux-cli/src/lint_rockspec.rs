@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::{
+    project::{Project, PROJECT_TOML},
+    rockspec::lint::{lint_project_toml, lint_rockspec as lint_rockspec_content},
+};
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
+
+#[derive(Args)]
+pub struct LintRockspec {
+    /// The rockspec or `lux.toml` to lint.{n}
+    /// Defaults to the current project's `lux.toml`.
+    file: Option<PathBuf>,
+}
+
+pub fn lint_rockspec(args: LintRockspec) -> Result<()> {
+    let path = match args.file {
+        Some(file) => file,
+        None => Project::current()?
+            .ok_or_else(|| eyre!("not in a lux project. Pass the rockspec or lux.toml to lint."))?
+            .toml_path(),
+    };
+    let content = std::fs::read_to_string(&path)?;
+    let report = if is_project_toml(&path) {
+        lint_project_toml(&content)
+    } else {
+        lint_rockspec_content(&content)
+    };
+
+    for diagnostic in report.diagnostics() {
+        if is_json_output() {
+            let position = diagnostic.position();
+            output::emit(
+                "diagnostic",
+                json!({
+                    "file": path,
+                    "severity": diagnostic.severity().to_string(),
+                    "code": diagnostic.code(),
+                    "message": diagnostic.message(),
+                    "line": position.map(|position| position.line),
+                    "column": position.map(|position| position.column),
+                }),
+            );
+        } else {
+            output::message(format!("{}:{diagnostic}", path.display()));
+        }
+    }
+
+    if !report.is_ok() {
+        return Err(eyre!("{} is invalid.", path.display()));
+    }
+    if report.diagnostics().is_empty() {
+        output::message(format!("{}: no problems found.", path.display()));
+    }
+    Ok(())
+}
+
+fn is_project_toml(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == PROJECT_TOML)
+        || path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+}
@@ -1,43 +1,141 @@
-use clap::Args;
-use eyre::Result;
+use std::collections::HashMap;
+
+use clap::{Args, ValueEnum};
+use eyre::{eyre, Result};
 use itertools::Itertools as _;
 use lux_lib::{
     config::{Config, LuaVersion},
-    lockfile::PinnedState,
+    lockfile::{OptState, PinnedState},
+    project::Project,
 };
+use serde_json::json;
 use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
 
+use crate::utils::output::{self, is_json_output, print_json};
+
 #[derive(Args)]
 pub struct ListCmd {
+    /// Only list packages whose name contains this string.
+    filter: Option<String>,
+
+    /// List the packages in the user tree. This is the default.
+    #[arg(long, conflicts_with = "project")]
+    global: bool,
+
+    /// List the packages in the current project's tree instead of the user tree.
+    #[arg(long)]
+    project: bool,
+
+    /// Only list the packages that were installed explicitly.
+    #[arg(long, conflicts_with = "dependencies")]
+    entrypoints: bool,
+
+    /// Only list the packages that were installed as dependencies.
+    #[arg(long)]
+    dependencies: bool,
+
+    /// Only list pinned packages.
     #[arg(long)]
+    pinned: bool,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+    format: ListFormat,
+
+    /// Print the listed packages as JSON, grouped by name.
+    #[arg(long, conflicts_with = "format")]
     porcelain: bool,
 }
 
-/// List rocks that are installed in the user tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListFormat {
+    /// The versions of each package, as a tree.
+    Text,
+    /// A JSON array of packages.
+    Json,
+}
+
+/// List rocks that are installed in the user tree or the current project's tree
 pub fn list_installed(list_data: ListCmd, config: Config) -> Result<()> {
-    let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
+    let tree = if list_data.project {
+        Project::current()?
+            .ok_or_else(|| eyre!("not in a lux project directory"))?
+            .tree(&config)?
+    } else {
+        config.user_tree(LuaVersion::from(&config)?.clone())?
+    };
     let available_rocks = tree.list()?;
 
-    if list_data.porcelain {
-        println!("{}", serde_json::to_string(&available_rocks)?);
+    let lockfile = tree.lockfile()?;
+    let packages = available_rocks
+        .into_values()
+        .flatten()
+        .filter(|package| match &list_data.filter {
+            Some(filter) => package.name().to_string().contains(filter),
+            None => true,
+        })
+        .filter(|package| {
+            let is_entrypoint = lockfile.is_entrypoint(&package.id());
+            (!list_data.entrypoints || is_entrypoint) && (!list_data.dependencies || !is_entrypoint)
+        })
+        .filter(|package| !list_data.pinned || package.pinned() == PinnedState::Pinned)
+        .sorted_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())))
+        .collect_vec();
+
+    if list_data.porcelain && !is_json_output() {
+        let packages: HashMap<_, Vec<_>> = packages
+            .into_iter()
+            .into_group_map_by(|package| package.name().clone());
+        output::message(serde_json::to_string(&packages)?);
+        return Ok(());
+    }
+
+    let format = if is_json_output() {
+        ListFormat::Json
     } else {
-        let formatting = TreeFormatting::dir_tree(FormatCharacters::box_chars());
-        for (name, packages) in available_rocks.into_iter().sorted() {
-            let mut tree = StringTreeNode::new(name.to_string());
-
-            for package in packages {
-                tree.push(format!(
-                    "{}{}",
-                    package.version(),
+        list_data.format
+    };
+    match format {
+        ListFormat::Text => {
+            let formatting = TreeFormatting::dir_tree(FormatCharacters::box_chars());
+            for (name, packages) in &packages.iter().chunk_by(|package| package.name()) {
+                let mut tree = StringTreeNode::new(name.to_string());
+
+                for package in packages {
+                    let mut labels = Vec::new();
                     if package.pinned() == PinnedState::Pinned {
-                        " (pinned)"
+                        labels.push("pinned");
+                    }
+                    if package.opt() == OptState::Optional {
+                        labels.push("optional");
+                    }
+                    if !lockfile.is_entrypoint(&package.id()) {
+                        labels.push("dependency");
+                    }
+                    if labels.is_empty() {
+                        tree.push(package.version().to_string());
                     } else {
-                        ""
+                        tree.push(format!("{} ({})", package.version(), labels.join(", ")));
                     }
-                ));
-            }
+                }
 
-            println!("{}", tree.to_string_with_format(&formatting)?);
+                output::message(tree.to_string_with_format(&formatting)?);
+            }
+        }
+        ListFormat::Json => {
+            let packages = packages
+                .iter()
+                .map(|package| {
+                    json!({
+                        "name": package.name().to_string(),
+                        "version": package.version().to_string(),
+                        "pinned": package.pinned() == PinnedState::Pinned,
+                        "optional": package.opt() == OptState::Optional,
+                        "entrypoint": lockfile.is_entrypoint(&package.id()),
+                    })
+                })
+                .collect_vec();
+            print_json("package", packages)?;
         }
     }
 
@@ -0,0 +1,90 @@
+use std::io::{IsTerminal, Read};
+
+use clap::Args;
+use eyre::{eyre, Result};
+use inquire::{Password, PasswordDisplayMode};
+use lux_lib::config::{credentials::CredentialsLocation, Config};
+use serde_json::json;
+use url::Url;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    prompt::PromptOrDefault,
+};
+
+#[derive(Args)]
+pub struct Login {
+    /// The server to log in to.{n}
+    /// Defaults to the configured server.
+    server: Option<Url>,
+}
+
+#[derive(Args)]
+pub struct Logout {
+    /// The server to log out of.{n}
+    /// Defaults to the configured server.
+    server: Option<Url>,
+}
+
+/// Store a token for a server, which is read from stdin if it isn't a terminal.
+pub fn login(args: Login, config: Config) -> Result<()> {
+    let server = args.server.unwrap_or_else(|| config.server().clone());
+    let token = if std::io::stdin().is_terminal() {
+        Password::new(&format!("Token for {server}:"))
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .prompt_or_default()?
+    } else {
+        let mut token = String::new();
+        std::io::stdin().read_to_string(&mut token)?;
+        token
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(eyre!("no token provided for {server}"));
+    }
+    let location = config.credentials().store(&server, token)?;
+
+    if is_json_output() {
+        let (keyring, file) = match &location {
+            CredentialsLocation::Keyring => (true, None),
+            CredentialsLocation::File(path) => (false, Some(path)),
+        };
+        output::emit(
+            "login",
+            json!({ "server": server.as_str(), "keyring": keyring, "file": file }),
+        );
+    } else {
+        match location {
+            CredentialsLocation::Keyring => {
+                output::message(format!("Stored the token for {server} in the keyring."))
+            }
+            CredentialsLocation::File(path) => {
+                eprintln!("⚠️ WARNING: No keyring is available.");
+                output::message(format!(
+                    "Stored the token for {server} in {}.",
+                    path.display()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove the stored token for a server.
+pub fn logout(args: Logout, config: Config) -> Result<()> {
+    let server = args.server.unwrap_or_else(|| config.server().clone());
+    let removed = config.credentials().remove(&server)?;
+
+    if is_json_output() {
+        output::emit(
+            "logout",
+            json!({ "server": server.as_str(), "removed": removed }),
+        );
+    } else if removed {
+        output::message(format!("Removed the token for {server}."));
+    } else {
+        output::message(format!("No token stored for {server}."));
+    }
+    Ok(())
+}
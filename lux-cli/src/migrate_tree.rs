@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    config::{Config, LuaVersion},
+    operations::MigrateTree,
+    progress::MultiProgress,
+};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct MigrateTreeArgs {
+    /// The root of the luarocks tree, e.g. `~/.luarocks` or `/usr/local`.
+    path: PathBuf,
+}
+
+/// Install the rocks from a luarocks tree into the user tree.
+pub async fn migrate_tree(data: MigrateTreeArgs, config: Config) -> Result<()> {
+    let lua_version = LuaVersion::from(&config)?.clone();
+    let tree = config.user_tree(lua_version)?;
+
+    let packages = MigrateTree::new(&data.path, tree, &config)
+        .progress(MultiProgress::new_arc())
+        .migrate()
+        .await?;
+
+    output::message(format!(
+        "Migrated {} rocks from {}",
+        packages.len(),
+        data.path.display()
+    ));
+
+    Ok(())
+}
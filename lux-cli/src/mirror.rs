@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::{config::Config, operations, package::PackageReq, progress::MultiProgress};
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
+
+#[derive(Args)]
+pub struct Mirror {
+    /// The directory to mirror the server into.{n}
+    /// It can be used as a rock server with a `file://` URL,{n}
+    /// e.g. `--server file:///path/to/dir`, or served by a static file server.
+    dir: PathBuf,
+
+    /// Only mirror these packages, e.g. `foo` or `"foo >= 1.0"`.{n}
+    /// If omitted, all packages on the server are mirrored.
+    packages: Vec<PackageReq>,
+
+    /// Only mirror the latest (matching) version of each package.
+    #[arg(long)]
+    latest: bool,
+
+    /// Also mirror binary rocks.{n}
+    /// By default, only rockspecs and source rocks are mirrored.
+    #[arg(long)]
+    binaries: bool,
+}
+
+/// Download a copy of the server's manifests and rocks into a directory.
+pub async fn mirror(args: Mirror, config: Config) -> Result<()> {
+    let report = operations::Mirror::new(args.dir.clone(), &config)
+        .packages(args.packages)
+        .latest(args.latest)
+        .binaries(args.binaries)
+        .progress(MultiProgress::new_arc())
+        .mirror()
+        .await?;
+
+    if is_json_output() {
+        output::emit(
+            "mirror",
+            json!({
+                "dir": args.dir,
+                "server": config.server().to_string(),
+                "packages": report.packages(),
+                "downloaded": report.downloaded(),
+                "skipped": report.skipped(),
+                "failed": report
+                    .failed()
+                    .iter()
+                    .map(|(file, error)| json!({ "file": file, "error": error }))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+    } else {
+        for (file, error) in report.failed() {
+            eprintln!("Failed to mirror {file}: {error}");
+        }
+        output::message(format!(
+            "Mirrored {} packages from {} into {} ({} files downloaded, {} already mirrored).",
+            report.packages(),
+            config.server(),
+            args.dir.display(),
+            report.downloaded(),
+            report.skipped(),
+        ));
+    }
+
+    if report.failed().is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "{} files could not be mirrored and were left out of the manifests",
+            report.failed().len()
+        ))
+    }
+}
@@ -9,9 +9,13 @@ use lux_lib::{
     project::Project,
     remote_package_db::RemotePackageDB,
 };
+use serde_json::json;
 use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
 
-use crate::utils::project::sync_dependencies_if_locked;
+use crate::utils::{
+    output::{self, is_json_output},
+    project::sync_dependencies_if_locked,
+};
 
 #[derive(Args)]
 pub struct Outdated {
@@ -59,7 +63,18 @@ pub async fn outdated(outdated_data: Outdated, config: Config) -> Result<()> {
 
     bar.map(|b| b.finish_and_clear());
 
-    if outdated_data.porcelain {
+    if is_json_output() {
+        for (rock, latest_version) in rock_list.values().flatten() {
+            output::emit(
+                "outdated",
+                json!({
+                    "name": rock.name().to_string(),
+                    "version": rock.version().to_string(),
+                    "latest": latest_version.to_string(),
+                }),
+            );
+        }
+    } else if outdated_data.porcelain {
         let jsonified_rock_list = rock_list
             .iter()
             .map(|(key, values)| {
@@ -73,7 +88,7 @@ pub async fn outdated(outdated_data: Outdated, config: Config) -> Result<()> {
             })
             .collect::<HashMap<_, _>>();
 
-        println!("{}", serde_json::to_string(&jsonified_rock_list)?);
+        output::message(serde_json::to_string(&jsonified_rock_list)?);
     } else {
         let formatting = TreeFormatting::dir_tree(FormatCharacters::box_chars());
 
@@ -84,7 +99,7 @@ pub async fn outdated(outdated_data: Outdated, config: Config) -> Result<()> {
                 tree.push(format!("{} => {}", rock.version(), latest_version));
             }
 
-            println!("{}", tree.to_string_with_format(&formatting)?);
+            output::message(tree.to_string_with_format(&formatting)?);
         }
     }
 
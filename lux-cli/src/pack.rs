@@ -1,6 +1,9 @@
 use std::{path::PathBuf, str::FromStr};
 
-use crate::build;
+use crate::{
+    build,
+    utils::{output, source_metadata::try_populate_source},
+};
 use clap::Args;
 use eyre::{eyre, Result};
 use lux_lib::{
@@ -60,6 +63,11 @@ pub struct Pack {
     /// Lua rockspec.{n}
     #[clap(value_parser)]
     package_or_rockspec: Option<PackageOrRockspec>,
+
+    /// Pack the current project's sources and generated rockspec into a `.src.rock`,{n}
+    /// instead of building it and packing the binaries.
+    #[arg(long, conflicts_with = "package_or_rockspec")]
+    src: bool,
 }
 
 pub async fn pack(args: Pack, config: Config) -> Result<()> {
@@ -134,8 +142,13 @@ pub async fn pack(args: Pack, config: Config) -> Result<()> {
                 .await?;
             Ok(rock_path)
         }
+        None if args.src => {
+            let project = try_populate_source(Project::current_or_err()?);
+            let rock_path = operations::PackSrc::new(dest_dir, &project).pack().await?;
+            Ok(rock_path)
+        }
         None => {
-            let project = Project::current_or_err()?;
+            let project = try_populate_source(Project::current_or_err()?);
             // luarocks expects a `<package>-<version>.rockspec` in the package root,
             // so we add a guard that it can be created here.
             project
@@ -152,6 +165,6 @@ pub async fn pack(args: Pack, config: Config) -> Result<()> {
             Ok(rock_path)
         }
     };
-    print!("packed rock created at {}", result?.display());
+    output::message(format!("packed rock created at {}", result?.display()));
     Ok(())
 }
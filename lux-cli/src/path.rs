@@ -8,7 +8,7 @@ use strum_macros::{Display, EnumString, VariantNames};
 
 use clap::{Args, ValueEnum};
 
-use crate::utils::project::current_project_or_user_tree;
+use crate::utils::{output, project::current_project_or_user_tree};
 
 #[derive(Args)]
 pub struct Path {
@@ -39,6 +39,9 @@ enum PathCmd {
     /// Generate a `LUA_INIT` expression for the lux loader.
     /// (not formatted as a shell command)
     Init,
+    /// Generate a snippet for Neovim's `init.lua`, which sets up `package.path`,
+    /// `package.cpath` and the `packpath`.
+    Nvim,
 }
 
 impl Default for PathCmd {
@@ -73,6 +76,7 @@ enum Shell {
     Posix,
     Fish,
     Nu,
+    Powershell,
 }
 
 impl Default for Shell {
@@ -124,12 +128,17 @@ To suppress this warning, set the `--no-loader` option.
                 result.push_str(format_export(&shell, "LUA_INIT", &paths.init()).as_str());
                 result.push('\n')
             }
-            println!("{}", &result);
+            output::message(&result);
         }
-        PathCmd::Lua => println!("{}", &mk_package_path(&paths, prepend)),
-        PathCmd::C => println!("{}", &mk_package_cpath(&paths, prepend)),
-        PathCmd::Bin => println!("{}", &mk_bin_path(&paths, prepend)?),
-        PathCmd::Init => println!("{}", paths.init()),
+        PathCmd::Lua => output::message(mk_package_path(&paths, prepend)),
+        PathCmd::C => output::message(mk_package_cpath(&paths, prepend)),
+        PathCmd::Bin => output::message(mk_bin_path(&paths, prepend)?),
+        PathCmd::Init => output::message(paths.init()),
+        PathCmd::Nvim => output::message(
+            paths
+                .nvim_init(tree.nvim_packpath().as_deref(), prepend)
+                .trim_end(),
+        ),
     }
     Ok(())
 }
@@ -168,5 +177,6 @@ where
         Shell::Posix => format!("export {var_name}='{var}';"),
         Shell::Fish => format!("set -x {var_name} \"{var}\";"),
         Shell::Nu => format!("$env.{var_name} = \"{var}\";"),
+        Shell::Powershell => format!("$env:{var_name} = '{var}';"),
     }
 }
@@ -1,4 +1,5 @@
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 use eyre::eyre;
 use eyre::Context;
 use eyre::Result;
@@ -13,11 +14,15 @@ use lux_lib::project::Project;
 use lux_lib::rockspec::lua_dependency;
 use lux_lib::tree::RockMatches;
 
+use crate::completion::installed_packages;
+use crate::utils::output;
+
 #[derive(Args)]
 pub struct ChangePin {
     /// Installed package or dependency to pin.
     /// If pinning a dependency in a project, this should
     /// be the package name.
+    #[arg(add = ArgValueCompleter::new(installed_packages))]
     package: Vec<PackageReq>,
 
     /// Pin a development dependency.
@@ -28,10 +33,20 @@ pub struct ChangePin {
     /// Pin a test dependency.
     #[arg(short, long)]
     test: Option<Vec<PackageName>>,
+
+    /// Change the pin state of rocks in the user tree,{n}
+    /// even if in a project.
+    #[arg(long, conflicts_with_all = ["build", "test"])]
+    global: bool,
 }
 
 pub async fn set_pinned_state(data: ChangePin, config: Config, pin: PinnedState) -> Result<()> {
-    match Project::current()? {
+    let project = if data.global {
+        None
+    } else {
+        Project::current()?
+    };
+    match project {
         Some(mut project) => {
             let progress = MultiProgress::new_arc();
             if data.package.iter().any(|pkg| !pkg.version_req().is_any()) {
@@ -89,8 +104,21 @@ pub async fn set_pinned_state(data: ChangePin, config: Config, pin: PinnedState)
                     RockMatches::Single(rock) => {
                         operations::set_pinned_state(&rock, &tree, pin)?;
                     }
-                    RockMatches::Many(_) => {
-                        todo!("Add an error here about many conflicting types and to use `all:`")
+                    // Change the pin state of all matching versions
+                    RockMatches::Many(rocks) => {
+                        for rock in rocks {
+                            operations::set_pinned_state(&rock, &tree, pin)?;
+                        }
+                    }
+                    RockMatches::NotFound(_) if tree.match_rocks(package)?.is_found() => {
+                        output::message(format!(
+                            "{} is already {}",
+                            package,
+                            match pin {
+                                PinnedState::Pinned => "pinned",
+                                PinnedState::Unpinned => "unpinned",
+                            }
+                        ));
                     }
                     RockMatches::NotFound(_) => return Err(eyre!("Rock {} not found!", package)),
                 }
@@ -1,8 +1,8 @@
 use clap::Args;
 use eyre::Result;
-use lux_lib::project::Project;
+use lux_lib::{config::Config, project::Project};
 
-use crate::utils::file_tree::term_tree_from_paths;
+use crate::utils::{file_tree::term_tree_from_paths, output};
 
 #[derive(Args)]
 pub struct DebugProject {
@@ -12,26 +12,39 @@ pub struct DebugProject {
     /// (e.g. by .gitignore or other .ignore files).
     #[arg(long)]
     list_files: bool,
+
+    /// Print the parsed and validated lux.toml.
+    #[arg(long)]
+    parsed: bool,
 }
 
-pub fn debug_project(args: DebugProject) -> Result<()> {
+pub fn debug_project(args: DebugProject, config: Config) -> Result<()> {
     let project = Project::current()?;
 
     if let Some(project) = project {
         let toml = project.toml();
 
-        println!("Project name: {}", toml.package());
-        println!("Project version: {}", toml.version()?);
+        output::message(format!("Project name: {}", toml.package()));
+        output::message(format!("Project version: {}", toml.version()?));
 
-        println!("Project location: {}", project.root().display());
+        output::message(format!("Project location: {}", project.root().display()));
+        output::message(format!("Lockfile: {}", project.lockfile_path().display()));
+        output::message(format!(
+            "Tree root: {}",
+            project.tree(&config)?.root().display()
+        ));
+
+        if args.parsed {
+            output::message(format!("\n{:#?}", toml.into_local()?));
+        }
 
         if args.list_files {
             let project_files = project.project_files();
             if project_files.is_empty() {
-                println!("\nNo included project files detected.");
+                output::message("\nNo included project files detected.");
             } else {
                 let project_tree = term_tree_from_paths(&project_files);
-                println!("\nIncluded project files:\n\n{project_tree}.");
+                output::message(format!("\nIncluded project files:\n\n{project_tree}."));
             }
         }
     } else {
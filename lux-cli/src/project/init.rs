@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::Config,
+    project::{
+        init::{init_project_toml, InitDescription, InitProjectError, ProjectLayout},
+        PROJECT_TOML,
+    },
+};
+use serde_json::json;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    source_metadata::{self, RepoMetadata},
+};
+
+#[derive(Args)]
+pub struct InitProject {
+    /// The directory of the existing codebase.{n}
+    /// Defaults to the current directory.
+    dir: Option<PathBuf>,
+
+    /// The project's name.{n}
+    /// Defaults to the name of the repository.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Overwrite an existing `lux.toml`.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Generate a `lux.toml` that matches the layout of an existing codebase.
+pub async fn init_project(args: InitProject, config: Config) -> Result<()> {
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let path = dir.join(PROJECT_TOML);
+    if path.exists() && !args.force {
+        return Err(eyre!(
+            "{} already exists. Use --force to overwrite it.",
+            path.display()
+        ));
+    }
+
+    let repo_metadata = match source_metadata::get_metadata_for(Some(&dir), &config).await {
+        Ok(Some(repo_metadata)) => repo_metadata,
+        Ok(None) | Err(_) => RepoMetadata::default(&dir)?,
+    };
+    let name = args.name.unwrap_or(repo_metadata.name);
+    let description = InitDescription {
+        summary: repo_metadata.description,
+        license: repo_metadata.license,
+        maintainer: repo_metadata.contributors.first().cloned(),
+        labels: repo_metadata.labels.unwrap_or_default(),
+    };
+
+    let mut layout = ProjectLayout::detect(&dir);
+    let content = match init_project_toml(&name, &layout, &description) {
+        Err(InitProjectError::ImportRockspec(rockspec, err)) => {
+            eprintln!(
+                "⚠️ WARNING: Could not import {}: {err}\nDetecting the project layout instead.",
+                rockspec.display()
+            );
+            layout.rockspec = None;
+            init_project_toml(&name, &layout, &description)?
+        }
+        result => result?,
+    };
+    std::fs::write(&path, content)?;
+
+    if is_json_output() {
+        output::emit(
+            "init",
+            json!({
+                "path": path,
+                "rockspec": layout.rockspec,
+                "source_dir": layout.source_dir,
+                "busted": layout.busted,
+                "c_sources": layout.c_sources,
+            }),
+        );
+        return Ok(());
+    }
+    if let Some(rockspec) = &layout.rockspec {
+        output::message(format!("Imported {}", rockspec.display()));
+    } else {
+        if let Some(source_dir) = &layout.source_dir {
+            output::message(format!("Lua modules: {}/", source_dir.display()));
+        }
+        if !layout.root_modules.is_empty() {
+            output::message(format!(
+                "Lua modules: {} in the project root",
+                layout.root_modules.len()
+            ));
+        }
+        if !layout.c_sources.is_empty() {
+            output::message(format!("C modules: {}", layout.c_sources.len()));
+        }
+    }
+    if layout.busted {
+        output::message("Busted specs: spec/");
+    }
+    output::message(format!("Wrote {}", path.display()));
+    Ok(())
+}
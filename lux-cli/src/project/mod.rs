@@ -1,5 +1,8 @@
 mod debug;
+mod init;
 mod new;
+mod scaffold;
 
 pub use debug::*;
+pub use init::*;
 pub use new::*;
@@ -1,5 +1,6 @@
 use std::{error::Error, fmt::Display, path::PathBuf, str::FromStr};
 
+use chrono::Datelike;
 use clap::Args;
 use eyre::{eyre, Result};
 use inquire::{
@@ -11,10 +12,19 @@ use itertools::Itertools;
 use spdx::LicenseId;
 use spinners::{Spinner, Spinners};
 
-use crate::utils::github_metadata::{self, RepoMetadata};
+use super::scaffold::{self, CiProvider};
+use crate::utils::{
+    output,
+    picker::pick_dependencies,
+    prompt::{is_interactive, PromptOrDefault},
+    source_metadata::{self, RepoMetadata},
+};
 use lux_lib::{
+    config::Config,
     package::PackageReq,
+    progress::{Progress, ProgressBar},
     project::{Project, PROJECT_TOML},
+    remote_package_db::RemotePackageDB,
 };
 
 // TODO:
@@ -70,6 +80,15 @@ pub struct NewProject {
 
     #[arg(long)]
     main: Option<SourceDirType>,
+
+    /// Generate a CI pipeline that builds and tests the project.
+    #[arg(long)]
+    ci: Option<CiProvider>,
+
+    /// Generate `.gitignore`, `.editorconfig` and `.busted` files.{n}
+    /// If not set, you will be asked whether to generate them, unless prompts are disabled.
+    #[arg(long)]
+    dotfiles: bool,
 }
 
 struct NewProjectValidated {
@@ -81,6 +100,9 @@ struct NewProjectValidated {
     lua_versions: PackageReq,
     main: SourceDirType,
     license: Option<LicenseId>,
+    dependencies: Vec<PackageReq>,
+    dotfiles: bool,
+    ci: CiProvider,
 }
 
 fn clap_parse_license(s: &str) -> std::result::Result<LicenseId, String> {
@@ -119,6 +141,30 @@ fn parse_license_unchecked(input: &str) -> LicenseId {
     spdx::imprecise_license_id(input).unwrap().0
 }
 
+/// The placeholders for the copyright year in the SPDX license texts.
+const LICENSE_YEAR_PLACEHOLDERS: [&str; 3] = ["<year>", "[yyyy]", "<yyyy>"];
+
+/// The placeholders for the copyright holder in the SPDX license texts.
+const LICENSE_HOLDER_PLACEHOLDERS: [&str; 5] = [
+    "<copyright holders>",
+    "<owner>",
+    "<name of author>",
+    "[name of copyright owner]",
+    "<author>",
+];
+
+/// The text of a license, with the copyright year and holder filled in.
+fn license_text(license: LicenseId, holder: &str, year: i32) -> String {
+    let text = LICENSE_YEAR_PLACEHOLDERS
+        .iter()
+        .fold(license.text().to_string(), |text, placeholder| {
+            text.replace(placeholder, &year.to_string())
+        });
+    LICENSE_HOLDER_PLACEHOLDERS
+        .iter()
+        .fold(text, |text, placeholder| text.replace(placeholder, holder))
+}
+
 fn validate_license(input: &str) -> std::result::Result<Validation, Box<dyn Error + Send + Sync>> {
     if input == "none" {
         return Ok(Validation::Valid);
@@ -134,7 +180,7 @@ fn validate_license(input: &str) -> std::result::Result<Validation, Box<dyn Erro
     )
 }
 
-pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
+pub async fn write_project_rockspec(cli_flags: NewProject, config: Config) -> Result<()> {
     let project = Project::from_exact(cli_flags.target.clone())?;
     let render_config = RenderConfig::default_colored()
         .with_prompt_prefix(Styled::new(">").with_fg(inquire::ui::Color::LightGreen));
@@ -145,7 +191,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             .with_default(false)
             .with_help_message(&format!("This may overwrite your existing {PROJECT_TOML}",))
             .with_render_config(render_config)
-            .prompt()?
+            .prompt_or_default()?
     {
         return Err(eyre!("cancelled creation of project (already exists)"));
     };
@@ -161,6 +207,8 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             name: Some(name),
             license,
             target,
+            ci,
+            dotfiles,
         } => Ok::<_, eyre::Report>(NewProjectValidated {
             description,
             labels,
@@ -170,6 +218,9 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             maintainer,
             name,
             target,
+            dependencies: Vec::new(),
+            dotfiles,
+            ci: ci.unwrap_or(CiProvider::None),
         }),
 
         NewProject {
@@ -181,20 +232,25 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             maintainer,
             name,
             target,
+            ci,
+            dotfiles,
         } => {
             let mut spinner = Spinner::new(
                 Spinners::Dots,
                 "Fetching remote repository metadata... ".into(),
             );
 
-            let repo_metadata = match github_metadata::get_metadata_for(Some(&target)).await {
-                Ok(value) => value.map_or_else(|| RepoMetadata::default(&target), Ok),
-                Err(_) => {
-                    println!("Could not fetch remote repo metadata, defaulting to empty values.");
+            let repo_metadata =
+                match source_metadata::get_metadata_for(Some(&target), &config).await {
+                    Ok(value) => value.map_or_else(|| RepoMetadata::default(&target), Ok),
+                    Err(_) => {
+                        output::message(
+                            "Could not fetch remote repo metadata, defaulting to empty values.",
+                        );
 
-                    RepoMetadata::default(&target)
-                }
-            }?;
+                        RepoMetadata::default(&target)
+                    }
+                }?;
 
             spinner.stop_and_persist("✔", "Fetched remote repository metadata.".into());
 
@@ -204,7 +260,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                         .with_default(&repo_metadata.name)
                         .with_help_message("A folder with the same name will be created for you.")
                         .with_render_config(render_config)
-                        .prompt()
+                        .prompt_or_default()
                 },
                 Ok,
             )?;
@@ -214,7 +270,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                     Text::new("Description:")
                         .with_default(&repo_metadata.description.unwrap_or_default())
                         .with_render_config(render_config)
-                        .prompt()
+                        .prompt_or_default()
                 },
                 Ok,
             )?;
@@ -227,7 +283,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                             .with_help_message("Type 'none' for no license")
                             .with_validator(validate_license)
                             .with_render_config(render_config)
-                            .prompt()?
+                            .prompt_or_default()?
                             .as_str()
                         {
                             "none" => None,
@@ -242,11 +298,13 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                 || {
                     Ok::<_, eyre::Error>(
                         Text::new("Labels:")
+                            .with_default("")
                             .with_placeholder("web,filesystem")
                             .with_help_message("Labels are comma separated")
-                            .prompt()?
+                            .prompt_or_default()?
                             .split(',')
                             .map(|label| label.trim().to_string())
+                            .filter(|label| !label.is_empty())
                             .collect_vec(),
                     )
                 },
@@ -262,7 +320,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                         .unwrap_or_else(whoami::realname);
                     Text::new("Maintainer:")
                         .with_default(&default_maintainer)
-                        .prompt()
+                        .prompt_or_default()
                 },
                 Ok,
             )?;
@@ -281,7 +339,7 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                             .with_help_message(
                                 "This is equivalent to the 'lua >= {version}' constraint."
                             )
-                            .prompt()?
+                            .prompt_or_default()?
                         )
                         .parse()?,
                     )
@@ -289,6 +347,42 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                 Ok,
             )?;
 
+            let dependencies = if Confirm::new("Add dependencies?")
+                .with_default(false)
+                .with_help_message("Search the rock servers for packages to depend on")
+                .with_render_config(render_config)
+                .prompt_or_default()?
+            {
+                let db =
+                    RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new()))
+                        .await?;
+                pick_dependencies(&db)?
+            } else {
+                Vec::new()
+            };
+
+            // Scripted runs only get dotfiles if they ask for them
+            let dotfiles = dotfiles
+                || (is_interactive()
+                    && Confirm::new("Generate .gitignore, .editorconfig and .busted files?")
+                        .with_default(true)
+                        .with_render_config(render_config)
+                        .prompt_or_default()?);
+
+            let ci = ci.map_or_else(
+                || {
+                    Select::new(
+                        "Generate a CI pipeline?",
+                        vec![CiProvider::None, CiProvider::Github, CiProvider::Gitlab],
+                    )
+                    .without_filtering()
+                    .with_help_message("The pipeline runs `lx build && lx test`.")
+                    .with_render_config(render_config)
+                    .prompt_or_default()
+                },
+                Ok,
+            )?;
+
             Ok(NewProjectValidated {
                 target,
                 name: package_name,
@@ -298,6 +392,9 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                 lua_versions,
                 maintainer,
                 main: main.unwrap_or(SourceDirType::Src),
+                dependencies,
+                dotfiles,
+                ci,
             })
         }
     }?;
@@ -321,8 +418,7 @@ labels = [ {labels} ]
 {license}
 
 [dependencies]
-# Add your dependencies here
-# `busted = ">=2.0"`
+{dependencies}
 
 [run]
 args = [ "{main}/main.lua" ]
@@ -344,10 +440,39 @@ type = "builtin"
                 .join(", "),
             lua_version_req = validated.lua_versions.version_req(),
             main = validated.main,
+            dependencies = if validated.dependencies.is_empty() {
+                "# Add your dependencies here\n# `busted = \">=2.0\"`".to_string()
+            } else {
+                validated
+                    .dependencies
+                    .iter()
+                    .map(|dep| format!(r#"{} = "{}""#, dep.name(), dep.version_req()))
+                    .join("\n")
+            },
         )
         .trim(),
     )?;
 
+    if let Some(license) = validated.license {
+        let license_path = validated.target.join("LICENSE");
+        if license_path.exists() {
+            eprintln!(
+                "`{}` already exists - we won't make any changes to it.",
+                license_path.display()
+            );
+        } else {
+            std::fs::write(
+                &license_path,
+                license_text(license, &validated.maintainer, chrono::Local::now().year()),
+            )?;
+        }
+    }
+
+    if validated.dotfiles {
+        scaffold::write_dotfiles(&validated.target)?;
+    }
+    scaffold::write_ci_pipeline(&validated.target, validated.ci)?;
+
     let main_dir = validated.target.join(validated.main.to_string());
     if main_dir.exists() {
         eprintln!(
@@ -359,9 +484,19 @@ type = "builtin"
         std::fs::write(main_dir.join("main.lua"), r#"print("Hello world!")"#)?;
     }
 
-    println!("All done!");
+    output::message("All done!");
 
     Ok(())
 }
 
-// TODO(vhyrro): Add tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_in_license_text() {
+        let text = license_text(parse_license_unchecked("MIT"), "Jane Doe", 2025);
+        assert!(text.contains("Copyright (c) 2025 Jane Doe"));
+        assert!(!text.contains("<year>"));
+    }
+}
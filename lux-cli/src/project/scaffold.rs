@@ -0,0 +1,183 @@
+//! Optional files that `lx new` can generate alongside the `lux.toml`.
+
+use std::{fmt::Display, path::Path};
+
+use eyre::Result;
+
+const GITIGNORE: &str = r#"# Lux project tree
+/.lux/
+
+# Build artifacts
+*.o
+*.so
+*.dll
+*.dylib
+*.rock
+/build/
+"#;
+
+const EDITORCONFIG: &str = r#"root = true
+
+[*]
+charset = utf-8
+end_of_line = lf
+insert_final_newline = true
+trim_trailing_whitespace = true
+
+[*.lua]
+indent_style = space
+indent_size = 2
+
+[*.toml]
+indent_style = space
+indent_size = 2
+"#;
+
+const BUSTED: &str = r#"return {
+  _all = {
+    lpath = "src/?.lua;src/?/init.lua;lua/?.lua;lua/?/init.lua",
+  },
+  default = {
+    ROOT = { "spec" },
+    verbose = true,
+  },
+}
+"#;
+
+const GITHUB_WORKFLOW: &str = r#"name: CI
+
+on:
+  push:
+    branches: [main]
+  pull_request:
+
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install system dependencies
+        run: sudo apt-get update && sudo apt-get install -y build-essential libgpgme11 libdbus-1-3
+      - name: Install lux
+        run: |
+          mkdir -p "$HOME/.local/bin"
+          curl -sSfL https://github.com/nvim-neorocks/lux/releases/latest/download/lx-x86_64-unknown-linux-gnu.tar.gz \
+            | tar -xz -C "$HOME/.local/bin" lx
+          echo "$HOME/.local/bin" >> "$GITHUB_PATH"
+      - name: Build and test
+        run: lx build && lx test
+"#;
+
+const GITLAB_CI: &str = r#"test:
+  image: ubuntu:latest
+  before_script:
+    - apt-get update && apt-get install -y build-essential ca-certificates curl libgpgme11 libdbus-1-3
+    - curl -sSfL https://github.com/nvim-neorocks/lux/releases/latest/download/lx-x86_64-unknown-linux-gnu.tar.gz | tar -xz -C /usr/local/bin lx
+  script:
+    - lx build && lx test
+"#;
+
+/// The CI service to generate a pipeline for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum CiProvider {
+    Github,
+    Gitlab,
+    None,
+}
+
+impl Display for CiProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Github => write!(f, "GitHub Actions"),
+            Self::Gitlab => write!(f, "GitLab CI"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl CiProvider {
+    fn pipeline(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Github => Some((".github/workflows/ci.yml", GITHUB_WORKFLOW)),
+            Self::Gitlab => Some((".gitlab-ci.yml", GITLAB_CI)),
+            Self::None => None,
+        }
+    }
+}
+
+/// Write the `.gitignore`, `.editorconfig` and `.busted` files into the project root.
+pub(crate) fn write_dotfiles(root: &Path) -> Result<()> {
+    for (file_name, content) in [
+        (".gitignore", GITIGNORE),
+        (".editorconfig", EDITORCONFIG),
+        (".busted", BUSTED),
+    ] {
+        write_unless_exists(root, file_name, content)?;
+    }
+    Ok(())
+}
+
+/// Write a CI pipeline that builds and tests the project.
+pub(crate) fn write_ci_pipeline(root: &Path, ci: CiProvider) -> Result<()> {
+    if let Some((path, content)) = ci.pipeline() {
+        write_unless_exists(root, path, content)?;
+    }
+    Ok(())
+}
+
+fn write_unless_exists(root: &Path, path: &str, content: &str) -> Result<()> {
+    let path = root.join(path);
+    if path.exists() {
+        eprintln!(
+            "`{}` already exists - we won't make any changes to it.",
+            path.display()
+        );
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_dotfiles_keeps_existing_files() {
+        let root = assert_fs::TempDir::new().unwrap();
+        std::fs::write(root.join(".gitignore"), "custom\n").unwrap();
+        write_dotfiles(&root).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join(".gitignore")).unwrap(),
+            "custom\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join(".editorconfig")).unwrap(),
+            EDITORCONFIG
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join(".busted")).unwrap(),
+            BUSTED
+        );
+    }
+
+    #[test]
+    fn write_ci_pipelines() {
+        let root = assert_fs::TempDir::new().unwrap();
+        write_ci_pipeline(&root, CiProvider::None).unwrap();
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        write_ci_pipeline(&root, CiProvider::Github).unwrap();
+        let workflow = std::fs::read_to_string(root.join(".github/workflows/ci.yml")).unwrap();
+        assert!(workflow.contains("libgpgme11"));
+        assert!(workflow.contains("lx build && lx test"));
+
+        write_ci_pipeline(&root, CiProvider::Gitlab).unwrap();
+        let pipeline = std::fs::read_to_string(root.join(".gitlab-ci.yml")).unwrap();
+        assert!(pipeline.contains("libgpgme11"));
+        assert!(pipeline.contains("lx build && lx test"));
+    }
+}
@@ -5,6 +5,8 @@ use lux_lib::{
     progress::{MultiProgress, ProgressBar},
 };
 
+use crate::utils::prompt::PromptOrDefault;
+
 /// Purge the user tree
 pub async fn purge(config: Config) -> Result<()> {
     let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
@@ -13,7 +15,7 @@ pub async fn purge(config: Config) -> Result<()> {
 
     if Confirm::new(&format!("Are you sure you want to purge all {len} rocks?"))
         .with_default(false)
-        .prompt()?
+        .prompt_or_default()?
     {
         let root_dir = tree.root();
 
@@ -0,0 +1,169 @@
+use clap::Args;
+use std::path::Path;
+
+use eyre::{eyre, OptionExt, Result};
+use lux_lib::{
+    config::Config,
+    operations::{BumpVersion, PackSrc},
+    package::VersionBump,
+    progress::{Progress, ProgressBar},
+    project::Project,
+    remote_package_db::RemotePackageDB,
+    upload::ProjectUpload,
+};
+
+#[cfg(not(target_env = "msvc"))]
+use lux_lib::upload::SignatureProtocol;
+
+use tokio::process::Command;
+
+use crate::utils::output;
+use crate::utils::source_metadata::{origin_source_url, populate_source};
+
+#[derive(Args)]
+pub struct Release {
+    /// How to bump the version: `major`, `minor`, `patch`,{n}
+    /// or an explicit version, e.g. `2.0.0`.{n}
+    /// If omitted, the current version is released.
+    bump: Option<VersionBump>,
+
+    /// Don't commit the version bump or create a git tag.
+    #[arg(long)]
+    no_git: bool,
+
+    /// Commit the version bump, but don't create a `v<version>` git tag.
+    #[arg(long)]
+    no_tag: bool,
+
+    /// Don't push the version bump commit and tag to the `origin` remote.
+    #[arg(long)]
+    no_push: bool,
+
+    /// Don't add a heading for the new version to the project's `CHANGELOG.md`.
+    #[arg(long)]
+    no_changelog: bool,
+
+    /// Don't write the generated rockspec to the project root.{n}
+    /// Otherwise, it is committed together with the version bump.
+    #[arg(long)]
+    no_rockspec: bool,
+
+    /// Don't pack the source rock into the current directory.
+    #[arg(long)]
+    no_pack: bool,
+
+    /// Don't upload the rockspec and source rock to the server.
+    #[arg(long)]
+    no_upload: bool,
+
+    /// The protocol to use when signing upload artefacts
+    #[cfg(not(target_env = "msvc"))]
+    #[arg(long, default_value_t)]
+    sign_protocol: SignatureProtocol,
+}
+
+/// Bump the version and regenerate the rockspec, commit and tag them, push them,
+/// then pack the source rock and upload it, in that order.
+pub async fn release(args: Release, config: Config) -> Result<()> {
+    let mut project = Project::current()?.ok_or_eyre("No project found")?;
+    let mut rockspec_written = false;
+
+    if let Some(bump) = args.bump {
+        let package_db = if config.offline() {
+            None
+        } else {
+            Some(
+                RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new()))
+                    .await?,
+            )
+        };
+        let tag = !args.no_git && !args.no_tag;
+        let has_source_url = project.toml().has_source_url();
+        // Without a `source.url`, the source is the tag that is created for the new version.
+        let git_source_url = if tag && !has_source_url {
+            origin_source_url(project.root())?
+        } else {
+            None
+        };
+        // The tag is created together with the version bump commit,
+        // so that a `$(REF)` in the `source` template resolves to it.
+        let bumped = BumpVersion::new(&mut project, bump)
+            .maybe_package_db(package_db.as_ref())
+            .commit(!args.no_git)
+            .tag(tag)
+            .changelog(!args.no_changelog)
+            .rockspec(!args.no_rockspec && (has_source_url || git_source_url.is_some()))
+            .maybe_git_source_url(git_source_url)
+            .bump()
+            .await?;
+        output::message(format!(
+            "Bumped version from {} to {}",
+            bumped.previous(),
+            bumped.version()
+        ));
+        if let Some(changelog) = bumped.changelog() {
+            output::message(format!("Updated {}", changelog.display()));
+        }
+        if let Some(rockspec) = bumped.rockspec() {
+            output::message(format!("Wrote rockspec to {}", rockspec.display()));
+        }
+        if let Some(tag) = bumped.tag() {
+            output::message(format!("Created tag {tag}"));
+        }
+        if !args.no_git && !args.no_push && !config.offline() {
+            push(project.root(), bumped.tag()).await?;
+            output::message("Pushed the release to origin");
+        }
+        rockspec_written = bumped.rockspec().is_some();
+    }
+
+    // Without a `source.url`, the source is the tag on the git remote.
+    // If it hasn't been pushed, this fails, and the release can be resumed
+    // by pushing it and running `lx release` without a version bump.
+    let project = populate_source(project, &config)?;
+
+    if !args.no_rockspec && !rockspec_written {
+        let path = project.root().join(project.rockspec_file_name()?);
+        std::fs::write(&path, project.to_rockspec()?)?;
+        output::message(format!("Wrote rockspec to {}", path.display()));
+    }
+
+    if !args.no_pack {
+        let rock_path = PackSrc::new(std::env::current_dir()?, &project)
+            .pack()
+            .await?;
+        output::message(format!("Packed source rock to {}", rock_path.display()));
+    }
+
+    if !args.no_upload {
+        let upload = ProjectUpload::new(project, &config);
+        #[cfg(not(target_env = "msvc"))]
+        let upload = upload.sign_protocol(args.sign_protocol);
+        upload.upload_to_luarocks().await?;
+        output::message(format!("Uploaded to {}", config.server()));
+    }
+
+    Ok(())
+}
+
+/// Push the current branch and the release tag to the `origin` remote.
+async fn push(project_root: &Path, tag: Option<&str>) -> Result<()> {
+    let refspecs = std::iter::once("HEAD".to_string())
+        .chain(tag.map(|tag| format!("refs/tags/{tag}")))
+        .collect::<Vec<_>>();
+    let status = Command::new("git")
+        .current_dir(project_root)
+        .args(["push", "--atomic", "origin"])
+        .args(&refspecs)
+        .status()
+        .await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "failed to push {} to origin.\n\
+            Push it and run `lx release` without a version bump to finish the release.",
+            refspecs.join(" ")
+        ))
+    }
+}
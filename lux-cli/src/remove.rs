@@ -1,13 +1,18 @@
 use clap::Args;
 use eyre::{OptionExt, Result};
+use itertools::Itertools;
 use lux_lib::{
-    config::Config, package::PackageName, progress::MultiProgress, project::Project,
-    rockspec::lua_dependency,
+    config::Config, lockfile::LocalPackage, package::PackageName, progress::MultiProgress,
+    project::Project, rockspec::lua_dependency,
 };
+use serde_json::json;
 
-use crate::utils::project::{
-    sync_build_dependencies_if_locked, sync_dependencies_if_locked,
-    sync_test_dependencies_if_locked,
+use crate::utils::{
+    output::{self, is_json_output},
+    project::{
+        sync_build_dependencies_if_locked, sync_dependencies_if_locked,
+        sync_test_dependencies_if_locked,
+    },
 };
 
 #[derive(Args)]
@@ -28,12 +33,14 @@ pub struct Remove {
 pub async fn remove(data: Remove, config: Config) -> Result<()> {
     let mut project = Project::current()?.ok_or_eyre("No project found")?;
     let progress = MultiProgress::new_arc();
+    let mut removed_packages: Vec<LocalPackage> = Vec::new();
 
     if !data.package.is_empty() {
         project
             .remove(lua_dependency::DependencyType::Regular(data.package))
             .await?;
-        sync_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        let report = sync_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        removed_packages.extend(report.removed().iter().cloned());
     }
 
     let build_packages = data.build.unwrap_or_default();
@@ -41,7 +48,8 @@ pub async fn remove(data: Remove, config: Config) -> Result<()> {
         project
             .remove(lua_dependency::DependencyType::Build(build_packages))
             .await?;
-        sync_build_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        let report = sync_build_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        removed_packages.extend(report.removed().iter().cloned());
     }
 
     let test_packages = data.test.unwrap_or_default();
@@ -49,7 +57,34 @@ pub async fn remove(data: Remove, config: Config) -> Result<()> {
         project
             .remove(lua_dependency::DependencyType::Test(test_packages))
             .await?;
-        sync_test_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        let report = sync_test_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        removed_packages.extend(report.removed().iter().cloned());
+    }
+
+    if is_json_output() {
+        for package in removed_packages
+            .iter()
+            .sorted_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())))
+            .dedup_by(|a, b| a.name() == b.name() && a.version() == b.version())
+        {
+            output::emit(
+                "removed",
+                json!({
+                    "name": package.name().to_string(),
+                    "version": package.version().to_string(),
+                }),
+            );
+        }
+    } else if !removed_packages.is_empty() {
+        output::message(format!(
+            "Removed the following rocks from the project tree:\n{}",
+            removed_packages
+                .iter()
+                .map(|pkg| format!("  {}@{}", pkg.name(), pkg.version()))
+                .sorted()
+                .dedup()
+                .join("\n")
+        ));
     }
 
     Ok(())
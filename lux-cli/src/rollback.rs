@@ -0,0 +1,66 @@
+use clap::Args;
+use eyre::{Context, Result};
+use lux_lib::{config::Config, operations::Sync, progress::MultiProgress, project::Project};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct Rollback {
+    /// The lockfile snapshot to restore:{n}
+    /// Either the number of changes to go back (defaults to 1),{n}
+    /// or the timestamp of a snapshot, as shown by `--list`.
+    snapshot: Option<String>,
+
+    /// List the lockfile snapshots, from the most recent to the oldest.
+    #[arg(long, conflicts_with = "snapshot")]
+    list: bool,
+}
+
+pub async fn rollback(data: Rollback, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let history = project.lockfile_history();
+
+    if data.list {
+        let snapshots = history.snapshots()?;
+        if snapshots.is_empty() {
+            output::message("No lockfile snapshots found.");
+        }
+        for (n, snapshot) in snapshots.iter().enumerate() {
+            output::message(format!(
+                "{}\t{}\t{}",
+                n + 1,
+                snapshot.timestamp(),
+                snapshot.date()
+            ));
+        }
+        return Ok(());
+    }
+
+    let snapshot = history.find(data.snapshot.as_deref().unwrap_or("1"))?;
+    history.restore(&snapshot)?;
+    output::message(format!("Restored the lockfile from {}", snapshot.date()));
+
+    // The tree is synced with the restored lockfile as it is, without re-resolving lux.toml,
+    // which may no longer match it.
+    let progress = MultiProgress::new_arc();
+    Sync::new(&project, &config)
+        .progress(progress.clone())
+        .locked(true)
+        .sync_dependencies()
+        .await
+        .wrap_err("syncing dependencies with the restored lockfile failed.")?;
+    Sync::new(&project, &config)
+        .progress(progress.clone())
+        .locked(true)
+        .sync_build_dependencies()
+        .await
+        .wrap_err("syncing build dependencies with the restored lockfile failed.")?;
+    Sync::new(&project, &config)
+        .progress(progress)
+        .locked(true)
+        .sync_test_dependencies()
+        .await
+        .wrap_err("syncing test dependencies with the restored lockfile failed.")?;
+
+    Ok(())
+}
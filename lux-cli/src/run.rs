@@ -6,8 +6,15 @@ use crate::build::{self, Build};
 
 #[derive(Args)]
 pub struct Run {
+    /// Arguments to pass to the program, in addition to the `args` in `lux.toml`.{n}
+    /// Use `--` to pass arguments that start with a `-`,{n}
+    /// e.g. `lx run -- --verbose`.
     args: Vec<String>,
 
+    /// Run with a profile from the `[run.profiles]` table in `lux.toml`.
+    #[arg(long)]
+    profile: Option<String>,
+
     /// Do not add `require('lux').loader()` to `LUA_INIT`.
     /// If a rock has conflicting transitive dependencies,
     /// disabling the Lux loader may result in the wrong modules being loaded.
@@ -29,6 +36,7 @@ pub async fn run(run_args: Run, config: Config) -> Result<()> {
         .args(&run_args.args)
         .config(&config)
         .disable_loader(run_args.no_loader)
+        .maybe_profile(run_args.profile.as_deref())
         .run()
         .await?;
 
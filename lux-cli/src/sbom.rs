@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    config::{Config, LuaVersion},
+    operations::{self, Licenses, SbomFormat},
+    package::PackageSpec,
+    progress::MultiProgress,
+    project::Project,
+};
+use serde_json::json;
+
+use crate::utils::{output, project::locked_packages};
+
+#[derive(Args)]
+pub struct Sbom {
+    /// The SBOM format.
+    #[arg(long, value_enum, default_value_t = SbomFormat::CycloneDx)]
+    format: SbomFormat,
+
+    /// Write the SBOM to a file instead of stdout.
+    #[arg(long, short, value_name = "file")]
+    output: Option<PathBuf>,
+}
+
+/// Generate a software bill of materials from the lockfile of the current project
+/// (or the user tree).
+pub async fn sbom(args: Sbom, config: Config) -> Result<()> {
+    let packages = locked_packages(&config)?;
+    let project = Project::current()?;
+    let trees = match &project {
+        Some(project) => {
+            let tree = project.tree(&config)?;
+            vec![tree.test_tree(&config)?, tree.build_tree(&config)?, tree]
+        }
+        None => vec![config.user_tree(LuaVersion::from(&config)?.clone())?],
+    };
+
+    let report = Licenses::new(&config)
+        .packages(packages)
+        .trees(trees)
+        .progress(MultiProgress::new_arc())
+        .collect()
+        .await?;
+
+    let mut sbom = operations::Sbom::new(&report);
+    if let Some(project) = &project {
+        let toml = project.toml();
+        if let Ok(version) = toml.version() {
+            sbom = sbom.subject(PackageSpec::new(toml.package().clone(), version));
+        }
+    }
+    let sbom = sbom.render(args.format);
+
+    match args.output {
+        Some(path) => std::fs::write(path, sbom)?,
+        None if output::is_json_output() => output::emit(
+            "sbom",
+            json!({ "document": serde_json::from_str::<serde_json::Value>(&sbom)? }),
+        ),
+        None => println!("{sbom}"),
+    }
+    Ok(())
+}
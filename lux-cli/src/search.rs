@@ -3,19 +3,41 @@ use std::collections::HashMap;
 use clap::Args;
 use eyre::Result;
 use itertools::Itertools;
+use serde_json::json;
 use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
 
 use lux_lib::{
     config::Config,
-    package::{PackageName, PackageReq, PackageVersion},
+    lua_rockspec::RockDescription,
+    operations::Download,
+    package::{PackageName, PackageReq, PackageSpec, PackageVersion},
     progress::{MultiProgress, Progress},
     remote_package_db::RemotePackageDB,
+    rockspec::Rockspec,
 };
 
+use crate::utils::output::{self, is_json_output, print_json};
+
 #[derive(Args)]
 pub struct Search {
+    /// The package name (or part of it) to search for,{n}
+    /// optionally with a version constraint, e.g. `lua-cjson@>=2.0`.
     lua_package_req: PackageReq,
-    // TODO(vhyrro): Add options.
+
+    /// Show at most this many packages.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Show the summary and labels of each package.{n}
+    /// This downloads the rockspec of the latest matching version of each package,{n}
+    /// so it is best combined with `--limit`.
+    #[arg(long)]
+    details: bool,
+
+    /// Print the results as a JSON array.
+    #[arg(long, conflicts_with = "porcelain")]
+    json: bool,
+
     /// Return a machine readable format.
     #[arg(long)]
     porcelain: bool,
@@ -31,26 +53,95 @@ pub async fn search(data: Search, config: Config) -> Result<()> {
     bar.map(|b| b.set_message(format!("🔎 Searching for `{}`...", data.lua_package_req)));
 
     let lua_package_req = data.lua_package_req;
+    let query = lua_package_req.name().to_string();
 
-    let result = package_db.search(&lua_package_req);
+    let result = package_db
+        .search(&lua_package_req)
+        .into_iter()
+        .filter(|(_, versions)| !versions.is_empty())
+        .sorted_by_key(|(name, _)| (match_rank(name, &query), name.to_string()))
+        .take(data.limit.unwrap_or(usize::MAX))
+        .collect_vec();
+
+    let mut descriptions = HashMap::new();
+    if data.details {
+        for (name, versions) in &result {
+            let latest = PackageSpec::new((*name).clone(), (*versions[0]).clone());
+            bar.map(|b| b.set_message(format!("📥 Downloading the rockspec of {latest}")));
+            let package_req = latest.into_package_req();
+            match Download::new(&package_req, &config, &bar)
+                .package_db(&package_db)
+                .download_rockspec()
+                .await
+            {
+                Ok(downloaded) => {
+                    descriptions.insert(*name, downloaded.rockspec.description().clone());
+                }
+                Err(err) => {
+                    bar.map(|b| b.println(format!("⚠️ WARNING: {err}")));
+                }
+            }
+        }
+    }
 
     bar.map(|b| b.finish_and_clear());
 
-    if data.porcelain {
+    if data.porcelain && !is_json_output() {
         let rock_to_version_map: HashMap<&PackageName, Vec<&PackageVersion>> =
             HashMap::from_iter(result);
-        println!("{}", serde_json::to_string(&rock_to_version_map)?);
+        output::message(serde_json::to_string(&rock_to_version_map)?);
+    } else if data.json || is_json_output() {
+        let packages = result
+            .iter()
+            .map(|(name, versions)| {
+                let description = descriptions.get(name);
+                json!({
+                    "name": name.to_string(),
+                    "versions": versions.iter().map(|version| version.to_string()).collect_vec(),
+                    "summary": description.and_then(|description| description.summary.clone()),
+                    "labels": description.map(|description| description.labels.clone()),
+                })
+            })
+            .collect_vec();
+        print_json("package", packages)?;
     } else {
-        for (key, versions) in result.into_iter().sorted() {
-            let mut tree = StringTreeNode::new(key.to_string().to_owned());
+        for (key, versions) in result {
+            let label = match descriptions.get(key) {
+                Some(description) => describe(key, description),
+                None => key.to_string(),
+            };
+            let mut tree = StringTreeNode::new(label);
 
             for version in versions {
                 tree.push(version.to_string());
             }
 
-            println!("{}", tree.to_string_with_format(&formatting).unwrap());
+            output::message(tree.to_string_with_format(&formatting).unwrap());
         }
     }
 
     Ok(())
 }
+
+/// Exact matches come first, followed by packages whose names start with the query.
+fn match_rank(name: &PackageName, query: &str) -> u8 {
+    let name = name.to_string();
+    if name == query {
+        0
+    } else if name.starts_with(query) {
+        1
+    } else {
+        2
+    }
+}
+
+fn describe(name: &PackageName, description: &RockDescription) -> String {
+    let mut label = name.to_string();
+    if let Some(summary) = &description.summary {
+        label.push_str(&format!(" - {}", summary.trim()));
+    }
+    if !description.labels.is_empty() {
+        label.push_str(&format!(" [{}]", description.labels.join(", ")));
+    }
+    label
+}
@@ -1,12 +1,21 @@
 use clap::Args;
-use eyre::{OptionExt, Result};
+use eyre::{eyre, OptionExt, Result};
+use itertools::Itertools;
 use lux_lib::{
-    config::Config,
-    operations::{self, TestEnv},
-    project::Project,
+    config::{Config, LuaVersion},
+    operations::{self, TestEnv, TestOutput},
+    package::PackageName,
+    project::{workspace::Workspace, Project},
 };
 
-#[derive(Args)]
+use serde_json::json;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    watch::watch,
+};
+
+#[derive(Args, Clone)]
 pub struct Test {
     /// Extra arguments to pass to the test runner or test script.
     test_args: Option<Vec<String>>,
@@ -18,22 +27,114 @@ pub struct Test {
     /// Ignore the project's lockfile and don't create one.
     #[arg(long)]
     no_lock: bool,
+
+    /// Only run tests whose names match this Lua pattern (busted only).
+    #[arg(long, value_name = "pattern")]
+    filter: Option<String>,
+
+    /// Only run tests with these tags (busted only).
+    #[arg(long, value_name = "tag", value_delimiter = ',')]
+    tags: Option<Vec<String>>,
+
+    /// Don't run tests with these tags (busted only).
+    #[arg(long, value_name = "tag", value_delimiter = ',')]
+    exclude_tags: Option<Vec<String>>,
+
+    /// Print the test results in a machine-readable format (busted only).
+    #[arg(long, value_enum)]
+    output: Option<TestOutput>,
+
+    /// Run the tests of the given workspace member.
+    #[arg(short, long, value_name = "member")]
+    package: Option<PackageName>,
+
+    /// Run the tests against each of these Lua versions,{n}
+    /// e.g. '5.1,5.4,jit', and report the results for each version.
+    #[arg(long, value_name = "ver", value_delimiter = ',')]
+    lua_versions: Option<Vec<LuaVersion>>,
+
+    /// Rerun the tests whenever the project's files change.
+    #[arg(long)]
+    watch: bool,
 }
 
 pub async fn test(test: Test, config: Config) -> Result<()> {
-    let project = Project::current()?
-        .ok_or_eyre("'lux test' must be run in a project root, with a 'project.rockspec'")?;
-    let test_args = test.test_args.unwrap_or_default();
+    if test.watch {
+        let root = match &test.package {
+            Some(_) => Workspace::current_or_err()?.root().to_path_buf(),
+            None => Project::current_or_err()?.root().to_path_buf(),
+        };
+        return watch(&root, "Test run", || {
+            let test = test.clone();
+            let config = config.clone();
+            async move { test_once(test, config).await }
+        })
+        .await;
+    }
+    test_once(test, config).await
+}
+
+async fn test_once(test: Test, config: Config) -> Result<()> {
+    let project = match &test.package {
+        Some(member) => Workspace::current_or_err()?.member(member)?.clone(),
+        None => Project::current()?
+            .ok_or_eyre("'lux test' must be run in a project root, with a 'project.rockspec'")?,
+    };
+    let lua_versions = match &test.lua_versions {
+        Some(lua_versions) => lua_versions.clone(),
+        None => return run_tests(&test, project, &config).await,
+    };
+    // Each Lua version has its own project tree, so the runs don't interfere with each other.
+    let mut results = Vec::new();
+    for lua_version in lua_versions {
+        eprintln!("🌙 Running tests with Lua {lua_version}");
+        let config = config.clone().with_lua_version(lua_version.clone());
+        let result = run_tests(&test, project.clone(), &config).await;
+        if let Err(err) = &result {
+            eprintln!("{err:?}");
+        }
+        results.push((lua_version, result.is_ok()));
+    }
+    eprintln!("\nLua version  Result");
+    for (lua_version, passed) in &results {
+        let result = if *passed { "✔ passed" } else { "✗ failed" };
+        eprintln!("{:<12} {result}", lua_version.to_string());
+    }
+    let failed = results
+        .iter()
+        .filter(|(_, passed)| !passed)
+        .map(|(lua_version, _)| lua_version.to_string())
+        .collect_vec();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("tests failed with Lua {}", failed.join(", ")))
+    }
+}
+
+async fn run_tests(test: &Test, project: Project, config: &Config) -> Result<()> {
     let test_env = if test.impure {
         TestEnv::Impure
     } else {
         TestEnv::Pure
     };
-    operations::Test::new(project, &config)
-        .args(test_args)
+    let report = operations::Test::new(project, config)
+        .args(test.test_args.clone().unwrap_or_default())
         .env(test_env)
         .no_lock(test.no_lock)
+        .maybe_filter(test.filter.clone())
+        .maybe_tags(test.tags.clone())
+        .maybe_exclude_tags(test.exclude_tags.clone())
+        .maybe_output(test.output)
         .run()
         .await?;
-    Ok(())
+    match (&report, test.output) {
+        (Some(report), _) if is_json_output() => output::emit("test-report", json!(report)),
+        (Some(report), Some(format)) => print!("{}", report.render(&format)),
+        _ => {}
+    }
+    match report {
+        Some(report) if !report.is_success() => Err(eyre!("tests failed!")),
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,37 @@
+use clap::Subcommand;
+use eyre::Result;
+use lux_lib::{config::Config, operations::list_global_tools};
+
+use crate::install::{install_tools, Install};
+use crate::utils::output;
+
+#[derive(Subcommand)]
+pub enum ToolCmd {
+    /// Install rocks into the user tree and make their executables globally available,{n}
+    /// with shims in the global bin directory (`~/.lux/bin` by default).{n}
+    /// Equivalent to `lx install --global`.
+    Install(Install),
+    /// List the globally available tools and the packages that provide them.
+    List,
+}
+
+pub async fn tool(cmd: ToolCmd, config: Config) -> Result<()> {
+    match cmd {
+        ToolCmd::Install(args) => install_tools(args, config).await?,
+        ToolCmd::List => {
+            let tools = list_global_tools(&config)?;
+            if tools.is_empty() {
+                output::message("No tools installed.");
+            }
+            for tool in tools {
+                let status = if tool.is_installed() {
+                    ""
+                } else {
+                    " (missing, reinstall with `lx tool install`)"
+                };
+                output::message(format!("{:<20} {}{status}", tool.name(), tool.package()));
+            }
+        }
+    }
+    Ok(())
+}
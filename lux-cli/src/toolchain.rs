@@ -0,0 +1,108 @@
+use clap::Subcommand;
+use eyre::{OptionExt, Result};
+use lux_lib::{
+    config::{
+        layers::{ConfigFile, ConfigLocation},
+        Config, LuaVersion,
+    },
+    lua_installation::LuaInstallation,
+    project::Project,
+};
+
+use crate::install_lua::install_lua;
+use crate::utils::output;
+
+#[derive(Subcommand)]
+pub enum ToolchainCmd {
+    /// List the Lua versions and where they are installed.{n}
+    /// The active version is marked with a `*`.
+    List,
+    /// Install a Lua version.
+    Install(ToolchainInstall),
+    /// Remove a Lua version that was installed by lux.
+    Remove(ToolchainVersion),
+    /// Set the default Lua version in the config file.
+    Default(ToolchainVersion),
+    /// Pin the Lua version of the current project in a `.lua-version` file.{n}
+    /// The pinned version takes precedence over the default version.
+    Pin(ToolchainVersion),
+}
+
+#[derive(clap::Args)]
+pub struct ToolchainVersion {
+    /// The Lua version.{n}
+    /// Valid versions are: '5.1', '5.2', '5.3', '5.4', 'jit' and 'jit52'.
+    version: LuaVersion,
+}
+
+#[derive(clap::Args)]
+pub struct ToolchainInstall {
+    #[clap(flatten)]
+    lua: ToolchainVersion,
+
+    /// Reinstall Lua without prompting if it is already installed.
+    #[arg(long)]
+    force: bool,
+}
+
+pub async fn toolchain(cmd: ToolchainCmd, config: Config) -> Result<()> {
+    match cmd {
+        ToolchainCmd::List => {
+            let active_version = LuaVersion::from(&config).ok();
+            for version in LuaVersion::ALL {
+                let marker = if active_version == Some(&version) {
+                    "*"
+                } else {
+                    " "
+                };
+                let location = if let Some(lua) = LuaInstallation::find_installed(&version, &config)
+                {
+                    lua.prefix()
+                        .map(|prefix| prefix.display().to_string())
+                        .unwrap_or_default()
+                } else if let Some(lua) = LuaInstallation::find_system(&version, &config) {
+                    match lua.prefix() {
+                        Some(prefix) => format!("{} (system)", prefix.display()),
+                        None => "(system)".into(),
+                    }
+                } else {
+                    "not installed".into()
+                };
+                output::message(format!("{marker} {:<6} {location}", version.to_string()));
+            }
+        }
+        ToolchainCmd::Install(args) => install_lua(args.lua.version, args.force, config).await?,
+        ToolchainCmd::Remove(args) => {
+            match LuaInstallation::uninstall(&args.version, &config).await? {
+                Some(dir) => output::message(format!(
+                    "Removed Lua {} from {}",
+                    args.version,
+                    dir.display()
+                )),
+                None => output::message(format!("Lua {} is not installed.", args.version)),
+            }
+        }
+        ToolchainCmd::Default(args) => {
+            // Only edit the `lua_version` of the user config file,
+            // so that its other values (and the other config layers) are left untouched.
+            let mut config_file = ConfigFile::open(ConfigLocation::User)?;
+            config_file.set("lua_version", &args.version.to_string())?;
+            config_file.save()?;
+            output::message(format!(
+                "Set the default Lua version to {} in {}",
+                args.version,
+                config_file.path().display()
+            ));
+        }
+        ToolchainCmd::Pin(args) => {
+            let project = Project::current()?.ok_or_eyre("not in a lux project directory")?;
+            project.pin_lua_version(&args.version)?;
+            output::message(format!(
+                "Pinned Lua {} in {}",
+                args.version,
+                project.lua_version_file_path().display()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use clap::{Args, ValueEnum};
+use eyre::{eyre, Result};
+use itertools::Itertools;
+use lux_lib::{
+    config::Config,
+    lockfile::{DependencyGraph, LocalPackage, LocalPackageId},
+    package::PackageName,
+};
+use serde_json::json;
+use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
+
+use crate::utils::{dependency_graph::LockedGraph, output};
+
+#[derive(Args)]
+pub struct Tree {
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = TreeFormat::Text)]
+    format: TreeFormat,
+
+    /// Show the reverse dependencies of a package,{n}
+    /// i.e. the packages that (transitively) depend on it.
+    #[arg(long, short, value_name = "package")]
+    invert: Option<PackageName>,
+
+    /// Show the test dependencies of the current project.
+    #[arg(long, conflicts_with = "build")]
+    test: bool,
+
+    /// Show the build dependencies of the current project.
+    #[arg(long)]
+    build: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TreeFormat {
+    /// An indented tree.
+    Text,
+    /// A Graphviz DOT graph.
+    Dot,
+    /// A JSON tree.
+    Json,
+    /// A Mermaid flowchart.
+    Mermaid,
+}
+
+/// Print the dependency graph of the current project's lockfile,
+/// or of the user tree's lockfile if not in a project.
+pub fn tree(args: Tree, config: Config) -> Result<()> {
+    let locked = LockedGraph::load(&config, args.test, args.build)?;
+    let graph = &locked.graph;
+
+    let roots = match &args.invert {
+        Some(name) => {
+            let packages = graph.find(name);
+            if packages.is_empty() {
+                return Err(eyre!("package {name} is not installed."));
+            }
+            packages
+        }
+        None => graph.entrypoints(),
+    };
+    let invert = args.invert.is_some();
+
+    let format = if output::is_json_output() {
+        TreeFormat::Json
+    } else {
+        args.format
+    };
+    match format {
+        TreeFormat::Text => {
+            let formatting = TreeFormatting::dir_tree(FormatCharacters::box_chars());
+            let mut expanded = HashSet::new();
+            for root in roots {
+                let tree = text_node(graph, root, invert, &mut expanded, &mut Vec::new());
+                output::message(tree.to_string_with_format(&formatting)?);
+            }
+        }
+        TreeFormat::Json => {
+            let trees = roots
+                .into_iter()
+                .map(|root| json_node(graph, root, invert, &mut Vec::new()))
+                .collect_vec();
+            output::print_json("tree", trees)?;
+        }
+        TreeFormat::Dot => {
+            let edges = edges(graph, &roots, invert);
+            output::message("digraph dependencies {");
+            for root in &roots {
+                output::message(format!("  \"{}\";", label(root)));
+            }
+            for (from, to) in edges {
+                output::message(format!(
+                    "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                    label(from),
+                    label(to),
+                    locked.requirement(Some(from), to).unwrap_or_default()
+                ));
+            }
+            output::message("}");
+        }
+        TreeFormat::Mermaid => {
+            let edges = edges(graph, &roots, invert);
+            let node_ids = roots
+                .iter()
+                .copied()
+                .chain(edges.iter().flat_map(|(from, to)| [*from, *to]))
+                .map(|package| package.id())
+                .unique()
+                .enumerate()
+                .map(|(i, id)| (id, format!("n{i}")))
+                .collect::<std::collections::HashMap<_, _>>();
+            output::message("graph TD");
+            for root in &roots {
+                output::message(format!("  {}[\"{}\"]", node_ids[&root.id()], label(root)));
+            }
+            for (from, to) in edges {
+                output::message(format!(
+                    "  {}[\"{}\"] --> {}[\"{}\"]",
+                    node_ids[&from.id()],
+                    label(from),
+                    node_ids[&to.id()],
+                    label(to)
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn children<'a>(
+    graph: &'a DependencyGraph,
+    package: &LocalPackage,
+    invert: bool,
+) -> Vec<&'a LocalPackage> {
+    if invert {
+        graph.dependents(package)
+    } else {
+        graph.dependencies(package)
+    }
+}
+
+fn label(package: &LocalPackage) -> String {
+    format!("{}@{}", package.name(), package.version())
+}
+
+fn text_node(
+    graph: &DependencyGraph,
+    package: &LocalPackage,
+    invert: bool,
+    expanded: &mut HashSet<LocalPackageId>,
+    path: &mut Vec<LocalPackageId>,
+) -> StringTreeNode {
+    let id = package.id();
+    let constraint = match package.constraint().to_string_opt() {
+        Some(constraint) if !invert => format!(" ({constraint})"),
+        _ => String::new(),
+    };
+    if path.contains(&id) {
+        return StringTreeNode::new(format!("{}{constraint} (cycle)", label(package)));
+    }
+    let children = children(graph, package, invert);
+    if !children.is_empty() && !expanded.insert(id.clone()) {
+        // Only expand each package once, like `cargo tree`
+        return StringTreeNode::new(format!("{}{constraint} (*)", label(package)));
+    }
+    let mut node = StringTreeNode::new(format!("{}{constraint}", label(package)));
+    path.push(id);
+    for child in children {
+        node.push_node(text_node(graph, child, invert, expanded, path));
+    }
+    path.pop();
+    node
+}
+
+fn json_node(
+    graph: &DependencyGraph,
+    package: &LocalPackage,
+    invert: bool,
+    path: &mut Vec<LocalPackageId>,
+) -> serde_json::Value {
+    let id = package.id();
+    let key = if invert { "dependents" } else { "dependencies" };
+    let children = if path.contains(&id) {
+        Vec::new()
+    } else {
+        path.push(id);
+        let children = children(graph, package, invert)
+            .into_iter()
+            .map(|child| json_node(graph, child, invert, path))
+            .collect_vec();
+        path.pop();
+        children
+    };
+    json!({
+        "name": package.name().to_string(),
+        "version": package.version().to_string(),
+        "constraint": package.constraint().to_string_opt(),
+        key: children,
+    })
+}
+
+/// All edges that are reachable from the `roots`, pointing from dependents to dependencies.
+fn edges<'a>(
+    graph: &'a DependencyGraph,
+    roots: &[&'a LocalPackage],
+    invert: bool,
+) -> Vec<(&'a LocalPackage, &'a LocalPackage)> {
+    let mut visited = HashSet::new();
+    let mut stack = roots.to_vec();
+    let mut edges = Vec::new();
+    while let Some(package) = stack.pop() {
+        if !visited.insert(package.id()) {
+            continue;
+        }
+        for child in children(graph, package, invert) {
+            if invert {
+                edges.push((child, package));
+            } else {
+                edges.push((package, child));
+            }
+            stack.push(child);
+        }
+    }
+    edges
+}
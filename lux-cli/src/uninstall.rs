@@ -1,24 +1,34 @@
 use clap::Args;
+use clap_complete::ArgValueCompleter;
 use eyre::{eyre, Result};
-use inquire::Confirm;
 use itertools::Itertools;
 use lux_lib::{
-    build::BuildBehaviour,
     config::{Config, LuaVersion},
-    lockfile::LocalPackageId,
-    operations::{self, PackageInstallSpec},
+    operations,
     package::PackageReq,
     progress::MultiProgress,
-    tree::{self, RockMatches, TreeError},
+    tree::{RockMatches, TreeError},
+};
+use serde_json::json;
+
+use crate::{
+    completion::installed_packages,
+    utils::output::{self, is_json_output},
 };
 
 #[derive(Args)]
 pub struct Uninstall {
     /// The package or packages to uninstall from the system.
+    #[arg(add = ArgValueCompleter::new(installed_packages))]
     packages: Vec<PackageReq>,
+
+    /// Uninstall the packages even if other packages depend on them.
+    #[arg(long)]
+    force: bool,
 }
 
-/// Uninstall one or multiple rocks from the user tree
+/// Uninstall one or multiple rocks from the user tree,
+/// along with the dependencies that are no longer needed.
 pub async fn uninstall(uninstall_args: Uninstall, config: Config) -> Result<()> {
     let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
 
@@ -63,128 +73,78 @@ Please specify the exact package to uninstall:
     }
 
     let lockfile = tree.lockfile()?;
-    let non_entrypoints = packages
+    let graph = lockfile.dependency_graph();
+    let targets = packages
+        .iter()
+        .filter_map(|pkg_id| graph.get(pkg_id))
+        .collect_vec();
+
+    // Packages that other installed packages (which are not being uninstalled) depend on
+    let required = targets
         .iter()
-        .filter_map(|pkg_id| {
-            if lockfile.is_entrypoint(pkg_id) {
+        .filter_map(|package| {
+            let dependents = graph
+                .dependents(package)
+                .into_iter()
+                .filter(|dependent| !packages.contains(&dependent.id()))
+                .map(|dependent| dependent.name().to_string())
+                .collect_vec();
+            if dependents.is_empty() {
                 None
             } else {
-                Some(unsafe { lockfile.get_unchecked(pkg_id) }.name().to_string())
+                Some(format!(
+                    "{} (required by {})",
+                    package.name(),
+                    dependents.join(", ")
+                ))
             }
         })
         .collect_vec();
-    if !non_entrypoints.is_empty() {
+    if !required.is_empty() && !uninstall_args.force {
         return Err(eyre!(
             "
-Cannot uninstall dependencies:
-{:#?}
+Cannot uninstall packages that other packages depend on:
+{}
+
+Use `--force` to uninstall them anyway.
 ",
-            non_entrypoints,
+            required.join("\n"),
         ));
     }
 
-    let (dependencies, entrypoints): (Vec<LocalPackageId>, Vec<LocalPackageId>) = packages
+    let orphaned = graph.orphaned_by(&packages);
+    let orphaned_dependencies = orphaned
         .iter()
-        .cloned()
-        .partition(|pkg_id| lockfile.is_dependency(pkg_id));
-
-    let progress = MultiProgress::new_arc();
-
-    if dependencies.is_empty() {
-        operations::Remove::new(&config)
-            .packages(entrypoints)
-            .remove()
-            .await?;
-    } else {
-        let package_names = dependencies
-            .iter()
-            .map(|pkg_id| unsafe { lockfile.get_unchecked(pkg_id) }.name().to_string())
-            .collect_vec();
-        let prompt = if package_names.len() == 1 {
-            format!(
-                "
-            Package {} can be removed from the entrypoints, but it is also a dependency, so it will have to be reinstalled.
-Reinstall?
-            ",
-                package_names[0]
-            )
-        } else {
-            format!(
-                "
-            The following packages can be removed from the entrypoints, but are also dependencies:
-{package_names:#?}
-
-They will have to be reinstalled.
-Reinstall?
-            ",
-            )
-        };
-        if Confirm::new(&prompt)
-            .with_default(false)
-            .prompt()
-            .expect("Error prompting for reinstall")
-        {
-            operations::Remove::new(&config)
-                .packages(entrypoints)
-                .progress(progress.clone())
-                .remove()
-                .await?;
-
-            let reinstall_specs = dependencies
-                .iter()
-                .map(|pkg_id| {
-                    let package = unsafe { lockfile.get_unchecked(pkg_id) };
-                    PackageInstallSpec::new(
-                        package.clone().into_package_req(),
-                        tree::EntryType::DependencyOnly,
-                    )
-                    .build_behaviour(BuildBehaviour::Force)
-                    .pin(package.pinned())
-                    .opt(package.opt())
-                    .constraint(package.constraint())
-                    .build()
-                })
-                .collect_vec();
-            operations::Remove::new(&config)
-                .packages(dependencies)
-                .progress(progress.clone())
-                .remove()
-                .await?;
-            operations::Install::new(&config)
-                .packages(reinstall_specs)
-                .tree(tree)
-                .progress(progress.clone())
-                .install()
-                .await?;
-        } else {
-            return Err(eyre!("Operation cancelled."));
-        }
-    };
-
-    let mut has_dangling_rocks = true;
-    while has_dangling_rocks {
-        let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
-        let lockfile = tree.lockfile()?;
-        let dangling_rocks = lockfile
-            .rocks()
-            .iter()
-            .filter_map(|(pkg_id, _)| {
-                if lockfile.is_entrypoint(pkg_id) || lockfile.is_dependency(pkg_id) {
-                    None
-                } else {
-                    Some(pkg_id)
-                }
+        .filter(|package| !packages.contains(&package.id()))
+        .map(|package| format!("{}@{}", package.name(), package.version()))
+        .collect_vec();
+    if !orphaned_dependencies.is_empty() {
+        output::message(format!(
+            "Also removing dependencies that are no longer needed: {}",
+            orphaned_dependencies.join(", ")
+        ));
+    }
+
+    let removed = orphaned
+        .iter()
+        .map(|package| {
+            json!({
+                "name": package.name().to_string(),
+                "version": package.version().to_string(),
             })
-            .cloned()
-            .collect_vec();
-        if dangling_rocks.is_empty() {
-            has_dangling_rocks = false
-        } else {
-            operations::Remove::new(&config)
-                .packages(dangling_rocks)
-                .progress(progress.clone())
-                .remove()
-                .await?;
+        })
+        .collect_vec();
+
+    // All packages are removed with a single lockfile update.
+    operations::Remove::new(&config)
+        .packages(orphaned.into_iter().map(|package| package.id()))
+        .progress(MultiProgress::new_arc())
+        .remove()
+        .await?;
+
+    if is_json_output() {
+        for package in removed {
+            output::emit("removed", package);
         }
     }
 
@@ -5,8 +5,11 @@ use lux_lib::package::{PackageName, PackageReq};
 use lux_lib::progress::{MultiProgress, Progress, ProgressBar};
 use lux_lib::project::Project;
 use lux_lib::remote_package_db::RemotePackageDB;
-use lux_lib::rockspec::lua_dependency;
+use lux_lib::rockspec::{lua_dependency, Rockspec};
 use lux_lib::{config::Config, operations};
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
 
 #[derive(Args)]
 pub struct Update {
@@ -14,10 +17,16 @@ pub struct Update {
     #[arg(long)]
     no_integrity_check: bool,
 
-    /// Upgrade packages in the project's lux.toml (if operating on a project)
-    #[arg(long)]
+    /// Upgrade packages in the project's lux.toml (if operating on a project),
+    /// rewriting their version constraints to the latest available versions.
+    #[arg(long, visible_alias = "latest")]
     toml: bool,
 
+    /// Print the planned version changes without modifying
+    /// the lux.toml, the lockfile or the install tree.
+    #[arg(long)]
+    dry_run: bool,
+
     /// Packages to update.
     /// When used with the --toml flag in a project, these must be package names.
     packages: Option<Vec<PackageReq>>,
@@ -38,6 +47,10 @@ pub async fn update(args: Update, config: Config) -> Result<()> {
     let progress = MultiProgress::new_arc();
     progress.map(|p| p.add(ProgressBar::from("🔎 Looking for updates...".to_string())));
 
+    if args.dry_run {
+        return print_planned_updates(args, config).await;
+    }
+
     if args.toml {
         let mut project = Project::current()?.ok_or_eyre("No project found")?;
 
@@ -81,10 +94,105 @@ pub async fn update(args: Update, config: Config) -> Result<()> {
         .wrap_err("update failed.")?;
 
     if updated_packages.is_empty() {
-        println!("Nothing to update.");
+        output::message("Nothing to update.");
         return Ok(());
     }
 
+    if is_json_output() {
+        for package in updated_packages {
+            output::emit(
+                "updated",
+                json!({
+                    "name": package.name().to_string(),
+                    "version": package.version().to_string(),
+                }),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn print_planned_updates(args: Update, config: Config) -> Result<()> {
+    let mut planned_updates = Vec::new();
+
+    if args.toml {
+        let project = Project::current()?.ok_or_eyre("No project found")?;
+        let db =
+            RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new())).await?;
+        let project_toml = project.toml().into_local()?;
+        let upgrade_all = args.packages.is_none() && args.build.is_none() && args.test.is_none();
+        for (dependencies, package_names) in [
+            (
+                project_toml.dependencies(),
+                to_package_names(args.packages.as_ref())?,
+            ),
+            (
+                project_toml.build_dependencies(),
+                to_package_names(args.build.as_ref())?,
+            ),
+            (
+                project_toml.test_dependencies(),
+                to_package_names(args.test.as_ref())?,
+            ),
+        ] {
+            for dep in dependencies.current_platform().iter().filter(|dep| {
+                // Git dependencies are upgraded to their latest tag or commit,
+                // which we can't determine without cloning them.
+                dep.source().is_none()
+                    && match &package_names {
+                        Some(names) => names.contains(dep.name()),
+                        None => upgrade_all,
+                    }
+            }) {
+                if let Some(latest) = db.latest_match(&dep.name().clone().into(), None) {
+                    // `upgrade` pins the dependency to the latest version.
+                    if dep.version_req() != &latest.version().into_version_req() {
+                        planned_updates.push((
+                            dep.name().to_string(),
+                            dep.version_req().to_string(),
+                            latest.version().to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    } else {
+        planned_updates.extend(
+            operations::Update::new(&config)
+                .packages(args.packages)
+                .build_dependencies(args.build)
+                .test_dependencies(args.test)
+                .plan()
+                .await
+                .wrap_err("failed to determine updates.")?
+                .into_iter()
+                .map(|(package, version)| {
+                    (
+                        package.name().to_string(),
+                        package.version().to_string(),
+                        version.to_string(),
+                    )
+                }),
+        );
+    }
+
+    if is_json_output() {
+        for (name, from, to) in planned_updates {
+            output::emit(
+                "planned-update",
+                json!({ "name": name, "from": from, "to": to }),
+            );
+        }
+    } else if planned_updates.is_empty() {
+        output::message("Nothing to update.");
+    } else {
+        output::message("Planned updates:");
+        for (name, from, to) in planned_updates {
+            output::message(format!("  {name}: {from} -> {to}"));
+        }
+    }
+
     Ok(())
 }
 
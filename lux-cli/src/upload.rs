@@ -2,36 +2,62 @@ use clap::Args;
 use eyre::Result;
 use lux_lib::{config::Config, project::Project, upload::ProjectUpload};
 
+use crate::utils::source_metadata::populate_source;
+
 #[cfg(not(target_env = "msvc"))]
 use lux_lib::upload::SignatureProtocol;
 
+use crate::utils::output;
+
 #[derive(Args)]
 pub struct Upload {
     /// The protocol to use when signing upload artefacts
     #[cfg(not(target_env = "msvc"))]
     #[arg(long, default_value_t)]
     sign_protocol: SignatureProtocol,
+
+    /// Generate the rockspec and pack the source rock without uploading them.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[cfg(not(target_env = "msvc"))]
 pub async fn upload(data: Upload, config: Config) -> Result<()> {
-    let project = Project::current()?.unwrap();
+    let project = populate_source(Project::current()?.unwrap(), &config)?;
 
-    ProjectUpload::new(project, &config)
-        .sign_protocol(data.sign_protocol)
-        .upload_to_luarocks()
-        .await?;
+    let upload = ProjectUpload::new(project, &config).sign_protocol(data.sign_protocol);
+    if data.dry_run {
+        return preview(upload).await;
+    }
+    upload.upload_to_luarocks().await?;
 
     Ok(())
 }
 
 #[cfg(target_env = "msvc")]
-pub async fn upload(_data: Upload, config: Config) -> Result<()> {
-    let project = Project::current()?.unwrap();
+pub async fn upload(data: Upload, config: Config) -> Result<()> {
+    let project = populate_source(Project::current()?.unwrap(), &config)?;
 
-    ProjectUpload::new(project, &config)
-        .upload_to_luarocks()
-        .await?;
+    let upload = ProjectUpload::new(project, &config);
+    if data.dry_run {
+        return preview(upload).await;
+    }
+    upload.upload_to_luarocks().await?;
+
+    Ok(())
+}
 
+async fn preview(upload: ProjectUpload<'_>) -> Result<()> {
+    let preview = upload.dry_run().await?;
+    output::message(format!("{}:\n", preview.rockspec_file_name()));
+    output::message(preview.rockspec());
+    output::message(format!("{}:", preview.src_rock_file_name()));
+    for file in preview.src_rock_files() {
+        output::message(format!("  {file}"));
+    }
+    output::message(format!(
+        "\nDry run: nothing was uploaded to {}.",
+        preview.server()
+    ));
     Ok(())
 }
@@ -0,0 +1,108 @@
+//! The dependency graph shown by `lx tree` and `lx why`.
+
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::{Config, LuaVersion},
+    lockfile::{DependencyGraph, LocalPackage, LocalPackageLockType},
+    lua_rockspec::RemoteLuaRockspec,
+    project::Project,
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree::Tree,
+};
+
+pub(crate) struct LockedGraph {
+    pub graph: DependencyGraph,
+    /// The name of the project, or "user tree" if not in a project.
+    pub root_name: String,
+    tree: Tree,
+    /// The dependencies declared in the project's `lux.toml`, if in a project.
+    root_dependencies: Option<Vec<LuaDependencySpec>>,
+}
+
+impl LockedGraph {
+    /// Load the dependency graph of the current project's lockfile,
+    /// or of the user tree's lockfile if not in a project.
+    /// With `test` or `build`, the graph of the project's test or build dependencies is loaded.
+    pub fn load(config: &Config, test: bool, build: bool) -> Result<Self> {
+        match Project::current()? {
+            Some(project) => {
+                let toml = project.toml().into_local()?;
+                let (lock_type, tree, dependencies) = if test {
+                    (
+                        LocalPackageLockType::Test,
+                        project.test_tree(config)?,
+                        toml.test_dependencies(),
+                    )
+                } else if build {
+                    (
+                        LocalPackageLockType::Build,
+                        project.build_tree(config)?,
+                        toml.build_dependencies(),
+                    )
+                } else {
+                    (
+                        LocalPackageLockType::Regular,
+                        project.tree(config)?,
+                        toml.dependencies(),
+                    )
+                };
+                Ok(Self {
+                    graph: project.lockfile()?.dependency_graph(&lock_type),
+                    root_name: project.toml().package().to_string(),
+                    tree,
+                    root_dependencies: Some(dependencies.current_platform().clone()),
+                })
+            }
+            None if test || build => Err(eyre!(
+                "`--test` and `--build` can only be used in a project."
+            )),
+            None => {
+                let tree = config.user_tree(LuaVersion::from(config)?.clone())?;
+                Ok(Self {
+                    graph: tree.lockfile()?.dependency_graph(),
+                    root_name: "user tree".into(),
+                    tree,
+                    root_dependencies: None,
+                })
+            }
+        }
+    }
+
+    /// The version requirement that the `dependent` declares for the `package`,
+    /// or that the project declares (or the user requested) if the `dependent` is `None`.
+    /// Returns `None` if any version is allowed.
+    pub fn requirement(
+        &self,
+        dependent: Option<&LocalPackage>,
+        package: &LocalPackage,
+    ) -> Option<String> {
+        let dependencies = match dependent {
+            Some(dependent) => match self.installed_dependencies(dependent) {
+                Some(dependencies) => dependencies,
+                None => return package.constraint().to_string_opt(),
+            },
+            None => match &self.root_dependencies {
+                Some(dependencies) => dependencies.clone(),
+                // Entrypoints of the user tree are locked with the requested constraint
+                None => return package.constraint().to_string_opt(),
+            },
+        };
+        dependencies
+            .iter()
+            .find(|dependency| dependency.name() == package.name())
+            .map(|dependency| dependency.version_req())
+            .filter(|version_req| !version_req.is_any())
+            .map(|version_req| version_req.to_string())
+    }
+
+    fn installed_dependencies(&self, package: &LocalPackage) -> Option<Vec<LuaDependencySpec>> {
+        let rockspec_path = self
+            .tree
+            .installed_rock_layout(package)
+            .ok()?
+            .rockspec_path();
+        let content = std::fs::read_to_string(rockspec_path).ok()?;
+        let rockspec = RemoteLuaRockspec::new(&content).ok()?;
+        Some(rockspec.dependencies().current_platform().clone())
+    }
+}
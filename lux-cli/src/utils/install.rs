@@ -5,13 +5,16 @@ use inquire::Confirm;
 use lux_lib::{
     build::BuildBehaviour,
     lockfile::{LocalPackageId, OptState, PinnedState},
-    operations::install::PackageInstallSpec,
+    lua_rockspec::RockSourceSpec,
+    operations::{install::PackageInstallSpec, PrefetchedRock},
     package::PackageReq,
     tree::{self, RockMatches, Tree},
 };
 
+use crate::utils::prompt::PromptOrDefault;
+
 pub fn apply_build_behaviour(
-    package_reqs: Vec<PackageReq>,
+    package_reqs: Vec<(PackageReq, Option<RockSourceSpec>, Option<PrefetchedRock>)>,
     pin: PinnedState,
     force: bool,
     tree: &Tree,
@@ -19,7 +22,7 @@ pub fn apply_build_behaviour(
     let lockfile = tree.lockfile()?;
     Ok(package_reqs
         .into_iter()
-        .filter_map(|req| {
+        .filter_map(|(req, source, prefetched)| {
             let existing_packages: Vec<LocalPackageId> = match tree
                 .match_rocks_and(&req, |rock| pin == rock.pinned())
                 .expect("unable to get tree data")
@@ -38,7 +41,7 @@ pub fn apply_build_behaviour(
                 Some(BuildBehaviour::from(force))
             } else if Confirm::new(&format!("Package {req} already exists. Overwrite?"))
                 .with_default(false)
-                .prompt()
+                .prompt_or_default()
                 .expect("Error prompting for reinstall")
             {
                 Some(BuildBehaviour::Force)
@@ -50,6 +53,8 @@ pub fn apply_build_behaviour(
                     .build_behaviour(build_behaviour)
                     .pin(pin)
                     .opt(OptState::Required)
+                    .maybe_source(source)
+                    .maybe_prefetched(prefetched)
                     .build()
             })
         })
@@ -0,0 +1,52 @@
+//! Logging of the spans and events that Lux emits.
+//!
+//! Closed spans are logged with their duration, e.g. how long it took
+//! to resolve the dependencies or to build a package.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use eyre::Result;
+use tracing_subscriber::{
+    fmt::{self, format::FmtSpan},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+/// The environment variable that overrides the log filter for stderr, e.g. `LUX_LOG=lux_lib=trace`.
+pub const LOG_ENV: &str = "LUX_LOG";
+
+/// Log to stderr at a level that depends on the number of `--verbose` flags,
+/// and everything at the debug level to the `log_file`, if set.
+pub fn init_logging(verbose: u8, log_file: Option<&Path>) -> Result<()> {
+    let stderr_filter = match std::env::var(LOG_ENV) {
+        Ok(filter) => Some(EnvFilter::try_new(filter)?),
+        Err(_) => match verbose {
+            0 => None,
+            1 => Some(EnvFilter::new("lux_lib=info,lux_cli=info")),
+            _ => Some(EnvFilter::new("lux_lib=debug,lux_cli=debug")),
+        },
+    };
+    let stderr_layer = stderr_filter.map(|filter| {
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_target(false)
+            .with_filter(filter)
+    });
+    let file_layer = match log_file {
+        Some(log_file) => Some(
+            fmt::layer()
+                .with_writer(Arc::new(File::create(log_file)?))
+                .with_ansi(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(EnvFilter::new("lux_lib=debug,lux_cli=debug")),
+        ),
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(stderr_layer)
+        .with(file_layer)
+        .try_init()?;
+    Ok(())
+}
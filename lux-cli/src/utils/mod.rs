@@ -1,4 +1,10 @@
+pub(crate) mod dependency_graph;
 pub(crate) mod file_tree;
-pub(crate) mod github_metadata;
 pub(crate) mod install;
+pub mod logging;
+pub mod output;
+pub(crate) mod picker;
 pub(crate) mod project;
+pub mod prompt;
+pub(crate) mod source_metadata;
+pub(crate) mod watch;
@@ -0,0 +1,65 @@
+//! Structured output for the global `--json` flag.
+//!
+//! In JSON mode, each result is printed to stdout as a JSON object on its own line,
+//! with an `event` field that identifies its kind.
+//! Human-readable messages are printed to stderr instead.
+
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use eyre::Result;
+use serde_json::{json, Value};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_json_output() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+pub fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Print an event as a JSON line. `fields` must be a JSON object.
+pub fn emit(event: &str, fields: Value) {
+    let mut object = json!({ "event": event });
+    if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), fields) {
+        object.extend(fields);
+    }
+    println!("{object}");
+}
+
+/// Print a human-readable message, to stderr in JSON mode.
+pub fn message<M: Display>(message: M) {
+    if is_json_output() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Print a command's JSON output.
+/// In JSON mode, it is printed as an `event`, or as one `event` per element if it is an array.
+pub fn print_json<V: Into<Value>>(event: &str, value: V) -> Result<()> {
+    match value.into() {
+        Value::Array(values) if is_json_output() => {
+            values.into_iter().for_each(|value| emit(event, value))
+        }
+        value if is_json_output() => emit(event, value),
+        value => println!("{}", serde_json::to_string_pretty(&value)?),
+    }
+    Ok(())
+}
+
+/// Print an error as an `error` event, including the errors that caused it.
+pub fn emit_error(err: &eyre::Report) {
+    emit(
+        "error",
+        json!({
+            "message": err.to_string(),
+            "causes": err.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+        }),
+    );
+}
@@ -0,0 +1,103 @@
+//! Interactive selection of dependencies from the rock servers' manifests.
+
+use std::sync::Arc;
+
+use eyre::Result;
+use inquire::{autocompletion::Replacement, Autocomplete, CustomUserError, Select, Text};
+use itertools::Itertools;
+use lux_lib::{
+    package::{PackageName, PackageReq},
+    remote_package_db::RemotePackageDB,
+};
+use nucleo::{
+    pattern::{CaseMatching, Normalization, Pattern},
+    Matcher,
+};
+
+use super::prompt::PromptOrDefault;
+
+/// The maximum number of package names suggested while typing.
+const MAX_SUGGESTIONS: usize = 15;
+
+/// Let the user pick dependencies by fuzzy-searching the package names
+/// in the rock servers' manifests and choosing the lowest version they need.
+/// Stops when the user enters an empty package name.
+pub(crate) fn pick_dependencies(db: &RemotePackageDB) -> Result<Vec<PackageReq>> {
+    let completer = PackageNameCompleter {
+        names: Arc::new(db.package_names().iter().map(ToString::to_string).collect()),
+    };
+    let mut dependencies = Vec::new();
+    loop {
+        let name = Text::new("Dependency:")
+            .with_autocomplete(completer.clone())
+            .with_help_message("Type to search, tab to complete. Leave empty to finish.")
+            .prompt_or_default()?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Ok(dependencies);
+        }
+        let versions = db.versions(&PackageName::new(name.into()));
+        if versions.is_empty() {
+            eprintln!("Package {name} not found.");
+            continue;
+        }
+        let version = Select::new(&format!("Lowest version of {name}:"), versions)
+            .with_help_message("The latest version is listed first.")
+            .prompt_or_default()?;
+        dependencies.push(format!("{name} >= {version}").parse()?);
+    }
+}
+
+#[derive(Clone)]
+struct PackageNameCompleter {
+    names: Arc<Vec<String>>,
+}
+
+impl PackageNameCompleter {
+    fn matches(&self, input: &str) -> Vec<String> {
+        if input.trim().is_empty() {
+            return Vec::new();
+        }
+        let mut matcher = Matcher::new(nucleo::Config::DEFAULT);
+        Pattern::parse(input, CaseMatching::Ignore, Normalization::Smart)
+            .match_list(self.names.iter(), &mut matcher)
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(name, _)| name.clone())
+            .collect_vec()
+    }
+}
+
+impl Autocomplete for PackageNameCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(self.matches(input))
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        Ok(highlighted_suggestion.or_else(|| self.matches(input).into_iter().next()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_package_names() {
+        let completer = PackageNameCompleter {
+            names: Arc::new(vec![
+                "busted".into(),
+                "luafilesystem".into(),
+                "lua-cjson".into(),
+                "penlight".into(),
+            ]),
+        };
+        assert_eq!(completer.matches("lfs").first().unwrap(), "luafilesystem");
+        assert_eq!(completer.matches("cjson"), vec!["lua-cjson".to_string()]);
+        assert!(completer.matches("").is_empty());
+    }
+}
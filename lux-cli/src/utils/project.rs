@@ -4,7 +4,8 @@ use eyre::{Context, Result};
 use lux_lib::{
     config::{Config, LuaVersion},
     git::shorthand::GitUrlShorthand,
-    operations::Sync,
+    lockfile::{LocalPackage, LocalPackageLockType},
+    operations::{Sync, SyncReport},
     package::PackageReq,
     progress::{MultiProgress, Progress},
     project::Project,
@@ -44,43 +45,69 @@ pub fn current_project_or_user_tree(config: &Config) -> Result<Tree> {
     })
 }
 
+/// All packages in the current project's lockfile, including test and build dependencies,
+/// or the packages in the user tree if not in a project.
+pub fn locked_packages(config: &Config) -> Result<Vec<LocalPackage>> {
+    Ok(match Project::current()? {
+        Some(project) => {
+            let lockfile = project.lockfile()?;
+            [
+                LocalPackageLockType::Regular,
+                LocalPackageLockType::Test,
+                LocalPackageLockType::Build,
+            ]
+            .iter()
+            .flat_map(|lock_type| {
+                lockfile
+                    .dependency_graph(lock_type)
+                    .packages()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+        }
+        None => {
+            let lua_version = LuaVersion::from(config)?.clone();
+            let tree = config.user_tree(lua_version)?;
+            tree.lockfile()?.rocks().values().cloned().collect()
+        }
+    })
+}
+
 pub async fn sync_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
-) -> Result<()> {
+) -> Result<SyncReport> {
     // NOTE: We only update the lockfile if one exists.
     // Otherwise, the next `lx build` will remove the packages.
     Sync::new(project, config)
         .progress(progress)
         .sync_dependencies()
         .await
-        .wrap_err("syncing dependencies with the project lockfile failed.")?;
-    Ok(())
+        .wrap_err("syncing dependencies with the project lockfile failed.")
 }
 
 pub async fn sync_build_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
-) -> Result<()> {
+) -> Result<SyncReport> {
     Sync::new(project, config)
         .progress(progress.clone())
         .sync_build_dependencies()
         .await
-        .wrap_err("syncing build dependencies with the project lockfile failed.")?;
-    Ok(())
+        .wrap_err("syncing build dependencies with the project lockfile failed.")
 }
 
 pub async fn sync_test_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
-) -> Result<()> {
+) -> Result<SyncReport> {
     Sync::new(project, config)
         .progress(progress.clone())
         .sync_test_dependencies()
         .await
-        .wrap_err("syncing test dependencies with the project lockfile failed.")?;
-    Ok(())
+        .wrap_err("syncing test dependencies with the project lockfile failed.")
 }
@@ -0,0 +1,155 @@
+//! Prompts that can be answered without user input.
+//!
+//! If `LUX_NONINTERACTIVE` is set to a truthy value (e.g. by the `--no-input` flag),
+//! prompts take their default answer, or fail if they don't have one.
+//! If `LUX_ASSUME_YES` is set (e.g. by the `--yes` flag), confirmations are answered with yes
+//! and all other prompts behave as in non-interactive mode.
+
+use std::io::IsTerminal;
+
+use eyre::{eyre, Result};
+use inquire::{Confirm, Password, Select, Text};
+
+pub const NON_INTERACTIVE_ENV: &str = "LUX_NONINTERACTIVE";
+pub const ASSUME_YES_ENV: &str = "LUX_ASSUME_YES";
+
+/// How prompts are answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMode {
+    /// Ask the user.
+    Interactive,
+    /// Take the default answer, or fail if there is none.
+    Defaults,
+    /// Answer confirmations with yes, and take the default answer for other prompts.
+    AssumeYes,
+}
+
+impl PromptMode {
+    pub fn current() -> Self {
+        if env_is_truthy(ASSUME_YES_ENV) {
+            Self::AssumeYes
+        } else if env_is_truthy(NON_INTERACTIVE_ENV) {
+            Self::Defaults
+        } else {
+            Self::Interactive
+        }
+    }
+}
+
+fn env_is_truthy(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| is_truthy(&value))
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+pub fn is_non_interactive() -> bool {
+    PromptMode::current() != PromptMode::Interactive
+}
+
+/// Whether the user can answer prompts, i.e. prompts are enabled and stdin is a terminal.
+pub fn is_interactive() -> bool {
+    !is_non_interactive() && std::io::stdin().is_terminal()
+}
+
+pub trait PromptOrDefault: Sized {
+    type Output;
+
+    /// Prompt the user, or answer without user input in non-interactive mode.
+    fn prompt_or_default(self) -> Result<Self::Output> {
+        self.answer(PromptMode::current())
+    }
+
+    fn answer(self, mode: PromptMode) -> Result<Self::Output>;
+}
+
+impl PromptOrDefault for Confirm<'_> {
+    type Output = bool;
+
+    fn answer(self, mode: PromptMode) -> Result<bool> {
+        match mode {
+            PromptMode::Interactive => Ok(self.prompt()?),
+            PromptMode::Defaults => self.default.ok_or_else(|| no_default(self.message)),
+            PromptMode::AssumeYes => Ok(true),
+        }
+    }
+}
+
+impl PromptOrDefault for Text<'_> {
+    type Output = String;
+
+    fn answer(self, mode: PromptMode) -> Result<String> {
+        match mode {
+            PromptMode::Interactive => Ok(self.prompt()?),
+            PromptMode::Defaults | PromptMode::AssumeYes => self
+                .default
+                .map(|default| default.to_string())
+                .ok_or_else(|| no_default(self.message)),
+        }
+    }
+}
+
+impl PromptOrDefault for Password<'_> {
+    type Output = String;
+
+    fn answer(self, mode: PromptMode) -> Result<String> {
+        match mode {
+            PromptMode::Interactive => Ok(self.prompt()?),
+            PromptMode::Defaults | PromptMode::AssumeYes => Err(no_default(self.message)),
+        }
+    }
+}
+
+impl<T: std::fmt::Display> PromptOrDefault for Select<'_, T> {
+    type Output = T;
+
+    fn answer(self, mode: PromptMode) -> Result<T> {
+        match mode {
+            PromptMode::Interactive => Ok(self.prompt()?),
+            PromptMode::Defaults | PromptMode::AssumeYes => Err(no_default(self.message)),
+        }
+    }
+}
+
+fn no_default(message: &str) -> eyre::Report {
+    eyre!("cannot answer '{message}' in non-interactive mode. Pass the answer as an argument instead.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truthy_values() {
+        for value in ["1", "true", "TRUE", "yes", "on", " 1 "] {
+            assert!(is_truthy(value), "{value}");
+        }
+        for value in ["", "0", "false", "no", "off"] {
+            assert!(!is_truthy(value), "{value}");
+        }
+    }
+
+    #[test]
+    fn answer_confirm_without_input() {
+        let confirm = || Confirm::new("Overwrite?").with_default(false);
+        assert!(!confirm().answer(PromptMode::Defaults).unwrap());
+        assert!(confirm().answer(PromptMode::AssumeYes).unwrap());
+        assert!(Confirm::new("Overwrite?")
+            .answer(PromptMode::Defaults)
+            .is_err());
+    }
+
+    #[test]
+    fn answer_text_without_input() {
+        let text = Text::new("Name:").with_default("foo");
+        assert_eq!(text.answer(PromptMode::AssumeYes).unwrap(), "foo");
+        assert!(Text::new("Name:").answer(PromptMode::Defaults).is_err());
+        assert!(Select::new("Pick:", vec!["a", "b"])
+            .answer(PromptMode::AssumeYes)
+            .is_err());
+    }
+}
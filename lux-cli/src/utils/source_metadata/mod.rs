@@ -0,0 +1,241 @@
+//! Metadata about a project's source repository:
+//! the repository metadata used to populate a new project,
+//! and the git source of a release, used to populate generated rockspecs.
+
+use eyre::eyre;
+use eyre::Result;
+use git2::Repository;
+use lux_lib::config::Config;
+use lux_lib::package::PackageVersion;
+use lux_lib::project::Project;
+use path_absolutize::Absolutize as _;
+use provider::RemoteRepo;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+mod provider;
+
+#[derive(Debug)]
+pub struct RepoMetadata {
+    pub name: String,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub labels: Option<Vec<String>>,
+    pub contributors: Vec<String>,
+}
+
+impl RepoMetadata {
+    pub fn default(path: &Path) -> io::Result<Self> {
+        Ok(RepoMetadata {
+            name: path
+                .absolutize()?
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            description: None,
+            license: None,
+            contributors: vec![whoami::realname()],
+            labels: None,
+        })
+    }
+}
+
+/// Retrieves metadata for a given directory.
+/// The metadata is fetched from the host of the `origin` remote,
+/// falling back to the local git repository if the host is unknown or unreachable.
+pub async fn get_metadata_for(
+    directory: Option<&PathBuf>,
+    config: &Config,
+) -> Result<Option<RepoMetadata>> {
+    let repo = match directory {
+        Some(path) => Repository::open(path)?,
+        None => Repository::open_from_env()?,
+    };
+    let remote_url = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().map(String::from));
+    let remote = match remote_url {
+        Some(remote_url) => Some(RemoteRepo::parse(&remote_url)?),
+        None => None,
+    };
+    let metadata = match &remote {
+        Some(remote) if remote.host == "github.com" => provider::fetch_github(remote, config).await,
+        Some(remote) if remote.host == "codeberg.org" => {
+            provider::fetch_codeberg(remote, config).await
+        }
+        Some(remote) if remote.host == "gitlab.com" || remote.host.starts_with("gitlab.") => {
+            provider::fetch_gitlab(remote, config).await
+        }
+        Some(remote) => Err(eyre!("no metadata provider for {}", remote.host)),
+        None => Err(eyre!("no remote `origin`")),
+    };
+    match metadata {
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(_) => Ok(Some(provider::local_metadata(&repo, remote.as_ref())?)),
+    }
+}
+
+/// The git source of a release.
+#[derive(Debug, PartialEq)]
+pub struct GitSourceMetadata {
+    /// The `git+https` URL of the `origin` remote.
+    pub url: String,
+    /// The tag that points to the current HEAD.
+    pub tag: String,
+}
+
+/// Fill in the rockspec `source.url` and `source.tag` of a release from the `origin` remote
+/// and the tag that points to the current HEAD, unless the `lux.toml` sets a `source.url`.
+/// Unless lux is offline, this fails if the tag does not exist on the remote,
+/// as the rockspec would not be installable.
+pub fn populate_source(project: Project, config: &Config) -> Result<Project> {
+    if !needs_git_source(&project) {
+        return Ok(project);
+    }
+    match detect_git_source(project.root())? {
+        Some(source) => {
+            if !config.offline() {
+                verify_remote_tag(&source)?;
+            }
+            Ok(project.with_git_source(source.url, source.tag))
+        }
+        None => Ok(project),
+    }
+}
+
+/// Like [`populate_source`], but leaves the project as it is if HEAD is not tagged,
+/// and doesn't check that the tag exists on the remote.
+/// For packing, where the rockspec doesn't need to be installable from the remote.
+pub fn try_populate_source(project: Project) -> Project {
+    if !needs_git_source(&project) {
+        return project;
+    }
+    match detect_git_source(project.root()) {
+        Ok(Some(source)) => project.with_git_source(source.url, source.tag),
+        _ => project,
+    }
+}
+
+fn needs_git_source(project: &Project) -> bool {
+    let is_release = matches!(project.toml().version(), Ok(PackageVersion::SemVer(_)));
+    is_release && !project.toml().has_source_url()
+}
+
+/// Detects the git source from the `origin` remote and the tag that points to the current HEAD,
+/// preferring SemVer tags.
+/// Returns `None` if the project is not in a git repository with an `origin` remote.
+pub fn detect_git_source(project_root: &Path) -> Result<Option<GitSourceMetadata>> {
+    let url = match origin_source_url(project_root)? {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+    let repo = Repository::discover(project_root)?;
+    let head = repo.head()?.peel_to_commit()?.id();
+    let mut tags = Vec::new();
+    for tag in repo.tag_names(None)?.iter().flatten() {
+        let target = repo
+            .revparse_single(&format!("refs/tags/{tag}"))?
+            .peel_to_commit()?
+            .id();
+        if target == head {
+            tags.push(tag.to_string());
+        }
+    }
+    let tag = tags
+        .iter()
+        .find(|tag| {
+            matches!(
+                PackageVersion::parse(tag.trim_start_matches('v')),
+                Ok(PackageVersion::SemVer(_))
+            )
+        })
+        .or(tags.first())
+        .cloned()
+        .ok_or_else(|| {
+            eyre!(
+                "cannot determine the rockspec source: HEAD is not tagged and lux.toml has no `source.url`.\n\
+                Tag the release (e.g. with `lx version <bump> --tag`) or set a `source.url`."
+            )
+        })?;
+    Ok(Some(GitSourceMetadata { url, tag }))
+}
+
+/// The `git+https` URL of the `origin` remote, for use as a rockspec `source.url`.
+/// Returns `None` if the project is not in a git repository with an `origin` remote.
+pub fn origin_source_url(project_root: &Path) -> Result<Option<String>> {
+    let repo = match Repository::discover(project_root) {
+        Ok(repo) => repo,
+        Err(_) => return Ok(None),
+    };
+    let remote_url = match repo.find_remote("origin") {
+        Ok(remote) => match remote.url() {
+            Some(url) => url.to_string(),
+            None => return Ok(None),
+        },
+        Err(_) => return Ok(None),
+    };
+    let parsed_url = git_url_parse::GitUrl::parse(&remote_url)?;
+    let host = parsed_url.host.ok_or_else(|| {
+        eyre!("unable to determine the host of the remote `origin` ({remote_url})")
+    })?;
+    Ok(Some(format!(
+        "git+https://{host}/{}.git",
+        parsed_url.fullname
+    )))
+}
+
+/// Check that the tag has been pushed to the remote.
+fn verify_remote_tag(source: &GitSourceMetadata) -> Result<()> {
+    let temp_dir = tempdir::TempDir::new("lux-git-meta")?;
+    let repo = Repository::init_bare(&temp_dir)?;
+    let fetch_url = source.url.trim_start_matches("git+");
+    let mut remote = repo.remote_anonymous(fetch_url)?;
+    remote.connect(git2::Direction::Fetch)?;
+    let tag_ref = format!("refs/tags/{}", source.tag);
+    let exists = remote.list()?.iter().any(|head| head.name() == tag_ref);
+    remote.disconnect()?;
+    if exists {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "tag {} does not exist on {fetch_url}.\nPush it with `git push origin {}`.",
+            source.tag,
+            source.tag
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_git_source_from_remote_and_tag() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        repo.remote("origin", "git@github.com:lumen-oss/lux.git")
+            .unwrap();
+        let signature = git2::Signature::now("lux", "lux@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        let commit = repo.find_object(commit, None).unwrap();
+        repo.tag_lightweight("latest", &commit, false).unwrap();
+        repo.tag("v1.0.0", &commit, &signature, "v1.0.0", false)
+            .unwrap();
+
+        assert_eq!(
+            detect_git_source(dir.path()).unwrap(),
+            Some(GitSourceMetadata {
+                url: "git+https://github.com/lumen-oss/lux.git".into(),
+                tag: "v1.0.0".into(),
+            })
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Providers of repository metadata for the hosts of git remotes.
+
+use eyre::{eyre, Result};
+use git2::Repository;
+use itertools::Itertools;
+use lux_lib::config::Config;
+use path_absolutize::Absolutize as _;
+use serde_json::Value;
+use url::Url;
+
+use super::RepoMetadata;
+
+const USER_AGENT: &str = concat!("lux/", env!("CARGO_PKG_VERSION"));
+
+/// A repository on a git host, parsed from a remote URL.
+#[derive(Debug, PartialEq)]
+pub struct RemoteRepo {
+    pub host: String,
+    pub owner: String,
+    pub name: String,
+    /// The path of the repository on the host, e.g. `group/subgroup/name` on GitLab.
+    pub fullname: String,
+}
+
+impl RemoteRepo {
+    pub fn parse(url: &str) -> Result<Self> {
+        let parsed_url = git_url_parse::GitUrl::parse(url)?;
+        match (parsed_url.host, parsed_url.owner) {
+            (Some(host), Some(owner)) => Ok(Self {
+                host,
+                owner,
+                name: parsed_url.name,
+                fullname: parsed_url.fullname,
+            }),
+            _ => Err(eyre!("unable to parse remote `origin` - it's likely that your upstream remote is malformed!")),
+        }
+    }
+}
+
+/// Fetches the metadata of a repository on GitHub.
+pub async fn fetch_github(repo: &RemoteRepo, config: &Config) -> Result<RepoMetadata> {
+    let api_url = format!("https://api.github.com/repos/{}/{}", repo.owner, repo.name);
+
+    let repo_data = get_json(&api_url, config).await?;
+    let contributors = get_json(&format!("{api_url}/contributors"), config).await?;
+
+    Ok(RepoMetadata {
+        name: string_field(&repo_data, "name").unwrap_or(repo.name.clone()),
+        description: string_field(&repo_data, "description"),
+        license: repo_data
+            .get("license")
+            .and_then(|license| string_field(license, "name")),
+        labels: string_array_field(&repo_data, "topics"),
+        contributors: contributors
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|contributor| string_field(contributor, "login"))
+            .collect(),
+    })
+}
+
+/// Fetches the metadata of a repository on gitlab.com or a self-hosted GitLab instance.
+pub async fn fetch_gitlab(repo: &RemoteRepo, config: &Config) -> Result<RepoMetadata> {
+    let project_id = url::form_urlencoded::byte_serialize(repo.fullname.as_bytes()).join("");
+    let api_url = format!("https://{}/api/v4/projects/{project_id}", repo.host);
+
+    let repo_data = get_json(&format!("{api_url}?license=true"), config).await?;
+    let contributors = get_json(&format!("{api_url}/repository/contributors"), config).await?;
+
+    Ok(RepoMetadata {
+        name: string_field(&repo_data, "path").unwrap_or(repo.name.clone()),
+        description: string_field(&repo_data, "description"),
+        license: repo_data.get("license").and_then(gitlab_license),
+        labels: string_array_field(&repo_data, "topics"),
+        contributors: contributors
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|contributor| string_field(contributor, "name"))
+            .collect(),
+    })
+}
+
+/// The `nickname` of a GitLab license is null for most licenses,
+/// so we use its `key` (e.g. `mit`), or else its `name`.
+fn gitlab_license(license: &Value) -> Option<String> {
+    string_field(license, "key").or_else(|| string_field(license, "name"))
+}
+
+/// Fetches the metadata of a repository on Codeberg, using the Forgejo API.
+pub async fn fetch_codeberg(repo: &RemoteRepo, config: &Config) -> Result<RepoMetadata> {
+    let api_url = format!(
+        "https://{}/api/v1/repos/{}/{}",
+        repo.host, repo.owner, repo.name
+    );
+
+    let repo_data = get_json(&api_url, config).await?;
+    let topics = get_json(&format!("{api_url}/topics"), config).await?;
+
+    Ok(RepoMetadata {
+        name: string_field(&repo_data, "name").unwrap_or(repo.name.clone()),
+        description: string_field(&repo_data, "description"),
+        license: string_array_field(&repo_data, "licenses")
+            .and_then(|licenses| licenses.into_iter().next()),
+        labels: string_array_field(&topics, "topics"),
+        // Forgejo has no contributors API, so we use the owner
+        contributors: repo_data
+            .get("owner")
+            .and_then(|owner| {
+                string_field(owner, "full_name").or_else(|| string_field(owner, "login"))
+            })
+            .into_iter()
+            .collect(),
+    })
+}
+
+/// Metadata from the local git repository,
+/// for remotes on unknown hosts, or if the host can't be reached.
+pub fn local_metadata(repo: &Repository, remote: Option<&RemoteRepo>) -> Result<RepoMetadata> {
+    let name = match remote {
+        Some(remote) => remote.name.clone(),
+        None => repo
+            .workdir()
+            .unwrap_or(repo.path())
+            .absolutize()?
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    };
+    let authors = commit_authors(repo).unwrap_or_default();
+    Ok(RepoMetadata {
+        name,
+        description: None,
+        license: None,
+        labels: None,
+        contributors: if authors.is_empty() {
+            vec![whoami::realname()]
+        } else {
+            authors
+        },
+    })
+}
+
+/// The commit authors, from the most to the least active.
+fn commit_authors(repo: &Repository) -> Result<Vec<String>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    let authors = revwalk
+        .filter_map(|oid| repo.find_commit(oid.ok()?).ok())
+        .filter_map(|commit| commit.author().name().map(String::from))
+        .counts()
+        .into_iter()
+        .sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)))
+        .map(|(author, _)| author)
+        .collect();
+    Ok(authors)
+}
+
+async fn get_json(url: &str, config: &Config) -> Result<Value> {
+    let url = Url::parse(url)?;
+    Ok(config
+        .http_client(&url)?
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+fn string_field(value: &Value, field: &str) -> Option<String> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+}
+
+fn string_array_field(value: &Value, field: &str) -> Option<Vec<String>> {
+    let values = value
+        .get(field)?
+        .as_array()?
+        .iter()
+        .filter_map(|value| value.as_str().map(String::from))
+        .collect_vec();
+    (!values.is_empty()).then_some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remote_repo() {
+        assert_eq!(
+            RemoteRepo::parse("git@codeberg.org:owner/project.git").unwrap(),
+            RemoteRepo {
+                host: "codeberg.org".into(),
+                owner: "owner".into(),
+                name: "project".into(),
+                fullname: "owner/project".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn gitlab_license_without_nickname() {
+        let license = serde_json::json!({
+            "key": "mit",
+            "name": "MIT License",
+            "nickname": null,
+        });
+        assert_eq!(gitlab_license(&license), Some("mit".into()));
+        let license = serde_json::json!({ "name": "MIT License" });
+        assert_eq!(gitlab_license(&license), Some("MIT License".into()));
+    }
+
+    #[test]
+    fn local_metadata_from_commit_authors() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let mut parent = None;
+        for author in ["John", "Jane", "Jane"] {
+            let signature = git2::Signature::now(author, "author@example.com").unwrap();
+            let parents = parent.iter().collect_vec();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    "commit",
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            parent = Some(repo.find_commit(commit).unwrap());
+        }
+        let metadata = local_metadata(&repo, None).unwrap();
+        assert_eq!(
+            metadata.name,
+            dir.path().file_name().unwrap().to_string_lossy()
+        );
+        assert_eq!(metadata.contributors, vec!["Jane", "John"]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    future::Future,
+    path::{Component, Path},
+    time::Duration,
+};
+
+use eyre::{OptionExt, Result};
+use ignore::gitignore::Gitignore;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+/// How long to wait for further changes before rerunning.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Run `run`, then rerun it whenever files under `root` change,
+/// until the process is interrupted.
+pub(crate) async fn watch<F, Fut>(root: &Path, action: &str, mut run: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            let _ = tx.send(event);
+        }
+    })?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    let filter = WatchFilter::new(root);
+
+    loop {
+        eprintln!("⟳ {action}...");
+        match run().await {
+            Ok(()) => eprintln!("✔ {action} succeeded."),
+            Err(err) => eprintln!("{err:?}\n✗ {action} failed."),
+        }
+        eprintln!(
+            "👀 Watching {} for changes (press Ctrl+C to stop)",
+            root.display()
+        );
+        loop {
+            let event = rx.recv().await.ok_or_eyre("the file watcher stopped")?;
+            if filter.is_relevant(&event) {
+                break;
+            }
+        }
+        // Editors often write files in several steps, so we wait until things calm down.
+        while let Ok(Some(_)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+    }
+}
+
+/// Ignores changes to files that are written by lux itself
+/// (the project tree and the lockfile), hidden files and git-ignored files.
+struct WatchFilter<'a> {
+    root: &'a Path,
+    gitignore: Gitignore,
+}
+
+impl<'a> WatchFilter<'a> {
+    fn new(root: &'a Path) -> Self {
+        let (gitignore, _) = Gitignore::new(root.join(".gitignore"));
+        Self { root, gitignore }
+    }
+
+    fn is_relevant(&self, event: &Event) -> bool {
+        matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && event.paths.iter().any(|path| self.is_watched(path))
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        let relative_path = match path.strip_prefix(self.root) {
+            Ok(relative_path) => relative_path,
+            Err(_) => return false,
+        };
+        let is_hidden = relative_path.components().any(|component| {
+            matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+        });
+        !is_hidden
+            && relative_path != Path::new("lux.lock")
+            && !self
+                .gitignore
+                .matched_path_or_any_parents(relative_path, path.is_dir())
+                .is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn watch_filter() {
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child(".gitignore").write_str("*.o\n").unwrap();
+        let filter = WatchFilter::new(root.path());
+        assert!(filter.is_watched(&root.join("src").join("main.lua")));
+        assert!(filter.is_watched(&root.join("lux.toml")));
+        assert!(!filter.is_watched(&root.join("lux.lock")));
+        assert!(!filter.is_watched(&root.join(".lux").join("5.4").join("foo.lua")));
+        assert!(!filter.is_watched(&root.join("src").join("foo.o")));
+        assert!(!filter.is_watched(Path::new("/elsewhere/foo.lua")));
+    }
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{config::Config, operations, progress::MultiProgress, project::Project};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct Vendor {
+    /// The directory to vendor the sources into.{n}
+    /// Defaults to `vendor` in the project root.
+    #[arg(long, value_name = "dir")]
+    dir: Option<PathBuf>,
+}
+
+/// Download the sources of all locked dependencies into a vendor directory.
+pub async fn vendor(args: Vendor, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let vendor_dir = operations::Vendor::new(&project, &config)
+        .maybe_vendor_dir(args.dir)
+        .progress(MultiProgress::new_arc())
+        .vendor()
+        .await?;
+    output::message(format!(
+        "Vendored the locked dependencies into {}.",
+        vendor_dir.root().display()
+    ));
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{config::Config, operations::CreateVenv, progress::MultiProgress, project::Project};
+
+use crate::utils::output;
+
+#[derive(Args)]
+pub struct VenvArgs {
+    /// The directory to create the environment in.{n}
+    /// Defaults to the `.lux` directory of the current project{n}
+    /// or of the current directory.
+    path: Option<PathBuf>,
+}
+
+/// Create an isolated environment with its own Lua installation and tree.
+pub async fn venv(data: VenvArgs, config: Config) -> Result<()> {
+    let root = match data.path {
+        Some(path) => path,
+        None => match Project::current()? {
+            Some(project) => project.root().join(".lux"),
+            None => std::env::current_dir()?.join(".lux"),
+        },
+    };
+
+    let venv = CreateVenv::new(&root, &config)
+        .lx(std::env::current_exe()?)
+        .progress(MultiProgress::new_arc())
+        .create()
+        .await?;
+
+    let root = venv.root().display();
+    output::message(format!(
+        "Created a Lua {} environment in {root}",
+        venv.lua_version()
+    ));
+    output::message("Activate it with one of:");
+    output::message(format!("  source {root}/activate       (bash/zsh)"));
+    output::message(format!("  source {root}/activate.fish  (fish)"));
+    output::message(format!("  . {root}/activate.ps1        (PowerShell)"));
+
+    Ok(())
+}
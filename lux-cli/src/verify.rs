@@ -0,0 +1,93 @@
+use clap::Args;
+use eyre::{eyre, Result};
+use itertools::Itertools;
+use lux_lib::{config::Config, operations, progress::MultiProgress};
+
+use serde_json::json;
+
+use crate::utils::{
+    output::{self, is_json_output},
+    project::current_project_or_user_tree,
+};
+
+#[derive(Args)]
+pub struct Verify {
+    /// Reinstall packages whose files have been modified or deleted,
+    /// and remove orphaned rock directories.
+    #[arg(long)]
+    fix: bool,
+}
+
+/// Verify the installed tree against the checksums recorded at install time.
+pub async fn verify(args: Verify, config: Config) -> Result<()> {
+    let tree = current_project_or_user_tree(&config)?;
+    let report = operations::Verify::new(&tree, &config)
+        .fix(args.fix)
+        .progress(MultiProgress::new_arc())
+        .verify()
+        .await?;
+
+    if is_json_output() {
+        output::emit(
+            "verified",
+            json!({
+                "corrupted": report
+                    .corrupted()
+                    .iter()
+                    .map(|corrupted| json!({
+                        "name": corrupted.package().name().to_string(),
+                        "version": corrupted.package().version().to_string(),
+                        "modified": corrupted.modified().iter().sorted().collect_vec(),
+                        "deleted": corrupted.deleted().iter().sorted().collect_vec(),
+                    }))
+                    .collect_vec(),
+                "orphaned": report.orphaned(),
+                "unverified": report
+                    .unverified()
+                    .iter()
+                    .map(|package| json!({
+                        "name": package.name().to_string(),
+                        "version": package.version().to_string(),
+                    }))
+                    .collect_vec(),
+            }),
+        );
+    } else {
+        for corrupted in report.corrupted() {
+            let package = corrupted.package();
+            output::message(format!(
+                "{}@{} is corrupted:",
+                package.name(),
+                package.version()
+            ));
+            for path in corrupted.modified().iter().sorted() {
+                output::message(format!("  modified: {}", path.display()));
+            }
+            for path in corrupted.deleted().iter().sorted() {
+                output::message(format!("  deleted: {}", path.display()));
+            }
+        }
+        for path in report.orphaned() {
+            output::message(format!("orphaned: {}", path.display()));
+        }
+        for package in report.unverified() {
+            output::message(format!(
+                "{}@{} was installed without checksums and cannot be verified.",
+                package.name(),
+                package.version()
+            ));
+        }
+    }
+
+    if report.is_ok() {
+        output::message("All installed rocks match their recorded checksums.");
+        Ok(())
+    } else if args.fix {
+        output::message("Reinstalled corrupted rocks and removed orphaned rock directories.");
+        Ok(())
+    } else {
+        Err(eyre!(
+            "verification failed. Run `lx verify --fix` to reinstall corrupted rocks."
+        ))
+    }
+}
@@ -0,0 +1,87 @@
+use clap::Args;
+use eyre::{OptionExt, Result};
+use lux_lib::{
+    config::Config,
+    operations::BumpVersion,
+    package::VersionBump,
+    progress::{Progress, ProgressBar},
+    project::Project,
+    remote_package_db::RemotePackageDB,
+};
+use serde_json::json;
+
+use crate::utils::output::{self, is_json_output};
+
+#[derive(Args)]
+pub struct Version {
+    /// How to bump the version: `major`, `minor`, `patch`,{n}
+    /// or an explicit version, e.g. `2.0.0`.
+    bump: VersionBump,
+
+    /// Commit the `lux.toml` with a conventional commit message,{n}
+    /// e.g. `chore(release): 1.2.0`.
+    #[arg(long)]
+    commit: bool,
+
+    /// Create an annotated `v<version>` git tag. Implies `--commit`.
+    #[arg(long)]
+    tag: bool,
+
+    /// Add a heading for the new version below the `## [Unreleased]` heading{n}
+    /// of the project's `CHANGELOG.md`.
+    #[arg(long)]
+    changelog: bool,
+
+    /// Don't check the new version against the versions{n}
+    /// that have already been published to the rock servers.{n}
+    /// The check is also skipped with `--offline`.
+    #[arg(long)]
+    no_registry_check: bool,
+}
+
+/// Bump the version in the current project's `lux.toml`.
+pub async fn version(args: Version, config: Config) -> Result<()> {
+    let mut project = Project::current()?.ok_or_eyre("No project found")?;
+
+    let package_db = if args.no_registry_check || config.offline() {
+        None
+    } else {
+        Some(RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new())).await?)
+    };
+
+    let bumped = BumpVersion::new(&mut project, args.bump)
+        .maybe_package_db(package_db.as_ref())
+        .commit(args.commit)
+        .tag(args.tag)
+        .changelog(args.changelog)
+        .bump()
+        .await?;
+
+    if is_json_output() {
+        output::emit(
+            "version",
+            json!({
+                "package": project.toml().package().to_string(),
+                "previous": bumped.previous().to_string(),
+                "version": bumped.version().to_string(),
+                "tag": bumped.tag(),
+                "changelog": bumped.changelog(),
+            }),
+        );
+    } else {
+        output::message(format!(
+            "Bumped {} from {} to {}",
+            project.toml().package(),
+            bumped.previous(),
+            bumped.version()
+        ));
+        if let Some(changelog) = bumped.changelog() {
+            output::message(format!("Updated {}", changelog.display()));
+        }
+        if let Some(tag) = bumped.tag() {
+            output::message(format!("Created tag {tag}"));
+        }
+    }
+
+    Ok(())
+}
@@ -1,19 +1,91 @@
 use clap::Args;
-use eyre::Result;
-use lux_lib::{config::Config, lua_rockspec::LuaModule, package::PackageReq, which};
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::{Config, LuaVersion},
+    lua_rockspec::LuaModule,
+    package::PackageReq,
+    project::Project,
+    which::{self, Located, WhichError},
+};
+use serde_json::json;
+
+use crate::utils::output;
 
 #[derive(Args)]
 pub struct Which {
-    /// The module to search for.
+    /// The module or binary to search for.
     module: LuaModule,
     /// Only search in these packages.
     packages: Option<Vec<PackageReq>>,
+    /// Also show the package that provides the module or binary.
+    #[arg(long)]
+    owner: bool,
 }
 
+/// Searches the current project's tree, if in a project, then the user tree.
+/// Lua modules take precedence over binaries.
 pub fn which(args: Which, config: Config) -> Result<()> {
-    let path = which::Which::new(args.module, &config)
-        .packages(args.packages.unwrap_or_default())
-        .search()?;
-    print!("{}", path.display());
-    Ok(())
+    let project = Project::current()?;
+    let mut trees = Vec::new();
+    if let Some(project) = &project {
+        trees.push(project.tree(&config)?);
+    }
+    let lua_version = match &project {
+        Some(project) => project.lua_version(&config)?,
+        None => LuaVersion::from(&config)?.clone(),
+    };
+    trees.push(config.user_tree(lua_version)?);
+
+    let packages = args.packages.unwrap_or_default();
+    for tree in &trees {
+        match which::Which::new(args.module.clone(), &config)
+            .packages(packages.clone())
+            .tree(tree.clone())
+            .locate()
+        {
+            Ok(located) => {
+                print_located(&located, args.owner);
+                return Ok(());
+            }
+            Err(WhichError::ModuleNotFound(_)) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if !packages.is_empty() {
+        return Err(WhichError::ModuleNotFound(args.module).into());
+    }
+    let name = args.module.to_string();
+    for tree in &trees {
+        match which::which_binary(&name, tree) {
+            Ok(located) => {
+                print_located(&located, args.owner);
+                return Ok(());
+            }
+            Err(WhichError::BinaryNotFound(_)) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(eyre!(WhichError::BinaryNotFound(name))
+        .wrap_err(WhichError::ModuleNotFound(args.module).to_string()))
+}
+
+fn print_located(located: &Located, owner: bool) {
+    if output::is_json_output() {
+        output::emit(
+            "located",
+            json!({
+                "path": located.path(),
+                "name": located.package().name().to_string(),
+                "version": located.package().version().to_string(),
+            }),
+        );
+    } else if owner {
+        output::message(format!(
+            "{} ({})",
+            located.path().display(),
+            located.package().to_package()
+        ));
+    } else {
+        output::message(located.path().display());
+    }
 }
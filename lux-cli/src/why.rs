@@ -0,0 +1,105 @@
+use clap::Args;
+use eyre::{eyre, Result};
+use itertools::Itertools;
+use lux_lib::{config::Config, lockfile::LocalPackage, package::PackageReq};
+use serde_json::json;
+
+use crate::utils::{
+    dependency_graph::LockedGraph,
+    output::{self, is_json_output},
+};
+
+#[derive(Args)]
+pub struct Why {
+    /// The package to explain, e.g. `foo` or `foo@1.0.0`.
+    package: PackageReq,
+
+    /// Explain a test dependency of the current project.
+    #[arg(long, conflicts_with = "build")]
+    test: bool,
+
+    /// Explain a build dependency of the current project.
+    #[arg(long)]
+    build: bool,
+}
+
+/// Explain why a package is installed, by printing the chains of dependencies
+/// and the version requirements that lead to it.
+pub fn why(args: Why, config: Config) -> Result<()> {
+    let locked = LockedGraph::load(&config, args.test, args.build)?;
+
+    let packages = locked
+        .graph
+        .find(args.package.name())
+        .into_iter()
+        .filter(|package| args.package.version_req().matches(package.version()))
+        .sorted_by_key(|package| package.version().clone())
+        .collect_vec();
+    if packages.is_empty() {
+        return Err(eyre!("package {} is not installed.", args.package));
+    }
+
+    for package in packages {
+        explain(&locked, package);
+    }
+
+    Ok(())
+}
+
+fn explain(locked: &LockedGraph, package: &LocalPackage) {
+    let chains = locked.graph.dependency_chains(package);
+    if is_json_output() {
+        let chains = chains
+            .iter()
+            .map(|chain| {
+                let dependents = std::iter::once(None).chain(chain.iter().copied().map(Some));
+                dependents
+                    .zip(chain)
+                    .map(|(dependent, package)| {
+                        json!({
+                            "name": package.name().to_string(),
+                            "version": package.version().to_string(),
+                            "requirement": locked.requirement(dependent, package),
+                        })
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        output::emit(
+            "why",
+            json!({
+                "name": package.name().to_string(),
+                "version": package.version().to_string(),
+                "chains": chains,
+            }),
+        );
+        return;
+    }
+    output::message(format!(
+        "{}@{} is installed because:",
+        package.name(),
+        package.version()
+    ));
+    if chains.is_empty() {
+        output::message("  nothing depends on it (it is orphaned)");
+    }
+    for chain in chains {
+        let mut dependent: Option<&LocalPackage> = None;
+        for (depth, package) in chain.into_iter().enumerate() {
+            let requirer = match dependent {
+                Some(dependent) => format!("{}@{}", dependent.name(), dependent.version()),
+                None => locked.root_name.clone(),
+            };
+            output::message(format!(
+                "{}{requirer} requires {} {} -> {}",
+                "  ".repeat(depth + 1),
+                package.name(),
+                locked
+                    .requirement(dependent, package)
+                    .unwrap_or("(any version)".into()),
+                package.version()
+            ));
+            dependent = Some(package);
+        }
+    }
+}
@@ -27,6 +27,8 @@ pub(crate) struct RunBuildArgs<'a> {
     pub(crate) tree: &'a Tree,
     pub(crate) build_dir: &'a Path,
     pub(crate) progress: &'a Progress<ProgressBar>,
+    /// The directory in which to keep state for incremental rebuilds, if enabled.
+    pub(crate) build_state_dir: Option<&'a Path>,
 }
 
 pub(crate) trait BuildBackend {
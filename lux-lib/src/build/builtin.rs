@@ -11,6 +11,7 @@ use walkdir::WalkDir;
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
+        incremental::{self, BuildState},
         utils,
     },
     lua_rockspec::{BuiltinBuildSpec, LuaModule, ModuleSpec},
@@ -36,6 +37,7 @@ pub enum BuiltinBuildError {
 impl BuildBackend for BuiltinBuildSpec {
     type Err = BuiltinBuildError;
 
+    #[tracing::instrument(name = "builtin_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let lua = args.lua;
@@ -53,42 +55,65 @@ impl BuildBackend for BuiltinBuildSpec {
 
         progress.map(|p| p.set_position(modules.len() as u64));
 
+        // The build state only caches shared libraries
+        let mut build_state = args
+            .build_state_dir
+            .filter(|_| !config.static_libs())
+            .map(BuildState::load);
+
         for (destination_path, module_type) in modules.iter() {
+            if let ModuleSpec::SourcePath(source) = module_type {
+                if source.extension().is_none_or(|ext| ext != "c") {
+                    progress.map(|p| {
+                        p.set_message(format!(
+                            "Copying {} to {}...",
+                            &source.to_string_lossy(),
+                            &destination_path
+                        ))
+                    });
+                    let absolute_source_path = build_dir.join(source);
+                    utils::copy_lua_to_module_path(
+                        &absolute_source_path,
+                        destination_path,
+                        &output_paths.src,
+                    )?;
+                    continue;
+                }
+            }
+
+            if let Some(build_state) = build_state.as_mut() {
+                let fingerprint = incremental::fingerprint(
+                    destination_path,
+                    module_type,
+                    build_dir,
+                    lua,
+                    config,
+                )?;
+                if build_state.restore(destination_path, fingerprint, &output_paths.lib)? {
+                    progress.map(|p| p.set_message(format!("♻️ {destination_path} is up to date")));
+                    continue;
+                }
+            }
+
             match module_type {
                 ModuleSpec::SourcePath(source) => {
-                    if source.extension().map(|ext| ext == "c").unwrap_or(false) {
-                        progress.map(|p| {
-                            p.set_message(format!(
-                                "Compiling {} -> {}...",
-                                &source.to_string_lossy(),
-                                &destination_path
-                            ))
-                        });
-                        let absolute_source_paths = vec![build_dir.join(source)];
-                        utils::compile_c_files(
-                            &absolute_source_paths,
-                            destination_path,
-                            &output_paths.lib,
-                            lua,
-                            external_dependencies,
-                            config,
-                        )
-                        .await?
-                    } else {
-                        progress.map(|p| {
-                            p.set_message(format!(
-                                "Copying {} to {}...",
-                                &source.to_string_lossy(),
-                                &destination_path
-                            ))
-                        });
-                        let absolute_source_path = build_dir.join(source);
-                        utils::copy_lua_to_module_path(
-                            &absolute_source_path,
-                            destination_path,
-                            &output_paths.src,
-                        )?
-                    }
+                    progress.map(|p| {
+                        p.set_message(format!(
+                            "Compiling {} -> {}...",
+                            &source.to_string_lossy(),
+                            &destination_path
+                        ))
+                    });
+                    let absolute_source_paths = vec![build_dir.join(source)];
+                    utils::compile_c_files(
+                        &absolute_source_paths,
+                        destination_path,
+                        &output_paths.lib,
+                        lua,
+                        external_dependencies,
+                        config,
+                    )
+                    .await?
                 }
                 ModuleSpec::SourcePaths(files) => {
                     progress.map(|p| p.set_message("Compiling C files..."));
@@ -118,6 +143,14 @@ impl BuildBackend for BuiltinBuildSpec {
                     .await?
                 }
             }
+
+            if let Some(build_state) = &build_state {
+                build_state.record(destination_path, &output_paths.lib)?;
+            }
+        }
+
+        if let Some(build_state) = build_state {
+            build_state.save()?;
         }
 
         let mut binaries = Vec::new();
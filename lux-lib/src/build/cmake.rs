@@ -9,6 +9,7 @@ use tokio::process::Command;
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
+        sandbox::{Sandbox, SandboxError},
         utils,
     },
     config::Config,
@@ -26,6 +27,8 @@ pub enum CMakeError {
     Tree(#[from] TreeError),
     #[error(transparent)]
     Paths(#[from] PathsError),
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
     #[error("{name} step failed.\n\n{status}\n\nstdout:\n{stdout}\n\nstderr:\n{stderr}")]
     CommandFailure {
         name: String,
@@ -59,6 +62,7 @@ impl HasVariables for CMakeVariables {
 impl BuildBackend for CMakeBuildSpec {
     type Err = CMakeError;
 
+    #[tracing::instrument(name = "cmake_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let no_install = args.no_install;
@@ -72,6 +76,7 @@ impl BuildBackend for CMakeBuildSpec {
         let lua_path = build_paths.package_path_prepended().joined();
         let lua_cpath = build_paths.package_cpath_prepended().joined();
         let bin_path = build_paths.path_prepended().joined();
+        let sandbox = Sandbox::new(config, build_dir, output_paths)?;
 
         let mut args = Vec::new();
         if let Some(content) = self.cmake_lists_content {
@@ -99,7 +104,8 @@ impl BuildBackend for CMakeBuildSpec {
             .fold_ok((), |(), variable| args.push(format!("-D{variable}")))?;
 
         spawn_cmake_cmd(
-            Command::new(config.cmake_cmd())
+            sandbox
+                .command(config.cmake_cmd())
                 .current_dir(build_dir)
                 .arg("-H.")
                 .arg(format!("-B{CMAKE_BUILD_FILE}"))
@@ -113,7 +119,8 @@ impl BuildBackend for CMakeBuildSpec {
 
         if self.build_pass {
             spawn_cmake_cmd(
-                Command::new(config.cmake_cmd())
+                sandbox
+                    .command(config.cmake_cmd())
                     .current_dir(build_dir)
                     .arg("--build")
                     .arg(CMAKE_BUILD_FILE)
@@ -129,7 +136,8 @@ impl BuildBackend for CMakeBuildSpec {
 
         if self.install_pass && !no_install {
             spawn_cmake_cmd(
-                Command::new(config.cmake_cmd())
+                sandbox
+                    .command(config.cmake_cmd())
                     .current_dir(build_dir)
                     .arg("--build")
                     .arg(CMAKE_BUILD_FILE)
@@ -150,10 +158,12 @@ impl BuildBackend for CMakeBuildSpec {
 }
 
 async fn spawn_cmake_cmd(cmd: &mut Command, config: &Config) -> Result<(), CMakeError> {
+    tracing::debug!(command = ?cmd.as_std(), "running cmake");
     match cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
         Ok(child) => match child.wait_with_output().await {
             Ok(output) if output.status.success() => utils::log_command_output(&output, config),
             Ok(output) => {
+                utils::emit_failed_command_output(&output, config);
                 return Err(CMakeError::CommandFailure {
                     name: config.cmake_cmd().clone(),
                     status: output.status,
@@ -1,4 +1,3 @@
-use shell_words::split;
 use std::{
     collections::HashMap,
     io,
@@ -15,10 +14,11 @@ use crate::{
     lua_rockspec::CommandBuildSpec,
     path::{Paths, PathsError},
     tree::{RockLayout, TreeError},
-    variables::VariableSubstitutionError,
+    variables::{self, Environment, GetVariableError, HasVariables, VariableSubstitutionError},
 };
 
 use super::external_dependency::ExternalDependencyInfo;
+use super::sandbox::{Sandbox, SandboxError};
 use super::utils;
 
 #[derive(Error, Debug)]
@@ -27,13 +27,10 @@ pub enum CommandError {
     Tree(#[from] TreeError),
     #[error(transparent)]
     Paths(#[from] PathsError),
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
     #[error("'build_command' and 'install_command' cannot be empty.")]
     EmptyCommand,
-    #[error("error parsing command:\n{command}\n\nerror: {err}")]
-    ParseError {
-        err: shell_words::ParseError,
-        command: String,
-    },
     #[error("error executing command:\n{command}\n\nerror: {err}")]
     Io { err: io::Error, command: String },
     #[error("failed to execute command:\n{command}\n\nstatus: {status}\nstdout: {stdout}\nstderr: {stderr}")]
@@ -50,6 +47,7 @@ pub enum CommandError {
 impl BuildBackend for CommandBuildSpec {
     type Err = CommandError;
 
+    #[tracing::instrument(name = "command_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let no_install = args.no_install;
@@ -61,6 +59,7 @@ impl BuildBackend for CommandBuildSpec {
 
         let build_tree = args.tree.build_tree(config)?;
         let build_paths = Paths::new(&build_tree)?;
+        let sandbox = Sandbox::new(config, build_dir, output_paths)?;
 
         progress.map(|bar| bar.set_message("Running build_command..."));
         if let Some(build_command) = &self.build_command {
@@ -73,6 +72,7 @@ impl BuildBackend for CommandBuildSpec {
                 config,
                 build_dir,
                 &build_paths,
+                &sandbox,
             )
             .await?;
         }
@@ -87,6 +87,7 @@ impl BuildBackend for CommandBuildSpec {
                     config,
                     build_dir,
                     &build_paths,
+                    &sandbox,
                 )
                 .await?;
             }
@@ -95,6 +96,7 @@ impl BuildBackend for CommandBuildSpec {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_command(
     command: &str,
     output_paths: &RockLayout,
@@ -103,27 +105,38 @@ async fn run_command(
     config: &Config,
     build_dir: &Path,
     build_paths: &Paths,
+    sandbox: &Sandbox<'_>,
 ) -> Result<(), CommandError> {
     let lua_path = build_paths.package_path_prepended().joined();
     let lua_cpath = build_paths.package_cpath_prepended().joined();
     let bin_path = build_paths.path_prepended().joined();
-    let substituted_cmd =
-        utils::substitute_variables(command, output_paths, lua, external_dependencies, config)?;
-    let cmd_parts = split(&substituted_cmd).map_err(|err| CommandError::ParseError {
-        err,
-        command: substituted_cmd.clone(),
-    })?;
-    let (program, args) = cmd_parts.split_first().ok_or(CommandError::EmptyCommand)?;
-    match Command::new(program)
-        .args(args)
-        .current_dir(build_dir)
+    let substituted_cmd = variables::substitute(
+        &[
+            &QuotedPaths(output_paths),
+            &QuotedPaths(lua),
+            &QuotedPaths(external_dependencies),
+            &ShellQuoted(&Environment {}),
+            config,
+        ],
+        command,
+    )?;
+    if substituted_cmd.trim().is_empty() {
+        return Err(CommandError::EmptyCommand);
+    }
+    tracing::debug!(command = %substituted_cmd, "running build command");
+    let mut cmd = shell_command(&substituted_cmd, sandbox);
+    // Like luarocks, we pass the configured C compiler.
+    if let Some(compiler) = config.variables().get("CC") {
+        cmd.env("CC", compiler);
+    }
+    cmd.current_dir(build_dir)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .env("PATH", &bin_path)
         .env("LUA_PATH", &lua_path)
-        .env("LUA_CPATH", &lua_cpath)
-        .spawn()
-    {
+        .env("LUA_CPATH", &lua_cpath);
+    utils::set_msvc_env(&mut cmd);
+    match cmd.spawn() {
         Err(err) => {
             return Err(CommandError::Io {
                 err,
@@ -133,6 +146,7 @@ async fn run_command(
         Ok(child) => match child.wait_with_output().await {
             Ok(output) if output.status.success() => utils::log_command_output(&output, config),
             Ok(output) => {
+                utils::emit_failed_command_output(&output, config);
                 return Err(CommandError::CommandFailure {
                     command: substituted_cmd,
                     status: output.status,
@@ -150,3 +164,104 @@ async fn run_command(
     }
     Ok(())
 }
+
+/// Quotes substituted values, so that the shell passes them on as single words.
+/// Config variables like `CFLAGS` hold lists of flags that the shell must split,
+/// so they are not quoted.
+struct ShellQuoted<'a>(&'a dyn HasVariables);
+
+impl HasVariables for ShellQuoted<'_> {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        Ok(self.0.get_variable(input)?.map(|value| shell_quote(&value)))
+    }
+}
+
+/// Quotes substituted paths.
+/// On Unix, paths are already quoted by [`utils::format_path`],
+/// but on Windows, they are not, so they are quoted for `cmd`.
+struct QuotedPaths<'a>(&'a dyn HasVariables);
+
+impl HasVariables for QuotedPaths<'_> {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        if cfg!(target_os = "windows") {
+            ShellQuoted(self.0).get_variable(input)
+        } else {
+            self.0.get_variable(input)
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_quote(value: &str) -> String {
+    shlex::try_quote(value)
+        .map(|value| value.to_string())
+        .unwrap_or(format!("'{value}'"))
+}
+
+#[cfg(target_os = "windows")]
+fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Commands are run by the platform's shell, like luarocks does,
+/// so that they can use shell features like `&&` or environment variable assignments.
+#[cfg(not(target_os = "windows"))]
+fn shell_command(command: &str, sandbox: &Sandbox<'_>) -> Command {
+    let mut cmd = sandbox.command("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(target_os = "windows")]
+fn shell_command(command: &str, sandbox: &Sandbox<'_>) -> Command {
+    let mut cmd = sandbox.command("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    struct TestVariables;
+
+    impl HasVariables for TestVariables {
+        fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+            Ok(match input {
+                "VALUE" => Some("foo  bar; echo injected".into()),
+                _ => None,
+            })
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn run_quoted_shell_command() {
+        let build_dir = assert_fs::TempDir::new().unwrap();
+        let rock_path = build_dir.join("rock");
+        let output_paths = RockLayout::at(&rock_path);
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let sandbox = Sandbox::new(&config, &build_dir, &output_paths).unwrap();
+        let command = variables::substitute(
+            &[&ShellQuoted(&TestVariables)],
+            "echo $(VALUE) > out.txt && echo done >> out.txt",
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            "echo 'foo  bar; echo injected' > out.txt && echo done >> out.txt"
+        );
+        let status = shell_command(&command, &sandbox)
+            .current_dir(&build_dir)
+            .status()
+            .await
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(build_dir.join("out.txt")).unwrap(),
+            "foo  bar; echo injected\ndone\n"
+        );
+    }
+}
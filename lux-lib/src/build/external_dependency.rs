@@ -17,15 +17,15 @@ use super::utils::{c_lib_extension, format_path};
 
 #[derive(Error, Debug)]
 pub enum ExternalDependencyError {
-    #[error("{}", not_found_error_msg(.0))]
+    #[error("missing system dependency: {0}\n{hint}", hint = override_hint(.0))]
     NotFound(String),
     #[error("IO error while trying to detect external dependencies: {0}")]
     Io(#[from] std::io::Error),
-    #[error("{0} was probed successfully, but the header {1} could not be found")]
+    #[error("{0} was found by pkg-config, but the header {1} could not be found\n{hint}", hint = override_hint(.0))]
     SuccessfulProbeHeaderNotFound(String, String),
-    #[error("error probing external dependency {0}: the header {1} could not be found")]
+    #[error("missing system dependency: {0} (the header {1} could not be found)\n{hint}", hint = override_hint(.0))]
     HeaderNotFound(String, String),
-    #[error("error probing external dependency {0}: the library {1} could not be found")]
+    #[error("missing system dependency: {0} (the library {1} could not be found)\n{hint}", hint = override_hint(.0))]
     LibraryNotFound(String, String),
 }
 
@@ -56,6 +56,10 @@ impl ExternalDependencyInfo {
         dependency: &ExternalDependencySpec,
         config: &ExternalDependencySearchConfig,
     ) -> Result<Self, ExternalDependencyError> {
+        // Explicitly configured directories take precedence over pkg-config
+        if has_override(name, config) {
+            return Self::fallback_probe(name, dependency, config);
+        }
         let lib_info = pkg_config_probe(name)
             .or(pkg_config_probe(&format!("lib{}", name.to_lowercase())))
             .or(dependency.library.as_ref().and_then(|lib_name| {
@@ -312,19 +316,30 @@ fn get_libdir(name: &str, config: &ExternalDependencySearchConfig) -> Option<Pat
     .filter(|dir| dir.is_dir())
 }
 
-fn not_found_error_msg(name: &String) -> String {
-    let env_dir = format!("{}_DIR", &name.to_uppercase());
-    let env_inc = format!("{}_INCDIR", &name.to_uppercase());
-    let env_lib = format!("{}_LIBDIR", &name.to_uppercase());
+/// Whether the installation prefix or directories of a dependency
+/// are set with environment variables or in the config.
+fn has_override(name: &str, config: &ExternalDependencySearchConfig) -> bool {
+    ["DIR", "INCDIR", "LIBDIR"]
+        .iter()
+        .map(|suffix| format!("{}_{suffix}", name.to_uppercase()))
+        .any(|var_name| std::env::var(&var_name).is_ok() || config.prefixes.contains_key(&var_name))
+}
+
+fn override_hint(name: &str) -> String {
+    let env_dir = format!("{}_DIR", name.to_uppercase());
+    let env_inc = format!("{}_INCDIR", name.to_uppercase());
+    let env_lib = format!("{}_LIBDIR", name.to_uppercase());
 
     format!(
-        r#"External dependency not found: {name}.
-Consider one of the following:
+        r#"Install the development package for {name} with your system's package manager,
+or consider one of the following:
 1. Set environment variables:
    - {env_dir} for the installation prefix, or
    - {env_inc} and {env_lib} for specific directories
 2. Add the installation prefix to the configuration:
-   {env_dir} = "/path/to/installation""#
+   [external_deps.prefixes]
+   {env_dir} = "/path/to/installation"
+3. Pass it as a variable: `lx --variables {env_dir}=/path/to/installation ...`"#
     )
 }
 
@@ -344,11 +359,12 @@ fn format_lib_link_arg(lib: &str, compiler: &cc::Tool) -> String {
     }
 }
 
+/// On MSVC, linker arguments are passed after `/link`.
 fn format_linker_arg(arg: &str, compiler: &cc::Tool) -> String {
     if compiler.is_like_msvc() {
-        format!("-Wl,{arg}")
+        arg.to_string()
     } else {
-        format!("/link {arg}")
+        format!("-Wl,{arg}")
     }
 }
 
@@ -626,6 +642,36 @@ mod tests {
             result,
             Err(ExternalDependencyError::HeaderNotFound { .. })
         ));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("missing system dependency: foo"));
+    }
+
+    #[tokio::test]
+    async fn test_override_takes_precedence_over_pkg_config() {
+        let temp = TempDir::new().unwrap();
+        let prefix_dir = temp.child("zlib");
+        let include_dir = prefix_dir.child("include");
+        include_dir.create_dir_all().unwrap();
+        include_dir.child("zlib.h").touch().unwrap();
+
+        let mut config = ExternalDependencySearchConfig::default();
+        config
+            .prefixes
+            .insert("ZLIB_DIR".into(), prefix_dir.path().to_path_buf());
+
+        let info = ExternalDependencyInfo::probe(
+            "zlib",
+            &ExternalDependencySpec {
+                header: Some("zlib.h".into()),
+                library: None,
+            },
+            &config,
+        )
+        .unwrap();
+        assert!(info.lib_info.is_none());
+        assert_eq!(info.include_dir, Some(include_dir.path().to_path_buf()));
     }
 
     #[cfg(not(target_env = "msvc"))]
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{
+    config::Config,
+    lua_installation::LuaInstallation,
+    lua_rockspec::{LuaModule, ModuleSpec},
+};
+
+const STATE_FILE: &str = "state.json";
+
+/// The state of previous builds of a local project with the builtin backend,
+/// so that rebuilds only recompile C modules whose inputs have changed.
+///
+/// Compiled modules are kept in the state directory, keyed by a fingerprint
+/// of their sources, the project's header files, the Lua version and the build variables.
+pub(crate) struct BuildState {
+    dir: PathBuf,
+    fingerprints: HashMap<LuaModule, String>,
+    /// The fingerprints of the modules of the current build.
+    current: HashMap<LuaModule, String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct BuildStateFile {
+    modules: HashMap<String, String>,
+}
+
+impl BuildState {
+    /// Load the build state from `dir`, starting with an empty state if there is none.
+    pub(crate) fn load(dir: &Path) -> Self {
+        let fingerprints = std::fs::read_to_string(dir.join(STATE_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<BuildStateFile>(&content).ok())
+            .unwrap_or_default()
+            .modules
+            .into_iter()
+            .filter_map(|(module, fingerprint)| Some((module.parse().ok()?, fingerprint)))
+            .collect();
+        Self {
+            dir: dir.to_path_buf(),
+            fingerprints,
+            current: HashMap::new(),
+        }
+    }
+
+    /// Restore a compiled module into the `target_dir`, if its inputs haven't changed
+    /// since it was last compiled. Returns `true` if the module was restored.
+    pub(crate) fn restore(
+        &mut self,
+        module: &LuaModule,
+        fingerprint: String,
+        target_dir: &Path,
+    ) -> io::Result<bool> {
+        let cached = self.dir.join("lib").join(module.to_lib_path());
+        let is_fresh = self.fingerprints.get(module) == Some(&fingerprint) && cached.is_file();
+        if is_fresh {
+            let target = target_dir.join(module.to_lib_path());
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(cached, target)?;
+        }
+        self.current.insert(module.clone(), fingerprint);
+        Ok(is_fresh)
+    }
+
+    /// Record a freshly compiled module from the `target_dir`.
+    pub(crate) fn record(&self, module: &LuaModule, target_dir: &Path) -> io::Result<()> {
+        let cached = self.dir.join("lib").join(module.to_lib_path());
+        if let Some(parent) = cached.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(target_dir.join(module.to_lib_path()), cached)?;
+        Ok(())
+    }
+
+    /// Write the state of the current build, forgetting modules that no longer exist.
+    pub(crate) fn save(self) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let state = BuildStateFile {
+            modules: self
+                .current
+                .into_iter()
+                .map(|(module, fingerprint)| (module.to_string(), fingerprint))
+                .collect(),
+        };
+        // Serializing the state can't fail
+        std::fs::write(
+            self.dir.join(STATE_FILE),
+            serde_json::to_string(&state).unwrap(),
+        )
+    }
+}
+
+/// A fingerprint of the inputs of a C module.
+/// Header files aren't declared in the rockspec, so we include all of them.
+pub(crate) fn fingerprint(
+    module: &LuaModule,
+    spec: &ModuleSpec,
+    build_dir: &Path,
+    lua: &LuaInstallation,
+    config: &Config,
+) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(module.to_string());
+    hasher.update(format!("{spec:?}"));
+    hasher.update(lua.version.to_string());
+    for (name, value) in config.variables().iter().sorted() {
+        hasher.update(format!("{name}={value}"));
+    }
+    let sources = match spec {
+        ModuleSpec::SourcePath(source) => vec![build_dir.join(source)],
+        ModuleSpec::SourcePaths(sources) => sources.iter().map(|src| build_dir.join(src)).collect(),
+        ModuleSpec::ModulePaths(paths) => paths
+            .sources
+            .iter()
+            .map(|src| build_dir.join(src))
+            .collect(),
+    };
+    let headers = WalkDir::new(build_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "h"))
+        .sorted();
+    for file in sources.into_iter().chain(headers) {
+        // The build directory is a different temporary directory for each build
+        let relative_path = file.strip_prefix(build_dir).unwrap_or(&file);
+        hasher.update(relative_path.to_string_lossy().as_bytes());
+        hasher.update(std::fs::read(&file)?);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn restore_unchanged_modules() {
+        let state_dir = assert_fs::TempDir::new().unwrap();
+        let target_dir = assert_fs::TempDir::new().unwrap();
+        let module = LuaModule::from_str("foo.bar").unwrap();
+        let lib = target_dir.join(module.to_lib_path());
+        std::fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        std::fs::write(&lib, "compiled").unwrap();
+
+        let mut state = BuildState::load(&state_dir);
+        assert!(!state.restore(&module, "a".into(), &target_dir).unwrap());
+        state.record(&module, &target_dir).unwrap();
+        state.save().unwrap();
+
+        std::fs::remove_file(&lib).unwrap();
+        let mut state = BuildState::load(&state_dir);
+        assert!(!state.restore(&module, "b".into(), &target_dir).unwrap());
+        assert!(!lib.is_file());
+        let mut state = BuildState::load(&state_dir);
+        assert!(state.restore(&module, "a".into(), &target_dir).unwrap());
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "compiled");
+    }
+}
@@ -1,21 +1,25 @@
 use itertools::Itertools;
 use path_slash::PathBufExt;
 use std::{
+    collections::HashMap,
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{ExitStatus, Stdio},
 };
 use thiserror::Error;
-use tokio::process::Command;
 
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
+        external_dependency::ExternalDependencyInfo,
+        sandbox::{Sandbox, SandboxError},
         utils,
     },
+    config::Config,
+    lua_installation::LuaInstallation,
     lua_rockspec::MakeBuildSpec,
     path::{Paths, PathsError},
-    tree::TreeError,
+    tree::{RockLayout, TreeError},
     variables::VariableSubstitutionError,
 };
 
@@ -25,6 +29,8 @@ pub enum MakeError {
     Tree(#[from] TreeError),
     #[error(transparent)]
     Paths(#[from] PathsError),
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
     #[error("{name} step failed.\n\n{status}\n\nstdout:\n{stdout}\n\nstderr:\n{stderr}")]
     CommandFailure {
         name: String,
@@ -45,6 +51,7 @@ pub enum MakeError {
 impl BuildBackend for MakeBuildSpec {
     type Err = MakeError;
 
+    #[tracing::instrument(name = "make_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let no_install = args.no_install;
@@ -58,45 +65,40 @@ impl BuildBackend for MakeBuildSpec {
         let lua_path = build_paths.package_path_prepended().joined();
         let lua_cpath = build_paths.package_cpath_prepended().joined();
         let bin_path = build_paths.path_prepended().joined();
+        let sandbox = Sandbox::new(config, build_dir, output_paths)?;
 
         // Build step
         if self.build_pass {
-            let build_args = self
-                .variables
-                .iter()
-                .chain(&self.build_variables)
-                .filter(|(_, value)| !value.is_empty())
-                .map(|(key, value)| {
-                    let substituted_value = utils::substitute_variables(
-                        value,
-                        output_paths,
-                        lua,
-                        external_dependencies,
-                        config,
-                    )?;
-                    Ok(format!("{key}={substituted_value}").trim().to_string())
-                })
-                .try_collect::<_, Vec<_>, Self::Err>()?;
-            let mut cmd = Command::new(config.make_cmd());
-            if let Some(build_target) = &self.build_target {
+            let makefile = locate_makefile(build_dir, &self.makefile)?;
+            let build_args = make_args(
+                &self.variables,
+                &self.build_variables,
+                output_paths,
+                lua,
+                external_dependencies,
+                config,
+            )?;
+            let mut cmd = sandbox.command(config.make_cmd());
+            if let Some(build_target) = self.build_target.as_ref().filter(|t| !t.is_empty()) {
                 cmd.arg(build_target);
             }
-            match cmd
-                .current_dir(build_dir)
-                .args(["-f", &self.makefile.to_slash_lossy()])
+            cmd.current_dir(build_dir)
+                .args(["-f", &makefile.to_slash_lossy()])
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
                 .args(build_args)
                 .env("PATH", &bin_path)
                 .env("LUA_PATH", &lua_path)
-                .env("LUA_CPATH", &lua_cpath)
-                .spawn()
-            {
+                .env("LUA_CPATH", &lua_cpath);
+            utils::set_msvc_env(&mut cmd);
+            tracing::debug!(command = ?cmd.as_std(), "running the make build pass");
+            match cmd.spawn() {
                 Ok(child) => match child.wait_with_output().await {
                     Ok(output) if output.status.success() => {
                         utils::log_command_output(&output, config)
                     }
                     Ok(output) => {
+                        utils::emit_failed_command_output(&output, config);
                         return Err(MakeError::CommandFailure {
                             name: match self.build_target {
                                 Some(build_target) => {
@@ -118,41 +120,35 @@ impl BuildBackend for MakeBuildSpec {
 
         // Install step
         if self.install_pass && !no_install {
-            let install_args = self
-                .variables
-                .iter()
-                .chain(&self.install_variables)
-                .filter(|(_, value)| !value.is_empty())
-                .map(|(key, value)| {
-                    let substituted_value = utils::substitute_variables(
-                        value,
-                        output_paths,
-                        lua,
-                        external_dependencies,
-                        config,
-                    )?;
-                    Ok(format!("{key}={substituted_value}").trim().to_string())
-                })
-                .try_collect::<_, Vec<_>, Self::Err>()?;
-            match Command::new(config.make_cmd())
-                .current_dir(build_dir)
+            let makefile = locate_makefile(build_dir, &self.makefile)?;
+            let install_args = make_args(
+                &self.variables,
+                &self.install_variables,
+                output_paths,
+                lua,
+                external_dependencies,
+                config,
+            )?;
+            let mut cmd = sandbox.command(config.make_cmd());
+            cmd.current_dir(build_dir)
                 .arg(&self.install_target)
-                .args(["-f", &self.makefile.to_slash_lossy()])
+                .args(["-f", &makefile.to_slash_lossy()])
                 .args(install_args)
                 .env("PATH", &bin_path)
                 .env("LUA_PATH", &lua_path)
-                .env("LUA_CPATH", &lua_cpath)
-                .output()
-                .await
-            {
+                .env("LUA_CPATH", &lua_cpath);
+            utils::set_msvc_env(&mut cmd);
+            tracing::debug!(command = ?cmd.as_std(), "running the make install pass");
+            match cmd.output().await {
                 Ok(output) if output.status.success() => utils::log_command_output(&output, config),
                 Ok(output) => {
+                    utils::emit_failed_command_output(&output, config);
                     return Err(MakeError::CommandFailure {
                         name: format!("{} {}", config.make_cmd(), self.install_target),
                         status: output.status,
                         stdout: String::from_utf8_lossy(&output.stdout).into(),
                         stderr: String::from_utf8_lossy(&output.stderr).into(),
-                    })
+                    });
                 }
                 Err(err) => return Err(MakeError::Io(err)),
             }
@@ -161,3 +157,71 @@ impl BuildBackend for MakeBuildSpec {
         Ok(BuildInfo::default())
     }
 }
+
+/// Assignments to pass to `make`, where the pass-specific `pass_variables`
+/// take precedence over the `variables` for both passes.
+fn make_args(
+    variables: &HashMap<String, String>,
+    pass_variables: &HashMap<String, String>,
+    output_paths: &RockLayout,
+    lua: &LuaInstallation,
+    external_dependencies: &HashMap<String, ExternalDependencyInfo>,
+    config: &Config,
+) -> Result<Vec<String>, MakeError> {
+    let cc = "CC".to_string();
+    let mut assignments: HashMap<&String, &String> =
+        variables.iter().chain(pass_variables).collect();
+    // Like luarocks, we pass the configured C compiler, unless the rockspec overrides it.
+    if let Some(compiler) = config.variables().get(&cc) {
+        assignments.entry(&cc).or_insert(compiler);
+    }
+    assignments
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .sorted()
+        .map(|(key, value)| {
+            let substituted_value = utils::substitute_variables(
+                value,
+                output_paths,
+                lua,
+                external_dependencies,
+                config,
+            )?;
+            Ok(format!("{key}={substituted_value}").trim().to_string())
+        })
+        .try_collect()
+}
+
+/// The makefile, relative to the `build_dir`.
+/// If the default makefile doesn't exist, this falls back to the other
+/// makefile names `make` recognises, and to the other platform's default.
+fn locate_makefile(build_dir: &Path, makefile: &Path) -> Result<PathBuf, MakeError> {
+    let fallbacks: &[&str] = match makefile.to_str() {
+        Some("Makefile") => &["GNUmakefile", "makefile", "Makefile.win"],
+        Some("Makefile.win") => &["Makefile", "GNUmakefile", "makefile"],
+        _ => &[],
+    };
+    std::iter::once(makefile.to_path_buf())
+        .chain(fallbacks.iter().map(PathBuf::from))
+        .find(|makefile| build_dir.join(makefile).is_file())
+        .ok_or_else(|| MakeError::MakefileNotFound(build_dir.join(makefile)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_fallback_makefile() {
+        let build_dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(build_dir.join("Makefile.win"), "").unwrap();
+        assert_eq!(
+            locate_makefile(&build_dir, Path::new("Makefile")).unwrap(),
+            PathBuf::from("Makefile.win")
+        );
+        assert!(matches!(
+            locate_makefile(&build_dir, Path::new("GNUmakefile")),
+            Err(MakeError::MakefileNotFound(_))
+        ));
+    }
+}
@@ -1,10 +1,11 @@
 use crate::build::backend::{BuildBackend, BuildInfo, RunBuildArgs};
-use crate::lockfile::{LockfileError, OptState, RemotePackageSourceUrl};
+use crate::cache::{BuildCache, BuildCacheKey};
+use crate::lockfile::{LockfileError, LockfileIntegrityError, OptState, RemotePackageSourceUrl};
 use crate::lua_installation::LuaInstallationError;
 use crate::lua_rockspec::LuaVersionError;
 use crate::operations::{RemotePackageSourceMetadata, UnpackError};
 use crate::rockspec::{LuaVersionCompatibility, Rockspec};
-use crate::tree::{self, EntryType, TreeError};
+use crate::tree::{self, checksums::RockChecksums, EntryType, TreeError};
 use bytes::Bytes;
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -16,6 +17,7 @@ use crate::{
     lockfile::{LocalPackage, LocalPackageHashes, LockConstraint, PinnedState},
     lua_installation::LuaInstallation,
     lua_rockspec::BuildBackendSpec,
+    message::Message,
     operations::{self, FetchSrcError},
     package::PackageSpec,
     progress::{Progress, ProgressBar},
@@ -34,6 +36,7 @@ use luarocks::LuarocksBuildError;
 use make::MakeError;
 use mlua::FromLua;
 use patch::{Patch, PatchError};
+use plugin::PluginBuildError;
 use rust_mlua::RustError;
 use source::SourceBuildError;
 use ssri::Integrity;
@@ -44,10 +47,12 @@ use utils::{recursive_copy_dir, CompileCFilesError, InstallBinaryError};
 mod builtin;
 mod cmake;
 mod command;
+mod incremental;
 mod luarocks;
 mod make;
 mod patch;
 mod rust_mlua;
+mod sandbox;
 mod source;
 mod treesitter_parser;
 
@@ -55,6 +60,7 @@ pub(crate) mod backend;
 pub(crate) mod utils;
 
 pub mod external_dependency;
+pub mod plugin;
 
 /// A rocks package builder, providing fine-grained control
 /// over how a package should be built.
@@ -85,6 +91,10 @@ pub struct Build<'a, R: Rockspec + HasIntegrity> {
     #[builder(setters(vis = "pub(crate)"))]
     source_spec: Option<RemotePackageSourceSpec>,
 
+    /// Hashes the rockspec and source are expected to have, e.g. when installing from a lockfile.
+    #[builder(setters(vis = "pub(crate)"))]
+    expected_hashes: Option<LocalPackageHashes>,
+
     // TODO(vhyrro): Remove this and enforce that this is provided at a type level.
     source: Option<RemotePackageSource>,
 }
@@ -126,6 +136,8 @@ pub enum BuildError {
     TreesitterBuild(#[from] TreesitterBuildError),
     #[error("luarocks build failed: {0}")]
     LuarocksBuild(#[from] LuarocksBuildError),
+    #[error("plugin build failed: {0}")]
+    PluginBuild(#[from] PluginBuildError),
     #[error("building from rock source failed: {0}")]
     SourceBuild(#[from] SourceBuildError),
     #[error("IO operation failed: {0}")]
@@ -149,6 +161,8 @@ pub enum BuildError {
         expected: Integrity,
         actual: Integrity,
     },
+    #[error("integrity check failed: {0}")]
+    Integrity(#[from] LockfileIntegrityError),
     #[error("failed to unpack src.rock: {0}")]
     UnpackSrcRock(UnpackError),
     #[error("failed to fetch rock source: {0}")]
@@ -189,6 +203,7 @@ impl From<bool> for BuildBehaviour {
     }
 }
 
+#[tracing::instrument(skip_all)]
 async fn run_build<R: Rockspec + HasIntegrity>(
     rockspec: &R,
     args: RunBuildArgs<'_>,
@@ -206,7 +221,12 @@ async fn run_build<R: Rockspec + HasIntegrity>(
             Some(BuildBackendSpec::TreesitterParser(treesitter_parser_spec)) => {
                 treesitter_parser_spec.run(args).await?
             }
-            Some(BuildBackendSpec::LuaRock(_)) => luarocks::build(rockspec, args).await?,
+            Some(BuildBackendSpec::LuaRock(build_type)) => {
+                match plugin::find_plugin(&build_type, args.tree, args.config) {
+                    Some(plugin) => plugin::build(&plugin, rockspec, args).await?,
+                    None => luarocks::build(rockspec, args).await?,
+                }
+            }
             Some(BuildBackendSpec::Source) => source::build(args).await?,
             None => BuildInfo::default(),
         },
@@ -214,6 +234,7 @@ async fn run_build<R: Rockspec + HasIntegrity>(
 }
 
 #[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(build_dir = %build_dir.display()))]
 async fn install<R: Rockspec + HasIntegrity>(
     rockspec: &R,
     tree: &Tree,
@@ -298,6 +319,7 @@ async fn install<R: Rockspec + HasIntegrity>(
     Ok(())
 }
 
+#[tracing::instrument(skip_all, fields(package = %build.rockspec.package(), version = %build.rockspec.version()))]
 async fn do_build<R>(build: Build<'_, R>) -> Result<LocalPackage, BuildError>
 where
     R: Rockspec + HasIntegrity,
@@ -311,6 +333,11 @@ where
             rockspec.version()
         ))
     });
+    Message::build_started(&PackageSpec::new(
+        rockspec.package().clone(),
+        rockspec.version().clone(),
+    ))
+    .emit(build.config);
 
     let lua_version = rockspec.lua_version_matches(build.config)?;
 
@@ -318,6 +345,10 @@ where
 
     let temp_dir = tempdir::TempDir::new(&rockspec.package().to_string())?;
 
+    let expected_source_hash = build
+        .expected_hashes
+        .as_ref()
+        .map(|hashes| hashes.source.clone());
     let source_metadata = match build.source_spec {
         Some(RemotePackageSourceSpec::SrcRock(SrcRockSource { bytes, source_url })) => {
             let hash = bytes.hash()?;
@@ -330,11 +361,13 @@ where
         Some(RemotePackageSourceSpec::RockSpec(source_url)) => {
             operations::FetchSrc::new(temp_dir.path(), rockspec, build.config, build.progress)
                 .maybe_source_url(source_url)
+                .maybe_expected_hash(expected_source_hash)
                 .fetch_internal()
                 .await?
         }
         None => {
             operations::FetchSrc::new(temp_dir.path(), rockspec, build.config, build.progress)
+                .maybe_expected_hash(expected_source_hash)
                 .fetch_internal()
                 .await?
         }
@@ -345,6 +378,10 @@ where
         source: source_metadata.hash.clone(),
     };
 
+    if let Some(expected_hashes) = &build.expected_hashes {
+        hashes.validate(expected_hashes)?;
+    }
+
     let mut package = LocalPackage::from(
         &PackageSpec::new(rockspec.package().clone(), rockspec.version().clone()),
         build.constraint,
@@ -367,13 +404,73 @@ where
     match tree.lockfile()?.get(&package.id()) {
         Some(package) if build.behaviour == BuildBehaviour::NoForce => Ok(package.clone()),
         _ => {
-            let output_paths = match build.entry_type {
-                tree::EntryType::Entrypoint => tree.entrypoint(&package)?,
-                tree::EntryType::DependencyOnly => tree.dependency(&package)?,
+            let rock_layout = match build.entry_type {
+                tree::EntryType::Entrypoint => tree.entrypoint_layout(&package),
+                tree::EntryType::DependencyOnly => tree.dependency_layout(&package),
             };
+            // The rock is built in a staging directory and moved into the tree once it is complete,
+            // so that a failed build never leaves a partially installed rock behind.
+            std::fs::create_dir_all(tree.root())?;
+            let staging_dir = tempdir::TempDir::new_in(tree.root(), ".lux-staging")?;
+            let output_paths = rock_layout.staged(&staging_dir.path().join("rock"));
+            std::fs::create_dir_all(&output_paths.lib)?;
+            std::fs::create_dir_all(&output_paths.src)?;
 
             let lua = LuaInstallation::new(&lua_version, build.config).await?;
 
+            let external_dependencies = rockspec
+                .external_dependencies()
+                .current_platform()
+                .iter()
+                .map(|(name, dep)| {
+                    ExternalDependencyInfo::probe(name, dep, build.config.external_deps())
+                        .map(|info| (name.clone(), info))
+                })
+                .try_collect::<_, HashMap<_, _>, _>()?;
+
+            let build_cache = BuildCache::new(build.config);
+            let build_cache_key = if is_build_cacheable(rockspec)
+                && !matches!(
+                    source_metadata.source_url,
+                    RemotePackageSourceUrl::File { .. }
+                ) {
+                let lockfile = tree.lockfile()?;
+                let dependencies = rockspec
+                    .dependencies()
+                    .current_platform()
+                    .iter()
+                    .flat_map(|dep| lockfile.find_rocks(dep.package_req()))
+                    .filter_map(|id| lockfile.get(&id))
+                    .collect_vec();
+                Some(BuildCacheKey::new(
+                    &package,
+                    &lua_version,
+                    &dependencies,
+                    &external_dependencies,
+                    build.config,
+                ))
+            } else {
+                None
+            };
+            if let Some(key) = &build_cache_key {
+                if build_cache.restore(key, &output_paths)? {
+                    tracing::debug!("restored the build from the cache");
+                    build.progress.map(|p| {
+                        p.set_message(format!(
+                            "♻️ Reusing cached build of {}@{}",
+                            rockspec.package(),
+                            rockspec.version()
+                        ))
+                    });
+                    if let Ok(rockspec_str) = rockspec.to_lua_remote_rockspec_string() {
+                        std::fs::write(output_paths.rockspec_path(), rockspec_str)?;
+                    }
+                    RockChecksums::write(&output_paths)?;
+                    rock_layout.move_from_staging(&output_paths)?;
+                    return Ok(package);
+                }
+            }
+
             let rock_source = rockspec.source().current_platform();
             let build_dir = match &rock_source.unpack_dir {
                 Some(unpack_dir) => temp_dir.path().join(unpack_dir),
@@ -415,15 +512,16 @@ where
             )
             .apply()?;
 
-            let external_dependencies = rockspec
-                .external_dependencies()
-                .current_platform()
-                .iter()
-                .map(|(name, dep)| {
-                    ExternalDependencyInfo::probe(name, dep, build.config.external_deps())
-                        .map(|info| (name.clone(), info))
-                })
-                .try_collect::<_, HashMap<_, _>, _>()?;
+            // Local projects are rebuilt incrementally
+            let build_state_dir = matches!(
+                source_metadata.source_url,
+                RemotePackageSourceUrl::File { .. }
+            )
+            .then(|| {
+                tree.root()
+                    .join(".build-state")
+                    .join(rockspec.package().to_string())
+            });
 
             let output = run_build(
                 rockspec,
@@ -437,6 +535,7 @@ where
                     .tree(tree)
                     .build_dir(&build_dir)
                     .progress(build.progress)
+                    .maybe_build_state_dir(build_state_dir.as_deref())
                     .build(),
             )
             .await?;
@@ -479,11 +578,34 @@ where
                 std::fs::write(output_paths.rockspec_path(), rockspec_str)?;
             }
 
+            // Rocks that install binaries into the tree can't be restored from the cache.
+            if let Some(key) = &build_cache_key {
+                if package.spec.binaries.is_empty() {
+                    build_cache.store(key, &output_paths)?;
+                }
+            }
+
+            RockChecksums::write(&output_paths)?;
+            rock_layout.move_from_staging(&output_paths)?;
+
             Ok(package)
         }
     }
 }
 
+/// Whether a rock's build output can be cached and reused by other trees.
+/// Build backends that may install files outside of the rock's directory can't be cached.
+fn is_build_cacheable<R: Rockspec>(rockspec: &R) -> bool {
+    let build_spec = rockspec.build().current_platform();
+    build_spec.install.bin.is_empty()
+        && matches!(
+            build_spec.build_backend,
+            None | Some(BuildBackendSpec::Builtin(_))
+                | Some(BuildBackendSpec::RustMlua(_))
+                | Some(BuildBackendSpec::TreesitterParser(_))
+        )
+}
+
 async fn recursive_copy_doc_dir(
     output_paths: &RockLayout,
     build_dir: &Path,
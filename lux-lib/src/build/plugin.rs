@@ -0,0 +1,228 @@
+//! Build backends provided by external plugins.
+//!
+//! If a rockspec has a `build.type` that lux doesn't support natively,
+//! lux looks for a `lux-build-<type>` executable on the `PATH` and then in the build tree's
+//! `bin` directory (where binaries of the `lux-build-<type>` rock are installed),
+//! and delegates the build to it.
+//! Plugin executables on the `PATH` take precedence, so a plugin rock is only installed
+//! if there is no such executable.
+//!
+//! The plugin receives a [`PluginRequest`] as JSON on stdin, must build and install the package
+//! into the given output directories, and may write a [`PluginResponse`] as JSON to stdout.
+//! A non-zero exit status indicates a failed build.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    build::{
+        backend::{BuildInfo, RunBuildArgs},
+        sandbox::{Sandbox, SandboxError},
+        utils,
+    },
+    config::Config,
+    package::PackageName,
+    rockspec::Rockspec,
+    tree::{Tree, TreeError},
+    variables::HasVariables,
+};
+
+/// The version of the plugin protocol.
+/// Incremented on breaking changes to the [`PluginRequest`] or [`PluginResponse`].
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum PluginBuildError {
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
+    #[error("{0}")] // GetVariableError is crate-private
+    GetVariable(String),
+    #[error("{0}")] // We don't know the concrete error type
+    Rockspec(String),
+    #[error("failed to run build plugin {}: {err}", plugin.display())]
+    Io { plugin: PathBuf, err: io::Error },
+    #[error("build plugin {} failed.\n\n{status}\n\nstdout:\n{stdout}\n\nstderr:\n{stderr}", plugin.display())]
+    CommandFailure {
+        plugin: PathBuf,
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+    #[error("build plugin {} returned an invalid response: {err}", plugin.display())]
+    InvalidResponse {
+        plugin: PathBuf,
+        err: serde_json::Error,
+    },
+}
+
+/// The request lux sends to a build plugin.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginRequest {
+    pub protocol: u32,
+    pub package: String,
+    pub version: String,
+    /// The rockspec, serialized as Lua.
+    pub rockspec: String,
+    /// The directory containing the unpacked sources.
+    pub build_dir: PathBuf,
+    /// If `true`, the package should only be built, not installed.
+    pub no_install: bool,
+    /// The directories to install the package into.
+    pub output: PluginOutputPaths,
+    /// Variables that can be substituted in a rockspec, like `LUA_INCDIR`, `LUA_LIBDIR` or `CFLAGS`.
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginOutputPaths {
+    pub prefix: PathBuf,
+    pub lua: PathBuf,
+    pub lib: PathBuf,
+    pub bin: PathBuf,
+    pub conf: PathBuf,
+    pub doc: PathBuf,
+}
+
+/// The response a build plugin may write to stdout.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PluginResponse {
+    /// Binaries the plugin installed into the `bin` directory.
+    #[serde(default)]
+    pub binaries: Vec<PathBuf>,
+}
+
+/// The name of the rock or executable that provides the build backend for `build_type`.
+pub(crate) fn plugin_name(build_type: &str) -> PackageName {
+    PackageName::new(format!("lux-build-{build_type}"))
+}
+
+/// Find the executable of the plugin that provides the build backend for `build_type`,
+/// preferring executables on the `PATH` over the binaries of plugin rocks in the build tree.
+pub(crate) fn find_plugin(build_type: &str, tree: &Tree, config: &Config) -> Option<PathBuf> {
+    let name = plugin_name(build_type).to_string();
+    which::which(&name).ok().or_else(|| {
+        tree.build_tree(config)
+            .ok()
+            .and_then(|build_tree| which::which_in_global(&name, Some(build_tree.bin())).ok())
+            .and_then(|mut paths| paths.next())
+    })
+}
+
+pub(crate) async fn build<R: Rockspec>(
+    plugin: &Path,
+    rockspec: &R,
+    args: RunBuildArgs<'_>,
+) -> Result<BuildInfo, PluginBuildError> {
+    let output_paths = args.output_paths;
+    let config = args.config;
+    let build_dir = args.build_dir;
+    let progress = args.progress;
+    progress.map(|p| {
+        p.set_message(format!(
+            "Building {} {} with {}...",
+            rockspec.package(),
+            rockspec.version(),
+            plugin.display(),
+        ))
+    });
+    let variables = ["LUA_INCDIR", "LUA_LIBDIR", "LUA_BINDIR", "LUA", "LUALIB"]
+        .into_iter()
+        .filter_map(|name| {
+            args.lua
+                .get_variable(name)
+                .map(|value| value.map(|value| (name.to_string(), value)))
+                .transpose()
+        })
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|err| PluginBuildError::GetVariable(err.to_string()))?
+        .into_iter()
+        .chain(config.variables().clone())
+        .collect();
+    let request = PluginRequest {
+        protocol: PLUGIN_PROTOCOL_VERSION,
+        package: rockspec.package().to_string(),
+        version: rockspec.version().to_string(),
+        rockspec: rockspec
+            .to_lua_remote_rockspec_string()
+            .map_err(|err| PluginBuildError::Rockspec(err.to_string()))?,
+        build_dir: build_dir.to_path_buf(),
+        no_install: args.no_install,
+        output: PluginOutputPaths {
+            prefix: output_paths.rock_path.clone(),
+            lua: output_paths.src.clone(),
+            lib: output_paths.lib.clone(),
+            bin: output_paths.bin.clone(),
+            conf: output_paths.conf.clone(),
+            doc: output_paths.doc.clone(),
+        },
+        variables,
+    };
+    // Serializing the request can't fail
+    let request = serde_json::to_vec(&request).unwrap();
+
+    let io_err = |err| PluginBuildError::Io {
+        plugin: plugin.to_path_buf(),
+        err,
+    };
+    let sandbox = Sandbox::new(config, build_dir, output_paths)?;
+    let mut child = sandbox
+        .command(plugin)
+        .current_dir(build_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_err)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&request).await.map_err(io_err)?;
+    }
+    let output = child.wait_with_output().await.map_err(io_err)?;
+    if !output.status.success() {
+        return Err(PluginBuildError::CommandFailure {
+            plugin: plugin.to_path_buf(),
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).into(),
+            stderr: String::from_utf8_lossy(&output.stderr).into(),
+        });
+    }
+    utils::log_command_output(&output, config);
+    let response =
+        parse_response(&output.stdout).map_err(|err| PluginBuildError::InvalidResponse {
+            plugin: plugin.to_path_buf(),
+            err,
+        })?;
+    Ok(BuildInfo {
+        binaries: response.binaries,
+    })
+}
+
+fn parse_response(stdout: &[u8]) -> Result<PluginResponse, serde_json::Error> {
+    if stdout.iter().all(u8::is_ascii_whitespace) {
+        Ok(PluginResponse::default())
+    } else {
+        serde_json::from_slice(stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plugin_response() {
+        assert!(parse_response(b"\n").unwrap().binaries.is_empty());
+        let response = parse_response(br#"{ "binaries": ["foo"] }"#).unwrap();
+        assert_eq!(response.binaries, vec![PathBuf::from("foo")]);
+        assert!(parse_response(b"building foo...").is_err());
+    }
+}
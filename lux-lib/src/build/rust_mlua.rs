@@ -1,12 +1,15 @@
-use super::utils::c_dylib_extension;
 use crate::build::backend::{BuildBackend, BuildInfo, RunBuildArgs};
 use crate::config::LuaVersionUnset;
+use crate::lua_rockspec::LuaModule;
 use crate::progress::{Progress, ProgressBar};
+use crate::variables::HasVariables;
 use crate::{config::LuaVersion, lua_rockspec::RustMluaBuildSpec, tree::RockLayout};
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::env::consts::{DLL_EXTENSION, DLL_PREFIX};
 use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
+use std::str::FromStr;
 use std::{fs, io};
 use thiserror::Error;
 use tokio::process::Command;
@@ -23,16 +26,26 @@ pub enum RustError {
     RustBuild(#[from] io::Error),
     #[error(transparent)]
     LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error("{0}")] // GetVariableError is crate-private
+    GetVariable(String),
+    #[error("could not find the library {lib} for module {module} in {}", target_dir.display())]
+    LibraryNotFound {
+        module: String,
+        lib: PathBuf,
+        target_dir: PathBuf,
+    },
 }
 
 impl BuildBackend for RustMluaBuildSpec {
     type Err = RustError;
 
+    #[tracing::instrument(name = "rust_mlua_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let config = args.config;
         let build_dir = args.build_dir;
         let progress = args.progress;
+        let lua = args.lua;
         let lua_version = LuaVersion::from(config)?;
         let lua_feature = match lua_version {
             LuaVersion::Lua51 => "lua51",
@@ -40,7 +53,7 @@ impl BuildBackend for RustMluaBuildSpec {
             LuaVersion::Lua53 => "lua53",
             LuaVersion::Lua54 => "lua54",
             LuaVersion::LuaJIT => "luajit",
-            LuaVersion::LuaJIT52 => "luajit",
+            LuaVersion::LuaJIT52 => "luajit52",
         };
         let features = self
             .features
@@ -54,12 +67,22 @@ impl BuildBackend for RustMluaBuildSpec {
         }
         build_args.push("--features");
         build_args.push(&features);
-        match Command::new("cargo")
-            .current_dir(build_dir)
-            .args(build_args)
-            .output()
-            .await
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(build_dir).args(build_args);
+        // Used by mlua to find the Lua headers and library if it is not vendored.
+        if let Some(include_dir) = lua
+            .get_variable("LUA_INCDIR")
+            .map_err(|err| RustError::GetVariable(err.to_string()))?
+        {
+            cmd.env("LUA_INC", include_dir);
+        }
+        if let Some(lib_dir) = lua
+            .get_variable("LUA_LIBDIR")
+            .map_err(|err| RustError::GetVariable(err.to_string()))?
         {
+            cmd.env("LUA_LIB", lib_dir);
+        }
+        match cmd.output().await {
             Ok(output) if output.status.success() => {}
             Ok(output) => {
                 return Err(RustError::CargoBuild {
@@ -75,7 +98,7 @@ impl BuildBackend for RustMluaBuildSpec {
             install_rust_libs(self.modules, &self.target_path, build_dir, output_paths)
         {
             cleanup(output_paths, progress).await?;
-            return Err(err.into());
+            return Err(err);
         }
         fs::create_dir_all(&output_paths.src)?;
         if let Err(err) = install_lua_libs(self.include, build_dir, output_paths) {
@@ -91,16 +114,44 @@ fn install_rust_libs(
     target_path: &Path,
     build_dir: &Path,
     output_paths: &RockLayout,
-) -> io::Result<()> {
+) -> Result<(), RustError> {
+    let target_dir = build_dir.join(target_path).join("release");
     for (module, rust_lib) in modules {
-        let src = build_dir.join(target_path).join("release").join(rust_lib);
-        let mut dst: PathBuf = output_paths.lib.join(module);
-        dst.set_extension(c_dylib_extension());
+        let src =
+            locate_rust_lib(&target_dir, &rust_lib).ok_or_else(|| RustError::LibraryNotFound {
+                module: module.clone(),
+                lib: rust_lib.clone(),
+                target_dir: target_dir.clone(),
+            })?;
+        // Infallible
+        let lua_module = LuaModule::from_str(&module).unwrap();
+        let dst = output_paths.lib.join(lua_module.to_lib_path());
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)?;
+        }
         fs::copy(src, dst)?;
     }
     Ok(())
 }
 
+/// Locate a cdylib produced by cargo.
+/// The `rust_lib` name is in the `lib<name>.<ext>` format luarocks-build-rust-mlua uses,
+/// but cargo replaces dashes in crate names with underscores and,
+/// depending on the platform, may not use the `lib` prefix or the same extension.
+fn locate_rust_lib(target_dir: &Path, rust_lib: &Path) -> Option<PathBuf> {
+    let file_name = rust_lib.file_name()?.to_string_lossy().replace('-', "_");
+    let stem = file_name.strip_suffix(&format!(".{DLL_EXTENSION}"))?;
+    let name = stem.strip_prefix("lib").unwrap_or(stem);
+    [
+        rust_lib.to_path_buf(),
+        PathBuf::from(&file_name),
+        PathBuf::from(format!("{DLL_PREFIX}{name}.{DLL_EXTENSION}")),
+    ]
+    .into_iter()
+    .map(|lib| target_dir.join(lib))
+    .find(|lib| lib.is_file())
+}
+
 fn install_lua_libs(
     include: HashMap<PathBuf, PathBuf>,
     build_dir: &Path,
@@ -129,3 +180,19 @@ async fn cleanup(output_paths: &RockLayout, progress: &Progress<ProgressBar>) ->
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_rust_lib_with_underscores() {
+        let target_dir = assert_fs::TempDir::new().unwrap();
+        let lib = target_dir.join(format!("{DLL_PREFIX}foo_bar.{DLL_EXTENSION}"));
+        fs::write(&lib, "").unwrap();
+        let rust_lib = PathBuf::from(format!("libfoo-bar.{DLL_EXTENSION}"));
+        assert_eq!(locate_rust_lib(&target_dir, &rust_lib), Some(lib));
+        let rust_lib = PathBuf::from(format!("libbaz.{DLL_EXTENSION}"));
+        assert_eq!(locate_rust_lib(&target_dir, &rust_lib), None);
+    }
+}
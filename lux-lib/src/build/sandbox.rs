@@ -0,0 +1,188 @@
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use tempdir::TempDir;
+use thiserror::Error;
+use tokio::process::Command;
+
+use crate::{
+    config::{build_sandbox::BuildSandbox, Config},
+    tree::RockLayout,
+};
+
+#[derive(Error, Debug)]
+pub enum SandboxError {
+    #[error("failed to set up the build sandbox: {0}")]
+    Io(#[from] io::Error),
+    #[error("the build sandbox is enabled, but {0} could not be found.\nInstall it or disable the build sandbox.")]
+    Unavailable(String),
+}
+
+/// Spawns build step commands in a restricted environment,
+/// as configured by the [`BuildSandbox`] config.
+pub(crate) struct Sandbox<'a> {
+    policy: &'a BuildSandbox,
+    restrictions: Option<Restrictions>,
+}
+
+struct Restrictions {
+    /// The program used to restrict file system and network access.
+    wrapper: PathBuf,
+    /// Directories the build steps are allowed to write to.
+    #[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+    writable: Vec<PathBuf>,
+    /// A temporary directory that serves as `HOME` and `TMPDIR`.
+    /// Deleted when the sandbox is dropped.
+    tmp_dir: TempDir,
+}
+
+impl<'a> Sandbox<'a> {
+    pub(crate) fn new(
+        config: &'a Config,
+        build_dir: &Path,
+        output_paths: &RockLayout,
+    ) -> Result<Self, SandboxError> {
+        let policy = config.build_sandbox();
+        if !policy.enabled() {
+            return Ok(Self {
+                policy,
+                restrictions: None,
+            });
+        }
+        let wrapper = platform_sandbox()?;
+        let writable = vec![
+            build_dir.to_path_buf(),
+            output_paths.rock_path.clone(),
+            output_paths.bin.clone(),
+        ];
+        for dir in &writable {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_dir = TempDir::new_in(build_dir, ".lux-sandbox")?;
+        Ok(Self {
+            policy,
+            restrictions: Some(Restrictions {
+                wrapper,
+                writable,
+                tmp_dir,
+            }),
+        })
+    }
+
+    /// Create a command for a build step.
+    /// If the sandbox is disabled, this is equivalent to [`Command::new`].
+    pub(crate) fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let restrictions = match &self.restrictions {
+            Some(restrictions) => restrictions,
+            None => return Command::new(program),
+        };
+        let tmp_dir = restrictions.tmp_dir.path();
+        let mut cmd = Command::new(&restrictions.wrapper);
+        cmd.args(restrictions.wrapper_args(self.policy))
+            .arg(program)
+            .env_clear()
+            .envs(filtered_env(self.policy, env::vars_os()))
+            .env("HOME", tmp_dir)
+            .env("TMPDIR", tmp_dir)
+            .env("TMP", tmp_dir)
+            .env("TEMP", tmp_dir);
+        cmd
+    }
+}
+
+impl Restrictions {
+    #[cfg(target_os = "linux")]
+    fn wrapper_args(&self, policy: &BuildSandbox) -> Vec<OsString> {
+        let mut args: Vec<OsString> = ["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        for dir in &self.writable {
+            args.extend(["--bind".into(), dir.into(), dir.into()]);
+        }
+        if policy.deny_network() {
+            args.push("--unshare-net".into());
+        }
+        args.extend(["--die-with-parent".into(), "--".into()]);
+        args
+    }
+
+    #[cfg(target_os = "macos")]
+    fn wrapper_args(&self, policy: &BuildSandbox) -> Vec<OsString> {
+        let mut profile = String::from("(version 1)\n(allow default)\n(deny file-write*)\n");
+        profile.push_str("(allow file-write* (subpath \"/dev\"))\n");
+        for dir in &self.writable {
+            profile.push_str(&format!(
+                "(allow file-write* (subpath {:?}))\n",
+                dir.canonicalize().unwrap_or(dir.clone())
+            ));
+        }
+        if policy.deny_network() {
+            profile.push_str("(deny network*)\n");
+        }
+        vec!["-p".into(), profile.into()]
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn wrapper_args(&self, _policy: &BuildSandbox) -> Vec<OsString> {
+        Vec::new()
+    }
+}
+
+/// The program used to restrict file system and network access.
+#[cfg(target_os = "linux")]
+fn platform_sandbox() -> Result<PathBuf, SandboxError> {
+    which::which("bwrap").map_err(|_| SandboxError::Unavailable("`bwrap` (bubblewrap)".into()))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_sandbox() -> Result<PathBuf, SandboxError> {
+    which::which("sandbox-exec").map_err(|_| SandboxError::Unavailable("`sandbox-exec`".into()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn platform_sandbox() -> Result<PathBuf, SandboxError> {
+    Err(SandboxError::Unavailable(format!(
+        "a sandbox for {}",
+        env::consts::OS
+    )))
+}
+
+/// The environment variables that are allowed by the `policy`.
+fn filtered_env(
+    policy: &BuildSandbox,
+    vars: impl Iterator<Item = (OsString, OsString)>,
+) -> Vec<(OsString, OsString)> {
+    vars.filter(|(key, _)| {
+        policy
+            .env()
+            .any(|allowed| key.to_string_lossy().eq_ignore_ascii_case(allowed))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_env() {
+        let policy = BuildSandbox::new(true, false, vec!["EXTRA".into()]);
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("EXTRA", "yes"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (OsString::from(key), OsString::from(value)));
+        let keys = filtered_env(&policy, vars)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![OsString::from("PATH"), OsString::from("EXTRA")]);
+    }
+}
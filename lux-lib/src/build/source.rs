@@ -6,6 +6,7 @@ use crate::{
     build::backend::{BuildBackend, BuildInfo, RunBuildArgs},
     lua_rockspec::{BuildBackendSpec, BuildSpec, LocalLuaRockspec, LuaRockspecError},
     project::{
+        diagnostic::ProjectTomlParseError,
         project_toml::{LocalProjectTomlValidationError, PartialProjectToml},
         ProjectRoot, PROJECT_TOML,
     },
@@ -24,7 +25,7 @@ pub enum SourceBuildError {
     #[error(transparent)]
     FromUtf8(#[from] FromUtf8Error),
     #[error(transparent)]
-    Toml(#[from] toml::de::Error),
+    Toml(#[from] ProjectTomlParseError),
     #[error(transparent)]
     LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
     #[error(transparent)]
@@ -30,6 +30,7 @@ pub enum TreesitterBuildError {
 impl BuildBackend for TreesitterParserBuildSpec {
     type Err = TreesitterBuildError;
 
+    #[tracing::instrument(name = "treesitter_parser_build", skip_all)]
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
         let build_dir = args.build_dir;
@@ -44,17 +45,25 @@ impl BuildBackend for TreesitterParserBuildSpec {
                 Ok(v) => v.parse()?,
                 Err(_) => DEFAULT_GENERATE_ABI_VERSION,
             };
+            // Generating from `grammar.js` requires a JavaScript runtime.
+            // If there is none, we fall back to the `src/grammar.json` that most grammars ship with.
+            let grammar_json = build_dir.join("src").join("grammar.json");
+            let grammar_path = if which::which("node").is_err() && grammar_json.is_file() {
+                Some(grammar_json.to_string_lossy().to_string())
+            } else {
+                None
+            };
             tree_sitter_generate::generate_parser_in_directory(
                 &build_dir,
                 None,
-                None,
+                grammar_path.as_deref(),
                 abi_version,
                 None,
                 None,
             )?;
         }
-        progress.map(|b| b.set_message("🌳 Building tree-sitter parser..."));
         if self.parser {
+            progress.map(|b| b.set_message("🌳 Building tree-sitter parser..."));
             let parser_dir = output_paths.etc.join("parser");
             tokio::fs::create_dir_all(&parser_dir)
                 .await
@@ -87,6 +96,14 @@ impl BuildBackend for TreesitterParserBuildSpec {
         }
         for (path, content) in self.queries {
             let dest = queries_dir.join(path);
+            if let Some(parent) = dest.parent() {
+                tokio::fs::create_dir_all(parent).await.map_err(|err| {
+                    TreesitterBuildError::CreateDir {
+                        dir: parent.to_path_buf(),
+                        err,
+                    }
+                })?;
+            }
             tokio::fs::write(&dest, content)
                 .await
                 .map_err(TreesitterBuildError::WriteQuery)?;
@@ -2,6 +2,7 @@ use crate::{
     config::Config,
     lua_installation::LuaInstallation,
     lua_rockspec::{DeploySpec, LuaModule, ModulePaths},
+    message::{Message, MessageFormat},
     path::{Paths, PathsError},
     tree::{RockLayout, Tree},
     variables::{self, Environment, VariableSubstitutionError},
@@ -12,6 +13,8 @@ use path_slash::PathExt;
 use shlex::try_quote;
 use std::{
     collections::HashMap,
+    env,
+    ffi::OsStr,
     io,
     path::{Path, PathBuf},
     process::{ExitStatus, Output, Stdio},
@@ -105,6 +108,8 @@ pub enum CompileCFilesError {
     OutputValidation(#[from] OutputValidationError),
     #[error("compiling C files succeeded, but the expected library {0} was not created")]
     LibOutputNotCreated(String),
+    #[error(transparent)]
+    ArchiveStaticLib(#[from] ArchiveStaticLibError),
 }
 
 /// Compiles a set of C files into a single dynamic library and places them under `{target_dir}/{target_file}`.
@@ -152,6 +157,7 @@ pub(crate) async fn compile_c_files(
         .out_dir(intermediate_dir)
         .target(&host.to_string());
 
+    apply_compiler_variables(build, config);
     let compiler = build.try_get_compiler()?;
     // Suppress all warnings
     if compiler.is_like_msvc() {
@@ -170,24 +176,24 @@ pub(crate) async fn compile_c_files(
     let output_path = parent.join(&file);
 
     let output = if compiler.is_like_msvc() {
-        let def_temp_dir = tempdir::TempDir::new("msvc-def")?.into_path().to_path_buf();
-        let def_file = mk_def_file(def_temp_dir, &file, target_module)?;
+        let msvc_temp_dir = tempdir::TempDir::new("msvc-link")?;
         let cmd = compiler.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
-        cmd.arg("/NOLOGO")
-            .args(&objects)
-            .arg("/LD")
-            .arg("/link")
-            .arg(format!("/DEF:{}", def_file.display()))
-            .arg(format!("/OUT:{}", output_path.display()))
-            .args(lua.lib_link_args(&compiler))
-            .args(
-                external_dependencies
-                    .iter()
-                    .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
-            )
-            .output()
-            .await?
+        cmd.args(msvc_dll_args(
+            &objects,
+            &output_path,
+            target_module,
+            msvc_temp_dir.path(),
+        )?)
+        .args(lua.lib_link_args(&compiler))
+        .args(
+            external_dependencies
+                .iter()
+                .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
+        )
+        .args(ldflags(config))
+        .output()
+        .await?
     } else {
         let cmd = build.shared_flag(true).try_get_compiler()?.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
@@ -199,6 +205,7 @@ pub(crate) async fn compile_c_files(
                     .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
             )
             .args(&objects)
+            .args(ldflags(config))
             .output()
             .await?
     };
@@ -214,6 +221,7 @@ pub(crate) async fn compile_c_files(
 
     validate_output(&output)?;
     log_command_output(&output, config);
+    archive_static_lib(build, &objects, &output_path, config).await?;
 
     if output_path.exists() {
         Ok(())
@@ -224,28 +232,130 @@ pub(crate) async fn compile_c_files(
     }
 }
 
+/// Arguments for `cl.exe` to link the `objects` into a Lua module DLL.
+/// Must be followed by the linker arguments.
+/// The module definition file, import library and exports file are written to `tmp_dir`,
+/// as the import library is only needed for linking against the module, which Lua doesn't do.
+fn msvc_dll_args(
+    objects: &[PathBuf],
+    output_path: &Path,
+    target_module: &LuaModule,
+    tmp_dir: &Path,
+) -> io::Result<Vec<String>> {
+    let output_file_name = output_path
+        .file_name()
+        .expect("Couldn't determine filename")
+        .to_string_lossy();
+    let def_file = mk_def_file(tmp_dir, &output_file_name, target_module)?;
+    let mut import_lib = tmp_dir.join(output_file_name.as_ref());
+    import_lib.set_extension("lib");
+    Ok(["/NOLOGO".into()]
+        .into_iter()
+        .chain(objects.iter().map(|obj| obj.display().to_string()))
+        .chain([
+            "/LD".into(),
+            "/link".into(),
+            format!("/DEF:{}", def_file.display()),
+            format!("/IMPLIB:{}", import_lib.display()),
+            format!("/OUT:{}", output_path.display()),
+        ])
+        .collect())
+}
+
 /// On MSVC, we need to create Lua definitions manually
 fn mk_def_file(
-    dir: PathBuf,
+    dir: &Path,
     output_file_name: &str,
     target_module: &LuaModule,
 ) -> io::Result<PathBuf> {
     let mut def_file: PathBuf = dir.join(output_file_name);
-    def_file.set_extension(".def");
-    let exported_name = target_module.to_string().replace(".", "_");
-    let exported_name = exported_name
-        .split_once('-')
-        .map(|(_, after_hyphen)| after_hyphen.to_string())
-        .unwrap_or_else(|| exported_name.clone());
+    def_file.set_extension("def");
     let content = format!(
         r#"EXPORTS
-luaopen_{exported_name}
+{}
 "#,
+        luaopen_symbol(target_module)
     );
     std::fs::write(&def_file, content)?;
     Ok(def_file)
 }
 
+/// The name of the function Lua calls to open a C module.
+pub(crate) fn luaopen_symbol(module: &LuaModule) -> String {
+    let exported_name = module.to_string().replace(".", "_");
+    let exported_name = exported_name
+        .split_once('-')
+        .map(|(_, after_hyphen)| after_hyphen.to_string())
+        .unwrap_or_else(|| exported_name.clone());
+    format!("luaopen_{exported_name}")
+}
+
+/// Archives the object files of a C module into a static library next to its shared library,
+/// if the [`Config`] asks for static libraries (e.g. for `lx bundle`).
+async fn archive_static_lib(
+    build: &cc::Build,
+    objects: &[PathBuf],
+    shared_lib: &Path,
+    config: &Config,
+) -> Result<(), ArchiveStaticLibError> {
+    if !config.static_libs() {
+        return Ok(());
+    }
+    let static_lib = shared_lib.with_extension(c_lib_extension());
+    let mut cmd: Command = build.try_get_archiver()?.into();
+    if build.try_get_compiler()?.is_like_msvc() {
+        cmd.arg("/NOLOGO")
+            .arg(format!("/OUT:{}", static_lib.display()));
+    } else {
+        cmd.arg("crs").arg(&static_lib);
+    }
+    let output = cmd.args(objects).output().await?;
+    validate_output(&output)?;
+    log_command_output(&output, config);
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum ArchiveStaticLibError {
+    #[error("IO operation while creating a static library: {0}")]
+    Io(#[from] io::Error),
+    #[error("error creating a static library: {0}")]
+    Archiver(#[from] cc::Error),
+    #[error("error creating a static library (output validation failed): {0}")]
+    OutputValidation(#[from] OutputValidationError),
+}
+
+/// If targeting MSVC outside of a developer command prompt,
+/// set up the environment that `vcvars` would, so that build steps
+/// can find `cl.exe`, `link.exe`, `nmake.exe` and the Windows SDK.
+pub(crate) fn set_msvc_env(cmd: &mut Command) {
+    if !cfg!(target_env = "msvc") || which("cl.exe").is_ok() {
+        return;
+    }
+    let tool = match cc::windows_registry::find_tool(&Triple::host().to_string(), "cl.exe") {
+        Some(tool) => tool,
+        None => return,
+    };
+    for (key, value) in tool.get_envs() {
+        if key.eq_ignore_ascii_case("PATH") {
+            // Keep the PATH the build step was configured with
+            let current_path = cmd
+                .as_std()
+                .get_envs()
+                .find(|(key, _)| key.eq_ignore_ascii_case("PATH"))
+                .and_then(|(_, value)| value.map(OsStr::to_os_string))
+                .or_else(|| env::var_os("PATH"))
+                .unwrap_or_default();
+            let paths = env::split_paths(value).chain(env::split_paths(&current_path));
+            if let Ok(path) = env::join_paths(paths) {
+                cmd.env("PATH", path);
+            }
+        } else {
+            cmd.env(key, value);
+        }
+    }
+}
+
 // TODO: (#261): special cases for mingw/cygwin?
 
 /// the extension for C shared libraries.
@@ -275,6 +385,37 @@ pub(crate) fn c_obj_extension() -> &'static str {
     }
 }
 
+pub(crate) fn default_make() -> &'static str {
+    if cfg!(target_env = "msvc") {
+        "nmake"
+    } else {
+        "make"
+    }
+}
+
+/// Use the project's C compiler and flags, if overridden.
+/// Otherwise, `cc` detects the compiler and picks up the `CC` and `CFLAGS` environment variables.
+fn apply_compiler_variables(build: &mut cc::Build, config: &Config) {
+    let project_config = config.project_config();
+    if let Some(compiler) = project_config.cc() {
+        build.compiler(compiler);
+    }
+    if let Some(cflags) = project_config.cflags() {
+        for flag in shlex::split(cflags).unwrap_or_default() {
+            build.flag(flag);
+        }
+    }
+}
+
+/// The project's linker flags, if overridden.
+fn ldflags(config: &Config) -> Vec<String> {
+    config
+        .project_config()
+        .ldflags()
+        .and_then(|ldflags| shlex::split(ldflags))
+        .unwrap_or_default()
+}
+
 pub(crate) fn default_cflags() -> &'static str {
     if cfg!(target_env = "msvc") {
         "/NOLOGO /MD /O2"
@@ -305,6 +446,8 @@ pub enum CompileCModulesError {
     OutputValidation(#[from] OutputValidationError),
     #[error("compiling C modules succeeded, but the expected library {0} was not created")]
     LibOutputNotCreated(String),
+    #[error(transparent)]
+    ArchiveStaticLib(#[from] ArchiveStaticLibError),
 }
 
 /// Compiles a set of C files (with extra metadata) to a given destination.
@@ -362,6 +505,7 @@ pub(crate) async fn compile_c_modules(
         .out_dir(intermediate_dir)
         .target(&host.to_string());
 
+    apply_compiler_variables(build, config);
     let compiler = build.try_get_compiler()?;
     let is_msvc = compiler.is_like_msvc();
     // Suppress all warnings
@@ -408,26 +552,26 @@ pub(crate) async fn compile_c_modules(
 
     let output_path = parent.join(&file);
     let output = if is_msvc {
-        let def_temp_dir = tempdir::TempDir::new("msvc-def")?.into_path().to_path_buf();
-        let def_file = mk_def_file(def_temp_dir, &file, target_module)?;
+        let msvc_temp_dir = tempdir::TempDir::new("msvc-link")?;
         let cmd = build.try_get_compiler()?.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
-        cmd.arg("/NOLOGO")
-            .args(&objects)
-            .arg("/LD")
-            .arg("/link")
-            .arg(format!("/DEF:{}", def_file.display()))
-            .arg(format!("/OUT:{}", output_path.display()))
-            .args(lua.lib_link_args(&build.try_get_compiler()?))
-            .args(
-                external_dependencies
-                    .iter()
-                    .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
-            )
-            .args(libdir_args)
-            .args(library_args)
-            .output()
-            .await?
+        cmd.args(msvc_dll_args(
+            &objects,
+            &output_path,
+            target_module,
+            msvc_temp_dir.path(),
+        )?)
+        .args(lua.lib_link_args(&build.try_get_compiler()?))
+        .args(
+            external_dependencies
+                .iter()
+                .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
+        )
+        .args(libdir_args)
+        .args(library_args)
+        .args(ldflags(config))
+        .output()
+        .await?
     } else {
         let cmd = build.shared_flag(true).try_get_compiler()?.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
@@ -441,6 +585,7 @@ pub(crate) async fn compile_c_modules(
             .args(&objects)
             .args(libdir_args)
             .args(library_args)
+            .args(ldflags(config))
             .output()
             .await?
     };
@@ -456,6 +601,7 @@ pub(crate) async fn compile_c_modules(
 
     validate_output(&output)?;
     log_command_output(&output, config);
+    archive_static_lib(build, &objects, &output_path, config).await?;
 
     if output_path.exists() {
         Ok(())
@@ -512,9 +658,12 @@ pub(crate) async fn install_binary(
     Ok(script)
 }
 
-/// Logs the output's stdout and stderr in verbose mode
+/// Logs the output's stdout and stderr in verbose mode,
+/// or emits it as a message with the JSON message format.
 pub(crate) fn log_command_output(output: &Output, config: &Config) {
-    if config.verbose() {
+    if config.message_format() == MessageFormat::Json {
+        Message::compiler_output(output).emit(config);
+    } else if config.verbose() {
         if !output.stderr.is_empty() {
             println!("{}", String::from_utf8_lossy(&output.stderr));
         }
@@ -524,6 +673,12 @@ pub(crate) fn log_command_output(output: &Output, config: &Config) {
     }
 }
 
+/// Emits the output of a failed command as a message with the JSON message format.
+/// Otherwise, the output is reported as part of the command's error.
+pub(crate) fn emit_failed_command_output(output: &Output, config: &Config) {
+    Message::compiler_output(output).emit(config);
+}
+
 async fn install_wrapped_binary(
     source: &Path,
     target: &str,
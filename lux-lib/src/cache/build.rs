@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use tempdir::TempDir;
+use walkdir::WalkDir;
+
+use crate::{
+    build::external_dependency::ExternalDependencyInfo,
+    config::{Config, LuaVersion},
+    lockfile::LocalPackage,
+    luarocks,
+    tree::RockLayout,
+};
+
+/// A cache of built rocks, shared by all trees and projects,
+/// so that installing a rock that has already been built for the same
+/// Lua version, platform and build configuration doesn't require recompiling it.
+#[derive(Clone, Debug)]
+pub struct BuildCache {
+    root: PathBuf,
+}
+
+/// Identifies a build of a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BuildCacheKey(String);
+
+impl BuildCacheKey {
+    /// The key is derived from the package, its rockspec and source hashes,
+    /// the Lua version, the platform, the build variables (e.g. `CFLAGS`),
+    /// the installed versions of its dependencies and the external dependencies it was built against.
+    pub(crate) fn new(
+        package: &LocalPackage,
+        lua_version: &LuaVersion,
+        dependencies: &[&LocalPackage],
+        external_dependencies: &HashMap<String, ExternalDependencyInfo>,
+        config: &Config,
+    ) -> Self {
+        let hashes = package.hashes();
+        let mut hasher = KeyHasher::default();
+        hasher.field("rockspec", hashes.rockspec.to_string());
+        hasher.field("source", hashes.source.to_string());
+        hasher.field("lua", lua_version.to_string());
+        hasher.field("platform", luarocks::current_platform_luarocks_identifier());
+        for (name, value) in config.variables().iter().sorted() {
+            hasher.field("variable", format!("{name}={value}"));
+        }
+        for dependency in dependencies
+            .iter()
+            .map(|dependency| format!("{}@{}", dependency.name(), dependency.version()))
+            .sorted()
+        {
+            hasher.field("dependency", dependency);
+        }
+        for (name, info) in external_dependencies
+            .iter()
+            .sorted_by_key(|(name, _)| *name)
+        {
+            hasher.field("external_dependency", name);
+            hasher.path("include_dir", info.include_dir.as_deref());
+            hasher.path("lib_dir", info.lib_dir.as_deref());
+            hasher.path("bin_dir", info.bin_dir.as_deref());
+            hasher.field("lib_name", info.lib_name.as_deref().unwrap_or_default());
+            if let Some(lib_info) = &info.lib_info {
+                hasher.field("pkg_config_version", &lib_info.version);
+                for path in &lib_info.include_paths {
+                    hasher.path("pkg_config_include_path", Some(path));
+                }
+                for path in &lib_info.link_paths {
+                    hasher.path("pkg_config_link_path", Some(path));
+                }
+                for lib in &lib_info.libs {
+                    hasher.field("pkg_config_lib", lib);
+                }
+            }
+        }
+        Self(format!(
+            "{}-{}-{}",
+            package.name(),
+            package.version(),
+            hex::encode(hasher.0.finalize())
+        ))
+    }
+}
+
+/// Hashes named fields, separating them so that adjacent values can't collide.
+#[derive(Default)]
+struct KeyHasher(Sha256);
+
+impl KeyHasher {
+    fn field(&mut self, name: &str, value: impl AsRef<[u8]>) {
+        let value = value.as_ref();
+        self.0.update(name);
+        self.0.update([0]);
+        self.0.update(value.len().to_le_bytes());
+        self.0.update(value);
+    }
+
+    fn path(&mut self, name: &str, path: Option<&Path>) {
+        self.field(
+            name,
+            path.map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+    }
+}
+
+/// The directories of a `RockLayout` that are cached, relative to the cache entry.
+fn cached_dirs(layout: &RockLayout) -> [(&'static str, &Path); 5] {
+    [
+        ("src", &layout.src),
+        ("lib", &layout.lib),
+        ("etc", &layout.etc),
+        ("conf", &layout.conf),
+        ("doc", &layout.doc),
+    ]
+}
+
+impl BuildCache {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            root: config.cache_dir().join("builds"),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Remove all builds from the cache.
+    pub fn clean(&self) -> io::Result<()> {
+        if self.root.is_dir() {
+            std::fs::remove_dir_all(&self.root)?;
+        }
+        Ok(())
+    }
+
+    /// Install a cached build into the `layout`, returning `false` if there is none.
+    pub(crate) fn restore(&self, key: &BuildCacheKey, layout: &RockLayout) -> io::Result<bool> {
+        let entry = self.root.join(&key.0);
+        if !entry.is_dir() {
+            return Ok(false);
+        }
+        for (name, dest) in cached_dirs(layout) {
+            copy_dir(&entry.join(name), dest)?;
+        }
+        Ok(true)
+    }
+
+    /// Add the rock that was built into the `layout` to the cache.
+    pub(crate) fn store(&self, key: &BuildCacheKey, layout: &RockLayout) -> io::Result<()> {
+        let entry = self.root.join(&key.0);
+        if entry.is_dir() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.root)?;
+        // Build the entry in a temporary directory first,
+        // so that concurrent installs never restore an incomplete build.
+        let tmp_dir = TempDir::new_in(&self.root, ".tmp")?;
+        for (name, source) in cached_dirs(layout) {
+            copy_dir(source, &tmp_dir.path().join(name))?;
+        }
+        match std::fs::rename(tmp_dir.path(), &entry) {
+            // Another install may have stored the same build in the meantime.
+            Err(_) if entry.is_dir() => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Copy the files in `source` into `dest`.
+/// Files are always copied, so that modifying a file in a tree never modifies the cache.
+/// Existing files are removed first, so that read-only files can be replaced.
+pub(super) fn copy_dir(source: &Path, dest: &Path) -> io::Result<()> {
+    if !source.is_dir() {
+        return Ok(());
+    }
+    for entry in WalkDir::new(source) {
+        let path = entry?.into_path();
+        let relative_path = path
+            .strip_prefix(source)
+            .expect("walked path is not in the source directory");
+        let target = dest.join(relative_path);
+        if path.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            if target.exists() {
+                std::fs::remove_file(&target)?;
+            }
+            std::fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::*;
+
+    #[test]
+    fn store_and_restore_build() {
+        let cache_dir = assert_fs::TempDir::new().unwrap();
+        let cache = BuildCache {
+            root: cache_dir.to_path_buf(),
+        };
+        let key = BuildCacheKey("foo-1.0.0-1-abc".into());
+
+        let built = assert_fs::TempDir::new().unwrap();
+        built.child("src/foo.lua").write_str("return {}").unwrap();
+        built.child("lib/foo.so").write_str("binary").unwrap();
+        built.child("etc/conf/foo.conf").write_str("conf").unwrap();
+
+        let installed = assert_fs::TempDir::new().unwrap();
+        let layout = RockLayout::at(installed.path());
+        assert!(!cache.restore(&key, &layout).unwrap());
+
+        cache.store(&key, &RockLayout::at(built.path())).unwrap();
+        assert!(cache.restore(&key, &layout).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(layout.src.join("foo.lua")).unwrap(),
+            "return {}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(layout.lib.join("foo.so")).unwrap(),
+            "binary"
+        );
+        assert_eq!(
+            std::fs::read_to_string(layout.conf.join("foo.conf")).unwrap(),
+            "conf"
+        );
+
+        // Modifying a restored file must not modify the cache entry
+        std::fs::write(layout.src.join("foo.lua"), "return nil").unwrap();
+        let restored = assert_fs::TempDir::new().unwrap();
+        let layout = RockLayout::at(restored.path());
+        assert!(cache.restore(&key, &layout).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(layout.src.join("foo.lua")).unwrap(),
+            "return {}"
+        );
+    }
+}
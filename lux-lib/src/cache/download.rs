@@ -0,0 +1,318 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssri::Integrity;
+use tempdir::TempDir;
+use url::Url;
+
+use crate::{config::Config, hash::HasIntegrity};
+
+/// A cache for downloaded archives (source archives and packed rocks),
+/// which is shared by all trees and projects.
+///
+/// Archives are stored by their content hash and indexed by the URL they were downloaded from,
+/// together with the integrity they were expected to have (e.g. from a lockfile),
+/// so that repeated installs never download the same archive twice
+/// and an archive that changed upstream is never confused with a locked one.
+#[derive(Clone, Debug)]
+pub struct DownloadCache {
+    root: PathBuf,
+}
+
+/// An archive in the [`DownloadCache`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DownloadCacheEntry {
+    url: String,
+    file_name: String,
+    integrity: Integrity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected_integrity: Option<Integrity>,
+    #[serde(skip)]
+    size: u64,
+}
+
+impl DownloadCacheEntry {
+    /// The URL the archive was downloaded from.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn integrity(&self) -> &Integrity {
+        &self.integrity
+    }
+
+    /// The size of the cached archive, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl DownloadCache {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            root: config.cache_dir().join("downloads"),
+        }
+    }
+
+    /// Create a cache that stores its archives in `root`.
+    pub(crate) fn new_in(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn index_dir(&self) -> PathBuf {
+        self.root.join("index")
+    }
+
+    fn index_path(&self, url: &Url, expected: Option<&Integrity>) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_str());
+        if let Some(expected) = expected {
+            hasher.update("#");
+            hasher.update(expected.to_string());
+        }
+        self.index_dir()
+            .join(format!("{}.json", hex::encode(hasher.finalize())))
+    }
+
+    fn content_path(&self, integrity: &Integrity) -> PathBuf {
+        let (algorithm, hex) = integrity.to_hex();
+        self.root
+            .join("content")
+            .join(algorithm.to_string())
+            .join(hex)
+    }
+
+    /// Get the archive that was downloaded from `url`, if it is cached and intact.
+    /// If `expected` is set, only an archive with that integrity is returned.
+    pub(crate) async fn get(
+        &self,
+        url: &Url,
+        expected: Option<&Integrity>,
+    ) -> io::Result<Option<Bytes>> {
+        let index_path = self.index_path(url, expected);
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+        let entry: DownloadCacheEntry =
+            serde_json::from_str(&tokio::fs::read_to_string(&index_path).await?)?;
+        if expected.is_some_and(|expected| expected.matches(&entry.integrity).is_none()) {
+            return Ok(None);
+        }
+        let content_path = self.content_path(&entry.integrity);
+        if !content_path.is_file() {
+            return Ok(None);
+        }
+        let bytes = Bytes::from(tokio::fs::read(&content_path).await?);
+        if entry.integrity.matches(&bytes.hash()?).is_none() {
+            // The cached archive is corrupted, so we download it again.
+            return Ok(None);
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Add an archive that was downloaded from `url` to the cache.
+    /// If `expected` is set, the archive is only cached if it has that integrity.
+    pub(crate) async fn insert(
+        &self,
+        url: &Url,
+        expected: Option<&Integrity>,
+        file_name: &str,
+        bytes: &Bytes,
+    ) -> io::Result<()> {
+        let integrity = bytes.hash()?;
+        if expected.is_some_and(|expected| expected.matches(&integrity).is_none()) {
+            return Ok(());
+        }
+        let content_path = self.content_path(&integrity);
+        if !content_path.is_file() {
+            write_atomic(&content_path, bytes).await?;
+        }
+        let entry = DownloadCacheEntry {
+            url: url.to_string(),
+            file_name: file_name.to_string(),
+            integrity,
+            expected_integrity: expected.cloned(),
+            size: bytes.len() as u64,
+        };
+        write_atomic(
+            &self.index_path(url, expected),
+            serde_json::to_string_pretty(&entry)?.as_bytes(),
+        )
+        .await
+    }
+
+    /// All archives in the cache.
+    pub async fn entries(&self) -> io::Result<Vec<DownloadCacheEntry>> {
+        let index_dir = self.index_dir();
+        if !index_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(index_dir).await?;
+        while let Some(index_file) = dir.next_entry().await? {
+            let index_path = index_file.path();
+            if index_path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let content = tokio::fs::read_to_string(index_path).await?;
+            // Skip index files that we can't parse, e.g. from a different version of Lux.
+            if let Ok(mut entry) = serde_json::from_str::<DownloadCacheEntry>(&content) {
+                entry.size = tokio::fs::metadata(self.content_path(&entry.integrity))
+                    .await
+                    .map(|metadata| metadata.len())
+                    .unwrap_or_default();
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(entries)
+    }
+
+    /// Remove all archives from the cache.
+    pub async fn clean(&self) -> io::Result<()> {
+        if self.root.is_dir() {
+            tokio::fs::remove_dir_all(&self.root).await?;
+        }
+        Ok(())
+    }
+
+    /// Check the integrity of all cached archives.
+    /// Entries whose archive is missing or corrupted are removed from the cache and returned.
+    pub async fn verify(&self) -> io::Result<Vec<DownloadCacheEntry>> {
+        let mut invalid = Vec::new();
+        for entry in self.entries().await? {
+            let content_path = self.content_path(&entry.integrity);
+            let is_valid = content_path.is_file()
+                && entry
+                    .integrity
+                    .matches(&Bytes::from(tokio::fs::read(&content_path).await?).hash()?)
+                    .is_some();
+            if !is_valid {
+                if content_path.is_file() {
+                    tokio::fs::remove_file(&content_path).await?;
+                }
+                if let Ok(url) = Url::parse(&entry.url) {
+                    tokio::fs::remove_file(
+                        self.index_path(&url, entry.expected_integrity.as_ref()),
+                    )
+                    .await?;
+                }
+                invalid.push(entry);
+            }
+        }
+        Ok(invalid)
+    }
+}
+
+/// Write to a temporary file first, so that concurrent installs
+/// never read a partially written cache entry.
+async fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let parent = path.parent().expect("cache path has no parent");
+    tokio::fs::create_dir_all(parent).await?;
+    let tmp_dir = TempDir::new_in(parent, ".tmp")?;
+    let tmp_path = tmp_dir.path().join("content");
+    tokio::fs::write(&tmp_path, content).await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn download_cache_roundtrip() {
+        let cache_dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .cache_dir(Some(cache_dir.to_path_buf()))
+            .build()
+            .unwrap();
+        let cache = DownloadCache::new(&config);
+        let url: Url = "https://example.com/foo-1.0.0.tar.gz".parse().unwrap();
+        let bytes = Bytes::from_static(b"foo");
+
+        assert!(cache.get(&url, None).await.unwrap().is_none());
+        cache
+            .insert(&url, None, "foo-1.0.0.tar.gz", &bytes)
+            .await
+            .unwrap();
+        assert_eq!(cache.get(&url, None).await.unwrap(), Some(bytes.clone()));
+
+        let entries = cache.entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].url(), url.as_str());
+        assert_eq!(entries[0].size(), 3);
+        assert!(cache.verify().await.unwrap().is_empty());
+
+        let content_path = cache.content_path(entries[0].integrity());
+        tokio::fs::write(&content_path, b"tampered").await.unwrap();
+        assert!(cache.get(&url, None).await.unwrap().is_none());
+        assert_eq!(cache.verify().await.unwrap().len(), 1);
+        assert!(cache.entries().await.unwrap().is_empty());
+
+        cache
+            .insert(&url, None, "foo-1.0.0.tar.gz", &bytes)
+            .await
+            .unwrap();
+        cache.clean().await.unwrap();
+        assert!(cache.get(&url, None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn download_cache_keyed_by_expected_integrity() {
+        let cache_dir = assert_fs::TempDir::new().unwrap();
+        let cache = DownloadCache::new_in(cache_dir.to_path_buf());
+        let url: Url = "https://example.com/foo-1.0.0.tar.gz".parse().unwrap();
+        let locked = Bytes::from_static(b"foo");
+        let retagged = Bytes::from_static(b"bar");
+        let locked_integrity = locked.hash().unwrap();
+
+        cache
+            .insert(&url, None, "foo-1.0.0.tar.gz", &retagged)
+            .await
+            .unwrap();
+        assert!(cache
+            .get(&url, Some(&locked_integrity))
+            .await
+            .unwrap()
+            .is_none());
+
+        // Archives that don't have the expected integrity are not cached
+        cache
+            .insert(&url, Some(&locked_integrity), "foo-1.0.0.tar.gz", &retagged)
+            .await
+            .unwrap();
+        assert!(cache
+            .get(&url, Some(&locked_integrity))
+            .await
+            .unwrap()
+            .is_none());
+
+        cache
+            .insert(&url, Some(&locked_integrity), "foo-1.0.0.tar.gz", &locked)
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get(&url, Some(&locked_integrity)).await.unwrap(),
+            Some(locked)
+        );
+        assert_eq!(cache.get(&url, None).await.unwrap(), Some(retagged));
+        assert_eq!(cache.entries().await.unwrap().len(), 2);
+        assert!(cache.verify().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+use std::io;
+
+use bytes::Bytes;
+use ssri::Integrity;
+use url::Url;
+
+use crate::config::Config;
+
+mod build;
+mod download;
+mod vendor;
+
+pub use build::BuildCache;
+pub(crate) use build::BuildCacheKey;
+pub use download::{DownloadCache, DownloadCacheEntry};
+pub use vendor::VendorDir;
+
+/// Look up a download in the vendor directory (if configured), then in the download cache.
+/// If `expected` is set, only a download with that integrity is returned.
+pub(crate) async fn get_cached(
+    url: &Url,
+    expected: Option<&Integrity>,
+    config: &Config,
+) -> io::Result<Option<Bytes>> {
+    if let Some(vendor_dir) = config.vendor_dir() {
+        if let Some(bytes) = VendorDir::new(vendor_dir.clone())
+            .get(url, expected)
+            .await?
+        {
+            return Ok(Some(bytes));
+        }
+    }
+    DownloadCache::new(config).get(url, expected).await
+}
@@ -0,0 +1,142 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use ssri::Integrity;
+use url::Url;
+
+use crate::hash::HasIntegrity;
+
+use super::{build::copy_dir, DownloadCache};
+
+/// A project-local directory, created by `lx vendor`, containing all downloads
+/// needed to install the project's locked dependencies without network access.
+///
+/// Archives and rockspecs are stored like in the [`DownloadCache`], by their content hash
+/// and indexed by the URL they were downloaded from.
+/// Git sources are stored as checkouts, indexed by their URL and the commit they were pinned to.
+#[derive(Clone, Debug)]
+pub struct VendorDir {
+    root: PathBuf,
+    archives: DownloadCache,
+}
+
+impl VendorDir {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            archives: DownloadCache::new_in(root.clone()),
+            root,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the archive or rockspec that was downloaded from `url`, if vendored.
+    /// The vendor directory holds a single, locked download per URL,
+    /// so it is only checked against the `expected` integrity, if set.
+    pub(crate) async fn get(
+        &self,
+        url: &Url,
+        expected: Option<&Integrity>,
+    ) -> io::Result<Option<Bytes>> {
+        match (self.archives.get(url, None).await?, expected) {
+            (Some(bytes), Some(expected)) if expected.matches(&bytes.hash()?).is_none() => Ok(None),
+            (bytes, _) => Ok(bytes),
+        }
+    }
+
+    pub(crate) async fn insert(&self, url: &Url, file_name: &str, bytes: &Bytes) -> io::Result<()> {
+        self.archives.insert(url, None, file_name, bytes).await
+    }
+
+    fn git_checkout_dir(&self, url: &str, checkout_ref: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{url}#{checkout_ref}"));
+        self.root.join("git").join(hex::encode(hasher.finalize()))
+    }
+
+    /// Copy the vendored checkout of a git source into `dest`, returning `false` if there is none.
+    pub(crate) fn restore_git_checkout(
+        &self,
+        url: &str,
+        checkout_ref: &str,
+        dest: &Path,
+    ) -> io::Result<bool> {
+        let checkout_dir = self.git_checkout_dir(url, checkout_ref);
+        if !checkout_dir.is_dir() {
+            return Ok(false);
+        }
+        copy_dir(&checkout_dir, dest)?;
+        Ok(true)
+    }
+
+    /// Add the checkout of a git source in `source` to the vendor directory.
+    pub(crate) fn store_git_checkout(
+        &self,
+        url: &str,
+        checkout_ref: &str,
+        source: &Path,
+    ) -> io::Result<()> {
+        let checkout_dir = self.git_checkout_dir(url, checkout_ref);
+        if checkout_dir.is_dir() {
+            std::fs::remove_dir_all(&checkout_dir)?;
+        }
+        copy_dir(source, &checkout_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn vendor_archives_and_git_checkouts() {
+        let root = assert_fs::TempDir::new().unwrap();
+        let vendor_dir = VendorDir::new(root.to_path_buf());
+
+        let url: Url = "https://example.com/foo-1.0.0.tar.gz".parse().unwrap();
+        let bytes = Bytes::from_static(b"foo");
+        assert!(vendor_dir.get(&url, None).await.unwrap().is_none());
+        vendor_dir
+            .insert(&url, "foo-1.0.0.tar.gz", &bytes)
+            .await
+            .unwrap();
+        assert_eq!(
+            vendor_dir.get(&url, None).await.unwrap(),
+            Some(bytes.clone())
+        );
+        let other = Bytes::from_static(b"bar").hash().unwrap();
+        assert!(vendor_dir.get(&url, Some(&other)).await.unwrap().is_none());
+
+        let checkout = assert_fs::TempDir::new().unwrap();
+        checkout
+            .child("src/foo.lua")
+            .write_str("return {}")
+            .unwrap();
+        let git_url = "https://example.com/foo.git";
+        let dest = assert_fs::TempDir::new().unwrap();
+        assert!(!vendor_dir
+            .restore_git_checkout(git_url, "abc", dest.path())
+            .unwrap());
+        vendor_dir
+            .store_git_checkout(git_url, "abc", checkout.path())
+            .unwrap();
+        assert!(!vendor_dir
+            .restore_git_checkout(git_url, "def", dest.path())
+            .unwrap());
+        assert!(vendor_dir
+            .restore_git_checkout(git_url, "abc", dest.path())
+            .unwrap());
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("src/foo.lua")).unwrap(),
+            "return {}"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+/// Environment variables that are passed through to sandboxed build steps by default.
+const DEFAULT_ENV: &[&str] = &[
+    "PATH",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "CC",
+    "CXX",
+    "CFLAGS",
+    "CXXFLAGS",
+    "CPPFLAGS",
+    "LDFLAGS",
+    "PKG_CONFIG_PATH",
+    "MAKEFLAGS",
+    "SYSTEMROOT",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+];
+
+/// Restrictions for the `make`, `cmake` and `command` build steps of third-party rocks.
+///
+/// If enabled, build steps run with a filtered environment and a fresh temporary
+/// `HOME`/`TMPDIR` inside the build directory. On Linux (using `bwrap`) and macOS
+/// (using `sandbox-exec`), they can only write to the build directory and the
+/// package's install directory, and can optionally be denied network access.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildSandbox {
+    #[serde(default)]
+    enabled: bool,
+    /// Deny network access to build steps.
+    #[serde(default)]
+    deny_network: bool,
+    /// Additional environment variables to pass through to build steps.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env: Vec<String>,
+}
+
+impl BuildSandbox {
+    pub fn new(enabled: bool, deny_network: bool, env: Vec<String>) -> Self {
+        Self {
+            enabled,
+            deny_network,
+            env,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn deny_network(&self) -> bool {
+        self.deny_network
+    }
+
+    /// The environment variables that are passed through to build steps.
+    pub fn env(&self) -> impl Iterator<Item = &str> {
+        DEFAULT_ENV
+            .iter()
+            .copied()
+            .chain(self.env.iter().map(String::as_str))
+    }
+
+    pub(crate) fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+}
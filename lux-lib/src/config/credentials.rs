@@ -0,0 +1,225 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use url::Url;
+
+/// The service name of the tokens in the OS keyring.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "lux";
+
+#[derive(Debug, Error)]
+pub enum CredentialsError {
+    #[error("failed to access credentials file {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("error deserializing credentials file {0}: {1}")]
+    Deserialize(PathBuf, toml::de::Error),
+    #[error("error serializing credentials: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// Where a token was stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CredentialsLocation {
+    Keyring,
+    File(PathBuf),
+}
+
+/// Tokens for rock servers, stored with `lx login`.
+///
+/// Tokens are stored in the OS keyring, or in a plaintext file
+/// that only the user can read if no keyring is available
+/// (or lux was built without the `keyring` feature).
+#[derive(Clone)]
+pub struct Credentials {
+    file: PathBuf,
+    keyring: bool,
+    /// Tokens that have already been looked up, so that the keyring
+    /// is queried at most once per server.
+    cache: Arc<Mutex<HashMap<String, Option<String>>>>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("file", &self.file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Credentials {
+    pub(crate) fn new(file: PathBuf) -> Self {
+        Self {
+            file,
+            keyring: true,
+            cache: Arc::default(),
+        }
+    }
+
+    /// Store the token for a server, replacing any existing token.
+    pub fn store(
+        &self,
+        server: &Url,
+        token: &str,
+    ) -> Result<CredentialsLocation, CredentialsError> {
+        let key = server.to_string();
+        self.cache.lock().unwrap().remove(&key);
+        if self.keyring && keyring::set(&key, token) {
+            // Don't leave behind an outdated token from before the keyring was available
+            self.remove_from_file(&key)?;
+            return Ok(CredentialsLocation::Keyring);
+        }
+        let mut tokens = self.read_file()?;
+        tokens.insert(key, token.to_string());
+        self.write_file(&tokens)?;
+        Ok(CredentialsLocation::File(self.file.clone()))
+    }
+
+    /// The token for a server, if one has been stored.
+    pub fn get(&self, server: &Url) -> Result<Option<String>, CredentialsError> {
+        let key = server.to_string();
+        if let Some(token) = self.cache.lock().unwrap().get(&key) {
+            return Ok(token.clone());
+        }
+        let token = match self.keyring_token(&key) {
+            Some(token) => Some(token),
+            None => self.read_file()?.remove(&key),
+        };
+        self.cache.lock().unwrap().insert(key, token.clone());
+        Ok(token)
+    }
+
+    /// Remove the token for a server.
+    /// Returns whether a token was stored.
+    pub fn remove(&self, server: &Url) -> Result<bool, CredentialsError> {
+        let key = server.to_string();
+        self.cache.lock().unwrap().remove(&key);
+        let removed_from_keyring = self.keyring && keyring::delete(&key);
+        let removed_from_file = self.remove_from_file(&key)?;
+        Ok(removed_from_keyring || removed_from_file)
+    }
+
+    fn keyring_token(&self, key: &str) -> Option<String> {
+        if !self.keyring {
+            return None;
+        }
+        keyring::get(key)
+    }
+
+    fn read_file(&self) -> Result<BTreeMap<String, String>, CredentialsError> {
+        match std::fs::read_to_string(&self.file) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|err| CredentialsError::Deserialize(self.file.clone(), err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(CredentialsError::Io(self.file.clone(), err)),
+        }
+    }
+
+    fn write_file(&self, tokens: &BTreeMap<String, String>) -> Result<(), CredentialsError> {
+        let io_err = |err| CredentialsError::Io(self.file.clone(), err);
+        if let Some(parent) = self.file.parent() {
+            std::fs::create_dir_all(parent).map_err(io_err)?;
+        }
+        let content = toml::to_string(tokens)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&self.file)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(io_err)
+    }
+
+    fn remove_from_file(&self, key: &str) -> Result<bool, CredentialsError> {
+        if !self.file.is_file() {
+            return Ok(false);
+        }
+        let mut tokens = self.read_file()?;
+        let removed = tokens.remove(key).is_some();
+        if removed {
+            self.write_file(&tokens)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(feature = "keyring")]
+mod keyring {
+    use super::KEYRING_SERVICE;
+
+    fn entry(server: &str) -> ::keyring::Result<::keyring::Entry> {
+        ::keyring::Entry::new(KEYRING_SERVICE, server)
+    }
+
+    pub(super) fn set(server: &str, token: &str) -> bool {
+        entry(server)
+            .and_then(|entry| entry.set_password(token))
+            .is_ok()
+    }
+
+    pub(super) fn get(server: &str) -> Option<String> {
+        entry(server).and_then(|entry| entry.get_password()).ok()
+    }
+
+    pub(super) fn delete(server: &str) -> bool {
+        entry(server)
+            .and_then(|entry| entry.delete_credential())
+            .is_ok()
+    }
+}
+
+/// Without the `keyring` feature, tokens are always stored in the credentials file.
+#[cfg(not(feature = "keyring"))]
+mod keyring {
+    pub(super) fn set(_server: &str, _token: &str) -> bool {
+        false
+    }
+
+    pub(super) fn get(_server: &str) -> Option<String> {
+        None
+    }
+
+    pub(super) fn delete(_server: &str) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_tokens_in_file() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let file = dir.join("credentials.toml");
+        let credentials = Credentials {
+            keyring: false,
+            ..Credentials::new(file.clone())
+        };
+        let server: Url = "https://rocks.example.com/".parse().unwrap();
+        assert_eq!(credentials.get(&server).unwrap(), None);
+        assert_eq!(
+            credentials.store(&server, "secret").unwrap(),
+            CredentialsLocation::File(file.clone())
+        );
+        assert_eq!(credentials.get(&server).unwrap(), Some("secret".into()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(credentials.remove(&server).unwrap());
+        assert!(!credentials.remove(&server).unwrap());
+        assert_eq!(credentials.get(&server).unwrap(), None);
+    }
+}
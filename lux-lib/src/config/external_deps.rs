@@ -29,6 +29,7 @@ pub struct ExternalDependencySearchConfig {
     pub(crate) search_prefixes: Vec<PathBuf>,
     /// Known installation prefixes for specific dependencies.
     /// These can also be set via environment variables.
+    #[serde(default)]
     pub(crate) prefixes: HashMap<String, PathBuf>,
 }
 
@@ -47,6 +48,34 @@ impl Default for ExternalDependencySearchConfig {
     }
 }
 
+impl ExternalDependencySearchConfig {
+    /// Like luarocks, use `<NAME>_DIR`, `<NAME>_INCDIR` and `<NAME>_LIBDIR` variables
+    /// (e.g. `OPENSSL_DIR`) as installation prefixes.
+    /// Variables take precedence, so that prefixes can be overridden with `--variables`.
+    pub(crate) fn with_variable_prefixes(mut self, variables: &HashMap<String, String>) -> Self {
+        for (key, value) in variables {
+            if is_prefix_variable(key) {
+                self.prefixes.insert(key.clone(), PathBuf::from(value));
+            }
+        }
+        self
+    }
+}
+
+/// Whether a variable is an installation prefix of an external dependency,
+/// e.g. `OPENSSL_DIR`, but not one of the Lua installation's `LUA_DIR` variables.
+fn is_prefix_variable(key: &str) -> bool {
+    ["_DIR", "_INCDIR", "_LIBDIR"].iter().any(|suffix| {
+        key.strip_suffix(suffix).is_some_and(|name| {
+            !name.is_empty()
+                && name != "LUA"
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        })
+    })
+}
+
 fn default_bin_patterns() -> Vec<String> {
     vec!["?".into()]
 }
@@ -118,3 +147,30 @@ fn default_include_subdir() -> String {
 fn default_lib_subdirs() -> Vec<PathBuf> {
     vec!["lib".into(), "lib64".into()]
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn variable_prefixes() {
+        let variables = HashMap::from_iter(
+            [
+                ("OPENSSL_DIR", "/opt/openssl"),
+                ("ZLIB_INCDIR", "/opt/zlib/include"),
+                ("LUA_DIR", "/usr"),
+                ("_DIR", "/"),
+                ("build_dir", "build"),
+                ("MAKE", "make"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        let config = ExternalDependencySearchConfig::default().with_variable_prefixes(&variables);
+        assert_eq!(
+            config.prefixes.keys().sorted().collect_vec(),
+            vec!["OPENSSL_DIR", "ZLIB_INCDIR"]
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// What to do if a package provides an executable or a Lua module
+/// with the same name as another package.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileConflictPolicy {
+    /// Fail the installation.
+    #[default]
+    Error,
+    /// Keep the executables of the packages that are already installed.
+    /// Lua modules are never overwritten, because each rock has its own directory,
+    /// so conflicting modules are only reported.
+    FirstWins,
+    /// Ask whether to continue with `first-wins`.
+    /// This behaves like `error` if a command cannot prompt.
+    Prompt,
+}
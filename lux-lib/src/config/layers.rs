@@ -0,0 +1,569 @@
+//! Layered configuration.
+//!
+//! The config is merged from the following layers, in order of increasing precedence:
+//!
+//! 1. The system config file: `/etc/lux/config.toml`
+//!    (`%PROGRAMDATA%\lux\config.toml` on Windows).
+//! 2. The user config file (see [`ConfigBuilder::config_file`]).
+//! 3. The project config file: `.lux/config.toml` in the current project's root.
+//! 4. Environment variables: `LUX_<KEY>` for the keys in [`ENV_KEYS`],
+//!    e.g. `LUX_LUA_VERSION=5.4`.
+//! 5. CLI flags, which are set with the [`ConfigBuilder`]'s methods.
+//!
+//! Tables are merged recursively. All other values, including arrays,
+//! are replaced by the layer with the higher precedence.
+//!
+//! The project config file is checked into the project's repository,
+//! so it can only set the keys in [`PROJECT_KEYS`]. Settings that affect
+//! where packages are downloaded from, credentials and the build environment
+//! (e.g. `server`, `servers`, `api_key` or `variables`) are ignored.
+
+use std::{
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use lets_find_up::{find_up_with, FindUpKind, FindUpOptions};
+use toml::{Table, Value};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::project::PROJECT_TOML;
+
+use super::{ConfigBuilder, ConfigError};
+
+/// The top-level config keys that can be set with `LUX_<KEY>` environment variables.
+pub const ENV_KEYS: &[&str] = &[
+    "server",
+    "namespace",
+    "only_sources",
+    "lua_version",
+    "user_tree",
+    "lua_dir",
+    "cache_dir",
+    "data_dir",
+    "global_bin_dir",
+    "no_project",
+    "enable_development_packages",
+    "verbose",
+    "offline",
+    "minimal_versions",
+    "vendor_dir",
+    "max_jobs",
+    "static_libs",
+    "file_conflicts",
+    "message_format",
+    "generate_luarc",
+];
+
+/// The top-level config keys that can be set in a project's `.lux/config.toml`.
+pub const PROJECT_KEYS: &[&str] = &[
+    "lua_version",
+    "namespace",
+    "only_sources",
+    "enable_development_packages",
+    "verbose",
+    "offline",
+    "refresh_manifests",
+    "minimal_versions",
+    "vendor_dir",
+    "timeout",
+    "retries",
+    "max_jobs",
+    "external_deps",
+    "licenses",
+    "static_libs",
+    "file_conflicts",
+    "message_format",
+    "entrypoint_layout",
+    "generate_luarc",
+    "cc",
+    "cflags",
+    "ldflags",
+    "tree",
+];
+
+/// The top-level config keys that only apply to the current project,
+/// and can therefore only be set in a project's `.lux/config.toml`.
+pub const PROJECT_ONLY_KEYS: &[&str] = &["cc", "cflags", "ldflags", "tree"];
+
+/// Config keys whose values are secret and must never be printed.
+const SECRET_KEYS: &[&str] = &["api_key", "auth"];
+
+/// The location of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
+pub enum ConfigLocation {
+    System,
+    User,
+    Project,
+}
+
+impl ConfigLocation {
+    /// All locations, in order of increasing precedence.
+    pub const ALL: [ConfigLocation; 3] = [
+        ConfigLocation::System,
+        ConfigLocation::User,
+        ConfigLocation::Project,
+    ];
+
+    /// The path to the config file at this location,
+    /// or `None` for the project location if not in a project.
+    pub fn path(&self) -> Result<Option<PathBuf>, ConfigError> {
+        match self {
+            ConfigLocation::System => Ok(Some(system_config_file())),
+            ConfigLocation::User => Ok(Some(ConfigBuilder::config_file()?)),
+            ConfigLocation::Project => {
+                // A read error means that we have searched too far upwards (see `Project::from`).
+                let project_toml = find_up_with(
+                    PROJECT_TOML,
+                    FindUpOptions {
+                        cwd: &env::current_dir()?,
+                        kind: FindUpKind::File,
+                    },
+                )
+                .ok()
+                .flatten();
+                Ok(project_toml.and_then(|project_toml| {
+                    project_toml
+                        .parent()
+                        .map(|root| root.join(".lux").join("config.toml"))
+                }))
+            }
+        }
+    }
+}
+
+impl ConfigLocation {
+    /// Whether the top-level config `key` can be set at this location.
+    pub fn allows_key(&self, key: &str) -> bool {
+        match self {
+            ConfigLocation::Project => PROJECT_KEYS.contains(&key),
+            ConfigLocation::System | ConfigLocation::User => !PROJECT_ONLY_KEYS.contains(&key),
+        }
+    }
+}
+
+impl Display for ConfigLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigLocation::System => "system".fmt(f),
+            ConfigLocation::User => "user".fmt(f),
+            ConfigLocation::Project => "project".fmt(f),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn system_config_file() -> PathBuf {
+    PathBuf::from(env::var("PROGRAMDATA").unwrap_or("C:\\ProgramData".into()))
+        .join("lux")
+        .join("config.toml")
+}
+
+#[cfg(not(target_os = "windows"))]
+fn system_config_file() -> PathBuf {
+    PathBuf::from("/etc/lux/config.toml")
+}
+
+/// A layer of the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    File {
+        location: ConfigLocation,
+        path: PathBuf,
+    },
+    Env,
+}
+
+impl Display for ConfigLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigLayer::File { location, path } => write!(f, "{location} ({})", path.display()),
+            ConfigLayer::Env => "environment".fmt(f),
+        }
+    }
+}
+
+/// The values of each config layer that is present, in order of increasing precedence.
+/// Does not include CLI flags.
+pub fn config_layers() -> Result<Vec<(ConfigLayer, Table)>, ConfigError> {
+    let mut layers = Vec::new();
+    for location in ConfigLocation::ALL {
+        if let Some(path) = location.path()?.filter(|path| path.is_file()) {
+            let mut table = read_table(&path)?;
+            table.retain(|key, _| {
+                let allowed = location.allows_key(key);
+                if !allowed {
+                    eprintln!(
+                        "⚠️ WARNING: ignoring `{key}` in {}: it can't be set in the {location} config.",
+                        path.display()
+                    );
+                }
+                allowed
+            });
+            if location == ConfigLocation::Project {
+                if let Some(project_root) = path.parent().and_then(Path::parent) {
+                    resolve_project_paths(&mut table, project_root);
+                }
+            }
+            layers.push((ConfigLayer::File { location, path }, table));
+        }
+    }
+    let env_table = env_table();
+    if !env_table.is_empty() {
+        layers.push((ConfigLayer::Env, env_table));
+    }
+    Ok(layers)
+}
+
+/// The config values that are set in a layer, with their dotted keys.
+pub fn flatten_table(table: &Table) -> Vec<(String, &Value)> {
+    table
+        .iter()
+        .flat_map(|(key, value)| match value {
+            Value::Table(table) => flatten_table(table)
+                .into_iter()
+                .map(|(subkey, value)| (format!("{key}.{subkey}"), value))
+                .collect(),
+            value => vec![(key.clone(), value)],
+        })
+        .collect()
+}
+
+/// Resolve the relative paths in a project's `.lux/config.toml` against the project root,
+/// rather than the current directory.
+fn resolve_project_paths(table: &mut Table, project_root: &Path) {
+    let resolve = |value: &mut Value| {
+        if let Value::String(path) = value {
+            *path = project_root.join(&*path).to_string_lossy().to_string();
+        }
+    };
+    if let Some(vendor_dir) = table.get_mut("vendor_dir") {
+        resolve(vendor_dir);
+    }
+    if let Some(Value::Table(external_deps)) = table.get_mut("external_deps") {
+        if let Some(Value::Table(prefixes)) = external_deps.get_mut("prefixes") {
+            prefixes.iter_mut().for_each(|(_, value)| resolve(value));
+        }
+        if let Some(Value::Array(search_prefixes)) = external_deps.get_mut("search_prefixes") {
+            search_prefixes.iter_mut().for_each(resolve);
+        }
+    }
+}
+
+/// Replace secret values (e.g. `api_key` and the `auth` of `servers`) with a placeholder,
+/// so that they can be printed.
+pub fn redact_secrets(table: &Table) -> Table {
+    table
+        .iter()
+        .map(|(key, value)| {
+            let value = if SECRET_KEYS.contains(&key.as_str()) {
+                Value::String("<redacted>".into())
+            } else {
+                redact_value(value)
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+fn redact_value(value: &Value) -> Value {
+    match value {
+        Value::Table(table) => Value::Table(redact_secrets(table)),
+        Value::Array(values) => Value::Array(values.iter().map(redact_value).collect()),
+        value => value.clone(),
+    }
+}
+
+pub(crate) fn merge_tables(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(layer_table)) => {
+                merge_tables(base_table, layer_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table, ConfigError> {
+    std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|err| ConfigError::DeserializeFile(path.to_path_buf(), err))
+}
+
+fn env_table() -> Table {
+    ENV_KEYS
+        .iter()
+        .filter_map(|key| {
+            let value = env::var(format!("LUX_{}", key.to_uppercase())).ok()?;
+            Some((key.to_string(), parse_env_value(&value)))
+        })
+        .collect()
+}
+
+/// Booleans and integers are parsed, so that e.g. `LUX_OFFLINE=true` works.
+/// Everything else is a string, so that e.g. `LUX_LUA_VERSION=5.4` is not parsed as a float.
+fn parse_env_value(value: &str) -> Value {
+    if let Ok(value) = value.parse::<bool>() {
+        Value::Boolean(value)
+    } else if let Ok(value) = value.parse::<i64>() {
+        Value::Integer(value)
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+/// A config file that can be edited, e.g. with `lx config set`.
+/// Edits preserve the file's formatting and comments.
+pub struct ConfigFile {
+    location: ConfigLocation,
+    path: PathBuf,
+    document: DocumentMut,
+    table: Table,
+}
+
+impl ConfigFile {
+    /// Open the config file at the given location, or an empty config if it does not exist.
+    pub fn open(location: ConfigLocation) -> Result<Self, ConfigError> {
+        let path = location.path()?.ok_or(ConfigError::NoProject)?;
+        Self::open_path(location, path)
+    }
+
+    pub(crate) fn open_path(location: ConfigLocation, path: PathBuf) -> Result<Self, ConfigError> {
+        if !path.is_file() {
+            return Ok(Self {
+                location,
+                path,
+                document: DocumentMut::new(),
+                table: Table::new(),
+            });
+        }
+        let table = read_table(&path)?;
+        let document = std::fs::read_to_string(&path)?
+            .parse()
+            .map_err(|err| ConfigError::EditFile(path.clone(), err))?;
+        Ok(Self {
+            location,
+            path,
+            document,
+            table,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Get a value by its dotted key, e.g. `external_deps.prefixes`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        get_value(&self.table, key)
+    }
+
+    /// Set a value by its dotted key.
+    /// The value is parsed as TOML if that results in a valid config,
+    /// and is set as a string otherwise.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let top_level_key = key.split_once('.').map_or(key, |(head, _)| head);
+        if !self.location.allows_key(top_level_key) {
+            return Err(ConfigError::KeyNotAllowed {
+                key: key.to_string(),
+                location: self.location,
+            });
+        }
+        let mut candidates = value
+            .parse::<toml_edit::Value>()
+            .ok()
+            .into_iter()
+            .collect::<Vec<_>>();
+        candidates.push(toml_edit::Value::from(value));
+
+        let mut last_err = None;
+        for candidate in candidates {
+            let mut document = self.document.clone();
+            set_item(document.as_table_mut(), key, candidate);
+            let result = toml::from_str::<Table>(&document.to_string()).and_then(|table| {
+                Value::Table(table.clone())
+                    .try_into::<ConfigBuilder>()
+                    .map(|_| table)
+            });
+            match result {
+                Ok(table) => {
+                    self.document = document;
+                    self.table = table;
+                    return Ok(());
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(ConfigError::InvalidValue {
+            key: key.to_string(),
+            err: last_err.expect("at least one candidate value"),
+        })
+    }
+
+    /// Write the config file, creating its parent directory if necessary.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, self.document.to_string())?;
+        Ok(())
+    }
+}
+
+/// Get a value by its dotted key, e.g. `external_deps.prefixes`.
+pub fn get_value<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    match key.split_once('.') {
+        Some((head, rest)) => get_value(table.get(head)?.as_table()?, rest),
+        None => table.get(key),
+    }
+}
+
+fn set_item(table: &mut dyn TableLike, key: &str, value: toml_edit::Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            if !table.get(head).is_some_and(Item::is_table_like) {
+                table.insert(head, Item::Table(toml_edit::Table::new()));
+            }
+            if let Some(subtable) = table.get_mut(head).and_then(Item::as_table_like_mut) {
+                set_item(subtable, rest, value);
+            }
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_layers() {
+        let mut base: Table = "lua_version = \"5.1\"\n[variables]\nCC = \"gcc\"\nLD = \"ld\""
+            .parse()
+            .unwrap();
+        let layer: Table = "lua_version = \"5.4\"\n[variables]\nCC = \"clang\""
+            .parse()
+            .unwrap();
+        merge_tables(&mut base, layer);
+        assert_eq!(
+            get_value(&base, "lua_version").unwrap().as_str(),
+            Some("5.4")
+        );
+        assert_eq!(
+            get_value(&base, "variables.CC").unwrap().as_str(),
+            Some("clang")
+        );
+        assert_eq!(
+            get_value(&base, "variables.LD").unwrap().as_str(),
+            Some("ld")
+        );
+    }
+
+    #[test]
+    fn set_values() {
+        let mut config_file = ConfigFile {
+            location: ConfigLocation::User,
+            path: PathBuf::from("config.toml"),
+            document: DocumentMut::new(),
+            table: Table::new(),
+        };
+        config_file.set("lua_version", "5.4").unwrap();
+        config_file.set("offline", "true").unwrap();
+        config_file.set("variables.CC", "clang").unwrap();
+        assert_eq!(
+            config_file.get("lua_version"),
+            Some(&Value::String("5.4".into()))
+        );
+        assert_eq!(config_file.get("offline"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            config_file.get("variables.CC"),
+            Some(&Value::String("clang".into()))
+        );
+        assert!(config_file.set("max_jobs", "many").is_err());
+        assert!(config_file.set("cc", "clang").is_err());
+        assert_eq!(parse_env_value("5.4"), Value::String("5.4".into()));
+    }
+
+    #[test]
+    fn set_values_preserves_formatting() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let content = "# Use LuaJIT for Neovim plugins\nlua_version = \"jit\" # pinned\n\n[variables]\nCC = \"gcc\"\n";
+        std::fs::write(&path, content).unwrap();
+        let mut config_file = ConfigFile::open_path(ConfigLocation::User, path.clone()).unwrap();
+        config_file.set("variables.CC", "clang").unwrap();
+        config_file.set("offline", "true").unwrap();
+        config_file.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("# Use LuaJIT for Neovim plugins"));
+        assert!(saved.contains("lua_version = \"jit\" # pinned"));
+        assert!(saved.contains("CC = \"clang\""));
+        assert_eq!(
+            ConfigFile::open_path(ConfigLocation::User, path)
+                .unwrap()
+                .get("offline"),
+            Some(&Value::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn project_layer_paths() {
+        let mut table: Table = r#"
+vendor_dir = "vendor"
+[external_deps]
+prefixes = { SQLITE_DIR = "deps/sqlite", OPENSSL_DIR = "/usr" }
+search_prefixes = ["deps"]
+"#
+        .parse()
+        .unwrap();
+        resolve_project_paths(&mut table, Path::new("/project"));
+        assert_eq!(
+            get_value(&table, "vendor_dir").unwrap().as_str(),
+            Some("/project/vendor")
+        );
+        assert_eq!(
+            get_value(&table, "external_deps.prefixes.SQLITE_DIR")
+                .unwrap()
+                .as_str(),
+            Some("/project/deps/sqlite")
+        );
+        assert_eq!(
+            get_value(&table, "external_deps.prefixes.OPENSSL_DIR")
+                .unwrap()
+                .as_str(),
+            Some("/usr")
+        );
+        assert!(ConfigLocation::Project.allows_key("cc"));
+        assert!(!ConfigLocation::Project.allows_key("server"));
+        assert!(!ConfigLocation::User.allows_key("tree"));
+    }
+
+    #[test]
+    fn redact_secret_values() {
+        let table: Table = r#"
+api_key = "secret"
+lua_version = "5.4"
+[[servers]]
+url = "https://rocks.example.com/"
+auth = { token = "secret" }
+"#
+        .parse()
+        .unwrap();
+        let redacted = Value::Table(redact_secrets(&table)).to_string();
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("5.4"));
+        assert!(redacted.contains("https://rocks.example.com/"));
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// License allow- and denylists, used by `lx license` to fail
+/// if a dependency has a forbidden license.
+///
+/// License names are compared case-insensitively.
+/// A license expression with alternatives, e.g. `MIT OR Apache-2.0` or `MIT/X11`,
+/// is allowed if any of its alternatives is allowed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LicensePolicy {
+    /// If set, only these licenses are allowed,
+    /// and packages without license metadata are forbidden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow: Option<Vec<String>>,
+    /// Forbidden licenses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny: Vec<String>,
+}
+
+impl LicensePolicy {
+    pub fn new(allow: Option<Vec<String>>, deny: Vec<String>) -> Self {
+        Self { allow, deny }
+    }
+
+    pub fn allow(&self) -> Option<&Vec<String>> {
+        self.allow.as_ref()
+    }
+
+    pub fn deny(&self) -> &Vec<String> {
+        &self.deny
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_none() && self.deny.is_empty()
+    }
+
+    /// Whether a package with the given license is allowed.
+    pub fn is_allowed(&self, license: Option<&str>) -> bool {
+        match license {
+            None => self.allow.is_none(),
+            Some(license) => alternatives(license).any(|alternative| {
+                let alternative = alternative.to_lowercase();
+                !self
+                    .deny
+                    .iter()
+                    .any(|denied| denied.to_lowercase() == alternative)
+                    && self.allow.as_ref().is_none_or(|allow| {
+                        allow
+                            .iter()
+                            .any(|allowed| allowed.to_lowercase() == alternative)
+                    })
+            }),
+        }
+    }
+}
+
+fn alternatives(license: &str) -> impl Iterator<Item = &str> {
+    license
+        .split(" OR ")
+        .flat_map(|license| license.split(" or "))
+        .flat_map(|license| license.split('/'))
+        .map(|license| license.trim().trim_matches(['(', ')']).trim())
+        .filter(|license| !license.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn license_policy() {
+        let policy = LicensePolicy::default();
+        assert!(policy.is_allowed(Some("GPL-3.0")));
+        assert!(policy.is_allowed(None));
+
+        let policy = LicensePolicy::new(None, vec!["GPL-3.0".into()]);
+        assert!(!policy.is_allowed(Some("gpl-3.0")));
+        assert!(policy.is_allowed(Some("GPL-3.0 OR MIT")));
+        assert!(policy.is_allowed(None));
+
+        let policy = LicensePolicy::new(Some(vec!["MIT".into(), "X11".into()]), Vec::new());
+        assert!(policy.is_allowed(Some("MIT/X11")));
+        assert!(policy.is_allowed(Some("(Apache-2.0 OR MIT)")));
+        assert!(!policy.is_allowed(Some("Apache-2.0")));
+        assert!(!policy.is_allowed(None));
+    }
+}
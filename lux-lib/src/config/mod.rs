@@ -1,8 +1,14 @@
+use build_sandbox::BuildSandbox;
+use credentials::Credentials;
 use directories::ProjectDirs;
 use external_deps::ExternalDependencySearchConfig;
+use file_conflicts::FileConflictPolicy;
 use itertools::Itertools;
+use license_policy::LicensePolicy;
 use mlua::{ExternalError, ExternalResult, FromLua, IntoLua, UserData};
+use reqwest::{Client, ClientBuilder, RequestBuilder};
 use serde::{Deserialize, Serialize, Serializer};
+use server::{with_directory_path, ServerAuth, ServerConfig, SignaturePolicy};
 use std::{
     collections::HashMap, env, fmt::Display, io, path::PathBuf, str::FromStr, time::Duration,
 };
@@ -10,15 +16,24 @@ use thiserror::Error;
 use tree::RockLayoutConfig;
 use url::Url;
 
+use crate::project::project_config::ProjectConfig;
 use crate::tree::{Tree, TreeError};
 use crate::variables::GetVariableError;
 use crate::{
     build::utils,
+    message::MessageFormat,
     package::{PackageVersion, PackageVersionReq},
+    upload::ApiKey,
     variables::HasVariables,
 };
 
+pub mod build_sandbox;
+pub mod credentials;
 pub mod external_deps;
+pub mod file_conflicts;
+pub mod layers;
+pub mod license_policy;
+pub mod server;
 pub mod tree;
 
 const DEV_PATH: &str = "dev/";
@@ -61,6 +76,15 @@ pub enum LuaVersionError {
 }
 
 impl LuaVersion {
+    pub const ALL: [LuaVersion; 6] = [
+        LuaVersion::Lua51,
+        LuaVersion::Lua52,
+        LuaVersion::Lua53,
+        LuaVersion::Lua54,
+        LuaVersion::LuaJIT,
+        LuaVersion::LuaJIT52,
+    ];
+
     pub fn as_version(&self) -> PackageVersion {
         match self {
             LuaVersion::Lua51 => "5.1.0".parse().unwrap(),
@@ -177,22 +201,60 @@ pub struct Config {
     enable_development_packages: bool,
     server: Url,
     extra_servers: Vec<Url>,
+    /// Additional servers, with priorities and credentials.
+    servers: Vec<ServerConfig>,
     only_sources: Option<String>,
     namespace: Option<String>,
     lua_dir: Option<PathBuf>,
+    prebuilt_lua_url: Option<Url>,
     lua_version: Option<LuaVersion>,
     user_tree: PathBuf,
     no_project: bool,
     verbose: bool,
+    /// Fail instead of accessing the network.
+    offline: bool,
+    /// Download manifests, instead of revalidating the cached manifests with the server.
+    refresh_manifests: bool,
+    /// Resolve dependencies to the lowest versions that satisfy all constraints.
+    minimal_versions: bool,
+    /// The URL of the security advisory database used by `lx audit`.
+    advisory_db: Option<Url>,
+    /// A directory created by `lx vendor` to look up downloads in.
+    vendor_dir: Option<PathBuf>,
+    /// The default timeout for connecting to and reading from servers,
+    /// which can be overridden per server.
     timeout: Duration,
+    /// How often to retry a failed download, with exponential backoff.
+    retries: usize,
+    /// The maximum number of packages to build in parallel.
+    max_jobs: usize,
     variables: HashMap<String, String>,
     external_deps: ExternalDependencySearchConfig,
+    /// License allow- and denylists for dependencies.
+    licenses: LicensePolicy,
+    /// Restrictions for the build steps of third-party rocks.
+    build_sandbox: BuildSandbox,
+    /// Also archive C modules built with the builtin backend into static libraries,
+    /// so that they can be linked into a single executable.
+    static_libs: bool,
+    /// What to do if packages provide the same executables or Lua modules.
+    file_conflicts: FileConflictPolicy,
+    /// The format of the messages printed while downloading, building and installing rocks.
+    message_format: MessageFormat,
+    /// The API key for uploading packages to the `server`.
+    api_key: Option<ApiKey>,
+    /// Tokens for servers, stored with `lx login`.
+    credentials: Credentials,
     /// The rock layout for entrypoints of new install trees.
     /// Does not affect existing install trees or dependency rock layouts.
     entrypoint_layout: RockLayoutConfig,
+    /// Overrides of the C compiler, flags and install tree for the current project.
+    project_config: ProjectConfig,
 
     cache_dir: PathBuf,
     data_dir: PathBuf,
+    /// The directory for the shims of globally installed tools.
+    global_bin_dir: PathBuf,
     generate_luarc: bool,
 }
 
@@ -211,6 +273,11 @@ impl Config {
         Ok(project_dirs.data_local_dir().to_path_buf())
     }
 
+    pub fn get_default_global_bin_path() -> Result<PathBuf, NoValidHomeDirectory> {
+        let base_dirs = directories::BaseDirs::new().ok_or(NoValidHomeDirectory)?;
+        Ok(base_dirs.home_dir().join(".lux").join("bin"))
+    }
+
     pub fn with_lua_version(self, lua_version: LuaVersion) -> Self {
         Self {
             lua_version: Some(lua_version),
@@ -225,6 +292,68 @@ impl Config {
         }
     }
 
+    pub fn with_lua_dir(self, lua_dir: PathBuf) -> Self {
+        Self {
+            lua_dir: Some(lua_dir),
+            ..self
+        }
+    }
+
+    pub fn with_file_conflicts(self, file_conflicts: FileConflictPolicy) -> Self {
+        Self {
+            file_conflicts,
+            ..self
+        }
+    }
+
+    pub fn with_message_format(self, message_format: MessageFormat) -> Self {
+        Self {
+            message_format,
+            ..self
+        }
+    }
+
+    /// Set the `variables`, keeping the existing variables that are not overridden.
+    pub fn with_variables(self, variables: HashMap<String, String>) -> Self {
+        Self {
+            variables: self.variables.into_iter().chain(variables).collect(),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_project_config(self, project_config: ProjectConfig) -> Self {
+        Self {
+            project_config,
+            ..self
+        }
+    }
+
+    /// Add installation prefixes for specific external dependencies,
+    /// and `search_prefixes` that are searched before the configured ones.
+    pub fn with_external_deps_prefixes(
+        self,
+        prefixes: HashMap<String, PathBuf>,
+        search_prefixes: Vec<PathBuf>,
+    ) -> Self {
+        let external_deps = ExternalDependencySearchConfig {
+            prefixes: self
+                .external_deps
+                .prefixes
+                .into_iter()
+                .chain(prefixes)
+                .collect(),
+            search_prefixes: search_prefixes
+                .into_iter()
+                .chain(self.external_deps.search_prefixes)
+                .collect(),
+            ..self.external_deps
+        };
+        Self {
+            external_deps,
+            ..self
+        }
+    }
+
     pub fn server(&self) -> &Url {
         &self.server
     }
@@ -233,17 +362,78 @@ impl Config {
         self.extra_servers.as_ref()
     }
 
+    pub fn servers(&self) -> &Vec<ServerConfig> {
+        &self.servers
+    }
+
+    /// All servers to fetch rocks/rockspecs from, in the order in which they should be searched.
+    /// Servers with a higher priority come first. For servers with the same priority,
+    /// the `servers` come before the `extra_servers`, and the main `server` comes last.
+    pub fn servers_by_priority(&self) -> Vec<&Url> {
+        self.servers
+            .iter()
+            .map(|server| (server.priority(), server.url()))
+            .chain(self.extra_servers.iter().map(|url| (0, url)))
+            .chain(std::iter::once((0, &self.server)))
+            // sort_by is stable, so servers with the same priority keep their order
+            .sorted_by(|(a, _), (b, _)| b.cmp(a))
+            .map(|(_, url)| url)
+            .collect()
+    }
+
     pub fn enabled_dev_servers(&self) -> Result<Vec<Url>, ConfigError> {
         let mut enabled_dev_servers = Vec::new();
         if self.enable_development_packages {
-            enabled_dev_servers.push(self.server().join(DEV_PATH)?);
-            for server in self.extra_servers() {
+            for server in self.servers_by_priority() {
                 enabled_dev_servers.push(server.join(DEV_PATH)?);
             }
         }
         Ok(enabled_dev_servers)
     }
 
+    /// The credentials for the configured server that `url` belongs to, if any.
+    pub fn server_auth(&self, url: &Url) -> Option<&ServerAuth> {
+        self.servers
+            .iter()
+            .find(|server| server.contains(url))
+            .and_then(|server| server.auth())
+    }
+
+    /// The signature policy of the configured server that `url` belongs to, if any.
+    pub fn signature_policy(&self, url: &Url) -> Option<&SignaturePolicy> {
+        self.servers
+            .iter()
+            .find(|server| server.contains(url))
+            .map(|server| server.signatures())
+    }
+
+    /// Add the credentials for the server that `url` belongs to, if any, to the `request`.
+    /// Credentials in the config take precedence over tokens stored with `lx login`,
+    /// which are only sent to the API of the server they were stored for.
+    pub(crate) fn authenticate(&self, url: &Url, request: RequestBuilder) -> RequestBuilder {
+        match self.server_auth(url) {
+            Some(auth) => auth.authenticate(request),
+            None => match self.stored_token(url) {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            },
+        }
+    }
+
+    /// The token stored with `lx login` for the server whose API `url` is an endpoint of, if any.
+    /// Tokens are only sent to the `api/` endpoints of configured servers,
+    /// never with downloads of rocks and rockspecs.
+    fn stored_token(&self, url: &Url) -> Option<String> {
+        self.servers_by_priority()
+            .into_iter()
+            .filter(|server| {
+                server
+                    .join("api/")
+                    .is_ok_and(|api_url| server::is_below(&api_url, url))
+            })
+            .find_map(|server| self.credentials.get(server).ok().flatten())
+    }
+
     pub fn only_sources(&self) -> Option<&String> {
         self.only_sources.as_ref()
     }
@@ -256,6 +446,12 @@ impl Config {
         self.lua_dir.as_ref()
     }
 
+    /// The base URL of a server with prebuilt Lua binaries,
+    /// which are preferred over building Lua from source.
+    pub fn prebuilt_lua_url(&self) -> Option<&Url> {
+        self.prebuilt_lua_url.as_ref()
+    }
+
     #[cfg(test)]
     pub(crate) fn lua_version(&self) -> Option<&LuaVersion> {
         self.lua_version.as_ref()
@@ -275,14 +471,76 @@ impl Config {
         self.verbose
     }
 
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn refresh_manifests(&self) -> bool {
+        self.refresh_manifests
+    }
+
+    pub fn minimal_versions(&self) -> bool {
+        self.minimal_versions
+    }
+
+    pub fn advisory_db(&self) -> Option<&Url> {
+        self.advisory_db.as_ref()
+    }
+
+    pub fn vendor_dir(&self) -> Option<&PathBuf> {
+        self.vendor_dir.as_ref()
+    }
+
     pub fn timeout(&self) -> &Duration {
         &self.timeout
     }
 
+    /// The timeout of the configured server that `url` belongs to,
+    /// or the default timeout.
+    pub fn timeout_for(&self, url: &Url) -> Duration {
+        self.servers
+            .iter()
+            .find(|server| server.contains(url))
+            .and_then(|server| server.timeout())
+            .unwrap_or(self.timeout)
+    }
+
+    /// A builder for an HTTP client for requests to `url`, with the timeout, proxy
+    /// and TLS settings of the configured server that `url` belongs to.
+    /// The `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables are honored,
+    /// unless the server has a proxy configured.
+    pub(crate) fn http_client_builder(&self, url: &Url) -> Result<ClientBuilder, reqwest::Error> {
+        let timeout = self.timeout_for(url);
+        let builder = if timeout.is_zero() {
+            Client::builder()
+        } else {
+            Client::builder()
+                .connect_timeout(timeout)
+                .read_timeout(timeout)
+        };
+        match self.servers.iter().find(|server| server.contains(url)) {
+            Some(server) => server.configure_client(builder),
+            None => Ok(builder),
+        }
+    }
+
+    /// An HTTP client for requests to `url`. See [`Config::http_client_builder`].
+    pub fn http_client(&self, url: &Url) -> Result<Client, reqwest::Error> {
+        self.http_client_builder(url)?.build()
+    }
+
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    pub fn max_jobs(&self) -> usize {
+        self.max_jobs
+    }
+
     pub fn make_cmd(&self) -> String {
         match self.variables.get("MAKE") {
             Some(make) => make.clone(),
-            None => "make".into(),
+            None => utils::default_make().into(),
         }
     }
 
@@ -301,10 +559,46 @@ impl Config {
         &self.external_deps
     }
 
+    /// Overrides of the C compiler, flags and install tree for the current project.
+    /// Only the C modules built by lux itself (rather than by `make` or `cmake`)
+    /// use the compiler and flags from here, so that the configured `variables`
+    /// don't override the compiler that is detected for each build.
+    pub fn project_config(&self) -> &ProjectConfig {
+        &self.project_config
+    }
+
+    pub fn licenses(&self) -> &LicensePolicy {
+        &self.licenses
+    }
+
+    pub fn build_sandbox(&self) -> &BuildSandbox {
+        &self.build_sandbox
+    }
+
+    pub fn static_libs(&self) -> bool {
+        self.static_libs
+    }
+
+    pub fn file_conflicts(&self) -> FileConflictPolicy {
+        self.file_conflicts
+    }
+
+    pub fn message_format(&self) -> MessageFormat {
+        self.message_format
+    }
+
     pub fn entrypoint_layout(&self) -> &RockLayoutConfig {
         &self.entrypoint_layout
     }
 
+    pub fn api_key(&self) -> Option<&ApiKey> {
+        self.api_key.as_ref()
+    }
+
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
     pub fn cache_dir(&self) -> &PathBuf {
         &self.cache_dir
     }
@@ -313,6 +607,10 @@ impl Config {
         &self.data_dir
     }
 
+    pub fn global_bin_dir(&self) -> &PathBuf {
+        &self.global_bin_dir
+    }
+
     pub fn generate_luarc(&self) -> bool {
         self.generate_luarc
     }
@@ -332,10 +630,27 @@ pub enum ConfigError {
     NoValidHomeDirectory(#[from] NoValidHomeDirectory),
     #[error("error deserializing lux config: {0}")]
     Deserialize(#[from] toml::de::Error),
+    #[error("error deserializing lux config {0}: {1}")]
+    DeserializeFile(PathBuf, toml::de::Error),
+    #[error("error serializing lux config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("error parsing lux config {0}: {1}")]
+    EditFile(PathBuf, toml_edit::TomlError),
+    #[error("invalid value for `{key}`: {err}")]
+    InvalidValue { key: String, err: toml::de::Error },
+    #[error("`{key}` can't be set in the {location} config")]
+    KeyNotAllowed {
+        key: String,
+        location: layers::ConfigLocation,
+    },
+    #[error("not in a project directory")]
+    NoProject,
     #[error("error parsing URL: {0}")]
     UrlParseError(#[from] url::ParseError),
     #[error("error initializing compiler toolchain: {0}")]
     CompilerToolchain(#[from] cc::Error),
+    #[error("failed to read CA certificate {0}: {1}")]
+    CaCertificate(PathBuf, io::Error),
 }
 
 #[derive(Clone, Default, Deserialize, Serialize)]
@@ -352,38 +667,68 @@ pub struct ConfigBuilder {
         serialize_with = "serialize_url_vec"
     )]
     extra_servers: Option<Vec<Url>>,
+    servers: Option<Vec<ServerConfig>>,
     only_sources: Option<String>,
     namespace: Option<String>,
     lua_version: Option<LuaVersion>,
     user_tree: Option<PathBuf>,
     lua_dir: Option<PathBuf>,
+    prebuilt_lua_url: Option<Url>,
     cache_dir: Option<PathBuf>,
     data_dir: Option<PathBuf>,
+    global_bin_dir: Option<PathBuf>,
     no_project: Option<bool>,
     enable_development_packages: Option<bool>,
     verbose: Option<bool>,
+    offline: Option<bool>,
+    refresh_manifests: Option<bool>,
+    minimal_versions: Option<bool>,
+    advisory_db: Option<Url>,
+    vendor_dir: Option<PathBuf>,
     timeout: Option<Duration>,
+    retries: Option<usize>,
+    max_jobs: Option<usize>,
     variables: Option<HashMap<String, String>>,
     #[serde(default)]
     external_deps: ExternalDependencySearchConfig,
+    #[serde(default)]
+    licenses: LicensePolicy,
+    #[serde(default)]
+    build_sandbox: BuildSandbox,
+    static_libs: Option<bool>,
+    file_conflicts: Option<FileConflictPolicy>,
+    message_format: Option<MessageFormat>,
+    /// Never serialized, so that the API key is not leaked when printing the config.
+    #[serde(skip_serializing)]
+    api_key: Option<String>,
     /// The rock layout for new install trees.
     /// Does not affect existing install trees.
     #[serde(default)]
     entrypoint_layout: RockLayoutConfig,
     generate_luarc: Option<bool>,
+    /// The C compiler for the current project.
+    /// Like `cflags`, `ldflags` and `tree`, this can only be set in a project's `.lux/config.toml`.
+    cc: Option<String>,
+    /// Flags that are passed to the C compiler for the current project.
+    cflags: Option<String>,
+    /// Flags that are passed to the linker for the current project.
+    ldflags: Option<String>,
+    /// The directory to install the current project's dependencies to,
+    /// relative to the project root.
+    tree: Option<PathBuf>,
 }
 
 /// A builder for the lux `Config`.
 impl ConfigBuilder {
-    /// Create a new `ConfigBuilder` from a config file by deserializing from a config file
-    /// if present, or otherwise by instantiating the default config.
+    /// Create a new `ConfigBuilder` by merging the system, user and project config files
+    /// and the `LUX_*` environment variables, if present.
+    /// See the [`layers`] module for the precedence of each layer.
     pub fn new() -> Result<Self, ConfigError> {
-        let config_file = Self::config_file()?;
-        if config_file.is_file() {
-            Ok(toml::from_str(&std::fs::read_to_string(&config_file)?)?)
-        } else {
-            Ok(Self::default())
+        let mut table = toml::Table::new();
+        for (_, layer) in layers::config_layers()? {
+            layers::merge_tables(&mut table, layer);
         }
+        Ok(toml::Value::Table(table).try_into()?)
     }
 
     /// Get the path to the lux config file.
@@ -414,6 +759,13 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn servers(self, servers: Option<Vec<ServerConfig>>) -> Self {
+        Self {
+            servers: servers.or(self.servers),
+            ..self
+        }
+    }
+
     pub fn only_sources(self, sources: Option<String>) -> Self {
         Self {
             only_sources: sources.or(self.only_sources),
@@ -435,6 +787,13 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn prebuilt_lua_url(self, prebuilt_lua_url: Option<Url>) -> Self {
+        Self {
+            prebuilt_lua_url: prebuilt_lua_url.or(self.prebuilt_lua_url),
+            ..self
+        }
+    }
+
     pub fn lua_version(self, lua_version: Option<LuaVersion>) -> Self {
         Self {
             lua_version: lua_version.or(self.lua_version),
@@ -470,6 +829,73 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn offline(self, offline: Option<bool>) -> Self {
+        Self {
+            offline: offline.or(self.offline),
+            ..self
+        }
+    }
+
+    pub fn refresh_manifests(self, refresh_manifests: Option<bool>) -> Self {
+        Self {
+            refresh_manifests: refresh_manifests.or(self.refresh_manifests),
+            ..self
+        }
+    }
+
+    pub fn minimal_versions(self, minimal_versions: Option<bool>) -> Self {
+        Self {
+            minimal_versions: minimal_versions.or(self.minimal_versions),
+            ..self
+        }
+    }
+
+    pub fn static_libs(self, static_libs: Option<bool>) -> Self {
+        Self {
+            static_libs: static_libs.or(self.static_libs),
+            ..self
+        }
+    }
+
+    pub fn file_conflicts(self, file_conflicts: Option<FileConflictPolicy>) -> Self {
+        Self {
+            file_conflicts: file_conflicts.or(self.file_conflicts),
+            ..self
+        }
+    }
+
+    pub fn message_format(self, message_format: Option<MessageFormat>) -> Self {
+        Self {
+            message_format: message_format.or(self.message_format),
+            ..self
+        }
+    }
+
+    /// Enable or disable the build sandbox, keeping the other sandbox settings.
+    pub fn sandbox_builds(self, sandbox_builds: Option<bool>) -> Self {
+        match sandbox_builds {
+            Some(enabled) => Self {
+                build_sandbox: self.build_sandbox.with_enabled(enabled),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    pub fn advisory_db(self, advisory_db: Option<Url>) -> Self {
+        Self {
+            advisory_db: advisory_db.or(self.advisory_db),
+            ..self
+        }
+    }
+
+    pub fn vendor_dir(self, vendor_dir: Option<PathBuf>) -> Self {
+        Self {
+            vendor_dir: vendor_dir.or(self.vendor_dir),
+            ..self
+        }
+    }
+
     pub fn timeout(self, timeout: Option<Duration>) -> Self {
         Self {
             timeout: timeout.or(self.timeout),
@@ -477,6 +903,20 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn retries(self, retries: Option<usize>) -> Self {
+        Self {
+            retries: retries.or(self.retries),
+            ..self
+        }
+    }
+
+    pub fn max_jobs(self, max_jobs: Option<usize>) -> Self {
+        Self {
+            max_jobs: max_jobs.or(self.max_jobs),
+            ..self
+        }
+    }
+
     pub fn cache_dir(self, cache_dir: Option<PathBuf>) -> Self {
         Self {
             cache_dir: cache_dir.or(self.cache_dir),
@@ -491,6 +931,13 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn global_bin_dir(self, global_bin_dir: Option<PathBuf>) -> Self {
+        Self {
+            global_bin_dir: global_bin_dir.or(self.global_bin_dir),
+            ..self
+        }
+    }
+
     pub fn entrypoint_layout(self, rock_layout: RockLayoutConfig) -> Self {
         Self {
             entrypoint_layout: rock_layout,
@@ -498,6 +945,28 @@ impl ConfigBuilder {
         }
     }
 
+    /// Configure lux for installing Neovim packages:
+    /// Entrypoints are installed into Neovim's `site/pack` layout and,
+    /// because Neovim embeds LuaJIT, the Lua version is set to `luajit`,
+    /// unless a version with a compatible ABI (Lua 5.1) is already set.
+    pub fn nvim_profile(self) -> Self {
+        let lua_version = match &self.lua_version {
+            Some(lua_version @ (LuaVersion::Lua51 | LuaVersion::LuaJIT)) => lua_version.clone(),
+            _ => LuaVersion::LuaJIT,
+        };
+        Self {
+            lua_version: Some(lua_version),
+            ..self.entrypoint_layout(RockLayoutConfig::new_nvim_layout())
+        }
+    }
+
+    pub fn api_key(self, api_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.or(self.api_key),
+            ..self
+        }
+    }
+
     pub fn generate_luarc(self, generate: Option<bool>) -> Self {
         Self {
             generate_luarc: generate.or(self.generate_luarc),
@@ -509,32 +978,79 @@ impl ConfigBuilder {
         let data_dir = self.data_dir.unwrap_or(Config::get_default_data_path()?);
         let cache_dir = self.cache_dir.unwrap_or(Config::get_default_cache_path()?);
         let user_tree = self.user_tree.unwrap_or(data_dir.join("tree"));
+        let global_bin_dir = match self.global_bin_dir {
+            Some(global_bin_dir) => global_bin_dir,
+            None => Config::get_default_global_bin_path()?,
+        };
 
         let lua_version = self
             .lua_version
             .or(crate::lua_installation::detect_installed_lua_version());
+        let variables: HashMap<String, String> = default_variables()
+            .chain(self.variables.unwrap_or_default())
+            .collect();
 
         Ok(Config {
             enable_development_packages: self.enable_development_packages.unwrap_or(false),
             server: self
                 .server
+                .map(with_directory_path)
                 .unwrap_or_else(|| Url::parse("https://luarocks.org/").unwrap()),
-            extra_servers: self.extra_servers.unwrap_or_default(),
+            extra_servers: self
+                .extra_servers
+                .unwrap_or_default()
+                .into_iter()
+                .map(with_directory_path)
+                .collect(),
+            servers: self
+                .servers
+                .unwrap_or_default()
+                .into_iter()
+                .map(ServerConfig::load_ca_certs)
+                .collect::<Result<_, _>>()
+                .map_err(|(path, err)| ConfigError::CaCertificate(path, err))?,
             only_sources: self.only_sources,
             namespace: self.namespace,
             lua_dir: self.lua_dir,
+            prebuilt_lua_url: self.prebuilt_lua_url,
             lua_version,
             user_tree,
             no_project: self.no_project.unwrap_or(false),
             verbose: self.verbose.unwrap_or(false),
+            offline: self.offline.unwrap_or(false),
+            refresh_manifests: self.refresh_manifests.unwrap_or(false),
+            minimal_versions: self.minimal_versions.unwrap_or(false),
+            advisory_db: self.advisory_db,
+            vendor_dir: self.vendor_dir,
             timeout: self.timeout.unwrap_or_else(|| Duration::from_secs(30)),
-            variables: default_variables()
-                .chain(self.variables.unwrap_or_default())
-                .collect(),
-            external_deps: self.external_deps,
+            retries: self.retries.unwrap_or(3),
+            max_jobs: match self.max_jobs {
+                Some(0) | None => std::thread::available_parallelism()
+                    .map(usize::from)
+                    .unwrap_or(1),
+                Some(max_jobs) => max_jobs,
+            },
+            external_deps: self.external_deps.with_variable_prefixes(&variables),
+            variables,
+            licenses: self.licenses,
+            build_sandbox: self.build_sandbox,
+            static_libs: self.static_libs.unwrap_or(false),
+            file_conflicts: self.file_conflicts.unwrap_or_default(),
+            message_format: self.message_format.unwrap_or_default(),
+            // SAFETY: The API key is sealed right after being read from the config.
+            api_key: self.api_key.map(|api_key| unsafe { ApiKey::from(api_key) }),
+            credentials: Credentials::new(data_dir.join("credentials.toml")),
             entrypoint_layout: self.entrypoint_layout,
+            project_config: ProjectConfig {
+                cc: self.cc,
+                cflags: self.cflags,
+                ldflags: self.ldflags,
+                tree: self.tree,
+                ..ProjectConfig::default()
+            },
             cache_dir,
             data_dir,
+            global_bin_dir,
             generate_luarc: self.generate_luarc.unwrap_or(true),
         })
     }
@@ -547,35 +1063,66 @@ impl From<Config> for ConfigBuilder {
             enable_development_packages: Some(value.enable_development_packages),
             server: Some(value.server),
             extra_servers: Some(value.extra_servers),
+            servers: Some(value.servers),
             only_sources: value.only_sources,
             namespace: value.namespace,
             lua_dir: value.lua_dir,
+            prebuilt_lua_url: value.prebuilt_lua_url,
             lua_version: value.lua_version,
             user_tree: Some(value.user_tree),
             no_project: Some(value.no_project),
             verbose: Some(value.verbose),
+            offline: Some(value.offline),
+            refresh_manifests: Some(value.refresh_manifests),
+            minimal_versions: Some(value.minimal_versions),
+            advisory_db: value.advisory_db,
+            vendor_dir: value.vendor_dir,
             timeout: Some(value.timeout),
+            retries: Some(value.retries),
+            max_jobs: Some(value.max_jobs),
             variables: Some(value.variables),
             cache_dir: Some(value.cache_dir),
             data_dir: Some(value.data_dir),
+            global_bin_dir: Some(value.global_bin_dir),
             external_deps: value.external_deps,
+            licenses: value.licenses,
+            build_sandbox: value.build_sandbox,
+            static_libs: Some(value.static_libs),
+            file_conflicts: Some(value.file_conflicts),
+            message_format: Some(value.message_format),
+            // SAFETY: The API key is never serialized.
+            api_key: value
+                .api_key
+                .map(|api_key| unsafe { api_key.get().clone() }),
             entrypoint_layout: value.entrypoint_layout,
             generate_luarc: Some(value.generate_luarc),
+            cc: value.project_config.cc,
+            cflags: value.project_config.cflags,
+            ldflags: value.project_config.ldflags,
+            tree: value.project_config.tree,
         }
     }
 }
 
 fn default_variables() -> impl Iterator<Item = (String, String)> {
     let cflags = env::var("CFLAGS").unwrap_or(utils::default_cflags().into());
-    vec![
-        ("MAKE".into(), "make".into()),
+    let mut variables = vec![
+        ("MAKE".into(), utils::default_make().into()),
         ("CMAKE".into(), "cmake".into()),
         ("LIB_EXTENSION".into(), utils::c_dylib_extension().into()),
         ("OBJ_EXTENSION".into(), utils::c_obj_extension().into()),
         ("CFLAGS".into(), cflags),
         ("LIBFLAG".into(), utils::default_libflag().into()),
-    ]
-    .into_iter()
+    ];
+    if cfg!(target_env = "msvc") {
+        // Like luarocks, we use the MSVC toolchain
+        variables.extend([
+            ("CC".into(), "cl".into()),
+            ("LD".into(), "link".into()),
+            ("AR".into(), "lib".into()),
+        ]);
+    }
+    variables.into_iter()
 }
 
 fn deserialize_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
@@ -661,9 +1208,23 @@ impl UserData for Config {
         });
         methods.add_method("no_project", |_, this, ()| Ok(this.no_project()));
         methods.add_method("verbose", |_, this, ()| Ok(this.verbose()));
+        methods.add_method("offline", |_, this, ()| Ok(this.offline()));
+        methods.add_method("refresh_manifests", |_, this, ()| {
+            Ok(this.refresh_manifests())
+        });
+        methods.add_method(
+            "minimal_versions",
+            |_, this, ()| Ok(this.minimal_versions()),
+        );
+        methods.add_method("vendor_dir", |_, this, ()| Ok(this.vendor_dir().cloned()));
         methods.add_method("timeout", |_, this, ()| Ok(this.timeout().as_secs()));
+        methods.add_method("retries", |_, this, ()| Ok(this.retries()));
+        methods.add_method("max_jobs", |_, this, ()| Ok(this.max_jobs()));
         methods.add_method("cache_dir", |_, this, ()| Ok(this.cache_dir().clone()));
         methods.add_method("data_dir", |_, this, ()| Ok(this.data_dir().clone()));
+        methods.add_method("global_bin_dir", |_, this, ()| {
+            Ok(this.global_bin_dir().clone())
+        });
         methods.add_method("entrypoint_layout", |_, this, ()| {
             Ok(this.entrypoint_layout().clone())
         });
@@ -719,15 +1280,45 @@ impl UserData for ConfigBuilder {
         methods.add_method("verbose", |_, this, verbose: Option<bool>| {
             Ok(this.clone().verbose(verbose))
         });
+        methods.add_method("offline", |_, this, offline: Option<bool>| {
+            Ok(this.clone().offline(offline))
+        });
+        methods.add_method(
+            "refresh_manifests",
+            |_, this, refresh_manifests: Option<bool>| {
+                Ok(this.clone().refresh_manifests(refresh_manifests))
+            },
+        );
+        methods.add_method(
+            "minimal_versions",
+            |_, this, minimal_versions: Option<bool>| {
+                Ok(this.clone().minimal_versions(minimal_versions))
+            },
+        );
+        methods.add_method("vendor_dir", |_, this, vendor_dir: Option<PathBuf>| {
+            Ok(this.clone().vendor_dir(vendor_dir))
+        });
         methods.add_method("timeout", |_, this, timeout: Option<u64>| {
             Ok(this.clone().timeout(timeout.map(Duration::from_secs)))
         });
+        methods.add_method("retries", |_, this, retries: Option<usize>| {
+            Ok(this.clone().retries(retries))
+        });
+        methods.add_method("max_jobs", |_, this, max_jobs: Option<usize>| {
+            Ok(this.clone().max_jobs(max_jobs))
+        });
         methods.add_method("cache_dir", |_, this, cache_dir: Option<PathBuf>| {
             Ok(this.clone().cache_dir(cache_dir))
         });
         methods.add_method("data_dir", |_, this, data_dir: Option<PathBuf>| {
             Ok(this.clone().data_dir(data_dir))
         });
+        methods.add_method(
+            "global_bin_dir",
+            |_, this, global_bin_dir: Option<PathBuf>| {
+                Ok(this.clone().global_bin_dir(global_bin_dir))
+            },
+        );
         methods.add_method(
             "entrypoint_layout",
             |_, this, entrypoint_layout: Option<RockLayoutConfig>| {
@@ -736,6 +1327,9 @@ impl UserData for ConfigBuilder {
                     .entrypoint_layout(entrypoint_layout.unwrap_or_default()))
             },
         );
+        methods.add_method("api_key", |_, this, api_key: Option<String>| {
+            Ok(this.clone().api_key(api_key))
+        });
         methods.add_method("generate_luarc", |_, this, generate: Option<bool>| {
             Ok(this.clone().generate_luarc(generate))
         });
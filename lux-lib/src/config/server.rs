@@ -0,0 +1,526 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use reqwest::{Certificate, ClientBuilder, Proxy, RequestBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use url::Url;
+
+/// A rock server to fetch rocks/rockspecs from, e.g. an organization's internal registry.
+/// The `url` can also be a `file://` URL of a local directory with the same layout,
+/// e.g. a mirror created with `lx mirror`.
+///
+/// Example:
+///
+/// ```toml
+/// [[servers]]
+/// url = "https://rocks.example.com/"
+/// priority = 10
+/// timeout = 120
+/// proxy = "http://proxy.example.com:8080"
+/// tls = { ca_certs = ["/etc/ssl/certs/example-ca.pem"] }
+/// auth = { token = "..." }
+/// signatures = { mode = "strict", trusted_keys = ["<GPG key fingerprint>"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ServerConfig {
+    #[serde(deserialize_with = "deserialize_url", serialize_with = "serialize_url")]
+    url: Url,
+    /// Servers with a higher priority are searched first.
+    /// The main `server` and the `extra_servers` have a priority of `0`.
+    /// Default: `0`
+    #[serde(default)]
+    priority: i32,
+    /// The timeout for connecting to and reading from this server, in seconds.
+    /// 0 means no timeout. Defaults to the global `timeout`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    /// The proxy for requests to this server.
+    /// Defaults to the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    /// TLS settings, e.g. for servers behind a corporate proxy with its own CA.
+    #[serde(default)]
+    tls: TlsConfig,
+    /// Never serialized, so that credentials are not leaked when printing the config.
+    #[serde(default, skip_serializing)]
+    auth: Option<ServerAuth>,
+    /// How to verify the signatures of rocks and rockspecs downloaded from this server.
+    #[serde(default)]
+    signatures: SignaturePolicy,
+}
+
+impl ServerConfig {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            priority: 0,
+            timeout: None,
+            proxy: None,
+            tls: TlsConfig::default(),
+            auth: None,
+            signatures: SignaturePolicy::default(),
+        }
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout.as_secs()),
+            ..self
+        }
+    }
+
+    pub fn with_proxy(self, proxy: String) -> Self {
+        Self {
+            proxy: Some(proxy),
+            ..self
+        }
+    }
+
+    pub fn with_tls(self, tls: TlsConfig) -> Self {
+        Self { tls, ..self }
+    }
+
+    pub fn with_auth(self, auth: ServerAuth) -> Self {
+        Self {
+            auth: Some(auth),
+            ..self
+        }
+    }
+
+    pub fn with_signatures(self, signatures: SignaturePolicy) -> Self {
+        Self { signatures, ..self }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    pub fn proxy(&self) -> Option<&String> {
+        self.proxy.as_ref()
+    }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn auth(&self) -> Option<&ServerAuth> {
+        self.auth.as_ref()
+    }
+
+    pub fn signatures(&self) -> &SignaturePolicy {
+        &self.signatures
+    }
+
+    /// Whether `url` points to a resource on this server,
+    /// i.e. it has the same origin and its path is below the server's path.
+    /// Paths are compared by whole segments, so that `https://example.com/rocks`
+    /// doesn't contain `https://example.com/rocks-evil/`.
+    pub(crate) fn contains(&self, url: &Url) -> bool {
+        is_below(&self.url, url)
+    }
+
+    /// Read the server's CA certificates, so that they don't have to be read for every request.
+    pub(crate) fn load_ca_certs(self) -> Result<Self, (PathBuf, io::Error)> {
+        Ok(Self {
+            tls: self.tls.load_ca_certs()?,
+            ..self
+        })
+    }
+
+    /// Apply the server's proxy and TLS settings to an HTTP client.
+    pub(crate) fn configure_client(
+        &self,
+        builder: ClientBuilder,
+    ) -> Result<ClientBuilder, reqwest::Error> {
+        let builder = match &self.proxy {
+            Some(proxy) => builder.proxy(Proxy::all(proxy)?),
+            None => builder,
+        };
+        self.tls.configure_client(builder)
+    }
+}
+
+/// TLS settings for a rock server.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// Paths to PEM files with additional CA certificates to trust.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ca_certs: Vec<PathBuf>,
+    /// Don't verify the server's TLS certificate.
+    /// This makes connections vulnerable to man-in-the-middle attacks,
+    /// so it should only be used as a last resort.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    insecure: bool,
+    /// The contents of the `ca_certs`, which are read when the config is built.
+    #[serde(skip)]
+    ca_cert_pems: Vec<Vec<u8>>,
+}
+
+impl TlsConfig {
+    pub fn new(ca_certs: Vec<PathBuf>, insecure: bool) -> Self {
+        Self {
+            ca_certs,
+            insecure,
+            ca_cert_pems: Vec::new(),
+        }
+    }
+
+    pub fn ca_certs(&self) -> &Vec<PathBuf> {
+        &self.ca_certs
+    }
+
+    pub fn insecure(&self) -> bool {
+        self.insecure
+    }
+
+    fn load_ca_certs(self) -> Result<Self, (PathBuf, io::Error)> {
+        let ca_cert_pems = self
+            .ca_certs
+            .iter()
+            .map(|path| std::fs::read(path).map_err(|err| (path.clone(), err)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            ca_cert_pems,
+            ..self
+        })
+    }
+
+    fn configure_client(&self, builder: ClientBuilder) -> Result<ClientBuilder, reqwest::Error> {
+        let mut builder = builder.danger_accept_invalid_certs(self.insecure);
+        for pem in &self.ca_cert_pems {
+            for certificate in Certificate::from_pem_bundle(pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// Credentials for authenticating with a rock server.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ServerAuth {
+    /// Sent as a bearer token.
+    Token { token: String },
+    /// HTTP basic authentication.
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+impl std::fmt::Debug for ServerAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token { .. } => f.write_str("Token(<redacted>)"),
+            Self::Basic { username, .. } => write!(f, "Basic({username}, <redacted>)"),
+        }
+    }
+}
+
+impl ServerAuth {
+    pub(crate) fn authenticate(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Token { token } => request.bearer_auth(token),
+            Self::Basic { username, password } => request.basic_auth(username, password.as_ref()),
+        }
+    }
+}
+
+/// Verification of detached GPG signatures (`<file>.sig`),
+/// published by a server alongside its rocks and rockspecs.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SignaturePolicy {
+    #[serde(default)]
+    mode: SignatureMode,
+    /// Fingerprints (40 hex digits) or long key IDs (16 hex digits)
+    /// of the keys that are trusted to sign artifacts.
+    /// If empty, any valid signature by a key in the user's keyring is trusted.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "deserialize_trusted_keys"
+    )]
+    trusted_keys: Vec<String>,
+}
+
+impl SignaturePolicy {
+    pub fn new(mode: SignatureMode, trusted_keys: Vec<String>) -> Self {
+        Self { mode, trusted_keys }
+    }
+
+    pub fn mode(&self) -> &SignatureMode {
+        &self.mode
+    }
+
+    pub fn trusted_keys(&self) -> &Vec<String> {
+        &self.trusted_keys
+    }
+
+    /// Whether a key with the given fingerprint is trusted.
+    /// A trusted key matches if it is the full fingerprint, or the long key ID,
+    /// i.e. the last 16 hex digits of the fingerprint.
+    /// Short key IDs are never trusted, as they are trivial to collide.
+    pub(crate) fn is_trusted(&self, fingerprint: &str) -> bool {
+        let fingerprint = normalize_key(fingerprint);
+        if self.trusted_keys.is_empty() {
+            return true;
+        }
+        if fingerprint.len() != FINGERPRINT_LEN || !is_hex(&fingerprint) {
+            return false;
+        }
+        self.trusted_keys
+            .iter()
+            .map(|key| normalize_key(key))
+            .any(|key| {
+                is_hex(&key)
+                    && match key.len() {
+                        FINGERPRINT_LEN => key == fingerprint,
+                        LONG_KEY_ID_LEN => fingerprint.ends_with(&key),
+                        _ => false,
+                    }
+            })
+    }
+}
+
+const FINGERPRINT_LEN: usize = 40;
+const LONG_KEY_ID_LEN: usize = 16;
+
+fn normalize_key(key: &str) -> String {
+    key.replace(' ', "").to_uppercase()
+}
+
+fn is_hex(key: &str) -> bool {
+    key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn deserialize_trusted_keys<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let keys = Vec::<String>::deserialize(deserializer)?;
+    match keys.iter().find(|key| {
+        let key = normalize_key(key);
+        !is_hex(&key) || (key.len() != FINGERPRINT_LEN && key.len() != LONG_KEY_ID_LEN)
+    }) {
+        Some(key) => Err(serde::de::Error::custom(format!(
+            "invalid trusted key '{key}': expected a fingerprint (40 hex digits) or a long key ID (16 hex digits)"
+        ))),
+        None => Ok(keys),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureMode {
+    /// Don't verify signatures.
+    #[default]
+    Ignore,
+    /// Verify signatures if the server publishes them.
+    Verify,
+    /// Refuse unsigned artifacts.
+    Strict,
+}
+
+/// Whether `url` has the same origin as `base` and its path is below the path of `base`,
+/// compared by whole segments.
+pub(crate) fn is_below(base: &Url, url: &Url) -> bool {
+    if url.scheme() != base.scheme()
+        || url.host() != base.host()
+        || url.port_or_known_default() != base.port_or_known_default()
+    {
+        return false;
+    }
+    let segments = |url: &Url| {
+        url.path_segments()
+            .map(|segments| {
+                segments
+                    .filter(|segment| !segment.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+    segments(url).starts_with(&segments(base))
+}
+
+fn deserialize_url<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Url::parse(&s)
+        .map(with_directory_path)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Rock servers in a local directory are configured with `file://` URLs,
+/// whose trailing `/` is easily forgotten. Without it, the files on the server
+/// would be looked up in the parent directory.
+pub(crate) fn with_directory_path(mut url: Url) -> Url {
+    if url.scheme() == "file" && !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
+
+fn serialize_url<S>(url: &Url, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(url.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Servers {
+        servers: Vec<ServerConfig>,
+    }
+
+    #[test]
+    fn parse_server_config() {
+        let servers: Servers = toml::from_str(
+            r#"
+            [[servers]]
+            url = "https://rocks.example.com/"
+            priority = 10
+            timeout = 120
+            proxy = "http://proxy.example.com:8080"
+            tls = { ca_certs = ["/etc/ssl/certs/example-ca.pem"] }
+            auth = { token = "secret" }
+            signatures = { mode = "strict", trusted_keys = ["0123 4567 89AB CDEF 0123 4567 89AB CDEF 0123 4567"] }
+
+            [[servers]]
+            url = "https://mirror.example.com/"
+            auth = { username = "user", password = "secret" }
+
+            [[servers]]
+            url = "https://public.example.com/"
+
+            [[servers]]
+            url = "https://self-signed.example.com/"
+            tls = { insecure = true }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            servers.servers,
+            vec![
+                ServerConfig::new("https://rocks.example.com/".parse().unwrap())
+                    .with_priority(10)
+                    .with_timeout(Duration::from_secs(120))
+                    .with_proxy("http://proxy.example.com:8080".into())
+                    .with_tls(TlsConfig::new(
+                        vec!["/etc/ssl/certs/example-ca.pem".into()],
+                        false
+                    ))
+                    .with_auth(ServerAuth::Token {
+                        token: "secret".into()
+                    })
+                    .with_signatures(SignaturePolicy::new(
+                        SignatureMode::Strict,
+                        vec!["0123 4567 89AB CDEF 0123 4567 89AB CDEF 0123 4567".into()]
+                    )),
+                ServerConfig::new("https://mirror.example.com/".parse().unwrap()).with_auth(
+                    ServerAuth::Basic {
+                        username: "user".into(),
+                        password: Some("secret".into())
+                    }
+                ),
+                ServerConfig::new("https://public.example.com/".parse().unwrap()),
+                ServerConfig::new("https://self-signed.example.com/".parse().unwrap())
+                    .with_tls(TlsConfig::new(Vec::new(), true)),
+            ]
+        );
+        let rendered = toml::to_string(&servers.servers[0]).unwrap();
+        assert!(!rendered.contains("secret"));
+    }
+
+    #[test]
+    fn local_server_directory_url() {
+        assert_eq!(
+            with_directory_path("file:///srv/rocks".parse().unwrap()).as_str(),
+            "file:///srv/rocks/"
+        );
+        assert_eq!(
+            with_directory_path("https://rocks.example.com/dev".parse().unwrap()).as_str(),
+            "https://rocks.example.com/dev"
+        );
+    }
+
+    #[test]
+    fn load_ca_certs() {
+        let server = ServerConfig::new("https://rocks.example.com/".parse().unwrap())
+            .with_tls(TlsConfig::new(vec!["/nonexistent/ca.pem".into()], false));
+        let (path, _) = server.load_ca_certs().unwrap_err();
+        assert_eq!(path, PathBuf::from("/nonexistent/ca.pem"));
+
+        let server = ServerConfig::new("https://rocks.example.com/".parse().unwrap())
+            .with_proxy("http://proxy.example.com:8080".into())
+            .with_tls(TlsConfig::new(Vec::new(), true));
+        assert!(server
+            .configure_client(ClientBuilder::new())
+            .unwrap()
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn server_contains_url() {
+        let server = ServerConfig::new("https://example.com/rocks".parse().unwrap());
+        let contains = |url: &str| server.contains(&url.parse().unwrap());
+        assert!(contains("https://example.com/rocks/foo-1.0.0-1.rockspec"));
+        assert!(contains("https://example.com:443/rocks/manifest"));
+        assert!(contains("https://example.com/rocks"));
+        assert!(!contains(
+            "https://example.com/rocks-evil/foo-1.0.0-1.rockspec"
+        ));
+        assert!(!contains("https://example.com.evil.com/rocks/manifest"));
+        assert!(!contains("http://example.com/rocks/manifest"));
+        assert!(!contains("https://example.com:8443/rocks/manifest"));
+        assert!(!contains("https://example.com/manifest"));
+    }
+
+    #[test]
+    fn trusted_keys() {
+        let fingerprint = "0123456789ABCDEF0123456789ABCDEF01234567";
+        let other = "0123456789ABCDEF0123456789ABCDEF01234568";
+
+        let policy = SignaturePolicy::new(
+            SignatureMode::Verify,
+            vec!["0123 4567 89ab cdef 0123 4567 89ab cdef 0123 4567".into()],
+        );
+        assert!(policy.is_trusted(fingerprint));
+        assert!(!policy.is_trusted(other));
+
+        let policy = SignaturePolicy::new(SignatureMode::Verify, vec!["89ABCDEF01234567".into()]);
+        assert!(policy.is_trusted(fingerprint));
+        assert!(!policy.is_trusted(other));
+        assert!(!policy.is_trusted("89ABCDEF01234567"));
+
+        let policy = SignaturePolicy::new(SignatureMode::Verify, vec!["01234567".into()]);
+        assert!(!policy.is_trusted(fingerprint));
+
+        assert!(SignaturePolicy::default().is_trusted(other));
+    }
+
+    #[test]
+    fn reject_short_trusted_keys() {
+        let result: Result<SignaturePolicy, _> = toml::from_str(r#"trusted_keys = ["ABCD 1234"]"#);
+        assert!(result.is_err());
+    }
+}
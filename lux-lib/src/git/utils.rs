@@ -1,6 +1,6 @@
 use std::io;
 
-use git2::{AutotagOption, FetchOptions, Repository};
+use git2::{AutotagOption, FetchOptions, ProxyOptions, Repository};
 use git_url_parse::GitUrl;
 use itertools::Itertools;
 use tempdir::TempDir;
@@ -22,6 +22,16 @@ pub enum GitError {
     NoTagOrCommitSha(String),
 }
 
+/// Options for fetching from a remote repository, which use the proxy configured
+/// in the git config (`http.proxy`), or in the `HTTPS_PROXY` or `HTTP_PROXY` environment variables.
+pub(crate) fn fetch_options<'a>() -> FetchOptions<'a> {
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.proxy_options(proxy_options);
+    fetch_options
+}
+
 pub(crate) fn latest_semver_tag_or_commit_sha(url: &GitUrl) -> Result<String, GitError> {
     match latest_semver_tag(url)? {
         Some(tag) => Ok(tag),
@@ -37,7 +47,7 @@ fn latest_semver_tag(url: &GitUrl) -> Result<Option<String>, GitError> {
     let mut remote = repo
         .remote_anonymous(&url_str)
         .map_err(|err| GitError::RemoteInit(url_str.clone(), err))?;
-    let mut fetch_opts = FetchOptions::new();
+    let mut fetch_opts = fetch_options();
     fetch_opts.download_tags(AutotagOption::All);
     remote
         .fetch(&[] as &[&str], Some(&mut fetch_opts), None)
@@ -70,7 +80,7 @@ fn latest_commit_sha(url: &GitUrl) -> Result<Option<String>, GitError> {
     let mut remote = repo
         .remote_anonymous(&url_str)
         .map_err(|err| GitError::RemoteInit(url_str.clone(), err))?;
-    let mut fetch_opts = FetchOptions::new();
+    let mut fetch_opts = fetch_options();
     remote
         .fetch(&[] as &[&str], Some(&mut fetch_opts), None)
         .map_err(|err| GitError::RemoteFetch(url_str.clone(), err))?;
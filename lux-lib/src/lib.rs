@@ -1,4 +1,5 @@
 pub mod build;
+pub mod cache;
 pub mod config;
 pub mod git;
 pub mod hash;
@@ -9,6 +10,7 @@ pub mod lua_rockspec;
 pub mod luarc;
 pub mod luarocks;
 pub mod manifest;
+pub mod message;
 pub mod operations;
 pub mod package;
 pub mod path;
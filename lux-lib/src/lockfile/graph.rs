@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::package::PackageName;
+
+use super::{LocalPackage, LocalPackageId};
+
+/// The resolved dependency graph of a lockfile.
+#[derive(Clone, Debug)]
+pub struct DependencyGraph {
+    packages: BTreeMap<LocalPackageId, LocalPackage>,
+    entrypoints: Vec<LocalPackageId>,
+}
+
+impl DependencyGraph {
+    pub(crate) fn new(
+        packages: BTreeMap<LocalPackageId, LocalPackage>,
+        entrypoints: Vec<LocalPackageId>,
+    ) -> Self {
+        Self {
+            packages,
+            entrypoints,
+        }
+    }
+
+    /// The packages that were installed explicitly, e.g. the dependencies of a project.
+    pub fn entrypoints(&self) -> Vec<&LocalPackage> {
+        self.entrypoints
+            .iter()
+            .filter_map(|id| self.packages.get(id))
+            .sorted_by_key(|package| package.name())
+            .collect_vec()
+    }
+
+    pub fn packages(&self) -> impl Iterator<Item = &LocalPackage> {
+        self.packages.values()
+    }
+
+    pub fn get(&self, id: &LocalPackageId) -> Option<&LocalPackage> {
+        self.packages.get(id)
+    }
+
+    /// All packages with the given name.
+    pub fn find(&self, name: &PackageName) -> Vec<&LocalPackage> {
+        self.packages
+            .values()
+            .filter(|package| package.name() == name)
+            .collect_vec()
+    }
+
+    /// The direct dependencies of a package.
+    pub fn dependencies(&self, package: &LocalPackage) -> Vec<&LocalPackage> {
+        package
+            .dependencies()
+            .into_iter()
+            .filter_map(|id| self.packages.get(id))
+            .sorted_by_key(|package| package.name())
+            .collect_vec()
+    }
+
+    /// The packages that directly depend on a package.
+    pub fn dependents(&self, package: &LocalPackage) -> Vec<&LocalPackage> {
+        let id = package.id();
+        self.packages
+            .values()
+            .filter(|dependent| dependent.dependencies().contains(&&id))
+            .sorted_by_key(|package| package.name())
+            .collect_vec()
+    }
+
+    pub fn is_entrypoint(&self, package: &LocalPackage) -> bool {
+        self.entrypoints.contains(&package.id())
+    }
+
+    /// The packages that would no longer be reachable from any entrypoint
+    /// if the `removed` packages were uninstalled, including the `removed` packages themselves.
+    pub fn orphaned_by(&self, removed: &[LocalPackageId]) -> Vec<&LocalPackage> {
+        let mut reachable = HashSet::new();
+        let mut stack = self
+            .entrypoints
+            .iter()
+            .filter(|id| !removed.contains(id))
+            .collect_vec();
+        while let Some(id) = stack.pop() {
+            if removed.contains(id) || !reachable.insert(id) {
+                continue;
+            }
+            if let Some(package) = self.packages.get(id) {
+                stack.extend(package.dependencies());
+            }
+        }
+        self.packages
+            .iter()
+            .filter(|(id, _)| !reachable.contains(id))
+            .map(|(_, package)| package)
+            .sorted_by_key(|package| package.name())
+            .collect_vec()
+    }
+
+    /// The packages that are not reachable from any entrypoint.
+    pub fn orphans(&self) -> Vec<&LocalPackage> {
+        self.orphaned_by(&[])
+    }
+
+    /// All dependency chains from an entrypoint to a package,
+    /// i.e. the reasons why the package is installed.
+    /// Each chain starts with an entrypoint and ends with the package.
+    pub fn dependency_chains(&self, package: &LocalPackage) -> Vec<Vec<&LocalPackage>> {
+        let mut chains = Vec::new();
+        let package = match self.packages.get(&package.id()) {
+            Some(package) => package,
+            None => return chains,
+        };
+        self.collect_chains(vec![package], &mut chains);
+        chains
+            .into_iter()
+            .map(|chain| chain.into_iter().rev().collect_vec())
+            .sorted_by_key(|chain| chain.iter().map(|package| package.name()).collect_vec())
+            .collect_vec()
+    }
+
+    fn collect_chains<'a>(
+        &'a self,
+        chain: Vec<&'a LocalPackage>,
+        chains: &mut Vec<Vec<&'a LocalPackage>>,
+    ) {
+        let package = *chain.last().expect("empty dependency chain");
+        if self.is_entrypoint(package) {
+            chains.push(chain.clone());
+        }
+        for dependent in self.dependents(package) {
+            // Skip cycles
+            if chain.iter().any(|package| package.id() == dependent.id()) {
+                continue;
+            }
+            let mut chain = chain.clone();
+            chain.push(dependent);
+            self.collect_chains(chain, chains);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::lockfile::{Lockfile, ReadOnly};
+
+    use super::*;
+
+    fn sample_lockfile() -> Lockfile<ReadOnly> {
+        let lockfile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test/sample-tree/5.1/lux.lock");
+        Lockfile::load(lockfile_path, None).unwrap()
+    }
+
+    #[test]
+    fn dependency_graph() {
+        let graph = sample_lockfile().dependency_graph();
+        let neorg = graph.find(&"neorg".into()).pop().unwrap();
+        assert!(graph.is_entrypoint(neorg));
+        let dependencies = graph.dependencies(neorg);
+        assert!(!dependencies.is_empty());
+        for dependency in dependencies {
+            assert!(graph
+                .dependents(dependency)
+                .iter()
+                .any(|dependent| dependent.id() == neorg.id()));
+            let chains = graph.dependency_chains(dependency);
+            assert!(chains.iter().any(|chain| chain.len() == 2
+                && chain[0].id() == neorg.id()
+                && chain[1].id() == dependency.id()));
+        }
+    }
+
+    #[test]
+    fn orphaned_packages() {
+        let graph = sample_lockfile().dependency_graph();
+        let neorg = graph.find(&"neorg".into()).pop().unwrap();
+        let orphaned = graph
+            .orphaned_by(&[neorg.id()])
+            .into_iter()
+            .map(|package| package.id())
+            .collect_vec();
+        assert!(orphaned.contains(&neorg.id()));
+        for dependency in graph.dependencies(neorg) {
+            assert!(orphaned.contains(&dependency.id()));
+        }
+        for orphan in graph.orphans() {
+            assert!(!graph.is_entrypoint(orphan));
+            assert!(orphaned.contains(&orphan.id()));
+        }
+        for entrypoint in graph.entrypoints() {
+            if entrypoint.id() != neorg.id() {
+                assert!(!orphaned.contains(&entrypoint.id()));
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Snapshots of a project lockfile.
+//!
+//! Whenever a project lockfile changes, the previous version is copied
+//! to the `.lux/lockfile-history` directory, next to the lockfile,
+//! so that a previous dependency state can be restored.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+const HISTORY_DIR: &str = "lockfile-history";
+
+/// The maximum number of snapshots that are kept.
+const MAX_SNAPSHOTS: usize = 50;
+
+/// The snapshots of a project lockfile.
+#[derive(Debug, Clone)]
+pub struct LockfileHistory {
+    lockfile_path: PathBuf,
+    dir: PathBuf,
+}
+
+/// A previous version of a project lockfile.
+#[derive(Debug, Clone)]
+pub struct LockfileSnapshot {
+    path: PathBuf,
+    timestamp: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum LockfileHistoryError {
+    #[error("no lockfile snapshot matches '{0}'. Use `lx rollback --list` to list the snapshots.")]
+    SnapshotNotFound(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl LockfileSnapshot {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The time at which the snapshot was taken, in milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// The time at which the snapshot was taken, as an HTTP date.
+    pub fn date(&self) -> String {
+        httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_millis(self.timestamp))
+    }
+}
+
+impl LockfileHistory {
+    pub fn new(lockfile_path: &Path) -> Self {
+        let dir = lockfile_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(".lux")
+            .join(HISTORY_DIR);
+        Self {
+            lockfile_path: lockfile_path.to_path_buf(),
+            dir,
+        }
+    }
+
+    /// The snapshots, from the most recent to the oldest.
+    pub fn snapshots(&self) -> io::Result<Vec<LockfileSnapshot>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        Ok(std::fs::read_dir(&self.dir)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                let timestamp = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("lux-")?
+                    .strip_suffix(".lock")?
+                    .parse()
+                    .ok()?;
+                Some(LockfileSnapshot { path, timestamp })
+            })
+            .sorted_by(|a, b| b.timestamp.cmp(&a.timestamp))
+            .collect_vec())
+    }
+
+    /// Find a snapshot, either by the number of changes to go back (starting at `1`),
+    /// or by its timestamp.
+    pub fn find(&self, selector: &str) -> Result<LockfileSnapshot, LockfileHistoryError> {
+        let not_found = || LockfileHistoryError::SnapshotNotFound(selector.to_string());
+        let value: u64 = selector.parse().map_err(|_| not_found())?;
+        let snapshots = self.snapshots()?;
+        match snapshots
+            .iter()
+            .find(|snapshot| snapshot.timestamp == value)
+        {
+            Some(snapshot) => Ok(snapshot.clone()),
+            None if value > 0 => snapshots
+                .into_iter()
+                .nth(value as usize - 1)
+                .ok_or_else(not_found),
+            None => Err(not_found()),
+        }
+    }
+
+    /// Replace the lockfile with a snapshot.
+    /// The current lockfile is snapshotted first, so that the rollback can be undone.
+    pub fn restore(&self, snapshot: &LockfileSnapshot) -> io::Result<()> {
+        let content = std::fs::read_to_string(&snapshot.path)?;
+        self.snapshot()?;
+        super::write_atomic(&self.lockfile_path, content)
+    }
+
+    /// Copy the current lockfile to the history, if it exists,
+    /// and remove the oldest snapshots.
+    pub(crate) fn snapshot(&self) -> io::Result<()> {
+        if !self.lockfile_path.is_file() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let mut timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        // Snapshots taken in quick succession must not overwrite each other
+        while self.snapshot_path(timestamp).exists() {
+            timestamp += 1;
+        }
+        std::fs::copy(&self.lockfile_path, self.snapshot_path(timestamp))?;
+        for snapshot in self.snapshots()?.into_iter().skip(MAX_SNAPSHOTS) {
+            std::fs::remove_file(snapshot.path)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot_path(&self, timestamp: u64) -> PathBuf {
+        self.dir.join(format!("lux-{timestamp}.lock"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_and_restore() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let lockfile_path = dir.join("lux.lock");
+        let history = LockfileHistory::new(&lockfile_path);
+        history.snapshot().unwrap();
+        assert!(history.snapshots().unwrap().is_empty());
+
+        std::fs::write(&lockfile_path, "first").unwrap();
+        history.snapshot().unwrap();
+        std::fs::write(&lockfile_path, "second").unwrap();
+        history.snapshot().unwrap();
+        std::fs::write(&lockfile_path, "third").unwrap();
+
+        let snapshots = history.snapshots().unwrap();
+        assert_eq!(snapshots.len(), 2);
+        let snapshot = history.find("2").unwrap();
+        assert_eq!(snapshot.path(), snapshots[1].path());
+        assert_eq!(
+            history
+                .find(&snapshots[0].timestamp().to_string())
+                .unwrap()
+                .path(),
+            snapshots[0].path()
+        );
+        assert!(history.find("3").is_err());
+
+        history.restore(&snapshot).unwrap();
+        assert_eq!(std::fs::read_to_string(&lockfile_path).unwrap(), "first");
+        assert_eq!(history.snapshots().unwrap().len(), 3);
+    }
+}
@@ -4,7 +4,12 @@ use std::fmt::Display;
 use std::io::{self, Write};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use std::{collections::HashMap, fs::File, io::ErrorKind, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
 use itertools::Itertools;
 use mlua::{ExternalResult, FromLua, IntoLua, UserData};
@@ -23,6 +28,12 @@ use crate::remote_package_source::RemotePackageSource;
 use crate::rockspec::lua_dependency::LuaDependencySpec;
 use crate::rockspec::RockBinaries;
 
+mod graph;
+mod history;
+
+pub use graph::*;
+pub use history::*;
+
 const LOCKFILE_VERSION_STR: &str = "1.0.0";
 
 #[derive(Copy, Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
@@ -558,6 +569,28 @@ impl PartialOrd for LocalPackageHashes {
     }
 }
 
+impl LocalPackageHashes {
+    /// Validate these hashes against the `expected` hashes, e.g. from a lockfile.
+    pub(crate) fn validate(
+        &self,
+        expected: &LocalPackageHashes,
+    ) -> Result<(), LockfileIntegrityError> {
+        if self.rockspec.matches(&expected.rockspec).is_none() {
+            return Err(LockfileIntegrityError::RockspecIntegrityMismatch {
+                expected: expected.rockspec.clone(),
+                got: self.rockspec.clone(),
+            });
+        }
+        if self.source.matches(&expected.source).is_none() {
+            return Err(LockfileIntegrityError::SourceIntegrityMismatch {
+                expected: expected.source.clone(),
+                got: self.source.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
 impl mlua::UserData for LocalPackageHashes {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("rockspec", |_, this, ()| Ok(this.rockspec.to_hex().1));
@@ -677,6 +710,10 @@ impl LocalPackageLock {
         self.entrypoints.contains(package)
     }
 
+    fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::new(self.rocks.clone(), self.entrypoints.clone())
+    }
+
     fn is_dependency(&self, package: &LocalPackageId) -> bool {
         self.rocks
             .values()
@@ -855,9 +892,9 @@ pub enum LockfileError {
 
 #[derive(Error, Debug)]
 pub enum LockfileIntegrityError {
-    #[error("rockspec integirty mismatch.\nExpected: {expected}\nBut got: {got}")]
+    #[error("rockspec integrity mismatch. The remote rockspec may have changed since it was locked.\nExpected: {expected}\nBut got: {got}")]
     RockspecIntegrityMismatch { expected: Integrity, got: Integrity },
-    #[error("source integrity mismatch.\nExpected: {expected}\nBut got: {got}")]
+    #[error("source integrity mismatch. The remote source may have changed since it was locked.\nExpected: {expected}\nBut got: {got}")]
     SourceIntegrityMismatch { expected: Integrity, got: Integrity },
     #[error("package {0} version {1} with pinned state {2} and constraint {3} not found in the lockfile.")]
     PackageNotFound(PackageName, PackageVersion, PinnedState, String),
@@ -906,6 +943,11 @@ impl<P: LockfilePermissions> Lockfile<P> {
         self.lock.is_entrypoint(package)
     }
 
+    /// The dependency graph of the packages in this lockfile.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.lock.dependency_graph()
+    }
+
     pub(crate) fn local_pkg_lock(&self) -> &LocalPackageLock {
         &self.lock
     }
@@ -964,31 +1006,7 @@ impl<P: LockfilePermissions> Lockfile<P> {
                 .find(|rock| rock.version() == package.version())
             {
                 None => Err(integrity_err_not_found(package)),
-                Some(expected_package) => {
-                    if package
-                        .hashes
-                        .rockspec
-                        .matches(&expected_package.hashes.rockspec)
-                        .is_none()
-                    {
-                        return Err(LockfileIntegrityError::RockspecIntegrityMismatch {
-                            expected: expected_package.hashes.rockspec.clone(),
-                            got: package.hashes.rockspec.clone(),
-                        });
-                    }
-                    if package
-                        .hashes
-                        .source
-                        .matches(&expected_package.hashes.source)
-                        .is_none()
-                    {
-                        return Err(LockfileIntegrityError::SourceIntegrityMismatch {
-                            expected: expected_package.hashes.source.clone(),
-                            got: package.hashes.source.clone(),
-                        });
-                    }
-                    Ok(())
-                }
+                Some(expected_package) => package.hashes.validate(&expected_package.hashes),
             },
         }
     }
@@ -996,7 +1014,7 @@ impl<P: LockfilePermissions> Lockfile<P> {
     fn flush(&self) -> io::Result<()> {
         let content = serde_json::to_string_pretty(&self)?;
 
-        std::fs::write(&self.filepath, content)?;
+        write_atomic(&self.filepath, content)?;
 
         Ok(())
     }
@@ -1050,6 +1068,11 @@ impl<P: LockfilePermissions> ProjectLockfile<P> {
         }
     }
 
+    /// The dependency graph of the given type of dependencies.
+    pub fn dependency_graph(&self, deps: &LocalPackageLockType) -> DependencyGraph {
+        self.local_pkg_lock(deps).dependency_graph()
+    }
+
     pub(crate) fn local_pkg_lock(&self, deps: &LocalPackageLockType) -> &LocalPackageLock {
         match deps {
             LocalPackageLockType::Regular => &self.dependencies,
@@ -1058,10 +1081,19 @@ impl<P: LockfilePermissions> ProjectLockfile<P> {
         }
     }
 
+    /// The snapshots of previous versions of this lockfile.
+    pub fn history(&self) -> LockfileHistory {
+        LockfileHistory::new(&self.filepath)
+    }
+
     fn flush(&self) -> io::Result<()> {
         let content = serde_json::to_string_pretty(&self)?;
 
-        std::fs::write(&self.filepath, content)?;
+        if std::fs::read_to_string(&self.filepath).is_ok_and(|current| current != content) {
+            self.history().snapshot()?;
+        }
+
+        write_atomic(&self.filepath, content)?;
 
         Ok(())
     }
@@ -1439,6 +1471,19 @@ where
     url.as_str().serialize(serializer)
 }
 
+/// Write to a temporary file next to the `path` and rename it,
+/// so that a lockfile is never left partially written.
+fn write_atomic(path: &Path, content: String) -> io::Result<()> {
+    tracing::debug!(path = %path.display(), "writing lockfile");
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+    std::fs::write(&temp_path, content)?;
+    std::fs::rename(&temp_path, path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1671,4 +1716,39 @@ mod tests {
             .iter()
             .any(|pkg| pkg.name().to_string() == "nvim-nio"));
     }
+
+    #[test]
+    fn validate_hashes() {
+        let hashes = LocalPackageHashes {
+            rockspec: "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+                .parse()
+                .unwrap(),
+            source: "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+                .parse()
+                .unwrap(),
+        };
+        hashes.validate(&hashes.clone()).unwrap();
+
+        let changed_source = LocalPackageHashes {
+            source: "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+                .parse()
+                .unwrap(),
+            ..hashes.clone()
+        };
+        assert!(matches!(
+            changed_source.validate(&hashes),
+            Err(LockfileIntegrityError::SourceIntegrityMismatch { .. })
+        ));
+
+        let changed_rockspec = LocalPackageHashes {
+            rockspec: "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+                .parse()
+                .unwrap(),
+            ..hashes.clone()
+        };
+        assert!(matches!(
+            changed_rockspec.validate(&hashes),
+            Err(LockfileIntegrityError::RockspecIntegrityMismatch { .. })
+        ));
+    }
 }
@@ -24,8 +24,11 @@ use crate::{
     variables::HasVariables,
 };
 use lazy_static::lazy_static;
+pub use prebuilt::PrebuiltLuaError;
 use tokio::sync::Mutex;
 
+mod prebuilt;
+
 // Because installing lua is not thread-safe, we have to synchronize with a global Mutex
 lazy_static! {
     static ref NEW_MUTEX: Mutex<i32> = Mutex::new(0i32);
@@ -77,61 +80,118 @@ pub enum DetectLuaVersionError {
 pub enum LuaInstallationError {
     #[error("error building Lua from source:\n{0}")]
     Build(String),
+    #[error(transparent)]
+    Prebuilt(#[from] PrebuiltLuaError),
+    #[error("the Lua installation in {} is Lua {found}, but Lua {version} is required", dir.display())]
+    VersionMismatch {
+        dir: PathBuf,
+        version: LuaVersion,
+        found: LuaVersion,
+    },
+    #[error("the Lua installation in {} is not managed by lux", .0.display())]
+    NotManaged(PathBuf),
+    #[error("failed to remove the Lua installation in {}: {err}", dir.display())]
+    Remove { dir: PathBuf, err: io::Error },
 }
 
 impl LuaInstallation {
     pub async fn new(version: &LuaVersion, config: &Config) -> Result<Self, LuaInstallationError> {
         let _lock = NEW_MUTEX.lock().await;
+        if let Some(lua_dir) = config.lua_dir() {
+            if let Some(lua_installation) = Self::from_dir(lua_dir, version)? {
+                return Ok(lua_installation);
+            }
+        }
         if let Some(lua_intallation) = Self::probe(version, config.external_deps()) {
             return Ok(lua_intallation);
         }
-        let output = Self::root_dir(version, config);
-        let include_dir = output.join("include");
-        let lib_dir = output.join("lib");
-        let lua_lib_name = get_lua_lib_name(&lib_dir, version);
-        if include_dir.is_dir() && lua_lib_name.is_some() {
-            let bin_dir = Some(output.join("bin")).filter(|bin_path| bin_path.is_dir());
-            let bin = bin_dir
-                .as_ref()
-                .and_then(|bin_path| find_lua_executable(bin_path));
-            let lib_dir = output.join("lib");
-            let lua_lib_name = get_lua_lib_name(&lib_dir, version);
-            let include_dir = Some(output.join("include"));
-            Ok(LuaInstallation {
-                version: version.clone(),
-                dependency_info: ExternalDependencyInfo {
-                    include_dir,
-                    lib_dir: Some(lib_dir),
-                    bin_dir,
-                    lib_info: None,
-                    lib_name: lua_lib_name,
-                },
-                bin,
-            })
-        } else {
-            Self::install(version, config).await
+        if config.lua_dir().is_none() {
+            // Installations managed by lux are rebuilt if they don't match the version.
+            if let Ok(Some(lua_installation)) =
+                Self::from_dir(&Self::root_dir(version, config), version)
+            {
+                return Ok(lua_installation);
+            }
+        }
+        Self::install(version, config).await
+    }
+
+    /// Use an existing Lua installation in the `prefix` directory, if there is one.
+    /// Fails if the installation's headers don't match the Lua `version`.
+    fn from_dir(prefix: &Path, version: &LuaVersion) -> Result<Option<Self>, LuaInstallationError> {
+        let include_dir = find_lua_include_dir(&prefix.join("include"), version);
+        let lib = ["lib", "lib64"].into_iter().find_map(|dir| {
+            let lib_dir = prefix.join(dir);
+            get_lua_lib_name(&lib_dir, version).map(|lua_lib_name| (lib_dir, lua_lib_name))
+        });
+        let (include_dir, (lib_dir, lua_lib_name)) = match (include_dir, lib) {
+            (Some(include_dir), Some(lib)) => (include_dir, lib),
+            _ => return Ok(None),
+        };
+        match header_lua_version(&include_dir) {
+            Some(found) if !is_compatible_header_version(&found, version) => {
+                return Err(LuaInstallationError::VersionMismatch {
+                    dir: prefix.to_path_buf(),
+                    version: version.clone(),
+                    found,
+                })
+            }
+            _ => {}
         }
+        let bin_dir = Some(prefix.join("bin")).filter(|bin_path| bin_path.is_dir());
+        let bin = bin_dir
+            .as_ref()
+            .and_then(|bin_path| find_lua_executable(bin_path));
+        Ok(Some(LuaInstallation {
+            version: version.clone(),
+            dependency_info: ExternalDependencyInfo {
+                include_dir: Some(include_dir),
+                lib_dir: Some(lib_dir),
+                bin_dir,
+                lib_info: None,
+                lib_name: Some(lua_lib_name),
+            },
+            bin,
+        }))
     }
 
     pub(crate) fn probe(
         version: &LuaVersion,
         search_config: &ExternalDependencySearchConfig,
     ) -> Option<Self> {
-        let pkg_name = match version {
-            LuaVersion::Lua51 => "lua5.1",
-            LuaVersion::Lua52 => "lua5.2",
-            LuaVersion::Lua53 => "lua5.3",
-            LuaVersion::Lua54 => "lua5.4",
-            LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => "luajit",
+        let pkg_names = match version {
+            LuaVersion::Lua51 => vec!["lua5.1", "lua-5.1", "lua51"],
+            LuaVersion::Lua52 => vec!["lua5.2", "lua-5.2", "lua52"],
+            LuaVersion::Lua53 => vec!["lua5.3", "lua-5.3", "lua53"],
+            LuaVersion::Lua54 => vec!["lua5.4", "lua-5.4", "lua54"],
+            LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => vec!["luajit"],
         };
 
-        let mut dependency_info = ExternalDependencyInfo::probe(
-            pkg_name,
-            &ExternalDependencySpec::default(),
-            search_config,
-        );
+        // LuaJIT's headers are in a distinct include directory, e.g. `include/luajit-2.1`,
+        // so we make sure we pick the one that contains them.
+        let dependency_spec = ExternalDependencySpec {
+            header: version.is_luajit().then(|| "luajit.h".into()),
+            library: None,
+        };
 
-        if let Ok(info) = &mut dependency_info {
+        // Some distributions only provide an unversioned `lua` package,
+        // which we can only use if its headers tell us which version it is.
+        let mut candidates = pkg_names
+            .into_iter()
+            .map(|pkg_name| (pkg_name, false))
+            .chain(std::iter::once(("lua", true)).filter(|_| !version.is_luajit()));
+
+        candidates.find_map(|(pkg_name, is_unversioned)| {
+            let mut info =
+                ExternalDependencyInfo::probe(pkg_name, &dependency_spec, search_config).ok()?;
+            let header_version = info.include_dir.as_deref().and_then(header_lua_version);
+            let is_compatible = match header_version {
+                Some(found) => is_compatible_header_version(&found, version),
+                None => !is_unversioned,
+            };
+            if !is_compatible {
+                return None;
+            }
             let bin = info.lib_dir.as_ref().and_then(|lib_dir| {
                 lib_dir
                     .parent()
@@ -146,12 +206,10 @@ impl LuaInstallation {
             info.lib_name = lua_lib_name;
             Some(Self {
                 version: version.clone(),
-                dependency_info: dependency_info.unwrap(),
+                dependency_info: info,
                 bin,
             })
-        } else {
-            None
-        }
+        })
     }
 
     // XXX: lua_src and luajit_src panic on failure, so we just unwrap errors here.
@@ -164,6 +222,29 @@ impl LuaInstallation {
         let target = &host.to_string();
         let host_operating_system = &host.operating_system.to_string();
 
+        if let Some(base_url) = config.prebuilt_lua_url().filter(|_| !config.offline()) {
+            let output = TempDir::new("lux_prebuilt_lua")
+                .expect("failed to create lua_installation temp directory");
+            match prebuilt::install_prebuilt_lua(base_url, version, target, output.path(), config)
+                .await
+            {
+                Ok(()) => {
+                    let root_dir = Self::root_dir(version, config);
+                    recursive_copy_dir(&output.path().to_path_buf(), &root_dir)
+                        .await
+                        .expect("error copying lua installation");
+                    if let Some(lua_installation) = Self::from_dir(&root_dir, version)? {
+                        return Ok(lua_installation);
+                    }
+                    eprintln!("⚠️ WARNING: the prebuilt Lua {version} binaries are incomplete.");
+                }
+                // We don't fall back to building from source if the binaries have been tampered with.
+                Err(err @ PrebuiltLuaError::IntegrityMismatch { .. }) => return Err(err.into()),
+                Err(err) => eprintln!("⚠️ WARNING: {err}"),
+            }
+            eprintln!("Building Lua {version} from source instead.");
+        }
+
         let output = TempDir::new("lux_lua_installation")
             .expect("failed to create lua_installation temp directory");
 
@@ -207,9 +288,14 @@ impl LuaInstallation {
         };
 
         let target = Self::root_dir(version, config);
-        recursive_copy_dir(&output.into_path(), &target)
+        let output = output.into_path();
+        recursive_copy_dir(&output, &target)
             .await
             .expect("error copying lua installation");
+        // The build outputs have been copied to the target directory
+        let include_dir = target.join(include_dir.strip_prefix(&output).unwrap_or(&include_dir));
+        let lib_dir = target.join(lib_dir.strip_prefix(&output).unwrap_or(&lib_dir));
+        let _ = std::fs::remove_dir_all(&output);
 
         let bin_dir = Some(target.join("bin")).filter(|bin_path| bin_path.is_dir());
         let bin = bin_dir
@@ -229,6 +315,49 @@ impl LuaInstallation {
         })
     }
 
+    /// Find a Lua installation that was installed by lux.
+    pub fn find_installed(version: &LuaVersion, config: &Config) -> Option<Self> {
+        Self::from_dir(&Self::root_dir(version, config), version)
+            .ok()
+            .flatten()
+    }
+
+    /// Find a Lua installation that is provided by the system.
+    pub fn find_system(version: &LuaVersion, config: &Config) -> Option<Self> {
+        Self::probe(version, config.external_deps())
+    }
+
+    /// Remove a Lua installation that was installed by lux.
+    /// Returns the directory it was installed to, if it was installed.
+    pub async fn uninstall(
+        version: &LuaVersion,
+        config: &Config,
+    ) -> Result<Option<PathBuf>, LuaInstallationError> {
+        let _lock = INSTALL_MUTEX.lock().await;
+        let root_dir = Self::root_dir(version, config);
+        if config.lua_dir().is_some() {
+            return Err(LuaInstallationError::NotManaged(root_dir));
+        }
+        if Self::find_installed(version, config).is_none() {
+            return Ok(None);
+        }
+        tokio::fs::remove_dir_all(&root_dir)
+            .await
+            .map_err(|err| LuaInstallationError::Remove {
+                dir: root_dir.clone(),
+                err,
+            })?;
+        Ok(Some(root_dir))
+    }
+
+    /// The installation prefix, i.e. the parent of the library directory.
+    pub fn prefix(&self) -> Option<&Path> {
+        self.dependency_info
+            .lib_dir
+            .as_deref()
+            .and_then(Path::parent)
+    }
+
     pub fn includes(&self) -> Vec<&PathBuf> {
         self.dependency_info.include_dir.iter().collect_vec()
     }
@@ -357,6 +486,18 @@ impl TryFrom<LuaBinary> for PathBuf {
                     if let Ok(path) = which("luajit") {
                         return Ok(path);
                     }
+                } else {
+                    // Distributions that ship several Lua versions often suffix the binaries
+                    let version_str = lua_version.version_compatibility_str();
+                    if let Some(path) = [
+                        format!("lua{version_str}"),
+                        format!("lua{}", version_str.replace(".", "")),
+                    ]
+                    .iter()
+                    .find_map(|bin| which(bin).ok())
+                    {
+                        return Ok(path);
+                    }
                 }
                 match which("lua") {
                     Ok(path) => {
@@ -404,7 +545,7 @@ fn find_lua_executable(bin_path: &Path) -> Option<PathBuf> {
             .map(|entry| entry.path().to_path_buf())
             .filter(|file| {
                 file.is_executable()
-                    && file.file_name().is_some_and(|name| {
+                    && file.file_stem().is_some_and(|name| {
                         matches!(
                             name.to_string_lossy().to_string().as_str(),
                             "lua" | "luajit"
@@ -452,6 +593,55 @@ fn get_lua_lib_name(lib_dir: &Path, lua_version: &LuaVersion) -> Option<String>
         .map(|file| to_lib_name(&file))
 }
 
+/// Find the directory containing the Lua headers, which may be a subdirectory
+/// of the `include_dir`, e.g. `include/lua5.4` or `include/luajit-2.1`.
+fn find_lua_include_dir(include_dir: &Path, lua_version: &LuaVersion) -> Option<PathBuf> {
+    let header = if lua_version.is_luajit() {
+        "luajit.h"
+    } else {
+        "lua.h"
+    };
+    if include_dir.join(header).is_file() {
+        return Some(include_dir.to_path_buf());
+    }
+    std::fs::read_dir(include_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|dir| dir.join(header).is_file())
+        .sorted()
+        .find(|dir| {
+            header_lua_version(dir)
+                .is_none_or(|found| is_compatible_header_version(&found, lua_version))
+        })
+}
+
+/// Detect the Lua version from the `LUA_VERSION_NUM` in the `lua.h` header.
+/// LuaJIT installations are recognised by their `luajit.h` header.
+fn header_lua_version(include_dir: &Path) -> Option<LuaVersion> {
+    let lua_h = std::fs::read_to_string(include_dir.join("lua.h")).ok()?;
+    let version_num =
+        lua_h.lines().find_map(
+            |line| match line.split_whitespace().collect_vec().as_slice() {
+                ["#define", "LUA_VERSION_NUM", version_num, ..] => version_num.parse::<u32>().ok(),
+                _ => None,
+            },
+        )?;
+    match version_num {
+        501 if include_dir.join("luajit.h").is_file() => Some(LuaVersion::LuaJIT),
+        501 => Some(LuaVersion::Lua51),
+        502 => Some(LuaVersion::Lua52),
+        503 => Some(LuaVersion::Lua53),
+        504 => Some(LuaVersion::Lua54),
+        _ => None,
+    }
+}
+
+/// LuaJIT's headers don't tell us whether it was built with Lua 5.2 compatibility.
+fn is_compatible_header_version(found: &LuaVersion, lua_version: &LuaVersion) -> bool {
+    found == lua_version || found.is_luajit() && lua_version.is_luajit()
+}
+
 fn detect_installed_lua_version_from_path(
     lua_cmd: &Path,
 ) -> Result<PackageVersion, DetectLuaVersionError> {
@@ -506,6 +696,32 @@ mod test {
         parse_lua_version_from_output(lua_output).unwrap();
     }
 
+    #[test]
+    fn detect_header_lua_version() {
+        let prefix = assert_fs::TempDir::new().unwrap();
+        let include_dir = prefix.join("include").join("lua5.3");
+        std::fs::create_dir_all(&include_dir).unwrap();
+        std::fs::write(
+            include_dir.join("lua.h"),
+            "#define LUA_VERSION_MAJOR\t\"5\"\n#define LUA_VERSION_NUM\t\t503\n",
+        )
+        .unwrap();
+        assert_eq!(header_lua_version(&include_dir), Some(LuaVersion::Lua53));
+        assert_eq!(
+            find_lua_include_dir(&prefix.join("include"), &LuaVersion::Lua53),
+            Some(include_dir.clone())
+        );
+        assert!(find_lua_include_dir(&prefix.join("include"), &LuaVersion::Lua54).is_none());
+
+        std::fs::write(include_dir.join("luajit.h"), "").unwrap();
+        std::fs::write(include_dir.join("lua.h"), "#define LUA_VERSION_NUM 501\n").unwrap();
+        assert_eq!(header_lua_version(&include_dir), Some(LuaVersion::LuaJIT));
+        assert!(is_compatible_header_version(
+            &LuaVersion::LuaJIT,
+            &LuaVersion::LuaJIT52
+        ));
+    }
+
     #[tokio::test]
     async fn lua_installation_bin() {
         if std::env::var("LUX_SKIP_IMPURE_TESTS").unwrap_or("0".into()) == "1" {
@@ -545,8 +761,9 @@ mod test {
         assert!(is_lua_lib_name("lua.lib", &LuaVersion::Lua51));
         assert!(is_lua_lib_name("lua-5.1.lib", &LuaVersion::Lua51));
         assert!(!is_lua_lib_name("lua-5.2.lib", &LuaVersion::Lua51));
-        assert!(!is_lua_lib_name("lua53.lib", &LuaVersion::Lua53));
-        assert!(!is_lua_lib_name("lua53.lib", &LuaVersion::Lua53));
+        assert!(is_lua_lib_name("lua53.lib", &LuaVersion::Lua53));
+        assert!(is_lua_lib_name("lua54.lib", &LuaVersion::Lua54));
+        assert!(!is_lua_lib_name("lua53.lib", &LuaVersion::Lua54));
         assert!(is_lua_lib_name("luajit-5.2.lib", &LuaVersion::LuaJIT52));
         assert!(is_lua_lib_name("lua-5.2.lib", &LuaVersion::LuaJIT52));
     }
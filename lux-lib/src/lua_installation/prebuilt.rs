@@ -0,0 +1,163 @@
+//! Prebuilt Lua binaries.
+//!
+//! A prebuilt Lua server provides an archive with the `bin`, `include` and `lib` directories
+//! of a Lua installation for each Lua version and target,
+//! at `<url>/<version>/<target>.tar.gz`.
+//!
+//! The SHA-256 checksums of the archives are pinned in [`CHECKSUMS`] rather than
+//! downloaded from the same server, so a compromised server can't serve tampered binaries.
+//! Archives without a pinned checksum are never downloaded.
+
+use std::{io::Cursor, path::Path};
+
+use bytes::Bytes;
+use reqwest::{Client, StatusCode};
+use ssri::{Algorithm, Integrity};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    config::{Config, LuaVersion},
+    hash::HasIntegrity,
+    operations::{self, UnpackError},
+    progress::Progress,
+};
+
+#[derive(Error, Debug)]
+pub enum PrebuiltLuaError {
+    #[error("no prebuilt Lua binaries found at {0}")]
+    NotFound(Url),
+    #[error("failed to download prebuilt Lua binaries: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    Url(#[from] url::ParseError),
+    #[error("no prebuilt Lua {version} binaries are known for {target}")]
+    NotPinned { version: LuaVersion, target: String },
+    #[error("integrity mismatch for prebuilt Lua binaries at {url}.\nExpected: {expected}\nActual: {actual}")]
+    IntegrityMismatch {
+        url: String,
+        expected: Integrity,
+        actual: Integrity,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Unpack(#[from] UnpackError),
+}
+
+/// The SHA-256 checksums of the prebuilt Lua archives,
+/// as `(<version>/<target>.tar.gz, <hex digest>)`.
+const CHECKSUMS: &[(&str, &str)] = &[];
+
+/// The path of the archive with the prebuilt binaries of a Lua version for a target,
+/// relative to the server's base URL.
+fn archive_path(version: &LuaVersion, target: &str) -> String {
+    format!("{version}/{target}.tar.gz")
+}
+
+/// The pinned checksum of the prebuilt binaries of a Lua version for a target.
+fn pinned_checksum(
+    checksums: &[(&str, &str)],
+    version: &LuaVersion,
+    target: &str,
+) -> Option<Integrity> {
+    let archive_path = archive_path(version, target);
+    checksums
+        .iter()
+        .find(|(path, _)| *path == archive_path)
+        .and_then(|(_, hex_digest)| Integrity::from_hex(hex_digest, Algorithm::Sha256).ok())
+}
+
+/// The URL of the prebuilt binaries of a Lua version for a target.
+pub(crate) fn prebuilt_lua_url(
+    base_url: &Url,
+    version: &LuaVersion,
+    target: &str,
+) -> Result<Url, url::ParseError> {
+    let base_url = if base_url.path().ends_with('/') {
+        base_url.clone()
+    } else {
+        Url::parse(&format!("{base_url}/"))?
+    };
+    base_url.join(&archive_path(version, target))
+}
+
+/// Download, verify and unpack the prebuilt binaries of a Lua version into the `dest_dir`.
+pub(crate) async fn install_prebuilt_lua(
+    base_url: &Url,
+    version: &LuaVersion,
+    target: &str,
+    dest_dir: &Path,
+    config: &Config,
+) -> Result<(), PrebuiltLuaError> {
+    let expected =
+        pinned_checksum(CHECKSUMS, version, target).ok_or_else(|| PrebuiltLuaError::NotPinned {
+            version: version.clone(),
+            target: target.to_string(),
+        })?;
+    let url = prebuilt_lua_url(base_url, version, target)?;
+    let client = config.http_client(&url)?;
+    let archive = download(&client, &url, config).await?;
+    let actual = archive.hash()?;
+    if expected.matches(&actual).is_none() {
+        return Err(PrebuiltLuaError::IntegrityMismatch {
+            url: url.to_string(),
+            expected,
+            actual,
+        });
+    }
+    operations::unpack(
+        Some("application/gzip"),
+        Cursor::new(archive),
+        false,
+        archive_path(version, target),
+        dest_dir,
+        &Progress::NoProgress,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn download(client: &Client, url: &Url, config: &Config) -> Result<Bytes, PrebuiltLuaError> {
+    let response = config
+        .authenticate(url, client.get(url.clone()))
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(PrebuiltLuaError::NotFound(url.clone()));
+    }
+    Ok(response.error_for_status()?.bytes().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prebuilt_lua_url() {
+        let base_url = Url::parse("https://example.com/lua").unwrap();
+        assert_eq!(
+            prebuilt_lua_url(&base_url, &LuaVersion::Lua54, "x86_64-unknown-linux-gnu")
+                .unwrap()
+                .as_str(),
+            "https://example.com/lua/5.4/x86_64-unknown-linux-gnu.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_pinned_checksum() {
+        let integrity = Bytes::from_static(b"lua").hash().unwrap();
+        let (_, hex_digest) = integrity.to_hex();
+        let checksums = [("5.4/x86_64-unknown-linux-gnu.tar.gz", hex_digest.as_str())];
+        assert!(
+            pinned_checksum(&checksums, &LuaVersion::Lua54, "x86_64-unknown-linux-gnu")
+                .unwrap()
+                .matches(&integrity)
+                .is_some()
+        );
+        assert!(
+            pinned_checksum(&checksums, &LuaVersion::Lua53, "x86_64-unknown-linux-gnu").is_none()
+        );
+        assert!(pinned_checksum(&checksums, &LuaVersion::Lua54, "aarch64-apple-darwin").is_none());
+    }
+}
@@ -4,17 +4,14 @@ mod make;
 mod rust_mlua;
 mod tree_sitter;
 
-pub use builtin::{BuiltinBuildSpec, LuaModule, ModulePaths, ModuleSpec};
+pub use builtin::{BuiltinBuildSpec, LuaModule, ModulePaths, ModuleSpec, ParseLuaModuleError};
 pub use cmake::*;
 pub use make::*;
 use path_slash::PathBufExt;
 pub use rust_mlua::*;
 pub use tree_sitter::*;
 
-use builtin::{
-    ModulePathsMissingSources, ModuleSpecAmbiguousPlatformOverride, ModuleSpecInternal,
-    ParseLuaModuleError,
-};
+use builtin::{ModulePathsMissingSources, ModuleSpecAmbiguousPlatformOverride, ModuleSpecInternal};
 
 use itertools::Itertools;
 
@@ -588,6 +585,14 @@ fn override_platform_specs(
     Ok(())
 }
 
+impl PartialOverride for BuildSpecInternal {
+    type Err = ModuleSpecAmbiguousPlatformOverride;
+
+    fn apply_overrides(&self, override_spec: &Self) -> Result<Self, Self::Err> {
+        override_build_spec_internal(self, override_spec)
+    }
+}
+
 fn override_build_spec_internal(
     base: &BuildSpecInternal,
     override_spec: &BuildSpecInternal,
@@ -636,7 +641,7 @@ fn override_build_spec_internal(
         ),
         make_install_variables: merge_map_opts(
             &override_spec.make_install_variables,
-            &base.make_build_variables,
+            &base.make_install_variables,
         ),
         variables: merge_map_opts(&override_spec.variables, &base.variables),
         cmake_lists_content: override_opt(
@@ -645,7 +650,32 @@ fn override_build_spec_internal(
         ),
         build_command: override_opt(&override_spec.build_command, &base.build_command),
         install_command: override_opt(&override_spec.install_command, &base.install_command),
-        install: override_opt(&override_spec.install, &base.install),
+        install: match (override_spec.install.clone(), base.install.clone()) {
+            (Some(override_install), Some(base_install)) => Some(InstallSpec {
+                lua: base_install
+                    .lua
+                    .into_iter()
+                    .chain(override_install.lua)
+                    .collect(),
+                lib: base_install
+                    .lib
+                    .into_iter()
+                    .chain(override_install.lib)
+                    .collect(),
+                conf: base_install
+                    .conf
+                    .into_iter()
+                    .chain(override_install.conf)
+                    .collect(),
+                bin: base_install
+                    .bin
+                    .into_iter()
+                    .chain(override_install.bin)
+                    .collect(),
+            }),
+            (override_install @ Some(_), None) => override_install,
+            (None, base_install) => base_install,
+        },
         copy_directories: match (
             override_spec.copy_directories.clone(),
             base.copy_directories.clone(),
@@ -29,7 +29,10 @@ use url::Url;
 use crate::{
     config::{LuaVersion, LuaVersionUnset},
     hash::HasIntegrity,
-    package::{PackageName, PackageSpec, PackageVersion, PackageVersionReq},
+    package::{
+        BuildDependencies, Dependencies, PackageName, PackageReq, PackageSpec, PackageVersion,
+        PackageVersionReq,
+    },
     project::project_toml::ProjectTomlError,
     project::ProjectRoot,
     rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
@@ -66,6 +69,8 @@ pub struct LocalLuaRockspec {
     supported_platforms: PlatformSupport,
     /// The Lua version requirement for this rock
     lua: PackageVersionReq,
+    /// Whether this rock depends on the virtual `luajit` package, which is provided by LuaJIT
+    requires_luajit: bool,
     dependencies: PerPlatform<Vec<LuaDependencySpec>>,
     build_dependencies: PerPlatform<Vec<LuaDependencySpec>>,
     external_dependencies: PerPlatform<HashMap<String, ExternalDependencySpec>>,
@@ -131,12 +136,18 @@ impl LocalLuaRockspec {
             .map(|dep| dep.version_req().clone())
             .unwrap_or(PackageVersionReq::Any);
 
+        let requires_luajit = dependencies
+            .current_platform()
+            .iter()
+            .any(|dep| dep.name().to_string() == "luajit");
+
+        /// `lua` and `luajit` are provided by the Lua installation, not by rocks.
         fn strip_lua(
             dependencies: PerPlatform<Vec<LuaDependencySpec>>,
         ) -> PerPlatform<Vec<LuaDependencySpec>> {
             dependencies.map(|deps| {
                 deps.iter()
-                    .filter(|dep| dep.name().to_string() != "lua")
+                    .filter(|dep| !matches!(dep.name().to_string().as_str(), "lua" | "luajit"))
                     .cloned()
                     .collect()
             })
@@ -155,6 +166,7 @@ impl LocalLuaRockspec {
             description: parse_lua_tbl_or_default(&lua, "description")?,
             supported_platforms: parse_lua_tbl_or_default(&lua, "supported_platforms")?,
             lua: lua_version_req,
+            requires_luajit,
             dependencies: strip_lua(dependencies),
             build_dependencies: strip_lua(build_dependencies),
             test_dependencies: strip_lua(test_dependencies),
@@ -218,6 +230,10 @@ impl Rockspec for LocalLuaRockspec {
         &self.lua
     }
 
+    fn requires_luajit(&self) -> bool {
+        self.requires_luajit
+    }
+
     fn dependencies(&self) -> &PerPlatform<Vec<LuaDependencySpec>> {
         &self.dependencies
     }
@@ -371,6 +387,7 @@ build = {{
             description: RockDescription::default(),
             supported_platforms: PlatformSupport::default(),
             lua: PackageVersionReq::Any,
+            requires_luajit: false,
             dependencies: PerPlatform::default(),
             build_dependencies: PerPlatform::default(),
             external_dependencies: PerPlatform::default(),
@@ -391,6 +408,42 @@ build = {{
             source: PerPlatform::new(source),
         }
     }
+
+    /// Set the Lua version requirement and dependencies of a rockspec
+    /// that was generated with [`RemoteLuaRockspec::from_package_and_source_spec`],
+    /// e.g. from a `lux.toml` or rockspec found in the fetched source.
+    pub(crate) fn with_dependencies(
+        mut self,
+        lua: PackageVersionReq,
+        dependencies: Vec<LuaDependencySpec>,
+        build_dependencies: Vec<LuaDependencySpec>,
+    ) -> Self {
+        let mut all_dependencies = dependencies.clone();
+        all_dependencies.insert(
+            0,
+            PackageReq {
+                name: "lua".into(),
+                version_req: lua.clone(),
+            }
+            .into(),
+        );
+        self.local.raw_content.push_str("\n\n");
+        self.local
+            .raw_content
+            .push_str(&Dependencies(&all_dependencies).display_lua().to_string());
+        if !build_dependencies.is_empty() {
+            self.local.raw_content.push_str("\n\n");
+            self.local.raw_content.push_str(
+                &BuildDependencies(&build_dependencies)
+                    .display_lua()
+                    .to_string(),
+            );
+        }
+        self.local.lua = lua;
+        self.local.dependencies = PerPlatform::new(dependencies);
+        self.local.build_dependencies = PerPlatform::new(build_dependencies);
+        self
+    }
 }
 
 impl Rockspec for RemoteLuaRockspec {
@@ -416,6 +469,10 @@ impl Rockspec for RemoteLuaRockspec {
         self.local.lua()
     }
 
+    fn requires_luajit(&self) -> bool {
+        self.local.requires_luajit()
+    }
+
     fn dependencies(&self) -> &PerPlatform<Vec<LuaDependencySpec>> {
         self.local.dependencies()
     }
@@ -521,7 +578,7 @@ pub struct RockDescription {
     pub labels: Vec<String>,
 }
 
-fn deserialize_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+pub(crate) fn deserialize_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -698,6 +755,7 @@ mod tests {
     use crate::git::GitSource;
     use crate::lua_rockspec::PlatformIdentifier;
     use crate::package::PackageSpec;
+    use crate::rockspec::LuaVersionCompatibility;
 
     use super::*;
 
@@ -1400,6 +1458,43 @@ mod tests {
         package = 'foo'\n
         version = '1.0.0-1'\n
         source = { url = 'git+https://hub.com/example-project/foo.zip' }\n
+        build = {\n
+            type = 'make',\n
+            build_variables = { CFLAGS = '-O2' },\n
+            install_variables = { PREFIX = '$(PREFIX)' },\n
+            platforms = {\n
+                linux = {\n
+                    build_target = 'linux',\n
+                    install_variables = { LIBDIR = '$(LIBDIR)' },\n
+                },\n
+            },\n
+        }\n
+        "
+        .to_string();
+        let rockspec = RemoteLuaRockspec::new(&rockspec_content).unwrap();
+        let per_platform = &rockspec.local.build.per_platform;
+        let linux = per_platform.get(&PlatformIdentifier::Linux).unwrap();
+        match &linux.build_backend {
+            Some(BuildBackendSpec::Make(make_spec)) => {
+                assert_eq!(make_spec.build_target, Some("linux".into()));
+                assert_eq!(
+                    make_spec.build_variables,
+                    HashMap::from([("CFLAGS".into(), "-O2".into())])
+                );
+                assert_eq!(
+                    make_spec.install_variables,
+                    HashMap::from([
+                        ("PREFIX".into(), "$(PREFIX)".into()),
+                        ("LIBDIR".into(), "$(LIBDIR)".into())
+                    ])
+                );
+            }
+            build_backend => panic!("expected a make build backend, got {build_backend:?}"),
+        }
+        let rockspec_content = "
+        package = 'foo'\n
+        version = '1.0.0-1'\n
+        source = { url = 'git+https://hub.com/example-project/foo.zip' }\n
         build = {\n
             type = 'builtin',\n
             modules = {\n
@@ -1703,4 +1798,26 @@ mod tests {
         assert_eq!(rockspec2.local.version, "1.0.5".parse().unwrap());
         assert_eq!(rockspec2.local.source, PerPlatform::new(source_spec.into()));
     }
+
+    #[tokio::test]
+    pub async fn parse_rockspec_luajit_dependency() {
+        let rockspec_content = "
+        package = 'foo'\n
+        version = '1.0.0-1'\n
+        source = {\n
+            url = 'https://github.com/nvim-neorocks/rocks.nvim/archive/1.0.0/rocks.nvim.zip',\n
+        }\n
+        dependencies = {\n
+            'lua >= 5.1',\n
+            'luajit >= 2.0',\n
+        }\n
+        "
+        .to_string();
+        let rockspec = RemoteLuaRockspec::new(&rockspec_content).unwrap();
+        assert!(rockspec.requires_luajit());
+        assert!(rockspec.dependencies().current_platform().is_empty());
+        assert!(rockspec.supports_lua_version(&LuaVersion::LuaJIT));
+        assert!(rockspec.supports_lua_version(&LuaVersion::LuaJIT52));
+        assert!(!rockspec.supports_lua_version(&LuaVersion::Lua51));
+    }
 }
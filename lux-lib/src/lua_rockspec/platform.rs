@@ -346,6 +346,44 @@ impl<T> PerPlatform<T> {
                 .collect(),
         }
     }
+
+    /// Display as a rockspec `key = value` pair, with the per-platform overrides
+    /// in a `platforms` table, e.g. `dependencies = { "foo", platforms = { windows = { ... } } }`.
+    pub(crate) fn display_lua_with<F>(&self, display: F) -> DisplayLuaKV
+    where
+        F: Fn(&T) -> DisplayLuaKV,
+    {
+        let mut kv = display(&self.default);
+        if self.per_platform.is_empty() {
+            return kv;
+        }
+        let platforms = DisplayLuaKV {
+            key: "platforms".to_string(),
+            value: DisplayLuaValue::Table(
+                self.per_platform
+                    .iter()
+                    .sorted_by_key(|(identifier, _)| identifier.to_string())
+                    .map(|(identifier, value)| DisplayLuaKV {
+                        key: identifier.to_string(),
+                        value: display(value).value,
+                    })
+                    .collect(),
+            ),
+        };
+        kv.value = match kv.value {
+            DisplayLuaValue::List(list) => DisplayLuaValue::Mixed(list, vec![platforms]),
+            DisplayLuaValue::Table(mut table) => {
+                table.push(platforms);
+                DisplayLuaValue::Table(table)
+            }
+            DisplayLuaValue::Mixed(list, mut table) => {
+                table.push(platforms);
+                DisplayLuaValue::Mixed(list, table)
+            }
+            value => value,
+        };
+        kv
+    }
 }
 
 impl<U, E> PerPlatform<Result<U, E>>
@@ -531,7 +569,7 @@ where
     }
 }
 
-fn apply_per_platform_overrides<T>(
+pub(crate) fn apply_per_platform_overrides<T>(
     per_platform: &mut HashMap<PlatformIdentifier, T>,
     base: &T,
 ) -> Result<(), T::Err>
@@ -635,6 +673,34 @@ mod tests {
         assert_eq!(*foo.get(&PlatformIdentifier::Windows), "default");
     }
 
+    #[tokio::test]
+    async fn display_per_platform() {
+        let deps = PerPlatform {
+            default: vec!["foo"],
+            per_platform: vec![(PlatformIdentifier::Windows, vec!["foo", "bar"])]
+                .into_iter()
+                .collect(),
+        };
+        let display = deps.display_lua_with(|deps| DisplayLuaKV {
+            key: "dependencies".to_string(),
+            value: DisplayLuaValue::List(
+                deps.iter()
+                    .map(|dep| DisplayLuaValue::String(dep.to_string()))
+                    .collect(),
+            ),
+        });
+        let lua = mlua::Lua::new();
+        lua.load(display.to_string()).exec().unwrap();
+        let dependencies: mlua::Table = lua.globals().get("dependencies").unwrap();
+        assert_eq!(dependencies.get::<String>(1).unwrap(), "foo");
+        let windows: mlua::Table = dependencies
+            .get::<mlua::Table>("platforms")
+            .unwrap()
+            .get("windows")
+            .unwrap();
+        assert_eq!(windows.get::<String>(2).unwrap(), "bar");
+    }
+
     #[cfg(target_os = "linux")]
     #[tokio::test]
     async fn test_target_identifier() {
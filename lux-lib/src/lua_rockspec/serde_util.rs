@@ -115,6 +115,8 @@ pub(crate) enum DisplayLuaValue {
     String(String),
     List(Vec<Self>),
     Table(Vec<DisplayLuaKV>),
+    /// A table with both an array part and a hash part.
+    Mixed(Vec<Self>, Vec<DisplayLuaKV>),
 }
 
 pub(crate) struct DisplayLuaKV {
@@ -147,6 +149,16 @@ impl Display for DisplayLuaValue {
 
                 write!(buf, "}}")?;
             }
+            DisplayLuaValue::Mixed(l, t) => {
+                writeln!(buf, "{{")?;
+                for item in l {
+                    writeln!(buf, "{item},")?;
+                }
+                for item in t {
+                    writeln!(buf, "{item},")?;
+                }
+                write!(buf, "}}")?;
+            }
         };
         let output = match stylua_lib::format_code(
             &buf,
@@ -17,7 +17,8 @@ use crate::{
     config::Config,
     hash::HasIntegrity,
     lockfile::{
-        LocalPackage, LocalPackageHashes, LockConstraint, LockfileError, OptState, PinnedState,
+        LocalPackage, LocalPackageHashes, LockConstraint, LockfileError, LockfileIntegrityError,
+        OptState, PinnedState,
     },
     lua_rockspec::{LuaVersionError, RemoteLuaRockspec},
     luarocks::rock_manifest::RockManifest,
@@ -25,7 +26,7 @@ use crate::{
     progress::{Progress, ProgressBar},
     remote_package_source::RemotePackageSource,
     rockspec::Rockspec,
-    tree::{self, Tree, TreeError},
+    tree::{self, checksums::RockChecksums, Tree, TreeError},
 };
 use crate::{lockfile::RemotePackageSourceUrl, rockspec::LuaVersionCompatibility};
 
@@ -43,6 +44,8 @@ pub enum InstallBinaryRockError {
     ExternalDependencyError(#[from] ExternalDependencyError),
     #[error(transparent)]
     LuaVersionError(#[from] LuaVersionError),
+    #[error("integrity check failed: {0}")]
+    Integrity(#[from] LockfileIntegrityError),
     #[error("failed to unpack packed rock: {0}")]
     Zip(#[from] zip::result::ZipError),
     #[error("rock_manifest not found. Cannot install rock files that were packed using LuaRocks version 1")]
@@ -64,6 +67,7 @@ pub(crate) struct BinaryRockInstall<'a> {
     entry_type: tree::EntryType,
     constraint: LockConstraint,
     behaviour: BuildBehaviour,
+    expected_hashes: Option<LocalPackageHashes>,
     config: &'a Config,
     tree: &'a Tree,
     progress: &'a Progress<ProgressBar>,
@@ -88,6 +92,7 @@ impl<'a> BinaryRockInstall<'a> {
             progress,
             constraint: LockConstraint::default(),
             behaviour: BuildBehaviour::default(),
+            expected_hashes: None,
             pin: PinnedState::default(),
             opt: OptState::default(),
             entry_type,
@@ -110,6 +115,13 @@ impl<'a> BinaryRockInstall<'a> {
         Self { behaviour, ..self }
     }
 
+    pub(crate) fn expected_hashes(self, expected_hashes: Option<LocalPackageHashes>) -> Self {
+        Self {
+            expected_hashes,
+            ..self
+        }
+    }
+
     pub(crate) async fn install(self) -> Result<LocalPackage, InstallBinaryRockError> {
         let rockspec = self.rockspec;
         self.progress.map(|p| {
@@ -129,6 +141,9 @@ impl<'a> BinaryRockInstall<'a> {
             rockspec: rockspec.hash()?,
             source: self.rock_bytes.hash()?,
         };
+        if let Some(expected_hashes) = &self.expected_hashes {
+            hashes.validate(expected_hashes)?;
+        }
         let source_url = match &self.source {
             RemotePackageSource::LuarocksBinaryRock(url) => {
                 Some(RemotePackageSourceUrl::Url { url: url.clone() })
@@ -162,10 +177,16 @@ impl<'a> BinaryRockInstall<'a> {
                     return Err(InstallBinaryRockError::RockManifestNotFound);
                 }
                 let rock_manifest_content = tokio::fs::read_to_string(rock_manifest_file).await?;
-                let output_paths = match self.entry_type {
-                    tree::EntryType::Entrypoint => self.tree.entrypoint(&package)?,
-                    tree::EntryType::DependencyOnly => self.tree.dependency(&package)?,
+                let rock_layout = match self.entry_type {
+                    tree::EntryType::Entrypoint => self.tree.entrypoint_layout(&package),
+                    tree::EntryType::DependencyOnly => self.tree.dependency_layout(&package),
                 };
+                // The rock is moved into the tree once it is complete.
+                std::fs::create_dir_all(self.tree.root())?;
+                let staging_dir = TempDir::new_in(self.tree.root(), ".lux-staging")?;
+                let output_paths = rock_layout.staged(&staging_dir.path().join("rock"));
+                std::fs::create_dir_all(&output_paths.lib)?;
+                std::fs::create_dir_all(&output_paths.src)?;
                 let rock_manifest = RockManifest::new(&rock_manifest_content)?;
                 install_manifest_entries(
                     &rock_manifest.lib.entries,
@@ -207,6 +228,8 @@ impl<'a> BinaryRockInstall<'a> {
                     tokio::fs::copy(&rockspec_path, output_paths.rockspec_path()).await?;
                     tokio::fs::remove_file(&rockspec_path).await?;
                 }
+                RockChecksums::write(&output_paths)?;
+                rock_layout.move_from_staging(&output_paths)?;
                 Ok(package)
             }
         }
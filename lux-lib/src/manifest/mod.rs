@@ -1,10 +1,17 @@
 use itertools::Itertools;
 use mlua::{Lua, LuaSerdeExt};
-use reqwest::{header::ToStrError, Client};
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, ToStrError, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    Client, RequestBuilder, StatusCode,
+};
 use std::path::{Path, PathBuf};
 use std::string::FromUtf8Error;
-use std::time::SystemTime;
-use std::{cmp::Ordering, collections::HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
 use thiserror::Error;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
@@ -13,6 +20,7 @@ use url::Url;
 use zip::ZipArchive;
 
 use crate::config::LuaVersionUnset;
+use crate::operations::retry_transient;
 use crate::package::{RemotePackageType, RemotePackageTypeFilterSpec};
 use crate::progress::{Progress, ProgressBar};
 use crate::{
@@ -41,29 +49,70 @@ pub enum ManifestFromServerError {
     ZipExtract(Url, zip::result::ZipError),
     #[error(transparent)]
     LuaVersion(#[from] LuaVersionUnset),
+    #[error("cannot download manifest {0} in offline mode")]
+    Offline(Url),
+    #[error("failed to read manifest {0}:\n{1}")]
+    ReadLocal(PathBuf, io::Error),
 }
 
-async fn get_manifest(
+/// Download the manifest at `url` to the `target` file, extracting it if it is zipped.
+/// If `revalidate` is set and the `target` is a previously downloaded manifest,
+/// it is only downloaded again if the server has a different version,
+/// according to the `ETag` and `Last-Modified` headers of the previous download.
+/// Failed downloads are retried like other downloads.
+pub(crate) async fn get_manifest(
     url: Url,
     manifest_version: String,
     target: &Path,
     client: &Client,
+    config: &Config,
+    revalidate: bool,
+    progress: &Progress<ProgressBar>,
 ) -> Result<String, ManifestFromServerError> {
-    let response = client.get(url.clone()).send().await?;
-    if response.status().is_client_error() {
-        let url = fallback_unzipped_url(&url)?;
-        let manifest_bytes = client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()?
-            .bytes()
-            .await?;
+    if let Ok(path) = url.to_file_path() {
+        let manifest = read_local_manifest(&url, &path, &manifest_version).await?;
+        fs::write(target, &manifest).await?;
+        return Ok(manifest);
+    }
+    let validators = if revalidate {
+        ManifestValidators::read(target).await
+    } else {
+        None
+    };
+    let validators = &validators;
+    let request = move |url: &Url| {
+        let request = config.authenticate(url, client.get(url.clone()));
+        match validators {
+            Some(validators) => validators.apply(request),
+            None => request,
+        }
+    };
+    let unzipped_url = &fallback_unzipped_url(&url)?;
+    let zipped_url = &url;
+    let response = retry_transient(&url, config, progress, move || async move {
+        let mut response = request(zipped_url).send().await?;
+        let is_zipped = !response.status().is_client_error();
+        if !is_zipped {
+            response = request(unzipped_url).send().await?;
+        }
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        let headers = response.headers().clone();
+        Ok(Some((is_zipped, headers, response.bytes().await?)))
+    })
+    .await?;
+    let (is_zipped, headers, manifest_bytes) = match response {
+        Some(response) => response,
+        None => return Ok(fs::read_to_string(target).await?),
+    };
+    let validators = ManifestValidators::from_headers(&headers)?;
+    let manifest = if !is_zipped {
         let manifest = String::from_utf8(manifest_bytes.to_vec())?;
         tokio::fs::write(&target, &manifest).await?;
-        Ok(manifest)
+        manifest
     } else {
-        let manifest_bytes = response.error_for_status()?.bytes().await?;
         let mut archive = ZipArchive::new(std::io::Cursor::new(manifest_bytes))
             .map_err(|err| ManifestFromServerError::ZipRead(url.clone(), err))?;
 
@@ -90,7 +139,97 @@ async fn get_manifest(
         target.seek(io::SeekFrom::Start(0)).await?;
         target.read_to_string(&mut manifest).await?;
 
-        Ok(manifest)
+        manifest
+    };
+    validators.write(target).await?;
+    Ok(manifest)
+}
+
+/// The `ETag` and `Last-Modified` headers of a downloaded manifest,
+/// which are stored next to the cached manifest, so that it can be revalidated
+/// with a conditional request instead of being downloaded again.
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct ManifestValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl ManifestValidators {
+    fn path(manifest: &Path) -> PathBuf {
+        let mut path = manifest.as_os_str().to_owned();
+        path.push(".validators.json");
+        PathBuf::from(path)
+    }
+
+    /// Read the validators of a cached manifest, or `None` if there is no cached manifest.
+    /// Caches without stored validators are revalidated by their modification time.
+    async fn read(manifest: &Path) -> Option<Self> {
+        let metadata = fs::metadata(manifest).await.ok()?;
+        let stored = fs::read_to_string(Self::path(manifest))
+            .await
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+        Some(stored.unwrap_or_else(|| Self {
+            etag: None,
+            last_modified: metadata.modified().ok().map(httpdate::fmt_http_date),
+        }))
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Result<Self, ToStrError> {
+        let header = |name| {
+            headers
+                .get(name)
+                .map(|value: &HeaderValue| value.to_str().map(String::from))
+                .transpose()
+        };
+        Ok(Self {
+            etag: header(ETAG)?,
+            last_modified: header(LAST_MODIFIED)?,
+        })
+    }
+
+    fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match &self.etag {
+            Some(etag) => request.header(IF_NONE_MATCH, etag),
+            None => request,
+        };
+        match &self.last_modified {
+            Some(last_modified) => request.header(IF_MODIFIED_SINCE, last_modified),
+            None => request,
+        }
+    }
+
+    async fn write(&self, manifest: &Path) -> io::Result<()> {
+        fs::write(Self::path(manifest), serde_json::to_string(self)?).await
+    }
+}
+
+/// Read the manifest of a rock server in a local directory, i.e. with a `file://` URL.
+/// Like with remote servers, the zipped manifest is preferred.
+async fn read_local_manifest(
+    url: &Url,
+    path: &Path,
+    manifest_version: &str,
+) -> Result<String, ManifestFromServerError> {
+    use std::io::Read as _;
+    match fs::read(path).await {
+        Ok(bytes) => {
+            let mut archive = ZipArchive::new(std::io::Cursor::new(bytes))
+                .map_err(|err| ManifestFromServerError::ZipRead(url.clone(), err))?;
+            let mut manifest_file = archive
+                .by_name(&format!("manifest-{manifest_version}"))
+                .map_err(|err| ManifestFromServerError::ZipExtract(url.clone(), err))?;
+            let mut manifest = String::new();
+            manifest_file.read_to_string(&mut manifest)?;
+            Ok(manifest)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let path = path.with_extension("");
+            fs::read_to_string(&path)
+                .await
+                .map_err(|err| ManifestFromServerError::ReadLocal(path, err))
+        }
+        Err(err) => Err(ManifestFromServerError::ReadLocal(path.to_path_buf(), err)),
     }
 }
 
@@ -108,45 +247,34 @@ async fn manifest_from_cache_or_server(
     // needing to pull it from the luarocks servers each time).
     let cache = mk_manifest_cache(&url, config).await?;
 
-    let client = Client::new();
-
-    // Read the metadata of the local cache and attempt to get the last modified date.
-    if let Ok(metadata) = fs::metadata(&cache).await {
-        let last_modified_local: SystemTime = metadata.modified()?;
+    // Local servers are always available and up to date, so they don't need a cache.
+    if let Ok(path) = url.to_file_path() {
+        return read_local_manifest(&url, &path, &manifest_version).await;
+    }
 
-        // Ask the server for the last modified date of its manifest.
-        let response = match client.head(url.clone()).send().await? {
-            response if response.status().is_client_error() => {
-                let url = fallback_unzipped_url(&url)?;
-                client.head(url).send().await?.error_for_status()?
-            }
-            response => response.error_for_status()?,
+    if config.offline() {
+        return match fs::read_to_string(&cache).await {
+            Ok(manifest) => Ok(manifest),
+            Err(_) => Err(ManifestFromServerError::Offline(url)),
         };
-
-        if let Some(last_modified_header) = response.headers().get("Last-Modified") {
-            let server_last_modified = httpdate::parse_http_date(last_modified_header.to_str()?)?;
-
-            // If the server's version of the manifest is newer than ours then update out manifest.
-            if server_last_modified > last_modified_local {
-                // Since we only pulled in the headers previously we must now request the entire
-                // manifest from scratch.
-                bar.map(|bar| {
-                    bar.set_message(format!("📥 Downloading updated manifest from {}", &url))
-                });
-
-                return get_manifest(url, manifest_version.clone(), &cache, &client).await;
-            }
-
-            // Else return the cached manifest.
-            return Ok(fs::read_to_string(&cache).await?);
-        }
     }
 
-    // If our cache file does not exist then pull the whole manifest.
+    let client = config.http_client(&url)?;
+
+    // If we have a cached manifest, the server only sends the manifest if it has changed.
     // TODO(#337): switch to something that can report progress
     bar.map(|bar| bar.set_message(format!("📥 Downloading manifest from {}", &url)));
 
-    get_manifest(url, manifest_version.clone(), &cache, &client).await
+    get_manifest(
+        url,
+        manifest_version.clone(),
+        &cache,
+        &client,
+        config,
+        !config.refresh_manifests(),
+        bar,
+    )
+    .await
 }
 
 /// Get the manifest from the server, ignoring the cache.
@@ -158,13 +286,28 @@ pub(crate) async fn manifest_from_server_only(
 ) -> Result<String, ManifestFromServerError> {
     let manifest_version = LuaVersion::from(config)?.version_compatibility_str();
     let url = mk_manifest_url(server_url, &manifest_version, config)?;
+    if let Ok(path) = url.to_file_path() {
+        return read_local_manifest(&url, &path, &manifest_version).await;
+    }
     let cache = mk_manifest_cache(&url, config).await?;
-    let client = Client::new();
+    if config.offline() {
+        return Err(ManifestFromServerError::Offline(url));
+    }
+    let client = config.http_client(&url)?;
     bar.map(|bar| bar.set_message(format!("📥 Downloading manifest from {}", &url)));
-    get_manifest(url, manifest_version.clone(), &cache, &client).await
+    get_manifest(
+        url,
+        manifest_version.clone(),
+        &cache,
+        &client,
+        config,
+        false,
+        bar,
+    )
+    .await
 }
 
-fn mk_manifest_url(
+pub(crate) fn mk_manifest_url(
     server_url: &Url,
     manifest_version: &str,
     config: &Config,
@@ -400,6 +543,135 @@ struct IntermediateManifest {
     repository: HashMap<PackageName, HashMap<String, Vec<ManifestRockEntry>>>,
 }
 
+/// The `repository` table of a luarocks manifest, which maps package names and versions
+/// to the architectures they are available for, e.g. `rockspec`, `src`, `all` or `linux-x86_64`.
+/// Unlike [`ManifestMetadata`], this keeps the entries for all architectures and unparsable versions,
+/// so that it can be written back to a manifest a server can serve.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ManifestRepository(BTreeMap<PackageName, BTreeMap<String, BTreeSet<String>>>);
+
+impl ManifestRepository {
+    pub fn new(manifest: &str) -> Result<Self, ManifestLuaError> {
+        let lua = Lua::new();
+        lua.load(manifest).exec()?;
+        let repository: HashMap<PackageName, HashMap<String, Vec<ManifestRockEntry>>> =
+            lua.from_value(lua.globals().get("repository")?)?;
+        Ok(Self(
+            repository
+                .into_iter()
+                .map(|(name, versions)| {
+                    let versions = versions
+                        .into_iter()
+                        .map(|(version, entries)| {
+                            (
+                                version,
+                                entries.into_iter().map(|entry| entry.arch).collect(),
+                            )
+                        })
+                        .collect();
+                    (name, versions)
+                })
+                .collect(),
+        ))
+    }
+
+    pub fn insert(&mut self, name: PackageName, version: String, arch: String) {
+        self.0
+            .entry(name)
+            .or_default()
+            .entry(version)
+            .or_default()
+            .insert(arch);
+    }
+
+    /// Add the entries of another repository.
+    pub fn merge(&mut self, other: &ManifestRepository) {
+        for (name, version, arch) in other.entries() {
+            self.insert(name.clone(), version.to_string(), arch.to_string());
+        }
+    }
+
+    /// Keep only the entries for which the predicate returns `true`.
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&PackageName, &str, &str) -> bool,
+    {
+        self.0.retain(|name, versions| {
+            versions.retain(|version, archs| {
+                archs.retain(|arch| predicate(name, version, arch));
+                !archs.is_empty()
+            });
+            !versions.is_empty()
+        });
+    }
+
+    pub fn package_count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The `(name, version, arch)` entries of the repository.
+    pub fn entries(&self) -> impl Iterator<Item = (&PackageName, &str, &str)> {
+        self.0.iter().flat_map(|(name, versions)| {
+            versions.iter().flat_map(move |(version, archs)| {
+                archs
+                    .iter()
+                    .map(move |arch| (name, version.as_str(), arch.as_str()))
+            })
+        })
+    }
+
+    /// Write a manifest with this repository.
+    /// The tables of commands and modules are left empty,
+    /// as Lux and luarocks only need the repository to find packages.
+    pub fn to_lua(&self) -> String {
+        let mut manifest = String::from("commands = {}\nmodules = {}\nrepository = {\n");
+        for (name, versions) in &self.0 {
+            manifest.push_str(&format!("  [\"{name}\"] = {{\n"));
+            for (version, archs) in versions {
+                manifest.push_str(&format!("    [\"{version}\"] = {{\n"));
+                for arch in archs {
+                    manifest.push_str(&format!("      {{ arch = \"{arch}\" }},\n"));
+                }
+                manifest.push_str("    },\n");
+            }
+            manifest.push_str("  },\n");
+        }
+        manifest.push_str("}\n");
+        manifest
+    }
+}
+
+/// The file name of a package on a luarocks server,
+/// e.g. `foo-1.0-1.rockspec` or `foo-1.0-1.src.rock`.
+pub(crate) fn manifest_entry_file_name(name: &PackageName, version: &str, arch: &str) -> String {
+    match arch {
+        "rockspec" => format!("{name}-{version}.rockspec"),
+        arch => format!("{name}-{version}.{arch}.rock"),
+    }
+}
+
+/// Parse the file name of a package on a luarocks server,
+/// e.g. `foo-1.0-1.rockspec` or `foo-1.0-1.src.rock`,
+/// into its name, version and architecture.
+pub(crate) fn parse_manifest_entry_file_name(
+    file_name: &str,
+) -> Option<(PackageName, String, String)> {
+    let (stem, arch) = match file_name.strip_suffix(".rockspec") {
+        Some(stem) => (stem, "rockspec"),
+        None => file_name.strip_suffix(".rock")?.rsplit_once('.')?,
+    };
+    let (name_and_version, revision) = stem.rsplit_once('-')?;
+    let (name, version) = name_and_version.rsplit_once('-')?;
+    if name.is_empty() || version.is_empty() || revision.parse::<u32>().is_err() {
+        return None;
+    }
+    Some((
+        PackageName::new(name.into()),
+        format!("{version}-{revision}"),
+        arch.into(),
+    ))
+}
+
 /// Given a URL to a zip file, create a URL to the same file without the .zip extension
 fn fallback_unzipped_url(url: &Url) -> Result<Url, url::ParseError> {
     url.to_string().trim_end_matches(".zip").parse()
@@ -409,7 +681,12 @@ fn fallback_unzipped_url(url: &Url) -> Result<Url, url::ParseError> {
 mod tests {
     use std::path::PathBuf;
 
-    use httptest::{matchers::request, responders::status_code, Expectation, Server};
+    use httptest::{
+        all_of, cycle,
+        matchers::{contains, key, not, request},
+        responders::status_code,
+        Expectation, Server,
+    };
     use serial_test::serial;
 
     use crate::{config::ConfigBuilder, package::PackageReq};
@@ -545,4 +822,141 @@ mod tests {
         let package_req: PackageReq = "30log > 1.3.0".parse().unwrap();
         assert!(metadata.latest_match(&package_req, None).is_none());
     }
+
+    #[tokio::test]
+    pub async fn manifest_repository_roundtrip() {
+        let mut test_manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_manifest_path.push("resources/test/manifest-5.1");
+        let manifest = String::from_utf8(fs::read(&test_manifest_path).await.unwrap()).unwrap();
+        let mut repository = ManifestRepository::new(&manifest).unwrap();
+        let package_name = PackageName::new("30log".into());
+        repository.retain(|name, _, arch| name == &package_name && arch == "src");
+        assert_eq!(repository.package_count(), 1);
+        assert!(repository.entries().all(|(_, _, arch)| arch == "src"));
+
+        let written = ManifestRepository::new(&repository.to_lua()).unwrap();
+        assert_eq!(written, repository);
+        ManifestMetadata::new(&repository.to_lua()).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn get_manifest_from_local_server() {
+        let server_dir = assert_fs::TempDir::new().unwrap();
+        let cache_dir = assert_fs::TempDir::new().unwrap().to_path_buf();
+        let mut test_manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_manifest_path.push("resources/test/manifest-5.1");
+        std::fs::copy(&test_manifest_path, server_dir.join("manifest-5.1")).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .cache_dir(Some(cache_dir))
+            .lua_version(Some(crate::config::LuaVersion::Lua51))
+            .server(Some(Url::from_file_path(server_dir.path()).unwrap()))
+            .offline(Some(true))
+            .build()
+            .unwrap();
+        let manifest =
+            Manifest::from_config(config.server().clone(), &config, &Progress::NoProgress)
+                .await
+                .unwrap();
+        assert!(manifest
+            .metadata()
+            .has_rock(&PackageName::new("30log".into())));
+    }
+
+    #[test]
+    fn parse_entry_file_names() {
+        for (file_name, arch) in [
+            ("lua-cjson-2.1.0-1.rockspec", "rockspec"),
+            ("lua-cjson-2.1.0-1.src.rock", "src"),
+            ("lua-cjson-2.1.0-1.linux-x86_64.rock", "linux-x86_64"),
+        ] {
+            let (name, version, parsed_arch) = parse_manifest_entry_file_name(file_name).unwrap();
+            assert_eq!(name, PackageName::new("lua-cjson".into()));
+            assert_eq!(version, "2.1.0-1");
+            assert_eq!(parsed_arch, arch);
+            assert_eq!(manifest_entry_file_name(&name, &version, arch), file_name);
+        }
+        assert!(parse_manifest_entry_file_name("foo-scm.rockspec").is_none());
+        assert!(parse_manifest_entry_file_name("manifest-5.1.zip").is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    pub async fn revalidate_cached_manifest() {
+        let cache_dir = assert_fs::TempDir::new().unwrap().to_path_buf();
+        let server = Server::run();
+        let manifest_zip = std::fs::read(format!(
+            "{}/resources/test/manifest-5.1.zip",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        server.expect(
+            Expectation::matching(all_of![
+                request::path("/manifest-5.1.zip"),
+                request::headers(not(contains(key("if-none-match")))),
+            ])
+            .times(1)
+            .respond_with(
+                status_code(200)
+                    .append_header("ETag", "\"v1\"")
+                    .body(manifest_zip),
+            ),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::path("/manifest-5.1.zip"),
+                request::headers(contains(("if-none-match", "\"v1\""))),
+            ])
+            .times(1)
+            .respond_with(status_code(304)),
+        );
+        let mut url_str = server.url_str("");
+        url_str.pop();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .cache_dir(Some(cache_dir))
+            .lua_version(Some(crate::config::LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let url = Url::parse(&url_str).unwrap();
+        let downloaded = manifest_from_cache_or_server(&url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        let revalidated = manifest_from_cache_or_server(&url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(downloaded, revalidated);
+    }
+
+    #[tokio::test]
+    #[serial]
+    pub async fn retry_manifest_download() {
+        let cache_dir = assert_fs::TempDir::new().unwrap().to_path_buf();
+        let server = Server::run();
+        let manifest_zip = std::fs::read(format!(
+            "{}/resources/test/manifest-5.1.zip",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        server.expect(
+            Expectation::matching(request::path("/manifest-5.1.zip"))
+                .times(2)
+                .respond_with(cycle![
+                    status_code(503),
+                    status_code(200).body(manifest_zip)
+                ]),
+        );
+        let mut url_str = server.url_str("");
+        url_str.pop();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .cache_dir(Some(cache_dir))
+            .lua_version(Some(crate::config::LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let url = Url::parse(&url_str).unwrap();
+        manifest_from_server_only(&url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+    }
 }
@@ -0,0 +1,107 @@
+//! Machine-readable build and install messages, similar to cargo's `--message-format json`.
+//!
+//! With [`MessageFormat::Json`], each message is printed to stdout as a JSON object
+//! on its own line, with an `event` field that identifies its kind,
+//! like the output of the CLI's `--json` flag.
+
+use std::process::Output;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, lockfile::LocalPackage, package::PackageSpec};
+
+/// The format of the messages that are printed while downloading, building and installing rocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// Human-readable progress bars.
+    #[default]
+    Human,
+    /// JSON messages on stdout.
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Message {
+    DownloadStarted {
+        /// The requested package, which may include a version constraint.
+        package: String,
+    },
+    DownloadFinished {
+        name: String,
+        version: String,
+    },
+    BuildStarted {
+        name: String,
+        version: String,
+    },
+    /// The output of a compiler or build command.
+    CompilerOutput {
+        success: bool,
+        stdout: String,
+        stderr: String,
+    },
+    InstallFinished {
+        name: String,
+        version: String,
+        id: String,
+    },
+}
+
+impl Message {
+    pub(crate) fn build_started(package: &PackageSpec) -> Self {
+        Self::BuildStarted {
+            name: package.name().to_string(),
+            version: package.version().to_string(),
+        }
+    }
+
+    pub(crate) fn download_finished(package: &PackageSpec) -> Self {
+        Self::DownloadFinished {
+            name: package.name().to_string(),
+            version: package.version().to_string(),
+        }
+    }
+
+    pub(crate) fn install_finished(package: &LocalPackage) -> Self {
+        Self::InstallFinished {
+            name: package.name().to_string(),
+            version: package.version().to_string(),
+            id: package.id().to_string(),
+        }
+    }
+
+    pub(crate) fn compiler_output(output: &Output) -> Self {
+        Self::CompilerOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into(),
+            stderr: String::from_utf8_lossy(&output.stderr).into(),
+        }
+    }
+
+    /// Print the message if the `config`'s message format is [`MessageFormat::Json`].
+    pub(crate) fn emit(&self, config: &Config) {
+        if config.message_format() == MessageFormat::Json {
+            if let Ok(json) = serde_json::to_string(self) {
+                println!("{json}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_json() {
+        let package = PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap();
+        assert_eq!(
+            serde_json::to_string(&Message::build_started(&package)).unwrap(),
+            r#"{"event":"build-started","name":"foo","version":"1.0.0-1"}"#
+        );
+    }
+}
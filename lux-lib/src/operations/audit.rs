@@ -0,0 +1,299 @@
+use std::{collections::HashSet, fmt::Display, io, path::PathBuf, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+use url::Url;
+
+use crate::{
+    config::Config,
+    lockfile::LocalPackage,
+    package::{PackageName, PackageVersionReq},
+    progress::{MultiProgress, Progress, ProgressBar},
+};
+
+/// Checks packages against a security advisory database.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Audit<'a> {
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The packages to check, e.g. the packages in a lockfile.
+    packages: Vec<LocalPackage>,
+
+    /// The URL of the advisory database.
+    /// Defaults to the `advisory_db` in the config.
+    advisory_db: Option<Url>,
+
+    /// IDs of advisories to ignore.
+    #[builder(default)]
+    ignore: HashSet<String>,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> AuditBuilder<'_, State>
+where
+    State: audit_builder::State + audit_builder::IsComplete,
+{
+    pub async fn audit(self) -> Result<Vec<Vulnerability>, AuditError> {
+        let args = self._build();
+        let url = args
+            .advisory_db
+            .or_else(|| args.config.advisory_db().cloned())
+            .ok_or(AuditError::NoAdvisoryDatabase)?;
+        let progress = args.progress.unwrap_or_else(MultiProgress::new_arc);
+        let bar = progress.map(|p| p.new_bar());
+        let db = AdvisoryDatabase::from_url(&url, args.config, &bar).await?;
+        bar.map(|b| b.finish_and_clear());
+        Ok(db
+            .vulnerabilities(&args.packages)
+            .into_iter()
+            .filter(|vulnerability| !args.ignore.contains(&vulnerability.advisory.id))
+            .collect_vec())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("no advisory database configured. Set `advisory_db` in the lux config or pass a URL.")]
+    NoAdvisoryDatabase,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to download the advisory database: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("failed to parse the advisory database: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("cannot download the advisory database {0} in offline mode")]
+    Offline(Url),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+        .fmt(f)
+    }
+}
+
+/// A security advisory, affecting a range of versions of a package.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Advisory {
+    id: String,
+    package: PackageName,
+    title: String,
+    #[serde(default)]
+    severity: Severity,
+    /// The affected versions.
+    vulnerable: PackageVersionReq,
+    /// The versions in which the vulnerability is fixed, if any.
+    patched: Option<PackageVersionReq>,
+    url: Option<Url>,
+}
+
+impl Advisory {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn package(&self) -> &PackageName {
+        &self.package
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn vulnerable(&self) -> &PackageVersionReq {
+        &self.vulnerable
+    }
+
+    pub fn patched(&self) -> Option<&PackageVersionReq> {
+        self.patched.as_ref()
+    }
+
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+}
+
+/// A database of security advisories, in the following JSON format:
+///
+/// ```json
+/// {
+///   "advisories": [
+///     {
+///       "id": "LUX-2025-0001",
+///       "package": "foo",
+///       "title": "Arbitrary code execution in foo.load",
+///       "severity": "high",
+///       "vulnerable": "< 1.2.0",
+///       "patched": ">= 1.2.0",
+///       "url": "https://example.com/advisories/LUX-2025-0001"
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdvisoryDatabase {
+    advisories: Vec<Advisory>,
+}
+
+impl AdvisoryDatabase {
+    pub fn from_json(json: &str) -> Result<Self, AuditError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Load the advisory database from a `file://` URL, or download it,
+    /// falling back to the cached database in offline mode.
+    pub async fn from_url(
+        url: &Url,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<Self, AuditError> {
+        if let Ok(path) = url.to_file_path() {
+            return Self::from_json(&fs::read_to_string(path).await?);
+        }
+        let cache = advisory_db_cache(url, config).await?;
+        if config.offline() {
+            return match fs::read_to_string(&cache).await {
+                Ok(json) => Self::from_json(&json),
+                Err(_) => Err(AuditError::Offline(url.clone())),
+            };
+        }
+        progress.map(|p| p.set_message(format!("📥 Downloading advisory database from {url}")));
+        let json = config
+            .authenticate(url, config.http_client(url)?.get(url.clone()))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let db = Self::from_json(&json)?;
+        fs::write(&cache, json).await?;
+        Ok(db)
+    }
+
+    pub fn advisories(&self) -> &[Advisory] {
+        &self.advisories
+    }
+
+    /// The advisories that affect the `packages`.
+    pub fn vulnerabilities(&self, packages: &[LocalPackage]) -> Vec<Vulnerability> {
+        packages
+            .iter()
+            .unique_by(|package| package.id())
+            .flat_map(|package| {
+                self.advisories
+                    .iter()
+                    .filter(|advisory| {
+                        &advisory.package == package.name()
+                            && advisory.vulnerable.matches(package.version())
+                    })
+                    .map(|advisory| Vulnerability {
+                        package: package.clone(),
+                        advisory: advisory.clone(),
+                    })
+            })
+            .sorted_by(|a, b| {
+                b.advisory
+                    .severity
+                    .cmp(&a.advisory.severity)
+                    .then_with(|| a.package.name().cmp(b.package.name()))
+            })
+            .collect_vec()
+    }
+}
+
+/// An installed package that is affected by an advisory.
+#[derive(Debug, Clone)]
+pub struct Vulnerability {
+    package: LocalPackage,
+    advisory: Advisory,
+}
+
+impl Vulnerability {
+    pub fn package(&self) -> &LocalPackage {
+        &self.package
+    }
+
+    pub fn advisory(&self) -> &Advisory {
+        &self.advisory
+    }
+}
+
+async fn advisory_db_cache(url: &Url, config: &Config) -> io::Result<PathBuf> {
+    let cache = config.cache_dir().join("advisories").join(
+        url.to_string()
+            .replace(&[':', '*', '?', '"', '<', '>', '|', '/', '\\'][..], "_"),
+    );
+    fs::create_dir_all(cache.parent().unwrap()).await?;
+    Ok(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::lockfile::{Lockfile, ReadOnly};
+
+    use super::*;
+
+    const ADVISORIES: &str = r#"{
+        "advisories": [
+            {
+                "id": "LUX-0001",
+                "package": "neorg",
+                "title": "Something bad",
+                "severity": "high",
+                "vulnerable": "< 9.0.0",
+                "patched": ">= 9.0.0"
+            },
+            {
+                "id": "LUX-0002",
+                "package": "say",
+                "title": "Something else",
+                "vulnerable": "< 1.0.0"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn find_vulnerabilities() {
+        let lockfile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test/sample-tree/5.1/lux.lock");
+        let lockfile: Lockfile<ReadOnly> = Lockfile::load(lockfile_path, None).unwrap();
+        let packages = lockfile.rocks().values().cloned().collect_vec();
+        let db = AdvisoryDatabase::from_json(ADVISORIES).unwrap();
+        let vulnerabilities = db.vulnerabilities(&packages);
+        assert_eq!(vulnerabilities.len(), 1);
+        let vulnerability = &vulnerabilities[0];
+        assert_eq!(vulnerability.package().name(), &"neorg".into());
+        assert_eq!(vulnerability.advisory().id(), "LUX-0001");
+        assert_eq!(vulnerability.advisory().severity(), Severity::High);
+        assert!(vulnerability.advisory().patched().is_some());
+    }
+}
@@ -93,7 +93,7 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
 
         if args.no_lock {
             let dependencies_to_install = dependencies
-                .into_iter()
+                .iter()
                 .filter(|dep| {
                     project_tree
                         .match_rocks(dep.package_req())
@@ -113,6 +113,7 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
 
             Install::new(config)
                 .packages(dependencies_to_install)
+                .patches(project.toml().patches())
                 .project(project)?
                 .progress(progress.clone())
                 .install()
@@ -143,6 +144,7 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
                 luarocks.ensure_installed(&bar).await?;
                 Install::new(config)
                     .packages(build_dependencies_to_install)
+                    .patches(project.toml().patches())
                     .tree(build_tree)
                     .progress(progress.clone())
                     .install()
@@ -175,12 +177,18 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
             .build()
             .await?;
 
+            // The tree may be shared with other workspace members,
+            // so only the project's own dependencies become dependencies of the package.
             let lockfile = project_tree.lockfile()?;
             let dependencies = lockfile
                 .rocks()
                 .iter()
                 .filter_map(|(pkg_id, value)| {
-                    if lockfile.is_entrypoint(pkg_id) {
+                    if lockfile.is_entrypoint(pkg_id)
+                        && dependencies
+                            .iter()
+                            .any(|dep| dep.package_req().matches(&value.as_package_spec()))
+                    {
                         Some(value)
                     } else {
                         None
@@ -192,7 +200,10 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
             lockfile.add_entrypoint(&package);
             for dep in dependencies {
                 lockfile.add_dependency(&package, &dep);
-                lockfile.remove_entrypoint(&dep);
+                // Other workspace members may depend on it, too.
+                if project.workspace_root().is_none() {
+                    lockfile.remove_entrypoint(&dep);
+                }
             }
             Ok(Some(package))
         } else {
@@ -0,0 +1,349 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use git2::Repository;
+use thiserror::Error;
+
+use crate::{
+    package::{HasModRev, PackageVersion, VersionBump, VersionBumpError},
+    project::{r#gen::GenerateVersionError, Project, ProjectEditError, ProjectRockspecError},
+    remote_package_db::RemotePackageDB,
+};
+
+const CHANGELOG: &str = "CHANGELOG.md";
+const UNRELEASED_HEADING: &str = "## [Unreleased]";
+
+/// Bump the version in a project's `lux.toml`,
+/// optionally committing the change and tagging the commit.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct BumpVersion<'a> {
+    #[builder(start_fn)]
+    project: &'a mut Project,
+    #[builder(start_fn)]
+    bump: VersionBump,
+
+    /// If set, the new version must be newer than all versions published on these servers.
+    package_db: Option<&'a RemotePackageDB>,
+
+    /// Commit the `lux.toml` with a conventional commit message,
+    /// e.g. `chore(release): 1.2.0`.
+    #[builder(default)]
+    commit: bool,
+
+    /// Create an annotated `v<version>` tag. Implies `commit`.
+    #[builder(default)]
+    tag: bool,
+
+    /// Write the rockspec generated for the new version to the project root,
+    /// so that it is committed together with the version bump.
+    #[builder(default)]
+    rockspec: bool,
+
+    /// The git URL to use as the `source.url` of the generated rockspec,
+    /// with the new `v<version>` tag as its `source.tag`,
+    /// unless the `lux.toml` sets a `source.url`.
+    git_source_url: Option<String>,
+
+    /// Turn the `## [Unreleased]` section of the project's `CHANGELOG.md`
+    /// into a section for the new version, if there is one.
+    #[builder(default)]
+    changelog: bool,
+}
+
+impl<State> BumpVersionBuilder<'_, State>
+where
+    State: bump_version_builder::State + bump_version_builder::IsComplete,
+{
+    pub async fn bump(self) -> Result<BumpedVersion, BumpVersionError> {
+        do_bump_version(self._build()).await
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BumpVersionError {
+    #[error("could not determine the current version:\n{0}")]
+    CurrentVersion(#[from] GenerateVersionError),
+    #[error(transparent)]
+    Bump(#[from] VersionBumpError),
+    #[error("the new version {new} is not newer than the current version {current}")]
+    NotNewer {
+        current: PackageVersion,
+        new: PackageVersion,
+    },
+    #[error("version {0} has already been published")]
+    AlreadyPublished(PackageVersion),
+    #[error("the new version {new} is older than the latest published version {published}")]
+    OlderThanPublished {
+        new: PackageVersion,
+        published: PackageVersion,
+    },
+    #[error("error updating lux.toml:\n{0}")]
+    Edit(#[from] ProjectEditError),
+    #[error("error updating {CHANGELOG}:\n{0}")]
+    Changelog(#[from] io::Error),
+    #[error("error generating the rockspec:\n{0}")]
+    Rockspec(#[from] ProjectRockspecError),
+    #[error("error writing the rockspec:\n{0}")]
+    WriteRockspec(io::Error),
+    #[error("error committing the version bump:\n{0}")]
+    Git(#[from] git2::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct BumpedVersion {
+    previous: PackageVersion,
+    version: PackageVersion,
+    tag: Option<String>,
+    changelog: Option<PathBuf>,
+    rockspec: Option<PathBuf>,
+}
+
+impl BumpedVersion {
+    pub fn previous(&self) -> &PackageVersion {
+        &self.previous
+    }
+
+    pub fn version(&self) -> &PackageVersion {
+        &self.version
+    }
+
+    /// The git tag that was created, if any.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// The changelog that was updated, if any.
+    pub fn changelog(&self) -> Option<&Path> {
+        self.changelog.as_deref()
+    }
+
+    /// The rockspec that was written, if any.
+    pub fn rockspec(&self) -> Option<&Path> {
+        self.rockspec.as_deref()
+    }
+}
+
+async fn do_bump_version(args: BumpVersion<'_>) -> Result<BumpedVersion, BumpVersionError> {
+    let project = args.project;
+    let previous = project.toml().version()?;
+    let version = previous.bump(&args.bump)?;
+
+    if previous.is_semver() && version.is_semver() && version <= previous {
+        return Err(BumpVersionError::NotNewer {
+            current: previous,
+            new: version,
+        });
+    }
+
+    if let Some(package_db) = args.package_db {
+        let published = package_db.versions(project.toml().package());
+        if published
+            .iter()
+            .any(|published| published.to_modrev_string() == version.to_modrev_string())
+        {
+            return Err(BumpVersionError::AlreadyPublished(version));
+        }
+        if let Some(latest) = published
+            .into_iter()
+            .filter(PackageVersion::is_semver)
+            .max()
+            .filter(|latest| version.is_semver() && &version < latest)
+        {
+            return Err(BumpVersionError::OlderThanPublished {
+                new: version,
+                published: latest,
+            });
+        }
+    }
+
+    project.set_version(&version).await?;
+    let version_str = version.to_modrev_string();
+
+    let changelog = project.root().join(CHANGELOG);
+    let changelog = if args.changelog && changelog.is_file() {
+        let content = tokio::fs::read_to_string(&changelog).await?;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        match release_changelog(&content, &version_str, &today) {
+            Some(content) => {
+                tokio::fs::write(&changelog, content).await?;
+                Some(changelog)
+            }
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    let rockspec = if args.rockspec {
+        let project = match args.git_source_url {
+            Some(url) => project
+                .clone()
+                .with_git_source(url, format!("v{version_str}")),
+            None => project.clone(),
+        };
+        let rockspec = project.root().join(project.rockspec_file_name()?);
+        tokio::fs::write(&rockspec, project.to_rockspec()?)
+            .await
+            .map_err(BumpVersionError::WriteRockspec)?;
+        Some(rockspec)
+    } else {
+        None
+    };
+
+    let tag = if args.commit || args.tag {
+        let mut files = vec![project.toml_path()];
+        files.extend(changelog.clone());
+        files.extend(rockspec.clone());
+        commit_version(project.root(), &files, &version_str)?;
+        if args.tag {
+            Some(tag_version(project.root(), &version_str)?)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    Ok(BumpedVersion {
+        previous,
+        version,
+        tag,
+        changelog,
+        rockspec,
+    })
+}
+
+/// Insert a heading for the `version` below the `## [Unreleased]` heading
+/// of a [Keep a Changelog](https://keepachangelog.com) style changelog,
+/// so that the unreleased changes are attributed to it.
+/// Returns `None` if there is no `## [Unreleased]` heading.
+fn release_changelog(content: &str, version: &str, date: &str) -> Option<String> {
+    let mut released = false;
+    let lines = content
+        .lines()
+        .map(|line| {
+            if !released && line.trim().eq_ignore_ascii_case(UNRELEASED_HEADING) {
+                released = true;
+                format!("{line}\n\n## [{version}] - {date}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    released.then(|| {
+        let mut content = lines.join("\n");
+        content.push('\n');
+        content
+    })
+}
+
+fn commit_version(
+    project_root: &Path,
+    files: &[PathBuf],
+    version: &str,
+) -> Result<(), git2::Error> {
+    let repo = Repository::discover(project_root)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("cannot commit in a bare repository"))?
+        .to_path_buf();
+    let workdir = workdir.canonicalize().unwrap_or(workdir);
+
+    let mut index = repo.index()?;
+    for file in files {
+        let file = file.canonicalize().unwrap_or(file.clone());
+        let relative_path = file.strip_prefix(&workdir).map_err(|_| {
+            git2::Error::from_str(&format!("{} is not in the git work tree", file.display()))
+        })?;
+        index.add_path(relative_path)?;
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = repo.signature()?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &format!("chore(release): {version}"),
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )?;
+    Ok(())
+}
+
+fn tag_version(project_root: &Path, version: &str) -> Result<String, git2::Error> {
+    let repo = Repository::discover(project_root)?;
+    let tag_name = format!("v{version}");
+    let head = repo.head()?.peel(git2::ObjectType::Commit)?;
+    let signature = repo.signature()?;
+    repo.tag(&tag_name, &head, &signature, &tag_name, false)?;
+    Ok(tag_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::PROJECT_TOML;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn bump_version_commit_and_tag() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            dir.join(PROJECT_TOML),
+            "package = \"foo\"\nversion = \"1.2.3\"\nlua = \">=5.1\"\n\n[build]\ntype = \"builtin\"\n",
+        )
+        .unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "lux").unwrap();
+        config.set_str("user.email", "lux@example.com").unwrap();
+
+        let mut project = Project::from(dir.path()).unwrap().unwrap();
+        let bumped = BumpVersion::new(&mut project, VersionBump::Minor)
+            .tag(true)
+            .rockspec(true)
+            .git_source_url("git+https://github.com/lumen-oss/foo.git".into())
+            .bump()
+            .await
+            .unwrap();
+        assert_eq!(bumped.version(), &PackageVersion::parse("1.3.0").unwrap());
+        assert_eq!(bumped.tag(), Some("v1.3.0"));
+        let rockspec = std::fs::read_to_string(bumped.rockspec().unwrap()).unwrap();
+        assert!(rockspec.contains("1.3.0-1"));
+        assert!(rockspec.contains("v1.3.0"));
+        assert_eq!(project.toml().version().unwrap(), *bumped.version());
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("chore(release): 1.3.0"));
+        // The rockspec is committed together with the version bump
+        assert!(head
+            .tree()
+            .unwrap()
+            .get_name("foo-1.3.0-1.rockspec")
+            .is_some());
+        assert!(repo.revparse_single("v1.3.0").is_ok());
+
+        let err = BumpVersion::new(&mut project, VersionBump::Exact("1.0.0".parse().unwrap()))
+            .bump()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BumpVersionError::NotNewer { .. }));
+    }
+
+    #[test]
+    fn release_unreleased_changes() {
+        let changelog =
+            "# Changelog\n\n## [Unreleased]\n\n### Added\n- foo\n\n## [1.0.0] - 2025-01-01\n";
+        assert_eq!(
+            release_changelog(changelog, "1.1.0", "2025-02-01").unwrap(),
+            "# Changelog\n\n## [Unreleased]\n\n## [1.1.0] - 2025-02-01\n\n### Added\n- foo\n\n## [1.0.0] - 2025-01-01\n"
+        );
+        assert!(release_changelog("# Changelog\n", "1.1.0", "2025-02-01").is_none());
+    }
+}
@@ -0,0 +1,603 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::Arc,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use target_lexicon::Triple;
+use tempdir::TempDir;
+use thiserror::Error;
+use tokio::process::Command;
+use walkdir::WalkDir;
+
+use crate::{
+    build::{
+        utils::{c_dylib_extension, c_lib_extension, luaopen_symbol},
+        Build, BuildBehaviour, BuildError,
+    },
+    config::{Config, ConfigBuilder, ConfigError},
+    lua_installation::{LuaInstallation, LuaInstallationError},
+    lua_rockspec::{LuaModule, LuaVersionError, ParseLuaModuleError},
+    luarocks::luarocks_installation::{LuaRocksError, LuaRocksInstallError, LuaRocksInstallation},
+    package::PackageName,
+    progress::{MultiProgress, Progress},
+    project::{project_toml::LocalProjectTomlValidationError, Project},
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree::{self, Tree, TreeError},
+};
+
+use super::{Install, InstallError, PackageInstallSpec};
+
+/// Builds a project and its dependencies, with C modules as static libraries,
+/// and links them into a single executable, along with the Lua interpreter
+/// and the project's Lua sources.
+/// Pure-Lua projects can also be amalgamated into a single Lua script.
+///
+/// The dependencies are installed into a separate tree, so that the project tree is left untouched.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Bundle<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The Lua script to run, relative to the project root.
+    /// Defaults to the project's binary, if it has exactly one.
+    entrypoint: Option<PathBuf>,
+
+    /// Where to write the bundle.
+    /// Defaults to the package name in the project's `dist` directory.
+    output: Option<PathBuf>,
+
+    #[builder(default)]
+    format: BundleFormat,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State> BundleBuilder<'_, State>
+where
+    State: bundle_builder::State + bundle_builder::IsComplete,
+{
+    /// Returns the path to the bundle.
+    pub async fn bundle(self) -> Result<PathBuf, BundleError> {
+        do_bundle(self._build()).await
+    }
+}
+
+/// The kind of bundle to create.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
+pub enum BundleFormat {
+    /// A self-contained executable, with the Lua interpreter and C modules linked statically.
+    #[default]
+    Executable,
+    /// A single Lua script, with the Lua modules it requires inlined.
+    /// Only works for pure-Lua projects and dependencies.
+    Lua,
+}
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    LuaVersion(#[from] LuaVersionError),
+    #[error(transparent)]
+    LuaInstallation(#[from] LuaInstallationError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    LuaRocks(#[from] LuaRocksError),
+    #[error(transparent)]
+    LuaRocksInstall(#[from] LuaRocksInstallError),
+    #[error("error installing dependencies:\n{0}")]
+    InstallDependencies(InstallError),
+    #[error("error installing build dependencies:\n{0}")]
+    InstallBuildDependencies(InstallError),
+    #[error("error building project:\n{0}")]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Modules(#[from] BundleModulesError),
+    #[error("error compiling the bundle: {0}")]
+    Compilation(#[from] cc::Error),
+    #[error("linking the bundle failed.\n\n{status}\n\nstdout:\n{stdout}\n\nstderr:\n{stderr}")]
+    Link {
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+    #[error("could not determine which script to bundle, as the project has {0} binaries.\nSpecify an entrypoint.")]
+    Entrypoint(usize),
+}
+
+/// Errors collecting the modules of a bundle and embedding them into its source.
+#[derive(Error, Debug)]
+pub enum BundleModulesError {
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    ParseLuaModule(#[from] ParseLuaModuleError),
+    #[error("cannot bundle the C module {module} of {package}, as it was not built as a static library.\nOnly C modules built with the builtin build backend can be bundled.")]
+    NoStaticLib {
+        package: PackageName,
+        module: LuaModule,
+    },
+    #[error("cannot bundle the C module {0} into a Lua script.\nBundle an executable instead.")]
+    CModule(String),
+}
+
+/// The modules to preload in the bundle.
+#[derive(Default)]
+struct BundleModules {
+    /// Lua sources, by module name.
+    lua: BTreeMap<String, PathBuf>,
+    /// Static libraries of C modules, by module name.
+    c: BTreeMap<String, PathBuf>,
+}
+
+async fn do_bundle(args: Bundle<'_>) -> Result<PathBuf, BundleError> {
+    let project = args.project;
+    let progress = args.progress;
+    let config = ConfigBuilder::from(args.config.clone())
+        .static_libs(Some(args.format == BundleFormat::Executable))
+        .build()?;
+    let project_toml = project.toml().into_local()?;
+    let lua_version = project.lua_version(&config)?;
+    let tree = Tree::new(
+        project.root().join(".lux").join("bundle"),
+        lua_version.clone(),
+        &config,
+    )?;
+
+    let install_spec = |dep: &LuaDependencySpec| {
+        PackageInstallSpec::new(dep.package_req().clone(), tree::EntryType::Entrypoint)
+            .pin(*dep.pin())
+            .opt(*dep.opt())
+            .maybe_source(dep.source().clone())
+            .build()
+    };
+    let build_dependencies = project_toml
+        .build_dependencies()
+        .current_platform()
+        .iter()
+        .map(install_spec)
+        .collect_vec();
+    if !build_dependencies.is_empty() {
+        let build_tree = tree.build_tree(&config)?;
+        LuaRocksInstallation::new(&config, build_tree.clone())?
+            .ensure_installed(&progress.map(|p| p.new_bar()))
+            .await?;
+        Install::new(&config)
+            .packages(build_dependencies)
+            .patches(project.toml().patches())
+            .tree(build_tree)
+            .progress(progress.clone())
+            .install()
+            .await
+            .map_err(BundleError::InstallBuildDependencies)?;
+    }
+    Install::new(&config)
+        .packages(
+            project_toml
+                .dependencies()
+                .current_platform()
+                .iter()
+                .map(install_spec)
+                .collect(),
+        )
+        .patches(project.toml().patches())
+        .tree(tree.clone())
+        .progress(progress.clone())
+        .install()
+        .await
+        .map_err(BundleError::InstallDependencies)?;
+    Build::new(
+        &project_toml,
+        &tree,
+        tree::EntryType::Entrypoint,
+        &config,
+        &progress.map(|p| p.new_bar()),
+    )
+    .behaviour(BuildBehaviour::Force)
+    .build()
+    .await?;
+
+    let entrypoint = match args.entrypoint {
+        Some(entrypoint) => entrypoint,
+        None => {
+            let binaries = &project_toml.build().current_platform().install.bin;
+            match binaries.values().exactly_one() {
+                Ok(bin) => bin.clone(),
+                Err(_) => return Err(BundleError::Entrypoint(binaries.len())),
+            }
+        }
+    };
+    let main_source = std::fs::read(project.root().join(entrypoint))?;
+    let modules = collect_modules(&tree, &config)?;
+
+    let extension = match args.format {
+        BundleFormat::Executable => std::env::consts::EXE_SUFFIX,
+        BundleFormat::Lua => ".lua",
+    };
+    let output = args.output.unwrap_or_else(|| {
+        project
+            .root()
+            .join("dist")
+            .join(format!("{}{extension}", project_toml.package()))
+    });
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if args.format == BundleFormat::Lua {
+        let script = amalgamate(&modules, &String::from_utf8_lossy(&main_source))?;
+        std::fs::write(&output, script)?;
+        return Ok(output);
+    }
+    let bar = progress.map(|p| p.new_bar());
+    bar.map(|b| b.set_message(format!("📦 Linking {}", output.display())));
+    let lua = LuaInstallation::new(&lua_version, &config).await?;
+    let main = generate_main(&modules, &main_source)?;
+    link(&main, &modules, &lua, &output, &config).await?;
+    bar.map(|b| b.finish_and_clear());
+    Ok(output)
+}
+
+/// Collect the Lua sources and C modules of all packages in the `tree`.
+/// If the `config` asks for static libraries, C modules are collected as static libraries.
+fn collect_modules(tree: &Tree, config: &Config) -> Result<BundleModules, BundleModulesError> {
+    let mut modules = BundleModules::default();
+    for package in tree.lockfile()?.rocks().values() {
+        let layout = tree.installed_rock_layout(package)?;
+        for file in files_with_extension(&layout.src, "lua") {
+            let module = LuaModule::from_pathbuf(file.strip_prefix(&layout.src).unwrap().into());
+            modules.lua.insert(module.to_string(), file);
+        }
+        for file in files_with_extension(&layout.lib, c_dylib_extension()) {
+            let module = LuaModule::from_pathbuf(file.strip_prefix(&layout.lib).unwrap().into());
+            if !config.static_libs() {
+                modules.c.insert(module.to_string(), file);
+                continue;
+            }
+            let static_lib = file.with_extension(c_lib_extension());
+            if !static_lib.is_file() {
+                return Err(BundleModulesError::NoStaticLib {
+                    package: package.name().clone(),
+                    module,
+                });
+            }
+            modules.c.insert(module.to_string(), static_lib);
+        }
+    }
+    Ok(modules)
+}
+
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|file| file.is_file() && file.extension().is_some_and(|ext| ext == extension))
+        .sorted()
+        .collect_vec()
+}
+
+/// Generate the C source of the bundle's `main` function, which preloads all modules
+/// into `package.preload` and runs the `main_source` with the command line arguments.
+fn generate_main(
+    modules: &BundleModules,
+    main_source: &[u8],
+) -> Result<String, BundleModulesError> {
+    let mut declarations = String::new();
+    let mut preloads = String::new();
+    for module in modules.c.keys() {
+        // Module names are validated by the rockspec parser, but we don't want to rely on that here
+        let symbol = luaopen_symbol(&module.parse()?);
+        declarations.push_str(&format!("int {symbol}(lua_State *L);\n"));
+        preloads.push_str(&format!(
+            "  lua_pushcfunction(L, {symbol});\n  lua_setfield(L, -2, {module:?});\n"
+        ));
+    }
+    let mut lua_modules = String::new();
+    for (index, (module, source)) in modules.lua.iter().enumerate() {
+        let source = std::fs::read(source)?;
+        declarations.push_str(&format!(
+            "static const unsigned char lux_module_{index}[] = {{{}}};\n",
+            c_bytes(&source)
+        ));
+        lua_modules.push_str(&format!(
+            "  {{ {module:?}, lux_module_{index}, sizeof(lux_module_{index}) }},\n"
+        ));
+    }
+    Ok(format!(
+        r#"/* Generated by lux. */
+#include <stdio.h>
+#include <stddef.h>
+#include "lua.h"
+#include "lauxlib.h"
+#include "lualib.h"
+
+{declarations}
+static const unsigned char lux_main[] = {{{main}}};
+
+static const struct {{
+  const char *name;
+  const unsigned char *source;
+  size_t size;
+}} lux_lua_modules[] = {{
+{lua_modules}  {{ NULL, NULL, 0 }}
+}};
+
+static int lux_traceback(lua_State *L) {{
+  lua_getglobal(L, "debug");
+  lua_getfield(L, -1, "traceback");
+  lua_pushvalue(L, 1);
+  lua_pushinteger(L, 2);
+  lua_call(L, 2, 1);
+  return 1;
+}}
+
+static int lux_report(lua_State *L, int status) {{
+  if (status != 0) {{
+    const char *msg = lua_tostring(L, -1);
+    fprintf(stderr, "%s\n", msg ? msg : "(error object is not a string)");
+    lua_pop(L, 1);
+  }}
+  return status;
+}}
+
+int main(int argc, char **argv) {{
+  int i;
+  int status;
+  lua_State *L = luaL_newstate();
+  if (L == NULL) {{
+    fprintf(stderr, "cannot create Lua state\n");
+    return 1;
+  }}
+  luaL_openlibs(L);
+  lua_getglobal(L, "package");
+  lua_getfield(L, -1, "preload");
+{preloads}  for (i = 0; lux_lua_modules[i].name != NULL; i++) {{
+    status = luaL_loadbuffer(L, (const char *)lux_lua_modules[i].source,
+                             lux_lua_modules[i].size, lux_lua_modules[i].name);
+    if (lux_report(L, status) != 0) {{
+      lua_close(L);
+      return 1;
+    }}
+    lua_setfield(L, -2, lux_lua_modules[i].name);
+  }}
+  lua_pop(L, 2);
+  lua_createtable(L, argc, 0);
+  for (i = 0; i < argc; i++) {{
+    lua_pushstring(L, argv[i]);
+    lua_rawseti(L, -2, i);
+  }}
+  lua_setglobal(L, "arg");
+  lua_pushcfunction(L, lux_traceback);
+  status = luaL_loadbuffer(L, (const char *)lux_main, sizeof(lux_main), "=main");
+  if (status == 0) {{
+    for (i = 1; i < argc; i++) {{
+      lua_pushstring(L, argv[i]);
+    }}
+    status = lua_pcall(L, argc - 1, 0, 1);
+  }}
+  lux_report(L, status);
+  lua_close(L);
+  return status == 0 ? 0 : 1;
+}}
+"#,
+        main = c_bytes(main_source),
+    ))
+}
+
+/// Inline the Lua modules that the `main_source` requires, directly or transitively,
+/// into a single script, as `package.preload` functions.
+/// Required modules that aren't in the bundle (e.g. standard libraries) are left to `require`.
+fn amalgamate(modules: &BundleModules, main_source: &str) -> Result<String, BundleModulesError> {
+    let mut required = BTreeSet::new();
+    let mut queue = VecDeque::from(required_modules(main_source));
+    let mut sources = BTreeMap::new();
+    while let Some(module) = queue.pop_front() {
+        if !required.insert(module.clone()) {
+            continue;
+        }
+        if modules.c.contains_key(&module) {
+            return Err(BundleModulesError::CModule(module));
+        }
+        if let Some(path) = modules.lua.get(&module) {
+            let source = std::fs::read_to_string(path)?;
+            queue.extend(required_modules(&source));
+            sources.insert(module, source);
+        }
+    }
+    let (shebang, main_source) = split_shebang(main_source);
+    let mut script = String::new();
+    if let Some(shebang) = shebang {
+        script.push_str(shebang);
+        script.push('\n');
+    }
+    script.push_str("-- Generated by lux.\n");
+    for (module, source) in sources {
+        let (_, source) = split_shebang(&source);
+        script.push_str(&format!(
+            "package.preload[{module:?}] = function(...)\n{source}\nend\n"
+        ));
+    }
+    script.push_str(main_source);
+    Ok(script)
+}
+
+/// The modules required with a string literal in a Lua `source`,
+/// e.g. `require("foo")`, `require 'foo'` or `require "foo"`.
+fn required_modules(source: &str) -> Vec<String> {
+    source
+        .match_indices("require")
+        .filter(|(index, _)| {
+            // Skip identifiers like `my_require`
+            !source[..*index]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == ':')
+        })
+        .filter_map(|(index, keyword)| {
+            let rest = source[index + keyword.len()..].trim_start();
+            let rest = rest.strip_prefix('(').unwrap_or(rest).trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let rest = &rest[1..];
+            let end = rest.find(quote)?;
+            Some(rest[..end].to_string())
+        })
+        .collect_vec()
+}
+
+fn split_shebang(source: &str) -> (Option<&str>, &str) {
+    if source.starts_with("#!") {
+        match source.split_once('\n') {
+            Some((shebang, rest)) => (Some(shebang), rest),
+            None => (Some(source), ""),
+        }
+    } else {
+        (None, source)
+    }
+}
+
+/// Format bytes as the contents of a C array initializer.
+fn c_bytes(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .map(|chunk| chunk.iter().map(|byte| format!("0x{byte:02x}")).join(","))
+        .join(",\n")
+}
+
+/// Compile the `main` source and link it with the static libraries and the Lua library.
+async fn link(
+    main: &str,
+    modules: &BundleModules,
+    lua: &LuaInstallation,
+    output: &Path,
+    config: &Config,
+) -> Result<(), BundleError> {
+    let build_dir = TempDir::new("lux-bundle")?;
+    let main_file = build_dir.path().join("main.c");
+    std::fs::write(&main_file, main)?;
+
+    let host = Triple::host();
+    let mut build = cc::Build::new();
+    let build = build
+        .cargo_output(false)
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .warnings(config.verbose())
+        .file(&main_file)
+        .host(std::env::consts::OS)
+        .includes(lua.includes())
+        .opt_level(2)
+        .out_dir(build_dir.path())
+        .target(&host.to_string());
+    for arg in lua.define_flags() {
+        build.flag(&arg);
+    }
+    let objects = build.try_compile_intermediates()?;
+    let compiler = build.try_get_compiler()?;
+    let mut cmd: Command = compiler.to_command().into();
+    if compiler.is_like_msvc() {
+        cmd.arg("/NOLOGO")
+            .args(&objects)
+            .args(modules.c.values())
+            .arg(format!("/Fe{}", output.display()))
+            .arg("/link")
+            .args(lua.lib_link_args(&compiler));
+    } else {
+        // Static libraries must come before the libraries they depend on
+        cmd.args(&objects)
+            .args(modules.c.values())
+            .args(lua.lib_link_args(&compiler))
+            .arg("-o")
+            .arg(output);
+        if cfg!(target_os = "linux") {
+            cmd.args(["-lm", "-ldl"]);
+        }
+    }
+    let link_output = cmd.output().await?;
+    if !link_output.status.success() {
+        return Err(BundleError::Link {
+            status: link_output.status,
+            stdout: String::from_utf8_lossy(&link_output.stdout).into(),
+            stderr: String::from_utf8_lossy(&link_output.stderr).into(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn generate_bundle_main() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let source = dir.child("foo.lua");
+        source.write_str("return {}").unwrap();
+        let modules = BundleModules {
+            lua: BTreeMap::from([("foo".into(), source.to_path_buf())]),
+            c: BTreeMap::from([("foo.core".into(), dir.join("core.a"))]),
+        };
+        let main = generate_main(&modules, b"print(1)").unwrap();
+        assert!(main.contains("int luaopen_foo_core(lua_State *L);"));
+        assert!(main.contains("lua_pushcfunction(L, luaopen_foo_core);"));
+        assert!(main.contains(r#"lua_setfield(L, -2, "foo.core");"#));
+        assert!(main.contains(r#"{ "foo", lux_module_0, sizeof(lux_module_0) },"#));
+        assert!(main.contains(&c_bytes(b"return {}")));
+        assert!(main.contains(&c_bytes(b"print(1)")));
+        assert_eq!(c_bytes(b"ab"), "0x61,0x62");
+    }
+
+    #[test]
+    fn amalgamate_required_modules() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let foo = dir.child("foo.lua");
+        foo.write_str("local bar = require 'foo.bar'\nreturn {}")
+            .unwrap();
+        let bar = dir.child("bar.lua");
+        bar.write_str("return require(\"string\")").unwrap();
+        let unused = dir.child("unused.lua");
+        unused.write_str("return {}").unwrap();
+        let modules = BundleModules {
+            lua: BTreeMap::from([
+                ("foo".into(), foo.to_path_buf()),
+                ("foo.bar".into(), bar.to_path_buf()),
+                ("unused".into(), unused.to_path_buf()),
+            ]),
+            c: BTreeMap::from([("foo.core".into(), dir.join("core.so"))]),
+        };
+        let script = amalgamate(&modules, "#!/usr/bin/env lua\nrequire(\"foo\")").unwrap();
+        assert!(script.starts_with("#!/usr/bin/env lua\n"));
+        assert!(script.contains("package.preload[\"foo\"] = function(...)"));
+        assert!(script.contains("package.preload[\"foo.bar\"] = function(...)"));
+        assert!(!script.contains("unused"));
+        assert!(script.ends_with("require(\"foo\")"));
+        assert!(matches!(
+            amalgamate(&modules, "require 'foo.core'"),
+            Err(BundleModulesError::CModule(module)) if module == "foo.core"
+        ));
+        assert_eq!(
+            required_modules("my_require('x') x:require('y')"),
+            Vec::<String>::new()
+        );
+    }
+}
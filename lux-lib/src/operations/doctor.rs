@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use bon::Builder;
+use target_lexicon::Triple;
+use which::which;
+
+use crate::{
+    config::{Config, ConfigBuilder, LuaVersion},
+    lua_installation::LuaInstallation,
+    tree::Tree,
+};
+
+use super::Verify;
+
+/// Checks the environment that Lux needs to build and install rocks,
+/// i.e. the toolchain, the configuration and the consistency of a tree with its lockfile.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Doctor<'a> {
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The tree to check against its lockfile.
+    tree: Option<&'a Tree>,
+}
+
+impl<State> DoctorBuilder<'_, State>
+where
+    State: doctor_builder::State + doctor_builder::IsComplete,
+{
+    pub async fn diagnose(self) -> DoctorReport {
+        do_diagnose(self._build()).await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    /// Lux works, but some rocks may fail to build or install.
+    Warning,
+    /// Lux will not be able to build or install rocks.
+    Error,
+}
+
+#[derive(Debug)]
+pub struct DoctorCheck {
+    name: &'static str,
+    status: CheckStatus,
+    message: String,
+    fix: Option<String>,
+}
+
+impl DoctorCheck {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn failed(
+        name: &'static str,
+        status: CheckStatus,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn status(&self) -> CheckStatus {
+        self.status
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// An actionable suggestion for fixing a failed check.
+    pub fn fix(&self) -> Option<&str> {
+        self.fix.as_deref()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn checks(&self) -> &[DoctorCheck] {
+        &self.checks
+    }
+
+    /// Whether none of the checks resulted in an error. Warnings are allowed.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Error)
+    }
+}
+
+async fn do_diagnose(args: Doctor<'_>) -> DoctorReport {
+    let config = args.config;
+    let mut checks = vec![check_config_file()];
+
+    let lua_version = LuaVersion::from(config).cloned();
+    checks.push(match &lua_version {
+        Ok(lua_version) => DoctorCheck::ok("lua version", format!("using Lua {lua_version}")),
+        Err(err) => DoctorCheck::failed(
+            "lua version",
+            CheckStatus::Error,
+            err.to_string(),
+            "run `lx toolchain default <version>` or pass `--lua-version <version>`",
+        ),
+    });
+    if let Ok(lua_version) = &lua_version {
+        checks.push(check_lua_headers(lua_version, config));
+    }
+
+    checks.push(check_c_compiler());
+    checks.push(check_executable(
+        "git",
+        "install git to build rocks whose build steps invoke it",
+    ));
+    checks.push(check_executable(
+        "unzip",
+        "install unzip to build rocks whose build steps invoke it",
+    ));
+
+    if let Some(tree) = args.tree {
+        checks.push(check_tree(tree, config).await);
+    }
+
+    DoctorReport { checks }
+}
+
+fn check_config_file() -> DoctorCheck {
+    let config_file = match ConfigBuilder::config_file() {
+        Ok(config_file) => config_file,
+        Err(err) => {
+            return DoctorCheck::failed(
+                "config",
+                CheckStatus::Error,
+                err.to_string(),
+                "set the HOME environment variable",
+            )
+        }
+    };
+    match ConfigBuilder::new().and_then(ConfigBuilder::build) {
+        Ok(_) if config_file.is_file() => {
+            DoctorCheck::ok("config", format!("{} is valid", config_file.display()))
+        }
+        Ok(_) => DoctorCheck::ok("config", "using the default config"),
+        Err(err) => DoctorCheck::failed(
+            "config",
+            CheckStatus::Error,
+            format!("{} is invalid: {err}", config_file.display()),
+            "fix the config file, or run `lx config init --default` to reset it",
+        ),
+    }
+}
+
+fn check_lua_headers(lua_version: &LuaVersion, config: &Config) -> DoctorCheck {
+    let lua = LuaInstallation::find_installed(lua_version, config)
+        .or_else(|| LuaInstallation::find_system(lua_version, config));
+    let header = lua.as_ref().and_then(|lua| {
+        lua.includes()
+            .into_iter()
+            .map(|include_dir| include_dir.join("lua.h"))
+            .find(|header| header.is_file())
+    });
+    match header {
+        Some(header) => DoctorCheck::ok("lua headers", format!("found {}", header.display())),
+        None => DoctorCheck::failed(
+            "lua headers",
+            CheckStatus::Error,
+            format!("could not find the Lua {lua_version} headers"),
+            format!(
+                "run `lx toolchain install {lua_version}`, or install your distribution's Lua {lua_version} development package"
+            ),
+        ),
+    }
+}
+
+fn check_c_compiler() -> DoctorCheck {
+    let host = Triple::host();
+    let mut build = cc::Build::new();
+    let compiler = build
+        .cargo_output(false)
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .host(std::env::consts::OS)
+        .opt_level(3)
+        .target(&host.to_string())
+        .try_get_compiler();
+    let fix = "install a C compiler (e.g. gcc or clang), or set the CC environment variable";
+    match compiler {
+        Ok(compiler) if is_executable(compiler.path()) => {
+            DoctorCheck::ok("c compiler", format!("found {}", compiler.path().display()))
+        }
+        Ok(compiler) => DoctorCheck::failed(
+            "c compiler",
+            CheckStatus::Error,
+            format!("{} not found", compiler.path().display()),
+            fix,
+        ),
+        Err(err) => DoctorCheck::failed("c compiler", CheckStatus::Error, err.to_string(), fix),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || which(path).is_ok()
+}
+
+fn check_executable(name: &'static str, fix: &str) -> DoctorCheck {
+    match which(name) {
+        Ok(path) => DoctorCheck::ok(name, format!("found {}", path.display())),
+        Err(_) => DoctorCheck::failed(
+            name,
+            CheckStatus::Warning,
+            format!("`{name}` not found on the PATH"),
+            fix,
+        ),
+    }
+}
+
+async fn check_tree(tree: &Tree, config: &Config) -> DoctorCheck {
+    let fix =
+        "run `lx verify --fix` to reinstall corrupted rocks and remove orphaned rock directories";
+    match Verify::new(tree, config).verify().await {
+        Ok(report) if report.is_ok() => DoctorCheck::ok(
+            "tree",
+            format!("{} matches its lockfile", tree.root().display()),
+        ),
+        Ok(report) => DoctorCheck::failed(
+            "tree",
+            CheckStatus::Error,
+            format!(
+                "{} corrupted and {} orphaned rock(s) in {}",
+                report.corrupted().len(),
+                report.orphaned().len(),
+                tree.root().display()
+            ),
+            fix,
+        ),
+        Err(err) => DoctorCheck::failed("tree", CheckStatus::Error, err.to_string(), fix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_executable_is_a_warning() {
+        let check = check_executable("lux-doctor-nonexistent", "install it");
+        assert_eq!(check.status(), CheckStatus::Warning);
+        assert_eq!(check.fix(), Some("install it"));
+        assert!(DoctorReport {
+            checks: vec![check]
+        }
+        .is_ok());
+    }
+}
@@ -1,30 +1,46 @@
 use std::{
+    future::Future,
     io::{self, Cursor, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
     string::FromUtf8Error,
+    time::Duration,
 };
 
 use bon::Builder;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use reqwest::{
+    header::{CONTENT_RANGE, RANGE},
+    Client, StatusCode,
+};
+use tempdir::TempDir;
 use thiserror::Error;
 use url::{ParseError, Url};
 
 use crate::{
+    cache::{self, DownloadCache, VendorDir},
     config::Config,
     git::GitSource,
     lockfile::RemotePackageSourceUrl,
-    lua_rockspec::{LuaRockspecError, RemoteLuaRockspec, RockSourceSpec},
+    lua_rockspec::{LocalLuaRockspec, LuaRockspecError, RemoteLuaRockspec, RockSourceSpec},
     luarocks,
+    message::Message,
     package::{
         PackageName, PackageReq, PackageSpec, PackageSpecFromPackageReqError, PackageVersion,
         RemotePackageTypeFilterSpec,
     },
     progress::{Progress, ProgressBar},
+    project::{
+        diagnostic::ProjectTomlParseError,
+        project_toml::{LocalProjectTomlValidationError, PartialProjectToml},
+        ProjectRoot, PROJECT_TOML,
+    },
     remote_package_db::{RemotePackageDB, RemotePackageDBError, SearchError},
     remote_package_source::RemotePackageSource,
     rockspec::Rockspec,
 };
 
+use super::{signature::verify_signature, FetchSrc, FetchSrcError, RockFile, SignatureError};
+
 /// Builder for a rock downloader.
 pub struct Download<'a> {
     package_req: &'a PackageReq,
@@ -60,10 +76,10 @@ impl<'a> Download<'a> {
     /// Download the package's Rockspec.
     pub async fn download_rockspec(self) -> Result<DownloadedRockspec, SearchAndDownloadError> {
         match self.package_db {
-            Some(db) => download_rockspec(self.package_req, db, self.progress).await,
+            Some(db) => download_rockspec(self.package_req, db, self.config, self.progress).await,
             None => {
                 let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_rockspec(self.package_req, &db, self.progress).await
+                download_rockspec(self.package_req, &db, self.config, self.progress).await
             }
         }
     }
@@ -76,13 +92,25 @@ impl<'a> Download<'a> {
     ) -> Result<DownloadedPackedRock, SearchAndDownloadError> {
         match self.package_db {
             Some(db) => {
-                download_src_rock_to_file(self.package_req, destination_dir, db, self.progress)
-                    .await
+                download_src_rock_to_file(
+                    self.package_req,
+                    destination_dir,
+                    db,
+                    self.config,
+                    self.progress,
+                )
+                .await
             }
             None => {
                 let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_src_rock_to_file(self.package_req, destination_dir, &db, self.progress)
-                    .await
+                download_src_rock_to_file(
+                    self.package_req,
+                    destination_dir,
+                    &db,
+                    self.config,
+                    self.progress,
+                )
+                .await
             }
         }
     }
@@ -92,10 +120,13 @@ impl<'a> Download<'a> {
         self,
     ) -> Result<DownloadedPackedRockBytes, SearchAndDownloadError> {
         match self.package_db {
-            Some(db) => search_and_download_src_rock(self.package_req, db, self.progress).await,
+            Some(db) => {
+                search_and_download_src_rock(self.package_req, db, self.config, self.progress).await
+            }
             None => {
                 let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                search_and_download_src_rock(self.package_req, &db, self.progress).await
+                search_and_download_src_rock(self.package_req, &db, self.config, self.progress)
+                    .await
             }
         }
     }
@@ -103,13 +134,26 @@ impl<'a> Download<'a> {
     pub(crate) async fn download_remote_rock(
         self,
     ) -> Result<RemoteRockDownload, SearchAndDownloadError> {
-        match self.package_db {
-            Some(db) => download_remote_rock(self.package_req, db, self.progress).await,
+        Message::DownloadStarted {
+            package: self.package_req.to_string(),
+        }
+        .emit(self.config);
+        let download = match self.package_db {
+            Some(db) => {
+                download_remote_rock(self.package_req, db, self.config, self.progress).await?
+            }
             None => {
                 let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_remote_rock(self.package_req, &db, self.progress).await
+                download_remote_rock(self.package_req, &db, self.config, self.progress).await?
             }
-        }
+        };
+        let rockspec = download.rockspec();
+        Message::download_finished(&PackageSpec::new(
+            rockspec.package().clone(),
+            rockspec.version().clone(),
+        ))
+        .emit(self.config);
+        Ok(download)
     }
 }
 
@@ -150,6 +194,19 @@ pub(crate) enum RemoteRockDownload {
     },
 }
 
+/// A rock that has already been read or fetched to determine the package it provides,
+/// so that it isn't read or fetched again when it is installed.
+#[derive(Clone, Debug)]
+pub struct PrefetchedRock(pub(crate) RemoteRockDownload);
+
+impl PrefetchedRock {
+    /// The package that the rock provides.
+    pub fn package(&self) -> PackageSpec {
+        let rockspec = self.0.rockspec();
+        PackageSpec::new(rockspec.package().clone(), rockspec.version().clone())
+    }
+}
+
 impl RemoteRockDownload {
     pub fn rockspec(&self) -> &RemoteLuaRockspec {
         &self.rockspec_download().rockspec
@@ -166,53 +223,179 @@ impl RemoteRockDownload {
         }
     }
     // Instead of downloading a rockspec, generate one from a `PackageReq` and a `RockSourceSpec`.
-    pub(crate) fn from_package_req_and_source_spec(
+    // Rock files (rockspecs, source rocks and binary rocks) are read as-is.
+    // The source is fetched, so that we can pick up the dependencies declared
+    // in its `lux.toml` or rockspec, and so that git sources are pinned to a commit.
+    pub(crate) async fn from_package_req_and_source_spec(
         package_req: PackageReq,
         source_spec: RockSourceSpec,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<Self, SearchAndDownloadError> {
+        if let Some(rock_file) = RockFile::from_source_spec(source_spec.clone()) {
+            return rock_file.download(config, progress).await;
+        }
+        let package_spec: PackageSpec = package_req.try_into()?;
+        if let RockSourceSpec::Git(GitSource {
+            url,
+            checkout_ref: None,
+        }) = &source_spec
+        {
+            return Err(SearchAndDownloadError::MissingCheckoutRef(url.to_string()));
+        }
+        let rockspec =
+            RemoteLuaRockspec::from_package_and_source_spec(package_spec.clone(), source_spec);
+        let temp_dir = TempDir::new(&package_spec.name().to_string())?;
+        let source_metadata = FetchSrc::new(temp_dir.path(), &rockspec, config, progress)
+            .fetch_internal()
+            .await?;
+        let source_spec = match (
+            rockspec.source().current_platform().source_spec.clone(),
+            &source_metadata.source_url,
+        ) {
+            (
+                RockSourceSpec::Git(GitSource { url, .. }),
+                RemotePackageSourceUrl::Git { checkout_ref, .. },
+            ) => RockSourceSpec::Git(GitSource {
+                url,
+                checkout_ref: Some(checkout_ref.clone()),
+            }),
+            (source_spec, _) => source_spec,
+        };
+        Self::from_fetched_source(
+            package_spec,
+            source_spec,
+            source_metadata.source_url,
+            temp_dir.path(),
+        )
+        .await
+    }
+
+    /// Generate a rockspec for a package from its fetched source,
+    /// with the dependencies declared in the source's `lux.toml` or rockspec.
+    /// The `source_spec` should be pinned to the revision that was fetched.
+    pub(crate) async fn from_fetched_source(
+        package_spec: PackageSpec,
+        source_spec: RockSourceSpec,
+        source_url: RemotePackageSourceUrl,
+        source_dir: &Path,
     ) -> Result<Self, SearchAndDownloadError> {
-        let package_spec = package_req.try_into()?;
-        let source_url = Some(match &source_spec {
-            RockSourceSpec::Git(GitSource { url, checkout_ref }) => RemotePackageSourceUrl::Git {
-                url: url.to_string(),
-                checkout_ref: checkout_ref
-                    .clone()
-                    .ok_or(SearchAndDownloadError::MissingCheckoutRef(url.to_string()))?,
-            },
-            RockSourceSpec::File(path) => RemotePackageSourceUrl::File { path: path.clone() },
-            RockSourceSpec::Url(url) => RemotePackageSourceUrl::Url { url: url.clone() },
-        });
         let rockspec = RemoteLuaRockspec::from_package_and_source_spec(package_spec, source_spec);
+        let rockspec = with_source_dependencies(rockspec, source_dir).await?;
         let rockspec_content = rockspec
             .to_lua_remote_rockspec_string()
             .expect("the infallible happened");
         let rockspec_download = DownloadedRockspec {
             rockspec,
-            source_url,
+            source_url: Some(source_url),
             source: RemotePackageSource::RockspecContent(rockspec_content),
         };
         Ok(Self::RockspecOnly { rockspec_download })
     }
 }
 
+/// Look for a `lux.toml` or a rockspec in the root of a fetched source directory,
+/// and add its dependencies to the generated `rockspec`.
+async fn with_source_dependencies(
+    rockspec: RemoteLuaRockspec,
+    source_dir: &Path,
+) -> Result<RemoteLuaRockspec, SearchAndDownloadError> {
+    fn add_dependencies<R: Rockspec>(rockspec: RemoteLuaRockspec, source: &R) -> RemoteLuaRockspec {
+        rockspec.with_dependencies(
+            source.lua().clone(),
+            source.dependencies().current_platform().clone(),
+            source.build_dependencies().current_platform().clone(),
+        )
+    }
+    let project_toml_path = source_dir.join(PROJECT_TOML);
+    if project_toml_path.is_file() {
+        let toml_content = tokio::fs::read_to_string(project_toml_path).await?;
+        let project_toml =
+            PartialProjectToml::new(&toml_content, ProjectRoot::new())?.into_local()?;
+        return Ok(add_dependencies(rockspec, &project_toml));
+    }
+    if let Some(path) = find_source_rockspec(source_dir)? {
+        let rockspec_content = tokio::fs::read_to_string(path).await?;
+        let source_rockspec = LocalLuaRockspec::new(&rockspec_content, ProjectRoot::new())?;
+        return Ok(add_dependencies(rockspec, &source_rockspec));
+    }
+    Ok(rockspec)
+}
+
+/// Find the rockspec in the root of a fetched source directory.
+/// If there are several, e.g. one for each release, the one with the highest version is picked,
+/// so that the choice doesn't depend on the order in which the directory is read.
+pub(crate) fn find_source_rockspec(source_dir: &Path) -> io::Result<Option<PathBuf>> {
+    fn version(path: &Path) -> Option<PackageVersion> {
+        let stem = path.file_stem()?.to_string_lossy();
+        let mut parts = stem.rsplitn(3, '-');
+        let specrev = parts.next()?;
+        let version = parts.next()?;
+        PackageVersion::parse(&format!("{version}-{specrev}")).ok()
+    }
+    Ok(std::fs::read_dir(source_dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rockspec"))
+        .max_by_key(|path| (version(path), path.clone())))
+}
+
 #[derive(Error, Debug)]
 pub enum DownloadRockspecError {
     #[error("failed to download rockspec: {0}")]
     Request(#[from] reqwest::Error),
     #[error("failed to convert rockspec response: {0}")]
     ResponseConversion(#[from] FromUtf8Error),
+    #[error("invalid rockspec URL: {0}")]
+    Url(#[from] ParseError),
+    #[error("failed to read vendored rockspec: {0}")]
+    Vendor(#[from] io::Error),
+    #[error("failed to read rockspec {0}: {1}")]
+    ReadLocal(PathBuf, io::Error),
+    #[error("cannot download {0} in offline mode")]
+    Offline(Url),
     #[error("error initialising remote package DB: {0}")]
     RemotePackageDB(#[from] RemotePackageDBError),
     #[error(transparent)]
     DownloadSrcRock(#[from] DownloadSrcRockError),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+}
+
+/// Download the rockspec at `url`, unless it has been vendored.
+#[tracing::instrument(skip_all, fields(url = %url))]
+pub(crate) async fn download_rockspec_bytes(
+    url: &Url,
+    config: &Config,
+) -> Result<Bytes, DownloadRockspecError> {
+    if let Some(vendor_dir) = config.vendor_dir() {
+        if let Some(bytes) = VendorDir::new(vendor_dir.clone()).get(url, None).await? {
+            return Ok(bytes);
+        }
+    }
+    if let Ok(path) = url.to_file_path() {
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|err| DownloadRockspecError::ReadLocal(path, err))?;
+        verify_signature(url, &bytes, config).await?;
+        return Ok(bytes.into());
+    }
+    if config.offline() {
+        return Err(DownloadRockspecError::Offline(url.clone()));
+    }
+    let bytes = download_bytes(url, config, &Progress::NoProgress).await?;
+    verify_signature(url, &bytes, config).await?;
+    Ok(bytes)
 }
 
 /// Find and download a rockspec for a given package requirement
 async fn download_rockspec(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedRockspec, SearchAndDownloadError> {
-    let rockspec = match download_remote_rock(package_req, package_db, progress).await? {
+    let rockspec = match download_remote_rock(package_req, package_db, config, progress).await? {
         RemoteRockDownload::RockspecOnly {
             rockspec_download: rockspec,
         } => rockspec,
@@ -228,25 +411,23 @@ async fn download_rockspec(
     Ok(rockspec)
 }
 
+#[tracing::instrument(skip_all, fields(package = %package_req))]
 async fn download_remote_rock(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<RemoteRockDownload, SearchAndDownloadError> {
     let remote_package = package_db.find(package_req, None, progress)?;
+    tracing::debug!(package = %remote_package.package, "found package");
     progress.map(|p| p.set_message(format!("📥 Downloading rockspec for {package_req}")));
     match &remote_package.source {
         RemotePackageSource::LuarocksRockspec(url) => {
             let package = &remote_package.package;
             let rockspec_name = format!("{}-{}.rockspec", package.name(), package.version());
-            let bytes = reqwest::get(format!("{}/{}", &url, rockspec_name))
-                .await
-                .map_err(DownloadRockspecError::Request)?
-                .error_for_status()
-                .map_err(DownloadRockspecError::Request)?
-                .bytes()
-                .await
-                .map_err(DownloadRockspecError::Request)?;
+            let rockspec_url = Url::parse(&format!("{}/{}", &url, rockspec_name))
+                .map_err(DownloadRockspecError::Url)?;
+            let bytes = download_rockspec_bytes(&rockspec_url, config).await?;
             let content = String::from_utf8(bytes.into())?;
             let rockspec = DownloadedRockspec {
                 rockspec: RemoteLuaRockspec::new(&content)?,
@@ -275,7 +456,7 @@ async fn download_remote_rock(
             } else {
                 url
             };
-            let rock = download_binary_rock(&remote_package.package, url, progress).await?;
+            let rock = download_binary_rock(&remote_package.package, url, config, progress).await?;
             let rockspec = DownloadedRockspec {
                 rockspec: unpack_rockspec(&rock).await?,
                 source: remote_package.source,
@@ -294,7 +475,7 @@ async fn download_remote_rock(
             } else {
                 url.clone()
             };
-            let rock = download_src_rock(&remote_package.package, &url, progress).await?;
+            let rock = download_src_rock(&remote_package.package, &url, config, progress).await?;
             let rockspec = DownloadedRockspec {
                 rockspec: unpack_rockspec(&rock).await?,
                 source: remote_package.source,
@@ -328,23 +509,27 @@ pub enum SearchAndDownloadError {
     Rockspec(#[from] LuaRockspecError),
     #[error("error initialising remote package DB: {0}")]
     RemotePackageDB(#[from] RemotePackageDBError),
-    #[error("failed to read packed rock {0}:\n{1}")]
-    ZipRead(String, zip::result::ZipError),
-    #[error("failed to extract packed rock {0}:\n{1}")]
-    ZipExtract(String, zip::result::ZipError),
-    #[error("{0} not found in the packed rock.")]
-    RockspecNotFoundInPackedRock(String),
+    #[error(transparent)]
+    ReadPackedRockspec(#[from] ReadPackedRockspecError),
     #[error(transparent)]
     PackageSpecFromPackageReq(#[from] PackageSpecFromPackageReqError),
     #[error("git source {0} without a revision or tag.")]
     MissingCheckoutRef(String),
+    #[error("failed to fetch source:\n{0}")]
+    FetchSrc(#[from] FetchSrcError),
+    #[error("error parsing lux.toml in source:\n{0}")]
+    ProjectToml(#[from] ProjectTomlParseError),
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
     #[error("cannot download from a local rock source.")]
     LocalSource,
 }
 
+#[tracing::instrument(skip_all, fields(package = %package_req))]
 async fn search_and_download_src_rock(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, SearchAndDownloadError> {
     let filter = Some(RemotePackageTypeFilterSpec {
@@ -356,6 +541,7 @@ async fn search_and_download_src_rock(
     Ok(download_src_rock(
         &remote_package.package,
         unsafe { &remote_package.source.url() },
+        config,
         progress,
     )
     .await?)
@@ -365,16 +551,25 @@ async fn search_and_download_src_rock(
 pub enum DownloadSrcRockError {
     #[error("failed to download source rock: {0}")]
     Request(#[from] reqwest::Error),
+    #[error("failed to access the download cache: {0}")]
+    Cache(#[from] io::Error),
     #[error("failed to parse source rock URL: {0}")]
     Parse(#[from] ParseError),
+    #[error("cannot download {0} in offline mode")]
+    Offline(Url),
+    #[error("failed to read rock {0}: {1}")]
+    ReadLocal(PathBuf, io::Error),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
 }
 
 pub(crate) async fn download_src_rock(
     package: &PackageSpec,
     server_url: &Url,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, DownloadSrcRockError> {
-    ArchiveDownload::new(package, server_url, "src.rock", progress)
+    ArchiveDownload::new(package, server_url, "src.rock", config, progress)
         .download()
         .await
 }
@@ -382,10 +577,11 @@ pub(crate) async fn download_src_rock(
 pub(crate) async fn download_binary_rock(
     package: &PackageSpec,
     server_url: &Url,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, DownloadSrcRockError> {
     let ext = format!("{}.rock", luarocks::current_platform_luarocks_identifier());
-    ArchiveDownload::new(package, server_url, &ext, progress)
+    ArchiveDownload::new(package, server_url, &ext, config, progress)
         .fallback_ext("all.rock")
         .download()
         .await
@@ -395,11 +591,12 @@ async fn download_src_rock_to_file(
     package_req: &PackageReq,
     destination_dir: Option<PathBuf>,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRock, SearchAndDownloadError> {
     progress.map(|p| p.set_message(format!("📥 Downloading {package_req}")));
 
-    let rock = search_and_download_src_rock(package_req, package_db, progress).await?;
+    let rock = search_and_download_src_rock(package_req, package_db, config, progress).await?;
     let full_rock_name = mk_packed_rock_name(&rock.name, &rock.version, "src.rock");
     tokio::fs::write(
         destination_dir
@@ -416,6 +613,165 @@ async fn download_src_rock_to_file(
     })
 }
 
+/// The delay before the first retry of a failed download, which doubles with each retry.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The maximum delay between retries of a failed download.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Download `url`, showing the download progress if its length is known.
+///
+/// Downloads that fail because of the connection or an unavailable server are retried,
+/// with exponential backoff, up to the configured number of retries.
+/// Interrupted downloads are resumed with a range request, if the server supports it.
+#[tracing::instrument(skip_all, fields(url = %url))]
+pub(crate) async fn download_bytes(
+    url: &Url,
+    config: &Config,
+    progress: &Progress<ProgressBar>,
+) -> Result<Bytes, reqwest::Error> {
+    let client = config.http_client(url)?;
+    let mut bytes = BytesMut::new();
+    let mut retry = 0;
+    let result = loop {
+        // The bytes are kept between attempts, so that the download can be resumed.
+        match download_attempt(url, &client, config, &mut bytes, progress).await {
+            Err(err) if retry < config.retries() && is_transient(&err) => {
+                backoff(url, &err, &mut retry, config, progress).await
+            }
+            result => break result,
+        }
+    };
+    progress.map(|p| p.clear_length());
+    result.map(|()| bytes.freeze())
+}
+
+/// Run the request `attempt` for `url`, retrying transient failures like [`download_bytes`].
+pub(crate) async fn retry_transient<T, F, Fut>(
+    url: &Url,
+    config: &Config,
+    progress: &Progress<ProgressBar>,
+    mut attempt: F,
+) -> Result<T, reqwest::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, reqwest::Error>>,
+{
+    let mut retry = 0;
+    loop {
+        match attempt().await {
+            Err(err) if retry < config.retries() && is_transient(&err) => {
+                backoff(url, &err, &mut retry, config, progress).await
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait before the next retry of a request for `url` that failed with `err`.
+async fn backoff(
+    url: &Url,
+    err: &reqwest::Error,
+    retry: &mut usize,
+    config: &Config,
+    progress: &Progress<ProgressBar>,
+) {
+    let delay = retry_delay(*retry);
+    *retry += 1;
+    tracing::debug!(error = %err, retry = *retry, ?delay, "retrying download");
+    progress.map(|p| {
+        p.set_message(format!(
+            "🔁 Retrying download of {url} in {}s ({retry}/{})",
+            delay.as_secs_f32(),
+            config.retries()
+        ))
+    });
+    tokio::time::sleep(delay).await;
+}
+
+/// Download `url` into `bytes`, resuming from the bytes that were already downloaded.
+async fn download_attempt(
+    url: &Url,
+    client: &Client,
+    config: &Config,
+    bytes: &mut BytesMut,
+    progress: &Progress<ProgressBar>,
+) -> Result<(), reqwest::Error> {
+    let mut response = loop {
+        let offset = bytes.len();
+        let mut request = config.authenticate(url, client.get(url.clone()));
+        if offset > 0 {
+            tracing::debug!(offset, "resuming download");
+            request = request.header(RANGE, format!("bytes={offset}-"));
+        }
+        let response = request.send().await?.error_for_status()?;
+        if offset == 0 {
+            break response;
+        }
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT
+            && response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(content_range_start)
+                == Some(offset);
+        if resumed {
+            break response;
+        }
+        // The server doesn't support resuming downloads, so we start over.
+        tracing::debug!("the server doesn't support resuming the download, starting over");
+        bytes.clear();
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            break response;
+        }
+    };
+    let offset = bytes.len() as u64;
+    if let Some(length) = response.content_length() {
+        progress.map(|p| {
+            p.set_length(offset + length);
+            p.set_position(offset);
+        });
+    }
+    while let Some(chunk) = response.chunk().await? {
+        progress.map(|p| p.inc(chunk.len() as u64));
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(())
+}
+
+/// Whether a download may succeed if it is retried.
+fn is_transient(err: &reqwest::Error) -> bool {
+    match err.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        // A body that ends before its `Content-Length` is reported as a decode error.
+        None => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.is_request()
+                || err.is_body()
+                || err.is_decode()
+        }
+    }
+}
+
+fn retry_delay(retry: usize) -> Duration {
+    let factor = 2u32.saturating_pow(retry.try_into().unwrap_or(u32::MAX));
+    INITIAL_RETRY_DELAY
+        .saturating_mul(factor)
+        .min(MAX_RETRY_DELAY)
+}
+
+/// The first byte of a `Content-Range: bytes <start>-<end>/<length>` header.
+fn content_range_start(content_range: &str) -> Option<usize> {
+    content_range
+        .strip_prefix("bytes ")?
+        .split_once('-')?
+        .0
+        .trim()
+        .parse()
+        .ok()
+}
+
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
 struct ArchiveDownload<'a> {
@@ -428,6 +784,9 @@ struct ArchiveDownload<'a> {
     #[builder(start_fn)]
     ext: &'a str,
 
+    #[builder(start_fn)]
+    config: &'a Config,
+
     #[builder(start_fn)]
     progress: &'a Progress<ProgressBar>,
 
@@ -454,24 +813,38 @@ where
         });
         let full_rock_name = mk_packed_rock_name(package.name(), package.version(), ext);
         let url = server_url.join(&full_rock_name)?;
-        let response = reqwest::get(url.clone()).await?;
-        let bytes = if response.status().is_success() {
-            response.bytes().await
-        } else {
-            match args.fallback_ext {
-                Some(ext) => {
+        if server_url.scheme() == "file" {
+            return read_local_rock(package, server_url, ext, args.fallback_ext, args.config).await;
+        }
+        if let Some(bytes) = cache::get_cached(&url, None, args.config).await? {
+            tracing::debug!(%url, "using the cached rock");
+            return Ok(DownloadedPackedRockBytes {
+                name: package.name().clone(),
+                version: package.version().clone(),
+                bytes,
+                file_name: full_rock_name,
+                url,
+            });
+        }
+        if args.config.offline() {
+            return Err(DownloadSrcRockError::Offline(url));
+        }
+        let cache = DownloadCache::new(args.config);
+        let (bytes, downloaded_url) = match download_bytes(&url, args.config, progress).await {
+            Ok(bytes) => (bytes, url.clone()),
+            Err(err) => match args.fallback_ext {
+                Some(ext) if err.status().is_some() => {
                     let full_rock_name =
                         mk_packed_rock_name(package.name(), package.version(), ext);
                     let url = server_url.join(&full_rock_name)?;
-                    reqwest::get(url.clone())
-                        .await?
-                        .error_for_status()?
-                        .bytes()
-                        .await
+                    (download_bytes(&url, args.config, progress).await?, url)
                 }
-                None => response.error_for_status()?.bytes().await,
-            }
-        }?;
+                _ => return Err(err.into()),
+            },
+        };
+        // Verify before caching, so that cached rocks can be trusted
+        verify_signature(&downloaded_url, &bytes, args.config).await?;
+        cache.insert(&url, None, &full_rock_name, &bytes).await?;
         Ok(DownloadedPackedRockBytes {
             name: package.name().clone(),
             version: package.version().clone(),
@@ -482,6 +855,38 @@ where
     }
 }
 
+/// Read a rock from a rock server in a local directory, i.e. with a `file://` URL.
+/// Local rocks are not cached, as they can be read as quickly as the cache.
+async fn read_local_rock(
+    package: &PackageSpec,
+    server_url: &Url,
+    ext: &str,
+    fallback_ext: Option<&str>,
+    config: &Config,
+) -> Result<DownloadedPackedRockBytes, DownloadSrcRockError> {
+    let local_rock = |ext: &str| -> Result<(String, Url, PathBuf), ParseError> {
+        let file_name = mk_packed_rock_name(package.name(), package.version(), ext);
+        let url = server_url.join(&file_name)?;
+        let path = url.to_file_path().unwrap_or_else(|()| url.path().into());
+        Ok((file_name, url, path))
+    };
+    let (file_name, url, path) = match (local_rock(ext)?, fallback_ext) {
+        ((_, _, path), Some(fallback_ext)) if !path.is_file() => local_rock(fallback_ext)?,
+        (local_rock, _) => local_rock,
+    };
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|err| DownloadSrcRockError::ReadLocal(path, err))?;
+    verify_signature(&url, &bytes, config).await?;
+    Ok(DownloadedPackedRockBytes {
+        name: package.name().clone(),
+        version: package.version().clone(),
+        bytes: bytes.into(),
+        file_name,
+        url,
+    })
+}
+
 fn mk_packed_rock_name(name: &PackageName, version: &PackageVersion, ext: &str) -> String {
     format!("{name}-{version}.{ext}")
 }
@@ -489,20 +894,154 @@ fn mk_packed_rock_name(name: &PackageName, version: &PackageVersion, ext: &str)
 pub(crate) async fn unpack_rockspec(
     rock: &DownloadedPackedRockBytes,
 ) -> Result<RemoteLuaRockspec, SearchAndDownloadError> {
-    let cursor = Cursor::new(&rock.bytes);
     let rockspec_file_name = format!("{}-{}.rockspec", rock.name, rock.version);
+    let content = read_packed_rockspec(&rock.bytes, &rock.file_name, Some(&rockspec_file_name))?;
+    let rockspec = RemoteLuaRockspec::new(&content)?;
+    Ok(rockspec)
+}
+
+#[derive(Error, Debug)]
+pub enum ReadPackedRockspecError {
+    #[error("failed to read packed rock {0}:\n{1}")]
+    ZipRead(String, zip::result::ZipError),
+    #[error("failed to extract packed rock {0}:\n{1}")]
+    ZipExtract(String, zip::result::ZipError),
+    #[error("{0} not found in the packed rock.")]
+    RockspecNotFound(String),
+    #[error("io operation failed: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Read the rockspec from a packed rock.
+/// If no `rockspec_file_name` is given, the first top-level rockspec is read.
+pub(crate) fn read_packed_rockspec(
+    bytes: &Bytes,
+    file_name: &str,
+    rockspec_file_name: Option<&str>,
+) -> Result<String, ReadPackedRockspecError> {
+    let cursor = Cursor::new(bytes);
     let mut zip = zip::ZipArchive::new(cursor)
-        .map_err(|err| SearchAndDownloadError::ZipRead(rock.file_name.clone(), err))?;
+        .map_err(|err| ReadPackedRockspecError::ZipRead(file_name.to_string(), err))?;
     let rockspec_index = (0..zip.len())
-        .find(|&i| zip.by_index(i).unwrap().name().eq(&rockspec_file_name))
-        .ok_or(SearchAndDownloadError::RockspecNotFoundInPackedRock(
-            rockspec_file_name,
+        .find(|&i| {
+            let entry = zip.by_index(i).unwrap();
+            match rockspec_file_name {
+                Some(rockspec_file_name) => entry.name().eq(rockspec_file_name),
+                None => !entry.name().contains('/') && entry.name().ends_with(".rockspec"),
+            }
+        })
+        .ok_or(ReadPackedRockspecError::RockspecNotFound(
+            rockspec_file_name.unwrap_or("rockspec").to_string(),
         ))?;
     let mut rockspec_file = zip
         .by_index(rockspec_index)
-        .map_err(|err| SearchAndDownloadError::ZipExtract(rock.file_name.clone(), err))?;
+        .map_err(|err| ReadPackedRockspecError::ZipExtract(file_name.to_string(), err))?;
     let mut content = String::new();
     rockspec_file.read_to_string(&mut content)?;
-    let rockspec = RemoteLuaRockspec::new(&content)?;
-    Ok(rockspec)
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{cycle, matchers::request, responders::status_code, Expectation, Server};
+
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    #[test]
+    fn find_source_rockspec_with_highest_version() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        for file_name in [
+            "foo-2.0.0-1.rockspec",
+            "foo-10.0.0-1.rockspec",
+            "foo-1.0.0-1.rockspec",
+        ] {
+            std::fs::write(dir.join(file_name), "").unwrap();
+        }
+        std::fs::create_dir(dir.join("rockspecs")).unwrap();
+        assert_eq!(
+            find_source_rockspec(&dir).unwrap(),
+            Some(dir.join("foo-10.0.0-1.rockspec"))
+        );
+        assert_eq!(find_source_rockspec(&dir.join("rockspecs")).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn retry_unavailable_server() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo.tar.gz"))
+                .times(2)
+                .respond_with(cycle![status_code(503), status_code(200).body("foo")]),
+        );
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let url = Url::parse(&server.url_str("/foo.tar.gz")).unwrap();
+        let bytes = download_bytes(&url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(bytes, "foo");
+
+        let config = ConfigBuilder::from(config)
+            .retries(Some(0))
+            .build()
+            .unwrap();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/bar.tar.gz"))
+                .times(1)
+                .respond_with(status_code(503)),
+        );
+        let url = Url::parse(&server.url_str("/bar.tar.gz")).unwrap();
+        assert!(download_bytes(&url, &config, &Progress::NoProgress)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn resume_interrupted_download() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        // httptest (hyper) refuses to send a body that is shorter than its `Content-Length`,
+        // so the interrupted download is served over a plain TCP socket.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/foo.tar.gz",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in [
+                "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nfoo",
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-5/6\r\nContent-Length: 3\r\n\r\nbar",
+            ] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let len = stream.read(&mut request).await.unwrap();
+                requests.push(String::from_utf8_lossy(&request[..len]).to_lowercase());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let bytes = download_bytes(&url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(bytes, "foobar");
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("range:"));
+        assert!(requests[1].contains("range: bytes=3-\r\n"));
+    }
+
+    #[test]
+    fn parse_content_range() {
+        assert_eq!(content_range_start("bytes 1024-2047/2048"), Some(1024));
+        assert_eq!(content_range_start("bytes */2048"), None);
+        assert_eq!(retry_delay(0), INITIAL_RETRY_DELAY);
+        assert_eq!(retry_delay(1), INITIAL_RETRY_DELAY * 2);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
 }
@@ -84,32 +84,8 @@ pub enum ExecError {
 }
 
 async fn exec(run: Exec<'_>) -> Result<(), ExecError> {
-    let lua_version = run
-        .project
-        .map(|project| project.lua_version(run.config))
-        .transpose()?
-        .unwrap_or(LuaVersion::from(run.config)?.clone());
-
-    let user_tree = run.config.user_tree(lua_version)?;
-    let mut paths = Paths::new(&user_tree)?;
-
-    if let Some(project) = run.project {
-        paths.prepend(&Paths::new(&project.tree(run.config)?)?);
-    }
-
-    let lua_init = if run.disable_loader.unwrap_or(false) {
-        None
-    } else if user_tree.version().lux_lib_dir().is_none() {
-        eprintln!(
-            "⚠️ WARNING: lux-lua library not found.
-    Cannot use the `lux.loader`.
-    To suppress this warning, set the `--no-loader` option.
-                    "
-        );
-        None
-    } else {
-        Some(paths.init())
-    };
+    let (paths, lua_init) =
+        exec_paths(run.project, run.config, run.disable_loader.unwrap_or(false))?;
 
     let status = match Command::new(run.command)
         .args(run.args)
@@ -136,6 +112,42 @@ async fn exec(run: Exec<'_>) -> Result<(), ExecError> {
     }
 }
 
+/// The paths of the user tree and, if present, the project tree, in which commands are executed,
+/// along with the `LUA_INIT` for the Lux loader.
+pub(super) fn exec_paths(
+    project: Option<&Project>,
+    config: &Config,
+    disable_loader: bool,
+) -> Result<(Paths, Option<String>), ExecError> {
+    let lua_version = project
+        .map(|project| project.lua_version(config))
+        .transpose()?
+        .unwrap_or(LuaVersion::from(config)?.clone());
+
+    let user_tree = config.user_tree(lua_version)?;
+    let mut paths = Paths::new(&user_tree)?;
+
+    if let Some(project) = project {
+        paths.prepend(&Paths::new(&project.tree(config)?)?);
+    }
+
+    let lua_init = if disable_loader {
+        None
+    } else if user_tree.version().lux_lib_dir().is_none() {
+        eprintln!(
+            "⚠️ WARNING: lux-lua library not found.
+    Cannot use the `lux.loader`.
+    To suppress this warning, set the `--no-loader` option.
+                    "
+        );
+        None
+    } else {
+        Some(paths.init())
+    };
+
+    Ok((paths, lua_init))
+}
+
 #[derive(Error, Debug)]
 #[error(transparent)]
 pub enum InstallCmdError {
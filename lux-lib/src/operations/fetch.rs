@@ -1,6 +1,5 @@
 use bon::Builder;
 use git2::build::RepoBuilder;
-use git2::FetchOptions;
 use git_url_parse::GitUrlParseError;
 use ssri::Integrity;
 use std::fs::File;
@@ -12,12 +11,13 @@ use std::path::PathBuf;
 use thiserror::Error;
 
 use crate::build::utils::recursive_copy_dir;
+use crate::cache::{self, DownloadCache, VendorDir};
 use crate::config::Config;
 use crate::git::GitSource;
 use crate::hash::HasIntegrity;
 use crate::lockfile::RemotePackageSourceUrl;
 use crate::lua_rockspec::RockSourceSpec;
-use crate::operations;
+use crate::operations::{self, download::download_bytes};
 use crate::package::PackageSpec;
 use crate::progress::Progress;
 use crate::progress::ProgressBar;
@@ -40,6 +40,9 @@ pub struct FetchSrc<'a, R: Rockspec> {
     #[builder(start_fn)]
     progress: &'a Progress<ProgressBar>,
     source_url: Option<RemotePackageSourceUrl>,
+    /// The integrity the source is expected to have, e.g. from a lockfile.
+    #[builder(setters(vis = "pub(crate)"))]
+    expected_hash: Option<Integrity>,
 }
 
 #[derive(Debug)]
@@ -111,6 +114,8 @@ pub enum FetchSrcError {
     Unpack(#[from] UnpackError),
     #[error(transparent)]
     FetchSrcRock(#[from] FetchSrcRockError),
+    #[error("cannot fetch {0} in offline mode")]
+    Offline(String),
 }
 
 /// A rocks package source fetcher, providing fine-grained control
@@ -145,6 +150,7 @@ pub enum FetchSrcRockError {
     Io(#[from] io::Error),
 }
 
+#[tracing::instrument(skip_all, fields(package = %fetch.rockspec.package(), version = %fetch.rockspec.version()))]
 async fn do_fetch_src<R: Rockspec>(
     fetch: &FetchSrc<'_, R>,
 ) -> Result<RemotePackageSourceMetadata, FetchSrcError> {
@@ -167,9 +173,29 @@ async fn do_fetch_src<R: Rockspec>(
     let metadata = match &source_spec {
         RockSourceSpec::Git(git) => {
             let url = git.url.to_string();
+            if let (Some(checkout_ref), Some(vendor_dir)) =
+                (&git.checkout_ref, fetch.config.vendor_dir())
+            {
+                if VendorDir::new(vendor_dir.clone()).restore_git_checkout(
+                    &url,
+                    checkout_ref,
+                    dest_dir,
+                )? {
+                    return Ok(RemotePackageSourceMetadata {
+                        hash: dest_dir.hash()?,
+                        source_url: RemotePackageSourceUrl::Git {
+                            url,
+                            checkout_ref: checkout_ref.clone(),
+                        },
+                    });
+                }
+            }
+            if fetch.config.offline() {
+                return Err(FetchSrcError::Offline(url));
+            }
             progress.map(|p| p.set_message(format!("🦠 Cloning {url}")));
 
-            let mut fetch_options = FetchOptions::new();
+            let mut fetch_options = crate::git::utils::fetch_options();
             fetch_options.update_fetchhead(false);
             if git.checkout_ref.is_none() {
                 fetch_options.depth(1);
@@ -182,7 +208,8 @@ async fn do_fetch_src<R: Rockspec>(
                 Some(checkout_ref) => {
                     let (object, _) = repo.revparse_ext(checkout_ref)?;
                     repo.checkout_tree(&object, None)?;
-                    checkout_ref.clone()
+                    // Pin branches and tags to the commit they resolve to
+                    object.peel_to_commit()?.id().to_string()
                 }
                 None => {
                     let head = repo.head()?;
@@ -201,12 +228,6 @@ async fn do_fetch_src<R: Rockspec>(
         RockSourceSpec::Url(url) => {
             progress.map(|p| p.set_message(format!("📥 Downloading {}", url.to_owned())));
 
-            let response = reqwest::get(url.to_owned())
-                .await?
-                .error_for_status()?
-                .bytes()
-                .await?;
-            let hash = response.hash()?;
             let file_name = url
                 .path_segments()
                 .and_then(|mut segments| segments.next_back())
@@ -218,6 +239,20 @@ async fn do_fetch_src<R: Rockspec>(
                     }
                 })
                 .unwrap_or(url.to_string());
+            let expected_hash = fetch.expected_hash.as_ref();
+            let response = match cache::get_cached(url, expected_hash, fetch.config).await? {
+                Some(bytes) => bytes,
+                None if fetch.config.offline() => {
+                    return Err(FetchSrcError::Offline(url.to_string()))
+                }
+                None => {
+                    let cache = DownloadCache::new(fetch.config);
+                    let bytes = download_bytes(url, fetch.config, progress).await?;
+                    cache.insert(url, expected_hash, &file_name, &bytes).await?;
+                    bytes
+                }
+            };
+            let hash = response.hash()?;
             let cursor = Cursor::new(response);
             let mime_type = infer::get(cursor.get_ref()).map(|file_type| file_type.mime_type());
             operations::unpack::unpack(
@@ -270,6 +305,7 @@ async fn do_fetch_src<R: Rockspec>(
     Ok(metadata)
 }
 
+#[tracing::instrument(skip_all, fields(package = %fetch.package))]
 async fn do_fetch_src_rock(
     fetch: FetchSrcRock<'_>,
 ) -> Result<RemotePackageSourceMetadata, FetchSrcRockError> {
@@ -277,7 +313,8 @@ async fn do_fetch_src_rock(
     let dest_dir = fetch.dest_dir;
     let config = fetch.config;
     let progress = fetch.progress;
-    let src_rock = operations::download_src_rock(package, config.server(), progress).await?;
+    let src_rock =
+        operations::download_src_rock(package, config.server(), config, progress).await?;
     let hash = src_rock.bytes.hash()?;
     let cursor = Cursor::new(src_rock.bytes);
     let mime_type = infer::get(cursor.get_ref()).map(|file_type| file_type.mime_type());
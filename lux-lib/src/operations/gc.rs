@@ -0,0 +1,281 @@
+//! Garbage collection of rocks that are no longer needed.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    config::Config,
+    lockfile::LocalPackage,
+    progress::{MultiProgress, Progress},
+    tree::{Tree, TreeError},
+};
+
+use super::{Remove, RemoveError};
+
+/// Removes the packages in a tree that are not reachable from any entrypoint in its lockfile,
+/// along with rock directories that are not in the lockfile.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Gc<'a> {
+    #[builder(start_fn)]
+    tree: &'a Tree,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Only report what would be removed.
+    #[builder(default)]
+    dry_run: bool,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> GcBuilder<'_, State>
+where
+    State: gc_builder::State + gc_builder::IsComplete,
+{
+    pub async fn gc(self) -> Result<GcReport, GcError> {
+        do_gc(self._build()).await
+    }
+}
+
+impl Tree {
+    /// Remove the packages that are not reachable from any entrypoint.
+    /// See [`Gc`].
+    pub fn gc<'a>(&'a self, config: &'a Config) -> GcBuilder<'a> {
+        Gc::new(self, config)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GcError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Remove(#[from] RemoveError),
+}
+
+/// The rocks removed (or, in a dry run, to be removed) by [`Gc`].
+#[derive(Debug, Default)]
+pub struct GcReport {
+    packages: Vec<LocalPackage>,
+    orphaned_dirs: Vec<PathBuf>,
+    reclaimed: u64,
+}
+
+impl GcReport {
+    /// Packages in the lockfile that are not reachable from any entrypoint.
+    pub fn packages(&self) -> &[LocalPackage] {
+        &self.packages
+    }
+
+    /// Rock directories that are not in the lockfile,
+    /// e.g. left behind by an interrupted installation.
+    pub fn orphaned_dirs(&self) -> &[PathBuf] {
+        &self.orphaned_dirs
+    }
+
+    /// The disk space reclaimed, in bytes.
+    pub fn reclaimed(&self) -> u64 {
+        self.reclaimed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty() && self.orphaned_dirs.is_empty()
+    }
+}
+
+#[tracing::instrument(skip_all, fields(tree = %args.tree.root().display(), dry_run = args.dry_run))]
+async fn do_gc(args: Gc<'_>) -> Result<GcReport, GcError> {
+    let tree = args.tree;
+    let lockfile = tree.lockfile()?;
+    let packages = lockfile
+        .dependency_graph()
+        .orphans()
+        .into_iter()
+        .cloned()
+        .collect_vec();
+
+    let rock_dirs: HashSet<PathBuf> = lockfile
+        .rocks()
+        .values()
+        .map(|package| tree.root_for(package))
+        .collect();
+    let orphaned_dirs = tree
+        .rock_dirs()?
+        .into_iter()
+        .filter(|dir| !rock_dirs.contains(dir))
+        .collect_vec();
+
+    // Executables that are shared with packages that are kept are not removed.
+    let removed_ids = packages.iter().map(LocalPackage::id).collect_vec();
+    let kept_binaries = lockfile
+        .rocks()
+        .values()
+        .filter(|package| !removed_ids.contains(&package.id()))
+        .flat_map(binary_names)
+        .collect::<HashSet<_>>();
+    let mut paths = orphaned_dirs.clone();
+    for package in &packages {
+        let layout = tree.installed_rock_layout(package)?;
+        paths.push(layout.rock_path);
+        if !layout.etc.starts_with(tree.root_for(package)) {
+            paths.push(layout.etc);
+        }
+        for name in binary_names(package) {
+            if !kept_binaries.contains(&name) {
+                paths.push(tree.bin().join(&name));
+                paths.push(tree.unwrapped_bin().join(&name));
+            }
+        }
+    }
+    let reclaimed = paths
+        .iter()
+        .unique()
+        .filter(|path| path.exists())
+        .map(|path| disk_usage(path))
+        .sum();
+
+    if !args.dry_run {
+        for dir in &orphaned_dirs {
+            std::fs::remove_dir_all(dir)?;
+        }
+        if !packages.is_empty() {
+            Remove::new(args.config)
+                .packages(removed_ids)
+                .tree(tree.clone())
+                .progress(args.progress.unwrap_or_else(MultiProgress::new_arc))
+                .remove()
+                .await?;
+        }
+    }
+
+    Ok(GcReport {
+        packages,
+        orphaned_dirs,
+        reclaimed,
+    })
+}
+
+fn binary_names(package: &LocalPackage) -> Vec<String> {
+    package
+        .spec
+        .binaries()
+        .into_iter()
+        .filter_map(|binary| binary.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect_vec()
+}
+
+/// The total size of the files in a directory, or of a single file, in bytes.
+fn disk_usage(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ConfigBuilder, LuaVersion},
+        lockfile::{LocalPackageHashes, LockConstraint},
+        package::PackageSpec,
+        remote_package_source::RemotePackageSource,
+        rockspec::RockBinaries,
+    };
+
+    #[test]
+    fn disk_usage_of_dir() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.join("foo")).unwrap();
+        std::fs::write(dir.join("foo").join("bar.lua"), "return {}").unwrap();
+        std::fs::write(dir.join("baz.lua"), "return 1").unwrap();
+        assert_eq!(disk_usage(&dir), 17);
+        assert_eq!(disk_usage(&dir.join("baz.lua")), 8);
+    }
+
+    fn package(name: &str, binary: &str) -> LocalPackage {
+        let hash: ssri::Integrity = "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+            .parse()
+            .unwrap();
+        let mut binaries = RockBinaries::default();
+        binaries.push(PathBuf::from("bin").join(binary));
+        LocalPackage::from(
+            &PackageSpec::parse(name.into(), "1.0.0-1".into()).unwrap(),
+            LockConstraint::Unconstrained,
+            binaries,
+            RemotePackageSource::Test,
+            None,
+            LocalPackageHashes {
+                rockspec: hash.clone(),
+                source: hash,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn gc_removes_orphans() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(dir.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+
+        let kept = package("kept", "shared");
+        let orphan = package("orphan", "shared");
+        let orphan_only = package("orphan-only", "orphan-only");
+        tree.entrypoint(&kept).unwrap();
+        tree.dependency(&orphan).unwrap();
+        tree.dependency(&orphan_only).unwrap();
+        std::fs::create_dir_all(tree.bin()).unwrap();
+        std::fs::write(tree.bin().join("shared"), "").unwrap();
+        std::fs::write(tree.bin().join("orphan-only"), "").unwrap();
+        let orphaned_dir = tree.root_for(&package("leftover", "leftover"));
+        std::fs::create_dir_all(&orphaned_dir).unwrap();
+        tree.lockfile()
+            .unwrap()
+            .map_then_flush(|lockfile| {
+                lockfile.add_entrypoint(&kept);
+                for package in [&orphan, &orphan_only] {
+                    lockfile.add_entrypoint(package);
+                    lockfile.remove_entrypoint(package);
+                }
+                Ok::<_, io::Error>(())
+            })
+            .unwrap();
+
+        let report = tree.gc(&config).dry_run(true).gc().await.unwrap();
+        assert_eq!(report.packages().len(), 2);
+        assert_eq!(report.orphaned_dirs(), std::slice::from_ref(&orphaned_dir));
+        assert!(orphaned_dir.is_dir());
+
+        Gc::new(&tree, &config).gc().await.unwrap();
+        let lockfile = tree.lockfile().unwrap();
+        assert!(lockfile.get(&kept.id()).is_some());
+        assert!(lockfile.get(&orphan.id()).is_none());
+        assert!(lockfile.get(&orphan_only.id()).is_none());
+        assert!(tree.root_for(&kept).is_dir());
+        assert!(!tree.root_for(&orphan).exists());
+        assert!(!orphaned_dir.exists());
+        // The executable of the package that is kept is not removed
+        assert!(tree.bin().join("shared").is_file());
+        assert!(!tree.bin().join("orphan-only").exists());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{io, str::FromStr};
+
+use git_url_parse::{GitUrl, GitUrlParseError};
+use tempdir::TempDir;
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    git::GitSource,
+    lockfile::RemotePackageSourceUrl,
+    lua_rockspec::{LocalLuaRockspec, LuaRockspecError, RemoteLuaRockspec, RockSourceSpec},
+    package::{PackageName, PackageSpec, PackageVersion},
+    progress::{Progress, ProgressBar},
+    project::{
+        diagnostic::ProjectTomlParseError, project_toml::PartialProjectToml, ProjectRoot,
+        PROJECT_TOML,
+    },
+    rockspec::Rockspec,
+};
+
+use super::{
+    download::find_source_rockspec, FetchSrc, FetchSrcError, PrefetchedRock, RemoteRockDownload,
+    SearchAndDownloadError,
+};
+
+/// A package in a git repository,
+/// specified as `git+<url>[#<rev>]`, e.g. `git+https://github.com/user/repo#v1.0.0`.
+#[derive(Debug, Clone)]
+pub struct GitPackage {
+    url: GitUrl,
+    checkout_ref: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum GitPackageParseError {
+    #[error("expected a git+<url>[#<rev>] URL, but got {0}")]
+    MissingPrefix(String),
+    #[error(transparent)]
+    Url(#[from] GitUrlParseError),
+}
+
+#[derive(Error, Debug)]
+pub enum GitPackageError {
+    #[error("failed to fetch {url}:\n{err}")]
+    FetchSrc { url: String, err: FetchSrcError },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("error parsing lux.toml in {url}:\n{err}")]
+    ProjectToml {
+        url: String,
+        err: ProjectTomlParseError,
+    },
+    #[error(transparent)]
+    Rockspec(#[from] LuaRockspecError),
+    #[error("could not find a lux.toml or rockspec in {0}")]
+    NotFound(String),
+    #[error("error generating a rockspec for {url}:\n{err}")]
+    GenerateRockspec {
+        url: String,
+        err: SearchAndDownloadError,
+    },
+}
+
+impl FromStr for GitPackage {
+    type Err = GitPackageParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = s
+            .strip_prefix("git+")
+            .ok_or_else(|| GitPackageParseError::MissingPrefix(s.to_string()))?;
+        let (url, checkout_ref) = match url.split_once('#') {
+            Some((url, checkout_ref)) => (url, Some(checkout_ref.to_string())),
+            None => (url, None),
+        };
+        Ok(Self {
+            url: url.parse()?,
+            checkout_ref,
+        })
+    }
+}
+
+impl GitPackage {
+    pub fn url(&self) -> &GitUrl {
+        &self.url
+    }
+
+    pub fn checkout_ref(&self) -> Option<&String> {
+        self.checkout_ref.as_ref()
+    }
+
+    /// Fetch the repository and look for a `lux.toml` or a rockspec in its root,
+    /// to determine the package it provides.
+    ///
+    /// The returned source is pinned to the commit that the revision
+    /// (or the default branch, if none was given) resolves to.
+    /// The returned rock can be passed on to the installer, so that the repository
+    /// isn't fetched again.
+    pub async fn resolve(
+        &self,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<(RockSourceSpec, PrefetchedRock), GitPackageError> {
+        let url = self.url.to_string();
+        let placeholder = PackageSpec::new(PackageName::new(self.url.name.clone()), dev_version());
+        let rockspec = RemoteLuaRockspec::from_package_and_source_spec(
+            placeholder,
+            RockSourceSpec::Git(GitSource {
+                url: self.url.clone(),
+                checkout_ref: self.checkout_ref.clone(),
+            }),
+        );
+        let temp_dir = TempDir::new("lux-git-package")?;
+        let source_metadata = FetchSrc::new(temp_dir.path(), &rockspec, config, progress)
+            .fetch_internal()
+            .await
+            .map_err(|err| GitPackageError::FetchSrc {
+                url: url.clone(),
+                err,
+            })?;
+        let checkout_ref = match &source_metadata.source_url {
+            RemotePackageSourceUrl::Git { checkout_ref, .. } => Some(checkout_ref.clone()),
+            _ => self.checkout_ref.clone(),
+        };
+
+        let project_toml_path = temp_dir.path().join(PROJECT_TOML);
+        let package = if project_toml_path.is_file() {
+            let content = tokio::fs::read_to_string(project_toml_path).await?;
+            let project_toml =
+                PartialProjectToml::new(&content, ProjectRoot::new()).map_err(|err| {
+                    GitPackageError::ProjectToml {
+                        url: url.clone(),
+                        err,
+                    }
+                })?;
+            // The version may be generated from git tags, which aren't fetched
+            let version = project_toml.version().unwrap_or_else(|_| dev_version());
+            PackageSpec::new(project_toml.package().clone(), version)
+        } else {
+            let rockspec_path = find_source_rockspec(temp_dir.path())?
+                .ok_or_else(|| GitPackageError::NotFound(url.clone()))?;
+            let content = tokio::fs::read_to_string(rockspec_path).await?;
+            let rockspec = LocalLuaRockspec::new(&content, ProjectRoot::new())?;
+            PackageSpec::new(rockspec.package().clone(), rockspec.version().clone())
+        };
+        let source_spec = RockSourceSpec::Git(GitSource {
+            url: self.url.clone(),
+            checkout_ref,
+        });
+        let download = RemoteRockDownload::from_fetched_source(
+            package,
+            source_spec.clone(),
+            source_metadata.source_url,
+            temp_dir.path(),
+        )
+        .await
+        .map_err(|err| GitPackageError::GenerateRockspec { url, err })?;
+        Ok((source_spec, PrefetchedRock(download)))
+    }
+}
+
+fn dev_version() -> PackageVersion {
+    PackageVersion::parse("scm-1").expect("scm-1 is a valid version")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_git_package() {
+        let package: GitPackage = "git+https://github.com/user/repo#v1.0.0".parse().unwrap();
+        assert_eq!(package.url().name, "repo");
+        assert_eq!(package.checkout_ref(), Some(&"v1.0.0".to_string()));
+        let package: GitPackage = "git+https://github.com/user/repo".parse().unwrap();
+        assert_eq!(package.checkout_ref(), None);
+        assert!(matches!(
+            "https://github.com/user/repo".parse::<GitPackage>(),
+            Err(GitPackageParseError::MissingPrefix(_))
+        ));
+    }
+}
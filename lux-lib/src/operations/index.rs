@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use bytes::Bytes;
+use itertools::Itertools;
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    config::LuaVersion,
+    lua_rockspec::RemoteLuaRockspec,
+    manifest::{parse_manifest_entry_file_name, ManifestRepository},
+    package::PackageName,
+    rockspec::LuaVersionCompatibility,
+};
+
+use super::read_packed_rockspec;
+
+/// Generate luarocks-compatible manifests for a directory of `.rockspec` and `.rock` files,
+/// so that it can be served as a rock server, like `luarocks-admin make-manifest`.
+///
+/// Writes a `manifest` with all packages, and a `manifest-<lua version>`
+/// with the packages that support each Lua version, along with zipped variants.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Index {
+    #[builder(start_fn)]
+    dir: PathBuf,
+}
+
+impl<State> IndexBuilder<State>
+where
+    State: index_builder::State + index_builder::IsComplete,
+{
+    pub fn index(self) -> Result<IndexReport, IndexError> {
+        do_index(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("failed to index {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("failed to zip {0}: {1}")]
+    Zip(PathBuf, zip::result::ZipError),
+}
+
+#[derive(Debug)]
+pub struct IndexReport {
+    packages: usize,
+    manifests: Vec<PathBuf>,
+    skipped: Vec<String>,
+}
+
+impl IndexReport {
+    /// The number of packages in the manifests.
+    pub fn packages(&self) -> usize {
+        self.packages
+    }
+
+    /// The manifest files that were written.
+    pub fn manifests(&self) -> &[PathBuf] {
+        &self.manifests
+    }
+
+    /// Rockspecs and rocks with names that don't follow the `<name>-<version>-<revision>` format,
+    /// which were left out of the manifests.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+}
+
+fn do_index(args: Index) -> Result<IndexReport, IndexError> {
+    let dir = args.dir;
+    let io_err = |path: &Path| {
+        let path = path.to_path_buf();
+        move |err| IndexError::Io(path, err)
+    };
+
+    let mut repository = ManifestRepository::default();
+    let mut skipped = Vec::new();
+    let file_names = std::fs::read_dir(&dir)
+        .map_err(io_err(&dir))?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|file_name| file_name.ends_with(".rockspec") || file_name.ends_with(".rock"))
+        .sorted()
+        .collect_vec();
+    for file_name in file_names {
+        match parse_manifest_entry_file_name(&file_name) {
+            Some((name, version, arch)) => repository.insert(name, version, arch),
+            None => skipped.push(file_name),
+        }
+    }
+
+    let supported_lua_versions = supported_lua_versions(&dir, &repository);
+    let mut manifests = vec![("manifest".to_string(), repository.clone())];
+    for manifest_version in LuaVersion::ALL
+        .iter()
+        .map(LuaVersion::version_compatibility_str)
+        .unique()
+    {
+        let mut versioned = repository.clone();
+        versioned.retain(|name, version, _| {
+            supported_lua_versions
+                .get(&(name.clone(), version.to_string()))
+                .is_none_or(|lua_versions| {
+                    lua_versions.iter().any(|lua_version| {
+                        lua_version.version_compatibility_str() == manifest_version
+                    })
+                })
+        });
+        manifests.push((format!("manifest-{manifest_version}"), versioned));
+    }
+
+    let mut written = Vec::new();
+    for (manifest_name, repository) in manifests {
+        let content = repository.to_lua();
+        let path = dir.join(&manifest_name);
+        std::fs::write(&path, &content).map_err(io_err(&path))?;
+        written.push(path);
+
+        let zip_path = dir.join(format!("{manifest_name}.zip"));
+        write_zipped_manifest(&zip_path, &manifest_name, &content)?;
+        written.push(zip_path);
+    }
+
+    Ok(IndexReport {
+        packages: repository.package_count(),
+        manifests: written,
+        skipped,
+    })
+}
+
+/// The Lua versions supported by each package version, according to its rockspec,
+/// which is read from the `.rockspec` file, or from a packed rock.
+/// Package versions whose rockspec can't be read are listed in all manifests.
+fn supported_lua_versions(
+    dir: &Path,
+    repository: &ManifestRepository,
+) -> HashMap<(PackageName, String), Vec<LuaVersion>> {
+    repository
+        .entries()
+        .into_group_map_by(|(name, version, _)| ((*name).clone(), version.to_string()))
+        .into_iter()
+        .filter_map(|((name, version), entries)| {
+            let rockspec_file_name = format!("{name}-{version}.rockspec");
+            let content = entries
+                .iter()
+                .sorted_by_key(|(_, _, arch)| *arch != "rockspec")
+                .find_map(|(_, _, arch)| match *arch {
+                    "rockspec" => std::fs::read_to_string(dir.join(&rockspec_file_name)).ok(),
+                    arch => {
+                        let file_name = format!("{name}-{version}.{arch}.rock");
+                        let bytes = Bytes::from(std::fs::read(dir.join(&file_name)).ok()?);
+                        read_packed_rockspec(&bytes, &file_name, Some(&rockspec_file_name)).ok()
+                    }
+                })?;
+            let rockspec = RemoteLuaRockspec::new(&content).ok()?;
+            let lua_versions = LuaVersion::ALL
+                .into_iter()
+                .filter(|lua_version| rockspec.supports_lua_version(lua_version))
+                .collect_vec();
+            Some(((name, version), lua_versions))
+        })
+        .collect()
+}
+
+fn write_zipped_manifest(
+    path: &Path,
+    manifest_name: &str,
+    content: &str,
+) -> Result<(), IndexError> {
+    let zip_err = |err| IndexError::Zip(path.to_path_buf(), err);
+    let file = File::create(path).map_err(|err| IndexError::Io(path.to_path_buf(), err))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(manifest_name, options).map_err(zip_err)?;
+    zip.write_all(content.as_bytes())
+        .map_err(|err| IndexError::Io(path.to_path_buf(), err))?;
+    zip.finish().map_err(zip_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::ManifestMetadata;
+
+    use super::*;
+
+    #[test]
+    fn index_rockspecs() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let rockspec = |version: &str, lua: &str| {
+            format!(
+                r#"
+                package = "foo"
+                version = "{version}"
+                source = {{ url = "https://example.com/foo.tar.gz" }}
+                dependencies = {{ "lua {lua}" }}
+                "#
+            )
+        };
+        std::fs::write(
+            dir.join("foo-1.0.0-1.rockspec"),
+            rockspec("1.0.0-1", ">= 5.1"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("foo-2.0.0-1.rockspec"),
+            rockspec("2.0.0-1", ">= 5.4"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("foo.rockspec"), "").unwrap();
+        std::fs::write(dir.join("README.md"), "").unwrap();
+
+        let report = Index::new(dir.to_path_buf()).index().unwrap();
+        assert_eq!(report.packages(), 1);
+        assert_eq!(report.skipped(), &["foo.rockspec".to_string()]);
+        assert_eq!(report.manifests().len(), 10);
+
+        let manifest_5_1 =
+            ManifestRepository::new(&std::fs::read_to_string(dir.join("manifest-5.1")).unwrap())
+                .unwrap();
+        assert_eq!(
+            manifest_5_1
+                .entries()
+                .map(|(_, version, _)| version)
+                .collect_vec(),
+            vec!["1.0.0-1"]
+        );
+        let manifest_5_4 = std::fs::read_to_string(dir.join("manifest-5.4")).unwrap();
+        let metadata = ManifestMetadata::new(&manifest_5_4).unwrap();
+        assert_eq!(
+            metadata.repository[&PackageName::new("foo".into())].len(),
+            2
+        );
+        assert!(dir.join("manifest-5.4.zip").is_file());
+    }
+}
@@ -1,22 +1,27 @@
-use std::{collections::HashMap, io, sync::Arc};
+use std::{collections::HashMap, io, path::Path, sync::Arc};
 
 use crate::{
-    build::{Build, BuildBehaviour, BuildError, RemotePackageSourceSpec, SrcRockSource},
-    config::{Config, LuaVersionUnset},
+    build::{
+        plugin::find_plugin, Build, BuildBehaviour, BuildError, RemotePackageSourceSpec,
+        SrcRockSource,
+    },
+    config::{file_conflicts::FileConflictPolicy, Config, LuaVersionUnset},
     lockfile::{
-        LocalPackage, LocalPackageId, LockConstraint, Lockfile, OptState, PinnedState, ReadWrite,
+        LocalPackage, LocalPackageHashes, LocalPackageId, LockConstraint, Lockfile, OptState,
+        PinnedState, ReadWrite,
     },
     lua_rockspec::BuildBackendSpec,
     luarocks::{
         install_binary_rock::{BinaryRockInstall, InstallBinaryRockError},
         luarocks_installation::{LuaRocksError, LuaRocksInstallError, LuaRocksInstallation},
     },
-    package::{PackageName, PackageNameList},
+    message::Message,
+    package::{PackageName, PackageNameList, PackageSpec},
     progress::{MultiProgress, Progress, ProgressBar},
     project::{Project, ProjectTreeError},
     remote_package_db::{RemotePackageDB, RemotePackageDBError, RemotePackageDbIntegrityError},
-    rockspec::Rockspec,
-    tree::{self, Tree, TreeError},
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree::{self, find_conflicts, ConflictingFile, FileConflicts, Tree, TreeError},
 };
 
 pub use crate::operations::install::spec::PackageInstallSpec;
@@ -26,16 +31,20 @@ use bytes::Bytes;
 use futures::future::join_all;
 use itertools::Itertools;
 use thiserror::Error;
+use tokio::sync::Semaphore;
 
 use super::{
-    resolve::get_all_dependencies, DownloadedRockspec, RemoteRockDownload, SearchAndDownloadError,
+    resolve::{get_all_dependencies, resolve_versions, PackageInstallData, ResolutionError},
+    DownloadedRockspec, RemoteRockDownload, SearchAndDownloadError,
 };
+use transaction::InstallTransaction;
 
 pub mod spec;
+mod transaction;
 
 /// A rocks package installer, providing fine-grained control
 /// over how packages should be installed.
-/// Can install multiple packages in parallel.
+/// Independent packages are built in parallel, limited by [`Config::max_jobs`].
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
 pub struct Install<'a> {
@@ -47,6 +56,10 @@ pub struct Install<'a> {
     tree: Tree,
     package_db: Option<RemotePackageDB>,
     progress: Option<Arc<Progress<MultiProgress>>>,
+    /// Packages to replace with a different source during resolution,
+    /// e.g. from a project's `[patch]` table.
+    #[builder(default)]
+    patches: HashMap<PackageName, LuaDependencySpec>,
 }
 
 impl<'a, State> InstallBuilder<'a, State>
@@ -124,6 +137,7 @@ where
         install_impl(
             install_built.packages,
             Arc::new(package_db),
+            Arc::new(install_built.patches),
             install_built.config,
             &install_built.tree,
             progress,
@@ -160,13 +174,36 @@ pub enum InstallError {
     ProjectTreeError(#[from] ProjectTreeError),
     #[error("cannot install duplicate entrypoints: {0}")]
     DuplicateEntrypoints(PackageNameList),
+    #[error(transparent)]
+    Resolution(#[from] ResolutionError),
+    #[error(transparent)]
+    FileConflicts(#[from] FileConflicts),
+    #[error("{err}\nfailed to roll back the changes to the tree: {rollback_err}")]
+    Rollback {
+        err: Box<InstallError>,
+        rollback_err: io::Error,
+    },
+}
+
+/// Roll back the `transaction` after an installation failed with `err`,
+/// reporting both errors if the rollback fails, too.
+fn rollback(transaction: InstallTransaction, err: InstallError) -> InstallError {
+    match transaction.rollback() {
+        Ok(()) => err,
+        Err(rollback_err) => InstallError::Rollback {
+            err: Box::new(err),
+            rollback_err,
+        },
+    }
 }
 
 // TODO(vhyrro): This function has too many arguments. Refactor it.
 #[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(packages = packages.len(), tree = %tree.root().display()))]
 async fn install_impl(
     packages: Vec<PackageInstallSpec>,
     package_db: Arc<RemotePackageDB>,
+    patches: Arc<HashMap<PackageName, LuaDependencySpec>>,
     config: &Config,
     tree: &Tree,
     progress_arc: Arc<Progress<MultiProgress>>,
@@ -177,6 +214,25 @@ async fn install_impl(
     let lockfile = tree.lockfile()?;
     let build_lockfile = tree.build_tree(config)?.lockfile()?;
 
+    // Packages with a custom source are not resolved from the package database
+    let roots = packages
+        .iter()
+        .filter(|spec| spec.source.is_none())
+        .map(|spec| spec.package.clone())
+        .collect_vec();
+    let bar = progress_arc.map(|p| p.new_bar());
+    bar.map(|b| b.set_message("🧩 Resolving dependencies"));
+    let resolved = resolve_versions(
+        &roots,
+        &patches.keys().cloned().collect(),
+        &package_db,
+        &lockfile,
+        config,
+        &bar,
+    )
+    .await?;
+    bar.map(|b| b.finish_and_clear());
+
     get_all_dependencies(
         dep_tx,
         build_dep_tx,
@@ -184,6 +240,8 @@ async fn install_impl(
         package_db.clone(),
         Arc::new(lockfile.clone()),
         Arc::new(build_lockfile.clone()),
+        patches,
+        Arc::new(resolved),
         config,
         progress_arc.clone(),
     )
@@ -213,6 +271,10 @@ async fn install_impl(
         )
         .constraint(build_dep_spec.spec.constraint())
         .behaviour(build_dep_spec.build_behaviour)
+        .maybe_expected_hashes(package_db.expected_hashes(&PackageSpec::new(
+            package.clone(),
+            rockspec.version().clone(),
+        )))
         .build()
         .await
         .map_err(|err| InstallError::BuildDependencyError(package, err))?;
@@ -223,82 +285,106 @@ async fn install_impl(
     while let Some(dep) = dep_rx.recv().await {
         all_packages.insert(dep.spec.id(), dep);
     }
-
-    let installed_packages = join_all(all_packages.clone().into_values().map(|install_spec| {
-        let progress_arc = progress_arc.clone();
-        let downloaded_rock = install_spec.downloaded_rock;
-        let config = config.clone();
-        let tree = tree.clone();
-
-        tokio::spawn({
-            async move {
-                let pkg = match downloaded_rock {
-                    RemoteRockDownload::RockspecOnly { rockspec_download } => {
-                        install_rockspec(
-                            rockspec_download,
-                            None,
-                            install_spec.spec.constraint(),
-                            install_spec.build_behaviour,
-                            install_spec.pin,
-                            install_spec.opt,
-                            install_spec.entry_type,
-                            &tree,
-                            &config,
-                            progress_arc,
-                        )
-                        .await?
-                    }
-                    RemoteRockDownload::BinaryRock {
-                        rockspec_download,
-                        packed_rock,
-                    } => {
-                        install_binary_rock(
-                            rockspec_download,
-                            packed_rock,
-                            install_spec.spec.constraint(),
-                            install_spec.build_behaviour,
-                            install_spec.pin,
-                            install_spec.opt,
-                            install_spec.entry_type,
-                            &config,
-                            &tree,
-                            progress_arc,
-                        )
-                        .await?
-                    }
-                    RemoteRockDownload::SrcRock {
-                        rockspec_download,
-                        src_rock,
-                        source_url,
-                    } => {
-                        let src_rock_source = SrcRockSource {
-                            bytes: src_rock,
-                            source_url,
-                        };
-                        install_rockspec(
-                            rockspec_download,
-                            Some(src_rock_source),
-                            install_spec.spec.constraint(),
-                            install_spec.build_behaviour,
-                            install_spec.pin,
-                            install_spec.opt,
-                            install_spec.entry_type,
-                            &tree,
-                            &config,
-                            progress_arc,
-                        )
-                        .await?
-                    }
-                };
-
-                Ok::<_, InstallError>((pkg.id(), (pkg, install_spec.entry_type)))
-            }
+    tracing::debug!(packages = all_packages.len(), "resolved all packages");
+
+    // Executables are installed into the tree's shared `bin` directory,
+    // so conflicting executables are detected before building anything.
+    let new_binaries = all_packages
+        .values()
+        .map(|install_spec| {
+            let rockspec = install_spec.downloaded_rock.rockspec();
+            (
+                PackageSpec::new(rockspec.package().clone(), rockspec.version().clone()),
+                file_names(rockspec.binaries().iter()),
+            )
         })
-    }))
+        .collect_vec();
+    let binary_conflicts = find_conflicts(
+        ConflictingFile::Binary,
+        lockfile
+            .rocks()
+            .values()
+            .map(|package| (package.to_package(), file_names(package.spec.binaries())))
+            .collect_vec(),
+        new_binaries.clone(),
+    );
+    let first_wins = config.file_conflicts() == FileConflictPolicy::FirstWins;
+    if !binary_conflicts.is_empty() && !first_wins {
+        return Err(FileConflicts::new(binary_conflicts).into());
+    }
+    // With `first-wins`, the executables of installed packages are restored after building.
+    let kept_binaries = binary_conflicts
+        .iter()
+        .filter(|conflict| {
+            lockfile.rocks().values().any(|package| {
+                package.name() == conflict.first().name()
+                    && package.version() == conflict.first().version()
+            })
+        })
+        .filter_map(|conflict| match conflict.file() {
+            ConflictingFile::Binary(name) => Some(name),
+            ConflictingFile::Module(_) => None,
+        })
+        .flat_map(|name| [tree.bin().join(name), tree.unwrapped_bin().join(name)])
+        .filter(|path| path.is_file())
+        .map(|path| Ok::<_, io::Error>((path.clone(), std::fs::read(&path)?)))
+        .try_collect::<_, Vec<_>, _>()?;
+
+    // The lockfile is only updated once all packages have been installed.
+    // If any package fails to install, the changes to the tree are rolled back.
+    let rebuilt = all_packages
+        .iter()
+        .filter(|(_, install_spec)| install_spec.build_behaviour == BuildBehaviour::Force)
+        .filter_map(|(id, _)| lockfile.get(id).cloned())
+        .collect_vec();
+    let binaries = new_binaries
+        .into_iter()
+        .flat_map(|(_, binaries)| binaries)
+        .collect_vec();
+    let transaction = InstallTransaction::begin(tree, &rebuilt, &binaries)?;
+    let installed_packages = match install_layers(
+        &all_packages,
+        &package_db,
+        config,
+        tree,
+        progress_arc.clone(),
+    )
     .await
-    .into_iter()
-    .flatten()
-    .try_collect::<_, HashMap<LocalPackageId, (LocalPackage, tree::EntryType)>, _>()?;
+    {
+        Ok(installed_packages) => installed_packages,
+        Err(err) => return Err(rollback(transaction, err)),
+    };
+    for (path, content) in kept_binaries {
+        std::fs::write(path, content)?;
+    }
+
+    // Each rock has its own directory, so Lua modules are never overwritten,
+    // but `require` only finds the module of one of the packages.
+    let module_conflicts = find_conflicts(
+        ConflictingFile::Module,
+        lockfile
+            .rocks()
+            .values()
+            .map(|package| (package.to_package(), tree.package_modules(package)))
+            .collect_vec(),
+        installed_packages
+            .values()
+            .map(|(package, _)| (package.to_package(), tree.package_modules(package)))
+            .collect_vec(),
+    );
+    if !module_conflicts.is_empty() && !first_wins {
+        return Err(rollback(
+            transaction,
+            FileConflicts::new(module_conflicts).into(),
+        ));
+    }
+    if first_wins {
+        let bar = progress_arc.map(|p| p.new_bar());
+        for conflict in binary_conflicts.iter().chain(&module_conflicts) {
+            bar.map(|b| b.println(format!("⚠️ WARNING: {conflict}")));
+        }
+        bar.map(|b| b.finish_and_clear());
+    }
 
     let write_dependency = |lockfile: &mut Lockfile<ReadWrite>,
                             id: &LocalPackageId,
@@ -325,7 +411,7 @@ async fn install_impl(
             });
     };
 
-    lockfile.map_then_flush(|lockfile| {
+    let flushed = lockfile.map_then_flush(|lockfile| {
         installed_packages
             .iter()
             .for_each(|(id, (pkg, is_entrypoint))| {
@@ -333,7 +419,13 @@ async fn install_impl(
             });
 
         Ok::<_, io::Error>(())
-    })?;
+    });
+    if let Err(err) = flushed {
+        return Err(rollback(transaction, err.into()));
+    }
+    transaction.commit()?;
+
+    tree.write_luarocks_manifest()?;
 
     Ok(installed_packages
         .into_values()
@@ -341,10 +433,165 @@ async fn install_impl(
         .collect_vec())
 }
 
+/// Install packages in layers of the dependency graph, so that each package
+/// is only built after its dependencies have been installed.
+/// Packages within the same layer don't depend on each other and are built in parallel.
+async fn install_layers(
+    all_packages: &HashMap<LocalPackageId, PackageInstallData>,
+    package_db: &RemotePackageDB,
+    config: &Config,
+    tree: &Tree,
+    progress_arc: Arc<Progress<MultiProgress>>,
+) -> Result<HashMap<LocalPackageId, (LocalPackage, tree::EntryType)>, InstallError> {
+    let semaphore = Arc::new(Semaphore::new(config.max_jobs()));
+    let mut installed_packages: HashMap<LocalPackageId, (LocalPackage, tree::EntryType)> =
+        HashMap::with_capacity(all_packages.len());
+    let mut remaining = all_packages.clone();
+    while !remaining.is_empty() {
+        let (mut ready, blocked): (HashMap<_, _>, HashMap<_, _>) =
+            remaining.into_iter().partition(|(_, install_spec)| {
+                install_spec
+                    .spec
+                    .dependencies()
+                    .into_iter()
+                    .all(|dependency_id| {
+                        !all_packages.contains_key(dependency_id)
+                            || installed_packages.contains_key(dependency_id)
+                    })
+            });
+        remaining = blocked;
+        if ready.is_empty() {
+            // NOTE: This can only happen if there is a dependency cycle,
+            // in which case we install the remaining packages all at once.
+            ready = std::mem::take(&mut remaining);
+        }
+
+        let layer = join_all(ready.into_values().map(|install_spec| {
+            let semaphore = Arc::clone(&semaphore);
+            let progress_arc = progress_arc.clone();
+            let expected_hashes = package_db.expected_hashes(&PackageSpec::new(
+                install_spec.downloaded_rock.rockspec().package().clone(),
+                install_spec.downloaded_rock.rockspec().version().clone(),
+            ));
+            let config = config.clone();
+            let tree = tree.clone();
+
+            tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("install semaphore closed [This is a bug!]");
+                let entry_type = install_spec.entry_type;
+                let pkg =
+                    install_package(install_spec, expected_hashes, &config, &tree, progress_arc)
+                        .await?;
+                Message::install_finished(&pkg).emit(&config);
+                Ok::<_, InstallError>((pkg.id(), (pkg, entry_type)))
+            })
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .try_collect::<_, Vec<_>, _>()?;
+
+        installed_packages.extend(layer);
+    }
+
+    Ok(installed_packages)
+}
+
+/// The file names of a package's executables.
+fn file_names<P: AsRef<Path>>(binaries: impl IntoIterator<Item = P>) -> Vec<String> {
+    binaries
+        .into_iter()
+        .filter_map(|binary| {
+            binary
+                .as_ref()
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .collect_vec()
+}
+
+#[tracing::instrument(skip_all, fields(
+    package = %install_spec.downloaded_rock.rockspec().package(),
+    version = %install_spec.downloaded_rock.rockspec().version(),
+))]
+async fn install_package(
+    install_spec: PackageInstallData,
+    expected_hashes: Option<LocalPackageHashes>,
+    config: &Config,
+    tree: &Tree,
+    progress_arc: Arc<Progress<MultiProgress>>,
+) -> Result<LocalPackage, InstallError> {
+    match install_spec.downloaded_rock {
+        RemoteRockDownload::RockspecOnly { rockspec_download } => {
+            install_rockspec(
+                rockspec_download,
+                None,
+                expected_hashes,
+                install_spec.spec.constraint(),
+                install_spec.build_behaviour,
+                install_spec.pin,
+                install_spec.opt,
+                install_spec.entry_type,
+                tree,
+                config,
+                progress_arc,
+            )
+            .await
+        }
+        RemoteRockDownload::BinaryRock {
+            rockspec_download,
+            packed_rock,
+        } => {
+            install_binary_rock(
+                rockspec_download,
+                packed_rock,
+                expected_hashes,
+                install_spec.spec.constraint(),
+                install_spec.build_behaviour,
+                install_spec.pin,
+                install_spec.opt,
+                install_spec.entry_type,
+                config,
+                tree,
+                progress_arc,
+            )
+            .await
+        }
+        RemoteRockDownload::SrcRock {
+            rockspec_download,
+            src_rock,
+            source_url,
+        } => {
+            let src_rock_source = SrcRockSource {
+                bytes: src_rock,
+                source_url,
+            };
+            install_rockspec(
+                rockspec_download,
+                Some(src_rock_source),
+                expected_hashes,
+                install_spec.spec.constraint(),
+                install_spec.build_behaviour,
+                install_spec.pin,
+                install_spec.opt,
+                install_spec.entry_type,
+                tree,
+                config,
+                progress_arc,
+            )
+            .await
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn install_rockspec(
     rockspec_download: DownloadedRockspec,
     src_rock_source: Option<SrcRockSource>,
+    expected_hashes: Option<LocalPackageHashes>,
     constraint: LockConstraint,
     behaviour: BuildBehaviour,
     pin: PinnedState,
@@ -360,10 +607,15 @@ async fn install_rockspec(
     let package = rockspec.package().clone();
     let bar = progress.map(|p| p.add(ProgressBar::from(format!("💻 Installing {}", &package,))));
 
-    if let Some(BuildBackendSpec::LuaRock(_)) = &rockspec.build().current_platform().build_backend {
-        let luarocks_tree = tree.build_tree(config)?;
-        let luarocks = LuaRocksInstallation::new(config, luarocks_tree)?;
-        luarocks.ensure_installed(&bar).await?;
+    // Build types without a `lux-build-<type>` plugin are built with luarocks.
+    if let Some(BuildBackendSpec::LuaRock(build_type)) =
+        &rockspec.build().current_platform().build_backend
+    {
+        if find_plugin(build_type, tree, config).is_none() {
+            let luarocks_tree = tree.build_tree(config)?;
+            let luarocks = LuaRocksInstallation::new(config, luarocks_tree)?;
+            luarocks.ensure_installed(&bar).await?;
+        }
     }
 
     let source_spec = match src_rock_source {
@@ -378,6 +630,7 @@ async fn install_rockspec(
         .behaviour(behaviour)
         .source(source)
         .source_spec(source_spec)
+        .maybe_expected_hashes(expected_hashes)
         .build()
         .await
         .map_err(|err| InstallError::BuildError(package, err))?;
@@ -391,6 +644,7 @@ async fn install_rockspec(
 async fn install_binary_rock(
     rockspec_download: DownloadedRockspec,
     packed_rock: Bytes,
+    expected_hashes: Option<LocalPackageHashes>,
     constraint: LockConstraint,
     behaviour: BuildBehaviour,
     pin: PinnedState,
@@ -422,6 +676,7 @@ async fn install_binary_rock(
     .opt(opt)
     .constraint(constraint)
     .behaviour(behaviour)
+    .expected_hashes(expected_hashes)
     .install()
     .await
     .map_err(|err| InstallError::InstallBinaryRockError(package, err))?;
@@ -4,12 +4,13 @@ use crate::{
     build::BuildBehaviour,
     lockfile::{LockConstraint, OptState, PinnedState},
     lua_rockspec::RockSourceSpec,
+    operations::PrefetchedRock,
     package::PackageReq,
     tree,
 };
 
 /// Specifies how to install a package
-#[derive(Debug, Builder)]
+#[derive(Debug, Clone, Builder)]
 #[builder(start_fn = new, finish_fn(name = build, vis = "pub"))]
 pub struct PackageInstallSpec {
     #[builder(start_fn)]
@@ -23,6 +24,9 @@ pub struct PackageInstallSpec {
     #[builder(default)]
     pub(crate) opt: OptState,
     pub(crate) source: Option<RockSourceSpec>,
+    /// The rock that provides the package, if it has already been read or fetched,
+    /// so that it isn't read or fetched again.
+    pub(crate) prefetched: Option<PrefetchedRock>,
     /// Optional constraint, carried over from a previous install,
     /// e.g. defined in a lockfile.
     pub(crate) constraint: Option<LockConstraint>,
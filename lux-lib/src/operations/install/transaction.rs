@@ -0,0 +1,190 @@
+//! Rolling back the changes to a tree if an installation fails.
+//!
+//! Each package is built in a staging directory and only moved into the tree once it is complete.
+//! Packages that are rebuilt are moved to a backup directory in the tree,
+//! and anything that an installation adds to the tree is removed on failure,
+//! so that the tree and its lockfile always stay in sync.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use tempdir::TempDir;
+
+use crate::{lockfile::LocalPackage, tree::Tree};
+
+pub(crate) struct InstallTransaction {
+    /// Directories whose entries are added by an installation.
+    dirs: Vec<PathBuf>,
+    /// The entries of `dirs` before the installation.
+    existing_entries: HashSet<PathBuf>,
+    staging: Option<TempDir>,
+    /// Paths that were moved or copied to the staging directory,
+    /// and their location in the staging directory.
+    backups: Vec<(PathBuf, PathBuf)>,
+}
+
+impl InstallTransaction {
+    /// Start an installation into the `tree`.
+    /// The installed `rebuilt` packages and the executables in `binaries` are backed up,
+    /// because they will be overwritten.
+    pub(crate) fn begin(
+        tree: &Tree,
+        rebuilt: &[LocalPackage],
+        binaries: &[String],
+    ) -> io::Result<Self> {
+        let mut transaction = Self {
+            dirs: vec![tree.root(), tree.bin(), tree.unwrapped_bin()]
+                .into_iter()
+                .chain(tree.entrypoint_etc_dirs())
+                .collect_vec(),
+            existing_entries: HashSet::new(),
+            staging: None,
+            backups: Vec::new(),
+        };
+        for package in rebuilt {
+            transaction.back_up_package(tree, package)?;
+        }
+        transaction.back_up(tree, binary_paths(tree, binaries.iter()))?;
+        transaction.existing_entries = transaction
+            .dirs
+            .iter()
+            .flat_map(|dir| dir_entries(dir))
+            .collect();
+        Ok(transaction)
+    }
+
+    /// Keep the changes to the tree.
+    pub(crate) fn commit(self) -> io::Result<()> {
+        if let Some(staging) = self.staging {
+            staging.close()?;
+        }
+        Ok(())
+    }
+
+    /// Remove everything that was added to the tree and restore the backed up packages.
+    pub(crate) fn rollback(self) -> io::Result<()> {
+        for dir in &self.dirs {
+            for entry in dir_entries(dir) {
+                if self.existing_entries.contains(&entry)
+                    || self
+                        .staging
+                        .as_ref()
+                        .is_some_and(|staging| staging.path() == entry)
+                {
+                    continue;
+                }
+                remove(&entry)?;
+            }
+        }
+        for (path, backup) in &self.backups {
+            if path.exists() {
+                remove(path)?;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(backup, path)?;
+        }
+        if let Some(staging) = self.staging {
+            staging.close()?;
+        }
+        Ok(())
+    }
+
+    fn back_up_package(&mut self, tree: &Tree, package: &LocalPackage) -> io::Result<()> {
+        let layout = tree
+            .installed_rock_layout(package)
+            .map_err(io::Error::other)?;
+        let etc = Some(layout.etc).filter(|etc| !etc.starts_with(&layout.rock_path));
+        self.back_up(tree, std::iter::once(layout.rock_path).chain(etc).collect())?;
+        let binaries = package
+            .spec
+            .binaries()
+            .into_iter()
+            .filter_map(|binary| binary.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect_vec();
+        self.back_up(tree, binary_paths(tree, binaries.iter()))
+    }
+
+    fn back_up(&mut self, tree: &Tree, paths: Vec<PathBuf>) -> io::Result<()> {
+        for path in paths {
+            if !path.exists() || self.backups.iter().any(|(backed_up, _)| *backed_up == path) {
+                continue;
+            }
+            if self.staging.is_none() {
+                self.staging = Some(TempDir::new_in(tree.root(), ".lux-install")?);
+            }
+            let staging = self
+                .staging
+                .as_ref()
+                .expect("staging directory not created");
+            let backup = staging.path().join(self.backups.len().to_string());
+            if path.is_dir() {
+                std::fs::rename(&path, &backup)?;
+            } else {
+                // Executables may be shared with other packages, so they are copied.
+                std::fs::copy(&path, &backup)?;
+            }
+            self.backups.push((path, backup));
+        }
+        Ok(())
+    }
+}
+
+/// The wrapped and unwrapped executables with the given names.
+fn binary_paths<'a>(tree: &Tree, names: impl Iterator<Item = &'a String>) -> Vec<PathBuf> {
+    names
+        .flat_map(|name| [tree.bin().join(name), tree.unwrapped_bin().join(name)])
+        .collect_vec()
+}
+
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .collect_vec()
+        })
+        .unwrap_or_default()
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigBuilder, LuaVersion};
+
+    #[test]
+    fn rollback_removes_added_entries() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(dir.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        std::fs::write(tree.bin().join("existing"), "").unwrap();
+
+        let transaction = InstallTransaction::begin(&tree, &[], &[]).unwrap();
+        std::fs::create_dir_all(tree.root().join("abc-foo@1.0.0-1").join("src")).unwrap();
+        std::fs::write(tree.bin().join("foo"), "").unwrap();
+        transaction.rollback().unwrap();
+
+        assert!(!tree.root().join("abc-foo@1.0.0-1").exists());
+        assert!(!tree.bin().join("foo").exists());
+        assert!(tree.bin().join("existing").is_file());
+    }
+}
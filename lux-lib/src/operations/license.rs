@@ -0,0 +1,156 @@
+use std::{io, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    config::{license_policy::LicensePolicy, Config},
+    lockfile::LocalPackage,
+    lua_rockspec::{LuaRockspecError, RemoteLuaRockspec},
+    progress::{MultiProgress, Progress},
+    remote_package_db::{RemotePackageDB, RemotePackageDBError},
+    rockspec::Rockspec,
+    tree::Tree,
+};
+
+use super::{Download, SearchAndDownloadError};
+
+/// Collects the license metadata of packages from their rockspecs.
+/// Rockspecs are read from the install trees if the packages are installed,
+/// and downloaded otherwise.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Licenses<'a> {
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The packages to collect the licenses of, e.g. the packages in a lockfile.
+    packages: Vec<LocalPackage>,
+
+    /// The trees in which to look for installed rockspecs.
+    #[builder(default)]
+    trees: Vec<Tree>,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> LicensesBuilder<'_, State>
+where
+    State: licenses_builder::State + licenses_builder::IsComplete,
+{
+    pub async fn collect(self) -> Result<LicenseReport, LicenseError> {
+        let args = self._build();
+        let progress = args.progress.unwrap_or_else(MultiProgress::new_arc);
+        let bar = progress.map(|p| p.new_bar());
+        let mut package_db: Option<RemotePackageDB> = None;
+        let mut packages = Vec::new();
+        for package in args
+            .packages
+            .into_iter()
+            .unique_by(|package| package.id())
+            .sorted_by(|a, b| a.name().cmp(b.name()).then(a.version().cmp(b.version())))
+        {
+            let installed_rockspec = args
+                .trees
+                .iter()
+                .filter_map(|tree| tree.installed_rock_layout(&package).ok())
+                .map(|layout| layout.rockspec_path())
+                .find(|path| path.is_file());
+            let license = match installed_rockspec {
+                Some(path) => RemoteLuaRockspec::new(&std::fs::read_to_string(path)?)?
+                    .description()
+                    .license
+                    .clone(),
+                None => {
+                    if package_db.is_none() {
+                        package_db = Some(RemotePackageDB::from_config(args.config, &bar).await?);
+                    }
+                    let db = package_db.as_ref().expect("package DB not initialised");
+                    bar.map(|b| {
+                        b.set_message(format!("📥 Downloading rockspec for {}", package.name()))
+                    });
+                    Download::new(&package.clone().into_package_req(), args.config, &bar)
+                        .package_db(db)
+                        .download_rockspec()
+                        .await?
+                        .rockspec
+                        .description()
+                        .license
+                        .clone()
+                }
+            };
+            packages.push(PackageLicense::new(package, license));
+        }
+        bar.map(|b| b.finish_and_clear());
+        Ok(LicenseReport::new(packages))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LicenseError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to parse installed rockspec: {0}")]
+    Rockspec(#[from] LuaRockspecError),
+    #[error(transparent)]
+    RemotePackageDB(#[from] RemotePackageDBError),
+    #[error(transparent)]
+    SearchAndDownload(#[from] SearchAndDownloadError),
+}
+
+/// The license of a package, as declared in its rockspec.
+#[derive(Debug, Clone)]
+pub struct PackageLicense {
+    package: LocalPackage,
+    license: Option<String>,
+}
+
+impl PackageLicense {
+    pub(crate) fn new(package: LocalPackage, license: Option<String>) -> Self {
+        Self { package, license }
+    }
+
+    pub fn package(&self) -> &LocalPackage {
+        &self.package
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LicenseReport {
+    packages: Vec<PackageLicense>,
+}
+
+impl LicenseReport {
+    pub(crate) fn new(packages: Vec<PackageLicense>) -> Self {
+        Self { packages }
+    }
+
+    pub fn packages(&self) -> &[PackageLicense] {
+        &self.packages
+    }
+
+    /// The number of packages per license, where `None` counts the packages
+    /// without license metadata.
+    pub fn summary(&self) -> Vec<(Option<&str>, usize)> {
+        self.packages
+            .iter()
+            .map(|package| package.license())
+            .counts()
+            .into_iter()
+            .sorted_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then(a.cmp(b)))
+            .collect_vec()
+    }
+
+    /// The packages whose licenses are forbidden by the `policy`.
+    pub fn violations(&self, policy: &LicensePolicy) -> Vec<&PackageLicense> {
+        self.packages
+            .iter()
+            .filter(|package| !policy.is_allowed(package.license()))
+            .collect_vec()
+    }
+}
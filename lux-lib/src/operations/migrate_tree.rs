@@ -0,0 +1,351 @@
+//! Migrate a tree managed by luarocks to a lux tree.
+//!
+//! Each rock installed in the luarocks tree is re-packed as a binary rock,
+//! using its `rock_manifest` to collect the installed files,
+//! and then installed into the lux tree without rebuilding it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use mlua::{Lua, Value};
+use tempdir::TempDir;
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use crate::{
+    config::{Config, LuaVersion, LuaVersionUnset},
+    lockfile::LocalPackage,
+    lua_rockspec::{LuaRockspecError, RemoteLuaRockspec, RockSourceSpec},
+    luarocks::{
+        self,
+        rock_manifest::{DirOrFileEntry, RockManifest, RockManifestError},
+    },
+    package::{PackageName, PackageSpec, PackageVersion, PackageVersionParseError},
+    progress::{MultiProgress, Progress},
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree::{self, Tree},
+};
+
+use super::{Install, InstallError, PackageInstallSpec};
+
+/// Recreates the rocks installed in a luarocks tree in a lux tree.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct MigrateTree<'a> {
+    /// The root of the luarocks tree, e.g. `~/.luarocks`.
+    #[builder(start_fn)]
+    luarocks_tree: &'a Path,
+    #[builder(start_fn)]
+    tree: Tree,
+    #[builder(start_fn)]
+    config: &'a Config,
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> MigrateTreeBuilder<'_, State>
+where
+    State: migrate_tree_builder::State + migrate_tree_builder::IsComplete,
+{
+    /// Migrate the rocks, returning the installed packages.
+    pub async fn migrate(self) -> Result<Vec<LocalPackage>, MigrateTreeError> {
+        do_migrate_tree(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MigrateTreeError {
+    #[error(transparent)]
+    LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error("{0} is not a luarocks tree (no rocks manifest found)")]
+    NotALuaRocksTree(PathBuf),
+    #[error("failed to read the luarocks manifest: {0}")]
+    Manifest(#[from] mlua::Error),
+    #[error("invalid version in the luarocks manifest: {0}")]
+    PackageVersion(#[from] PackageVersionParseError),
+    #[error("{0} is listed in the rock_manifest, but does not exist")]
+    MissingFile(PathBuf),
+    #[error(transparent)]
+    RockManifest(#[from] RockManifestError),
+    #[error(transparent)]
+    Rockspec(#[from] LuaRockspecError),
+    #[error("failed to pack rock: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Install(#[from] Box<InstallError>),
+}
+
+/// A rock installed in a luarocks tree.
+struct InstalledRock {
+    package: PackageSpec,
+    rockspec: RemoteLuaRockspec,
+    packed_rock: PathBuf,
+}
+
+async fn do_migrate_tree(args: MigrateTree<'_>) -> Result<Vec<LocalPackage>, MigrateTreeError> {
+    let lua_version = LuaVersion::from(args.config)?.version_compatibility_str();
+    let luarocks_tree = args.luarocks_tree;
+    let rocks_dir = [
+        luarocks_tree
+            .join("lib")
+            .join("luarocks")
+            .join(format!("rocks-{lua_version}")),
+        // luarocks 2 doesn't separate rocks by Lua version
+        luarocks_tree.join("lib").join("luarocks").join("rocks"),
+    ]
+    .into_iter()
+    .find(|dir| dir.join("manifest").is_file())
+    .ok_or_else(|| MigrateTreeError::NotALuaRocksTree(luarocks_tree.to_path_buf()))?;
+    let deploy_dirs = DeployDirs {
+        lua: luarocks_tree.join("share").join("lua").join(&lua_version),
+        lib: luarocks_tree.join("lib").join("lua").join(&lua_version),
+    };
+
+    let manifest_content = tokio::fs::read_to_string(rocks_dir.join("manifest")).await?;
+    let packages = read_manifest(&manifest_content)?;
+
+    let temp_dir = TempDir::new("lux-migrate-tree")?;
+    let rocks = packages
+        .iter()
+        .map(|package| {
+            let rock_dir = rocks_dir
+                .join(package.name().to_string())
+                .join(package.version().to_string());
+            pack_installed_rock(package, &rock_dir, &deploy_dirs, temp_dir.path())
+        })
+        .try_collect::<_, Vec<_>, _>()?;
+
+    // Rocks that other rocks in the tree depend on are installed as dependencies
+    let dependencies: HashSet<&PackageName> = rocks
+        .iter()
+        .flat_map(|rock| rock.rockspec.dependencies().current_platform())
+        .map(|dep| dep.name())
+        .collect();
+    // Dependencies on rocks from the tree are resolved to the re-packed rocks
+    let patches = rocks
+        .iter()
+        .map(|rock| {
+            (
+                rock.package.name().clone(),
+                LuaDependencySpec {
+                    source: Some(RockSourceSpec::File(rock.packed_rock.clone())),
+                    ..LuaDependencySpec::from(rock.package.clone().into_package_req())
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let packages = rocks
+        .iter()
+        .map(|rock| {
+            let entry_type = if dependencies.contains(rock.package.name()) {
+                tree::EntryType::DependencyOnly
+            } else {
+                tree::EntryType::Entrypoint
+            };
+            PackageInstallSpec::new(rock.package.clone().into_package_req(), entry_type).build()
+        })
+        .collect_vec();
+
+    Ok(Install::new(args.config)
+        .packages(packages)
+        .patches(patches)
+        .tree(args.tree)
+        .maybe_progress(args.progress)
+        .install()
+        .await
+        .map_err(Box::new)?)
+}
+
+/// Read the installed packages from a luarocks manifest.
+/// If several versions of a package are installed, only the latest one is migrated.
+fn read_manifest(content: &str) -> Result<Vec<PackageSpec>, MigrateTreeError> {
+    let lua = Lua::new();
+    lua.load(content).exec()?;
+    let repository: HashMap<String, HashMap<String, Value>> = lua.globals().get("repository")?;
+    repository
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .filter_map(|(name, versions)| {
+            versions
+                .into_keys()
+                .map(|version| PackageVersion::parse(&version))
+                .process_results(|versions| versions.max())
+                .map(|version| {
+                    version.map(|version| PackageSpec::new(PackageName::new(name), version))
+                })
+                .transpose()
+        })
+        .try_collect()
+        .map_err(MigrateTreeError::from)
+}
+
+/// The directories luarocks deploys Lua modules and libraries to.
+struct DeployDirs {
+    lua: PathBuf,
+    lib: PathBuf,
+}
+
+/// Pack a rock installed in a luarocks tree, in the same way `luarocks pack` would.
+fn pack_installed_rock(
+    package: &PackageSpec,
+    rock_dir: &Path,
+    deploy_dirs: &DeployDirs,
+    dest_dir: &Path,
+) -> Result<InstalledRock, MigrateTreeError> {
+    let rock_manifest_content = std::fs::read_to_string(rock_dir.join("rock_manifest"))?;
+    let rock_manifest = RockManifest::new(&rock_manifest_content)?;
+    let rockspec_file_name = format!("{}-{}.rockspec", package.name(), package.version());
+    let rockspec_content = std::fs::read_to_string(rock_dir.join(&rockspec_file_name))?;
+    let rockspec = RemoteLuaRockspec::new(&rockspec_content)?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    add_entries(
+        &mut zip,
+        &rock_manifest.lua.entries,
+        &deploy_dirs.lua,
+        Path::new("lua"),
+    )?;
+    add_entries(
+        &mut zip,
+        &rock_manifest.lib.entries,
+        &deploy_dirs.lib,
+        Path::new("lib"),
+    )?;
+    add_entries(
+        &mut zip,
+        &rock_manifest.doc.entries,
+        &rock_dir.join("doc"),
+        Path::new("doc"),
+    )?;
+    add_entries(
+        &mut zip,
+        &rock_manifest.conf.entries,
+        &rock_dir.join("conf"),
+        Path::new("conf"),
+    )?;
+    add_entries(
+        &mut zip,
+        &rock_manifest.root.entries,
+        rock_dir,
+        Path::new(""),
+    )?;
+    if !rock_manifest
+        .root
+        .entries
+        .contains_key(Path::new(&rockspec_file_name))
+    {
+        add_file(
+            &mut zip,
+            &rock_dir.join(&rockspec_file_name),
+            Path::new(&rockspec_file_name),
+        )?;
+    }
+    for binary in rock_manifest.bin.entries.keys() {
+        add_file(
+            &mut zip,
+            &rock_dir.join("bin").join(binary),
+            &Path::new("bin").join(binary),
+        )?;
+    }
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("rock_manifest", options)?;
+    zip.write_all(rock_manifest_content.as_bytes())?;
+    let bytes = zip.finish()?.into_inner();
+
+    let packed_rock = dest_dir.join(format!(
+        "{}-{}.{}.rock",
+        package.name(),
+        package.version(),
+        luarocks::current_platform_luarocks_identifier()
+    ));
+    std::fs::write(&packed_rock, bytes)?;
+    Ok(InstalledRock {
+        package: package.clone(),
+        rockspec,
+        packed_rock,
+    })
+}
+
+fn add_entries(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    entries: &HashMap<PathBuf, DirOrFileEntry>,
+    source_dir: &Path,
+    zip_dir: &Path,
+) -> Result<(), MigrateTreeError> {
+    for (path, entry) in entries {
+        match entry {
+            DirOrFileEntry::FileEntry(_) => {
+                add_file(zip, &source_dir.join(path), &zip_dir.join(path))?
+            }
+            DirOrFileEntry::DirEntry(entries) => {
+                add_entries(zip, entries, &source_dir.join(path), &zip_dir.join(path))?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_file(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    file: &Path,
+    zip_path: &Path,
+) -> Result<(), MigrateTreeError> {
+    if !file.is_file() {
+        return Err(MigrateTreeError::MissingFile(file.to_path_buf()));
+    }
+    let mut f = File::open(file)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
+
+    #[cfg(unix)]
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .unix_permissions(f.metadata()?.permissions().mode());
+    #[cfg(not(unix))]
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+    zip.start_file(zip_path.to_string_lossy(), options)?;
+    zip.write_all(&buffer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_luarocks_manifest() {
+        let content = r#"
+commands = {}
+modules = {}
+repository = {
+   foo = {
+      ["1.0.0-1"] = { { arch = "installed" } },
+      ["1.1.0-1"] = { { arch = "installed" } },
+   },
+   bar = {
+      ["scm-1"] = { { arch = "installed" } },
+   },
+}
+"#;
+        let packages = read_manifest(content).unwrap();
+        assert_eq!(
+            packages
+                .iter()
+                .map(|package| package.to_string())
+                .collect_vec(),
+            vec!["bar scm-1", "foo 1.1.0-1"]
+        );
+    }
+}
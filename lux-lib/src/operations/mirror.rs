@@ -0,0 +1,381 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bon::Builder;
+use futures::StreamExt;
+use itertools::Itertools;
+use reqwest::Client;
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    config::{Config, LuaVersion},
+    manifest::{
+        get_manifest, manifest_entry_file_name, mk_manifest_url, ManifestFromServerError,
+        ManifestLuaError, ManifestRepository,
+    },
+    package::{PackageName, PackageReq, PackageVersion},
+    progress::{MultiProgress, Progress},
+};
+
+/// The number of files to download at the same time.
+const CONCURRENT_DOWNLOADS: usize = 8;
+
+/// Download a copy of a rock server's manifests, rockspecs and rocks into a local directory,
+/// which can itself be served as a rock server, e.g. for machines without internet access.
+///
+/// Files that already exist in the directory are not downloaded again,
+/// so an existing mirror can be updated by mirroring into it again.
+/// The entries of the existing manifests are kept, so that mirroring other packages
+/// into an existing mirror adds them to it.
+/// The manifests are written last, so that they only list files the mirror has.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Mirror<'a> {
+    #[builder(start_fn)]
+    dest_dir: PathBuf,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The server to mirror. Defaults to the configured server.
+    server_url: Option<Url>,
+
+    /// Only mirror the packages that match these requirements.
+    /// If empty, all packages are mirrored.
+    #[builder(default)]
+    packages: Vec<PackageReq>,
+
+    /// Only mirror the latest (matching) version of each package.
+    #[builder(default)]
+    latest: bool,
+
+    /// Also mirror binary rocks. By default, only rockspecs and source rocks are mirrored.
+    #[builder(default)]
+    binaries: bool,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> MirrorBuilder<'_, State>
+where
+    State: mirror_builder::State + mirror_builder::IsComplete,
+{
+    pub async fn mirror(self) -> Result<MirrorReport, MirrorError> {
+        do_mirror(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MirrorError {
+    #[error("cannot mirror {0} in offline mode")]
+    Offline(Url),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Manifest(#[from] ManifestFromServerError),
+    #[error(transparent)]
+    ManifestLua(#[from] ManifestLuaError),
+    #[error("failed to download manifest or rock: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("error parsing rock URL: {0}")]
+    Url(#[from] url::ParseError),
+}
+
+#[derive(Debug)]
+pub struct MirrorReport {
+    packages: usize,
+    downloaded: usize,
+    skipped: usize,
+    failed: Vec<(String, String)>,
+}
+
+impl MirrorReport {
+    /// The number of packages in the mirror's manifests.
+    pub fn packages(&self) -> usize {
+        self.packages
+    }
+
+    /// The number of files that were downloaded.
+    pub fn downloaded(&self) -> usize {
+        self.downloaded
+    }
+
+    /// The number of files that were already mirrored.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// The files that could not be downloaded, and why.
+    /// These are left out of the mirror's manifests.
+    pub fn failed(&self) -> &[(String, String)] {
+        &self.failed
+    }
+}
+
+async fn do_mirror(args: Mirror<'_>) -> Result<MirrorReport, MirrorError> {
+    let config = args.config;
+    let server_url = args.server_url.unwrap_or(config.server().clone());
+    if config.offline() && server_url.scheme() != "file" {
+        return Err(MirrorError::Offline(server_url));
+    }
+    let dest_dir = args.dest_dir;
+    tokio::fs::create_dir_all(&dest_dir).await?;
+
+    let progress = args.progress.unwrap_or(MultiProgress::new_arc());
+    let bar = progress.map(|p| p.new_bar());
+    let client = config.http_client(&server_url)?;
+
+    let manifest_versions = LuaVersion::ALL
+        .iter()
+        .map(LuaVersion::version_compatibility_str)
+        .unique()
+        .collect_vec();
+    // The downloaded manifests are filtered before they are written to the mirror
+    let temp_dir = tempdir::TempDir::new("lux-mirror")?;
+    let mut manifests = Vec::new();
+    for manifest_version in manifest_versions {
+        let url = mk_manifest_url(&server_url, &manifest_version, config)?;
+        bar.map(|b| b.set_message(format!("📥 Downloading manifest from {url}")));
+        let target = temp_dir.path().join(format!("manifest-{manifest_version}"));
+        let content = get_manifest(
+            url,
+            manifest_version.clone(),
+            &target,
+            &client,
+            config,
+            false,
+            &bar,
+        )
+        .await?;
+        let mut repository = ManifestRepository::new(&content)?;
+        filter_repository(&mut repository, &args.packages, args.latest, args.binaries);
+        manifests.push((manifest_version, repository));
+    }
+
+    let mut all = ManifestRepository::default();
+    for (_, repository) in &manifests {
+        all.merge(repository);
+    }
+    let files = all
+        .entries()
+        .map(|(name, version, arch)| manifest_entry_file_name(name, version, arch))
+        .collect_vec();
+
+    bar.map(|b| {
+        b.set_message(format!(
+            "📥 Mirroring {} files from {server_url}",
+            files.len()
+        ));
+        b.set_length(files.len() as u64);
+    });
+    let results = futures::stream::iter(files.into_iter().map(|file_name| {
+        let client = &client;
+        let server_url = &server_url;
+        let dest_dir = &dest_dir;
+        let bar = &bar;
+        async move {
+            let result = mirror_file(&file_name, server_url, dest_dir, client, config).await;
+            bar.map(|b| b.inc(1));
+            (file_name, result)
+        }
+    }))
+    .buffer_unordered(CONCURRENT_DOWNLOADS)
+    .collect::<Vec<_>>()
+    .await;
+    bar.map(|b| b.finish_and_clear());
+
+    let mut downloaded = 0;
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+    for (file_name, result) in results {
+        match result {
+            Ok(true) => downloaded += 1,
+            Ok(false) => skipped += 1,
+            Err(err) => failed.push((file_name, err.to_string())),
+        }
+    }
+    failed.sort();
+
+    let is_mirrored = |name: &PackageName, version: &str, arch: &str| {
+        let file_name = manifest_entry_file_name(name, version, arch);
+        failed
+            .binary_search_by(|(failed, _)| failed.cmp(&file_name))
+            .is_err()
+    };
+    all.retain(is_mirrored);
+    for (manifest_version, mut repository) in manifests {
+        repository.retain(is_mirrored);
+        let manifest_file = format!("manifest-{manifest_version}");
+        let mut mirrored = mirrored_repository(&dest_dir, &manifest_file).await?;
+        mirrored.merge(&repository);
+        tokio::fs::write(dest_dir.join(manifest_file), mirrored.to_lua()).await?;
+    }
+    let mut mirrored = mirrored_repository(&dest_dir, "manifest").await?;
+    mirrored.merge(&all);
+    let all = mirrored;
+    tokio::fs::write(dest_dir.join("manifest"), all.to_lua()).await?;
+
+    Ok(MirrorReport {
+        packages: all.package_count(),
+        downloaded,
+        skipped,
+        failed,
+    })
+}
+
+/// The entries of the mirror's existing `manifest_file`, if any, whose files have been mirrored.
+async fn mirrored_repository(
+    dest_dir: &Path,
+    manifest_file: &str,
+) -> Result<ManifestRepository, MirrorError> {
+    let path = dest_dir.join(manifest_file);
+    if !path.is_file() {
+        return Ok(ManifestRepository::default());
+    }
+    let mut repository = ManifestRepository::new(&tokio::fs::read_to_string(&path).await?)?;
+    repository.retain(|name, version, arch| {
+        dest_dir
+            .join(manifest_entry_file_name(name, version, arch))
+            .is_file()
+    });
+    Ok(repository)
+}
+
+/// Keep the entries that match the package requirements and are rockspecs or source rocks,
+/// or binary rocks, if requested.
+/// If `latest` is set, only the latest version of each package is kept.
+fn filter_repository(
+    repository: &mut ManifestRepository,
+    packages: &[PackageReq],
+    latest: bool,
+    binaries: bool,
+) {
+    repository.retain(|name, version, arch| {
+        let matches_req = packages.is_empty()
+            || packages.iter().any(|req| {
+                req.name() == name
+                    && PackageVersion::parse(version)
+                        .is_ok_and(|version| req.version_req().matches(&version))
+            });
+        let included_arch = matches!(arch, "rockspec" | "src") || binaries;
+        matches_req && included_arch
+    });
+    if latest {
+        let latest_versions: HashMap<PackageName, String> = repository
+            .entries()
+            .filter_map(|(name, version, _)| {
+                Some((
+                    name.clone(),
+                    (PackageVersion::parse(version).ok()?, version),
+                ))
+            })
+            .into_grouping_map()
+            .max_by(|_, (a, _), (b, _)| a.cmp(b))
+            .into_iter()
+            .map(|(name, (_, version))| (name, version.to_string()))
+            .collect();
+        repository.retain(|name, version, _| {
+            latest_versions.get(name).map(String::as_str) == Some(version)
+        });
+    }
+}
+
+/// Download a file from the server into the mirror, unless it has already been mirrored.
+/// Returns whether the file was downloaded.
+async fn mirror_file(
+    file_name: &str,
+    server_url: &Url,
+    dest_dir: &Path,
+    client: &Client,
+    config: &Config,
+) -> Result<bool, MirrorError> {
+    let target = dest_dir.join(file_name);
+    if target.is_file() {
+        return Ok(false);
+    }
+    let url = server_url.join(file_name)?;
+    if let Ok(path) = url.to_file_path() {
+        tokio::fs::copy(path, target).await?;
+        return Ok(true);
+    }
+    let bytes = config
+        .authenticate(&url, client.get(url.clone()))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    // Write to a temporary file first, so that an interrupted mirror
+    // doesn't leave behind a partial file that would be skipped the next time.
+    let partial = dest_dir.join(format!("{file_name}.part"));
+    tokio::fs::write(&partial, &bytes).await?;
+    tokio::fs::rename(&partial, &target).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_latest_matching_versions() {
+        let mut repository = ManifestRepository::default();
+        let foo = PackageName::new("foo".into());
+        let bar = PackageName::new("bar".into());
+        for version in ["1.0.0-1", "1.1.0-1", "2.0.0-1"] {
+            repository.insert(foo.clone(), version.into(), "rockspec".into());
+            repository.insert(foo.clone(), version.into(), "src".into());
+            repository.insert(foo.clone(), version.into(), "linux-x86_64".into());
+        }
+        repository.insert(bar.clone(), "1.0.0-1".into(), "rockspec".into());
+
+        filter_repository(
+            &mut repository,
+            &["foo < 2.0.0".parse().unwrap()],
+            true,
+            false,
+        );
+        assert_eq!(
+            repository
+                .entries()
+                .map(|(name, version, arch)| manifest_entry_file_name(name, version, arch))
+                .collect_vec(),
+            vec!["foo-1.1.0-1.rockspec", "foo-1.1.0-1.src.rock"]
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_mirrored_entries() {
+        let dest_dir = assert_fs::TempDir::new().unwrap();
+        let mut repository = ManifestRepository::default();
+        let foo = PackageName::new("foo".into());
+        let bar = PackageName::new("bar".into());
+        repository.insert(foo.clone(), "1.0.0-1".into(), "rockspec".into());
+        repository.insert(bar.clone(), "1.0.0-1".into(), "rockspec".into());
+        std::fs::write(dest_dir.join("manifest"), repository.to_lua()).unwrap();
+        std::fs::write(dest_dir.join("foo-1.0.0-1.rockspec"), "").unwrap();
+
+        // The entries whose files are missing from the mirror are dropped
+        let mirrored = mirrored_repository(dest_dir.path(), "manifest")
+            .await
+            .unwrap();
+        assert_eq!(
+            mirrored
+                .entries()
+                .map(|(name, version, arch)| manifest_entry_file_name(name, version, arch))
+                .collect_vec(),
+            vec!["foo-1.0.0-1.rockspec"]
+        );
+        assert_eq!(
+            mirrored_repository(dest_dir.path(), "manifest-5.4")
+                .await
+                .unwrap()
+                .package_count(),
+            0
+        );
+    }
+}
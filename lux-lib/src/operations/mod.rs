@@ -1,32 +1,71 @@
 #![allow(ambiguous_glob_reexports)]
 
+mod audit;
 mod build_project;
+mod bump_version;
+mod bundle;
+mod doctor;
 mod download;
 mod exec;
 mod fetch;
+mod gc;
+mod git_package;
+mod index;
 pub mod install;
+mod license;
+mod migrate_tree;
+mod mirror;
 mod pack;
 mod pin;
 mod remove;
 mod resolve;
+mod rock_file;
 mod run;
 mod run_lua;
+mod sbom;
+mod script;
+mod signature;
 mod sync;
 mod test;
+mod test_report;
+mod tool;
 mod unpack;
 mod update;
+mod vendor;
+mod venv;
+mod verify;
 
+pub use audit::*;
 pub use build_project::*;
+pub use bump_version::*;
+pub use bundle::*;
+pub use doctor::*;
 pub use download::*;
 pub use exec::*;
 pub use fetch::*;
+pub use gc::*;
+pub use git_package::*;
+pub use index::*;
 pub use install::*;
+pub use license::*;
+pub use migrate_tree::*;
+pub use mirror::*;
 pub use pack::*;
 pub use pin::*;
 pub use remove::*;
+pub use resolve::*;
+pub use rock_file::*;
 pub use run::*;
 pub use run_lua::*;
+pub use sbom::*;
+pub use script::*;
+pub use signature::*;
 pub use sync::*;
 pub use test::*;
+pub use test_report::*;
+pub use tool::*;
 pub use unpack::*;
 pub use update::*;
+pub use vendor::*;
+pub use venv::*;
+pub use verify::*;
@@ -10,6 +10,9 @@ use crate::luarocks::rock_manifest::RockManifestDoc;
 use crate::luarocks::rock_manifest::RockManifestLib;
 use crate::luarocks::rock_manifest::RockManifestLua;
 use crate::luarocks::rock_manifest::RockManifestRoot;
+use crate::project::project_toml::{ProjectTomlError, RemoteProjectTomlValidationError};
+use crate::project::Project;
+use crate::rockspec::Rockspec;
 use crate::tree::RockLayout;
 use crate::tree::Tree;
 use bon::{builder, Builder};
@@ -143,6 +146,70 @@ async fn do_pack(args: Pack) -> Result<PathBuf, PackError> {
     Ok(output_path)
 }
 
+/// A source rock packer, which bundles a project's generated rockspec
+/// and its source files into a `.src.rock` archive.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct PackSrc<'a> {
+    #[builder(start_fn)]
+    dest_dir: PathBuf,
+    #[builder(start_fn)]
+    project: &'a Project,
+}
+
+impl<State> PackSrcBuilder<'_, State>
+where
+    State: pack_src_builder::State + pack_src_builder::IsComplete,
+{
+    pub async fn pack(self) -> Result<PathBuf, PackSrcError> {
+        do_pack_src(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("failed to pack source rock: {0}")]
+pub enum PackSrcError {
+    Zip(#[from] zip::result::ZipError),
+    Io(#[from] io::Error),
+    RemoteProjectToml(#[from] RemoteProjectTomlValidationError),
+    ProjectToml(#[from] ProjectTomlError),
+}
+
+fn do_pack_src(args: PackSrc<'_>) -> Result<PathBuf, PackSrcError> {
+    let project = args.project;
+    let rockspec = project.toml().into_remote()?;
+    let rockspec_content = rockspec.to_lua_remote_rockspec_string()?;
+    let package_name = format!("{}-{}", rockspec.package(), rockspec.version());
+    let output_path = args.dest_dir.join(format!("{package_name}.src.rock"));
+    let mut zip = ZipWriter::new(File::create(&output_path)?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    // luarocks expects a <package>-<version>.rockspec at the root of the archive
+    zip.start_file(format!("{package_name}.rockspec"), options)?;
+    zip.write_all(rockspec_content.as_bytes())?;
+
+    // If the rockspec specifies a `source.dir`, the sources are expected to be unpacked into it.
+    let source_dir = rockspec
+        .source()
+        .current_platform()
+        .unpack_dir
+        .clone()
+        .unwrap_or_default();
+    let project_root = project.root().to_path_buf();
+    for file in project.project_files() {
+        let relative_path = pathdiff::diff_paths(&file, &project_root)
+            .expect("project file is not in the project root");
+        let mut buffer = Vec::new();
+        File::open(&file)?.read_to_end(&mut buffer)?;
+        #[cfg(target_family = "unix")]
+        let options = options.unix_permissions(file.metadata()?.permissions().mode());
+        zip.start_file(source_dir.join(relative_path).to_string_lossy(), options)?;
+        zip.write_all(&buffer)?;
+    }
+    zip.finish()?;
+    Ok(output_path)
+}
+
 fn is_binary_rock(layout: &RockLayout) -> bool {
     if !&layout.lib.is_dir() {
         return false;
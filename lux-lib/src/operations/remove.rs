@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::io;
 use std::sync::Arc;
 
@@ -22,6 +24,7 @@ pub enum RemoveError {
 pub struct Remove<'a> {
     config: &'a Config,
     packages: Vec<LocalPackageId>,
+    tree: Option<Tree>,
     progress: Option<Arc<Progress<MultiProgress>>>,
 }
 
@@ -33,6 +36,7 @@ impl<'a> Remove<'a> {
         Self {
             config,
             packages: Vec::new(),
+            tree: None,
             progress: None,
         }
     }
@@ -53,6 +57,15 @@ impl<'a> Remove<'a> {
         self.packages(std::iter::once(package))
     }
 
+    /// Remove the packages from this tree.
+    /// Defaults to the user tree.
+    pub fn tree(self, tree: Tree) -> Self {
+        Self {
+            tree: Some(tree),
+            ..self
+        }
+    }
+
     /// Pass a `MultiProgress` to this installer.
     /// By default, a new one will be created.
     pub fn progress(self, progress: Arc<Progress<MultiProgress>>) -> Self {
@@ -68,14 +81,18 @@ impl<'a> Remove<'a> {
             Some(p) => p,
             None => MultiProgress::new_arc(),
         };
-        let tree = self
-            .config
-            .user_tree(LuaVersion::from(self.config)?.clone())?;
+        let tree = match self.tree {
+            Some(tree) => tree,
+            None => self
+                .config
+                .user_tree(LuaVersion::from(self.config)?.clone())?,
+        };
         remove(self.packages, tree, &Arc::clone(&progress)).await
     }
 }
 
 // TODO: Remove dependencies recursively too!
+#[tracing::instrument(skip_all, fields(packages = package_ids.len(), tree = %tree.root().display()))]
 async fn remove(
     package_ids: Vec<LocalPackageId>,
     tree: Tree,
@@ -89,13 +106,28 @@ async fn remove(
         .cloned()
         .collect_vec();
 
+    // Executables that are also provided by packages that are kept must not be removed.
+    let kept_binaries: Arc<HashSet<OsString>> = Arc::new(
+        lockfile
+            .rocks()
+            .values()
+            .filter(|package| !package_ids.contains(&package.id()))
+            .flat_map(|package| package.spec.binaries())
+            .filter_map(|binary| binary.file_name().map(OsString::from))
+            .collect(),
+    );
+
     join_all(packages.into_iter().map(|package| {
         let bar = progress.map(|p| p.new_bar());
 
         let tree = tree.clone();
-        tokio::spawn(remove_package(package, tree, bar))
+        let kept_binaries = Arc::clone(&kept_binaries);
+        tokio::spawn(remove_package(package, tree, kept_binaries, bar))
     }))
-    .await;
+    .await
+    .into_iter()
+    .flatten()
+    .try_collect::<_, Vec<_>, _>()?;
 
     lockfile.map_then_flush(|lockfile| {
         package_ids
@@ -105,12 +137,16 @@ async fn remove(
         Ok::<_, io::Error>(())
     })?;
 
+    tree.write_luarocks_manifest()?;
+
     Ok(())
 }
 
+#[tracing::instrument(skip_all, fields(package = %package.name(), version = %package.version()))]
 async fn remove_package(
     package: LocalPackage,
     tree: Tree,
+    kept_binaries: Arc<HashSet<OsString>>,
     bar: Progress<ProgressBar>,
 ) -> Result<(), RemoveError> {
     bar.map(|p| {
@@ -122,14 +158,21 @@ async fn remove_package(
     });
 
     let rock_layout = tree.installed_rock_layout(&package)?;
-    tokio::fs::remove_dir_all(&rock_layout.etc).await?;
-    tokio::fs::remove_dir_all(&rock_layout.rock_path).await?;
+    if rock_layout.etc.is_dir() {
+        tokio::fs::remove_dir_all(&rock_layout.etc).await?;
+    }
+    if rock_layout.rock_path.is_dir() {
+        tokio::fs::remove_dir_all(&rock_layout.rock_path).await?;
+    }
 
     // Delete the corresponding binaries attached to the current package (located under `{LUX_TREE}/bin/`)
     for relative_binary_path in package.spec.binaries() {
         let binary_file_name = relative_binary_path
             .file_name()
             .expect("malformed lockfile");
+        if kept_binaries.contains(binary_file_name) {
+            continue;
+        }
 
         let binary_path = tree.bin().join(binary_file_name);
         if binary_path.is_file() {
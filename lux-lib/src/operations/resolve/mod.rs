@@ -0,0 +1,519 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use async_recursion::async_recursion;
+use futures::future::{join_all, try_join_all};
+use itertools::Itertools;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::Instrument;
+
+use crate::{
+    build::{plugin::plugin_name, BuildBehaviour},
+    config::Config,
+    lockfile::{
+        LocalPackageId, LocalPackageSpec, Lockfile, LockfilePermissions, OptState, PinnedState,
+    },
+    lua_rockspec::BuildBackendSpec,
+    package::{PackageName, PackageReq, PackageSpec, PackageVersion},
+    progress::{MultiProgress, Progress, ProgressBar},
+    remote_package_db::RemotePackageDB,
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree,
+};
+
+use super::{
+    Download, PackageInstallSpec, PrefetchedRock, RemoteRockDownload, SearchAndDownloadError,
+};
+
+mod version_solver;
+
+pub use version_solver::*;
+
+#[derive(Clone, Debug)]
+pub(crate) struct PackageInstallData {
+    pub build_behaviour: BuildBehaviour,
+    pub pin: PinnedState,
+    pub opt: OptState,
+    pub downloaded_rock: RemoteRockDownload,
+    pub spec: LocalPackageSpec,
+    pub entry_type: tree::EntryType,
+}
+
+#[async_recursion]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(packages = packages.len()))]
+pub(crate) async fn get_all_dependencies<P>(
+    dependencies_tx: UnboundedSender<PackageInstallData>,
+    build_dependencies_tx: UnboundedSender<PackageInstallData>,
+    packages: Vec<PackageInstallSpec>,
+    package_db: Arc<RemotePackageDB>,
+    lockfile: Arc<Lockfile<P>>,
+    build_lockfile: Arc<Lockfile<P>>,
+    patches: Arc<HashMap<PackageName, LuaDependencySpec>>,
+    resolved: Arc<Resolution>,
+    config: &Config,
+    progress: Arc<Progress<MultiProgress>>,
+) -> Result<Vec<LocalPackageId>, SearchAndDownloadError>
+where
+    P: LockfilePermissions + Send + Sync + 'static,
+{
+    join_all(
+        packages
+            .into_iter()
+            // Exclude packages that are already installed
+            .filter(
+                |PackageInstallSpec {
+                     package,
+                     build_behaviour,
+                     ..
+                 }| {
+                    *build_behaviour == BuildBehaviour::Force
+                        || lockfile.has_rock(package, None).is_none()
+                },
+            )
+            .map(
+                // NOTE: we propagate build_behaviour, pin and opt to all dependencies
+                |PackageInstallSpec {
+                     package,
+                     build_behaviour,
+                     pin,
+                     opt,
+                     entry_type,
+                     constraint,
+                     source,
+                     prefetched,
+                 }| {
+                    let config = config.clone();
+                    let dependencies_tx = dependencies_tx.clone();
+                    let build_dependencies_tx = build_dependencies_tx.clone();
+                    let package_db = Arc::clone(&package_db);
+                    let progress = Arc::clone(&progress);
+                    let build_dep_progress = Arc::clone(&progress);
+                    let lockfile = Arc::clone(&lockfile);
+                    let build_lockfile = Arc::clone(&build_lockfile);
+                    let patches = Arc::clone(&patches);
+                    let resolved = Arc::clone(&resolved);
+
+                    // Replace patched packages with their patch source,
+                    // retaining the original constraint.
+                    let patch = match &source {
+                        None => patches.get(package.name()).cloned(),
+                        Some(_) => None,
+                    };
+                    let (package, source, constraint) = match patch {
+                        Some(patch) => {
+                            let constraint =
+                                constraint.unwrap_or(package.version_req().clone().into());
+                            (
+                                patch.package_req().clone(),
+                                patch.source().clone(),
+                                Some(constraint),
+                            )
+                        }
+                        None => (package, source, constraint),
+                    };
+
+                    // Pin the package to the version selected by the version solver,
+                    // retaining the original constraint.
+                    let (package, constraint) = match resolved.version(package.name()) {
+                        Some(version)
+                            if source.is_none() && package.version_req().matches(version) =>
+                        {
+                            let constraint =
+                                constraint.unwrap_or(package.version_req().clone().into());
+                            (
+                                PackageSpec::new(package.name().clone(), version.clone())
+                                    .into_package_req(),
+                                Some(constraint),
+                            )
+                        }
+                        _ => (package, constraint),
+                    };
+
+                    // The rock may already have been downloaded by the version solver.
+                    let resolved_download = match &source {
+                        None => resolved.download(&package),
+                        Some(_) => None,
+                    };
+
+                    tokio::spawn(
+                        async move {
+                            let bar = progress.map(|p| p.new_bar());
+
+                            let downloaded_rock = if let Some(download) = resolved_download {
+                                download
+                            } else if let Some(PrefetchedRock(download)) = prefetched {
+                                download
+                            } else if let Some(source) = source {
+                                RemoteRockDownload::from_package_req_and_source_spec(
+                                    package.clone(),
+                                    source,
+                                    &config,
+                                    &bar,
+                                )
+                                .await?
+                            } else {
+                                Download::new(&package, &config, &bar)
+                                    .package_db(&package_db)
+                                    .download_remote_rock()
+                                    .await?
+                            };
+
+                            let constraint =
+                                constraint.unwrap_or(package.version_req().clone().into());
+
+                            let rockspec = downloaded_rock.rockspec();
+                            tracing::debug!(
+                                package = %rockspec.package(),
+                                version = %rockspec.version(),
+                                "resolving dependencies"
+                            );
+
+                            // NOTE: We don't need to install build dependencies to install binary rocks.
+                            if !matches!(downloaded_rock, RemoteRockDownload::BinaryRock { .. }) {
+                                let build_dependencies = rockspec
+                                    .build_dependencies()
+                                    .current_platform()
+                                    .iter()
+                                    .map(|dep| {
+                                        // We always install build dependencies as entrypoints
+                                        // with regard to the build tree
+                                        let entry_type = tree::EntryType::Entrypoint;
+                                        PackageInstallSpec::new(
+                                            dep.package_req().clone(),
+                                            entry_type,
+                                        )
+                                        .build_behaviour(build_behaviour)
+                                        .pin(pin)
+                                        .opt(opt)
+                                        .maybe_source(dep.source().clone())
+                                        .build()
+                                    })
+                                    .chain(build_plugin(rockspec, &package_db).map(|plugin| {
+                                        PackageInstallSpec::new(plugin, tree::EntryType::Entrypoint)
+                                            .build_behaviour(build_behaviour)
+                                            .pin(pin)
+                                            .opt(opt)
+                                            .build()
+                                    }))
+                                    .collect_vec();
+
+                                // NOTE: We treat transitive regular dependencies of build dependencies
+                                // as build dependencies
+                                get_all_dependencies(
+                                    build_dependencies_tx.clone(),
+                                    build_dependencies_tx.clone(),
+                                    build_dependencies,
+                                    package_db.clone(),
+                                    build_lockfile.clone(),
+                                    build_lockfile.clone(),
+                                    patches.clone(),
+                                    // Build dependencies are resolved independently
+                                    Arc::new(Resolution::default()),
+                                    &config,
+                                    build_dep_progress,
+                                )
+                                .await?;
+                            }
+
+                            let dependencies = rockspec
+                                .dependencies()
+                                .current_platform()
+                                .iter()
+                                .map(|dep| {
+                                    // If we're forcing a rebuild, retain the `EntryType`
+                                    // of existing dependencies
+                                    let entry_type = if build_behaviour == BuildBehaviour::Force
+                                        && lockfile.has_rock(dep.package_req(), None).is_some_and(
+                                            |installed_rock| {
+                                                lockfile.is_entrypoint(&installed_rock.id())
+                                            },
+                                        ) {
+                                        tree::EntryType::Entrypoint
+                                    } else {
+                                        tree::EntryType::DependencyOnly
+                                    };
+
+                                    PackageInstallSpec::new(dep.package_req().clone(), entry_type)
+                                        .build_behaviour(build_behaviour)
+                                        .pin(pin)
+                                        .opt(opt)
+                                        .maybe_source(dep.source().clone())
+                                        .build()
+                                })
+                                .collect_vec();
+
+                            let dependencies = get_all_dependencies(
+                                dependencies_tx.clone(),
+                                build_dependencies_tx,
+                                dependencies,
+                                package_db,
+                                lockfile,
+                                build_lockfile,
+                                patches,
+                                resolved,
+                                &config,
+                                progress,
+                            )
+                            .await?;
+
+                            let rockspec = downloaded_rock.rockspec();
+                            let local_spec = LocalPackageSpec::new(
+                                rockspec.package(),
+                                rockspec.version(),
+                                constraint,
+                                dependencies,
+                                &pin,
+                                &opt,
+                                rockspec.binaries(),
+                            );
+
+                            let install_spec = PackageInstallData {
+                                build_behaviour,
+                                pin,
+                                opt,
+                                spec: local_spec.clone(),
+                                downloaded_rock,
+                                entry_type,
+                            };
+
+                            dependencies_tx.send(install_spec).unwrap();
+
+                            Ok::<_, SearchAndDownloadError>(local_spec.id())
+                        }
+                        .in_current_span(),
+                    )
+                },
+            ),
+    )
+    .await
+    .into_iter()
+    .flatten()
+    .try_collect()
+}
+
+/// Candidate versions from the remote package database
+/// and dependencies from downloaded rockspecs.
+struct PackageDbProvider<'a> {
+    package_db: &'a RemotePackageDB,
+    /// Installed versions are preferred, to avoid unnecessary reinstalls.
+    installed: HashMap<PackageName, Vec<PackageVersion>>,
+    /// Prefer the lowest versions instead of the latest ones.
+    minimal_versions: bool,
+    dependencies: HashMap<(PackageName, PackageVersion), Vec<PackageReq>>,
+}
+
+impl DependencyProvider for PackageDbProvider<'_> {
+    fn versions(&self, name: &PackageName) -> Vec<PackageVersion> {
+        let available = self.package_db.versions(name);
+        if self.minimal_versions {
+            return available.into_iter().rev().collect_vec();
+        }
+        self.installed
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|version| available.contains(version))
+            .cloned()
+            .chain(available.iter().cloned())
+            .unique()
+            .collect_vec()
+    }
+
+    fn dependencies(&self, package: &PackageSpec) -> Option<&Vec<PackageReq>> {
+        self.dependencies
+            .get(&(package.name().clone(), package.version().clone()))
+    }
+}
+
+/// The `lux-build-<type>` rock that provides the build backend of the rockspec, if any.
+/// Plugin executables on the `PATH` take precedence over plugin rocks.
+fn build_plugin(rockspec: &impl Rockspec, package_db: &RemotePackageDB) -> Option<PackageReq> {
+    match &rockspec.build().current_platform().build_backend {
+        Some(BuildBackendSpec::LuaRock(build_type)) => {
+            let name = plugin_name(build_type);
+            (which::which(name.to_string()).is_err() && !package_db.versions(&name).is_empty())
+                .then(|| name.into())
+        }
+        _ => None,
+    }
+}
+
+/// The versions selected by [`resolve_versions`],
+/// with the rocks that were downloaded to look up their dependencies.
+#[derive(Default)]
+pub(crate) struct Resolution {
+    versions: HashMap<PackageName, PackageVersion>,
+    downloads: HashMap<(PackageName, PackageVersion), RemoteRockDownload>,
+}
+
+impl Resolution {
+    fn version(&self, name: &PackageName) -> Option<&PackageVersion> {
+        self.versions.get(name)
+    }
+
+    /// The rock that was downloaded while resolving the `package`, if any.
+    fn download(&self, package: &PackageReq) -> Option<RemoteRockDownload> {
+        let version = self.versions.get(package.name())?;
+        package
+            .version_req()
+            .matches(version)
+            .then(|| {
+                self.downloads
+                    .get(&(package.name().clone(), version.clone()))
+                    .cloned()
+            })
+            .flatten()
+    }
+}
+
+/// Select a single version for each of the `packages` and their transitive dependencies,
+/// backtracking across version choices if necessary.
+///
+/// Packages in `skip` (e.g. patched packages) and dependencies with a custom source
+/// are not resolved.
+#[tracing::instrument(skip_all, fields(packages = packages.len()))]
+pub(crate) async fn resolve_versions<P: LockfilePermissions>(
+    packages: &[PackageReq],
+    skip: &HashSet<PackageName>,
+    package_db: &RemotePackageDB,
+    lockfile: &Lockfile<P>,
+    config: &Config,
+    progress: &Progress<ProgressBar>,
+) -> Result<Resolution, ResolutionError> {
+    let mut provider = PackageDbProvider {
+        package_db,
+        installed: lockfile
+            .rocks()
+            .values()
+            .map(|package| (package.name().clone(), package.version().clone()))
+            .into_group_map(),
+        minimal_versions: config.minimal_versions(),
+        dependencies: HashMap::new(),
+    };
+    let packages = packages
+        .iter()
+        .filter(|package| !skip.contains(package.name()))
+        .cloned()
+        .collect_vec();
+    // The downloads are reused when installing the selected versions.
+    let mut downloads = HashMap::new();
+    loop {
+        match VersionSolver::new(&provider).solve(&packages) {
+            Ok(versions) => {
+                tracing::debug!(packages = versions.len(), "selected versions");
+                downloads.retain(|(name, version), _| versions.get(name) == Some(version));
+                return Ok(Resolution {
+                    versions: versions.into_iter().collect(),
+                    downloads,
+                });
+            }
+            Err(SolveError::Conflict(conflict)) => return Err(ResolutionError::Conflict(conflict)),
+            Err(SolveError::MissingDependencies(missing)) => {
+                tracing::debug!(missing = %missing.iter().join(", "), "fetching the dependencies of candidate versions");
+                progress
+                    .map(|p| p.set_message(format!("🧩 Resolving {}", missing.iter().join(", "))));
+                let downloaded = try_join_all(missing.into_iter().map(|package| async move {
+                    let download =
+                        Download::new(&package.clone().into_package_req(), config, progress)
+                            .package_db(package_db)
+                            .download_remote_rock()
+                            .await?;
+                    Ok::<_, SearchAndDownloadError>((package, download))
+                }))
+                .await?;
+                for (package, download) in downloaded {
+                    let dependencies = download
+                        .rockspec()
+                        .dependencies()
+                        .current_platform()
+                        .iter()
+                        .filter(|dep| dep.source().is_none() && !skip.contains(dep.name()))
+                        .map(|dep| dep.package_req().clone())
+                        .collect_vec();
+                    let key = (package.name().clone(), package.version().clone());
+                    provider.dependencies.insert(key.clone(), dependencies);
+                    downloads.insert(key, download);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use url::Url;
+
+    use crate::manifest::{Manifest, ManifestMetadata};
+
+    use super::*;
+
+    fn package_db() -> RemotePackageDB {
+        let manifest = r#"
+            repository = {
+                foo = {
+                    ["1.0.0-1"] = { { arch = "rockspec" } },
+                    ["2.0.0-1"] = { { arch = "rockspec" } },
+                },
+                bar = {
+                    ["1.0.0-1"] = { { arch = "rockspec" } },
+                    ["1.5.0-1"] = { { arch = "rockspec" } },
+                    ["2.0.0-1"] = { { arch = "rockspec" } },
+                },
+            }
+        "#
+        .to_string();
+        Manifest::new(
+            Url::parse("https://luarocks.org/").unwrap(),
+            ManifestMetadata::new(&manifest).unwrap(),
+        )
+        .into()
+    }
+
+    fn solve(provider: &PackageDbProvider) -> BTreeMap<PackageName, PackageVersion> {
+        let packages = vec!["foo".parse().unwrap()];
+        match VersionSolver::new(provider).solve(&packages) {
+            Ok(solution) => solution,
+            Err(_) => panic!("failed to resolve foo"),
+        }
+    }
+
+    #[test]
+    fn minimal_versions() {
+        let package_db = package_db();
+        let dependencies = HashMap::from([
+            (
+                ("foo".into(), "1.0.0-1".parse().unwrap()),
+                vec!["bar >= 1.5".parse().unwrap()],
+            ),
+            (
+                ("foo".into(), "2.0.0-1".parse().unwrap()),
+                vec!["bar >= 2.0".parse().unwrap()],
+            ),
+            (("bar".into(), "1.0.0-1".parse().unwrap()), Vec::new()),
+            (("bar".into(), "1.5.0-1".parse().unwrap()), Vec::new()),
+            (("bar".into(), "2.0.0-1".parse().unwrap()), Vec::new()),
+        ]);
+        let mut provider = PackageDbProvider {
+            package_db: &package_db,
+            installed: HashMap::from([("bar".into(), vec!["2.0.0-1".parse().unwrap()])]),
+            minimal_versions: true,
+            dependencies,
+        };
+
+        // The lowest versions that satisfy all constraints are selected,
+        // even if other versions are installed.
+        let solution = solve(&provider);
+        assert_eq!(solution[&"foo".into()].to_string(), "1.0.0-1");
+        assert_eq!(solution[&"bar".into()].to_string(), "1.5.0-1");
+
+        provider.minimal_versions = false;
+        let solution = solve(&provider);
+        assert_eq!(solution[&"foo".into()].to_string(), "2.0.0-1");
+        assert_eq!(solution[&"bar".into()].to_string(), "2.0.0-1");
+    }
+}
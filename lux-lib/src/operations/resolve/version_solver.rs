@@ -0,0 +1,602 @@
+//! A backtracking version solver, which selects a single version of each package
+//! such that all version constraints are satisfied.
+//!
+//! The solver makes decisions for the most constrained packages first and, when it runs into
+//! a conflict, backjumps to the most recent decision that contributed to it,
+//! so that it can backtrack across version choices instead of failing on the first conflict.
+//! Unlike PubGrub, it does not learn from conflicts, so it may explore
+//! the same dead ends more than once.
+//! If there is no solution, the conflict that ruled out the preferred versions is reported.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::package::{PackageName, PackageReq, PackageSpec, PackageVersion, PackageVersionReq};
+
+use super::super::SearchAndDownloadError;
+
+/// Provides the candidate versions and the dependencies of packages to the [`VersionSolver`].
+pub(crate) trait DependencyProvider {
+    /// The candidate versions of a package, in order of preference.
+    fn versions(&self, name: &PackageName) -> Vec<PackageVersion>;
+
+    /// The dependencies of a package, or `None` if they are not known yet.
+    fn dependencies(&self, package: &PackageSpec) -> Option<&Vec<PackageReq>>;
+}
+
+/// Who requires a version of a package.
+#[derive(Clone, Debug)]
+pub enum Requirer {
+    /// The packages that were requested to be installed.
+    Root,
+    Package(PackageSpec),
+}
+
+impl Display for Requirer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Root => f.write_str("root"),
+            Self::Package(package) => package.fmt(f),
+        }
+    }
+}
+
+impl Requirer {
+    fn name(&self) -> Option<&PackageName> {
+        match self {
+            Self::Root => None,
+            Self::Package(package) => Some(package.name()),
+        }
+    }
+}
+
+/// A version requirement on a package.
+#[derive(Clone, Debug)]
+pub struct Requirement {
+    version_req: PackageVersionReq,
+    required_by: Requirer,
+}
+
+impl Requirement {
+    pub fn version_req(&self) -> &PackageVersionReq {
+        &self.version_req
+    }
+
+    pub fn required_by(&self) -> &Requirer {
+        &self.required_by
+    }
+}
+
+/// A set of requirements on a package that no available version satisfies.
+#[derive(Clone, Debug)]
+pub struct VersionConflict {
+    package: PackageName,
+    requirements: Vec<Requirement>,
+    /// The available versions of the package, from the most to the least preferred.
+    available: Vec<PackageVersion>,
+    /// The packages that contributed to the conflict, used for backjumping.
+    culprits: HashSet<PackageName>,
+}
+
+impl VersionConflict {
+    /// The package for which no version could be selected.
+    pub fn package(&self) -> &PackageName {
+        &self.package
+    }
+
+    /// The requirements on the package that could not be satisfied together.
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    pub fn available_versions(&self) -> &[PackageVersion] {
+        &self.available
+    }
+
+    /// Requirements that, if relaxed, would allow a version that satisfies all other requirements.
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        self.requirements
+            .iter()
+            .enumerate()
+            .filter_map(|(i, requirement)| {
+                let versions = self
+                    .available
+                    .iter()
+                    .filter(|version| {
+                        !requirement.version_req.matches(version)
+                            && self
+                                .requirements
+                                .iter()
+                                .enumerate()
+                                .all(|(j, other)| i == j || other.version_req.matches(version))
+                    })
+                    .cloned()
+                    .collect_vec();
+                if versions.is_empty() {
+                    None
+                } else {
+                    Some(Suggestion {
+                        package: self.package.clone(),
+                        requirement: requirement.clone(),
+                        versions,
+                    })
+                }
+            })
+            .unique_by(|suggestion| suggestion.to_string())
+            .collect_vec()
+    }
+
+    /// A one-line summary of the conflict.
+    pub fn summary(&self) -> String {
+        let requirements = self
+            .requirements
+            .iter()
+            .map(|requirement| {
+                format!(
+                    "{} requires {}",
+                    requirement.required_by,
+                    fmt_req(&self.package, &requirement.version_req)
+                )
+            })
+            .collect_vec();
+        match requirements.as_slice() {
+            [] => format!("no versions of {} are available", self.package),
+            [requirement] => format!(
+                "{requirement}, but no matching version of {} is available",
+                self.package
+            ),
+            [init @ .., last] => format!("{}, but {last}", init.join(", ")),
+        }
+    }
+}
+
+/// A constraint relaxation that would resolve a [`VersionConflict`].
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    package: PackageName,
+    requirement: Requirement,
+    /// The versions the relaxed requirement would allow, from the most to the least preferred.
+    versions: Vec<PackageVersion>,
+}
+
+impl Suggestion {
+    /// The requirement to relax.
+    pub fn requirement(&self) -> &Requirement {
+        &self.requirement
+    }
+
+    pub fn versions(&self) -> &[PackageVersion] {
+        &self.versions
+    }
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = self.versions.first().expect("suggestion without versions");
+        match &self.requirement.required_by {
+            Requirer::Root => write!(
+                f,
+                "change the requested constraint `{}` to allow {} {version}",
+                fmt_req(&self.package, &self.requirement.version_req),
+                self.package,
+            ),
+            Requirer::Package(package) => write!(
+                f,
+                "relax the constraint `{}` of {package} to allow {} {version}, \
+                or try a different version of {}",
+                fmt_req(&self.package, &self.requirement.version_req),
+                self.package,
+                package.name(),
+            ),
+        }
+    }
+}
+
+impl Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        if !self.requirements.is_empty() {
+            writeln!(f, "  conflicting constraints on {}:", self.package)?;
+            for requirement in &self.requirements {
+                writeln!(
+                    f,
+                    "    {} (required by {})",
+                    fmt_req(&self.package, &requirement.version_req),
+                    requirement.required_by
+                )?;
+            }
+        }
+        if !self.available.is_empty() {
+            writeln!(
+                f,
+                "  available versions: {}",
+                self.available.iter().join(", ")
+            )?;
+        }
+        for suggestion in self.suggestions() {
+            writeln!(f, "  help: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_req(name: &PackageName, version_req: &PackageVersionReq) -> String {
+    if version_req.is_any() {
+        name.to_string()
+    } else {
+        format!("{name} {version_req}")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ResolutionError {
+    #[error("failed to resolve dependencies: {0}")]
+    Conflict(VersionConflict),
+    #[error(transparent)]
+    SearchAndDownload(#[from] SearchAndDownloadError),
+}
+
+#[derive(Debug)]
+pub(crate) enum SolveError {
+    Conflict(VersionConflict),
+    /// The solver needs the dependencies of packages that the [`DependencyProvider`]
+    /// doesn't know yet. The first package is the one the solver got stuck on,
+    /// the others are likely to be needed next, so that they can be fetched together.
+    MissingDependencies(Vec<PackageSpec>),
+}
+
+#[derive(Clone, Default)]
+struct State {
+    decisions: BTreeMap<PackageName, PackageVersion>,
+    requirements: HashMap<PackageName, Vec<Requirement>>,
+}
+
+impl State {
+    fn require(&mut self, package: &PackageReq, required_by: Requirer) {
+        self.requirements
+            .entry(package.name().clone())
+            .or_default()
+            .push(Requirement {
+                version_req: package.version_req().clone(),
+                required_by,
+            });
+    }
+
+    /// The packages that (transitively) caused the requirements on the `package`.
+    fn culprits(&self, package: &PackageName) -> HashSet<PackageName> {
+        let mut culprits = HashSet::new();
+        let mut stack = vec![package.clone()];
+        while let Some(name) = stack.pop() {
+            if !culprits.insert(name.clone()) {
+                continue;
+            }
+            for requirement in self.requirements.get(&name).into_iter().flatten() {
+                if let Some(requirer) = requirement.required_by.name() {
+                    stack.push(requirer.clone());
+                }
+            }
+        }
+        culprits
+    }
+
+    fn conflict(&self, package: &PackageName, available: Vec<PackageVersion>) -> VersionConflict {
+        VersionConflict {
+            package: package.clone(),
+            requirements: self.requirements.get(package).cloned().unwrap_or_default(),
+            available,
+            culprits: self.culprits(package),
+        }
+    }
+}
+
+pub(crate) struct VersionSolver<'a, P: DependencyProvider> {
+    provider: &'a P,
+}
+
+impl<'a, P: DependencyProvider> VersionSolver<'a, P> {
+    pub(crate) fn new(provider: &'a P) -> Self {
+        Self { provider }
+    }
+
+    /// Select a version for each of the `packages` and their transitive dependencies.
+    pub(crate) fn solve(
+        &self,
+        packages: &[PackageReq],
+    ) -> Result<BTreeMap<PackageName, PackageVersion>, SolveError> {
+        let mut state = State::default();
+        for package in packages {
+            state.require(package, Requirer::Root);
+        }
+        Ok(self.solve_state(state)?.decisions)
+    }
+
+    fn candidates(&self, state: &State, name: &PackageName) -> Vec<PackageVersion> {
+        let requirements = state.requirements.get(name).into_iter().flatten();
+        self.provider
+            .versions(name)
+            .into_iter()
+            .filter(|version| {
+                requirements
+                    .clone()
+                    .all(|requirement| requirement.version_req.matches(version))
+            })
+            .collect_vec()
+    }
+
+    /// The `package` and the preferred candidates of the other undecided packages
+    /// whose dependencies are not known yet.
+    fn missing_dependencies(&self, state: &State, package: PackageSpec) -> Vec<PackageSpec> {
+        let mut missing = vec![package];
+        for name in state
+            .requirements
+            .keys()
+            .filter(|name| !state.decisions.contains_key(*name))
+            .sorted()
+        {
+            if let Some(version) = self.candidates(state, name).into_iter().next() {
+                let candidate = PackageSpec::new(name.clone(), version);
+                if self.provider.dependencies(&candidate).is_none()
+                    && !missing.iter().any(|package| {
+                        package.name() == candidate.name()
+                            && package.version() == candidate.version()
+                    })
+                {
+                    missing.push(candidate);
+                }
+            }
+        }
+        missing
+    }
+
+    fn solve_state(&self, state: State) -> Result<State, SolveError> {
+        // Decide the most constrained package first, so that conflicts are found early.
+        let next = state
+            .requirements
+            .keys()
+            .filter(|name| !state.decisions.contains_key(*name))
+            .map(|name| (name.clone(), self.candidates(&state, name)))
+            .min_by(|(name_a, candidates_a), (name_b, candidates_b)| {
+                candidates_a
+                    .len()
+                    .cmp(&candidates_b.len())
+                    .then_with(|| name_a.cmp(name_b))
+            });
+        let (name, candidates) = match next {
+            Some(next) => next,
+            None => return Ok(state),
+        };
+        if candidates.is_empty() {
+            return Err(SolveError::Conflict(
+                state.conflict(&name, self.provider.versions(&name)),
+            ));
+        }
+
+        let mut first_conflict: Option<VersionConflict> = None;
+        let mut culprits = HashSet::new();
+        for version in candidates {
+            let package = PackageSpec::new(name.clone(), version.clone());
+            let dependencies = match self.provider.dependencies(&package) {
+                Some(dependencies) => dependencies,
+                None => {
+                    return Err(SolveError::MissingDependencies(
+                        self.missing_dependencies(&state, package),
+                    ))
+                }
+            };
+
+            let mut next_state = state.clone();
+            next_state.decisions.insert(name.clone(), version);
+            for dependency in dependencies {
+                next_state.require(dependency, Requirer::Package(package.clone()));
+            }
+            // Check the new requirements against the decisions that have already been made.
+            let conflict = dependencies
+                .iter()
+                .find(|dependency| {
+                    next_state
+                        .decisions
+                        .get(dependency.name())
+                        .is_some_and(|decided| !dependency.version_req().matches(decided))
+                })
+                .map(|dependency| {
+                    next_state
+                        .conflict(dependency.name(), self.provider.versions(dependency.name()))
+                });
+            let result = match conflict {
+                Some(conflict) => Err(SolveError::Conflict(conflict)),
+                None => self.solve_state(next_state),
+            };
+            match result {
+                Ok(state) => return Ok(state),
+                Err(SolveError::Conflict(conflict)) => {
+                    if !conflict.culprits.contains(&name) {
+                        // This decision didn't contribute to the conflict,
+                        // so trying another version won't resolve it.
+                        return Err(SolveError::Conflict(conflict));
+                    }
+                    culprits.extend(conflict.culprits.iter().cloned());
+                    first_conflict.get_or_insert(conflict);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // None of the candidates worked, so whatever caused the requirements
+        // on this package is to blame.
+        let mut conflict = first_conflict.expect("no candidates were tried");
+        culprits.remove(&name);
+        culprits.extend(state.culprits(&name));
+        conflict.culprits = culprits;
+        Err(SolveError::Conflict(conflict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestProvider {
+        packages: HashMap<PackageName, Vec<(PackageVersion, Vec<PackageReq>)>>,
+    }
+
+    impl TestProvider {
+        fn add(mut self, package: &str, version: &str, dependencies: &[&str]) -> Self {
+            let dependencies = dependencies
+                .iter()
+                .map(|dependency| dependency.parse().unwrap())
+                .collect_vec();
+            self.packages
+                .entry(package.into())
+                .or_default()
+                .push((version.parse().unwrap(), dependencies));
+            self
+        }
+    }
+
+    impl DependencyProvider for TestProvider {
+        fn versions(&self, name: &PackageName) -> Vec<PackageVersion> {
+            self.packages
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|(version, _)| version.clone())
+                .sorted_by(|a, b| b.cmp(a))
+                .collect_vec()
+        }
+
+        fn dependencies(&self, package: &PackageSpec) -> Option<&Vec<PackageReq>> {
+            self.packages
+                .get(package.name())?
+                .iter()
+                .find(|(version, _)| version == package.version())
+                .map(|(_, dependencies)| dependencies)
+        }
+    }
+
+    fn solve(
+        provider: &TestProvider,
+        packages: &[&str],
+    ) -> Result<BTreeMap<PackageName, PackageVersion>, SolveError> {
+        let packages = packages
+            .iter()
+            .map(|package| package.parse().unwrap())
+            .collect_vec();
+        VersionSolver::new(provider).solve(&packages)
+    }
+
+    fn version(solution: &BTreeMap<PackageName, PackageVersion>, name: &str) -> String {
+        solution[&PackageName::from(name)].to_string()
+    }
+
+    #[test]
+    fn selects_latest_versions() {
+        let provider = TestProvider::default()
+            .add("foo", "1.0.0-1", &["bar >= 1.0"])
+            .add("foo", "2.0.0-1", &["bar >= 2.0"])
+            .add("bar", "1.0.0-1", &[])
+            .add("bar", "2.0.0-1", &[]);
+        let solution = solve(&provider, &["foo"]).unwrap();
+        assert_eq!(version(&solution, "foo"), "2.0.0-1");
+        assert_eq!(version(&solution, "bar"), "2.0.0-1");
+    }
+
+    #[test]
+    fn backtracks_across_versions() {
+        // The latest foo requires bar >= 2, which conflicts with baz,
+        // so the solver has to fall back to foo 1.0.0.
+        let provider = TestProvider::default()
+            .add("foo", "1.0.0-1", &["bar >= 1.0"])
+            .add("foo", "2.0.0-1", &["bar >= 2.0"])
+            .add("bar", "1.0.0-1", &[])
+            .add("bar", "2.0.0-1", &[])
+            .add("baz", "1.0.0-1", &["bar < 2.0"]);
+        let solution = solve(&provider, &["foo", "baz"]).unwrap();
+        assert_eq!(version(&solution, "foo"), "1.0.0-1");
+        assert_eq!(version(&solution, "bar"), "1.0.0-1");
+        assert_eq!(version(&solution, "baz"), "1.0.0-1");
+    }
+
+    #[test]
+    fn explains_conflicts() {
+        let provider = TestProvider::default()
+            .add("foo", "1.2.0-1", &["bar < 2.0"])
+            .add("bar", "1.0.0-1", &[])
+            .add("bar", "2.0.0-1", &[])
+            .add("baz", "1.0.0-1", &["bar >= 2.0"]);
+        let conflict = match solve(&provider, &["foo", "baz"]) {
+            Err(SolveError::Conflict(conflict)) => conflict,
+            result => panic!("expected a conflict, but got {result:?}"),
+        };
+        assert_eq!(conflict.package(), &PackageName::from("bar"));
+        let message = conflict.to_string();
+        assert!(message.contains("foo 1.2.0-1 requires bar"), "{message}");
+        assert!(message.contains("baz 1.0.0-1 requires bar"), "{message}");
+        assert!(message.contains(", but "), "{message}");
+        assert!(
+            message.contains("available versions: 2.0.0-1, 1.0.0-1"),
+            "{message}"
+        );
+        let suggestions = conflict.suggestions();
+        assert_eq!(suggestions.len(), 2);
+        assert!(suggestions.iter().any(|suggestion| {
+            let suggestion = suggestion.to_string();
+            suggestion.starts_with("relax the constraint `bar <")
+                && suggestion.contains("` of foo 1.2.0-1 to allow bar 2.0.0-1")
+        }));
+    }
+
+    #[test]
+    fn suggests_root_constraint_relaxation() {
+        let provider =
+            TestProvider::default()
+                .add("foo", "1.0.0-1", &[])
+                .add("foo", "2.0.0-1", &[]);
+        let conflict = match solve(&provider, &["foo >= 3.0"]) {
+            Err(SolveError::Conflict(conflict)) => conflict,
+            result => panic!("expected a conflict, but got {result:?}"),
+        };
+        let suggestions = conflict.suggestions();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].versions().len(), 2);
+        let suggestion = suggestions[0].to_string();
+        assert!(
+            suggestion.starts_with("change the requested constraint `foo >")
+                && suggestion.ends_with("` to allow foo 2.0.0-1"),
+            "{suggestion}"
+        );
+    }
+
+    #[test]
+    fn missing_dependencies() {
+        struct NoDependencies;
+        impl DependencyProvider for NoDependencies {
+            fn versions(&self, _name: &PackageName) -> Vec<PackageVersion> {
+                vec!["1.0.0-1".parse().unwrap()]
+            }
+
+            fn dependencies(&self, _package: &PackageSpec) -> Option<&Vec<PackageReq>> {
+                None
+            }
+        }
+        let packages = vec!["foo".parse().unwrap(), "bar".parse().unwrap()];
+        let missing = match VersionSolver::new(&NoDependencies).solve(&packages) {
+            Err(SolveError::MissingDependencies(missing)) => missing,
+            result => panic!("expected missing dependencies, but got {result:?}"),
+        };
+        // Both packages are missing, so they can be fetched together
+        assert_eq!(
+            missing
+                .iter()
+                .map(|package| package.name().to_string())
+                .sorted()
+                .collect_vec(),
+            vec!["bar", "foo"]
+        );
+    }
+}
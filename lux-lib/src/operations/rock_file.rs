@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use url::Url;
+
+use crate::{
+    cache::{self, DownloadCache},
+    config::Config,
+    lockfile::RemotePackageSourceUrl,
+    lua_rockspec::{RemoteLuaRockspec, RockSourceSpec},
+    progress::{Progress, ProgressBar},
+    remote_package_source::RemotePackageSource,
+};
+
+use super::{
+    download::read_packed_rockspec, download_rockspec_bytes, signature::verify_signature,
+    DownloadSrcRockError, DownloadedRockspec, PrefetchedRock, RemoteRockDownload,
+    SearchAndDownloadError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RockFileKind {
+    /// A `.rockspec` file.
+    Rockspec,
+    /// A `.src.rock` file, containing a rockspec and the package's sources.
+    SrcRock,
+    /// A packed binary `.rock` file.
+    BinaryRock,
+}
+
+impl RockFileKind {
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        if file_name.ends_with(".rockspec") {
+            Some(Self::Rockspec)
+        } else if file_name.ends_with(".src.rock") {
+            Some(Self::SrcRock)
+        } else if file_name.ends_with(".rock") {
+            Some(Self::BinaryRock)
+        } else {
+            None
+        }
+    }
+}
+
+/// A `.rockspec`, `.src.rock` or packed binary `.rock` file
+/// at a local path or URL, which can be installed directly.
+#[derive(Debug, Clone)]
+pub struct RockFile {
+    source_spec: RockSourceSpec,
+    kind: RockFileKind,
+}
+
+impl RockFile {
+    /// Parse a local path or `http(s)://` or `file://` URL.
+    /// Local paths are canonicalized, so that they can be recorded in a lockfile.
+    /// Returns `None` if the location does not point to a rock file.
+    pub fn parse(location: &str) -> Option<Self> {
+        let source_spec = match Url::parse(location) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => RockSourceSpec::Url(url),
+            Ok(url) if url.scheme() == "file" => {
+                RockSourceSpec::File(canonicalize(url.to_file_path().ok()?))
+            }
+            _ => RockSourceSpec::File(canonicalize(PathBuf::from(location))),
+        };
+        Self::from_source_spec(source_spec)
+    }
+
+    /// Returns `None` if the source does not point to a rock file.
+    pub fn from_source_spec(source_spec: RockSourceSpec) -> Option<Self> {
+        let file_name = match &source_spec {
+            RockSourceSpec::File(path) => path.file_name()?.to_string_lossy().to_string(),
+            RockSourceSpec::Url(url) => url.path_segments()?.next_back()?.to_string(),
+            RockSourceSpec::Git(_) => return None,
+        };
+        let kind = RockFileKind::from_file_name(&file_name)?;
+        Some(Self { source_spec, kind })
+    }
+
+    pub fn kind(&self) -> RockFileKind {
+        self.kind
+    }
+
+    pub fn source_spec(&self) -> &RockSourceSpec {
+        &self.source_spec
+    }
+
+    /// Read the rock file, to determine the package it provides.
+    /// The returned rock can be passed on to the installer, so that it isn't read again.
+    pub async fn fetch(
+        &self,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<PrefetchedRock, SearchAndDownloadError> {
+        Ok(PrefetchedRock(self.download(config, progress).await?))
+    }
+
+    pub(crate) async fn download(
+        &self,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<RemoteRockDownload, SearchAndDownloadError> {
+        let (bytes, file_name, source_url) = match &self.source_spec {
+            RockSourceSpec::File(path) => {
+                progress.map(|p| p.set_message(format!("📖 Reading {}", path.display())));
+                let bytes = Bytes::from(tokio::fs::read(path).await?);
+                let source_url = RemotePackageSourceUrl::File { path: path.clone() };
+                (bytes, file_name(path), source_url)
+            }
+            RockSourceSpec::Url(url) => {
+                progress.map(|p| p.set_message(format!("📥 Downloading {url}")));
+                let bytes = match self.kind {
+                    RockFileKind::Rockspec => download_rockspec_bytes(url, config).await?,
+                    RockFileKind::SrcRock | RockFileKind::BinaryRock => {
+                        download_rock_file_bytes(url, config).await?
+                    }
+                };
+                let source_url = RemotePackageSourceUrl::Url { url: url.clone() };
+                (bytes, url.to_string(), source_url)
+            }
+            RockSourceSpec::Git(_) => unreachable!("git sources are not rock files"),
+        };
+        let content = match self.kind {
+            RockFileKind::Rockspec => String::from_utf8(bytes.to_vec())?,
+            RockFileKind::SrcRock | RockFileKind::BinaryRock => {
+                read_packed_rockspec(&bytes, &file_name, None)?
+            }
+        };
+        let rockspec_download = DownloadedRockspec {
+            rockspec: RemoteLuaRockspec::new(&content)?,
+            source: RemotePackageSource::RockspecContent(content),
+            source_url: None,
+        };
+        Ok(match self.kind {
+            // The rockspec's own source is fetched when building
+            RockFileKind::Rockspec => RemoteRockDownload::RockspecOnly { rockspec_download },
+            RockFileKind::SrcRock => RemoteRockDownload::SrcRock {
+                rockspec_download,
+                src_rock: bytes,
+                source_url,
+            },
+            RockFileKind::BinaryRock => RemoteRockDownload::BinaryRock {
+                rockspec_download: DownloadedRockspec {
+                    source_url: Some(source_url),
+                    ..rockspec_download
+                },
+                packed_rock: bytes,
+            },
+        })
+    }
+}
+
+/// Fall back to the path as it is if it doesn't exist,
+/// so that the error is reported when reading it.
+fn canonicalize(path: PathBuf) -> PathBuf {
+    path.canonicalize().unwrap_or(path)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+async fn download_rock_file_bytes(
+    url: &Url,
+    config: &Config,
+) -> Result<Bytes, DownloadSrcRockError> {
+    if let Some(bytes) = cache::get_cached(url, None, config).await? {
+        return Ok(bytes);
+    }
+    if config.offline() {
+        return Err(DownloadSrcRockError::Offline(url.clone()));
+    }
+    let bytes = config
+        .authenticate(url, config.http_client(url)?.get(url.clone()))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    // Verify before caching, so that cached rocks can be trusted
+    verify_signature(url, &bytes, config).await?;
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+    DownloadCache::new(config)
+        .insert(url, None, file_name, &bytes)
+        .await?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rock_file() {
+        let rock_file = RockFile::parse("foo-1.0.0-1.rockspec").unwrap();
+        assert_eq!(rock_file.kind(), RockFileKind::Rockspec);
+        assert!(matches!(rock_file.source_spec(), RockSourceSpec::File(_)));
+        let rock_file = RockFile::parse("https://example.com/rocks/foo-1.0.0-1.src.rock").unwrap();
+        assert_eq!(rock_file.kind(), RockFileKind::SrcRock);
+        assert!(matches!(rock_file.source_spec(), RockSourceSpec::Url(_)));
+        let rock_file = RockFile::parse("/tmp/foo-1.0.0-1.linux-x86_64.rock").unwrap();
+        assert_eq!(rock_file.kind(), RockFileKind::BinaryRock);
+        let dir = assert_fs::TempDir::new().unwrap();
+        let rockspec_path = dir.join("foo-1.0.0-1.rockspec");
+        std::fs::write(&rockspec_path, "").unwrap();
+        let relative_path = dir.join(".").join("foo-1.0.0-1.rockspec");
+        let rock_file = RockFile::parse(&relative_path.to_string_lossy()).unwrap();
+        assert_eq!(
+            rock_file.source_spec(),
+            &RockSourceSpec::File(rockspec_path.canonicalize().unwrap())
+        );
+        assert!(RockFile::parse("foo@1.0.0").is_none());
+        assert!(RockFile::parse("https://example.com/foo.tar.gz").is_none());
+    }
+}
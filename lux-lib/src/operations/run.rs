@@ -71,6 +71,8 @@ pub enum RunError {
     Paths(#[from] PathsError),
     #[error("No `run` field found in `lux.toml`")]
     NoRunField,
+    #[error("No run profile named `{0}` found in `lux.toml`")]
+    NoRunProfile(String),
 }
 
 #[derive(Builder)]
@@ -80,6 +82,8 @@ pub struct Run<'a> {
     args: &'a [String],
     config: &'a Config,
     disable_loader: Option<bool>,
+    /// The name of a profile in the `[run.profiles]` table.
+    profile: Option<&'a str>,
 }
 
 impl<State> RunBuilder<'_, State>
@@ -99,13 +103,27 @@ where
             .current_platform()
             .clone();
 
-        let mut args = run_spec.args.unwrap_or_default();
+        let (command, args) = match run.profile {
+            Some(name) => {
+                let profile = run_spec
+                    .profiles
+                    .get(name)
+                    .ok_or_else(|| RunError::NoRunProfile(name.to_string()))?;
+                (
+                    profile.command.clone().or(run_spec.command),
+                    profile.args.clone().or(run_spec.args),
+                )
+            }
+            None => (run_spec.command, run_spec.args),
+        };
+
+        let mut args = args.unwrap_or_default();
 
         if !extra_args.is_empty() {
             args.extend(extra_args.iter().cloned());
         }
         let disable_loader = run.disable_loader.unwrap_or(false);
-        match &run_spec.command {
+        match &command {
             Some(command) => {
                 run_with_command(project, command, disable_loader, &args, config).await
             }
@@ -97,14 +97,20 @@ To suppress this warning, set the `--no-loader` option.
         } else {
             paths.init()
         };
+        // Lua only starts a REPL if it isn't given a script or a chunk to execute.
+        let is_interactive = args.args.is_empty() || args.args.iter().any(|arg| arg == "-i");
+        let print_welcome_message = match args.welcome_message {
+            Some(welcome_message) if is_interactive => {
+                format!("print([==[{welcome_message}]==])")
+            }
+            _ => String::new(),
+        };
         let lua_init = format!(
-            r#"print([==[{}]==])
+            r#"{}
+            {}
             {}
-            print([==[
-{}
-]==])
         "#,
-            args.welcome_message.unwrap_or_default(),
+            print_welcome_message,
             args.lua_init.unwrap_or_default(),
             loader_init
         );
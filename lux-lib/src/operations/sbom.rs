@@ -0,0 +1,421 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use itertools::Itertools;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use ssri::{Algorithm, Integrity};
+
+use crate::{
+    lockfile::{LocalPackage, RemotePackageSourceUrl},
+    package::PackageSpec,
+    remote_package_source::RemotePackageSource,
+};
+
+use super::{LicenseReport, PackageLicense};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[cfg_attr(feature = "clap", value(name = "cyclonedx"))]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    #[cfg_attr(feature = "clap", value(name = "spdx-json"))]
+    SpdxJson,
+}
+
+/// A software bill of materials for the packages of a [`LicenseReport`],
+/// including their versions, hashes, licenses and source URLs.
+pub struct Sbom<'a> {
+    report: &'a LicenseReport,
+    /// The package the bill of materials describes, e.g. a project.
+    subject: Option<PackageSpec>,
+}
+
+impl<'a> Sbom<'a> {
+    pub fn new(report: &'a LicenseReport) -> Self {
+        Self {
+            report,
+            subject: None,
+        }
+    }
+
+    pub fn subject(self, subject: PackageSpec) -> Self {
+        Self {
+            subject: Some(subject),
+            ..self
+        }
+    }
+
+    pub fn render(&self, format: SbomFormat) -> String {
+        let sbom = self.to_json(format, SystemTime::now());
+        serde_json::to_string_pretty(&sbom).expect("failed to serialize SBOM")
+    }
+
+    fn to_json(&self, format: SbomFormat, created: SystemTime) -> Value {
+        match format {
+            SbomFormat::CycloneDx => self.to_cyclonedx(created),
+            SbomFormat::SpdxJson => self.to_spdx(created),
+        }
+    }
+
+    fn to_cyclonedx(&self, created: SystemTime) -> Value {
+        let packages = self.report.packages();
+        let components = packages
+            .iter()
+            .map(|package| {
+                let local = package.package();
+                let mut component = json!({
+                    "type": "library",
+                    "bom-ref": local.id().to_string(),
+                    "name": local.name().to_string(),
+                    "version": local.version().to_string(),
+                    "purl": purl(local),
+                    "hashes": hashes(local)
+                        .into_iter()
+                        .filter_map(|(algorithm, content)| {
+                            cyclonedx_algorithm(algorithm)
+                                .map(|alg| json!({ "alg": alg, "content": content }))
+                        })
+                        .collect_vec(),
+                    "externalReferences": external_references(local)
+                        .into_iter()
+                        .map(|(kind, url)| json!({ "type": kind, "url": url }))
+                        .collect_vec(),
+                });
+                match package
+                    .license()
+                    .map(|license| (license, spdx_expression(license)))
+                {
+                    Some((_, Some(expression))) => {
+                        component["licenses"] = json!([{ "expression": expression }]);
+                    }
+                    Some((license, None)) => {
+                        component["licenses"] = json!([{ "license": { "name": license } }]);
+                    }
+                    None => {}
+                }
+                component
+            })
+            .collect_vec();
+        let dependencies = packages
+            .iter()
+            .map(|package| {
+                let local = package.package();
+                json!({
+                    "ref": local.id().to_string(),
+                    "dependsOn": local
+                        .dependencies()
+                        .into_iter()
+                        .map(|id| id.to_string())
+                        .collect_vec(),
+                })
+            })
+            .collect_vec();
+        let mut metadata = json!({
+            "timestamp": iso8601(created),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "lux",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+        });
+        if let Some(subject) = &self.subject {
+            metadata["component"] = json!({
+                "type": "application",
+                "name": subject.name().to_string(),
+                "version": subject.version().to_string(),
+            });
+        }
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": metadata,
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+
+    fn to_spdx(&self, created: SystemTime) -> Value {
+        let packages = self.report.packages();
+        let name = match &self.subject {
+            Some(subject) => format!("{}-{}", subject.name(), subject.version()),
+            None => "lux-tree".into(),
+        };
+        let mut hasher = Sha256::new();
+        for package in packages {
+            hasher.update(package.package().id().to_string());
+        }
+        let namespace = format!(
+            "https://spdx.org/spdxdocs/{name}-{}",
+            hex::encode(hasher.finalize())
+        );
+        let spdx_packages = packages
+            .iter()
+            .map(|package| {
+                let local = package.package();
+                let license = package
+                    .license()
+                    .and_then(spdx_expression)
+                    .unwrap_or_else(|| "NOASSERTION".into());
+                let download_location =
+                    download_location(local).unwrap_or_else(|| "NOASSERTION".into());
+                let checksums = hashes(local)
+                    .into_iter()
+                    .filter_map(|(algorithm, value)| {
+                        spdx_algorithm(algorithm).map(
+                            |algorithm| json!({ "algorithm": algorithm, "checksumValue": value }),
+                        )
+                    })
+                    .collect_vec();
+                json!({
+                    "SPDXID": spdx_id(local),
+                    "name": local.name().to_string(),
+                    "versionInfo": local.version().to_string(),
+                    "downloadLocation": download_location,
+                    "filesAnalyzed": false,
+                    "licenseConcluded": "NOASSERTION",
+                    "licenseDeclared": license,
+                    "copyrightText": "NOASSERTION",
+                    "checksums": checksums,
+                    "externalRefs": [{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl(local),
+                    }],
+                })
+            })
+            .collect_vec();
+        let relationships = packages
+            .iter()
+            .flat_map(|package| {
+                let local = package.package();
+                local
+                    .dependencies()
+                    .into_iter()
+                    .filter_map(|id| {
+                        packages
+                            .iter()
+                            .map(PackageLicense::package)
+                            .find(|dependency| &dependency.id() == id)
+                    })
+                    .map(|dependency| {
+                        json!({
+                            "spdxElementId": spdx_id(local),
+                            "relationshipType": "DEPENDS_ON",
+                            "relatedSpdxElement": spdx_id(dependency),
+                        })
+                    })
+                    .collect_vec()
+            })
+            .chain(packages.iter().map(|package| {
+                json!({
+                    "spdxElementId": "SPDXRef-DOCUMENT",
+                    "relationshipType": "DESCRIBES",
+                    "relatedSpdxElement": spdx_id(package.package()),
+                })
+            }))
+            .collect_vec();
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            "documentNamespace": namespace,
+            "creationInfo": {
+                "created": iso8601(created),
+                "creators": [format!("Tool: lux-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": spdx_packages,
+            "relationships": relationships,
+        })
+    }
+}
+
+fn purl(package: &LocalPackage) -> String {
+    format!("pkg:luarocks/{}@{}", package.name(), package.version())
+}
+
+fn spdx_id(package: &LocalPackage) -> String {
+    // SPDX IDs may only contain letters, numbers, `.` and `-`
+    let id = format!("{}-{}", package.name(), package.id())
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    format!("SPDXRef-Package-{id}")
+}
+
+/// The rockspec and source hashes of a package, as `(algorithm, hex digest)` pairs.
+fn hashes(package: &LocalPackage) -> Vec<(Algorithm, String)> {
+    let hashes = package.hashes();
+    [&hashes.rockspec, &hashes.source]
+        .into_iter()
+        .map(Integrity::to_hex)
+        .unique()
+        .collect_vec()
+}
+
+fn cyclonedx_algorithm(algorithm: Algorithm) -> Option<&'static str> {
+    match algorithm {
+        Algorithm::Sha1 => Some("SHA-1"),
+        Algorithm::Sha256 => Some("SHA-256"),
+        Algorithm::Sha384 => Some("SHA-384"),
+        Algorithm::Sha512 => Some("SHA-512"),
+        _ => None,
+    }
+}
+
+fn spdx_algorithm(algorithm: Algorithm) -> Option<&'static str> {
+    match algorithm {
+        Algorithm::Sha1 => Some("SHA1"),
+        Algorithm::Sha256 => Some("SHA256"),
+        Algorithm::Sha384 => Some("SHA384"),
+        Algorithm::Sha512 => Some("SHA512"),
+        _ => None,
+    }
+}
+
+fn download_location(package: &LocalPackage) -> Option<String> {
+    match &package.source_url {
+        Some(RemotePackageSourceUrl::Url { url }) => Some(url.to_string()),
+        Some(RemotePackageSourceUrl::Git { url, checkout_ref }) => {
+            Some(format!("git+{url}@{checkout_ref}"))
+        }
+        Some(RemotePackageSourceUrl::File { .. }) | None => None,
+    }
+}
+
+/// References to the source archive or repository and to the rockspec or rock
+/// the package was installed from, as `(CycloneDX reference type, URL)` pairs.
+fn external_references(package: &LocalPackage) -> Vec<(&'static str, String)> {
+    let source = match &package.source_url {
+        Some(RemotePackageSourceUrl::Url { url }) => Some(("distribution", url.to_string())),
+        Some(RemotePackageSourceUrl::Git { url, .. }) => Some(("vcs", url.clone())),
+        Some(RemotePackageSourceUrl::File { .. }) | None => None,
+    };
+    let rockspec = match package.source() {
+        RemotePackageSource::LuarocksRockspec(url)
+        | RemotePackageSource::LuarocksSrcRock(url)
+        | RemotePackageSource::LuarocksBinaryRock(url) => Some(("distribution", url.to_string())),
+        _ => None,
+    };
+    source.into_iter().chain(rockspec).collect_vec()
+}
+
+/// The SPDX license expression for a rockspec license.
+/// Rockspecs often use non-SPDX names like `MIT/X11`, which we map to SPDX license IDs.
+fn spdx_expression(license: &str) -> Option<String> {
+    if spdx::Expression::parse(license).is_ok() {
+        return Some(license.to_string());
+    }
+    spdx::imprecise_license_id(license).map(|(id, _)| id.name.to_string())
+}
+
+/// Format a time as an ISO 8601 UTC timestamp, e.g. `2025-01-31T12:00:00Z`.
+fn iso8601(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::lockfile::{Lockfile, ReadOnly};
+
+    use super::*;
+
+    fn sample_report() -> LicenseReport {
+        let lockfile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test/sample-tree/5.1/lux.lock");
+        let lockfile: Lockfile<ReadOnly> = Lockfile::load(lockfile_path, None).unwrap();
+        LicenseReport::new(
+            lockfile
+                .rocks()
+                .values()
+                .map(|package| PackageLicense::new(package.clone(), Some("MIT".into())))
+                .collect_vec(),
+        )
+    }
+
+    fn created() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn timestamp() {
+        assert_eq!(iso8601(created()), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn spdx_license_expressions() {
+        assert_eq!(spdx_expression("MIT"), Some("MIT".into()));
+        assert_eq!(spdx_expression("MIT/X11"), Some("MIT".into()));
+        assert_eq!(
+            spdx_expression("MIT OR Apache-2.0"),
+            Some("MIT OR Apache-2.0".into())
+        );
+        assert_eq!(spdx_expression("Public Domain"), None);
+    }
+
+    #[test]
+    fn cyclonedx() {
+        let report = sample_report();
+        let sbom = Sbom::new(&report).to_json(SbomFormat::CycloneDx, created());
+        assert_eq!(sbom["bomFormat"], "CycloneDX");
+        let components = sbom["components"].as_array().unwrap();
+        assert_eq!(components.len(), report.packages().len());
+        let neorg = components
+            .iter()
+            .find(|component| component["name"] == "neorg")
+            .unwrap();
+        assert_eq!(neorg["purl"], "pkg:luarocks/neorg@8.8.1-1");
+        assert_eq!(neorg["licenses"][0]["expression"], "MIT");
+        assert!(!neorg["hashes"].as_array().unwrap().is_empty());
+        let neorg_ref = neorg["bom-ref"].as_str().unwrap();
+        let dependencies = sbom["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|dependency| dependency["ref"] == neorg_ref)
+            .unwrap();
+        assert!(!dependencies["dependsOn"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn spdx() {
+        let report = sample_report();
+        let sbom = Sbom::new(&report)
+            .subject(PackageSpec::parse("my-project".into(), "1.0.0".into()).unwrap())
+            .to_json(SbomFormat::SpdxJson, created());
+        assert_eq!(sbom["spdxVersion"], "SPDX-2.3");
+        assert!(sbom["name"]
+            .as_str()
+            .unwrap()
+            .starts_with("my-project-1.0.0"));
+        assert_eq!(sbom["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+        let packages = sbom["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), report.packages().len());
+        assert!(packages.iter().all(|package| package["SPDXID"]
+            .as_str()
+            .unwrap()
+            .starts_with("SPDXRef-Package-")));
+        assert!(sbom["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|relationship| relationship["relationshipType"] == "DEPENDS_ON"));
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::Deref;
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+use tokio::process::Command;
+
+use crate::{
+    config::Config,
+    project::{project_toml::LocalProjectTomlValidationError, Project},
+};
+
+use super::{exec::exec_paths, ExecError};
+
+/// Runs a named script from the `[scripts]` table in a project's `lux.toml`,
+/// with the project's tree on the `PATH`, `LUA_PATH` and `LUA_CPATH`.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct RunScript<'a> {
+    #[builder(start_fn)]
+    name: &'a str,
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Arguments that are appended to the script.
+    #[builder(field)]
+    args: Vec<String>,
+
+    disable_loader: Option<bool>,
+}
+
+impl<State: run_script_builder::State> RunScriptBuilder<'_, State> {
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item: Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map_into());
+        self
+    }
+}
+
+impl<State> RunScriptBuilder<'_, State>
+where
+    State: run_script_builder::State + run_script_builder::IsComplete,
+{
+    pub async fn run(self) -> Result<(), RunScriptError> {
+        do_run_script(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RunScriptError {
+    #[error(transparent)]
+    Toml(#[from] LocalProjectTomlValidationError),
+    #[error("no script named `{0}` found in the `[scripts]` table of `lux.toml`")]
+    NotFound(String),
+    #[error(transparent)]
+    Exec(#[from] ExecError),
+}
+
+async fn do_run_script(run: RunScript<'_>) -> Result<(), RunScriptError> {
+    let toml = run.project.toml().into_local()?;
+    let script = toml
+        .scripts()
+        .get(run.name)
+        .ok_or_else(|| RunScriptError::NotFound(run.name.to_string()))?;
+
+    let (paths, lua_init) = exec_paths(
+        Some(run.project),
+        run.config,
+        run.disable_loader.unwrap_or(false),
+    )?;
+
+    let status = shell_command(script, &run.args)
+        .current_dir(run.project.root().deref())
+        .env("PATH", paths.path_prepended().joined())
+        .env("LUA_INIT", lua_init.unwrap_or_default())
+        .env("LUA_PATH", paths.package_path().joined())
+        .env("LUA_CPATH", paths.package_cpath().joined())
+        .status()
+        .await
+        .map_err(|err| ExecError::RunCommandFailed {
+            cmd: script.clone(),
+            source: err,
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ExecError::RunCommandNonZeroExitCode {
+            cmd: script.clone(),
+            exit_code: status.code(),
+        }
+        .into())
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn shell_command(script: &str, args: &[String]) -> Command {
+    let mut command = Command::new("sh");
+    // Extra arguments are forwarded to the script via "$@"
+    command
+        .arg("-c")
+        .arg(format!("{script} \"$@\""))
+        .arg("sh")
+        .args(args);
+    command
+}
+
+#[cfg(target_family = "windows")]
+fn shell_command(script: &str, args: &[String]) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(script).args(args);
+    command
+}
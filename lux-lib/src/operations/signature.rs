@@ -0,0 +1,136 @@
+use std::{io, path::PathBuf};
+
+use reqwest::StatusCode;
+use thiserror::Error;
+use url::Url;
+
+use crate::config::{
+    server::{SignatureMode, SignaturePolicy},
+    Config,
+};
+
+#[cfg(not(target_env = "msvc"))]
+use gpgme::{Context, Protocol};
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("refusing to use unsigned artifact {0}")]
+    Unsigned(Url),
+    #[error("failed to download signature {0}: {1}")]
+    Request(Url, reqwest::Error),
+    #[error("failed to read signature {0}: {1}")]
+    ReadLocal(PathBuf, io::Error),
+    #[error("invalid signature for {0}")]
+    Invalid(Url),
+    #[error("{0} is not signed by a trusted key")]
+    Untrusted(Url),
+    #[error("failed to verify the signature of {0}: {1}")]
+    Join(Url, tokio::task::JoinError),
+    #[cfg(not(target_env = "msvc"))]
+    #[error("failed to verify the signature of {0}: {1}")]
+    Gpg(Url, gpgme::Error),
+    #[cfg(target_env = "msvc")]
+    #[error("cannot verify the signature of {0}: signature verification is not supported on this platform")]
+    Unsupported(Url),
+}
+
+/// Verify the detached signature (`<url>.sig`) of an artifact downloaded from `url`,
+/// according to the signature policy of the server it belongs to.
+pub(crate) async fn verify_signature(
+    url: &Url,
+    content: &[u8],
+    config: &Config,
+) -> Result<(), SignatureError> {
+    let policy = match config.signature_policy(url) {
+        Some(policy) if policy.mode() != &SignatureMode::Ignore => policy,
+        _ => return Ok(()),
+    };
+    let signature = match download_signature(url, config).await? {
+        Some(signature) => signature,
+        None if policy.mode() == &SignatureMode::Strict => {
+            return Err(SignatureError::Unsigned(url.clone()))
+        }
+        None => return Ok(()),
+    };
+    // gpgme is synchronous and may block on the gpg agent, so we don't run it on the async runtime.
+    let url = url.clone();
+    let content = content.to_vec();
+    let policy = policy.clone();
+    tokio::task::spawn_blocking({
+        let url = url.clone();
+        move || verify_detached(&url, &signature, &content, &policy)
+    })
+    .await
+    .map_err(|err| SignatureError::Join(url, err))?
+}
+
+/// Download the detached signature of the artifact at `url`, or `None` if the server
+/// doesn't publish one.
+async fn download_signature(url: &Url, config: &Config) -> Result<Option<Vec<u8>>, SignatureError> {
+    let mut signature_url = url.clone();
+    signature_url.set_path(&format!("{}.sig", url.path()));
+    if let Ok(path) = signature_url.to_file_path() {
+        return match tokio::fs::read(&path).await {
+            Ok(signature) => Ok(Some(signature)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(SignatureError::ReadLocal(path, err)),
+        };
+    }
+    if config.offline() {
+        return Ok(None);
+    }
+    let map_err = |err| SignatureError::Request(signature_url.clone(), err);
+    let client = config.http_client(&signature_url).map_err(map_err)?;
+    let response = config
+        .authenticate(&signature_url, client.get(signature_url.clone()))
+        .send()
+        .await
+        .map_err(map_err)?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let signature = response
+        .error_for_status()
+        .map_err(map_err)?
+        .bytes()
+        .await
+        .map_err(map_err)?;
+    Ok(Some(signature.to_vec()))
+}
+
+#[cfg(not(target_env = "msvc"))]
+fn verify_detached(
+    url: &Url,
+    signature: &[u8],
+    content: &[u8],
+    policy: &SignaturePolicy,
+) -> Result<(), SignatureError> {
+    let map_err = |err| SignatureError::Gpg(url.clone(), err);
+    let mut ctx = Context::from_protocol(Protocol::OpenPgp).map_err(map_err)?;
+    let result = ctx.verify_detached(signature, content).map_err(map_err)?;
+    let valid_fingerprints = result
+        .signatures()
+        .filter(|signature| signature.status().is_ok())
+        .filter_map(|signature| signature.fingerprint().ok().map(str::to_string))
+        .collect::<Vec<_>>();
+    if valid_fingerprints.is_empty() {
+        Err(SignatureError::Invalid(url.clone()))
+    } else if valid_fingerprints
+        .iter()
+        .any(|fingerprint| policy.is_trusted(fingerprint))
+    {
+        Ok(())
+    } else {
+        Err(SignatureError::Untrusted(url.clone()))
+    }
+}
+
+#[cfg(target_env = "msvc")]
+fn verify_detached(
+    url: &Url,
+    _signature: &[u8],
+    _content: &[u8],
+    _policy: &SignaturePolicy,
+) -> Result<(), SignatureError> {
+    Err(SignatureError::Unsupported(url.clone()))
+}
@@ -3,15 +3,16 @@ use std::{io, sync::Arc};
 use crate::{
     build::BuildBehaviour,
     config::Config,
-    lockfile::{LocalPackage, LocalPackageLockType, LockfileIntegrityError},
+    lockfile::{LocalPackage, LocalPackageLockType, LockfileIntegrityError, PackageSyncSpec},
     luarc,
     luarocks::luarocks_installation::LUAROCKS_VERSION,
     package::{PackageName, PackageReq},
     progress::{MultiProgress, Progress},
     project::{
-        project_toml::LocalProjectTomlValidationError, Project, ProjectError, ProjectTreeError,
+        project_toml::LocalProjectTomlValidationError, workspace::WorkspaceError, Project,
+        ProjectError, ProjectTreeError,
     },
-    rockspec::Rockspec,
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
     tree::{self, TreeError},
 };
 use bon::{builder, Builder};
@@ -35,6 +36,10 @@ pub struct Sync<'a> {
     progress: Option<Arc<Progress<MultiProgress>>>,
     /// Whether to validate the integrity of installed packages.
     validate_integrity: Option<bool>,
+    /// Whether to sync the tree with the lockfile as it is,
+    /// without resolving the dependencies in lux.toml that are missing from it
+    /// or removing the ones that lux.toml no longer requires.
+    locked: Option<bool>,
 }
 
 impl<State> SyncBuilder<'_, State>
@@ -67,6 +72,7 @@ where
                     .test_dependencies()
                     .current_platform()
                     .iter()
+                    .chain(toml.dev_dependencies().current_platform())
                     .any(|dep| dep.name() == test_dep.name())
             })
             .cloned()
@@ -106,6 +112,19 @@ pub struct SyncReport {
     pub(crate) removed: Vec<LocalPackage>,
 }
 
+impl SyncReport {
+    /// Packages that were installed into the tree while syncing.
+    pub fn added(&self) -> &[LocalPackage] {
+        &self.added
+    }
+
+    /// Packages that were removed from the tree while syncing,
+    /// because they are no longer required by the lockfile.
+    pub fn removed(&self) -> &[LocalPackage] {
+        &self.removed
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum SyncError {
     #[error(transparent)]
@@ -124,8 +143,11 @@ pub enum SyncError {
     ProjectError(#[from] ProjectError),
     #[error(transparent)]
     LocalProjectTomlValidationError(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    Workspace(#[from] WorkspaceError),
 }
 
+#[tracing::instrument(skip_all)]
 async fn do_sync(
     args: Sync<'_>,
     lock_type: &LocalPackageLockType,
@@ -142,34 +164,26 @@ async fn do_sync(
 
     let progress = args.progress.unwrap_or(MultiProgress::new_arc());
 
-    let packages = match lock_type {
-        LocalPackageLockType::Regular => args
-            .project
-            .toml()
-            .into_local()?
-            .dependencies()
-            .current_platform()
-            .clone(),
-        LocalPackageLockType::Build => args
-            .project
-            .toml()
-            .into_local()?
-            .build_dependencies()
-            .current_platform()
-            .clone(),
-        LocalPackageLockType::Test => args
-            .project
-            .toml()
-            .into_local()?
-            .test_dependencies()
-            .current_platform()
-            .clone(),
+    // Members of a workspace share the lockfile, so their dependencies are resolved together.
+    let mut packages = Vec::new();
+    let members = match args.project.workspace()? {
+        Some(workspace) => workspace.members().to_vec(),
+        None => vec![args.project.clone()],
+    };
+    for member in &members {
+        for dep in lock_type_dependencies(member, lock_type)? {
+            if !packages.contains(&dep) {
+                packages.push(dep);
+            }
+        }
     }
-    .into_iter()
-    .chain(args.extra_packages.into_iter().map_into())
-    .collect_vec();
+    packages.extend(args.extra_packages.into_iter().map_into());
 
-    let package_sync_spec = project_lockfile.package_sync_spec(&packages, lock_type);
+    let package_sync_spec = if args.locked.unwrap_or(false) {
+        PackageSyncSpec::default()
+    } else {
+        project_lockfile.package_sync_spec(&packages, lock_type)
+    };
 
     package_sync_spec
         .to_remove
@@ -193,7 +207,11 @@ async fn do_sync(
         }
     }
     for (id, local_package) in dest_lockfile.rocks() {
-        if project_lockfile.get(id, lock_type).is_none() {
+        // The workspace members that were built into the shared tree are not in the lockfile.
+        let is_member = members
+            .iter()
+            .any(|member| member.toml().package() == local_package.name());
+        if project_lockfile.get(id, lock_type).is_none() && !is_member {
             report.removed.push(local_package.clone());
         }
     }
@@ -219,6 +237,7 @@ async fn do_sync(
     Install::new(args.config)
         .package_db(package_db)
         .packages(packages_to_install)
+        .patches(args.project.toml().patches())
         .tree(tree.clone())
         .progress(progress.clone())
         .install()
@@ -244,6 +263,7 @@ async fn do_sync(
 
     Remove::new(args.config)
         .packages(packages_to_remove)
+        .tree(tree.clone())
         .progress(progress.clone())
         .remove()
         .await?;
@@ -270,6 +290,7 @@ async fn do_sync(
 
         let added = Install::new(args.config)
             .packages(missing_packages)
+            .patches(args.project.toml().patches())
             .tree(tree.clone())
             .progress(progress.clone())
             .install()
@@ -287,16 +308,67 @@ async fn do_sync(
     Ok(report)
 }
 
+fn lock_type_dependencies(
+    project: &Project,
+    lock_type: &LocalPackageLockType,
+) -> Result<Vec<LuaDependencySpec>, LocalProjectTomlValidationError> {
+    let toml = project.toml().into_local()?;
+    Ok(match lock_type {
+        LocalPackageLockType::Regular => toml.dependencies().current_platform().clone(),
+        LocalPackageLockType::Build => toml.build_dependencies().current_platform().clone(),
+        LocalPackageLockType::Test => toml
+            .test_dependencies()
+            .current_platform()
+            .iter()
+            .chain(toml.dev_dependencies().current_platform())
+            .cloned()
+            .collect_vec(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::Sync;
     use crate::{
-        config::ConfigBuilder, lockfile::LocalPackageLockType, package::PackageReq,
+        config::{ConfigBuilder, LuaVersion},
+        lockfile::LocalPackageLockType,
+        package::PackageReq,
         project::Project,
     };
     use assert_fs::{prelude::PathCopy, TempDir};
     use std::path::PathBuf;
 
+    #[tokio::test]
+    async fn test_sync_locked_does_not_resolve() {
+        let temp_dir = TempDir::new().unwrap();
+        temp_dir
+            .copy_from(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("resources/test/sample-projects/dependencies/"),
+                &["**"],
+            )
+            .unwrap();
+        let project = Project::from_exact(temp_dir.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        // The dependencies in lux.toml are not in the (empty) lockfile, e.g. after a rollback
+        let report = Sync::new(&project, &config)
+            .locked(true)
+            .sync_dependencies()
+            .await
+            .unwrap();
+        assert!(report.added.is_empty());
+        assert!(report.removed.is_empty());
+        assert!(project
+            .lockfile()
+            .unwrap()
+            .rocks(&LocalPackageLockType::Regular)
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_sync_add_rocks() {
         if std::env::var("LUX_SKIP_IMPURE_TESTS").unwrap_or("0".into()) == "1" {
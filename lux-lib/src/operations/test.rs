@@ -1,4 +1,10 @@
-use std::{io, ops::Deref, path::PathBuf, process::Command, sync::Arc};
+use std::{
+    io,
+    ops::Deref,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::Arc,
+};
 
 use crate::{
     build::BuildBehaviour,
@@ -9,7 +15,8 @@ use crate::{
     path::{Paths, PathsError},
     progress::{MultiProgress, Progress},
     project::{
-        project_toml::LocalProjectTomlValidationError, Project, ProjectError, ProjectTreeError,
+        project_toml::{LocalProjectToml, LocalProjectTomlValidationError},
+        Project, ProjectError, ProjectTreeError,
     },
     rockspec::Rockspec,
     tree::{self, TreeError},
@@ -20,6 +27,7 @@ use thiserror::Error;
 
 use super::{
     BuildProject, BuildProjectError, Install, InstallError, PackageInstallSpec, Sync, SyncError,
+    TestReport, TestReportError,
 };
 
 #[cfg(target_family = "unix")]
@@ -40,6 +48,15 @@ pub struct Test<'a> {
 
     no_lock: Option<bool>,
 
+    /// Only run tests whose names match this Lua pattern (busted only).
+    filter: Option<String>,
+    /// Only run tests with these tags (busted only).
+    tags: Option<Vec<String>>,
+    /// Don't run tests with these tags (busted only).
+    exclude_tags: Option<Vec<String>>,
+    /// Machine-readable test output (busted only).
+    output: Option<TestOutput>,
+
     #[builder(default)]
     env: TestEnv,
     #[builder(default = MultiProgress::new_arc())]
@@ -57,7 +74,9 @@ impl<State: test_builder::State> TestBuilder<'_, State> {
         self
     }
 
-    pub async fn run(self) -> Result<(), RunTestsError>
+    /// With an `output` format, the report of the test run is returned, even if tests failed,
+    /// so that it can be rendered. Use [`TestReport::is_success`] to check the results.
+    pub async fn run(self) -> Result<Option<TestReport>, RunTestsError>
     where
         State: test_builder::IsComplete,
     {
@@ -79,6 +98,19 @@ impl Default for TestEnv {
     }
 }
 
+/// A machine-readable output format for test results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
+pub enum TestOutput {
+    /// Test Anything Protocol
+    Tap,
+    /// JUnit XML
+    Junit,
+    /// A JSON report with per-test durations and failure diagnostics
+    Json,
+}
+
 #[derive(Error, Debug)]
 pub enum RunTestsError {
     #[error(transparent)]
@@ -89,6 +121,8 @@ pub enum RunTestsError {
     BuildProject(#[from] BuildProjectError),
     #[error("tests failed!")]
     TestFailure,
+    #[error("test filters and output formats are only supported by the busted test backends")]
+    BustedOptionsUnsupported,
     #[error("failed to execute `{0}`: {1}")]
     RunCommandFailure(String, io::Error),
     #[error(transparent)]
@@ -109,9 +143,11 @@ pub enum RunTestsError {
     LuaVersion(#[from] LuaVersionError),
     #[error(transparent)]
     LuaBinary(#[from] LuaBinaryError),
+    #[error(transparent)]
+    TestReport(#[from] TestReportError),
 }
 
-async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
+async fn run_tests(test: Test<'_>) -> Result<Option<TestReport>, RunTestsError> {
     let rocks = test.project.toml().into_local()?;
 
     let test_spec = rocks
@@ -121,6 +157,16 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
 
     let config = test_spec.test_config(test.config)?;
 
+    let busted_args = busted_args(&test);
+    if !busted_args.is_empty()
+        && !matches!(
+            test_spec,
+            ValidatedTestSpec::Busted(_) | ValidatedTestSpec::BustedNlua(_)
+        )
+    {
+        return Err(RunTestsError::BustedOptionsUnsupported);
+    }
+
     let no_lock = test.no_lock.unwrap_or(false);
 
     if no_lock {
@@ -158,6 +204,7 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
     let mut command = command
         .current_dir(test.project.root().deref())
         .args(test_spec.args())
+        .args(busted_args)
         .args(test.args)
         .env("PATH", paths.path_prepended().joined())
         .env("LUA_PATH", paths.package_path().joined())
@@ -180,17 +227,49 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
             .env("XDG_STATE_HOME", xdg_state_home)
             .env("XDG_DATA_HOME", xdg_data_home);
     }
+    if test.output.is_some() {
+        let result = match command.stderr(Stdio::inherit()).output() {
+            Ok(result) => Ok(result),
+            Err(err) => Err(RunTestsError::RunCommandFailure("busted".into(), err)),
+        }?;
+        let report = TestReport::from_busted_json(&String::from_utf8_lossy(&result.stdout))?;
+        // busted fails without reporting a failed test if it can't load the tests
+        return if result.status.success() || !report.is_success() {
+            Ok(Some(report))
+        } else {
+            Err(RunTestsError::TestFailure)
+        };
+    }
     let status = match command.status() {
         Ok(status) => Ok(status),
         Err(err) => Err(RunTestsError::RunCommandFailure("busted".into(), err)),
     }?;
     if status.success() {
-        Ok(())
+        Ok(None)
     } else {
         Err(RunTestsError::TestFailure)
     }
 }
 
+/// Translates the test filters and output format into busted CLI flags.
+fn busted_args(test: &Test<'_>) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(filter) = &test.filter {
+        args.push(format!("--filter={filter}"));
+    }
+    if let Some(tags) = test.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        args.push(format!("--tags={}", tags.join(",")));
+    }
+    if let Some(tags) = test.exclude_tags.as_ref().filter(|tags| !tags.is_empty()) {
+        args.push(format!("--exclude-tags={}", tags.join(",")));
+    }
+    if test.output.is_some() {
+        // We render the requested format from busted's json report
+        args.push("--output=json".into());
+    }
+    args
+}
+
 #[derive(Error, Debug)]
 #[error("error installing test dependencies: {0}")]
 pub enum InstallTestDependenciesError {
@@ -204,12 +283,17 @@ pub enum InstallTestDependenciesError {
 /// This defaults to the local project tree if cwd is a project root.
 async fn ensure_test_dependencies(
     project: &Project,
-    rockspec: &impl Rockspec,
+    rockspec: &LocalProjectToml,
     config: &Config,
     progress: Arc<Progress<MultiProgress>>,
 ) -> Result<(), InstallTestDependenciesError> {
     let test_tree = project.test_tree(config)?;
-    let rockspec_dependencies = rockspec.test_dependencies().current_platform();
+    let rockspec_dependencies = rockspec
+        .test_dependencies()
+        .current_platform()
+        .iter()
+        .chain(rockspec.dev_dependencies().current_platform())
+        .collect_vec();
     let test_dependencies = rockspec
         .test()
         .current_platform()
@@ -299,6 +383,22 @@ mod tests {
         run_test(&project_root).await
     }
 
+    #[tokio::test]
+    async fn test_busted_options_unsupported() {
+        let project_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test/sample-projects/command-test/");
+        let project: Project = Project::from(&project_root).unwrap().unwrap();
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let result = Test::new(project, &config)
+            .output(TestOutput::Junit)
+            .run()
+            .await;
+        assert!(matches!(
+            result,
+            Err(RunTestsError::BustedOptionsUnsupported)
+        ));
+    }
+
     async fn run_test(project_root: &Path) {
         let temp_dir = TempDir::new().unwrap();
         temp_dir.copy_from(project_root, &["**"]).unwrap();
@@ -0,0 +1,346 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::TestOutput;
+
+/// The results of a test suite run, collected from busted's `json` output handler.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestReport {
+    tests: Vec<TestCase>,
+    /// The duration of the whole test suite, in seconds.
+    duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestCase {
+    name: String,
+    status: TestStatus,
+    /// The duration of the test, in seconds.
+    duration: Option<f64>,
+    /// The failure or error message.
+    message: Option<String>,
+    /// The source location of the failure, e.g. `spec/foo_spec.lua:12`.
+    location: Option<String>,
+    traceback: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Success,
+    Failure,
+    Error,
+    Pending,
+}
+
+#[derive(Error, Debug)]
+#[error("failed to parse the test results reported by busted: {0}")]
+pub struct TestReportError(#[from] serde_json::Error);
+
+#[derive(Deserialize)]
+struct BustedReport {
+    #[serde(default)]
+    successes: Vec<BustedElement>,
+    #[serde(default)]
+    failures: Vec<BustedElement>,
+    #[serde(default)]
+    errors: Vec<BustedElement>,
+    #[serde(default)]
+    pending: Vec<BustedElement>,
+    duration: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct BustedElement {
+    name: String,
+    message: Option<Value>,
+    trace: Option<BustedTrace>,
+    element: Option<BustedElementInfo>,
+}
+
+#[derive(Deserialize)]
+struct BustedTrace {
+    short_src: Option<String>,
+    currentline: Option<i64>,
+    traceback: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BustedElementInfo {
+    duration: Option<f64>,
+}
+
+impl BustedElement {
+    fn into_test_case(self, status: TestStatus) -> TestCase {
+        let message = self.message.and_then(|message| match message {
+            Value::Null => None,
+            Value::String(message) => Some(message),
+            message => Some(message.to_string()),
+        });
+        let (location, traceback) = match self.trace {
+            Some(trace) => (
+                trace.short_src.map(|src| match trace.currentline {
+                    Some(line) if line > 0 => format!("{src}:{line}"),
+                    _ => src,
+                }),
+                trace
+                    .traceback
+                    .map(|traceback| traceback.trim().to_string()),
+            ),
+            None => (None, None),
+        };
+        TestCase {
+            name: self.name,
+            status,
+            duration: self.element.and_then(|element| element.duration),
+            message,
+            location,
+            traceback,
+        }
+    }
+}
+
+impl TestCase {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> TestStatus {
+        self.status
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn traceback(&self) -> Option<&str> {
+        self.traceback.as_deref()
+    }
+}
+
+impl TestReport {
+    /// Parse the output of busted's `json` output handler.
+    /// Anything the tests printed to stdout before the report is ignored.
+    pub fn from_busted_json(stdout: &str) -> Result<Self, TestReportError> {
+        let json = stdout
+            .lines()
+            .rev()
+            .find(|line| line.trim_start().starts_with('{'))
+            .unwrap_or(stdout);
+        let report: BustedReport = serde_json::from_str(json)?;
+        let tests = report
+            .successes
+            .into_iter()
+            .map(|element| element.into_test_case(TestStatus::Success))
+            .chain(
+                report
+                    .failures
+                    .into_iter()
+                    .map(|element| element.into_test_case(TestStatus::Failure)),
+            )
+            .chain(
+                report
+                    .errors
+                    .into_iter()
+                    .map(|element| element.into_test_case(TestStatus::Error)),
+            )
+            .chain(
+                report
+                    .pending
+                    .into_iter()
+                    .map(|element| element.into_test_case(TestStatus::Pending)),
+            )
+            .collect_vec();
+        Ok(Self {
+            tests,
+            duration: report.duration,
+        })
+    }
+
+    pub fn tests(&self) -> &[TestCase] {
+        &self.tests
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    /// Whether all tests succeeded or are pending.
+    pub fn is_success(&self) -> bool {
+        self.tests
+            .iter()
+            .all(|test| matches!(test.status, TestStatus::Success | TestStatus::Pending))
+    }
+
+    fn count(&self, status: TestStatus) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.status == status)
+            .count()
+    }
+
+    pub fn render(&self, output: &TestOutput) -> String {
+        match output {
+            TestOutput::Tap => self.to_tap(),
+            TestOutput::Junit => self.to_junit(),
+            TestOutput::Json => {
+                serde_json::to_string_pretty(self).expect("failed to serialize test report")
+            }
+        }
+    }
+
+    fn to_tap(&self) -> String {
+        let mut tap = format!("1..{}\n", self.tests.len());
+        for (i, test) in self.tests.iter().enumerate() {
+            let number = i + 1;
+            let _ = match test.status {
+                TestStatus::Success => writeln!(tap, "ok {number} - {}", test.name),
+                TestStatus::Pending => writeln!(tap, "ok {number} - {} # SKIP", test.name),
+                TestStatus::Failure | TestStatus::Error => {
+                    writeln!(tap, "not ok {number} - {}", test.name)
+                }
+            };
+            if matches!(test.status, TestStatus::Failure | TestStatus::Error) {
+                tap.push_str("  ---\n");
+                let _ = writeln!(tap, "  severity: {}", severity(test.status));
+                if let Some(duration) = test.duration {
+                    let _ = writeln!(tap, "  duration_ms: {:.3}", duration * 1000.0);
+                }
+                if let Some(location) = &test.location {
+                    let _ = writeln!(tap, "  at: {location}");
+                }
+                if let Some(message) = &test.message {
+                    tap.push_str("  message: |\n");
+                    for line in message.lines() {
+                        let _ = writeln!(tap, "    {line}");
+                    }
+                }
+                tap.push_str("  ...\n");
+            }
+        }
+        tap
+    }
+
+    fn to_junit(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"busted\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.6}\">",
+            self.tests.len(),
+            self.count(TestStatus::Failure),
+            self.count(TestStatus::Error),
+            self.count(TestStatus::Pending),
+            self.duration.unwrap_or_default(),
+        );
+        for test in &self.tests {
+            let classname = test
+                .location
+                .as_deref()
+                .and_then(|location| location.split(':').next())
+                .unwrap_or("busted");
+            let _ = write!(
+                xml,
+                "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.6}\"",
+                escape_xml(classname),
+                escape_xml(&test.name),
+                test.duration.unwrap_or_default(),
+            );
+            match test.status {
+                TestStatus::Success => xml.push_str("/>\n"),
+                TestStatus::Pending => xml.push_str(">\n    <skipped/>\n  </testcase>\n"),
+                TestStatus::Failure | TestStatus::Error => {
+                    let tag = severity(test.status);
+                    let message = test.message.as_deref().unwrap_or_default();
+                    let details = [test.location.as_deref(), test.traceback.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .join("\n");
+                    let _ = write!(
+                        xml,
+                        ">\n    <{tag} message=\"{}\">{}</{tag}>\n  </testcase>\n",
+                        escape_xml(message.lines().next().unwrap_or_default()),
+                        escape_xml(&format!("{message}\n{details}")),
+                    );
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn severity(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Error => "error",
+        _ => "failure",
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUSTED_JSON: &str = r#"some output printed by a test
+{"successes":[{"name":"foo adds numbers","element":{"duration":0.0012},"trace":{"short_src":"spec/foo_spec.lua","currentline":3}}],"failures":[{"name":"foo compares <strings>","message":"spec/foo_spec.lua:7: Expected objects to be equal.","element":{"duration":0.002},"trace":{"short_src":"spec/foo_spec.lua","currentline":7,"traceback":"\nstack traceback:\n\tspec/foo_spec.lua:7: in function <spec/foo_spec.lua:6>\n"}}],"errors":[],"pending":[{"name":"foo is pending","element":{},"trace":{"short_src":"spec/foo_spec.lua","currentline":10}}],"duration":0.01}"#;
+
+    #[test]
+    fn parse_busted_json() {
+        let report = TestReport::from_busted_json(BUSTED_JSON).unwrap();
+        assert_eq!(report.tests().len(), 3);
+        assert!(!report.is_success());
+        assert_eq!(report.duration(), Some(0.01));
+        let failure = &report.tests()[1];
+        assert_eq!(failure.status, TestStatus::Failure);
+        assert_eq!(failure.duration, Some(0.002));
+        assert_eq!(failure.location.as_deref(), Some("spec/foo_spec.lua:7"));
+        assert!(failure
+            .traceback
+            .as_deref()
+            .unwrap()
+            .starts_with("stack traceback:"));
+    }
+
+    #[test]
+    fn render_tap() {
+        let report = TestReport::from_busted_json(BUSTED_JSON).unwrap();
+        let tap = report.render(&TestOutput::Tap);
+        assert!(tap.starts_with("1..3\n"));
+        assert!(tap.contains("ok 1 - foo adds numbers\n"));
+        assert!(tap.contains("not ok 2 - foo compares <strings>\n"));
+        assert!(tap.contains("  at: spec/foo_spec.lua:7\n"));
+        assert!(tap.contains("ok 3 - foo is pending # SKIP\n"));
+    }
+
+    #[test]
+    fn render_junit() {
+        let report = TestReport::from_busted_json(BUSTED_JSON).unwrap();
+        let junit = report.render(&TestOutput::Junit);
+        assert!(junit.contains(
+            r#"<testsuite name="busted" tests="3" failures="1" errors="0" skipped="1" time="0.010000">"#
+        ));
+        assert!(junit.contains(
+            r#"<testcase classname="spec/foo_spec.lua" name="foo compares &lt;strings&gt;" time="0.002000">"#
+        ));
+        assert!(junit.contains("<skipped/>"));
+    }
+}
@@ -0,0 +1,275 @@
+//! Globally available tools.
+//!
+//! The executables of rocks that are installed as tools get a shim in the
+//! global bin directory (`~/.lux/bin` by default), which sets up the paths
+//! of the rock and its dependencies before running the executable.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    lockfile::{LocalPackage, Lockfile, ReadOnly},
+    package::{PackageSpec, PackageVersionParseError},
+    path::{Paths, PathsError},
+    tree::{Tree, TreeError},
+};
+
+/// Marks a shim that was generated by lux,
+/// followed by the package name, version and the shim's target.
+const SHIM_MARKER: &str = "lux tool:";
+
+/// An executable in the global bin directory.
+#[derive(Debug, Clone)]
+pub struct GlobalTool {
+    name: String,
+    package: PackageSpec,
+    shim: PathBuf,
+    target: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum GlobalToolError {
+    #[error("{0} does not provide any executables")]
+    NoExecutables(PackageSpec),
+    #[error("invalid version in the shim {}: {err}", shim.display())]
+    ShimVersion {
+        shim: PathBuf,
+        err: PackageVersionParseError,
+    },
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl GlobalTool {
+    /// The name of the executable.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The package that provides the executable.
+    pub fn package(&self) -> &PackageSpec {
+        &self.package
+    }
+
+    /// The shim in the global bin directory.
+    pub fn shim(&self) -> &Path {
+        &self.shim
+    }
+
+    /// The executable in the tree that the shim runs.
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Whether the shim's target still exists,
+    /// i.e. the package hasn't been removed from the tree.
+    pub fn is_installed(&self) -> bool {
+        self.target.is_file()
+    }
+}
+
+/// Write shims for the executables of `packages`, which are installed in the `tree`,
+/// to the global bin directory.
+pub fn write_global_tool_shims(
+    packages: &[LocalPackage],
+    tree: &Tree,
+    config: &Config,
+) -> Result<Vec<GlobalTool>, GlobalToolError> {
+    let lockfile = tree.lockfile()?;
+    let bin_dir = config.global_bin_dir();
+    std::fs::create_dir_all(bin_dir)?;
+    let mut tools = Vec::new();
+    for package in packages {
+        let binaries = package
+            .spec
+            .binaries()
+            .into_iter()
+            .filter_map(|binary| binary.file_name())
+            .map(|binary| binary.to_string_lossy().to_string())
+            .collect_vec();
+        if binaries.is_empty() {
+            return Err(GlobalToolError::NoExecutables(package.to_package()));
+        }
+        let dependencies = dependency_closure(package, &lockfile);
+        let paths = Paths::from_packages(tree, dependencies)?;
+        for name in binaries {
+            let target = binary_target(tree, &name);
+            let shim = shim_path(bin_dir, &name);
+            let tool = GlobalTool {
+                name,
+                package: package.to_package(),
+                shim,
+                target,
+            };
+            std::fs::write(&tool.shim, shim_content(&tool, &paths))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mut perms = std::fs::metadata(&tool.shim)?.permissions();
+                perms.set_mode(0o755);
+                std::fs::set_permissions(&tool.shim, perms)?;
+            }
+            tools.push(tool);
+        }
+    }
+    Ok(tools)
+}
+
+/// List the shims in the global bin directory that were generated by lux.
+pub fn list_global_tools(config: &Config) -> Result<Vec<GlobalTool>, GlobalToolError> {
+    let bin_dir = config.global_bin_dir();
+    if !bin_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_dir(bin_dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .sorted()
+        .filter_map(|shim| {
+            let content = std::fs::read_to_string(&shim).ok()?;
+            let name = shim.file_name()?.to_string_lossy().to_string();
+            #[cfg(target_family = "windows")]
+            let name = name.trim_end_matches(".bat").to_string();
+            parse_shim_marker(&content).map(|(package, version, target)| {
+                let package = PackageSpec::parse(package.to_string(), version.to_string())
+                    .map_err(|err| GlobalToolError::ShimVersion {
+                        shim: shim.clone(),
+                        err,
+                    })?;
+                Ok(GlobalTool {
+                    name,
+                    package,
+                    target: PathBuf::from(target),
+                    shim,
+                })
+            })
+        })
+        .try_collect()
+}
+
+/// The package and its (transitive) dependencies.
+fn dependency_closure<'a>(
+    package: &'a LocalPackage,
+    lockfile: &'a Lockfile<ReadOnly>,
+) -> Vec<&'a LocalPackage> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![package];
+    let mut packages = Vec::new();
+    while let Some(package) = stack.pop() {
+        if seen.insert(package.id()) {
+            stack.extend(
+                package
+                    .dependencies()
+                    .into_iter()
+                    .filter_map(|id| lockfile.get(id)),
+            );
+            packages.push(package);
+        }
+    }
+    packages
+}
+
+/// The path of an installed executable in the tree.
+/// Wrapped Lua scripts are installed as batch files on Windows.
+fn binary_target(tree: &Tree, name: &str) -> PathBuf {
+    let batch_file = tree.bin().join(format!("{name}.bat"));
+    if cfg!(target_family = "windows") && batch_file.is_file() {
+        batch_file
+    } else {
+        tree.bin().join(name)
+    }
+}
+
+fn shim_path(bin_dir: &Path, name: &str) -> PathBuf {
+    if cfg!(target_family = "windows") {
+        bin_dir.join(format!("{name}.bat"))
+    } else {
+        bin_dir.join(name)
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn shim_content(tool: &GlobalTool, paths: &Paths) -> String {
+    // The trailing `;;` adds Lua's default paths.
+    format!(
+        r#"#!/bin/sh
+# {SHIM_MARKER} {} {} {}
+
+LUA_PATH='{};;'
+LUA_CPATH='{};;'
+PATH='{}':"$PATH"
+export LUA_PATH LUA_CPATH PATH
+
+exec '{}' "$@"
+"#,
+        tool.package.name(),
+        tool.package.version(),
+        tool.target.display(),
+        paths.package_path(),
+        paths.package_cpath(),
+        paths.path(),
+        tool.target.display(),
+    )
+}
+
+#[cfg(target_family = "windows")]
+fn shim_content(tool: &GlobalTool, paths: &Paths) -> String {
+    // The trailing `;;` adds Lua's default paths.
+    format!(
+        r#"@echo off
+rem {SHIM_MARKER} {} {} {}
+setlocal
+
+set "LUA_PATH={};;"
+set "LUA_CPATH={};;"
+set "PATH={};%PATH%"
+
+call "{}" %*
+
+exit /b %ERRORLEVEL%
+"#,
+        tool.package.name(),
+        tool.package.version(),
+        tool.target.display(),
+        paths.package_path(),
+        paths.package_cpath(),
+        paths.path(),
+        tool.target.display(),
+    )
+}
+
+/// Returns the package name, version and target of a shim.
+fn parse_shim_marker(content: &str) -> Option<(&str, &str, &str)> {
+    content.lines().find_map(|line| {
+        let (_, marker) = line.split_once(SHIM_MARKER)?;
+        let mut parts = marker.trim().splitn(3, ' ');
+        Some((parts.next()?, parts.next()?, parts.next()?))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shim() {
+        let content = "#!/bin/sh\n# lux tool: busted 2.2.0-1 /home/user/tree/5.1/bin/busted\n";
+        assert_eq!(
+            parse_shim_marker(content),
+            Some(("busted", "2.2.0-1", "/home/user/tree/5.1/bin/busted"))
+        );
+        assert_eq!(parse_shim_marker("#!/bin/sh\nexec busted \"$@\"\n"), None);
+    }
+}
@@ -53,6 +53,7 @@ async fn unpack_src_rock_impl<R: Read + Seek + Send>(
 }
 
 #[async_recursion]
+#[tracing::instrument(skip_all, fields(file_name = %file_name, dest_dir = %dest_dir.display()))]
 pub(crate) async fn unpack<R>(
     mime_type: Option<&str>,
     reader: R,
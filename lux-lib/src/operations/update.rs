@@ -10,7 +10,7 @@ use crate::{
         LocalPackage, LocalPackageLockType, Lockfile, PinnedState, ProjectLockfile, ReadOnly,
         ReadWrite,
     },
-    package::{PackageReq, RockConstraintUnsatisfied},
+    package::{PackageReq, PackageVersion, RockConstraintUnsatisfied},
     progress::{MultiProgress, Progress},
     project::{Project, ProjectError, ProjectTreeError},
     remote_package_db::{RemotePackageDB, RemotePackageDBError},
@@ -112,6 +112,55 @@ impl<State: update_builder::State> UpdateBuilder<'_, State> {
             None => update_install_tree(args, package_db).await,
         }
     }
+
+    /// Returns the packages that would be updated, along with the versions they
+    /// would be updated to, without modifying the lockfile or the install tree.
+    pub async fn plan(self) -> Result<Vec<(LocalPackage, PackageVersion)>, UpdateError>
+    where
+        State: update_builder::IsComplete,
+    {
+        let args = self._update();
+
+        let package_db = match &args.package_db {
+            Some(db) => db.clone(),
+            None => {
+                let bar = args.progress.map(|p| p.new_bar());
+                let db = RemotePackageDB::from_config(args.config, &bar).await?;
+                bar.map(|b| b.finish_and_clear());
+                db
+            }
+        };
+
+        let trees = match Project::current()? {
+            Some(project) => vec![
+                (project.tree(args.config)?, args.packages),
+                (project.test_tree(args.config)?, args.test_dependencies),
+                (project.build_tree(args.config)?, args.build_dependencies),
+            ],
+            None => vec![(
+                args.config
+                    .user_tree(LuaVersion::from(args.config)?.clone())?,
+                args.packages,
+            )],
+        };
+
+        let mut planned_updates = Vec::new();
+        for (tree, packages) in trees {
+            let lockfile = tree.lockfile()?;
+            for (package, constraint) in updatable_packages(&lockfile)
+                .into_iter()
+                .filter(|pkg| is_included(pkg, &packages))
+            {
+                if let Ok(Some(version)) = package
+                    .to_package()
+                    .has_update_with(&constraint, &package_db)
+                {
+                    planned_updates.push((package, version));
+                }
+            }
+        }
+        Ok(planned_updates)
+    }
 }
 
 async fn update_project(
@@ -273,6 +322,7 @@ async fn update(
     } else {
         Remove::new(config)
             .packages(updatable.iter().map(|(package, _)| package.id()))
+            .tree(tree.clone())
             .progress(progress.clone())
             .remove()
             .await?;
@@ -0,0 +1,203 @@
+use std::{io, path::PathBuf, string::FromUtf8Error, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+use ssri::Integrity;
+use tempdir::TempDir;
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    cache::{self, VendorDir},
+    config::Config,
+    hash::HasIntegrity,
+    lockfile::{LocalPackage, LocalPackageLockType, RemotePackageSourceUrl},
+    lua_rockspec::{LuaRockspecError, RemoteLuaRockspec},
+    package::PackageSpec,
+    progress::{MultiProgress, Progress, ProgressBar},
+    project::{Project, ProjectError},
+    remote_package_source::RemotePackageSource,
+};
+
+use super::{
+    download::download_bytes, download_binary_rock, download_rockspec_bytes, download_src_rock,
+    DownloadRockspecError, DownloadSrcRockError, FetchSrc, FetchSrcError,
+};
+
+/// Downloads the rockspecs and sources of all packages in a project's lockfile
+/// into a vendor directory, so that they can be installed without network access.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Vendor<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// The directory to vendor the sources into.
+    /// Defaults to the `vendor` directory in the project root.
+    vendor_dir: Option<PathBuf>,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> VendorBuilder<'_, State>
+where
+    State: vendor_builder::State + vendor_builder::IsComplete,
+{
+    pub async fn vendor(self) -> Result<VendorDir, VendorError> {
+        do_vendor(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VendorError {
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("error parsing rockspec URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error(transparent)]
+    DownloadRockspec(#[from] DownloadRockspecError),
+    #[error("failed to convert rockspec response: {0}")]
+    Utf8(#[from] FromUtf8Error),
+    #[error(transparent)]
+    Rockspec(#[from] LuaRockspecError),
+    #[error(transparent)]
+    DownloadSrcRock(#[from] DownloadSrcRockError),
+    #[error("failed to fetch source: {0}")]
+    FetchSrc(#[from] FetchSrcError),
+    #[error("the source archive of {0} ({1}) is not cached and lux is offline")]
+    SourceNotCached(PackageSpec, Url),
+    #[error("failed to download the source archive of {0}: {1}")]
+    DownloadSource(PackageSpec, reqwest::Error),
+    #[error("integrity mismatch for the source archive of {package}:\nexpected: {expected}\nactual: {actual}")]
+    SourceIntegrity {
+        package: PackageSpec,
+        expected: Integrity,
+        actual: Integrity,
+    },
+}
+
+async fn do_vendor(args: Vendor<'_>) -> Result<VendorDir, VendorError> {
+    let project = args.project;
+    let config = args.config;
+    let vendor_dir = VendorDir::new(
+        args.vendor_dir
+            .unwrap_or_else(|| project.root().join("vendor")),
+    );
+    let lockfile = project.lockfile()?;
+    let packages = [
+        LocalPackageLockType::Regular,
+        LocalPackageLockType::Test,
+        LocalPackageLockType::Build,
+    ]
+    .iter()
+    .flat_map(|lock_type| lockfile.rocks(lock_type).values().cloned())
+    .unique_by(|package| package.id())
+    .collect_vec();
+
+    let progress = args.progress.unwrap_or(MultiProgress::new_arc());
+    for package in packages {
+        let bar = progress.map(|p| p.new_bar());
+        vendor_package(&package, &vendor_dir, config, &bar).await?;
+        bar.map(|b| b.finish_and_clear());
+    }
+
+    Ok(vendor_dir)
+}
+
+async fn vendor_package(
+    package: &LocalPackage,
+    vendor_dir: &VendorDir,
+    config: &Config,
+    progress: &Progress<ProgressBar>,
+) -> Result<(), VendorError> {
+    progress.map(|p| p.set_message(format!("📦 Vendoring {}", package.to_package())));
+    let package_spec = package.to_package();
+    // prioritise lockfile source_url
+    let locked_url = match &package.source_url {
+        Some(RemotePackageSourceUrl::Url { url }) => Some(url.clone()),
+        _ => None,
+    };
+    let rockspec = match package.source() {
+        RemotePackageSource::LuarocksRockspec(url) => {
+            let rockspec_name = format!("{}-{}.rockspec", package.name(), package.version());
+            let rockspec_url = Url::parse(&format!("{}/{}", &url, rockspec_name))?;
+            let bytes = download_rockspec_bytes(&rockspec_url, config).await?;
+            vendor_dir
+                .insert(&rockspec_url, &rockspec_name, &bytes)
+                .await?;
+            Some(RemoteLuaRockspec::new(&String::from_utf8(bytes.into())?)?)
+        }
+        RemotePackageSource::RockspecContent(content) => Some(RemoteLuaRockspec::new(content)?),
+        RemotePackageSource::LuarocksBinaryRock(url) => {
+            let url = locked_url.unwrap_or(url.clone());
+            let rock = download_binary_rock(&package_spec, &url, config, progress).await?;
+            vendor_dir
+                .insert(&rock.url, &rock.file_name, &rock.bytes)
+                .await?;
+            None
+        }
+        RemotePackageSource::LuarocksSrcRock(url) => {
+            let url = locked_url.unwrap_or(url.clone());
+            let rock = download_src_rock(&package_spec, &url, config, progress).await?;
+            vendor_dir
+                .insert(&rock.url, &rock.file_name, &rock.bytes)
+                .await?;
+            None
+        }
+        RemotePackageSource::Local => None,
+        #[cfg(test)]
+        RemotePackageSource::Test => None,
+    };
+    let rockspec = match rockspec {
+        Some(rockspec) => rockspec,
+        None => return Ok(()),
+    };
+
+    let temp_dir = TempDir::new(&package.name().to_string())?;
+    let metadata = FetchSrc::new(temp_dir.path(), &rockspec, config, progress)
+        .maybe_source_url(package.source_url.clone())
+        .expected_hash(package.hashes().source.clone())
+        .fetch_internal()
+        .await?;
+    match &metadata.source_url {
+        RemotePackageSourceUrl::Url { url } => {
+            // The archive is usually added to the download cache when it is fetched,
+            // but it may have been evicted or never have come through the cache.
+            let expected_hash = &package.hashes().source;
+            let bytes = match cache::get_cached(url, Some(expected_hash), config).await? {
+                Some(bytes) => bytes,
+                None if config.offline() => {
+                    return Err(VendorError::SourceNotCached(package_spec, url.clone()))
+                }
+                None => {
+                    let bytes = download_bytes(url, config, progress)
+                        .await
+                        .map_err(|err| VendorError::DownloadSource(package_spec.clone(), err))?;
+                    let hash = bytes.hash()?;
+                    if expected_hash.matches(&hash).is_none() {
+                        return Err(VendorError::SourceIntegrity {
+                            package: package_spec,
+                            expected: expected_hash.clone(),
+                            actual: hash,
+                        });
+                    }
+                    bytes
+                }
+            };
+            let file_name = metadata
+                .archive_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or(url.to_string());
+            vendor_dir.insert(url, &file_name, &bytes).await?;
+        }
+        RemotePackageSourceUrl::Git { url, checkout_ref } => {
+            vendor_dir.store_git_checkout(url, checkout_ref, temp_dir.path())?;
+        }
+        RemotePackageSourceUrl::File { .. } => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,368 @@
+//! Self-contained environments, consisting of a Lua installation, a tree
+//! and scripts for activating them in a shell, similar to hererocks.
+//!
+//! An environment's tree is the environment directory itself,
+//! so an environment created in a project's `.lux` directory shares the project's tree.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::{Config, LuaVersion, LuaVersionUnset},
+    lua_installation::{LuaInstallation, LuaInstallationError},
+    progress::{MultiProgress, Progress, ProgressBar},
+    tree::{Tree, TreeError},
+};
+
+/// The environment variable that points to the active environment.
+pub const LUX_VENV: &str = "LUX_VENV";
+
+const VENV_TOML: &str = "lux-venv.toml";
+
+/// The environment variables that are modified by the activation scripts.
+const ACTIVATED_VARS: [&str; 4] = ["PATH", "LUA_PATH", "LUA_CPATH", "LUA_INIT"];
+
+#[derive(Serialize, Deserialize)]
+struct VenvToml {
+    lua_version: LuaVersion,
+}
+
+/// An environment created with [`CreateVenv`].
+#[derive(Debug, Clone)]
+pub struct Venv {
+    root: PathBuf,
+    lua_version: LuaVersion,
+}
+
+#[derive(Error, Debug)]
+pub enum VenvError {
+    #[error("{} is not a lux environment (no {VENV_TOML} found)", .0.display())]
+    NotAVenv(PathBuf),
+    #[error("error parsing {}:\n{err}", path.display())]
+    VenvToml { path: PathBuf, err: toml::de::Error },
+    #[error(transparent)]
+    SerializeVenvToml(#[from] toml::ser::Error),
+    #[error(transparent)]
+    LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error("error installing Lua:\n{0}")]
+    LuaInstallation(#[from] LuaInstallationError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Venv {
+    /// Open the environment in the `root` directory.
+    pub fn open(root: &Path) -> Result<Self, VenvError> {
+        let path = root.join(VENV_TOML);
+        if !path.is_file() {
+            return Err(VenvError::NotAVenv(root.to_path_buf()));
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let venv_toml: VenvToml =
+            toml::from_str(&content).map_err(|err| VenvError::VenvToml { path, err })?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            lua_version: venv_toml.lua_version,
+        })
+    }
+
+    /// The environment that is currently activated, if any.
+    pub fn from_env() -> Result<Option<Self>, VenvError> {
+        match std::env::var_os(LUX_VENV).filter(|root| !root.is_empty()) {
+            Some(root) => Ok(Some(Self::open(Path::new(&root))?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn lua_version(&self) -> &LuaVersion {
+        &self.lua_version
+    }
+
+    /// The prefix of the environment's Lua installation.
+    pub fn lua_dir(&self) -> PathBuf {
+        self.root.join("lua")
+    }
+
+    /// The environment's tree.
+    pub fn tree(&self, config: &Config) -> Result<Tree, TreeError> {
+        config
+            .clone()
+            .with_tree(self.root.clone())
+            .user_tree(self.lua_version.clone())
+    }
+}
+
+/// Creates an environment with its own Lua installation and tree.
+/// If the directory already contains an environment, its Lua installation
+/// is reused and the activation scripts are regenerated.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct CreateVenv<'a> {
+    #[builder(start_fn)]
+    root: &'a Path,
+    #[builder(start_fn)]
+    config: &'a Config,
+    /// The `lx` executable that the activation scripts run
+    /// to set up the paths. Defaults to `lx` on the `PATH`.
+    lx: Option<PathBuf>,
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> CreateVenvBuilder<'_, State>
+where
+    State: create_venv_builder::State + create_venv_builder::IsComplete,
+{
+    pub async fn create(self) -> Result<Venv, VenvError> {
+        do_create_venv(self._build()).await
+    }
+}
+
+async fn do_create_venv(args: CreateVenv<'_>) -> Result<Venv, VenvError> {
+    let root = std::path::absolute(args.root)?;
+    let lua_version = LuaVersion::from(args.config)?.clone();
+    let venv = Venv {
+        root: root.clone(),
+        lua_version: lua_version.clone(),
+    };
+    let config = args
+        .config
+        .clone()
+        .with_lua_dir(venv.lua_dir())
+        .with_tree(root.clone());
+
+    if LuaInstallation::find_installed(&lua_version, &config).is_none() {
+        let progress = args.progress.unwrap_or_else(MultiProgress::new_arc);
+        let bar = progress.map(|p| {
+            p.add(ProgressBar::from(format!(
+                "🌔 Installing Lua ({lua_version})"
+            )))
+        });
+        LuaInstallation::install(&lua_version, &config).await?;
+        bar.map(|b| b.finish_and_clear());
+    }
+    let tree = venv.tree(&config)?;
+
+    let venv_toml = VenvToml {
+        lua_version: lua_version.clone(),
+    };
+    std::fs::write(root.join(VENV_TOML), toml::to_string(&venv_toml)?)?;
+
+    let lx = args.lx.unwrap_or_else(|| PathBuf::from("lx"));
+    let scripts = ActivationScripts {
+        venv: &venv,
+        bin_dirs: vec![tree.bin(), venv.lua_dir().join("bin")],
+        lx: &lx,
+    };
+    std::fs::write(root.join("activate"), scripts.posix())?;
+    std::fs::write(root.join("activate.fish"), scripts.fish())?;
+    std::fs::write(root.join("activate.ps1"), scripts.powershell())?;
+    Ok(venv)
+}
+
+struct ActivationScripts<'a> {
+    venv: &'a Venv,
+    /// Directories to prepend to the `PATH`
+    bin_dirs: Vec<PathBuf>,
+    lx: &'a Path,
+}
+
+impl ActivationScripts<'_> {
+    fn posix(&self) -> String {
+        let restore = ACTIVATED_VARS
+            .iter()
+            .map(|var| {
+                format!(
+                    r#"        if [ -n "${{_LUX_OLD_{var}+x}}" ]; then
+            {var}="$_LUX_OLD_{var}"
+            export {var}
+            unset _LUX_OLD_{var}
+        else
+            unset {var}
+        fi
+"#
+                )
+            })
+            .collect::<String>();
+        let save = ACTIVATED_VARS
+            .iter()
+            .map(|var| format!("if [ -n \"${{{var}+x}}\" ]; then _LUX_OLD_{var}=\"${var}\"; fi\n"))
+            .collect::<String>();
+        let path = self
+            .bin_dirs
+            .iter()
+            .map(|dir| format!("{}:", single_quoted(dir)))
+            .collect::<String>();
+        format!(
+            r#"# Activate this environment with `source {root}/activate` (bash or zsh).
+# Run `deactivate` to leave it.
+# Source this file again after installing packages, to update the paths.
+
+deactivate () {{
+    if [ -n "${{{LUX_VENV}:-}}" ]; then
+{restore}        unset {LUX_VENV}
+        hash -r 2>/dev/null
+    fi
+    if [ "${{1:-}}" != "nondestructive" ]; then
+        unset -f deactivate
+    fi
+}}
+
+deactivate nondestructive
+
+{save}
+{LUX_VENV}={root_quoted}
+export {LUX_VENV}
+PATH={path}"$PATH"
+export PATH
+unset LUA_PATH LUA_CPATH LUA_INIT
+eval "$({lx} path full --no-bin --shell posix)"
+hash -r 2>/dev/null
+"#,
+            root = self.venv.root.display(),
+            root_quoted = single_quoted(&self.venv.root),
+            lx = single_quoted(self.lx),
+        )
+    }
+
+    fn fish(&self) -> String {
+        let vars = ACTIVATED_VARS.join(" ");
+        let path = self
+            .bin_dirs
+            .iter()
+            .map(|dir| single_quoted(dir))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            r#"# Activate this environment with `source {root}/activate.fish`.
+# Run `deactivate` to leave it.
+# Source this file again after installing packages, to update the paths.
+
+function deactivate -d "Leave the lux environment"
+    if set -q {LUX_VENV}
+        for var in {vars}
+            set -l old _LUX_OLD_$var
+            if set -q $old
+                set -gx $var $$old
+                set -e $old
+            else
+                set -e $var
+            end
+        end
+        set -e {LUX_VENV}
+    end
+    if test "$argv[1]" != nondestructive
+        functions -e deactivate
+    end
+end
+
+deactivate nondestructive
+
+for var in {vars}
+    if set -q $var
+        set -gx _LUX_OLD_$var $$var
+    end
+end
+set -gx {LUX_VENV} {root_quoted}
+set -gx PATH {path} $PATH
+set -e LUA_PATH LUA_CPATH LUA_INIT
+{lx} path full --no-bin --shell fish | source
+"#,
+            root = self.venv.root.display(),
+            root_quoted = single_quoted(&self.venv.root),
+            lx = single_quoted(self.lx),
+        )
+    }
+
+    fn powershell(&self) -> String {
+        let vars = ACTIVATED_VARS
+            .iter()
+            .map(|var| format!("'{var}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let path = self
+            .bin_dirs
+            .iter()
+            .map(|dir| format!("{} + [IO.Path]::PathSeparator + ", ps_quoted(dir)))
+            .collect::<String>();
+        format!(
+            r#"# Activate this environment with `. {root}/activate.ps1`.
+# Run `deactivate` to leave it.
+# Run this script again after installing packages, to update the paths.
+
+function global:deactivate([switch]$NonDestructive) {{
+    if (Test-Path Env:{LUX_VENV}) {{
+        foreach ($var in @({vars})) {{
+            if (Test-Path "Env:_LUX_OLD_$var") {{
+                Set-Item "Env:$var" (Get-Item "Env:_LUX_OLD_$var").Value
+                Remove-Item "Env:_LUX_OLD_$var"
+            }} elseif (Test-Path "Env:$var") {{
+                Remove-Item "Env:$var"
+            }}
+        }}
+        Remove-Item Env:{LUX_VENV}
+    }}
+    if (-not $NonDestructive) {{
+        Remove-Item Function:deactivate
+    }}
+}}
+
+deactivate -NonDestructive
+
+foreach ($var in @({vars})) {{
+    if (Test-Path "Env:$var") {{
+        Set-Item "Env:_LUX_OLD_$var" (Get-Item "Env:$var").Value
+    }}
+}}
+$env:{LUX_VENV} = {root_quoted}
+$env:PATH = {path}$env:PATH
+foreach ($var in @('LUA_PATH', 'LUA_CPATH', 'LUA_INIT')) {{
+    if (Test-Path "Env:$var") {{
+        Remove-Item "Env:$var"
+    }}
+}}
+& {lx} path full --no-bin --shell powershell | Out-String | Invoke-Expression
+"#,
+            root = self.venv.root.display(),
+            root_quoted = ps_quoted(&self.venv.root),
+            lx = ps_quoted(self.lx),
+        )
+    }
+}
+
+/// Quote a path for use in a POSIX or fish shell script.
+fn single_quoted(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+/// Quote a path for use in a PowerShell script.
+fn ps_quoted(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_venv() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        assert!(matches!(Venv::open(&dir), Err(VenvError::NotAVenv(_))));
+        std::fs::write(dir.join(VENV_TOML), "lua_version = \"5.4\"\n").unwrap();
+        let venv = Venv::open(&dir).unwrap();
+        assert_eq!(venv.lua_version(), &LuaVersion::Lua54);
+        assert_eq!(venv.lua_dir(), dir.join("lua"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashSet, io, path::PathBuf, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    build::BuildBehaviour,
+    config::Config,
+    lockfile::LocalPackage,
+    progress::{MultiProgress, Progress},
+    tree::{self, checksums::RockChecksums, Tree, TreeError},
+};
+
+use super::{Install, InstallError, PackageInstallSpec};
+
+/// Verifies the files of the packages installed in a tree
+/// against the checksums that were recorded at install time.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Verify<'a> {
+    #[builder(start_fn)]
+    tree: &'a Tree,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Whether to reinstall corrupted packages and remove orphaned rock directories.
+    #[builder(default)]
+    fix: bool,
+
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> VerifyBuilder<'_, State>
+where
+    State: verify_builder::State + verify_builder::IsComplete,
+{
+    pub async fn verify(self) -> Result<VerifyReport, VerifyError> {
+        do_verify(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error("failed to reinstall corrupted packages: {0}")]
+    Install(#[from] Box<InstallError>),
+}
+
+/// A package whose installed files don't match the checksums recorded at install time.
+#[derive(Debug)]
+pub struct CorruptedPackage {
+    package: LocalPackage,
+    modified: Vec<PathBuf>,
+    deleted: Vec<PathBuf>,
+}
+
+impl CorruptedPackage {
+    pub fn package(&self) -> &LocalPackage {
+        &self.package
+    }
+
+    /// Files (relative to the rock directory) that were modified or added after installation.
+    pub fn modified(&self) -> &[PathBuf] {
+        &self.modified
+    }
+
+    /// Files (relative to the rock directory) that were deleted after installation.
+    pub fn deleted(&self) -> &[PathBuf] {
+        &self.deleted
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    corrupted: Vec<CorruptedPackage>,
+    orphaned: Vec<PathBuf>,
+    unverified: Vec<LocalPackage>,
+}
+
+impl VerifyReport {
+    /// Packages whose files have been modified or deleted.
+    pub fn corrupted(&self) -> &[CorruptedPackage] {
+        &self.corrupted
+    }
+
+    /// Rock directories in the tree that don't belong to any package in the lockfile.
+    pub fn orphaned(&self) -> &[PathBuf] {
+        &self.orphaned
+    }
+
+    /// Packages that could not be verified, because no checksums were recorded
+    /// when they were installed.
+    pub fn unverified(&self) -> &[LocalPackage] {
+        &self.unverified
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.corrupted.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+async fn do_verify(args: Verify<'_>) -> Result<VerifyReport, VerifyError> {
+    let tree = args.tree;
+    let lockfile = tree.lockfile()?;
+
+    let mut report = VerifyReport::default();
+
+    for package in lockfile.rocks().values() {
+        let layout = tree.installed_rock_layout(package)?;
+        if !layout.rock_path.is_dir() {
+            report.corrupted.push(CorruptedPackage {
+                package: package.clone(),
+                modified: Vec::new(),
+                deleted: vec![layout.rock_path],
+            });
+            continue;
+        }
+        match RockChecksums::read(&layout)? {
+            Some(checksums) => {
+                let diff = checksums.diff(&RockChecksums::compute(&layout)?);
+                if !diff.is_empty() {
+                    report.corrupted.push(CorruptedPackage {
+                        package: package.clone(),
+                        modified: diff.modified,
+                        deleted: diff.deleted,
+                    });
+                }
+            }
+            None => report.unverified.push(package.clone()),
+        }
+    }
+
+    let rock_dirs: HashSet<PathBuf> = lockfile
+        .rocks()
+        .values()
+        .map(|package| tree.root_for(package))
+        .collect();
+    report.orphaned.extend(
+        tree.rock_dirs()?
+            .into_iter()
+            .filter(|dir| !rock_dirs.contains(dir)),
+    );
+
+    if args.fix {
+        for path in &report.orphaned {
+            tokio::fs::remove_dir_all(path).await?;
+        }
+
+        if !report.corrupted.is_empty() {
+            for corrupted in &report.corrupted {
+                let rock_path = tree.root_for(&corrupted.package);
+                if rock_path.is_dir() {
+                    tokio::fs::remove_dir_all(rock_path).await?;
+                }
+            }
+            let packages = report
+                .corrupted
+                .iter()
+                .map(|corrupted| {
+                    let package = &corrupted.package;
+                    let entry_type = if lockfile.is_entrypoint(&package.id()) {
+                        tree::EntryType::Entrypoint
+                    } else {
+                        tree::EntryType::DependencyOnly
+                    };
+                    PackageInstallSpec::new(package.clone().into_package_req(), entry_type)
+                        .build_behaviour(BuildBehaviour::Force)
+                        .pin(package.pinned())
+                        .opt(package.opt())
+                        .constraint(package.constraint())
+                        .build()
+                })
+                .collect_vec();
+
+            Install::new(args.config)
+                .packages(packages)
+                .package_db(lockfile.local_pkg_lock().clone().into())
+                .tree(tree.clone())
+                .progress(args.progress.unwrap_or(MultiProgress::new_arc()))
+                .install()
+                .await
+                .map_err(Box::new)?;
+        }
+    }
+
+    Ok(report)
+}
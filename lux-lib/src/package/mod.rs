@@ -8,15 +8,15 @@ mod outdated;
 mod version;
 
 pub use outdated::*;
+pub(crate) use version::HasModRev;
 pub use version::{
     PackageVersion, PackageVersionParseError, PackageVersionReq, PackageVersionReqError,
-    VersionReqToVersionError,
+    VersionBump, VersionBumpError, VersionReqToVersionError,
 };
 
 use crate::{
     lockfile::RemotePackageSourceUrl,
     lua_rockspec::{DisplayAsLuaKV, DisplayLuaKV, DisplayLuaValue},
-    package::version::HasModRev,
     remote_package_source::RemotePackageSource,
     rockspec::lua_dependency::LuaDependencySpec,
     variables::{GetVariableError, HasVariables},
@@ -77,6 +77,65 @@ impl PackageVersion {
     pub(crate) fn default_dev_version() -> Self {
         Self::DevVer(DevVer::default())
     }
+
+    /// Increment the version.
+    /// Only SemVer versions can be bumped by component.
+    /// The pre-release and build metadata are dropped and the specrev is reset.
+    pub fn bump(&self, bump: &VersionBump) -> Result<Self, VersionBumpError> {
+        let (major, minor, patch, component_count) = match (self, bump) {
+            (_, VersionBump::Exact(version)) => return Ok(version.clone()),
+            (PackageVersion::SemVer(SemVer { version, .. }), VersionBump::Major) => {
+                (version.major + 1, 0, 0, 1)
+            }
+            (PackageVersion::SemVer(SemVer { version, .. }), VersionBump::Minor) => {
+                (version.major, version.minor + 1, 0, 2)
+            }
+            (PackageVersion::SemVer(SemVer { version, .. }), VersionBump::Patch) => {
+                (version.major, version.minor, version.patch + 1, 3)
+            }
+            (version, _) => return Err(VersionBumpError::NotSemVer(version.clone())),
+        };
+        let component_count = match self {
+            PackageVersion::SemVer(semver) => cmp::max(semver.component_count, component_count),
+            _ => component_count,
+        };
+        Ok(PackageVersion::SemVer(SemVer {
+            version: Version::new(major, minor, patch),
+            component_count,
+            specrev: 1,
+        }))
+    }
+}
+
+/// How to increment a [`PackageVersion`]: `major`, `minor`, `patch`,
+/// or an exact version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionBump {
+    Major,
+    Minor,
+    Patch,
+    Exact(PackageVersion),
+}
+
+impl FromStr for VersionBump {
+    type Err = PackageVersionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(Self::Major),
+            "minor" => Ok(Self::Minor),
+            "patch" => Ok(Self::Patch),
+            version => Ok(Self::Exact(PackageVersion::parse(version)?)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum VersionBumpError {
+    #[error(
+        "cannot bump {0}, as it is not a SemVer version.\nSpecify the new version explicitly."
+    )]
+    NotSemVer(PackageVersion),
 }
 
 impl TryFrom<PackageVersionReq> for PackageVersion {
@@ -707,6 +766,39 @@ fn append_minor_patch_if_missing(version: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn bump_version() {
+        let version = PackageVersion::parse("1.2.3-2").unwrap();
+        assert_eq!(
+            version.bump(&VersionBump::Major).unwrap(),
+            PackageVersion::parse("2.0.0-1").unwrap()
+        );
+        assert_eq!(
+            version.bump(&VersionBump::Minor).unwrap(),
+            PackageVersion::parse("1.3.0-1").unwrap()
+        );
+        assert_eq!(
+            version.bump(&VersionBump::Patch).unwrap(),
+            PackageVersion::parse("1.2.4-1").unwrap()
+        );
+        assert_eq!(
+            PackageVersion::parse("1.0")
+                .unwrap()
+                .bump(&VersionBump::Patch)
+                .unwrap()
+                .to_string(),
+            "1.0.1-1"
+        );
+        assert_eq!(
+            "3.0.0".parse::<VersionBump>().unwrap(),
+            VersionBump::Exact(PackageVersion::parse("3.0.0").unwrap())
+        );
+        assert!(PackageVersion::parse("dev")
+            .unwrap()
+            .bump(&VersionBump::Minor)
+            .is_err());
+    }
+
     #[tokio::test]
     async fn parse_semver_version() {
         assert_eq!(
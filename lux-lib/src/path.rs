@@ -1,12 +1,18 @@
 use itertools::Itertools;
-use path_slash::PathBufExt;
+use path_slash::{PathBufExt, PathExt};
 use serde::Serialize;
-use std::{env, fmt::Display, path::PathBuf, str::FromStr};
+use std::{
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use thiserror::Error;
 
 use crate::{
     build::utils::c_dylib_extension,
     config::LuaVersion,
+    lockfile::LocalPackage,
     tree::{Tree, TreeError},
 };
 
@@ -42,15 +48,22 @@ impl Paths {
     }
 
     pub fn new(tree: &Tree) -> Result<Self, PathsError> {
-        let mut paths = tree
+        let packages = tree
             .list()?
             .into_iter()
-            .flat_map(|(_, packages)| {
-                packages
-                    .into_iter()
-                    .map(|package| tree.installed_rock_layout(&package))
-                    .collect_vec()
-            })
+            .flat_map(|(_, packages)| packages)
+            .collect_vec();
+        Self::from_packages(tree, &packages)
+    }
+
+    /// The paths for a subset of the packages installed in the tree.
+    pub(crate) fn from_packages<'a>(
+        tree: &Tree,
+        packages: impl IntoIterator<Item = &'a LocalPackage>,
+    ) -> Result<Self, PathsError> {
+        let mut paths = packages
+            .into_iter()
+            .map(|package| tree.installed_rock_layout(package))
             .try_fold(Self::default(tree), |mut paths, package| {
                 let package = package?;
                 paths.src.0.push(package.src.join("?.lua"));
@@ -112,6 +125,33 @@ impl Paths {
         format!("if _VERSION:find('{}') then {LUA_INIT} end", self.version)
     }
 
+    /// Get a snippet for Neovim's `init.lua`, which adds the Lua and native library paths
+    /// to `package.path` and `package.cpath`, and the `packpath` (if any) to Neovim's `packpath`.
+    pub fn nvim_init(&self, packpath: Option<&Path>, prepend: bool) -> String {
+        let extend = |var: &str, paths: &PackagePath| {
+            if prepend {
+                format!("{var} = [[{paths}]] .. \"{LUA_PATH_SEPARATOR}\" .. {var}\n")
+            } else {
+                format!("{var} = {var} .. \"{LUA_PATH_SEPARATOR}\" .. [[{paths}]]\n")
+            }
+        };
+        let mut snippet = String::new();
+        if !self.src.is_empty() {
+            snippet.push_str(&extend("package.path", &self.src));
+        }
+        if !self.lib.is_empty() {
+            snippet.push_str(&extend("package.cpath", &self.lib));
+        }
+        if let Some(packpath) = packpath {
+            let method = if prepend { "prepend" } else { "append" };
+            snippet.push_str(&format!(
+                "vim.opt.packpath:{method}([[{}]])\n",
+                packpath.to_slash_lossy()
+            ));
+        }
+        snippet
+    }
+
     /// Get the `$PATH`, prepended to the existing `$PATH` environment.
     pub fn path_prepended(&self) -> BinPath {
         let mut path = BinPath::from_env();
@@ -182,6 +222,9 @@ impl BinPath {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.0.iter().any(|path| path == dir)
+    }
     pub fn joined(&self) -> String {
         env::join_paths(self.0.iter().unique())
             .expect("Failed to join bin paths.")
@@ -1,4 +1,72 @@
-use std::{borrow::Cow, sync::Arc, time::Duration};
+//! Progress bars for long-running operations.
+//!
+//! If stderr is a terminal, each bar is rendered in place, so that parallel downloads
+//! and builds each get their own line. Otherwise, each message is printed as a plain log line.
+
+use std::{
+    borrow::Cow,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use indicatif::{ProgressDrawTarget, ProgressStyle};
+
+/// How much progress output to show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Don't show any progress. Warnings are still printed.
+    Quiet,
+    #[default]
+    Normal,
+    /// Also log each step above the progress bars.
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the verbosity of the progress bars that are created afterwards.
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+static BARS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Print progress as plain log lines, even if stderr is a terminal,
+/// e.g. because the output is machine-readable.
+pub fn disable_bars() {
+    BARS_DISABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DrawMode {
+    Bars,
+    Lines,
+    Hidden,
+}
+
+impl DrawMode {
+    fn current() -> Self {
+        if verbosity() == Verbosity::Quiet {
+            Self::Hidden
+        } else if std::io::stderr().is_terminal() && !BARS_DISABLED.load(Ordering::Relaxed) {
+            Self::Bars
+        } else {
+            Self::Lines
+        }
+    }
+}
 
 mod private {
     pub trait HasProgress {}
@@ -36,11 +104,19 @@ where
 
 // WARNING: Don't implement `Clone` for this.
 pub struct MultiProgress(indicatif::MultiProgress);
-pub struct ProgressBar(indicatif::ProgressBar);
+pub struct ProgressBar {
+    bar: indicatif::ProgressBar,
+    mode: DrawMode,
+}
 
 impl MultiProgress {
     pub fn new() -> Self {
-        Self(indicatif::MultiProgress::new())
+        match DrawMode::current() {
+            DrawMode::Bars => Self(indicatif::MultiProgress::new()),
+            DrawMode::Lines | DrawMode::Hidden => Self(indicatif::MultiProgress::with_draw_target(
+                ProgressDrawTarget::hidden(),
+            )),
+        }
     }
 
     pub fn new_arc() -> Arc<Progress<MultiProgress>> {
@@ -48,7 +124,10 @@ impl MultiProgress {
     }
 
     pub fn add(&self, bar: ProgressBar) -> ProgressBar {
-        ProgressBar(self.0.insert_from_back(0, bar.0))
+        ProgressBar {
+            bar: self.0.insert_from_back(0, bar.bar),
+            mode: bar.mode,
+        }
     }
 
     pub fn new_bar(&self) -> ProgressBar {
@@ -71,48 +150,95 @@ impl Default for MultiProgress {
 
 impl ProgressBar {
     pub fn new() -> Self {
-        let bar =
-            indicatif::ProgressBar::new_spinner().with_finish(indicatif::ProgressFinish::AndClear);
-        bar.enable_steady_tick(Duration::from_millis(100));
+        let mode = DrawMode::current();
+        let bar = match mode {
+            DrawMode::Bars => indicatif::ProgressBar::new_spinner(),
+            DrawMode::Lines | DrawMode::Hidden => {
+                indicatif::ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden())
+            }
+        }
+        .with_finish(indicatif::ProgressFinish::AndClear);
+        if mode == DrawMode::Bars {
+            bar.enable_steady_tick(Duration::from_millis(100));
+        }
 
-        Self(bar)
+        Self { bar, mode }
     }
 
     pub fn into_raw(self) -> indicatif::ProgressBar {
-        self.0
+        self.bar
     }
 
     pub fn set_message<M>(&self, message: M)
     where
         M: Into<Cow<'static, str>>,
     {
-        self.0.set_message(message)
+        let message = message.into();
+        match self.mode {
+            DrawMode::Lines if !message.is_empty() && self.bar.message() != message => {
+                eprintln!("{message}")
+            }
+            DrawMode::Bars if verbosity() == Verbosity::Verbose && !message.is_empty() => {
+                self.bar.println(&message)
+            }
+            _ => {}
+        }
+        self.bar.set_message(message)
     }
 
     pub fn set_position(&self, position: u64) {
-        self.0.set_position(position)
+        self.bar.set_position(position)
     }
 
     pub fn position(&self) -> u64 {
-        self.0.position()
+        self.bar.position()
+    }
+
+    /// Show the progress of a download of `length` bytes, with the estimated time remaining.
+    pub fn set_length(&self, length: u64) {
+        self.bar.set_length(length);
+        self.bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner} {msg} [{bar:25}] {bytes}/{total_bytes} ({eta})",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta)
+    }
+
+    /// Go back to showing a spinner after a download.
+    pub fn clear_length(&self) {
+        self.bar.set_style(ProgressStyle::default_spinner());
+        self.bar.set_position(0);
     }
 
     pub fn println<M>(&self, message: M)
     where
         M: AsRef<str>,
     {
-        self.0.println(message)
+        match self.mode {
+            DrawMode::Bars => self.bar.println(message),
+            // Hidden bars don't print anything, but the messages may be warnings.
+            DrawMode::Lines | DrawMode::Hidden => eprintln!("{}", message.as_ref()),
+        }
     }
 
     pub fn finish_with_message<M>(&self, message: M)
     where
         M: Into<Cow<'static, str>>,
     {
-        self.0.finish_with_message(message)
+        let message = message.into();
+        if self.mode == DrawMode::Lines && !message.is_empty() {
+            eprintln!("{message}");
+        }
+        self.bar.finish_with_message(message)
     }
 
     pub fn finish_and_clear(&self) {
-        self.0.finish_and_clear()
+        self.bar.finish_and_clear()
     }
 }
 
@@ -124,7 +250,10 @@ impl Default for ProgressBar {
 
 impl From<String> for ProgressBar {
     fn from(message: String) -> Self {
-        Self(Self::new().0.with_message(message))
+        let new = Self::new();
+        new.set_message(message);
+
+        new
     }
 }
 
@@ -0,0 +1,161 @@
+//! Diagnostics for errors in a `lux.toml`, which point at the offending source.
+
+use std::{fmt::Display, ops::Range};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::rockspec::lint::{similar_key, Position};
+
+use super::PROJECT_TOML;
+
+/// An error in a `lux.toml`, rendered with the offending line, e.g.
+///
+/// ```text
+/// unknown field `dependancies`, did you mean `dependencies`?
+///  --> lux.toml:5:2
+///   |
+/// 5 | [dependancies]
+///   |  ^^^^^^^^^^^^
+/// ```
+#[derive(Debug, Error)]
+pub struct ProjectTomlParseError {
+    message: String,
+    position: Option<Position>,
+    /// The offending line and the number of characters to underline.
+    snippet: Option<(String, usize)>,
+}
+
+impl ProjectTomlParseError {
+    fn new(message: impl Into<String>, content: &str, span: Option<Range<usize>>) -> Self {
+        let (position, snippet) = match span {
+            Some(span) => {
+                let position = Position::from_offset(content, span.start);
+                let line = content
+                    .lines()
+                    .nth(position.line - 1)
+                    .unwrap_or_default()
+                    .to_string();
+                let width = content
+                    .get(span)
+                    .map(|spanned| spanned.lines().next().unwrap_or_default().chars().count())
+                    .unwrap_or_default()
+                    .max(1);
+                (Some(position), Some((line, width)))
+            }
+            None => (None, None),
+        };
+        Self {
+            message: message.into(),
+            position,
+            snippet,
+        }
+    }
+
+    pub(crate) fn from_toml(content: &str, err: toml::de::Error) -> Self {
+        let message = err.message().trim();
+        let message = suggest_known_field(message).unwrap_or_else(|| message.to_string());
+        Self::new(message, content, err.span())
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The position of the offending source, where it can be determined.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+}
+
+impl Display for ProjectTomlParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(position), Some((line, width))) = (self.position, &self.snippet) {
+            let gutter = " ".repeat(position.line.to_string().len());
+            write!(
+                f,
+                "\n{gutter}--> {PROJECT_TOML}:{}:{}\n{gutter} |\n{} | {line}\n{gutter} | {}{}",
+                position.line,
+                position.column,
+                position.line,
+                " ".repeat(position.column - 1),
+                "^".repeat(*width),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace serde's list of expected fields in an "unknown field `x`, expected one of `a`, `b`"
+/// (or "expected `a` or `b`") message with the most similar field, if any.
+fn suggest_known_field(message: &str) -> Option<String> {
+    let (field, expected) = message
+        .strip_prefix("unknown field `")?
+        .split_once("`, expected ")?;
+    let known = expected
+        .trim_start_matches("one of ")
+        .split(", ")
+        .flat_map(|fields| fields.split(" or "))
+        .map(|field| field.trim_matches('`'))
+        .collect_vec();
+    let suggestion = similar_key(field, &known)?;
+    Some(format!(
+        "unknown field `{field}`, did you mean `{suggestion}`?"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::{project_toml::PartialProjectToml, ProjectRoot};
+
+    use super::*;
+
+    fn parse_error(content: &str) -> ProjectTomlParseError {
+        PartialProjectToml::new(content, ProjectRoot::new()).unwrap_err()
+    }
+
+    #[test]
+    fn unknown_field_with_suggestion() {
+        let err = parse_error("package = \"foo\"\n\n[dependancies]\nbar = \"1.0\"\n");
+        assert_eq!(
+            err.message(),
+            "unknown field `dependancies`, did you mean `dependencies`?"
+        );
+        assert_eq!(err.position(), Some(Position { line: 3, column: 2 }));
+        assert_eq!(
+            err.to_string(),
+            "unknown field `dependancies`, did you mean `dependencies`?
+ --> lux.toml:3:2
+  |
+3 | [dependancies]
+  |  ^^^^^^^^^^^^"
+        );
+        assert!(PartialProjectToml::new(
+            "package = \"foo\"\n[workspace]\nmembers = []\n",
+            ProjectRoot::new()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unknown_field_in_nested_table() {
+        let err = parse_error("package = \"foo\"\n[description]\nlicence = \"MIT\"\n");
+        assert_eq!(
+            err.message(),
+            "unknown field `licence`, did you mean `license`?"
+        );
+        assert_eq!(err.position(), Some(Position { line: 3, column: 1 }));
+
+        let err = parse_error("package = \"foo\"\n[run]\ncomand = \"nvim\"\n");
+        assert_eq!(
+            err.message(),
+            "unknown field `comand`, did you mean `command`?"
+        );
+
+        let err = parse_error("package = \"foo\"\n[config]\nfoo = \"bar\"\n");
+        assert!(err
+            .message()
+            .starts_with("unknown field `foo`, expected one of"));
+    }
+}
@@ -22,6 +22,7 @@ use super::ProjectRoot;
 /// - `$(REF)`: Git tag or revision (prioritising tags if present)
 ///
 /// Fields can also be substituted with environment variables.
+#[serde(deny_unknown_fields)]
 pub(crate) struct RockSourceTemplate {
     /// URL template for `SemVer` releases
     url: Option<String>,
@@ -78,6 +79,23 @@ impl HasVariables for GitProject<'_> {
 }
 
 impl RockSourceTemplate {
+    pub(crate) fn has_url(&self) -> bool {
+        self.url.is_some()
+    }
+
+    /// Use a git `url` and `tag` for releases, unless a release URL is set.
+    pub(crate) fn or_git(self, url: String, tag: String) -> Self {
+        if self.has_url() {
+            self
+        } else {
+            Self {
+                url: Some(url),
+                tag: Some(tag),
+                ..self
+            }
+        }
+    }
+
     pub(crate) fn try_generate(
         &self,
         project_root: &ProjectRoot,
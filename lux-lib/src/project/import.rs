@@ -0,0 +1,321 @@
+//! Import a luarocks rockspec into a `lux.toml`.
+
+use itertools::Itertools;
+use mlua::{Lua, Table, Value};
+use thiserror::Error;
+use toml_edit::{Array, DocumentMut, InlineTable, Item};
+
+use crate::{
+    lua_rockspec::PerPlatform, package::PackageVersion, rockspec::lua_dependency::LuaDependencySpec,
+};
+
+use super::project_toml::PartialProjectToml;
+
+#[derive(Error, Debug)]
+pub enum ImportRockspecError {
+    #[error("error evaluating rockspec: {0}")]
+    Lua(#[from] mlua::Error),
+    #[error("the rockspec is missing the `{0}` field")]
+    MissingField(&'static str),
+    #[error("unsupported value in rockspec field `{0}`")]
+    UnsupportedValue(String),
+    #[error("the imported lux.toml is invalid. This is probably a bug.\n{0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+/// Fields that have the same structure in a rockspec and in a `lux.toml`.
+const VERBATIM_FIELDS: [&str; 5] = [
+    "description",
+    "external_dependencies",
+    "build",
+    "test",
+    "deploy",
+];
+
+/// Convert the content of a rockspec into the content of a `lux.toml`.
+pub fn import_rockspec(rockspec_content: &str) -> Result<String, ImportRockspecError> {
+    let lua = Lua::new();
+    lua.load(rockspec_content).exec()?;
+    let globals = lua.globals();
+
+    let mut doc = DocumentMut::new();
+    let package: Option<String> = globals.get("package")?;
+    doc["package"] = toml_edit::value(package.ok_or(ImportRockspecError::MissingField("package"))?);
+    let version: Option<String> = globals.get("version")?;
+    let version = version.ok_or(ImportRockspecError::MissingField("version"))?;
+    // The rockspec revision is added when generating a rockspec
+    let version = match version.rsplit_once('-') {
+        Some((version, specrev)) if specrev.chars().all(|c| c.is_ascii_digit()) => version,
+        _ => &version,
+    };
+    doc["version"] = toml_edit::value(version);
+    if let Some(rockspec_format) = globals.get::<Option<String>>("rockspec_format")? {
+        doc["rockspec_format"] = toml_edit::value(rockspec_format);
+    }
+
+    let dependencies: PerPlatform<Vec<LuaDependencySpec>> = globals.get("dependencies")?;
+    if let Some(lua) = dependencies.default.iter().find(|dep| is_lua(dep)) {
+        doc["lua"] = toml_edit::value(version_req(lua));
+    }
+    if let Some(Value::Table(platforms)) = globals.get::<Option<Value>>("supported_platforms")? {
+        let mut supported_platforms = InlineTable::new();
+        for platform in platforms.sequence_values::<String>() {
+            let platform = platform?;
+            match platform.strip_prefix('!') {
+                Some(platform) => supported_platforms.insert(platform, false.into()),
+                None => supported_platforms.insert(platform.as_str(), true.into()),
+            };
+        }
+        doc["supported_platforms"] = Item::Value(supported_platforms.into());
+    }
+
+    if let Some(source) = globals.get::<Option<Table>>("source")? {
+        let mut source_table = toml_edit::Table::new();
+        // A lux.toml can't pin a branch, so a source that tracks one is a dev source,
+        // for which the default branch is checked out.
+        let is_dev = source.contains_key("branch")?
+            || matches!(
+                PackageVersion::parse(version),
+                Ok(PackageVersion::DevVer(_))
+            );
+        for (field, key) in [
+            ("url", if is_dev { "dev" } else { "url" }),
+            ("tag", "tag"),
+            ("file", "file"),
+            ("dir", "dir"),
+        ] {
+            if let Some(value) = source.get::<Option<String>>(field)? {
+                source_table[key] = toml_edit::value(value);
+            }
+        }
+        doc["source"] = Item::Table(source_table);
+    }
+
+    for (field, dependencies) in [
+        ("dependencies", dependencies),
+        ("build_dependencies", globals.get("build_dependencies")?),
+        ("test_dependencies", globals.get("test_dependencies")?),
+    ] {
+        if let Some(table) = dependency_table(&dependencies) {
+            doc[field] = Item::Table(table);
+        }
+    }
+
+    for field in VERBATIM_FIELDS {
+        if let Some(value) = globals.get::<Option<Value>>(field)? {
+            doc[field] = lua_to_item(&value, field)?;
+        }
+    }
+
+    let content = doc.to_string();
+    let _: PartialProjectToml = toml::from_str(&content)?;
+    Ok(content)
+}
+
+/// The `lux.toml` table of a rockspec's dependencies and their `platforms` overrides.
+/// `lua` is left out, as it is not a dependency in a `lux.toml`.
+fn dependency_table(
+    dependencies: &PerPlatform<Vec<LuaDependencySpec>>,
+) -> Option<toml_edit::Table> {
+    let mut table = toml_edit::Table::new();
+    for dependency in dependencies.default.iter().filter(|dep| !is_lua(dep)) {
+        table[dependency.name().to_string().as_str()] = toml_edit::value(version_req(dependency));
+    }
+    let mut platforms_table = toml_edit::Table::new();
+    platforms_table.set_implicit(true);
+    for (platform, platform_dependencies) in dependencies
+        .per_platform
+        .iter()
+        .sorted_by_key(|(platform, _)| platform.to_string())
+    {
+        // The default dependencies are merged into the platform overrides when parsing
+        let mut platform_table = toml_edit::Table::new();
+        for dependency in platform_dependencies
+            .iter()
+            .filter(|dep| !is_lua(dep) && !dependencies.default.contains(dep))
+        {
+            platform_table[dependency.name().to_string().as_str()] =
+                toml_edit::value(version_req(dependency));
+        }
+        if !platform_table.is_empty() {
+            platforms_table[platform.to_string().as_str()] = Item::Table(platform_table);
+        }
+    }
+    if !platforms_table.is_empty() {
+        table["platforms"] = Item::Table(platforms_table);
+    }
+    (!table.is_empty()).then_some(table)
+}
+
+fn is_lua(dependency: &LuaDependencySpec) -> bool {
+    dependency.name().to_string() == "lua"
+}
+
+fn version_req(dependency: &LuaDependencySpec) -> String {
+    if dependency.version_req().is_any() {
+        "*".into()
+    } else {
+        dependency.version_req().to_string()
+    }
+}
+
+fn lua_to_item(value: &Value, field: &str) -> Result<Item, ImportRockspecError> {
+    match value {
+        Value::Table(table) if !is_sequence(table) => {
+            let mut toml_table = toml_edit::Table::new();
+            for (key, value) in table_entries(table, field)? {
+                toml_table[key.as_str()] = lua_to_item(&value, &format!("{field}.{key}"))?;
+            }
+            Ok(Item::Table(toml_table))
+        }
+        value => Ok(Item::Value(lua_to_value(value, field)?)),
+    }
+}
+
+fn lua_to_value(value: &Value, field: &str) -> Result<toml_edit::Value, ImportRockspecError> {
+    match value {
+        Value::Boolean(value) => Ok((*value).into()),
+        Value::Integer(value) => Ok((*value).into()),
+        Value::Number(value) => Ok((*value).into()),
+        Value::String(value) => Ok(value.to_str()?.to_string().into()),
+        Value::Table(table) if is_sequence(table) => {
+            let mut array = Array::new();
+            for value in table.sequence_values::<Value>() {
+                array.push_formatted(lua_to_value(&value?, field)?);
+            }
+            Ok(array.into())
+        }
+        Value::Table(table) => {
+            let mut inline_table = InlineTable::new();
+            for (key, value) in table_entries(table, field)? {
+                let value = lua_to_value(&value, &format!("{field}.{key}"))?;
+                inline_table.insert(key.as_str(), value);
+            }
+            Ok(inline_table.into())
+        }
+        _ => Err(ImportRockspecError::UnsupportedValue(field.into())),
+    }
+}
+
+fn is_sequence(table: &Table) -> bool {
+    table.raw_len() > 0 && table.raw_len() == table.pairs::<Value, Value>().count()
+}
+
+/// The entries of a Lua table, sorted by key, so that the output is deterministic.
+fn table_entries(table: &Table, field: &str) -> Result<Vec<(String, Value)>, ImportRockspecError> {
+    let mut entries: Vec<(String, Value)> = table
+        .pairs::<Value, Value>()
+        .map(|pair| {
+            let (key, value) = pair?;
+            let key = match key {
+                Value::String(key) => key.to_str()?.to_string(),
+                Value::Integer(key) => key.to_string(),
+                _ => return Err(ImportRockspecError::UnsupportedValue(field.into())),
+            };
+            Ok((key, value))
+        })
+        .try_collect()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lua_rockspec::PlatformIdentifier;
+
+    use super::*;
+
+    #[test]
+    fn test_import_rockspec() {
+        let rockspec = r#"
+            package = "foo"
+            version = "1.0.0-1"
+            source = {
+                url = "git+https://github.com/example/foo",
+                tag = "v1.0.0",
+            }
+            description = {
+                summary = "A foo",
+                license = "MIT",
+            }
+            dependencies = {
+                "lua >= 5.1",
+                "luafilesystem ~> 1.8",
+                "penlight",
+                platforms = {
+                    windows = { "winapi" },
+                },
+            }
+            test_dependencies = { "busted" }
+            build = {
+                type = "builtin",
+                modules = {
+                    ["foo.bar"] = "src/foo/bar.lua",
+                    ["foo.core"] = { sources = { "src/core.c" } },
+                },
+            }
+        "#;
+        let content = import_rockspec(rockspec).unwrap();
+        let project_toml: PartialProjectToml = toml::from_str(&content).unwrap();
+        assert_eq!(project_toml.package.to_string(), "foo");
+        let dependencies = project_toml.dependencies.as_ref().unwrap();
+        assert_eq!(dependencies.default.len(), 2);
+        assert_eq!(
+            dependencies.get(&PlatformIdentifier::Windows).len(),
+            3,
+            "unexpected lux.toml:\n{content}"
+        );
+        assert_eq!(
+            project_toml
+                .test_dependencies
+                .as_ref()
+                .unwrap()
+                .default
+                .len(),
+            1
+        );
+        assert_eq!(
+            project_toml
+                .build
+                .default
+                .builtin_spec
+                .as_ref()
+                .unwrap()
+                .len(),
+            2,
+            "unexpected lux.toml:\n{content}"
+        );
+        assert!(content.contains(r#"version = "1.0.0""#));
+        assert!(content.contains(r#"lua = ">=5.1""#));
+        assert!(content.contains(r#"tag = "v1.0.0""#));
+    }
+
+    #[test]
+    fn test_import_branch_source() {
+        let rockspec = r#"
+            package = "foo"
+            version = "scm-1"
+            source = {
+                url = "git+https://github.com/example/foo",
+                branch = "main",
+            }
+            dependencies = {
+                "foo>=1.0",
+                "bar == 2.0",
+                "Baz",
+                platforms = {
+                    unix = { "luaposix" },
+                },
+            }
+            build = { type = "builtin" }
+        "#;
+        let content = import_rockspec(rockspec).unwrap();
+        let project_toml: PartialProjectToml = toml::from_str(&content).unwrap();
+        assert!(content.contains(r#"dev = "git+https://github.com/example/foo""#));
+        assert!(!content.contains("tag"));
+        let dependencies = project_toml.dependencies.as_ref().unwrap();
+        assert_eq!(dependencies.default.len(), 3);
+        assert!(content.contains(r#"baz = "*""#));
+        assert!(content.contains("[dependencies.platforms.unix]\nluaposix = \"*\""));
+    }
+}
@@ -0,0 +1,277 @@
+//! Generate a `lux.toml` for an existing codebase.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+use toml_edit::{Array, DocumentMut, InlineTable, Item};
+use walkdir::WalkDir;
+
+use crate::{manifest::parse_manifest_entry_file_name, package::PackageVersion};
+
+use super::{
+    import::{import_rockspec, ImportRockspecError},
+    project_toml::PartialProjectToml,
+};
+
+/// The directories that the builtin build backend detects Lua modules in.
+const LUA_SOURCE_DIRS: [&str; 3] = ["lua", "src", "lib"];
+
+/// The directories that C modules are commonly kept in.
+const C_SOURCE_DIRS: [&str; 3] = ["src", "csrc", "c"];
+
+#[derive(Error, Debug)]
+pub enum InitProjectError {
+    #[error("failed to read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("failed to import {0}: {1}")]
+    ImportRockspec(PathBuf, ImportRockspecError),
+    #[error("error parsing the imported lux.toml: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error("the generated lux.toml is invalid. This is probably a bug.\n{0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+/// The layout of an existing codebase.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectLayout {
+    /// A rockspec in the project root or in a `rockspecs/` directory.
+    /// If there are several, a development rockspec or the latest version is used.
+    pub rockspec: Option<PathBuf>,
+    /// The directory with the Lua modules (`lua/`, `src/` or `lib/`),
+    /// which the builtin build backend detects the modules in.
+    pub source_dir: Option<PathBuf>,
+    /// Lua modules in the project root, relative to the project root.
+    pub root_modules: Vec<PathBuf>,
+    /// Whether the project has busted specs, i.e. a `.busted` file
+    /// or `*_spec.lua` files in a `spec/` directory.
+    pub busted: bool,
+    /// C sources, relative to the project root.
+    pub c_sources: Vec<PathBuf>,
+}
+
+impl ProjectLayout {
+    pub fn detect(root: &Path) -> Self {
+        let source_dir = LUA_SOURCE_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .find(|dir| !files_with_extension(&root.join(dir), "lua", usize::MAX).is_empty());
+        // Without a source directory, the Lua files in the project root are its modules
+        let root_modules = match source_dir {
+            Some(_) => Vec::new(),
+            None => files_with_extension(root, "lua", 1)
+                .into_iter()
+                .filter(|path| !is_spec(path))
+                .filter_map(|path| pathdiff::diff_paths(path, root))
+                .collect(),
+        };
+        Self {
+            rockspec: detect_rockspec(root),
+            source_dir,
+            root_modules,
+            busted: root.join(".busted").is_file()
+                || files_with_extension(&root.join("spec"), "lua", usize::MAX)
+                    .iter()
+                    .any(|path| is_spec(path)),
+            c_sources: files_with_extension(root, "c", 1)
+                .into_iter()
+                .chain(
+                    C_SOURCE_DIRS
+                        .iter()
+                        .flat_map(|dir| files_with_extension(&root.join(dir), "c", usize::MAX)),
+                )
+                .filter_map(|path| pathdiff::diff_paths(path, root))
+                .collect(),
+        }
+    }
+}
+
+/// The `[description]` of a generated `lux.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct InitDescription {
+    pub summary: Option<String>,
+    pub license: Option<String>,
+    pub maintainer: Option<String>,
+    pub labels: Vec<String>,
+}
+
+/// Generate the content of a `lux.toml` that matches the layout of an existing codebase.
+///
+/// If the codebase has a rockspec, it is imported. Otherwise, the Lua modules are left to
+/// the builtin build backend to detect, and the C sources are added as modules.
+/// Busted specs are registered by adding `busted` to the `test_dependencies`.
+pub fn init_project_toml(
+    package: &str,
+    layout: &ProjectLayout,
+    description: &InitDescription,
+) -> Result<String, InitProjectError> {
+    let mut doc = match &layout.rockspec {
+        Some(rockspec) => {
+            let content = std::fs::read_to_string(rockspec)
+                .map_err(|err| InitProjectError::Io(rockspec.clone(), err))?;
+            import_rockspec(&content)
+                .map_err(|err| InitProjectError::ImportRockspec(rockspec.clone(), err))?
+                .parse::<DocumentMut>()?
+        }
+        None => new_project_toml(package, layout, description),
+    };
+
+    let has_busted = doc
+        .get("test_dependencies")
+        .and_then(|dependencies| dependencies.get("busted"))
+        .is_some();
+    if layout.busted && !has_busted && !doc.contains_key("test") {
+        doc["test_dependencies"]["busted"] = toml_edit::value("*");
+    }
+
+    let content = doc.to_string();
+    let _: PartialProjectToml = toml::from_str(&content)?;
+    Ok(content)
+}
+
+fn new_project_toml(
+    package: &str,
+    layout: &ProjectLayout,
+    description: &InitDescription,
+) -> DocumentMut {
+    let mut doc = DocumentMut::new();
+    doc["package"] = toml_edit::value(package);
+    doc["version"] = toml_edit::value("0.1.0");
+    doc["lua"] = toml_edit::value(">= 5.1");
+
+    let mut description_table = toml_edit::Table::new();
+    for (key, value) in [
+        ("summary", &description.summary),
+        ("license", &description.license),
+        ("maintainer", &description.maintainer),
+    ] {
+        if let Some(value) = value {
+            description_table[key] = toml_edit::value(value);
+        }
+    }
+    if !description.labels.is_empty() {
+        description_table["labels"] = toml_edit::value(Array::from_iter(&description.labels));
+    }
+    if !description_table.is_empty() {
+        doc["description"] = Item::Table(description_table);
+    }
+
+    let mut build = toml_edit::Table::new();
+    build["type"] = toml_edit::value("builtin");
+    let mut modules = toml_edit::Table::new();
+    for path in &layout.root_modules {
+        modules[module_name(path).as_str()] = toml_edit::value(path_str(path));
+    }
+    for path in &layout.c_sources {
+        let mut module = InlineTable::new();
+        module.insert("sources", Array::from_iter([path_str(path)]).into());
+        modules[module_name(path).as_str()] = toml_edit::value(module);
+    }
+    if !modules.is_empty() {
+        build["modules"] = Item::Table(modules);
+    }
+    doc["build"] = Item::Table(build);
+    doc
+}
+
+/// The rockspec to import. Development rockspecs are preferred, as they track the repository.
+fn detect_rockspec(root: &Path) -> Option<PathBuf> {
+    files_with_extension(root, "rockspec", 1)
+        .into_iter()
+        .chain(files_with_extension(&root.join("rockspecs"), "rockspec", 1))
+        // Dev versions are ordered after all other versions
+        .max_by_key(|path| {
+            path.file_name()
+                .and_then(|file_name| parse_manifest_entry_file_name(&file_name.to_string_lossy()))
+                .and_then(|(_, version, _)| PackageVersion::parse(&version).ok())
+        })
+}
+
+/// The name of the module that a source file provides, e.g. `foo.core` for `src/foo/core.c`.
+fn module_name(path: &Path) -> String {
+    let path = match path.components().count() {
+        1 => path.to_path_buf(),
+        _ => path.components().skip(1).collect(),
+    };
+    path.with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .join(".")
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn is_spec(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().ends_with("_spec"))
+}
+
+fn files_with_extension(dir: &Path, extension: &str, max_depth: usize) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == extension))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_existing_codebase() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let write = |path: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+        write("lua/foo/init.lua");
+        write("spec/foo_spec.lua");
+        write("csrc/foo/core.c");
+
+        let layout = ProjectLayout::detect(&dir);
+        assert_eq!(layout.source_dir, Some(PathBuf::from("lua")));
+        assert!(layout.busted);
+        assert!(layout.root_modules.is_empty());
+        assert_eq!(layout.c_sources, vec![PathBuf::from("csrc/foo/core.c")]);
+
+        let description = InitDescription {
+            summary: Some("A foo".into()),
+            ..InitDescription::default()
+        };
+        let content = init_project_toml("foo", &layout, &description).unwrap();
+        let project_toml: PartialProjectToml = toml::from_str(&content).unwrap();
+        assert_eq!(project_toml.package.to_string(), "foo");
+        assert!(content.contains(r#"summary = "A foo""#));
+        assert!(content.contains(r#""foo.core" = { sources = ["csrc/foo/core.c"] }"#));
+        assert!(content.contains(r#"busted = "*""#));
+    }
+
+    #[test]
+    fn prefer_dev_rockspec() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir(dir.join("rockspecs")).unwrap();
+        for file_name in [
+            "rockspecs/foo-1.0.0-1.rockspec",
+            "rockspecs/foo-2.0.0-1.rockspec",
+            "foo-scm-1.rockspec",
+        ] {
+            std::fs::write(dir.join(file_name), "").unwrap();
+        }
+        assert_eq!(detect_rockspec(&dir), Some(dir.join("foo-scm-1.rockspec")));
+        std::fs::remove_file(dir.join("foo-scm-1.rockspec")).unwrap();
+        assert_eq!(
+            detect_rockspec(&dir),
+            Some(dir.join("rockspecs/foo-2.0.0-1.rockspec"))
+        );
+    }
+}
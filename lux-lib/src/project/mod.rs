@@ -1,9 +1,12 @@
+use diagnostic::ProjectTomlParseError;
 use itertools::Itertools;
 use lets_find_up::{find_up_with, FindUpKind, FindUpOptions};
 use mlua::{ExternalResult, UserData};
 use path_slash::PathBufExt;
+use project_config::ProjectConfig;
 use project_toml::{
-    LocalProjectTomlValidationError, PartialProjectToml, RemoteProjectTomlValidationError,
+    LocalProjectTomlValidationError, PartialProjectToml, ProjectTomlError,
+    RemoteProjectTomlValidationError,
 };
 use std::{
     io,
@@ -18,7 +21,7 @@ use crate::{
     build,
     config::{Config, LuaVersion},
     git::{self, shorthand::GitUrlShorthand, utils::GitError},
-    lockfile::{LockfileError, ProjectLockfile, ReadOnly},
+    lockfile::{LockfileError, LockfileHistory, ProjectLockfile, ReadOnly},
     lua::lua_runtime,
     lua_rockspec::{
         LocalLuaRockspec, LuaRockspecError, LuaVersionError, PartialLuaRockspec,
@@ -28,22 +31,31 @@ use crate::{
     remote_package_db::RemotePackageDB,
     rockspec::{
         lua_dependency::{DependencyType, LuaDependencySpec, LuaDependencyType},
-        LuaVersionCompatibility,
+        LuaVersionCompatibility, Rockspec,
     },
     tree::{Tree, TreeError},
 };
 use crate::{
     lockfile::PinnedState,
-    package::{PackageName, PackageReq},
+    package::{HasModRev, PackageName, PackageReq, PackageVersion},
 };
 
+pub mod diagnostic;
 pub(crate) mod gen;
+pub mod import;
+pub mod init;
+pub mod project_config;
+
+use r#gen::GenerateVersionError;
 pub mod project_toml;
+pub mod workspace;
+use workspace::{Workspace, WorkspaceError};
 
 pub use project_toml::PROJECT_TOML;
 
 pub const EXTRA_ROCKSPEC: &str = "extra.rockspec";
 pub const LUARC: &str = ".luarc.json";
+pub const LUA_VERSION_FILE: &str = ".lua-version";
 
 #[derive(Error, Debug)]
 #[error(transparent)]
@@ -51,7 +63,7 @@ pub enum ProjectError {
     Io(#[from] io::Error),
     Lockfile(#[from] LockfileError),
     Project(#[from] LocalProjectTomlValidationError),
-    Toml(#[from] toml::de::Error),
+    Toml(#[from] ProjectTomlParseError),
     #[error("error when parsing `extra.rockspec`: {0}")]
     Rockspec(#[from] PartialRockspecError),
     #[error("not in a lux project directory")]
@@ -72,6 +84,13 @@ pub enum IntoRemoteRockspecError {
     RockspecError(#[from] LuaRockspecError),
 }
 
+#[derive(Error, Debug)]
+#[error(transparent)]
+pub enum ProjectRockspecError {
+    RemoteProjectToml(#[from] RemoteProjectTomlValidationError),
+    ProjectToml(#[from] ProjectTomlError),
+}
+
 #[derive(Error, Debug)]
 pub enum ProjectEditError {
     #[error(transparent)]
@@ -79,7 +98,7 @@ pub enum ProjectEditError {
     #[error(transparent)]
     Toml(#[from] toml_edit::TomlError),
     #[error("error parsing lux.toml after edit. This is probably a bug.")]
-    TomlDe(#[from] toml::de::Error),
+    TomlDe(#[from] ProjectTomlParseError),
     #[error(transparent)]
     Git(#[from] GitError),
     #[error("unable to query latest version for {0}")]
@@ -99,6 +118,14 @@ pub enum ProjectTreeError {
     LuaVersionError(#[from] LuaVersionError),
 }
 
+#[derive(Error, Debug)]
+pub enum PinnedLuaVersionError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid Lua version in {}: {err}", path.display())]
+    Parse { path: PathBuf, err: String },
+}
+
 #[derive(Error, Debug)]
 pub enum PinError {
     #[error("package {0} not found in dependencies")]
@@ -111,7 +138,7 @@ pub enum PinError {
     #[error(transparent)]
     Toml(#[from] toml_edit::TomlError),
     #[error("error parsing lux.toml after edit. This is probably a bug.")]
-    TomlDe(#[from] toml::de::Error),
+    TomlDe(#[from] ProjectTomlParseError),
     #[error(transparent)]
     Io(#[from] tokio::io::Error),
 }
@@ -148,6 +175,8 @@ pub struct Project {
     root: ProjectRoot,
     /// The parsed lux.toml.
     toml: PartialProjectToml,
+    /// The root of the workspace this project is a member of, if any.
+    workspace_root: Option<PathBuf>,
 }
 
 impl UserData for Project {
@@ -264,6 +293,7 @@ impl Project {
             let mut project = Project {
                 root: ProjectRoot(root.to_path_buf()),
                 toml: PartialProjectToml::new(&toml_content, ProjectRoot(root.to_path_buf()))?,
+                workspace_root: workspace::find_workspace_root(root),
             };
 
             if let Some(extra_rockspec) = project.extra_rockspec()? {
@@ -295,6 +325,7 @@ impl Project {
                 let mut project = Project {
                     root: ProjectRoot(root.to_path_buf()),
                     toml: PartialProjectToml::new(&toml_content, ProjectRoot(root.to_path_buf()))?,
+                    workspace_root: workspace::find_workspace_root(root),
                 };
 
                 if let Some(extra_rockspec) = project.extra_rockspec()? {
@@ -327,9 +358,35 @@ impl Project {
         self.root.join(EXTRA_ROCKSPEC)
     }
 
+    /// Get the `.lua-version` path.
+    pub fn lua_version_file_path(&self) -> PathBuf {
+        self.root.join(LUA_VERSION_FILE)
+    }
+
+    /// Get the Lua version pinned in the `.lua-version` file, if present.
+    pub fn pinned_lua_version(&self) -> Result<Option<LuaVersion>, PinnedLuaVersionError> {
+        let path = self.lua_version_file_path();
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let version = LuaVersion::from_str(content.trim())
+            .map_err(|err| PinnedLuaVersionError::Parse { path, err })?;
+        Ok(Some(version))
+    }
+
+    /// Pin the project's Lua version in the `.lua-version` file.
+    pub fn pin_lua_version(&self, lua_version: &LuaVersion) -> io::Result<()> {
+        std::fs::write(self.lua_version_file_path(), format!("{lua_version}\n"))
+    }
+
     /// Get the `lux.lock` lockfile path.
+    /// Members of a workspace share the lockfile in the workspace root.
     pub fn lockfile_path(&self) -> PathBuf {
-        self.root.join("lux.lock")
+        self.workspace_root
+            .as_deref()
+            .unwrap_or(&self.root)
+            .join("lux.lock")
     }
 
     /// Get the `lux.lock` lockfile in the project root.
@@ -337,6 +394,11 @@ impl Project {
         Ok(ProjectLockfile::new(self.lockfile_path())?)
     }
 
+    /// Get the snapshots of previous versions of the `lux.lock` lockfile.
+    pub fn lockfile_history(&self) -> LockfileHistory {
+        LockfileHistory::new(&self.lockfile_path())
+    }
+
     /// Get the `lux.lock` lockfile in the project root, if present.
     pub fn try_lockfile(&self) -> Result<Option<ProjectLockfile<ReadOnly>>, ProjectError> {
         let path = self.lockfile_path();
@@ -351,6 +413,26 @@ impl Project {
         &self.root
     }
 
+    /// The root of the workspace this project is a member of, if any.
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.workspace_root.as_deref()
+    }
+
+    /// The workspace this project is a member of, if any.
+    pub fn workspace(&self) -> Result<Option<Workspace>, WorkspaceError> {
+        match &self.workspace_root {
+            Some(root) => Workspace::from_exact(root),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn in_workspace(self, workspace_root: PathBuf) -> Self {
+        Self {
+            workspace_root: Some(workspace_root),
+            ..self
+        }
+    }
+
     pub fn toml(&self) -> &PartialProjectToml {
         &self.toml
     }
@@ -363,6 +445,34 @@ impl Project {
         Ok(self.toml().into_remote()?.to_lua_rockspec()?)
     }
 
+    /// Generate a luarocks-compatible rockspec from the project,
+    /// including the build instructions and a source pinned to the current version.
+    pub fn to_rockspec(&self) -> Result<String, ProjectRockspecError> {
+        Ok(self.toml().into_remote()?.to_lua_remote_rockspec_string()?)
+    }
+
+    /// Use a git `url` and `tag` as the source of generated release rockspecs,
+    /// unless the `lux.toml` sets a `source.url`.
+    /// This is used to fill in the source from the git remote when publishing.
+    pub fn with_git_source(self, url: String, tag: String) -> Self {
+        Self {
+            toml: PartialProjectToml {
+                source_template: self.toml.source_template.or_git(url, tag),
+                ..self.toml
+            },
+            ..self
+        }
+    }
+
+    /// The file name of the rockspec generated by [`Project::to_rockspec`].
+    pub fn rockspec_file_name(&self) -> Result<String, GenerateVersionError> {
+        Ok(format!(
+            "{}-{}.rockspec",
+            self.toml().package(),
+            self.toml().version()?
+        ))
+    }
+
     pub fn extra_rockspec(&self) -> Result<Option<PartialLuaRockspec>, PartialRockspecError> {
         if self.extra_rockspec_path().exists() {
             Ok(Some(PartialLuaRockspec::new(&std::fs::read_to_string(
@@ -373,8 +483,28 @@ impl Project {
         }
     }
 
-    pub(crate) fn default_tree_root_dir(&self) -> PathBuf {
-        self.root.join(".lux")
+    /// The overrides of the config for this project, from the `lux.toml`
+    /// and the project's `.lux/config.toml` (which is a layer of the `config`).
+    pub fn project_config(&self, config: &Config) -> ProjectConfig {
+        self.toml.config().clone().merge(config.project_config())
+    }
+
+    /// Apply the project's overrides (e.g. the C compiler and flags) to the `config`.
+    pub fn apply_config_overrides(&self, config: Config) -> Config {
+        self.project_config(&config).apply(config, &self.root)
+    }
+
+    /// Members of a workspace share the tree in the workspace root,
+    /// unless the project overrides the tree.
+    pub(crate) fn tree_root_dir(&self, config: &Config) -> PathBuf {
+        match self.project_config(config).tree() {
+            Some(tree) => self.root.join(tree),
+            None => self
+                .workspace_root
+                .as_deref()
+                .unwrap_or(&self.root)
+                .join(".lux"),
+        }
     }
 
     pub fn tree(&self, config: &Config) -> Result<Tree, ProjectTreeError> {
@@ -386,11 +516,7 @@ impl Project {
         lua_version: LuaVersion,
         config: &Config,
     ) -> Result<Tree, ProjectTreeError> {
-        Ok(Tree::new(
-            self.default_tree_root_dir(),
-            lua_version,
-            config,
-        )?)
+        Ok(Tree::new(self.tree_root_dir(config), lua_version, config)?)
     }
 
     pub fn test_tree(&self, config: &Config) -> Result<Tree, ProjectTreeError> {
@@ -414,9 +540,10 @@ impl Project {
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
+        let build_dependencies = build_dependencies_table(&project_toml);
         let table = match dependencies {
             DependencyType::Regular(_) => &mut project_toml["dependencies"],
-            DependencyType::Build(_) => &mut project_toml["build_dependencies"],
+            DependencyType::Build(_) => &mut project_toml[build_dependencies],
             DependencyType::Test(_) => &mut project_toml["test_dependencies"],
             DependencyType::External(_) => &mut project_toml["external_dependencies"],
         };
@@ -429,12 +556,7 @@ impl Project {
                     let dep_version_str = if dep.version_req().is_any() {
                         package_db
                             .latest_version(dep.name())
-                            // This condition should never be reached, as the package should
-                            // have been found in the database or an error should have been
-                            // reported prior.
-                            // Still worth making an error message for this in the future,
-                            // though.
-                            .expect("unable to query latest version for package")
+                            .ok_or(ProjectEditError::LatestVersionNotFound(dep.name().clone()))?
                             .to_string()
                     } else {
                         dep.version_req().to_string()
@@ -470,9 +592,10 @@ impl Project {
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
+        let build_dependencies = build_dependencies_table(&project_toml);
         let table = match dependencies {
             LuaDependencyType::Regular(_) => &mut project_toml["dependencies"],
-            LuaDependencyType::Build(_) => &mut project_toml["build_dependencies"],
+            LuaDependencyType::Build(_) => &mut project_toml[build_dependencies],
             LuaDependencyType::Test(_) => &mut project_toml["test_dependencies"],
         };
 
@@ -496,6 +619,19 @@ impl Project {
         Ok(())
     }
 
+    /// Set the `version` in the `lux.toml`.
+    pub async fn set_version(&mut self, version: &PackageVersion) -> Result<(), ProjectEditError> {
+        let mut project_toml =
+            toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
+        project_toml["version"] = toml_edit::value(version.to_modrev_string());
+
+        let toml_content = project_toml.to_string();
+        tokio::fs::write(self.toml_path(), &toml_content).await?;
+        self.toml = PartialProjectToml::new(&toml_content, self.root.clone())?;
+
+        Ok(())
+    }
+
     pub async fn remove(
         &mut self,
         dependencies: DependencyType<PackageName>,
@@ -504,9 +640,10 @@ impl Project {
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
+        let build_dependencies = build_dependencies_table(&project_toml);
         let table = match dependencies {
             DependencyType::Regular(_) => &mut project_toml["dependencies"],
-            DependencyType::Build(_) => &mut project_toml["build_dependencies"],
+            DependencyType::Build(_) => &mut project_toml[build_dependencies],
             DependencyType::Test(_) => &mut project_toml["test_dependencies"],
             DependencyType::External(_) => &mut project_toml["external_dependencies"],
         };
@@ -547,9 +684,10 @@ impl Project {
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
+        let build_dependencies = build_dependencies_table(&project_toml);
         let table = match dependencies {
             LuaDependencyType::Regular(_) => &mut project_toml["dependencies"],
-            LuaDependencyType::Build(_) => &mut project_toml["build_dependencies"],
+            LuaDependencyType::Build(_) => &mut project_toml[build_dependencies],
             LuaDependencyType::Test(_) => &mut project_toml["test_dependencies"],
         };
 
@@ -617,6 +755,7 @@ impl Project {
     ) -> Result<(), ProjectEditError> {
         if let Some(dependencies) = &self.toml().dependencies {
             let packages = dependencies
+                .default
                 .iter()
                 .map(|dep| dep.name())
                 .cloned()
@@ -626,6 +765,7 @@ impl Project {
         }
         if let Some(dependencies) = &self.toml().build_dependencies {
             let packages = dependencies
+                .default
                 .iter()
                 .map(|dep| dep.name())
                 .cloned()
@@ -635,6 +775,7 @@ impl Project {
         }
         if let Some(dependencies) = &self.toml().test_dependencies {
             let packages = dependencies
+                .default
                 .iter()
                 .map(|dep| dep.name())
                 .cloned()
@@ -654,9 +795,10 @@ impl Project {
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
+        let build_dependencies = build_dependencies_table(&project_toml);
         let table = match dependencies {
             LuaDependencyType::Regular(_) => &mut project_toml["dependencies"],
-            LuaDependencyType::Build(_) => &mut project_toml["build_dependencies"],
+            LuaDependencyType::Build(_) => &mut project_toml[build_dependencies],
             LuaDependencyType::Test(_) => &mut project_toml["test_dependencies"],
         };
 
@@ -667,9 +809,11 @@ impl Project {
                         .dependencies
                         .take()
                         .unwrap_or_default()
-                        .into_iter()
-                        .map(|dep| LuaDependencySpec { pin, ..dep })
-                        .collect(),
+                        .map(|deps| {
+                            deps.iter()
+                                .map(|dep| LuaDependencySpec { pin, ..dep.clone() })
+                                .collect()
+                        }),
                 )
             }
             LuaDependencyType::Build(ref _deps) => {
@@ -678,9 +822,11 @@ impl Project {
                         .build_dependencies
                         .take()
                         .unwrap_or_default()
-                        .into_iter()
-                        .map(|dep| LuaDependencySpec { pin, ..dep })
-                        .collect(),
+                        .map(|deps| {
+                            deps.iter()
+                                .map(|dep| LuaDependencySpec { pin, ..dep.clone() })
+                                .collect()
+                        }),
                 )
             }
             LuaDependencyType::Test(ref _deps) => {
@@ -689,9 +835,11 @@ impl Project {
                         .test_dependencies
                         .take()
                         .unwrap_or_default()
-                        .into_iter()
-                        .map(|dep| LuaDependencySpec { pin, ..dep })
-                        .collect(),
+                        .map(|deps| {
+                            deps.iter()
+                                .map(|dep| LuaDependencySpec { pin, ..dep.clone() })
+                                .collect()
+                        }),
                 )
             }
         }
@@ -735,6 +883,16 @@ impl Project {
     }
 }
 
+/// The name of the table that build dependencies are declared in.
+/// `build-dependencies` is accepted as an alternative spelling of `build_dependencies`.
+fn build_dependencies_table(project_toml: &DocumentMut) -> &'static str {
+    if project_toml.contains_table("build-dependencies") {
+        "build-dependencies"
+    } else {
+        "build_dependencies"
+    }
+}
+
 fn prepare_dependency_tables(project_toml: &mut DocumentMut) {
     if !project_toml.contains_table("dependencies") {
         let mut table = toml_edit::table().into_table().unwrap();
@@ -742,7 +900,9 @@ fn prepare_dependency_tables(project_toml: &mut DocumentMut) {
 
         project_toml["dependencies"] = toml_edit::Item::Table(table);
     }
-    if !project_toml.contains_table("build_dependencies") {
+    if !project_toml.contains_table("build_dependencies")
+        && !project_toml.contains_table("build-dependencies")
+    {
         let mut table = toml_edit::table().into_table().unwrap();
         table.set_implicit(true);
 
@@ -772,12 +932,70 @@ mod tests {
 
     use super::*;
     use crate::{
-        lua_rockspec::ExternalDependencySpec,
+        lua_rockspec::{ExternalDependencySpec, RockSourceSpec},
         manifest::{Manifest, ManifestMetadata},
         package::PackageReq,
         rockspec::Rockspec,
     };
 
+    #[test]
+    fn test_to_rockspec() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            project_root.join(PROJECT_TOML),
+            r#"
+package = "foo"
+version = "1.0.0"
+lua = ">=5.1"
+
+[source]
+url = "https://example.com/foo.tar.gz"
+
+[dependencies]
+penlight = "1.5"
+
+[build]
+type = "builtin"
+
+[build.modules]
+foo = "src/foo.lua"
+"#,
+        )
+        .unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        assert_eq!(
+            project.rockspec_file_name().unwrap(),
+            "foo-1.0.0-1.rockspec"
+        );
+        let rockspec = RemoteLuaRockspec::new(&project.to_rockspec().unwrap()).unwrap();
+        assert_eq!(rockspec.package(), &"foo".into());
+        assert_eq!(
+            rockspec.source().current_platform().source_spec,
+            RockSourceSpec::Url("https://example.com/foo.tar.gz".parse().unwrap())
+        );
+        assert!(rockspec
+            .dependencies()
+            .current_platform()
+            .iter()
+            .any(|dep| dep.name() == &"penlight".into()));
+    }
+
+    #[test]
+    fn test_pinned_lua_version() {
+        let sample_project: PathBuf = "resources/test/sample-projects/no-build-spec/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        assert!(project.pinned_lua_version().unwrap().is_none());
+        project.pin_lua_version(&LuaVersion::LuaJIT).unwrap();
+        assert_eq!(
+            project.pinned_lua_version().unwrap(),
+            Some(LuaVersion::LuaJIT)
+        );
+        std::fs::write(project.lua_version_file_path(), "6.0").unwrap();
+        assert!(project.pinned_lua_version().is_err());
+    }
+
     #[tokio::test]
     async fn test_add_various_dependencies() {
         let sample_project: PathBuf = "resources/test/sample-projects/no-build-spec/".into();
@@ -858,6 +1076,38 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_add_unknown_dependency() {
+        let sample_project: PathBuf = "resources/test/sample-projects/no-build-spec/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        let project_root: PathBuf = project_root.path().into();
+        let mut project = Project::from(&project_root).unwrap().unwrap();
+        let toml_content = std::fs::read_to_string(project.toml_path()).unwrap();
+
+        let test_manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/manifest-5.1");
+        let content = String::from_utf8(std::fs::read(&test_manifest_path).unwrap()).unwrap();
+        let metadata = ManifestMetadata::new(&content).unwrap();
+        let package_db = Manifest::new(Url::parse("https://example.com").unwrap(), metadata).into();
+
+        let result = project
+            .add(
+                DependencyType::Regular(vec![PackageReq::parse("does-not-exist").unwrap()]),
+                &package_db,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ProjectEditError::LatestVersionNotFound(_))
+        ));
+        // The lux.toml should not have been modified
+        assert_eq!(
+            std::fs::read_to_string(project.toml_path()).unwrap(),
+            toml_content
+        );
+    }
+
     #[tokio::test]
     async fn test_remove_dependencies() {
         let sample_project: PathBuf = "resources/test/sample-projects/dependencies/".into();
@@ -877,6 +1127,7 @@ mod tests {
                     .dependencies
                     .clone()
                     .unwrap_or_default()
+                    .current_platform()
                     .iter()
                     .any(|dep| dep.name() == name));
             }
@@ -932,6 +1183,7 @@ mod tests {
                     .dependencies
                     .clone()
                     .unwrap_or_default()
+                    .current_platform()
                     .iter()
                     .any(|dep| dep.name() == name && dep.pin == pin));
             }
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Overrides of the [`Config`] that only apply to a single project.
+///
+/// They can be set in the `[config]` table of the `lux.toml`.
+/// Except for `external_deps`, they can also be set at the top level
+/// of the project's `.lux/config.toml`, which is a layer of the config
+/// (see [`crate::config::layers`]) and takes precedence.
+/// In the `.lux/config.toml`, use the config's `external_deps` instead,
+/// whose relative paths are resolved against the project root.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// The C compiler.
+    pub(crate) cc: Option<String>,
+    /// Flags that are passed to the C compiler.
+    pub(crate) cflags: Option<String>,
+    /// Flags that are passed to the linker.
+    pub(crate) ldflags: Option<String>,
+    /// The directory to install the project's dependencies to,
+    /// relative to the project root. Defaults to `.lux`.
+    pub(crate) tree: Option<PathBuf>,
+    #[serde(default)]
+    pub(crate) external_deps: ProjectExternalDeps,
+}
+
+/// Additional search paths for external dependencies.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectExternalDeps {
+    /// Installation prefixes for specific dependencies, e.g. `SQLITE_DIR`.
+    #[serde(default)]
+    pub(crate) prefixes: HashMap<String, PathBuf>,
+    /// Search paths that are tried before the system-wide search paths.
+    #[serde(default)]
+    pub(crate) search_prefixes: Vec<PathBuf>,
+}
+
+impl ProjectConfig {
+    /// Merge with `other`, whose overrides take precedence.
+    /// The `external_deps` are kept, as they can only be set in the `lux.toml`.
+    pub(crate) fn merge(self, other: &ProjectConfig) -> Self {
+        Self {
+            cc: other.cc.clone().or(self.cc),
+            cflags: other.cflags.clone().or(self.cflags),
+            ldflags: other.ldflags.clone().or(self.ldflags),
+            tree: other.tree.clone().or(self.tree),
+            external_deps: self.external_deps,
+        }
+    }
+
+    pub fn cc(&self) -> Option<&String> {
+        self.cc.as_ref()
+    }
+
+    pub fn cflags(&self) -> Option<&String> {
+        self.cflags.as_ref()
+    }
+
+    pub fn ldflags(&self) -> Option<&String> {
+        self.ldflags.as_ref()
+    }
+
+    pub fn tree(&self) -> Option<&PathBuf> {
+        self.tree.as_ref()
+    }
+
+    /// Apply the overrides to the `config`.
+    /// Relative external dependency paths are resolved against the `project_root`.
+    pub(crate) fn apply(self, config: Config, project_root: &Path) -> Config {
+        let variables = [
+            ("CC", &self.cc),
+            ("CFLAGS", &self.cflags),
+            ("LDFLAGS", &self.ldflags),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+        .collect();
+        let prefixes = self
+            .external_deps
+            .prefixes
+            .iter()
+            .map(|(name, prefix)| (name.clone(), project_root.join(prefix)))
+            .collect();
+        let search_prefixes = self
+            .external_deps
+            .search_prefixes
+            .iter()
+            .map(|prefix| project_root.join(prefix))
+            .collect();
+        config
+            .with_variables(variables)
+            .with_external_deps_prefixes(prefixes, search_prefixes)
+            .with_project_config(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_project_config() {
+        let project_config: ProjectConfig = toml::from_str(
+            r#"
+cc = "gcc"
+cflags = "-O2"
+[external_deps.prefixes]
+SQLITE_DIR = "/usr"
+"#,
+        )
+        .unwrap();
+        let layer = ProjectConfig {
+            cc: Some("clang".into()),
+            tree: Some("build/lux".into()),
+            ..ProjectConfig::default()
+        };
+        let merged = project_config.merge(&layer);
+        assert_eq!(merged.cc(), Some(&"clang".to_string()));
+        assert_eq!(merged.cflags(), Some(&"-O2".to_string()));
+        assert_eq!(merged.tree(), Some(&PathBuf::from("build/lux")));
+        assert_eq!(
+            merged.external_deps.prefixes.get("SQLITE_DIR"),
+            Some(&PathBuf::from("/usr"))
+        );
+    }
+}
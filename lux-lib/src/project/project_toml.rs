@@ -5,6 +5,8 @@ use crate::git::GitSource;
 use crate::hash::HasIntegrity;
 use crate::lockfile::OptState;
 use crate::lockfile::PinnedState;
+use crate::lua_rockspec::apply_per_platform_overrides;
+use crate::lua_rockspec::deserialize_url;
 use crate::lua_rockspec::DeploySpec;
 use crate::lua_rockspec::LocalLuaRockspec;
 use crate::lua_rockspec::LocalRockSource;
@@ -13,6 +15,7 @@ use crate::lua_rockspec::RemoteLuaRockspec;
 use crate::lua_rockspec::RockSourceSpec;
 use crate::operations::RunCommand;
 use crate::package::PackageNameList;
+use crate::project::diagnostic::ProjectTomlParseError;
 use crate::rockspec::lua_dependency::LuaDependencySpec;
 use std::io;
 use std::{collections::HashMap, path::PathBuf};
@@ -25,18 +28,19 @@ use serde::de;
 use serde::{Deserialize, Deserializer};
 use ssri::Integrity;
 use thiserror::Error;
+use url::Url;
 
 use crate::{
     config::{Config, LuaVersion},
     lua_rockspec::{
-        BuildSpec, BuildSpecInternal, BuildSpecInternalError, DisplayAsLuaKV, ExternalDependencies,
-        ExternalDependencySpec, FromPlatformOverridable, LuaVersionError, PartialLuaRockspec,
-        PerPlatform, PlatformIdentifier, PlatformSupport, PlatformValidationError,
-        RemoteRockSource, RockDescription, RockSourceError, RockspecFormat, TestSpec,
-        TestSpecDecodeError, TestSpecInternal,
+        BuildSpec, BuildSpecInternal, BuildSpecInternalError, DisplayAsLuaKV, DisplayLuaKV,
+        ExternalDependencies, ExternalDependencySpec, FromPlatformOverridable, LuaVersionError,
+        PartialLuaRockspec, PerPlatform, PlatformIdentifier, PlatformSupport,
+        PlatformValidationError, RemoteRockSource, RockDescription, RockSourceError,
+        RockspecFormat, TestSpec, TestSpecDecodeError, TestSpecInternal,
     },
     package::{
-        BuildDependencies, Dependencies, PackageName, PackageReq, PackageVersion,
+        BuildDependencies, Dependencies, PackageName, PackageReq, PackageSpec, PackageVersion,
         PackageVersionReq, TestDependencies,
     },
     rockspec::{LuaVersionCompatibility, Rockspec},
@@ -44,6 +48,7 @@ use crate::{
 
 use super::gen::GenerateSourceError;
 use super::gen::RockSourceTemplate;
+use super::project_config::ProjectConfig;
 use super::r#gen::GenerateVersionError;
 use super::r#gen::PackageVersionTemplate;
 use super::ProjectRoot;
@@ -60,7 +65,8 @@ enum DependencyEntry {
 
 #[derive(Debug, Deserialize)]
 struct DependencyTableEntry {
-    version: PackageVersionReq,
+    #[serde(default)]
+    version: Option<PackageVersionReq>,
     #[serde(default)]
     opt: Option<bool>,
     #[serde(default)]
@@ -71,57 +77,197 @@ struct DependencyTableEntry {
     rev: Option<String>,
 }
 
+/// The `[description]` table of a `lux.toml`.
+/// Unlike a rockspec's `description`, it rejects unknown fields.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectDescription {
+    summary: Option<String>,
+    detailed: Option<String>,
+    license: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_url")]
+    homepage: Option<Url>,
+    issues_url: Option<String>,
+    maintainer: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+fn deserialize_description<'de, D>(deserializer: D) -> Result<Option<RockDescription>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        Option::<ProjectDescription>::deserialize(deserializer)?.map(|description| {
+            RockDescription {
+                summary: description.summary,
+                detailed: description.detailed,
+                license: description.license,
+                homepage: description.homepage,
+                issues_url: description.issues_url,
+                maintainer: description.maintainer,
+                labels: description.labels,
+            }
+        }),
+    )
+}
+
+/// Parse a dependency table, with optional `platforms.<platform>` override tables,
+/// e.g. `[dependencies.platforms.windows]`.
 fn parse_map_to_dependency_vec_opt<'de, D>(
     deserializer: D,
-) -> Result<Option<Vec<LuaDependencySpec>>, D::Error>
+) -> Result<Option<PerPlatform<Vec<LuaDependencySpec>>>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let packages: Option<HashMap<PackageName, DependencyEntry>> =
-        Option::deserialize(deserializer)?;
+    let table: Option<toml::Table> = Option::deserialize(deserializer)?;
 
-    match packages {
+    match table {
         None => Ok(None),
-        Some(packages) => Ok(Some(
-            packages
+        Some(mut table) => {
+            let per_platform: HashMap<PlatformIdentifier, HashMap<PackageName, DependencyEntry>> =
+                match table.remove("platforms") {
+                    Some(platforms) => platforms.try_into().map_err(de::Error::custom)?,
+                    None => HashMap::default(),
+                };
+            let default = to_dependency_vec(table.try_into().map_err(de::Error::custom)?)?;
+            let mut per_platform = per_platform
                 .into_iter()
-                .map(|(name, spec)| match spec {
-                    DependencyEntry::Simple(version_req) => {
-                        Ok(PackageReq { name, version_req }.into())
-                    }
-                    DependencyEntry::Detailed(entry) => {
-                        let source = match (entry.git, entry.rev) {
-                            (None, None) => Ok(None),
-                            (None, Some(_)) => Err(de::Error::custom(format!(
-                                "dependency {} specifies a 'rev', but missing a 'git' field",
-                                &name
-                            ))),
-                            (Some(git), Some(rev)) => Ok(Some(RockSourceSpec::Git(GitSource {
-                                url: git.into(),
-                                checkout_ref: Some(rev),
-                            }))),
-                            (Some(git), None) => Ok(Some(RockSourceSpec::Git(GitSource {
-                                url: git.into(),
-                                checkout_ref: Some(
-                                    entry
-                                        .version
-                                        .clone()
-                                        .to_string()
-                                        .trim_start_matches("=")
-                                        .to_string(),
-                                ),
-                            }))),
-                        }?;
-                        Ok(LuaDependencySpec {
-                            package_req: PackageReq {
-                                name,
-                                version_req: entry.version,
-                            },
-                            opt: OptState::from(entry.opt.unwrap_or(false)),
-                            pin: PinnedState::from(entry.pin.unwrap_or(false)),
-                            source,
-                        })
-                    }
+                .map(|(platform, packages)| Ok((platform, to_dependency_vec(packages)?)))
+                .try_collect::<_, _, D::Error>()?;
+            apply_per_platform_overrides(&mut per_platform, &default).map_err(de::Error::custom)?;
+            Ok(Some(PerPlatform {
+                default,
+                per_platform,
+            }))
+        }
+    }
+}
+
+fn to_dependency_vec<E>(
+    packages: HashMap<PackageName, DependencyEntry>,
+) -> Result<Vec<LuaDependencySpec>, E>
+where
+    E: de::Error,
+{
+    packages
+        .into_iter()
+        .map(|(name, spec)| match spec {
+            DependencyEntry::Simple(version_req) => {
+                Ok(PackageReq { name, version_req }.into())
+            }
+            DependencyEntry::Detailed(entry) => {
+                let (source, version_req) = match (entry.git, entry.rev, entry.version) {
+                    (None, None, Some(version_req)) => Ok((None, version_req)),
+                    (None, None, None) => Err(de::Error::custom(format!(
+                        "dependency {} is missing a 'version' field",
+                        &name
+                    ))),
+                    (None, Some(_), _) => Err(de::Error::custom(format!(
+                        "dependency {} specifies a 'rev', but missing a 'git' field",
+                        &name
+                    ))),
+                    (Some(git), Some(rev), version_req) => Ok((
+                        Some(RockSourceSpec::Git(GitSource {
+                            url: git.into(),
+                            checkout_ref: Some(rev),
+                        })),
+                        // Git dependencies without a version are treated as dev versions
+                        version_req.unwrap_or_else(|| {
+                            PackageVersion::default_dev_version().into_version_req()
+                        }),
+                    )),
+                    (Some(git), None, Some(version_req)) => Ok((
+                        Some(RockSourceSpec::Git(GitSource {
+                            url: git.into(),
+                            checkout_ref: Some(
+                                version_req
+                                    .to_string()
+                                    .trim_start_matches("=")
+                                    .to_string(),
+                            ),
+                        })),
+                        version_req,
+                    )),
+                    (Some(_), None, None) => Err(de::Error::custom(format!(
+                        "dependency {} specifies a 'git' source, but is missing a 'rev' or 'version' field",
+                        &name
+                    ))),
+                }?;
+                Ok(LuaDependencySpec {
+                    package_req: PackageReq { name, version_req },
+                    opt: OptState::from(entry.opt.unwrap_or(false)),
+                    pin: PinnedState::from(entry.pin.unwrap_or(false)),
+                    source,
+                })
+            }
+        })
+        .try_collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct PatchTableEntry {
+    #[serde(default)]
+    version: Option<PackageVersion>,
+    #[serde(default)]
+    path: Option<PathBuf>,
+    #[serde(default)]
+    git: Option<GitUrlShorthand>,
+    #[serde(default)]
+    rev: Option<String>,
+}
+
+/// A replacement source for a dependency, declared in the `[patch]` table.
+#[derive(Debug, Clone)]
+pub(crate) struct DependencyPatch {
+    version: PackageVersion,
+    source: RockSourceSpec,
+}
+
+fn parse_map_to_patches_opt<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<PackageName, DependencyPatch>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let patches: Option<HashMap<PackageName, PatchTableEntry>> = Option::deserialize(deserializer)?;
+
+    match patches {
+        None => Ok(None),
+        Some(patches) => Ok(Some(
+            patches
+                .into_iter()
+                .map(|(name, entry)| {
+                    let source = match (entry.path, entry.git, entry.rev) {
+                        (Some(path), None, None) => Ok(RockSourceSpec::File(path)),
+                        (None, Some(git), Some(rev)) => Ok(RockSourceSpec::Git(GitSource {
+                            url: git.into(),
+                            checkout_ref: Some(rev),
+                        })),
+                        (None, Some(_), None) => Err(de::Error::custom(format!(
+                            "patch for {} specifies a 'git' source, but is missing a 'rev' field",
+                            &name
+                        ))),
+                        (None, None, Some(_)) => Err(de::Error::custom(format!(
+                            "patch for {} specifies a 'rev', but is missing a 'git' field",
+                            &name
+                        ))),
+                        (None, None, None) => Err(de::Error::custom(format!(
+                            "patch for {} must specify either a 'path' or a 'git' source",
+                            &name
+                        ))),
+                        (Some(_), _, _) => Err(de::Error::custom(format!(
+                            "patch for {} cannot specify both a 'path' and a 'git' source",
+                            &name
+                        ))),
+                    }?;
+                    let patch = DependencyPatch {
+                        version: entry
+                            .version
+                            .unwrap_or_else(PackageVersion::default_dev_version),
+                        source,
+                    };
+                    Ok((name, patch))
                 })
                 .try_collect()?,
         )),
@@ -156,6 +302,8 @@ pub enum LocalProjectTomlValidationError {
     DuplicateTestDependencies(PackageNameList),
     #[error("duplicate build dependencies: {0}")]
     DuplicateBuildDependencies(PackageNameList),
+    #[error("duplicate dev or test dependencies: {0}")]
+    DuplicateDevDependencies(PackageNameList),
     #[error("dependencies field cannot contain lua - please provide the version in the top-level lua field")]
     DependenciesContainLua,
     #[error("error generating rockspec source:\n{0}")]
@@ -178,35 +326,64 @@ pub enum RemoteProjectTomlValidationError {
 /// The only required fields are `package` and `build`, which are required to build a project using `lux build`.
 /// The rest of the fields are optional, but are required to build a rockspec.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PartialProjectToml {
     pub(crate) package: PackageName,
     #[serde(default, rename = "version")]
     pub(crate) version_template: PackageVersionTemplate,
     #[serde(default)]
-    pub(crate) build: BuildSpecInternal,
+    pub(crate) build: PerPlatform<BuildSpecInternal>,
     pub(crate) rockspec_format: Option<RockspecFormat>,
     #[serde(default)]
     pub(crate) run: Option<RunSpec>,
     #[serde(default)]
     pub(crate) lua: Option<PackageVersionReq>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_description")]
     pub(crate) description: Option<RockDescription>,
     #[serde(default)]
     pub(crate) supported_platforms: Option<HashMap<PlatformIdentifier, bool>>,
     #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
-    pub(crate) dependencies: Option<Vec<LuaDependencySpec>>,
-    #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
-    pub(crate) build_dependencies: Option<Vec<LuaDependencySpec>>,
+    pub(crate) dependencies: Option<PerPlatform<Vec<LuaDependencySpec>>>,
+    /// Dependencies that are only needed to build the project (e.g. `luarocks-build-rust-mlua`).
+    /// These are installed into a separate build tree, which is not part of the runtime environment.
+    #[serde(
+        default,
+        alias = "build-dependencies",
+        deserialize_with = "parse_map_to_dependency_vec_opt"
+    )]
+    pub(crate) build_dependencies: Option<PerPlatform<Vec<LuaDependencySpec>>>,
     #[serde(default)]
-    pub(crate) external_dependencies: Option<HashMap<String, ExternalDependencySpec>>,
+    pub(crate) external_dependencies: Option<PerPlatform<HashMap<String, ExternalDependencySpec>>>,
     #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
-    pub(crate) test_dependencies: Option<Vec<LuaDependencySpec>>,
+    pub(crate) test_dependencies: Option<PerPlatform<Vec<LuaDependencySpec>>>,
+    /// Dependencies that are only needed for development (`lux test`, `lux check`).
+    /// Unlike `test_dependencies`, these are never written to the generated rockspec.
+    #[serde(
+        default,
+        alias = "dev-dependencies",
+        deserialize_with = "parse_map_to_dependency_vec_opt"
+    )]
+    pub(crate) dev_dependencies: Option<PerPlatform<Vec<LuaDependencySpec>>>,
+    /// Named shell commands, run with `lux exec <script>`.
+    #[serde(default)]
+    pub(crate) scripts: Option<HashMap<String, String>>,
     #[serde(default, rename = "source")]
     pub(crate) source_template: RockSourceTemplate,
     #[serde(default)]
     pub(crate) test: Option<TestSpecInternal>,
     #[serde(default)]
     pub(crate) deploy: Option<DeploySpec>,
+    #[serde(default, deserialize_with = "parse_map_to_patches_opt")]
+    pub(crate) patch: Option<HashMap<PackageName, DependencyPatch>>,
+    /// Overrides of the config for this project, which are not part of the rockspec.
+    #[serde(default)]
+    pub(crate) config: ProjectConfig,
+    /// The `[workspace]` table, which is read by the `Workspace`.
+    #[serde(default, rename = "workspace")]
+    pub(crate) _workspace: Option<de::IgnoredAny>,
+    /// The `[stylua]` table, which is read by `lx fmt`.
+    #[serde(default, rename = "stylua")]
+    pub(crate) _stylua: Option<de::IgnoredAny>,
 
     /// Used to bind the project TOML to a project root
     #[serde(skip, default = "ProjectRoot::new")]
@@ -238,10 +415,10 @@ impl HasIntegrity for PartialProjectToml {
 }
 
 impl PartialProjectToml {
-    pub(crate) fn new(str: &str, project_root: ProjectRoot) -> Result<Self, toml::de::Error> {
+    pub(crate) fn new(str: &str, project_root: ProjectRoot) -> Result<Self, ProjectTomlParseError> {
         Ok(Self {
             project_root,
-            ..toml::from_str(str)?
+            ..toml::from_str(str).map_err(|err| ProjectTomlParseError::from_toml(str, err))?
         })
     }
 
@@ -251,17 +428,20 @@ impl PartialProjectToml {
         let project_toml = self.clone();
 
         // Disallow `lua` to be part of the `dependencies` field
-        if project_toml
-            .dependencies
-            .as_ref()
-            .is_some_and(|deps| deps.iter().any(|dep| dep.name() == &"lua".into()))
-        {
+        if project_toml.dependencies.as_ref().is_some_and(|deps| {
+            std::iter::once(&deps.default)
+                .chain(deps.per_platform.values())
+                .flatten()
+                .any(|dep| dep.name() == &"lua".into())
+        }) {
             return Err(LocalProjectTomlValidationError::DependenciesContainLua);
         }
 
-        let get_duplicates = |dependencies: &Option<Vec<LuaDependencySpec>>| {
+        // Per-platform overrides replace dependencies with the same name,
+        // so only the base dependencies can contain duplicates.
+        let get_duplicates = |dependencies: Option<&Vec<LuaDependencySpec>>| {
             dependencies
-                .iter()
+                .into_iter()
                 .flat_map(|deps| {
                     deps.iter()
                         .map(|dep| dep.package_req().name())
@@ -270,19 +450,36 @@ impl PartialProjectToml {
                 })
                 .collect_vec()
         };
-        let duplicate_dependencies = get_duplicates(&self.dependencies);
+        let duplicate_dependencies =
+            get_duplicates(self.dependencies.as_ref().map(|deps| &deps.default));
         if !duplicate_dependencies.is_empty() {
             return Err(LocalProjectTomlValidationError::DuplicateDependencies(
                 PackageNameList::new(duplicate_dependencies),
             ));
         }
-        let duplicate_test_dependencies = get_duplicates(&self.test_dependencies);
+        let duplicate_test_dependencies =
+            get_duplicates(self.test_dependencies.as_ref().map(|deps| &deps.default));
         if !duplicate_test_dependencies.is_empty() {
             return Err(LocalProjectTomlValidationError::DuplicateTestDependencies(
                 PackageNameList::new(duplicate_test_dependencies),
             ));
         }
-        let duplicate_build_dependencies = get_duplicates(&self.build_dependencies);
+        let duplicate_dev_dependencies = get_duplicates(Some(
+            &self
+                .dev_dependencies
+                .iter()
+                .chain(self.test_dependencies.iter())
+                .flat_map(|deps| deps.default.iter())
+                .cloned()
+                .collect_vec(),
+        ));
+        if !duplicate_dev_dependencies.is_empty() {
+            return Err(LocalProjectTomlValidationError::DuplicateDevDependencies(
+                PackageNameList::new(duplicate_dev_dependencies),
+            ));
+        }
+        let duplicate_build_dependencies =
+            get_duplicates(self.build_dependencies.as_ref().map(|deps| &deps.default));
         if !duplicate_build_dependencies.is_empty() {
             return Err(LocalProjectTomlValidationError::DuplicateBuildDependencies(
                 PackageNameList::new(duplicate_build_dependencies),
@@ -318,18 +515,19 @@ impl PartialProjectToml {
             )?,
             // Merge dependencies internally with lua version
             // so the output of `dependencies()` is consistent
-            dependencies: PerPlatform::new(project_toml.dependencies.unwrap_or_default()),
-            build_dependencies: PerPlatform::new(
-                project_toml.build_dependencies.unwrap_or_default(),
-            ),
-            external_dependencies: PerPlatform::new(
-                project_toml.external_dependencies.unwrap_or_default(),
-            ),
-            test_dependencies: PerPlatform::new(project_toml.test_dependencies.unwrap_or_default()),
+            dependencies: project_toml.dependencies.unwrap_or_default(),
+            build_dependencies: project_toml.build_dependencies.unwrap_or_default(),
+            external_dependencies: project_toml.external_dependencies.unwrap_or_default(),
+            test_dependencies: project_toml.test_dependencies.unwrap_or_default(),
+            dev_dependencies: project_toml.dev_dependencies.unwrap_or_default(),
+            scripts: project_toml.scripts.unwrap_or_default(),
             test: PerPlatform::new(TestSpec::from_platform_overridable(
                 project_toml.test.clone().unwrap_or_default(),
             )?),
-            build: PerPlatform::new(BuildSpec::from_internal_spec(project_toml.build.clone())?),
+            build: project_toml
+                .build
+                .map(|build| BuildSpec::from_internal_spec(build.clone()))
+                .transpose()?,
             deploy: PerPlatform::new(project_toml.deploy.clone().unwrap_or_default()),
             rockspec_format: project_toml.rockspec_format.clone(),
 
@@ -399,6 +597,107 @@ impl PartialProjectToml {
         self.version_template.try_generate(&self.project_root)
     }
 
+    /// Whether the `lux.toml` sets a `source.url` for releases.
+    pub fn has_source_url(&self) -> bool {
+        self.source_template.has_url()
+    }
+
+    /// Overrides of the config from the `[config]` table.
+    pub fn config(&self) -> &ProjectConfig {
+        &self.config
+    }
+
+    /// Named shell commands from the `[scripts]` table.
+    /// Unlike [`LocalProjectToml::scripts`], this doesn't require a valid local project.
+    pub fn scripts(&self) -> Option<&HashMap<String, String>> {
+        self.scripts.as_ref()
+    }
+
+    /// Dependency patches declared in the `[patch]` table.
+    /// During resolution, any (transitive) dependency with a patch is replaced
+    /// with the patch's local path or git source.
+    pub fn patches(&self) -> HashMap<PackageName, LuaDependencySpec> {
+        self.patch
+            .iter()
+            .flatten()
+            .map(|(name, patch)| {
+                let source = match &patch.source {
+                    RockSourceSpec::File(path) if path.is_relative() => {
+                        RockSourceSpec::File(self.project_root.join(path))
+                    }
+                    source => source.clone(),
+                };
+                let dependency = LuaDependencySpec {
+                    package_req: PackageSpec::new(name.clone(), patch.version.clone())
+                        .into_package_req(),
+                    pin: PinnedState::default(),
+                    opt: OptState::default(),
+                    source: Some(source),
+                };
+                (name.clone(), dependency)
+            })
+            .collect()
+    }
+
+    /// The rockspec's dependency fields, with their per-platform overrides.
+    fn display_lua_dependencies(&self, lua: &PackageVersionReq) -> Vec<DisplayLuaKV> {
+        let mut fields = Vec::new();
+
+        // Per-platform overrides are written out in full, so the `lua` dependency
+        // has to be added to each of them.
+        let lua_dependency: LuaDependencySpec = PackageReq {
+            name: "lua".into(),
+            version_req: lua.clone(),
+        }
+        .into();
+        let dependencies = self.dependencies.clone().unwrap_or_default().map(|deps| {
+            std::iter::once(lua_dependency.clone())
+                .chain(deps.iter().cloned())
+                .collect_vec()
+        });
+        fields.push(dependencies.display_lua_with(|deps| Dependencies(deps).display_lua()));
+
+        match self.build_dependencies {
+            Some(ref build_dependencies)
+                if !build_dependencies.default.is_empty()
+                    || !build_dependencies.per_platform.is_empty() =>
+            {
+                fields.push(
+                    build_dependencies
+                        .display_lua_with(|deps| BuildDependencies(deps).display_lua()),
+                );
+            }
+            _ => {}
+        }
+
+        match self.external_dependencies {
+            Some(ref external_dependencies)
+                if !external_dependencies.default.is_empty()
+                    || !external_dependencies.per_platform.is_empty() =>
+            {
+                fields.push(
+                    external_dependencies
+                        .display_lua_with(|deps| ExternalDependencies(deps).display_lua()),
+                );
+            }
+            _ => {}
+        }
+
+        match self.test_dependencies {
+            Some(ref test_dependencies)
+                if !test_dependencies.default.is_empty()
+                    || !test_dependencies.per_platform.is_empty() =>
+            {
+                fields.push(
+                    test_dependencies.display_lua_with(|deps| TestDependencies(deps).display_lua()),
+                );
+            }
+            _ => {}
+        }
+
+        fields
+    }
+
     /// Merge the `ProjectToml` struct with an unvalidated `LuaRockspec`.
     /// The final merged struct can then be validated.
     pub fn merge(self, other: PartialLuaRockspec) -> Self {
@@ -420,7 +719,7 @@ impl PartialProjectToml {
                         })
                 })
                 .or(self.lua),
-            build: other.build.unwrap_or(self.build),
+            build: other.build.map(PerPlatform::new).unwrap_or(self.build),
             run: self.run,
             description: other.description.or(self.description),
             supported_platforms: other
@@ -430,18 +729,36 @@ impl PartialProjectToml {
             dependencies: other
                 .dependencies
                 .map(|deps| {
-                    deps.into_iter()
-                        .filter(|dep| dep.name() != &"lua".into())
-                        .collect()
+                    PerPlatform::new(
+                        deps.into_iter()
+                            .filter(|dep| dep.name() != &"lua".into())
+                            .collect(),
+                    )
                 })
                 .or(self.dependencies),
-            build_dependencies: other.build_dependencies.or(self.build_dependencies),
-            test_dependencies: other.test_dependencies.or(self.test_dependencies),
-            external_dependencies: other.external_dependencies.or(self.external_dependencies),
+            build_dependencies: other
+                .build_dependencies
+                .map(PerPlatform::new)
+                .or(self.build_dependencies),
+            test_dependencies: other
+                .test_dependencies
+                .map(PerPlatform::new)
+                .or(self.test_dependencies),
+            // Dev dependencies and scripts are not part of the lua rockspec
+            dev_dependencies: self.dev_dependencies,
+            scripts: self.scripts,
+            external_dependencies: other
+                .external_dependencies
+                .map(PerPlatform::new)
+                .or(self.external_dependencies),
             source_template: self.source_template,
             test: other.test.or(self.test),
             deploy: other.deploy.or(self.deploy),
             rockspec_format: other.rockspec_format.or(self.rockspec_format),
+            patch: self.patch,
+            config: self.config,
+            _workspace: self._workspace,
+            _stylua: self._stylua,
 
             // Keep the project root the same, as it is not part of the lua rockspec
             project_root: self.project_root,
@@ -500,11 +817,23 @@ impl LuaVersionCompatibility for PartialProjectToml {
 
 // TODO(vhyrro): Move this struct into a different directory.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RunSpec {
     /// The command to execute when running the project
     pub(crate) command: Option<RunCommand>,
     /// Arguments to pass to the command
     pub(crate) args: Option<NonEmpty<String>>,
+    /// Named profiles, selected with `lx run --profile <name>`
+    #[serde(default)]
+    pub(crate) profiles: HashMap<String, RunProfile>,
+}
+
+/// A named run profile, which overrides the `command` and `args` of the `[run]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunProfile {
+    pub(crate) command: Option<RunCommand>,
+    pub(crate) args: Option<NonEmpty<String>>,
 }
 
 /// The `lux.toml` file, after being properly deserialized.
@@ -523,6 +852,8 @@ pub struct LocalProjectToml {
     build_dependencies: PerPlatform<Vec<LuaDependencySpec>>,
     external_dependencies: PerPlatform<HashMap<String, ExternalDependencySpec>>,
     test_dependencies: PerPlatform<Vec<LuaDependencySpec>>,
+    dev_dependencies: PerPlatform<Vec<LuaDependencySpec>>,
+    scripts: HashMap<String, String>,
     test: PerPlatform<TestSpec>,
     build: PerPlatform<BuildSpec>,
     deploy: PerPlatform<DeploySpec>,
@@ -539,6 +870,17 @@ impl LocalProjectToml {
         self.run.as_ref()
     }
 
+    /// Dependencies that are installed into the test tree for `lux test` and `lux check`,
+    /// but are not part of the generated rockspec.
+    pub fn dev_dependencies(&self) -> &PerPlatform<Vec<LuaDependencySpec>> {
+        &self.dev_dependencies
+    }
+
+    /// Named shell commands from the `[scripts]` table, which are not part of the rockspec.
+    pub fn scripts(&self) -> &HashMap<String, String> {
+        &self.scripts
+    }
+
     /// Convert this project TOML to a Lua rockspec.
     /// Fails if there is no valid project root or if there are off-spec dependencies.
     pub fn to_lua_rockspec(&self) -> Result<LocalLuaRockspec, LuaRockspecError> {
@@ -677,39 +1019,7 @@ version = "{}""#,
             template.push(self.supported_platforms.display_lua());
         }
 
-        {
-            let mut dependencies = self.internal.dependencies.clone().unwrap_or_default();
-            dependencies.insert(
-                0,
-                PackageReq {
-                    name: "lua".into(),
-                    version_req: self.lua.clone(),
-                }
-                .into(),
-            );
-            template.push(Dependencies(&dependencies).display_lua());
-        }
-
-        match self.internal.build_dependencies {
-            Some(ref build_dependencies) if !build_dependencies.is_empty() => {
-                template.push(BuildDependencies(build_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.internal.external_dependencies {
-            Some(ref external_dependencies) if !external_dependencies.is_empty() => {
-                template.push(ExternalDependencies(external_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.internal.test_dependencies {
-            Some(ref test_dependencies) if !test_dependencies.is_empty() => {
-                template.push(TestDependencies(test_dependencies).display_lua());
-            }
-            _ => {}
-        }
+        template.extend(self.internal.display_lua_dependencies(&self.lua));
 
         let source =
             self.internal
@@ -721,7 +1031,11 @@ version = "{}""#,
             template.push(test.display_lua());
         }
 
-        template.push(self.internal.build.display_lua());
+        template.push(
+            self.internal
+                .build
+                .display_lua_with(|build| build.display_lua()),
+        );
 
         Ok(std::iter::once(starter)
             .chain(template.into_iter().map(|kv| kv.to_string()))
@@ -860,39 +1174,11 @@ version = "{}""#,
             template.push(self.local.supported_platforms.display_lua());
         }
 
-        {
-            let mut dependencies = self.local.internal.dependencies.clone().unwrap_or_default();
-            dependencies.insert(
-                0,
-                PackageReq {
-                    name: "lua".into(),
-                    version_req: self.local.lua.clone(),
-                }
-                .into(),
-            );
-            template.push(Dependencies(&dependencies).display_lua());
-        }
-
-        match self.local.internal.build_dependencies {
-            Some(ref build_dependencies) if !build_dependencies.is_empty() => {
-                template.push(BuildDependencies(build_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.local.internal.external_dependencies {
-            Some(ref external_dependencies) if !external_dependencies.is_empty() => {
-                template.push(ExternalDependencies(external_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.local.internal.test_dependencies {
-            Some(ref test_dependencies) if !test_dependencies.is_empty() => {
-                template.push(TestDependencies(test_dependencies).display_lua());
-            }
-            _ => {}
-        }
+        template.extend(
+            self.local
+                .internal
+                .display_lua_dependencies(&self.local.lua),
+        );
 
         let source = self.local.internal.source_template.try_generate(
             project_root,
@@ -909,7 +1195,12 @@ version = "{}""#,
             template.push(deploy.display_lua());
         }
 
-        template.push(self.local.internal.build.display_lua());
+        template.push(
+            self.local
+                .internal
+                .build
+                .display_lua_with(|build| build.display_lua()),
+        );
 
         let unformatted_code = std::iter::once(starter)
             .chain(template.into_iter().map(|kv| kv.to_string()))
@@ -1014,16 +1305,20 @@ mod tests {
     use assert_fs::prelude::PathCopy;
     use git2::{Repository, RepositoryInitOptions};
     use git_url_parse::GitUrl;
+    use itertools::Itertools;
     use url::Url;
 
     use crate::{
         git::GitSource,
-        lua_rockspec::{PartialLuaRockspec, PerPlatform, RemoteLuaRockspec, RockSourceSpec},
+        lua_rockspec::{
+            PartialLuaRockspec, PerPlatform, PlatformIdentifier, RemoteLuaRockspec, RockSourceSpec,
+        },
+        package::{PackageName, PackageVersion, PackageVersionReq},
         project::{Project, ProjectRoot},
         rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
     };
 
-    use super::PartialProjectToml;
+    use super::{LocalProjectTomlValidationError, PartialProjectToml};
 
     #[test]
     fn project_toml_parsing() {
@@ -1504,6 +1799,390 @@ mod tests {
         }
     }
 
+    #[test]
+    fn project_toml_with_patches() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [dependencies]
+        foo = "1.0"
+
+        [patch]
+        foo = { path = "../foo" }
+        bar = { git = "github:owner/bar", rev = "abc123", version = "2.0.0" }
+
+        [build]
+        type = "builtin"
+        "#;
+
+        let project_root = ProjectRoot(PathBuf::from("/project"));
+        let project = PartialProjectToml::new(project_toml, project_root).unwrap();
+        let patches = project.patches();
+        assert_eq!(patches.len(), 2);
+
+        let foo = patches.get(&PackageName::new("foo".into())).unwrap();
+        assert_eq!(
+            foo.source(),
+            &Some(RockSourceSpec::File(PathBuf::from("/project/../foo")))
+        );
+        assert!(foo
+            .version_req()
+            .matches(&PackageVersion::default_dev_version()));
+
+        let bar = patches.get(&PackageName::new("bar".into())).unwrap();
+        assert!(matches!(
+            bar.source(),
+            Some(RockSourceSpec::Git(GitSource {
+                checkout_ref: Some(rev),
+                ..
+            })) if rev == "abc123"
+        ));
+        assert!(bar.version_req().matches(&"2.0.0".parse().unwrap()));
+    }
+
+    #[test]
+    fn project_toml_with_git_dependencies() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [dependencies]
+        foo = { git = "https://github.com/owner/foo", rev = "abc123" }
+        bar = { git = "github:owner/bar", version = "2.0.0" }
+
+        [build]
+        type = "builtin"
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        let dependencies = project.dependencies().current_platform();
+
+        let foo = dependencies
+            .iter()
+            .find(|dep| dep.name() == &PackageName::new("foo".into()))
+            .unwrap();
+        assert!(matches!(
+            foo.source(),
+            Some(RockSourceSpec::Git(GitSource {
+                checkout_ref: Some(rev),
+                ..
+            })) if rev == "abc123"
+        ));
+        assert!(foo
+            .version_req()
+            .matches(&PackageVersion::default_dev_version()));
+
+        let bar = dependencies
+            .iter()
+            .find(|dep| dep.name() == &PackageName::new("bar".into()))
+            .unwrap();
+        assert!(matches!(
+            bar.source(),
+            Some(RockSourceSpec::Git(GitSource {
+                checkout_ref: Some(rev),
+                ..
+            })) if rev == "2.0.0"
+        ));
+    }
+
+    #[test]
+    fn project_toml_with_invalid_git_dependencies() {
+        for dependency in [
+            r#"foo = { git = "github:owner/foo" }"#,
+            r#"foo = { rev = "abc123" }"#,
+            r#"foo = { opt = true }"#,
+        ] {
+            let project_toml = format!(
+                r#"
+                package = "my-package"
+                version = "1.0.0"
+                lua = "5.1"
+
+                [dependencies]
+                {dependency}
+                "#,
+            );
+
+            PartialProjectToml::new(&project_toml, ProjectRoot::default()).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn project_toml_with_dev_dependencies() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [source]
+        url = "https://example.com"
+
+        [dependencies]
+        foo = "1.0"
+
+        [test_dependencies]
+        busted = "2.0"
+
+        [dev-dependencies]
+        luacheck = "1.2"
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        let local = project.into_local().unwrap();
+        assert_eq!(
+            local
+                .dev_dependencies()
+                .current_platform()
+                .iter()
+                .map(|dep| dep.name().to_string())
+                .collect_vec(),
+            vec!["luacheck".to_string()]
+        );
+        assert!(!local
+            .test_dependencies()
+            .current_platform()
+            .iter()
+            .any(|dep| dep.name() == &"luacheck".into()));
+
+        let rockspec = project
+            .into_remote()
+            .unwrap()
+            .to_lua_remote_rockspec_string()
+            .unwrap();
+        assert!(!rockspec.contains("luacheck"));
+
+        let duplicate_dev_dependency = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [test_dependencies]
+        busted = "2.0"
+
+        [dev-dependencies]
+        busted = "2.0"
+        "#;
+        let project =
+            PartialProjectToml::new(duplicate_dev_dependency, ProjectRoot::default()).unwrap();
+        assert!(matches!(
+            project.into_local(),
+            Err(LocalProjectTomlValidationError::DuplicateDevDependencies(_))
+        ));
+    }
+
+    #[test]
+    fn project_toml_with_build_dependencies() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [dependencies]
+        foo = "1.0"
+
+        [build-dependencies]
+        luarocks-build-rust-mlua = "0.2"
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        let local = project.into_local().unwrap();
+        assert_eq!(
+            local
+                .build_dependencies()
+                .current_platform()
+                .iter()
+                .map(|dep| dep.name().to_string())
+                .collect_vec(),
+            vec!["luarocks-build-rust-mlua".to_string()]
+        );
+        assert!(!local
+            .dependencies()
+            .current_platform()
+            .iter()
+            .any(|dep| dep.name() == &"luarocks-build-rust-mlua".into()));
+    }
+
+    #[test]
+    fn project_toml_with_platform_overrides() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [source]
+        url = "https://example.com"
+
+        [dependencies]
+        foo = "1.0"
+
+        [dependencies.platforms.windows]
+        winapi = "1.0"
+
+        [dependencies.platforms.unix]
+        foo = "2.0"
+
+        [build]
+        type = "builtin"
+
+        [build.variables]
+        CFLAGS = "-O2"
+
+        [build.install.lua]
+        "foo.bar" = "src/bar.lua"
+
+        [build.platforms.windows.variables]
+        LIBFLAG = "-shared"
+
+        [build.platforms.windows.install.lua]
+        "foo.win" = "src/win.lua"
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        let local = project.into_local().unwrap();
+        let dependency_names = |platform: &PlatformIdentifier| {
+            local
+                .dependencies()
+                .get(platform)
+                .iter()
+                .map(|dep| dep.name().to_string())
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(
+            dependency_names(&PlatformIdentifier::Windows),
+            vec!["foo", "winapi"]
+        );
+        assert_eq!(dependency_names(&PlatformIdentifier::Linux), vec!["foo"]);
+        assert_eq!(
+            local.dependencies().get(&PlatformIdentifier::Linux)[0].version_req(),
+            &"2.0".parse::<PackageVersionReq>().unwrap()
+        );
+
+        let windows_build = local.build().get(&PlatformIdentifier::Windows);
+        assert_eq!(windows_build.install.lua.len(), 2);
+        let linux_build = local.build().get(&PlatformIdentifier::Linux);
+        assert_eq!(linux_build.install.lua.len(), 1);
+
+        let rockspec = project.into_remote().unwrap().to_lua_rockspec().unwrap();
+        for platform in [PlatformIdentifier::Windows, PlatformIdentifier::Linux] {
+            assert_eq!(
+                rockspec.build().get(&platform),
+                local.build().get(&platform)
+            );
+        }
+        assert!(rockspec
+            .dependencies()
+            .get(&PlatformIdentifier::Windows)
+            .iter()
+            .any(|dep| dep.name() == &"winapi".into()));
+    }
+
+    #[test]
+    fn project_toml_with_run_profiles() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [run]
+        args = ["src/main.lua"]
+
+        [run.profiles.debug]
+        args = ["src/main.lua", "--debug"]
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        let local = project.into_local().unwrap();
+        let run_spec = local.run().unwrap().current_platform();
+        assert_eq!(
+            run_spec.args.clone().unwrap().into_iter().collect_vec(),
+            vec!["src/main.lua".to_string()]
+        );
+        let debug = run_spec.profiles.get("debug").unwrap();
+        assert!(debug.command.is_none());
+        assert_eq!(
+            debug.args.clone().unwrap().into_iter().collect_vec(),
+            vec!["src/main.lua".to_string(), "--debug".to_string()]
+        );
+    }
+
+    #[test]
+    fn project_toml_with_scripts() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [source]
+        url = "https://example.com"
+
+        [scripts]
+        lint = "luacheck ."
+        fmt = "stylua ."
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        assert_eq!(
+            project.scripts().unwrap().get("lint").unwrap(),
+            "luacheck ."
+        );
+        let local = project.into_local().unwrap();
+        assert_eq!(local.scripts().get("lint").unwrap(), "luacheck .");
+        assert_eq!(local.scripts().get("fmt").unwrap(), "stylua .");
+
+        let rockspec = project
+            .into_remote()
+            .unwrap()
+            .to_lua_remote_rockspec_string()
+            .unwrap();
+        assert!(!rockspec.contains("luacheck"));
+    }
+
+    #[test]
+    fn project_toml_with_stylua_table() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [stylua]
+        column_width = 100
+        indent_type = "Spaces"
+        "#;
+
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        assert_eq!(project.package().to_string(), "my-package");
+    }
+
+    #[test]
+    fn project_toml_with_invalid_patches() {
+        for patch in [
+            r#"foo = { git = "github:owner/foo" }"#,
+            r#"foo = { rev = "abc123" }"#,
+            r#"foo = { version = "1.0.0" }"#,
+            r#"foo = { path = "../foo", git = "github:owner/foo", rev = "abc123" }"#,
+        ] {
+            let project_toml = format!(
+                r#"
+                package = "my-package"
+                version = "1.0.0"
+                lua = "5.1"
+
+                [patch]
+                {patch}
+                "#,
+            );
+
+            PartialProjectToml::new(&project_toml, ProjectRoot::default()).unwrap_err();
+        }
+    }
+
     #[test]
     fn generate_non_deterministic_git_source() {
         let rockspec_content = r#"
@@ -0,0 +1,276 @@
+//! Workspaces, which group multiple lux projects under a common root.
+//!
+//! A workspace is declared in a root `lux.toml` with a `[workspace]` table:
+//!
+//! ```toml
+//! [workspace]
+//! members = [ "rocks/foo", "rocks/bar" ]
+//! ```
+//!
+//! Each member is a directory (relative to the workspace root) containing its own `lux.toml`.
+//! The members share the `lux.lock` lockfile and the `.lux` tree in the workspace root,
+//! and their dependencies are resolved together, so that syncing one member
+//! doesn't prune the dependencies of the others.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::package::PackageName;
+
+use super::{Project, ProjectError, PROJECT_TOML};
+
+#[derive(Error, Debug)]
+pub enum WorkspaceError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("error parsing workspace lux.toml:\n{0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("error loading workspace member {0}:\n{1}")]
+    Project(PathBuf, ProjectError),
+    #[error("workspace member {0} is not a lux project (no lux.toml found)")]
+    MemberNotAProject(PathBuf),
+    #[error("no workspace member named {0}")]
+    MemberNotFound(PackageName),
+    #[error("not in a lux workspace directory")]
+    NotAWorkspaceDir,
+}
+
+/// The `[workspace]` table of a workspace root's `lux.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkspaceSpec {
+    /// Paths to the member projects, relative to the workspace root.
+    #[serde(default)]
+    pub(crate) members: Vec<PathBuf>,
+}
+
+impl WorkspaceSpec {
+    pub fn members(&self) -> &[PathBuf] {
+        &self.members
+    }
+}
+
+/// Helper for probing a `lux.toml` for a `[workspace]` table,
+/// ignoring all other fields.
+#[derive(Deserialize)]
+struct WorkspaceToml {
+    #[serde(default)]
+    workspace: Option<WorkspaceSpec>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Workspace {
+    /// The path where the workspace's `lux.toml` resides.
+    root: PathBuf,
+    spec: WorkspaceSpec,
+    members: Vec<Project>,
+}
+
+impl Workspace {
+    pub fn current() -> Result<Option<Self>, WorkspaceError> {
+        Self::from(&std::env::current_dir()?)
+    }
+
+    pub fn current_or_err() -> Result<Self, WorkspaceError> {
+        Self::current()?.ok_or(WorkspaceError::NotAWorkspaceDir)
+    }
+
+    /// Search `start` and its parent directories for a `lux.toml` with a `[workspace]` table.
+    pub fn from(start: impl AsRef<Path>) -> Result<Option<Self>, WorkspaceError> {
+        for dir in start.as_ref().ancestors() {
+            if let Some(workspace) = Self::from_exact(dir)? {
+                return Ok(Some(workspace));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Load a workspace whose `lux.toml` is located directly in `root`.
+    pub fn from_exact(root: impl AsRef<Path>) -> Result<Option<Self>, WorkspaceError> {
+        let toml_path = root.as_ref().join(PROJECT_TOML);
+        if !toml_path.is_file() {
+            return Ok(None);
+        }
+        let toml_content = std::fs::read_to_string(&toml_path)?;
+        let spec = match toml::from_str::<WorkspaceToml>(&toml_content)?.workspace {
+            Some(spec) => spec,
+            None => return Ok(None),
+        };
+        let members = spec
+            .members
+            .iter()
+            .map(|member| {
+                let member_root = root.as_ref().join(member);
+                match Project::from_exact(&member_root) {
+                    Ok(Some(project)) => Ok(project.in_workspace(root.as_ref().to_path_buf())),
+                    Ok(None) => Err(WorkspaceError::MemberNotAProject(member_root)),
+                    Err(err) => Err(WorkspaceError::Project(member_root, err)),
+                }
+            })
+            .try_collect()?;
+        Ok(Some(Self {
+            root: root.as_ref().to_path_buf(),
+            spec,
+            members,
+        }))
+    }
+
+    /// Get the workspace's `lux.toml` path.
+    pub fn toml_path(&self) -> PathBuf {
+        self.root.join(PROJECT_TOML)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn spec(&self) -> &WorkspaceSpec {
+        &self.spec
+    }
+
+    /// The member projects, in the order they are declared in the `lux.toml`.
+    pub fn members(&self) -> &[Project] {
+        &self.members
+    }
+
+    /// Find a member project by its package name.
+    pub fn member(&self, name: &PackageName) -> Result<&Project, WorkspaceError> {
+        self.members
+            .iter()
+            .find(|project| project.toml().package() == name)
+            .ok_or_else(|| WorkspaceError::MemberNotFound(name.clone()))
+    }
+}
+
+/// Find the root of the workspace that the project in `project_root` is a member of, if any.
+/// Unreadable or invalid `lux.toml` files in parent directories are ignored.
+pub(crate) fn find_workspace_root(project_root: &Path) -> Option<PathBuf> {
+    let project_root = normalize(project_root);
+    project_root.ancestors().skip(1).find_map(|dir| {
+        let toml_content = std::fs::read_to_string(dir.join(PROJECT_TOML)).ok()?;
+        let spec = toml::from_str::<WorkspaceToml>(&toml_content)
+            .ok()?
+            .workspace?;
+        spec.members
+            .iter()
+            .any(|member| normalize(&dir.join(member)) == project_root)
+            .then(|| dir.to_path_buf())
+    })
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild, PathCopy};
+
+    use super::*;
+
+    #[test]
+    fn test_workspace_members() {
+        let sample_project: PathBuf = "resources/test/sample-projects/init/".into();
+        let workspace_root = assert_fs::TempDir::new().unwrap();
+        workspace_root
+            .child("rocks/foo")
+            .copy_from(&sample_project, &["**"])
+            .unwrap();
+        workspace_root
+            .child(PROJECT_TOML)
+            .write_str(
+                r#"
+[workspace]
+members = [ "rocks/foo" ]
+"#,
+            )
+            .unwrap();
+
+        let workspace = Workspace::from(workspace_root.child("rocks/foo").path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(workspace.root(), workspace_root.path());
+        assert_eq!(workspace.members().len(), 1);
+        let member = &workspace.members()[0];
+        assert!(workspace.member(member.toml().package()).is_ok());
+        assert_eq!(
+            normalize(member.workspace_root().unwrap()),
+            normalize(workspace_root.path())
+        );
+        assert!(matches!(
+            workspace.member(&"nonexistent".into()),
+            Err(WorkspaceError::MemberNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_workspace_members_share_lockfile() {
+        let sample_project: PathBuf = "resources/test/sample-projects/init/".into();
+        let workspace_root = assert_fs::TempDir::new().unwrap();
+        for member in ["rocks/foo", "rocks/bar"] {
+            workspace_root
+                .child(member)
+                .copy_from(&sample_project, &["**"])
+                .unwrap();
+        }
+        workspace_root
+            .child(PROJECT_TOML)
+            .write_str(
+                r#"
+[workspace]
+members = [ "rocks/foo", "rocks/bar" ]
+"#,
+            )
+            .unwrap();
+
+        let workspace_root = normalize(workspace_root.path());
+        let foo = Project::from(workspace_root.join("rocks/foo"))
+            .unwrap()
+            .unwrap();
+        let bar = Project::from_exact(workspace_root.join("rocks/bar"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(foo.workspace_root(), Some(workspace_root.as_path()));
+        assert_eq!(foo.lockfile_path(), workspace_root.join("lux.lock"));
+        assert_eq!(foo.lockfile_path(), bar.lockfile_path());
+        let config = crate::config::ConfigBuilder::new()
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(foo.tree_root_dir(&config), workspace_root.join(".lux"));
+        assert_eq!(foo.tree_root_dir(&config), bar.tree_root_dir(&config));
+    }
+
+    #[test]
+    fn test_workspace_missing_member() {
+        let workspace_root = assert_fs::TempDir::new().unwrap();
+        workspace_root
+            .child(PROJECT_TOML)
+            .write_str(
+                r#"
+[workspace]
+members = [ "nonexistent" ]
+"#,
+            )
+            .unwrap();
+        assert!(matches!(
+            Workspace::from_exact(workspace_root.path()),
+            Err(WorkspaceError::MemberNotAProject(_))
+        ));
+    }
+
+    #[test]
+    fn test_project_is_not_a_workspace() {
+        let sample_project: PathBuf = "resources/test/sample-projects/init/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        assert!(Workspace::from_exact(project_root.path())
+            .unwrap()
+            .is_none());
+    }
+}
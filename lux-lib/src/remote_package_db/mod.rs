@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::{
     config::{Config, ConfigError},
-    lockfile::{LocalPackageLock, LockfileIntegrityError},
+    lockfile::{LocalPackageHashes, LocalPackageLock, LockfileIntegrityError},
     manifest::{Manifest, ManifestError},
     package::{
         PackageName, PackageReq, PackageSpec, PackageVersion, RemotePackage,
@@ -50,6 +50,7 @@ pub enum RemotePackageDbIntegrityError {
 }
 
 impl RemotePackageDB {
+    #[tracing::instrument(skip_all)]
     pub async fn from_config(
         config: &Config,
         progress: &Progress<ProgressBar>,
@@ -59,15 +60,15 @@ impl RemotePackageDB {
             let manifest = Manifest::from_config(server, config, progress).await?;
             manifests.push(manifest);
         }
-        for server in config.extra_servers() {
+        for server in config.servers_by_priority() {
             let manifest = Manifest::from_config(server.clone(), config, progress).await?;
             manifests.push(manifest);
         }
-        manifests.push(Manifest::from_config(config.server().clone(), config, progress).await?);
         Ok(Self(Impl::LuarocksManifests(manifests)))
     }
 
     /// Find a remote package that matches the requirement, returning the latest match.
+    #[tracing::instrument(skip_all, fields(package = %package_req))]
     pub(crate) fn find(
         &self,
         package_req: &PackageReq,
@@ -97,6 +98,22 @@ impl RemotePackageDB {
         }
     }
 
+    /// Get the hashes that a package's rockspec and source are expected to have.
+    /// Only lockfile-backed package DBs know about hashes.
+    pub(crate) fn expected_hashes(&self, package: &PackageSpec) -> Option<LocalPackageHashes> {
+        match &self.0 {
+            Impl::LuarocksManifests(_) => None,
+            Impl::Lock(lockfile) => lockfile
+                .rocks()
+                .values()
+                .find(|local_package| {
+                    local_package.name() == package.name()
+                        && local_package.version() == package.version()
+                })
+                .map(|local_package| local_package.hashes().clone()),
+        }
+    }
+
     /// Search for all packages that match the requirement.
     pub fn search(&self, package_req: &PackageReq) -> Vec<(&PackageName, Vec<&PackageVersion>)> {
         match &self.0 {
@@ -142,6 +159,46 @@ impl RemotePackageDB {
         }
     }
 
+    /// The names of all available packages, sorted alphabetically.
+    pub fn package_names(&self) -> Vec<&PackageName> {
+        match &self.0 {
+            Impl::LuarocksManifests(manifests) => manifests
+                .iter()
+                .flat_map(|manifest| manifest.metadata().repository.keys())
+                .unique()
+                .sorted()
+                .collect_vec(),
+            Impl::Lock(lockfile) => lockfile
+                .rocks()
+                .values()
+                .map(|package| package.name())
+                .unique()
+                .sorted()
+                .collect_vec(),
+        }
+    }
+
+    /// All available versions of a package, from the latest to the oldest.
+    pub fn versions(&self, rock_name: &PackageName) -> Vec<PackageVersion> {
+        match &self.0 {
+            Impl::LuarocksManifests(manifests) => manifests
+                .iter()
+                .filter_map(|manifest| manifest.metadata().repository.get(rock_name))
+                .flat_map(|versions| versions.keys().cloned())
+                .unique()
+                .sorted_by(|a, b| Ord::cmp(b, a))
+                .collect_vec(),
+            Impl::Lock(lockfile) => lockfile
+                .rocks()
+                .values()
+                .filter(|package| package.name() == rock_name)
+                .map(|package| package.version().clone())
+                .unique()
+                .sorted_by(|a, b| Ord::cmp(b, a))
+                .collect_vec(),
+        }
+    }
+
     /// Find the latest version for a package by name.
     pub(crate) fn latest_version(&self, rock_name: &PackageName) -> Option<PackageVersion> {
         self.latest_match(&rock_name.clone().into(), None)
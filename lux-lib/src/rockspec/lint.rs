@@ -0,0 +1,650 @@
+//! Validation of rockspecs and `lux.toml` files.
+//!
+//! Unlike parsing, linting does not stop at the first error.
+//! Each problem is reported as a [`LintDiagnostic`], with the position of the
+//! offending field, where it can be determined.
+
+use std::fmt::Display;
+
+use itertools::Itertools;
+use mlua::{Lua, Table, Value};
+use serde::Serialize;
+use toml_edit::{Document, Item, TableLike};
+
+use crate::{
+    lua_rockspec::RemoteLuaRockspec,
+    package::{PackageReq, PackageVersion, PackageVersionReq},
+    project::{project_toml::PartialProjectToml, ProjectRoot},
+};
+
+/// The top-level fields of a rockspec.
+const ROCKSPEC_FIELDS: &[&str] = &[
+    "rockspec_format",
+    "package",
+    "version",
+    "description",
+    "supported_platforms",
+    "dependencies",
+    "build_dependencies",
+    "external_dependencies",
+    "test_dependencies",
+    "source",
+    "build",
+    "test",
+    "deploy",
+    "hooks",
+];
+
+/// The fields of a rockspec's `description` table.
+const ROCKSPEC_DESCRIPTION_FIELDS: &[&str] = &[
+    "summary",
+    "detailed",
+    "license",
+    "homepage",
+    "issues_url",
+    "maintainer",
+    "labels",
+];
+
+const ROCKSPEC_SOURCE_FIELDS: &[&str] = &[
+    "url",
+    "hash",
+    "md5",
+    "file",
+    "dir",
+    "tag",
+    "branch",
+    "module",
+    "cvs_tag",
+    "cvs_module",
+];
+
+const DEPENDENCY_FIELDS: [&str; 3] = ["dependencies", "build_dependencies", "test_dependencies"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Error,
+    Warning,
+}
+
+impl Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintSeverity::Error => "error".fmt(f),
+            LintSeverity::Warning => "warning".fmt(f),
+        }
+    }
+}
+
+/// A 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of a byte offset in `content`.
+    pub(crate) fn from_offset(content: &str, offset: usize) -> Self {
+        let before = &content[..offset.min(content.len())];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintDiagnostic {
+    severity: LintSeverity,
+    /// A short identifier of the check, e.g. `unknown-field`.
+    code: &'static str,
+    message: String,
+    #[serde(flatten)]
+    position: Option<Position>,
+}
+
+impl LintDiagnostic {
+    fn error(code: &'static str, message: impl Into<String>, position: Option<Position>) -> Self {
+        Self {
+            severity: LintSeverity::Error,
+            code,
+            message: message.into(),
+            position,
+        }
+    }
+
+    fn warning(code: &'static str, message: impl Into<String>, position: Option<Position>) -> Self {
+        Self {
+            severity: LintSeverity::Warning,
+            code,
+            message: message.into(),
+            position,
+        }
+    }
+
+    pub fn severity(&self) -> LintSeverity {
+        self.severity
+    }
+
+    pub fn code(&self) -> &str {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+}
+
+impl Display for LintDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(Position { line, column }) => write!(
+                f,
+                "{line}:{column}: {}[{}]: {}",
+                self.severity, self.code, self.message
+            ),
+            None => write!(f, "{}[{}]: {}", self.severity, self.code, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LintReport {
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+impl LintReport {
+    pub fn diagnostics(&self) -> &[LintDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Whether there are no errors. Warnings are allowed.
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity != LintSeverity::Error)
+    }
+
+    fn push(&mut self, diagnostic: LintDiagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Lint the content of a rockspec.
+pub fn lint_rockspec(content: &str) -> LintReport {
+    let mut report = LintReport::default();
+    let lua = Lua::new();
+    let env = match evaluate_rockspec(&lua, content) {
+        Ok(env) => env,
+        Err(err) => {
+            let message = err.to_string();
+            let position = lua_error_line(&message).map(|line| Position { line, column: 1 });
+            report.push(LintDiagnostic::error("syntax", message, position));
+            return report;
+        }
+    };
+    if let Err(err) = lint_rockspec_fields(&env, content, &mut report) {
+        report.push(LintDiagnostic::error("schema", err.to_string(), None));
+    }
+    if report.is_ok() {
+        if let Err(err) = RemoteLuaRockspec::new(content) {
+            let message = err.to_string();
+            let position = lua_error_line(&message).map(|line| Position { line, column: 1 });
+            report.push(LintDiagnostic::error("schema", message, position));
+        }
+    }
+    report
+}
+
+/// Lint the content of a `lux.toml`.
+pub fn lint_project_toml(content: &str) -> LintReport {
+    let mut report = LintReport::default();
+    let document = match Document::parse(content) {
+        Ok(document) => document,
+        Err(err) => {
+            let position = err
+                .span()
+                .map(|span| Position::from_offset(content, span.start));
+            report.push(LintDiagnostic::error(
+                "syntax",
+                err.message().trim().to_string(),
+                position,
+            ));
+            return report;
+        }
+    };
+    lint_project_toml_fields(document.as_table(), content, &mut report);
+    if report.is_ok() {
+        match PartialProjectToml::new(content, ProjectRoot::new()) {
+            Ok(project_toml) => {
+                if let Err(err) = project_toml.into_local() {
+                    report.push(LintDiagnostic::error("schema", err.to_string(), None));
+                }
+            }
+            Err(err) => {
+                let code = if err.message().starts_with("unknown field") {
+                    "unknown-field"
+                } else {
+                    "schema"
+                };
+                report.push(LintDiagnostic::error(code, err.message(), err.position()))
+            }
+        }
+    }
+    report
+}
+
+/// Evaluate the rockspec in its own environment,
+/// so that its fields can be told apart from the Lua globals.
+fn evaluate_rockspec(lua: &Lua, content: &str) -> mlua::Result<Table> {
+    let env = lua.create_table()?;
+    let metatable = lua.create_table()?;
+    metatable.set("__index", lua.globals())?;
+    env.set_metatable(Some(metatable));
+    lua.load(content).set_environment(env.clone()).exec()?;
+    Ok(env)
+}
+
+fn lint_rockspec_fields(env: &Table, content: &str, report: &mut LintReport) -> mlua::Result<()> {
+    for key in table_keys(env)? {
+        if !ROCKSPEC_FIELDS.contains(&key.as_str()) {
+            report.push(unknown_field(
+                &key,
+                ROCKSPEC_FIELDS,
+                find_key(content, &key),
+            ));
+        }
+    }
+
+    match env.get::<Option<String>>("package")? {
+        Some(_) => {}
+        None => report.push(LintDiagnostic::error(
+            "missing-field",
+            "missing field `package`",
+            None,
+        )),
+    }
+    match env.get::<Option<String>>("version")? {
+        Some(version) => lint_version(&version, find_string(content, &version), report),
+        None => report.push(LintDiagnostic::error(
+            "missing-field",
+            "missing field `version`",
+            None,
+        )),
+    }
+
+    if let Value::Table(description) = env.get::<Value>("description")? {
+        for key in table_keys(&description)? {
+            if !ROCKSPEC_DESCRIPTION_FIELDS.contains(&key.as_str()) {
+                report.push(unknown_field(
+                    &format!("description.{key}"),
+                    ROCKSPEC_DESCRIPTION_FIELDS,
+                    find_key(content, &key),
+                ));
+            }
+        }
+        if let Some(license) = description.get::<Option<String>>("license")? {
+            lint_license(&license, find_string(content, &license), report);
+        }
+    }
+
+    match env.get::<Value>("source")? {
+        Value::Table(source) => {
+            for key in table_keys(&source)? {
+                if !ROCKSPEC_SOURCE_FIELDS.contains(&key.as_str()) {
+                    report.push(unknown_field(
+                        &format!("source.{key}"),
+                        ROCKSPEC_SOURCE_FIELDS,
+                        find_key(content, &key),
+                    ));
+                }
+            }
+            if source.get::<Option<String>>("url")?.is_none() {
+                report.push(LintDiagnostic::error(
+                    "missing-source-url",
+                    "missing field `source.url`",
+                    find_key(content, "source"),
+                ));
+            }
+        }
+        _ => report.push(LintDiagnostic::error(
+            "missing-source-url",
+            "missing field `source`, with a `url`",
+            None,
+        )),
+    }
+
+    for field in DEPENDENCY_FIELDS {
+        if let Value::Table(dependencies) = env.get::<Value>(field)? {
+            lint_rockspec_dependencies(&dependencies, content, report)?;
+            if let Value::Table(platforms) = dependencies.get::<Value>("platforms")? {
+                for pair in platforms.pairs::<Value, Value>() {
+                    if let (_, Value::Table(platform_dependencies)) = pair? {
+                        lint_rockspec_dependencies(&platform_dependencies, content, report)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lint_rockspec_dependencies(
+    dependencies: &Table,
+    content: &str,
+    report: &mut LintReport,
+) -> mlua::Result<()> {
+    for dependency in dependencies.sequence_values::<String>() {
+        let dependency = dependency?;
+        let message = match dependency.parse::<PackageReq>() {
+            Ok(package_req) if is_malformed_version_req(package_req.version_req()) => {
+                format!("invalid version constraint in dependency '{dependency}'")
+            }
+            Ok(_) => continue,
+            Err(err) => err.to_string(),
+        };
+        report.push(LintDiagnostic::error(
+            "invalid-dependency",
+            message,
+            find_string(content, &dependency),
+        ));
+    }
+    Ok(())
+}
+
+fn lint_project_toml_fields(table: &dyn TableLike, content: &str, report: &mut LintReport) {
+    if !table.contains_key("package") {
+        report.push(LintDiagnostic::error(
+            "missing-field",
+            "missing field `package`",
+            None,
+        ));
+    }
+    if let Some((version, position)) = string_value(content, table.get("version")) {
+        lint_version(version, position, report);
+    }
+    if let Some((lua, position)) = string_value(content, table.get("lua")) {
+        lint_version_req(lua, position, report);
+    }
+
+    if let Some(description) = table.get("description").and_then(Item::as_table_like) {
+        if let Some((license, position)) = string_value(content, description.get("license")) {
+            lint_license(license, position, report);
+        }
+    }
+
+    // The source is optional, as it can be generated from the git remote,
+    // but a `[source]` table without a URL can't be used to generate a rockspec.
+    if let Some(source) = table.get("source").and_then(Item::as_table_like) {
+        if !source.contains_key("url") {
+            report.push(LintDiagnostic::error(
+                "missing-source-url",
+                "missing field `source.url`",
+                key_position(content, table, "source"),
+            ));
+        }
+    }
+
+    for field in [
+        "dependencies",
+        "build_dependencies",
+        "build-dependencies",
+        "test_dependencies",
+        "dev_dependencies",
+        "dev-dependencies",
+    ] {
+        if let Some(dependencies) = table.get(field).and_then(Item::as_table_like) {
+            lint_project_toml_dependencies(dependencies, content, report);
+            let platforms = dependencies.get("platforms").and_then(Item::as_table_like);
+            for platform_dependencies in platforms
+                .into_iter()
+                .flat_map(|platforms| platforms.iter())
+                .filter_map(|(_, value)| value.as_table_like())
+            {
+                lint_project_toml_dependencies(platform_dependencies, content, report);
+            }
+        }
+    }
+}
+
+fn lint_project_toml_dependencies(
+    dependencies: &dyn TableLike,
+    content: &str,
+    report: &mut LintReport,
+) {
+    for (_, entry) in dependencies.iter().filter(|(name, _)| *name != "platforms") {
+        let version_req = match entry.as_table_like() {
+            Some(entry) => string_value(content, entry.get("version")),
+            None => string_value(content, Some(entry)),
+        };
+        if let Some((version_req, position)) = version_req {
+            lint_version_req(version_req, position, report);
+        }
+    }
+}
+
+fn lint_version(version: &str, position: Option<Position>, report: &mut LintReport) {
+    let message = match PackageVersion::parse(version) {
+        Ok(parsed) if !parsed.is_semver() && is_malformed_version(version) => {
+            format!("invalid version '{version}'")
+        }
+        Ok(_) => return,
+        Err(err) => format!("invalid version '{version}': {err}"),
+    };
+    report.push(LintDiagnostic::error("invalid-version", message, position));
+}
+
+fn lint_version_req(version_req: &str, position: Option<Position>, report: &mut LintReport) {
+    let message = match PackageVersionReq::parse(version_req) {
+        Ok(parsed) if is_malformed_version_req(&parsed) => {
+            format!("invalid version constraint '{version_req}'")
+        }
+        Ok(_) => return,
+        Err(err) => format!("invalid version constraint '{version_req}': {err}"),
+    };
+    report.push(LintDiagnostic::error(
+        "invalid-version-req",
+        message,
+        position,
+    ));
+}
+
+/// Version requirements that are not SemVer are accepted as arbitrary version strings
+/// (e.g. `==1.0beta`), so a typo in a constraint is not a parse error.
+/// Version strings with whitespace or operators are almost certainly typos.
+fn is_malformed_version_req(version_req: &PackageVersionReq) -> bool {
+    match version_req {
+        PackageVersionReq::StringVer(version) => is_malformed_version(version),
+        _ => false,
+    }
+}
+
+fn is_malformed_version(version: &str) -> bool {
+    version.is_empty() || version.contains(|c: char| c.is_whitespace() || "<>~=,".contains(c))
+}
+
+/// Check that a license is a valid SPDX expression, e.g. `MIT OR Apache-2.0`.
+fn lint_license(license: &str, position: Option<Position>, report: &mut LintReport) {
+    if license.trim().is_empty() {
+        report.push(LintDiagnostic::warning(
+            "license",
+            "the license is empty",
+            position,
+        ));
+        return;
+    }
+    let err = match spdx::Expression::parse(license) {
+        Ok(_) => return,
+        Err(err) => err,
+    };
+    let identifier = &license[err.span.clone()];
+    let message = match spdx::imprecise_license_id(identifier) {
+        Some((license_id, _)) => format!(
+            "'{identifier}' is not a valid SPDX license identifier. Did you mean '{}'?",
+            license_id.name
+        ),
+        None => format!(
+            "'{license}' is not a valid SPDX license expression: {}",
+            err.reason
+        ),
+    };
+    report.push(LintDiagnostic::warning("license", message, position));
+}
+
+/// Unknown fields are ignored in rockspecs, so they are only reported as warnings.
+/// In a `lux.toml`, they are rejected by the schema.
+fn unknown_field(key: &str, known: &[&str], position: Option<Position>) -> LintDiagnostic {
+    let field = key.rsplit('.').next().unwrap_or(key);
+    let message = match similar_key(field, known) {
+        Some(suggestion) => format!("unknown field `{key}`, did you mean `{suggestion}`?"),
+        None => format!("unknown field `{key}`"),
+    };
+    LintDiagnostic::warning("unknown-field", message, position)
+}
+
+/// The known key that is most similar to `key`, if any is close enough to be a typo.
+pub(crate) fn similar_key<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (strsim::levenshtein(key, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn table_keys(table: &Table) -> mlua::Result<Vec<String>> {
+    table
+        .pairs::<Value, Value>()
+        .filter_map(|pair| match pair {
+            Ok((Value::String(key), _)) => Some(key.to_str().map(|key| key.to_string())),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .try_collect()
+}
+
+/// The position of the first line of a rockspec that assigns `key`, i.e. `key = ...`.
+fn find_key(content: &str, key: &str) -> Option<Position> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed
+            .strip_prefix(key)
+            .is_some_and(|after_key| after_key.trim_start().starts_with('='))
+        {
+            let indent = line.len() - trimmed.len();
+            return Some(Position::from_offset(content, offset + indent));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// The position of the first quoted occurrence of `value` in a rockspec.
+fn find_string(content: &str, value: &str) -> Option<Position> {
+    ['"', '\'']
+        .into_iter()
+        .filter_map(|quote| content.find(&format!("{quote}{value}{quote}")))
+        .min()
+        .map(|offset| Position::from_offset(content, offset + 1))
+}
+
+/// Lua error messages include the line number, e.g. `[string "..."]:3: unexpected symbol`.
+fn lua_error_line(message: &str) -> Option<usize> {
+    let (_, rest) = message.split_once("]:")?;
+    rest.split(':').next()?.parse().ok()
+}
+
+/// The position of a key in a `lux.toml` table.
+fn key_position(content: &str, table: &dyn TableLike, key: &str) -> Option<Position> {
+    let (key, _) = table.get_key_value(key)?;
+    key.span()
+        .map(|span| Position::from_offset(content, span.start))
+}
+
+/// A string value in a `lux.toml` and the position of its content (inside the quotes).
+fn string_value<'a>(content: &str, item: Option<&'a Item>) -> Option<(&'a str, Option<Position>)> {
+    let value = item?.as_value()?;
+    let position = value
+        .span()
+        .map(|span| Position::from_offset(content, span.start + 1));
+    Some((value.as_str()?, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_rockspec_diagnostics() {
+        let rockspec = r#"
+package = "foo"
+version = "1.0.0-1"
+description = {
+    license = "mit",
+}
+dependencis = { "lua >= 5.1" }
+dependencies = { "lua >= 5.1", "bar >= x.y" }
+build = { type = "builtin" }
+"#;
+        let report = lint_rockspec(rockspec);
+        assert!(!report.is_ok());
+        let codes = report
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.code(), diagnostic.position().map(|p| p.line)))
+            .collect_vec();
+        assert!(codes.contains(&("unknown-field", Some(7))));
+        assert!(codes.contains(&("license", Some(5))));
+        assert!(codes.contains(&("missing-source-url", None)));
+        assert!(codes.contains(&("invalid-dependency", Some(8))));
+        assert!(report
+            .diagnostics()
+            .iter()
+            .any(|diagnostic| diagnostic.message().contains("did you mean `dependencies`")));
+    }
+
+    #[test]
+    fn lint_project_toml_diagnostics() {
+        let project_toml = r#"
+package = "foo"
+version = "1.0.0"
+lua = ">=5.1"
+
+[description]
+license = "MIT OR Apache-2.0"
+
+[dependencies]
+bar = "not a version"
+"#;
+        let report = lint_project_toml(project_toml);
+        assert_eq!(report.diagnostics().len(), 1, "{:?}", report.diagnostics());
+        let diagnostic = &report.diagnostics()[0];
+        assert_eq!(diagnostic.code(), "invalid-version-req");
+        assert_eq!(
+            diagnostic.position(),
+            Some(Position {
+                line: 10,
+                column: 8
+            })
+        );
+
+        let report = lint_project_toml("package = \"foo\"\n[description]\nlicence = \"MIT\"\n");
+        assert_eq!(report.diagnostics()[0].code(), "unknown-field");
+        assert_eq!(
+            report.diagnostics()[0].message(),
+            "unknown field `licence`, did you mean `license`?"
+        );
+
+        let report = lint_project_toml("package = \"foo\"\nversion = \n");
+        assert_eq!(report.diagnostics()[0].code(), "syntax");
+        assert_eq!(report.diagnostics()[0].position().map(|p| p.line), Some(2));
+    }
+}
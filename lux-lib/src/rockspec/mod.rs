@@ -9,6 +9,7 @@ use itertools::Itertools;
 use lua_dependency::LuaDependencySpec;
 use mlua::IntoLua;
 use serde::{Deserialize, Serialize};
+pub mod lint;
 pub mod lua_dependency;
 
 use crate::{
@@ -28,6 +29,11 @@ pub trait Rockspec {
     fn description(&self) -> &RockDescription;
     fn supported_platforms(&self) -> &PlatformSupport;
     fn lua(&self) -> &PackageVersionReq;
+    /// Whether the rock can only be used with LuaJIT,
+    /// i.e. whether it depends on the virtual `luajit` package.
+    fn requires_luajit(&self) -> bool {
+        false
+    }
     fn dependencies(&self) -> &PerPlatform<Vec<LuaDependencySpec>>;
     fn build_dependencies(&self) -> &PerPlatform<Vec<LuaDependencySpec>>;
     fn external_dependencies(&self) -> &PerPlatform<HashMap<String, ExternalDependencySpec>>;
@@ -99,6 +105,7 @@ impl<T: Rockspec> LuaVersionCompatibility for T {
 
     fn supports_lua_version(&self, lua_version: &LuaVersion) -> bool {
         self.lua().matches(&lua_version.as_version())
+            && (!self.requires_luajit() || lua_version.is_luajit())
     }
 
     fn lua_version(&self) -> Option<LuaVersion> {
@@ -0,0 +1,136 @@
+use std::{collections::BTreeMap, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use ssri::Integrity;
+use walkdir::WalkDir;
+
+use crate::hash::HasIntegrity;
+
+use super::RockLayout;
+
+/// Checksums of the files installed into a rock's directory,
+/// recorded at install time so that the tree can be verified later.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RockChecksums {
+    files: BTreeMap<PathBuf, Integrity>,
+}
+
+/// The result of comparing a rock's files with its recorded checksums.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RockChecksumsDiff {
+    /// Files whose content no longer matches the recorded checksum,
+    /// or which were not present at install time.
+    pub modified: Vec<PathBuf>,
+    /// Files that were installed, but no longer exist.
+    pub deleted: Vec<PathBuf>,
+}
+
+impl RockChecksumsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl RockChecksums {
+    /// Compute the checksums of all files in the rock's directory.
+    #[tracing::instrument(skip_all, fields(rock_path = %layout.rock_path.display()))]
+    pub fn compute(layout: &RockLayout) -> io::Result<Self> {
+        let checksums_path = layout.checksums_path();
+        let mut files = BTreeMap::new();
+        for entry in WalkDir::new(&layout.rock_path) {
+            let path = entry?.into_path();
+            if !path.is_file() || path == checksums_path {
+                continue;
+            }
+            let relative_path = path
+                .strip_prefix(&layout.rock_path)
+                .expect("walked path is not in the rock directory")
+                .to_path_buf();
+            files.insert(relative_path, path.hash()?);
+        }
+        Ok(Self { files })
+    }
+
+    /// Compute the checksums of all files in the rock's directory and write them to the
+    /// [`RockLayout::checksums_path`].
+    pub fn write(layout: &RockLayout) -> io::Result<()> {
+        let checksums = Self::compute(layout)?;
+        let content = serde_json::to_string_pretty(&checksums)?;
+        std::fs::write(layout.checksums_path(), content)
+    }
+
+    /// Read the recorded checksums, if present.
+    pub fn read(layout: &RockLayout) -> io::Result<Option<Self>> {
+        let checksums_path = layout.checksums_path();
+        if !checksums_path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(checksums_path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Compare the recorded checksums with the `actual` checksums of a rock's files.
+    pub fn diff(&self, actual: &Self) -> RockChecksumsDiff {
+        let modified = actual
+            .files
+            .iter()
+            .filter(|(path, integrity)| {
+                self.files
+                    .get(*path)
+                    .is_none_or(|expected| expected.matches(integrity).is_none())
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        let deleted = self
+            .files
+            .keys()
+            .filter(|path| !actual.files.contains_key(*path))
+            .cloned()
+            .collect();
+        RockChecksumsDiff { modified, deleted }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::*;
+
+    #[test]
+    fn verify_rock_checksums() {
+        let rock_dir = assert_fs::TempDir::new().unwrap();
+        rock_dir
+            .child("src/foo.lua")
+            .write_str("return {}")
+            .unwrap();
+        rock_dir
+            .child("src/bar.lua")
+            .write_str("return {}")
+            .unwrap();
+        let layout = RockLayout::at(rock_dir.path());
+
+        RockChecksums::write(&layout).unwrap();
+        let recorded = RockChecksums::read(&layout).unwrap().unwrap();
+        assert!(recorded
+            .diff(&RockChecksums::compute(&layout).unwrap())
+            .is_empty());
+
+        rock_dir
+            .child("src/foo.lua")
+            .write_str("return { tampered = true }")
+            .unwrap();
+        std::fs::remove_file(rock_dir.child("src/bar.lua").path()).unwrap();
+        rock_dir
+            .child("src/baz.lua")
+            .write_str("return {}")
+            .unwrap();
+
+        let diff = recorded.diff(&RockChecksums::compute(&layout).unwrap());
+        assert_eq!(
+            diff.modified,
+            vec![PathBuf::from("src/baz.lua"), PathBuf::from("src/foo.lua")]
+        );
+        assert_eq!(diff.deleted, vec![PathBuf::from("src/bar.lua")]);
+    }
+}
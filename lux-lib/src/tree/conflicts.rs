@@ -0,0 +1,157 @@
+//! Detection of packages that provide executables or Lua modules with the same name.
+
+use std::fmt::Display;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{build::utils::c_dylib_extension, lockfile::LocalPackage, package::PackageSpec};
+
+use super::{luarocks_manifest::lua_modules, Tree};
+
+/// A file that is provided by more than one package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictingFile {
+    /// An executable in the tree's `bin` directory.
+    Binary(String),
+    /// A Lua module.
+    Module(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct FileConflict {
+    file: ConflictingFile,
+    /// The package that provided the file first.
+    first: PackageSpec,
+    second: PackageSpec,
+}
+
+/// Files that would be provided by more than one package.
+#[derive(Error, Debug, Clone)]
+pub struct FileConflicts(Vec<FileConflict>);
+
+impl FileConflict {
+    pub fn file(&self) -> &ConflictingFile {
+        &self.file
+    }
+
+    /// The package that provided the file first, e.g. because it is already installed.
+    pub fn first(&self) -> &PackageSpec {
+        &self.first
+    }
+
+    pub fn second(&self) -> &PackageSpec {
+        &self.second
+    }
+}
+
+impl FileConflicts {
+    pub(crate) fn new(conflicts: Vec<FileConflict>) -> Self {
+        Self(conflicts)
+    }
+
+    pub fn conflicts(&self) -> &[FileConflict] {
+        &self.0
+    }
+}
+
+impl Display for ConflictingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Binary(name) => write!(f, "executable `{name}`"),
+            Self::Module(name) => write!(f, "Lua module `{name}`"),
+        }
+    }
+}
+
+impl Display for FileConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is provided by both {} and {}",
+            self.file, self.first, self.second
+        )
+    }
+}
+
+impl Display for FileConflicts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "conflicting files:")?;
+        for conflict in &self.0 {
+            writeln!(f, "  {conflict}")?;
+        }
+        write!(
+            f,
+            "Set `file_conflicts = \"first-wins\"` in the config to keep the files of the installed packages."
+        )
+    }
+}
+
+impl Tree {
+    /// The Lua modules that an installed package provides.
+    pub(crate) fn package_modules(&self, package: &LocalPackage) -> Vec<String> {
+        // The `src` and `lib` directories don't depend on the rock layout.
+        let layout = self.dependency_layout(package);
+        lua_modules(&layout.src, "lua")
+            .into_iter()
+            .chain(lua_modules(&layout.lib, c_dylib_extension()))
+            .map(|(module, _)| module)
+            .collect_vec()
+    }
+}
+
+/// Find the files that the `new` packages provide, which are also provided
+/// by the `installed` packages or by another one of the `new` packages.
+/// Different versions of the same package never conflict.
+#[tracing::instrument(skip_all, fields(installed = installed.len(), new = new.len()))]
+pub(crate) fn find_conflicts(
+    to_file: fn(String) -> ConflictingFile,
+    installed: Vec<(PackageSpec, Vec<String>)>,
+    new: Vec<(PackageSpec, Vec<String>)>,
+) -> Vec<FileConflict> {
+    let mut providers = installed;
+    let mut conflicts = Vec::new();
+    for (package, files) in new
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| (a.name(), a.version()).cmp(&(b.name(), b.version())))
+    {
+        for file in &files {
+            if let Some((first, _)) = providers.iter().find(|(other, other_files)| {
+                other.name() != package.name() && other_files.contains(file)
+            }) {
+                conflicts.push(FileConflict {
+                    file: to_file(file.clone()),
+                    first: first.clone(),
+                    second: package.clone(),
+                });
+            }
+        }
+        providers.push((package, files));
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str) -> PackageSpec {
+        PackageSpec::parse(name.into(), version.into()).unwrap()
+    }
+
+    #[test]
+    fn conflicting_binaries() {
+        let installed = vec![(package("busted", "2.2.0-1"), vec!["busted".into()])];
+        let new = vec![
+            (package("busted", "2.3.0-1"), vec!["busted".into()]),
+            (package("busted-fork", "1.0.0-1"), vec!["busted".into()]),
+            (package("luacheck", "1.2.0-1"), vec!["luacheck".into()]),
+        ];
+        let conflicts = find_conflicts(ConflictingFile::Binary, installed, new);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "executable `busted` is provided by both busted 2.2.0-1 and busted-fork 1.0.0-1"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! A luarocks-compatible manifest of the rocks installed in a tree,
+//! for tools that probe luarocks trees for installed packages and their modules.
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use path_slash::PathExt;
+use walkdir::WalkDir;
+
+use crate::{
+    build::utils::c_dylib_extension,
+    lockfile::{LocalPackage, Lockfile, ReadOnly},
+    lua_rockspec::{DisplayLuaKV, DisplayLuaValue},
+};
+
+use super::{Tree, TreeError};
+
+impl Tree {
+    /// Write a luarocks-compatible manifest for the installed rocks
+    /// to `lib/luarocks/rocks-<lua version>/manifest`, where luarocks looks for it.
+    #[tracing::instrument(skip_all, fields(tree = %self.root().display()))]
+    pub fn write_luarocks_manifest(&self) -> Result<(), TreeError> {
+        let lockfile = self.lockfile()?;
+        let content = self.luarocks_manifest(&lockfile)?;
+        let manifest_path = self.luarocks_manifest_path();
+        std::fs::create_dir_all(manifest_path.parent().expect("manifest has no parent"))?;
+        std::fs::write(&manifest_path, &content)?;
+        Ok(())
+    }
+
+    pub(crate) fn luarocks_manifest_path(&self) -> PathBuf {
+        self.root()
+            .join("lib")
+            .join("luarocks")
+            .join(format!(
+                "rocks-{}",
+                self.version().version_compatibility_str()
+            ))
+            .join("manifest")
+    }
+
+    fn luarocks_manifest(&self, lockfile: &Lockfile<ReadOnly>) -> Result<String, TreeError> {
+        let packages = lockfile
+            .rocks()
+            .values()
+            .sorted_by_key(|package| (package.name().clone(), package.version().clone()))
+            .map(|package| {
+                let layout = self.installed_rock_layout(package)?;
+                let modules = lua_modules(&layout.src, "lua")
+                    .into_iter()
+                    .chain(lua_modules(&layout.lib, c_dylib_extension()))
+                    .collect_vec();
+                Ok::<_, TreeError>((package, modules))
+            })
+            .try_collect::<_, Vec<_>, _>()?;
+
+        let rock_id = |package: &LocalPackage| format!("{}/{}", package.name(), package.version());
+        let commands = packages
+            .iter()
+            .flat_map(|(package, _)| {
+                package
+                    .spec
+                    .binaries()
+                    .into_iter()
+                    .filter_map(|binary| binary.file_name())
+                    .map(|binary| (binary.to_string_lossy().to_string(), rock_id(package)))
+            })
+            .into_group_map();
+        let modules = packages
+            .iter()
+            .flat_map(|(package, modules)| {
+                modules
+                    .iter()
+                    .map(|(module, _)| (module.clone(), rock_id(package)))
+            })
+            .into_group_map();
+        let dependencies = packages.iter().map(|(package, _)| {
+            let dependencies = package
+                .dependencies()
+                .into_iter()
+                .filter_map(|id| lockfile.get(id))
+                .map(|dependency| {
+                    DisplayLuaValue::Table(vec![DisplayLuaKV {
+                        key: "name".into(),
+                        value: DisplayLuaValue::String(dependency.name().to_string()),
+                    }])
+                })
+                .collect_vec();
+            (package, DisplayLuaValue::List(dependencies))
+        });
+        let repository = packages.iter().map(|(package, modules)| {
+            let installed = DisplayLuaValue::Table(vec![
+                string_kv("arch", "installed"),
+                DisplayLuaKV {
+                    key: "commands".into(),
+                    value: DisplayLuaValue::Table(
+                        package
+                            .spec
+                            .binaries()
+                            .into_iter()
+                            .filter_map(|binary| binary.file_name())
+                            .map(|binary| {
+                                let binary = binary.to_string_lossy();
+                                string_kv(&binary, &binary)
+                            })
+                            .collect_vec(),
+                    ),
+                },
+                DisplayLuaKV {
+                    key: "dependencies".into(),
+                    value: DisplayLuaValue::Table(
+                        package
+                            .dependencies()
+                            .into_iter()
+                            .filter_map(|id| lockfile.get(id))
+                            .map(|dependency| {
+                                string_kv(
+                                    &dependency.name().to_string(),
+                                    &dependency.version().to_string(),
+                                )
+                            })
+                            .collect_vec(),
+                    ),
+                },
+                DisplayLuaKV {
+                    key: "modules".into(),
+                    value: DisplayLuaValue::Table(
+                        modules
+                            .iter()
+                            .map(|(module, path)| string_kv(module, &path.to_slash_lossy()))
+                            .collect_vec(),
+                    ),
+                },
+            ]);
+            (package, DisplayLuaValue::List(vec![installed]))
+        });
+
+        let manifest = [
+            DisplayLuaKV {
+                key: "commands".into(),
+                value: DisplayLuaValue::Table(
+                    commands
+                        .into_iter()
+                        .sorted()
+                        .map(|(command, rocks)| list_kv(&command, rocks))
+                        .collect_vec(),
+                ),
+            },
+            DisplayLuaKV {
+                key: "dependencies".into(),
+                value: by_name_and_version(dependencies),
+            },
+            DisplayLuaKV {
+                key: "modules".into(),
+                value: DisplayLuaValue::Table(
+                    modules
+                        .into_iter()
+                        .sorted()
+                        .map(|(module, rocks)| list_kv(&module, rocks))
+                        .collect_vec(),
+                ),
+            },
+            DisplayLuaKV {
+                key: "repository".into(),
+                value: by_name_and_version(repository),
+            },
+        ];
+        Ok(manifest.iter().map(|kv| format!("{kv}\n")).join(""))
+    }
+}
+
+/// The Lua modules with the given extension in a rock's `src` or `lib` directory,
+/// and their paths, relative to that directory.
+pub(super) fn lua_modules(dir: &Path, extension: &str) -> Vec<(String, PathBuf)> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry.path().strip_prefix(dir).ok()?.to_path_buf();
+            if path.extension()? != extension {
+                return None;
+            }
+            let module = path.with_extension("").to_slash_lossy().replace('/', ".");
+            let module = match module.strip_suffix(".init") {
+                Some(module) => module.to_string(),
+                None => module,
+            };
+            Some((module, path))
+        })
+        .sorted()
+        .collect_vec()
+}
+
+/// Group entries into a `{ [name] = { [version] = value } }` table.
+fn by_name_and_version<'a>(
+    entries: impl Iterator<Item = (&'a &'a LocalPackage, DisplayLuaValue)>,
+) -> DisplayLuaValue {
+    DisplayLuaValue::Table(
+        entries
+            .chunk_by(|(package, _)| package.name().clone())
+            .into_iter()
+            .map(|(name, versions)| DisplayLuaKV {
+                key: name.to_string(),
+                value: DisplayLuaValue::Table(
+                    versions
+                        .map(|(package, value)| DisplayLuaKV {
+                            key: package.version().to_string(),
+                            value,
+                        })
+                        .collect_vec(),
+                ),
+            })
+            .collect_vec(),
+    )
+}
+
+fn string_kv(key: &str, value: &str) -> DisplayLuaKV {
+    DisplayLuaKV {
+        key: key.to_string(),
+        value: DisplayLuaValue::String(value.to_string()),
+    }
+}
+
+fn list_kv(key: &str, values: Vec<String>) -> DisplayLuaKV {
+    DisplayLuaKV {
+        key: key.to_string(),
+        value: DisplayLuaValue::List(values.into_iter().map(DisplayLuaValue::String).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ConfigBuilder, LuaVersion},
+        lockfile::{LocalPackageHashes, LockConstraint},
+        package::PackageSpec,
+        remote_package_source::RemotePackageSource,
+        rockspec::RockBinaries,
+    };
+
+    #[test]
+    fn write_manifest() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(dir.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let hash: ssri::Integrity = "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+            .parse()
+            .unwrap();
+        let package = LocalPackage::from(
+            &PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap(),
+            LockConstraint::Unconstrained,
+            RockBinaries::default(),
+            RemotePackageSource::Test,
+            None,
+            LocalPackageHashes {
+                rockspec: hash.clone(),
+                source: hash,
+            },
+        );
+        let layout = tree.entrypoint(&package).unwrap();
+        std::fs::write(layout.src.join("foo.lua"), "return {}").unwrap();
+        tree.lockfile()
+            .unwrap()
+            .map_then_flush(|lockfile| {
+                lockfile.add_entrypoint(&package);
+                Ok::<_, std::io::Error>(())
+            })
+            .unwrap();
+
+        tree.write_luarocks_manifest().unwrap();
+
+        let manifest_path = tree.luarocks_manifest_path();
+        assert_eq!(
+            manifest_path,
+            tree.root().join("lib/luarocks/rocks-5.1/manifest")
+        );
+        let manifest = std::fs::read_to_string(manifest_path).unwrap();
+        assert!(manifest.contains("foo.lua"));
+        assert!(!tree.root().join("manifest").exists());
+        assert!(!tree.root().join("manifest-5.1").exists());
+    }
+
+    #[test]
+    fn find_lua_modules() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.join("foo")).unwrap();
+        std::fs::write(dir.join("foo").join("init.lua"), "").unwrap();
+        std::fs::write(dir.join("foo").join("bar.lua"), "").unwrap();
+        std::fs::write(dir.join("foo").join("README.md"), "").unwrap();
+        let modules = lua_modules(&dir, "lua")
+            .into_iter()
+            .map(|(module, _)| module)
+            .collect_vec();
+        assert_eq!(modules, vec!["foo", "foo.bar"]);
+    }
+}
@@ -2,16 +2,25 @@ use crate::{
     build::utils::format_path,
     config::{tree::RockLayoutConfig, Config, LuaVersion},
     lockfile::{LocalPackage, LocalPackageId, Lockfile, LockfileError, OptState, ReadOnly},
-    package::PackageReq,
+    package::{PackageReq, PackageVersion},
     variables::{GetVariableError, HasVariables},
 };
-use std::{io, path::PathBuf};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 
 use itertools::Itertools;
 use mlua::{ExternalResult, IntoLua};
 use thiserror::Error;
 
+pub(crate) mod checksums;
+mod conflicts;
 mod list;
+mod luarocks_manifest;
+
+pub(crate) use conflicts::find_conflicts;
+pub use conflicts::{ConflictingFile, FileConflict, FileConflicts};
 
 const LOCKFILE_NAME: &str = "lux.lock";
 
@@ -84,6 +93,55 @@ impl RockLayout {
     pub fn rockspec_path(&self) -> PathBuf {
         self.rock_path.join("package.rockspec")
     }
+
+    /// The checksums of the rock's installed files, recorded at install time.
+    pub fn checksums_path(&self) -> PathBuf {
+        self.rock_path.join("checksums.json")
+    }
+
+    /// The same layout, with the rock's directory moved to `staging_dir`.
+    /// Paths outside of the rock's directory, e.g. the tree's `bin` directory, are unchanged.
+    pub(crate) fn staged(&self, staging_dir: &Path) -> Self {
+        let stage = |path: &PathBuf| match path.strip_prefix(&self.rock_path) {
+            Ok(relative_path) => staging_dir.join(relative_path),
+            Err(_) => path.clone(),
+        };
+        Self {
+            rock_path: staging_dir.to_path_buf(),
+            etc: stage(&self.etc),
+            lib: stage(&self.lib),
+            src: stage(&self.src),
+            bin: stage(&self.bin),
+            conf: stage(&self.conf),
+            doc: stage(&self.doc),
+        }
+    }
+
+    /// Replace the rock's directory with the directory of the `staged` layout.
+    #[tracing::instrument(skip_all, fields(rock_path = %self.rock_path.display()))]
+    pub(crate) fn move_from_staging(&self, staged: &RockLayout) -> io::Result<()> {
+        if self.rock_path.exists() {
+            std::fs::remove_dir_all(&self.rock_path)?;
+        }
+        if let Some(parent) = self.rock_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&staged.rock_path, &self.rock_path)
+    }
+
+    /// A layout with all directories inside `rock_path`, for testing.
+    #[cfg(test)]
+    pub(crate) fn at(rock_path: &Path) -> Self {
+        Self {
+            rock_path: rock_path.to_path_buf(),
+            etc: rock_path.join("etc"),
+            lib: rock_path.join("lib"),
+            src: rock_path.join("src"),
+            bin: rock_path.join("bin"),
+            conf: rock_path.join("etc/conf"),
+            doc: rock_path.join("etc/doc"),
+        }
+    }
 }
 
 impl HasVariables for RockLayout {
@@ -126,6 +184,7 @@ impl Tree {
         Self::new_with_paths(root, test_tree_dir, build_tree_dir, version, config)
     }
 
+    #[tracing::instrument(skip_all, fields(root = %root.display(), version = %version))]
     fn new_with_paths(
         root: PathBuf,
         test_tree_dir: PathBuf,
@@ -145,6 +204,7 @@ impl Tree {
         std::fs::create_dir_all(path_with_version.join("bin"))?;
         let lockfile_path = root.join(LOCKFILE_NAME);
         let rock_layout_config = if lockfile_path.is_file() {
+            tracing::debug!(path = %lockfile_path.display(), "reading the entrypoint layout from the lockfile");
             let lockfile = Lockfile::load(lockfile_path, None)?;
             lockfile.entrypoint_layout
         } else {
@@ -177,18 +237,62 @@ impl Tree {
         ))
     }
 
+    /// The directories in the tree's root that are named like rock directories (see [`Tree::root_for`]),
+    /// including those of rocks that are not in the lockfile.
+    pub(crate) fn rock_dirs(&self) -> io::Result<Vec<PathBuf>> {
+        let root = self.root();
+        if !root.is_dir() {
+            return Ok(Vec::new());
+        }
+        Ok(std::fs::read_dir(root)?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.path().is_dir() && is_rock_dir_name(&entry.file_name().to_string_lossy())
+            })
+            .map(|entry| entry.path())
+            .sorted()
+            .collect_vec())
+    }
+
+    /// The directory to add to Neovim's `packpath`,
+    /// if entrypoints are installed into a Neovim `site/pack/<name>` layout.
+    pub fn nvim_packpath(&self) -> Option<PathBuf> {
+        let etc_root = self.root().join(self.entrypoint_layout.etc_root.as_ref()?);
+        etc_root
+            .parent()
+            .and_then(|pack_dir| pack_dir.parent())
+            .map(|site_dir| site_dir.to_path_buf())
+    }
+
     pub fn bin(&self) -> PathBuf {
         self.root().join("bin")
     }
 
+    /// The directories containing the `etc` directories of entrypoints,
+    /// if they are not installed in the rock directories.
+    pub(crate) fn entrypoint_etc_dirs(&self) -> Vec<PathBuf> {
+        match &self.entrypoint_layout.etc_root {
+            Some(etc_root) => {
+                let etc_root = self.root().join(etc_root);
+                vec![
+                    etc_root.join(&self.entrypoint_layout.etc),
+                    etc_root.join(&self.entrypoint_layout.opt_etc),
+                ]
+            }
+            None => Vec::new(),
+        }
+    }
+
     /// Directory containing unwrapped Lua scripts
     /// The wrapped scripts are in `Self::bin()`
     pub(crate) fn unwrapped_bin(&self) -> PathBuf {
         self.bin().join("unwrapped")
     }
 
+    #[tracing::instrument(skip_all, fields(req = %req))]
     pub fn match_rocks(&self, req: &PackageReq) -> Result<RockMatches, TreeError> {
         let mut found_packages = self.lockfile()?.find_rocks(req);
+        tracing::debug!(matches = found_packages.len(), "matched installed rocks");
         Ok(match found_packages.len() {
             0 => RockMatches::NotFound(req.clone()),
             1 => RockMatches::Single(found_packages.pop().unwrap()),
@@ -196,6 +300,7 @@ impl Tree {
         })
     }
 
+    #[tracing::instrument(skip_all, fields(req = %req))]
     pub fn match_rocks_and<F>(&self, req: &PackageReq, filter: F) -> Result<RockMatches, TreeError>
     where
         F: Fn(&LocalPackage) -> bool,
@@ -279,6 +384,7 @@ impl Tree {
     /// Create a `RockLayout` for an entrypoint package, creating the `lib` and `src` directories.
     pub fn entrypoint(&self, package: &LocalPackage) -> io::Result<RockLayout> {
         let rock_layout = self.entrypoint_layout(package);
+        tracing::debug!(package = %package.name(), version = %package.version(), rock_path = %rock_layout.rock_path.display(), "creating entrypoint layout");
         std::fs::create_dir_all(&rock_layout.lib)?;
         std::fs::create_dir_all(&rock_layout.src)?;
         Ok(rock_layout)
@@ -287,6 +393,7 @@ impl Tree {
     /// Create a `RockLayout` for a dependency package, creating the `lib` and `src` directories.
     pub fn dependency(&self, package: &LocalPackage) -> io::Result<RockLayout> {
         let rock_layout = self.dependency_layout(package);
+        tracing::debug!(package = %package.name(), version = %package.version(), rock_path = %rock_layout.rock_path.display(), "creating dependency layout");
         std::fs::create_dir_all(&rock_layout.lib)?;
         std::fs::create_dir_all(&rock_layout.src)?;
         Ok(rock_layout)
@@ -405,6 +512,17 @@ impl IntoLua for RockMatches {
     }
 }
 
+/// Whether `name` has the form `<id>-<name>@<version>`, where `<id>` is a [`LocalPackageId`].
+fn is_rock_dir_name(name: &str) -> bool {
+    name.split_once('-').is_some_and(|(id, rest)| {
+        id.len() == 64
+            && id.chars().all(|c| c.is_ascii_hexdigit())
+            && rest.rsplit_once('@').is_some_and(|(name, version)| {
+                !name.is_empty() && PackageVersion::parse(version).is_ok()
+            })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use assert_fs::prelude::PathCopy;
@@ -501,6 +619,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn staged_rock_layout() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let layout = RockLayout {
+            bin: temp.join("bin"),
+            rock_path: temp.join("foo"),
+            etc: temp.join("foo/etc"),
+            lib: temp.join("foo/lib"),
+            src: temp.join("foo/src"),
+            conf: temp.join("foo/etc/conf"),
+            doc: temp.join("foo/etc/doc"),
+        };
+        std::fs::create_dir_all(layout.src.join("old")).unwrap();
+
+        let staged = layout.staged(&temp.join("staging"));
+        assert_eq!(staged.src, temp.join("staging/src"));
+        assert_eq!(staged.conf, temp.join("staging/etc/conf"));
+        assert_eq!(staged.bin, layout.bin);
+
+        std::fs::create_dir_all(&staged.src).unwrap();
+        std::fs::write(staged.src.join("foo.lua"), "return {}").unwrap();
+        layout.move_from_staging(&staged).unwrap();
+        assert!(layout.src.join("foo.lua").is_file());
+        assert!(!layout.src.join("old").exists());
+        assert!(!staged.rock_path.exists());
+    }
+
     #[test]
     fn tree_list() {
         let tree_path =
@@ -596,4 +741,57 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn nvim_packpath() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::LuaJIT).unwrap();
+        assert_eq!(tree.nvim_packpath(), None);
+
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .nvim_profile()
+            .build()
+            .unwrap();
+        assert_eq!(config.lua_version(), Some(&LuaVersion::LuaJIT));
+        let tree = config.user_tree(LuaVersion::LuaJIT).unwrap();
+        assert_eq!(tree.nvim_packpath(), Some(tree.root().join("site")));
+    }
+
+    #[test]
+    fn rock_dirs() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let hash: ssri::Integrity = "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+            .parse()
+            .unwrap();
+        let package = LocalPackage::from(
+            &PackageSpec::parse("lua-cjson".into(), "2.1.0-1".into()).unwrap(),
+            LockConstraint::Unconstrained,
+            RockBinaries::default(),
+            RemotePackageSource::Test,
+            None,
+            LocalPackageHashes {
+                rockspec: hash.clone(),
+                source: hash,
+            },
+        );
+        let rock_dir = tree.root_for(&package);
+        std::fs::create_dir_all(&rock_dir).unwrap();
+        std::fs::create_dir_all(tree.root().join(".lux-staging.abc")).unwrap();
+        std::fs::create_dir_all(tree.root().join("user@host")).unwrap();
+        std::fs::create_dir_all(tree.root().join("abc-foo@1.0.0-1")).unwrap();
+        assert_eq!(tree.rock_dirs().unwrap(), vec![rock_dir]);
+    }
 }
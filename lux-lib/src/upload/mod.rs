@@ -1,20 +1,22 @@
 use std::env;
+use std::fs::File;
+use std::path::Path;
 
+use crate::operations::{PackSrc, PackSrcError};
 use crate::package::PackageVersion;
-use crate::project::project_toml::RemoteProjectTomlValidationError;
+use crate::project::project_toml::{RemoteProjectToml, RemoteProjectTomlValidationError};
 use crate::rockspec::Rockspec;
 use crate::TOOL_VERSION;
 use crate::{config::Config, project::Project};
 
+use reqwest::multipart::{Form, Part};
 use reqwest::StatusCode;
-use reqwest::{
-    multipart::{Form, Part},
-    Client,
-};
 use serde::Deserialize;
 use serde_enum_str::Serialize_enum_str;
+use tempdir::TempDir;
 use thiserror::Error;
 use url::Url;
+use zip::ZipArchive;
 
 #[cfg(not(target_env = "msvc"))]
 use gpgme::{Context, Data};
@@ -58,10 +60,73 @@ impl<'a> ProjectUpload<'a> {
     }
 
     /// Upload a package to a luarocks server.
+    /// If no API key is set, it is read from `$LUX_API_KEY`, falling back to the config,
+    /// and to the token stored for the server with `lx login`.
     pub async fn upload_to_luarocks(self) -> Result<(), UploadError> {
-        let api_key = self.api_key.unwrap_or(ApiKey::new()?);
+        let api_key = match self.api_key {
+            Some(api_key) => api_key,
+            None => ApiKey::new()
+                .or_else(|err| self.config.api_key().cloned().ok_or(err))
+                .or_else(|err| ApiKey::stored(self.config).ok_or(err))?,
+        };
         upload_from_project(&self.project, &api_key, self.sign_protocol, self.config).await
     }
+
+    /// Generate and pack everything that would be uploaded, without contacting the server.
+    pub async fn dry_run(self) -> Result<UploadPreview, UploadError> {
+        let rockspec = validate_rockspec(&self.project)?;
+        let rockspec_content = rockspec_content(&rockspec)?;
+        let temp_dir = TempDir::new("lux-upload")?;
+        let src_rock = PackSrc::new(temp_dir.path().to_path_buf(), &self.project)
+            .pack()
+            .await?;
+        let mut src_rock_files = ZipArchive::new(File::open(&src_rock)?)?
+            .file_names()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        src_rock_files.sort();
+        Ok(UploadPreview {
+            server: self.config.server().clone(),
+            rockspec_file_name: rockspec_file_name(&rockspec),
+            rockspec: rockspec_content,
+            src_rock_file_name: file_name(&src_rock),
+            src_rock_files,
+        })
+    }
+}
+
+/// A preview of what a [`ProjectUpload`] would upload.
+pub struct UploadPreview {
+    server: Url,
+    rockspec_file_name: String,
+    rockspec: String,
+    src_rock_file_name: String,
+    src_rock_files: Vec<String>,
+}
+
+impl UploadPreview {
+    /// The server the package would be uploaded to.
+    pub fn server(&self) -> &Url {
+        &self.server
+    }
+
+    pub fn rockspec_file_name(&self) -> &str {
+        &self.rockspec_file_name
+    }
+
+    /// The content of the generated rockspec.
+    pub fn rockspec(&self) -> &str {
+        &self.rockspec
+    }
+
+    pub fn src_rock_file_name(&self) -> &str {
+        &self.src_rock_file_name
+    }
+
+    /// The files contained in the packed source rock.
+    pub fn src_rock_files(&self) -> &[String] {
+        &self.src_rock_files
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -69,6 +134,16 @@ pub struct VersionCheckResponse {
     version: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct UploadResponse {
+    version: UploadedVersion,
+}
+
+#[derive(Deserialize, Debug)]
+struct UploadedVersion {
+    id: u64,
+}
+
 #[derive(Error, Debug)]
 pub enum ToolCheckError {
     #[error("error parsing tool check URL: {0}")]
@@ -116,6 +191,9 @@ pub enum UploadError {
     RockExists(Url),
     #[error("unable to read rockspec: {0}")]
     RockspecRead(#[from] std::io::Error),
+    PackSrc(#[from] PackSrcError),
+    #[error("unable to read packed source rock: {0}")]
+    SrcRockRead(#[from] zip::result::ZipError),
     #[cfg(not(target_env = "msvc"))]
     #[error("{0}.\nHINT: If you'd like to skip the signing step supply `--sign-protocol none` to the CLI")]
     Signature(#[from] gpgme::Error),
@@ -131,10 +209,17 @@ pub enum UploadError {
     Rockspec(String),
 }
 
+#[derive(Clone)]
 pub struct ApiKey(String);
 
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKey(<redacted>)")
+    }
+}
+
 #[derive(Error, Debug)]
-#[error("no API key provided! Please set the $LUX_API_KEY variable")]
+#[error("no API key provided! Please set the $LUX_API_KEY variable or the `api_key` config option, or run `lx login`")]
 pub struct ApiKeyUnspecified;
 
 impl ApiKey {
@@ -146,6 +231,13 @@ impl ApiKey {
         ))
     }
 
+    /// Retrieves the API key for the configured server, stored with `lx login`.
+    fn stored(config: &Config) -> Option<Self> {
+        let token = config.credentials().get(config.server()).ok().flatten()?;
+        // SAFETY: The token is sealed right after being read from the credentials store.
+        Some(unsafe { Self::from(token) })
+    }
+
     /// Creates an API key from a String.
     ///
     /// # Safety
@@ -225,93 +317,124 @@ impl From<SignatureProtocol> for gpgme::Protocol {
     }
 }
 
+fn validate_rockspec(project: &Project) -> Result<RemoteProjectToml, UploadError> {
+    let rockspec = project.toml().into_remote()?;
+    if let PackageVersion::StringVer(ver) = rockspec.version() {
+        return Err(UploadError::UnsupportedVersion(ver.to_string()));
+    }
+    Ok(rockspec)
+}
+
+fn rockspec_content(rockspec: &RemoteProjectToml) -> Result<String, UploadError> {
+    rockspec
+        .to_lua_remote_rockspec_string()
+        .map_err(|err| UploadError::Rockspec(err.to_string()))
+}
+
+fn rockspec_file_name(rockspec: &RemoteProjectToml) -> String {
+    format!("{}-{}.rockspec", rockspec.package(), rockspec.version())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .expect("packed rock has no file name")
+        .to_string_lossy()
+        .to_string()
+}
+
 async fn upload_from_project(
     project: &Project,
     api_key: &ApiKey,
-    #[cfg(target_env = "msvc")] _protocol: SignatureProtocol,
-    #[cfg(not(target_env = "msvc"))] protocol: SignatureProtocol,
+    protocol: SignatureProtocol,
     config: &Config,
 ) -> Result<(), UploadError> {
-    let client = Client::builder().https_only(true).build()?;
-
-    let rockspec = project.toml().into_remote()?;
+    let client = config
+        .http_client_builder(config.server())?
+        .https_only(true)
+        .build()?;
 
-    if let PackageVersion::StringVer(ver) = rockspec.version() {
-        return Err(UploadError::UnsupportedVersion(ver.to_string()));
-    }
+    let rockspec = validate_rockspec(project)?;
 
-    helpers::ensure_tool_version(&client, config.server()).await?;
-    helpers::ensure_user_exists(&client, api_key, config.server()).await?;
+    helpers::ensure_tool_version(&client, config).await?;
+    helpers::ensure_user_exists(&client, api_key, config).await?;
 
     if helpers::rock_exists(
         &client,
         api_key,
         rockspec.package(),
         rockspec.version(),
-        config.server(),
+        config,
     )
     .await?
     {
         return Err(UploadError::RockExists(config.server().clone()));
     }
 
-    let rockspec_content = rockspec
-        .to_lua_remote_rockspec_string()
-        .map_err(|err| UploadError::Rockspec(err.to_string()))?;
-
-    #[cfg(target_env = "msvc")]
-    let signed: Option<String> = None;
+    let rockspec_content = rockspec_content(&rockspec)?;
+    let rockspec_signature = helpers::sign(&protocol, rockspec_content.as_bytes())?;
 
-    #[cfg(not(target_env = "msvc"))]
-    let signed = if let SignatureProtocol::None = protocol {
-        None
-    } else {
-        let mut ctx = Context::from_protocol(protocol.into())?;
-        let mut signature = Data::new()?;
+    let temp_dir = TempDir::new("lux-upload")?;
+    let src_rock = PackSrc::new(temp_dir.path().to_path_buf(), project)
+        .pack()
+        .await?;
+    let src_rock_content = tokio::fs::read(&src_rock).await?;
+    let src_rock_signature = helpers::sign(&protocol, &src_rock_content)?;
 
-        ctx.set_armor(true);
-        ctx.sign_detached(rockspec_content.clone(), &mut signature)?;
+    let rockspec_part = Part::text(rockspec_content)
+        .file_name(rockspec_file_name(&rockspec))
+        .mime_str("application/octet-stream")?;
 
-        let mut signature_str = String::new();
-        signature.read_to_string(&mut signature_str)?;
+    let multipart = {
+        let multipart = Form::new().part("rockspec_file", rockspec_part);
 
-        Some(signature_str)
+        match rockspec_signature {
+            Some(signature) => {
+                let part = Part::text(signature).file_name("project.rockspec.sig");
+                multipart.part("rockspec_sig", part)
+            }
+            None => multipart,
+        }
     };
 
-    let rockspec = Part::text(rockspec_content)
-        .file_name(format!(
-            "{}-{}.rockspec",
-            rockspec.package(),
-            rockspec.version()
-        ))
+    let url = unsafe { helpers::url_for_method(config.server(), api_key, "upload")? };
+    let response = config
+        .authenticate(&url, client.post(url.clone()))
+        .multipart(multipart)
+        .send()
+        .await?;
+    helpers::ensure_success(config.server(), response.status())?;
+    let uploaded: UploadResponse = response.json().await?;
+
+    // The source rock is attached to the version that was created by uploading the rockspec.
+    let src_rock_part = Part::bytes(src_rock_content)
+        .file_name(file_name(&src_rock))
         .mime_str("application/octet-stream")?;
 
     let multipart = {
-        let multipart = Form::new().part("rockspec_file", rockspec);
+        let multipart = Form::new().part("rock_file", src_rock_part);
 
-        match signed {
+        match src_rock_signature {
             Some(signature) => {
-                let part = Part::text(signature).file_name("project.rockspec.sig");
-                multipart.part("rockspec_sig", part)
+                let part = Part::text(signature).file_name("project.src.rock.sig");
+                multipart.part("rock_sig", part)
             }
             None => multipart,
         }
     };
 
-    let response = client
-        .post(unsafe { helpers::url_for_method(config.server(), api_key, "upload")? })
+    let url = unsafe {
+        helpers::url_for_method(
+            config.server(),
+            api_key,
+            &format!("upload_rock/{}", uploaded.version.id),
+        )?
+    };
+    let response = config
+        .authenticate(&url, client.post(url.clone()))
         .multipart(multipart)
         .send()
         .await?;
-
-    let status = response.status();
-    if status.is_client_error() {
-        Err(UploadError::Client(config.server().clone(), status))
-    } else if status.is_server_error() {
-        Err(UploadError::Server(config.server().clone(), status))
-    } else {
-        Ok(())
-    }
+    helpers::ensure_success(config.server(), response.status())
 }
 
 mod helpers {
@@ -338,13 +461,54 @@ mod helpers {
             .join(endpoint)
     }
 
+    pub(crate) fn ensure_success(server_url: &Url, status: StatusCode) -> Result<(), UploadError> {
+        if status.is_client_error() {
+            Err(UploadError::Client(server_url.clone(), status))
+        } else if status.is_server_error() {
+            Err(UploadError::Server(server_url.clone(), status))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Create a detached, armored signature for the `content`,
+    /// or `None` if the protocol is [`SignatureProtocol::None`].
+    #[cfg(not(target_env = "msvc"))]
+    pub(crate) fn sign(
+        protocol: &SignatureProtocol,
+        content: &[u8],
+    ) -> Result<Option<String>, UploadError> {
+        if let SignatureProtocol::None = protocol {
+            return Ok(None);
+        }
+        let mut ctx = Context::from_protocol(protocol.clone().into())?;
+        let mut signature = Data::new()?;
+
+        ctx.set_armor(true);
+        ctx.sign_detached(content, &mut signature)?;
+
+        let mut signature_str = String::new();
+        signature.read_to_string(&mut signature_str)?;
+
+        Ok(Some(signature_str))
+    }
+
+    #[cfg(target_env = "msvc")]
+    pub(crate) fn sign(
+        _protocol: &SignatureProtocol,
+        _content: &[u8],
+    ) -> Result<Option<String>, UploadError> {
+        Ok(None)
+    }
+
     pub(crate) async fn ensure_tool_version(
         client: &Client,
-        server_url: &Url,
+        config: &Config,
     ) -> Result<(), ToolCheckError> {
+        let server_url = config.server();
         let url = server_url.join("api/tool_version")?;
-        let response: VersionCheckResponse = client
-            .post(url)
+        let response: VersionCheckResponse = config
+            .authenticate(&url, client.post(url.clone()))
             .json(&("current", TOOL_VERSION))
             .send()
             .await?
@@ -364,10 +528,12 @@ mod helpers {
     pub(crate) async fn ensure_user_exists(
         client: &Client,
         api_key: &ApiKey,
-        server_url: &Url,
+        config: &Config,
     ) -> Result<(), UserCheckError> {
-        let response = client
-            .get(unsafe { url_for_method(server_url, api_key, "status")? })
+        let server_url = config.server();
+        let url = unsafe { url_for_method(server_url, api_key, "status")? };
+        let response = config
+            .authenticate(&url, client.get(url.clone()))
             .send()
             .await?;
         let status = response.status();
@@ -385,10 +551,11 @@ mod helpers {
         api_key: &ApiKey,
         name: &PackageName,
         version: &PackageVersion,
-        server: &Url,
+        config: &Config,
     ) -> Result<bool, RockCheckError> {
-        Ok(client
-            .get(unsafe { url_for_method(server, api_key, "check_rockspec")? })
+        let url = unsafe { url_for_method(config.server(), api_key, "check_rockspec")? };
+        Ok(config
+            .authenticate(&url, client.get(url.clone()))
             .query(&(
                 ("package", name.to_string()),
                 ("version", version.to_string()),
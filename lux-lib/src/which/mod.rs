@@ -6,9 +6,10 @@ use thiserror::Error;
 
 use crate::{
     config::{Config, LuaVersion, LuaVersionUnset},
+    lockfile::LocalPackage,
     lua_rockspec::LuaModule,
     package::PackageReq,
-    tree::TreeError,
+    tree::{Tree, TreeError},
 };
 
 /// A rocks module finder.
@@ -21,6 +22,8 @@ pub struct Which<'a> {
     config: &'a Config,
     #[builder(field)]
     packages: Vec<PackageReq>,
+    /// The tree to search in. Defaults to the user tree.
+    tree: Option<Tree>,
 }
 
 impl<State> WhichBuilder<'_, State>
@@ -38,6 +41,14 @@ where
     }
 
     pub fn search(self) -> Result<PathBuf, WhichError>
+    where
+        State: which_builder::IsComplete,
+    {
+        Ok(do_search(self._build())?.path)
+    }
+
+    /// Like [`search`](Self::search), but also returns the package that provides the module.
+    pub fn locate(self) -> Result<Located, WhichError>
     where
         State: which_builder::IsComplete,
     {
@@ -45,6 +56,23 @@ where
     }
 }
 
+/// A file that is provided by an installed package.
+#[derive(Debug, Clone)]
+pub struct Located {
+    path: PathBuf,
+    package: LocalPackage,
+}
+
+impl Located {
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn package(&self) -> &LocalPackage {
+        &self.package
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum WhichError {
     #[error(transparent)]
@@ -55,12 +83,41 @@ pub enum WhichError {
     LuaVersionUnset(#[from] LuaVersionUnset),
     #[error("lua module {0} not found.")]
     ModuleNotFound(LuaModule),
+    #[error("binary {0} not found.")]
+    BinaryNotFound(String),
+}
+
+/// Find the installed package that provides the binary (script) `name` in the `tree`.
+pub fn which_binary(name: &str, tree: &Tree) -> Result<Located, WhichError> {
+    let lockfile = tree.lockfile()?;
+    lockfile
+        .list()
+        .into_values()
+        .flatten()
+        .find_map(|pkg| {
+            let binary = pkg
+                .spec
+                .binaries()
+                .into_iter()
+                .filter_map(|binary| binary.file_name())
+                .find(|file_name| {
+                    *file_name == name
+                        || PathBuf::from(file_name)
+                            .file_stem()
+                            .is_some_and(|stem| stem == name)
+                })?;
+            let path = tree.bin().join(binary);
+            path.is_file().then_some(Located { path, package: pkg })
+        })
+        .ok_or_else(|| WhichError::BinaryNotFound(name.to_string()))
 }
 
-fn do_search(which: Which<'_>) -> Result<PathBuf, WhichError> {
+fn do_search(which: Which<'_>) -> Result<Located, WhichError> {
     let config = which.config;
-    let lua_version = LuaVersion::from(config)?;
-    let tree = config.user_tree(lua_version.clone())?;
+    let tree = match which.tree {
+        Some(tree) => tree,
+        None => config.user_tree(LuaVersion::from(config)?.clone())?,
+    };
     let lockfile = tree.lockfile()?;
     let local_packages = if which.packages.is_empty() {
         lockfile
@@ -87,18 +144,12 @@ fn do_search(which: Which<'_>) -> Result<PathBuf, WhichError> {
         .filter_map(|pkg| {
             let rock_layout = tree.installed_rock_layout(&pkg).ok()?;
             let lib_path = rock_layout.lib.join(which.module.to_lib_path());
-            if lib_path.is_file() {
-                return Some(lib_path);
-            }
             let lua_path = rock_layout.src.join(which.module.to_lua_path());
-            if lua_path.is_file() {
-                return Some(lua_path);
-            }
-            let lua_path = rock_layout.src.join(which.module.to_lua_init_path());
-            if lua_path.is_file() {
-                return Some(lua_path);
-            }
-            None
+            let lua_init_path = rock_layout.src.join(which.module.to_lua_init_path());
+            [lib_path, lua_path, lua_init_path]
+                .into_iter()
+                .find(|path| path.is_file())
+                .map(|path| Located { path, package: pkg })
         })
         .next()
         .ok_or(WhichError::ModuleNotFound(which.module))
@@ -151,6 +202,14 @@ mod test {
                 .to_string_lossy(),
             "bat"
         );
+        let located = Which::new(LuaModule::from_str("bat.baz").unwrap(), &config)
+            .locate()
+            .unwrap();
+        assert_eq!(
+            located.path().file_name().unwrap().to_string_lossy(),
+            "baz.so"
+        );
+        assert_eq!(located.package().name().to_string(), "neorg");
         let result = Which::new(LuaModule::from_str("foo.bar").unwrap(), &config)
             .package("lua-cjson".parse().unwrap())
             .search();